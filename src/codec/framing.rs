@@ -11,9 +11,21 @@ impl MessageFramer {
 
     /// Reads a single length-prefixed payload from `stream`.
     pub fn read(stream: &mut impl Read) -> io::Result<Vec<u8>> {
+        Self::read_with_limit(stream, usize::MAX)
+    }
+
+    /// Reads a single length-prefixed payload from `stream`, rejecting payloads
+    /// longer than `max_bytes` before allocating for them.
+    pub fn read_with_limit(stream: &mut impl Read, max_bytes: usize) -> io::Result<Vec<u8>> {
         let mut len_buf = [0_u8; Self::LENGTH_PREFIX_SIZE];
         stream.read_exact(&mut len_buf)?;
         let length = u32::from_be_bytes(len_buf) as usize;
+        if length > max_bytes {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("payload of {length} bytes exceeds the limit of {max_bytes} bytes"),
+            ));
+        }
 
         let mut payload = vec![0_u8; length];
         stream.read_exact(&mut payload)?;
@@ -58,6 +70,15 @@ mod tests {
         assert_eq!(decoded, payload);
     }
 
+    #[test]
+    fn read_with_limit_rejects_oversized_payload() {
+        let framed = MessageFramer::frame(&[0; 16]).expect("frame should succeed");
+
+        let err = MessageFramer::read_with_limit(&mut Cursor::new(framed), 15)
+            .expect_err("payload is over the limit");
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    }
+
     #[test]
     fn write_produces_expected_framed_bytes() {
         let payload = vec![9, 8, 7];
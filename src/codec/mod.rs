@@ -5,25 +5,155 @@ pub mod request_decoder;
 pub use framing::MessageFramer;
 pub use request_decoder::RequestDecoder;
 
-use crate::protocol::{ApiVersionsRequest, ApiVersionsResponse, Request, RequestHeader};
+use crate::protocol::consumer_group_describe::CONSUMER_GROUP_DESCRIBE_KEY;
+use crate::protocol::consumer_group_heartbeat::CONSUMER_GROUP_HEARTBEAT_KEY;
+use crate::protocol::create_topics::CREATE_TOPICS_KEY;
+use crate::protocol::delete_groups::DELETE_GROUPS_KEY;
+use crate::protocol::delete_topics::DELETE_TOPICS_KEY;
+use crate::protocol::describe_cluster::DESCRIBE_CLUSTER_KEY;
+use crate::protocol::describe_groups::DESCRIBE_GROUPS_KEY;
+use crate::protocol::describe_topic_partitions::DESCRIBE_TOPIC_PARTITIONS_KEY;
+use crate::protocol::fetch::FETCH_KEY;
+use crate::protocol::find_coordinator::FIND_COORDINATOR_KEY;
+use crate::protocol::heartbeat::HEARTBEAT_KEY;
+use crate::protocol::init_producer_id::INIT_PRODUCER_ID_KEY;
+use crate::protocol::join_group::JOIN_GROUP_KEY;
+use crate::protocol::leave_group::LEAVE_GROUP_KEY;
+use crate::protocol::list_groups::LIST_GROUPS_KEY;
+use crate::protocol::list_offsets::LIST_OFFSETS_KEY;
+use crate::protocol::metadata::METADATA_KEY;
+use crate::protocol::offset_commit::OFFSET_COMMIT_KEY;
+use crate::protocol::offset_delete::OFFSET_DELETE_KEY;
+use crate::protocol::offset_fetch::OFFSET_FETCH_KEY;
+use crate::protocol::produce::PRODUCE_KEY;
+use crate::protocol::share_acknowledge::SHARE_ACKNOWLEDGE_KEY;
+use crate::protocol::share_fetch::SHARE_FETCH_KEY;
+use crate::protocol::share_group_heartbeat::SHARE_GROUP_HEARTBEAT_KEY;
+use crate::protocol::sync_group::SYNC_GROUP_KEY;
+use crate::protocol::{
+    ApiVersionsRequest, ConsumerGroupDescribeRequest, ConsumerGroupHeartbeatRequest,
+    CreateTopicsRequest, DeleteGroupsRequest, DeleteTopicsRequest, DescribeClusterRequest,
+    DescribeGroupsRequest, DescribeTopicPartitionsRequest, FetchRequest, FindCoordinatorRequest,
+    HeartbeatRequest, InitProducerIdRequest, JoinGroupRequest, LeaveGroupRequest,
+    ListGroupsRequest, ListOffsetsRequest, MetadataRequest, OffsetCommitRequest,
+    OffsetDeleteRequest, OffsetFetchRequest, ProduceRequest, Request, RequestHeader, Response,
+    ShareAcknowledgeRequest, ShareFetchRequest, ShareGroupHeartbeatRequest, SyncGroupRequest,
+};
 use std::io::{self, Cursor, Read, Write};
 
 pub struct KafkaCodec;
 
 impl KafkaCodec {
+    /// Reads and decodes one request.
+    ///
+    /// Requests for APIs or versions that are not served are decoded as
+    /// ApiVersions requests, whose handler answers them with
+    /// `UNSUPPORTED_VERSION`.
     pub fn read_request(stream: &mut impl Read) -> io::Result<Request> {
-        let payload = MessageFramer::read(stream)?;
+        Self::read_request_with_limit(stream, usize::MAX)
+    }
+
+    /// Like [`read_request`](Self::read_request), but fails on requests larger
+    /// than `max_bytes`.
+    pub fn read_request_with_limit(
+        stream: &mut impl Read,
+        max_bytes: usize,
+    ) -> io::Result<Request> {
+        let payload = MessageFramer::read_with_limit(stream, max_bytes)?;
         let mut cursor = Cursor::new(payload.as_slice());
         let header = RequestDecoder::read_header(&mut cursor)?;
-        Ok(Request::ApiVersions(Self::build_api_versions_request(
-            header,
-        )))
+        let version = header.request_api_version;
+        match header.request_api_key {
+            METADATA_KEY if MetadataRequest::supports(version) => Ok(Request::Metadata(
+                MetadataRequest::decode(header, &mut cursor)?,
+            )),
+            LIST_OFFSETS_KEY if ListOffsetsRequest::supports(version) => Ok(Request::ListOffsets(
+                ListOffsetsRequest::decode(header, &mut cursor)?,
+            )),
+            OFFSET_COMMIT_KEY if OffsetCommitRequest::supports(version) => Ok(
+                Request::OffsetCommit(OffsetCommitRequest::decode(header, &mut cursor)?),
+            ),
+            OFFSET_FETCH_KEY if OffsetFetchRequest::supports(version) => Ok(Request::OffsetFetch(
+                OffsetFetchRequest::decode(header, &mut cursor)?,
+            )),
+            FIND_COORDINATOR_KEY if FindCoordinatorRequest::supports(version) => Ok(
+                Request::FindCoordinator(FindCoordinatorRequest::decode(header, &mut cursor)?),
+            ),
+            JOIN_GROUP_KEY if JoinGroupRequest::supports(version) => Ok(Request::JoinGroup(
+                JoinGroupRequest::decode(header, &mut cursor)?,
+            )),
+            HEARTBEAT_KEY if HeartbeatRequest::supports(version) => Ok(Request::Heartbeat(
+                HeartbeatRequest::decode(header, &mut cursor)?,
+            )),
+            LEAVE_GROUP_KEY if LeaveGroupRequest::supports(version) => Ok(Request::LeaveGroup(
+                LeaveGroupRequest::decode(header, &mut cursor)?,
+            )),
+            SYNC_GROUP_KEY if SyncGroupRequest::supports(version) => Ok(Request::SyncGroup(
+                SyncGroupRequest::decode(header, &mut cursor)?,
+            )),
+            DESCRIBE_GROUPS_KEY if DescribeGroupsRequest::supports(version) => Ok(
+                Request::DescribeGroups(DescribeGroupsRequest::decode(header, &mut cursor)?),
+            ),
+            LIST_GROUPS_KEY if ListGroupsRequest::supports(version) => Ok(Request::ListGroups(
+                ListGroupsRequest::decode(header, &mut cursor)?,
+            )),
+            DELETE_GROUPS_KEY if DeleteGroupsRequest::supports(version) => Ok(
+                Request::DeleteGroups(DeleteGroupsRequest::decode(header, &mut cursor)?),
+            ),
+            OFFSET_DELETE_KEY if OffsetDeleteRequest::supports(version) => Ok(
+                Request::OffsetDelete(OffsetDeleteRequest::decode(header, &mut cursor)?),
+            ),
+            DESCRIBE_CLUSTER_KEY if DescribeClusterRequest::supports(version) => Ok(
+                Request::DescribeCluster(DescribeClusterRequest::decode(header, &mut cursor)?),
+            ),
+            FETCH_KEY if FetchRequest::supports(version) => {
+                Ok(Request::Fetch(FetchRequest::decode(header, &mut cursor)?))
+            }
+            DESCRIBE_TOPIC_PARTITIONS_KEY if DescribeTopicPartitionsRequest::supports(version) => {
+                Ok(Request::DescribeTopicPartitions(
+                    DescribeTopicPartitionsRequest::decode(header, &mut cursor)?,
+                ))
+            }
+            CREATE_TOPICS_KEY if CreateTopicsRequest::supports(version) => Ok(
+                Request::CreateTopics(CreateTopicsRequest::decode(header, &mut cursor)?),
+            ),
+            DELETE_TOPICS_KEY if DeleteTopicsRequest::supports(version) => Ok(
+                Request::DeleteTopics(DeleteTopicsRequest::decode(header, &mut cursor)?),
+            ),
+            CONSUMER_GROUP_HEARTBEAT_KEY if ConsumerGroupHeartbeatRequest::supports(version) => {
+                Ok(Request::ConsumerGroupHeartbeat(
+                    ConsumerGroupHeartbeatRequest::decode(header, &mut cursor)?,
+                ))
+            }
+            CONSUMER_GROUP_DESCRIBE_KEY if ConsumerGroupDescribeRequest::supports(version) => {
+                Ok(Request::ConsumerGroupDescribe(
+                    ConsumerGroupDescribeRequest::decode(header, &mut cursor)?,
+                ))
+            }
+            SHARE_GROUP_HEARTBEAT_KEY if ShareGroupHeartbeatRequest::supports(version) => {
+                Ok(Request::ShareGroupHeartbeat(
+                    ShareGroupHeartbeatRequest::decode(header, &mut cursor)?,
+                ))
+            }
+            SHARE_FETCH_KEY if ShareFetchRequest::supports(version) => Ok(Request::ShareFetch(
+                ShareFetchRequest::decode(header, &mut cursor)?,
+            )),
+            SHARE_ACKNOWLEDGE_KEY if ShareAcknowledgeRequest::supports(version) => Ok(
+                Request::ShareAcknowledge(ShareAcknowledgeRequest::decode(header, &mut cursor)?),
+            ),
+            INIT_PRODUCER_ID_KEY if InitProducerIdRequest::supports(version) => Ok(
+                Request::InitProducerId(InitProducerIdRequest::decode(header, &mut cursor)?),
+            ),
+            PRODUCE_KEY if ProduceRequest::supports(version) => Ok(Request::Produce(
+                ProduceRequest::decode(header, &mut cursor)?,
+            )),
+            _ => Ok(Request::ApiVersions(Self::build_api_versions_request(
+                header,
+            ))),
+        }
     }
 
-    pub fn write_response(
-        stream: &mut impl Write,
-        response: &ApiVersionsResponse,
-    ) -> io::Result<()> {
+    pub fn write_response(stream: &mut impl Write, response: &Response) -> io::Result<()> {
         stream.write_all(&response.to_bytes())
     }
 
@@ -41,7 +171,7 @@ impl KafkaCodec {
 mod tests {
     use super::{KafkaCodec, MessageFramer};
     use crate::protocol::{
-        api_versions::ApiVersionsResponseBody, ApiVersion, ApiVersionsResponse, Request,
+        api_versions::ApiVersionsResponseBody, ApiVersion, ApiVersionsResponse, Request, Response,
     };
     use std::convert::{TryFrom, TryInto};
     use std::io::{self, Cursor, Read, Write};
@@ -96,6 +226,29 @@ mod tests {
                 assert_eq!(actual.correlation_id, 7);
                 assert_eq!(actual.client_id.as_deref(), Some("client"));
             }
+            other => panic!("unexpected request {other:?}"),
+        }
+    }
+
+    #[test]
+    fn decodes_metadata_request() {
+        let mut payload = Vec::new();
+        payload.extend_from_slice(&3_i16.to_be_bytes());
+        payload.extend_from_slice(&1_i16.to_be_bytes());
+        payload.extend_from_slice(&8_i32.to_be_bytes());
+        payload.extend_from_slice(&(-1_i16).to_be_bytes());
+        payload.extend_from_slice(&(-1_i32).to_be_bytes());
+        let framed = MessageFramer::frame(&payload).expect("frame should succeed");
+        let mut stream = MockStream::with_bytes(framed);
+
+        let decoded = KafkaCodec::read_request(&mut stream).expect("request should decode");
+
+        match decoded {
+            Request::Metadata(actual) => {
+                assert_eq!(actual.header.correlation_id, 8);
+                assert_eq!(actual.topics, None);
+            }
+            other => panic!("unexpected request {other:?}"),
         }
     }
 
@@ -125,7 +278,8 @@ mod tests {
         let response = ApiVersionsResponse::new(99, body);
         let mut stream = MockStream::empty();
 
-        KafkaCodec::write_response(&mut stream, &response).expect("response should serialize");
+        KafkaCodec::write_response(&mut stream, &Response::ApiVersions(response))
+            .expect("response should serialize");
 
         assert!(stream.output.len() > 4);
         let declared_len = u32::from_be_bytes(stream.output[0..4].try_into().unwrap()) as usize;
@@ -35,6 +35,183 @@ pub fn read_nullable_string(cursor: &mut Cursor<&[u8]>) -> io::Result<Option<Str
         .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
 }
 
+/// Reads a big-endian `i64` from the provided cursor.
+pub fn read_i64(cursor: &mut Cursor<&[u8]>) -> io::Result<i64> {
+    use std::io::Read as _;
+    let mut buf = [0_u8; 8];
+    cursor.read_exact(&mut buf)?;
+    Ok(i64::from_be_bytes(buf))
+}
+
+/// Reads an unsigned LEB128 varint of at most 32 bits.
+pub fn read_unsigned_varint(cursor: &mut Cursor<&[u8]>) -> io::Result<u32> {
+    let value = read_unsigned_varlong(cursor)?;
+    u32::try_from(value).map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "varint overflow"))
+}
+
+/// Reads an unsigned LEB128 varint of at most 64 bits.
+pub fn read_unsigned_varlong(cursor: &mut Cursor<&[u8]>) -> io::Result<u64> {
+    use std::io::Read as _;
+    let mut value = 0_u64;
+    for shift in (0..64).step_by(7) {
+        let mut byte = [0_u8; 1];
+        cursor.read_exact(&mut byte)?;
+        value |= u64::from(byte[0] & 0x7F) << shift;
+        if byte[0] & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(io::Error::new(
+        io::ErrorKind::InvalidData,
+        "varint overflow",
+    ))
+}
+
+/// Reads a zig-zag encoded signed varint.
+pub fn read_varint(cursor: &mut Cursor<&[u8]>) -> io::Result<i32> {
+    let value = read_unsigned_varint(cursor)?;
+    Ok(((value >> 1) as i32) ^ -((value & 1) as i32))
+}
+
+/// Reads a zig-zag encoded signed varlong.
+pub fn read_varlong(cursor: &mut Cursor<&[u8]>) -> io::Result<i64> {
+    let value = read_unsigned_varlong(cursor)?;
+    Ok(((value >> 1) as i64) ^ -((value & 1) as i64))
+}
+
+/// Reads a single signed byte.
+pub fn read_i8(cursor: &mut Cursor<&[u8]>) -> io::Result<i8> {
+    use std::io::Read as _;
+    let mut buf = [0_u8; 1];
+    cursor.read_exact(&mut buf)?;
+    Ok(buf[0] as i8)
+}
+
+/// Reads a big-endian `u16` from the provided cursor.
+pub fn read_u16(cursor: &mut Cursor<&[u8]>) -> io::Result<u16> {
+    use std::io::Read as _;
+    let mut buf = [0_u8; 2];
+    cursor.read_exact(&mut buf)?;
+    Ok(u16::from_be_bytes(buf))
+}
+
+/// Reads a boolean encoded as a single byte, where any non-zero value is `true`.
+pub fn read_bool(cursor: &mut Cursor<&[u8]>) -> io::Result<bool> {
+    Ok(read_i8(cursor)? != 0)
+}
+
+/// Reads the 16 raw bytes of a UUID.
+pub fn read_uuid(cursor: &mut Cursor<&[u8]>) -> io::Result<[u8; 16]> {
+    use std::io::Read as _;
+    let mut buf = [0_u8; 16];
+    cursor.read_exact(&mut buf)?;
+    Ok(buf)
+}
+
+/// Reads a compact string, whose length is encoded as an unsigned varint plus one.
+///
+/// A null string is rejected with `io::ErrorKind::InvalidData`.
+pub fn read_compact_string(cursor: &mut Cursor<&[u8]>) -> io::Result<String> {
+    read_compact_nullable_string(cursor)?
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "unexpected null string"))
+}
+
+/// Reads a compact string where a length of zero represents `None`.
+pub fn read_compact_nullable_string(cursor: &mut Cursor<&[u8]>) -> io::Result<Option<String>> {
+    use std::io::Read as _;
+    let Some(length) = read_compact_array_len(cursor)? else {
+        return Ok(None);
+    };
+
+    let mut buffer = vec![0_u8; length];
+    cursor.read_exact(&mut buffer)?;
+    String::from_utf8(buffer)
+        .map(Some)
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+/// Reads the element count of a compact array, or `None` for a null array.
+pub fn read_compact_array_len(cursor: &mut Cursor<&[u8]>) -> io::Result<Option<usize>> {
+    let length = read_unsigned_varint(cursor)?;
+    Ok(length.checked_sub(1).map(|length| length as usize))
+}
+
+/// Reads a tagged field section, returning each field's tag and raw bytes.
+pub fn read_tagged_fields(cursor: &mut Cursor<&[u8]>) -> io::Result<Vec<(u32, Vec<u8>)>> {
+    use std::io::Read as _;
+    let count = read_unsigned_varint(cursor)?;
+    let mut fields = Vec::new();
+    for _ in 0..count {
+        let tag = read_unsigned_varint(cursor)?;
+        let size = read_unsigned_varint(cursor)? as usize;
+        let mut data = vec![0_u8; size];
+        cursor.read_exact(&mut data)?;
+        fields.push((tag, data));
+    }
+    Ok(fields)
+}
+
+/// Appends `value` as a single `0` or `1` byte.
+pub fn write_bool(buffer: &mut Vec<u8>, value: bool) {
+    buffer.push(u8::from(value));
+}
+
+/// Appends `value` as an unsigned LEB128 varint.
+pub fn write_unsigned_varint(buffer: &mut Vec<u8>, value: u32) {
+    write_unsigned_varlong(buffer, u64::from(value));
+}
+
+/// Appends `value` as an unsigned LEB128 varlong.
+pub fn write_unsigned_varlong(buffer: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        buffer.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    buffer.push(value as u8);
+}
+
+/// Appends `value` as a zig-zag encoded signed varint.
+pub fn write_varint(buffer: &mut Vec<u8>, value: i32) {
+    write_unsigned_varint(buffer, ((value << 1) ^ (value >> 31)) as u32);
+}
+
+/// Appends `value` as a zig-zag encoded signed varlong.
+pub fn write_varlong(buffer: &mut Vec<u8>, value: i64) {
+    write_unsigned_varlong(buffer, ((value << 1) ^ (value >> 63)) as u64);
+}
+
+/// Appends a compact string.
+pub fn write_compact_string(buffer: &mut Vec<u8>, value: &str) {
+    write_compact_nullable_string(buffer, Some(value));
+}
+
+/// Appends a compact string, writing a zero length for `None`.
+pub fn write_compact_nullable_string(buffer: &mut Vec<u8>, value: Option<&str>) {
+    match value {
+        Some(value) => {
+            write_compact_array_len(buffer, Some(value.len()));
+            buffer.extend_from_slice(value.as_bytes());
+        }
+        None => write_compact_array_len(buffer, None),
+    }
+}
+
+/// Appends the element count of a compact array, or zero for a null array.
+pub fn write_compact_array_len(buffer: &mut Vec<u8>, length: Option<usize>) {
+    write_unsigned_varint(buffer, length.map_or(0, |length| length as u32 + 1));
+}
+
+/// Appends a tagged field section holding the given fields, which must be
+/// sorted by tag.
+pub fn write_tagged_fields(buffer: &mut Vec<u8>, fields: &[(u32, Vec<u8>)]) {
+    write_unsigned_varint(buffer, fields.len() as u32);
+    for (tag, data) in fields {
+        write_unsigned_varint(buffer, *tag);
+        write_unsigned_varint(buffer, data.len() as u32);
+        buffer.extend_from_slice(data);
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -104,4 +281,59 @@ mod tests {
         let err = read_i32(&mut cursor).expect_err("read should fail");
         assert_eq!(err.kind(), ErrorKind::UnexpectedEof);
     }
+
+    #[test]
+    fn varints_round_trip() {
+        for value in [0, 1, -1, 63, -64, 300, i32::MAX, i32::MIN] {
+            let mut buffer = Vec::new();
+            write_varint(&mut buffer, value);
+            let mut cursor = Cursor::new(buffer.as_slice());
+            assert_eq!(
+                read_varint(&mut cursor).expect("read should succeed"),
+                value
+            );
+        }
+
+        for value in [0, -1, 1_700_000_000_000, i64::MAX, i64::MIN] {
+            let mut buffer = Vec::new();
+            write_varlong(&mut buffer, value);
+            let mut cursor = Cursor::new(buffer.as_slice());
+            assert_eq!(
+                read_varlong(&mut cursor).expect("read should succeed"),
+                value
+            );
+        }
+    }
+
+    #[test]
+    fn unsigned_varint_uses_leb128_layout() {
+        let mut buffer = Vec::new();
+        write_unsigned_varint(&mut buffer, 300);
+        assert_eq!(buffer, [0xAC, 0x02]);
+
+        let data = [0xFF, 0xFF, 0xFF, 0xFF, 0x7F];
+        let mut cursor = Cursor::new(&data[..]);
+        let err = read_unsigned_varint(&mut cursor).expect_err("read should fail");
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn compact_strings_round_trip() {
+        let mut buffer = Vec::new();
+        write_compact_string(&mut buffer, "kafka");
+        write_compact_nullable_string(&mut buffer, None);
+        write_tagged_fields(&mut buffer, &[(1, vec![7, 8])]);
+        assert_eq!(buffer[0], 6);
+
+        let mut cursor = Cursor::new(buffer.as_slice());
+        assert_eq!(read_compact_string(&mut cursor).expect("string"), "kafka");
+        assert_eq!(
+            read_compact_nullable_string(&mut cursor).expect("null"),
+            None
+        );
+        assert_eq!(
+            read_tagged_fields(&mut cursor).expect("tagged fields"),
+            vec![(1, vec![7, 8])]
+        );
+    }
 }
@@ -0,0 +1,7 @@
+pub mod codec;
+pub mod metadata;
+pub mod protocol;
+pub mod server;
+pub mod state;
+pub mod storage;
+pub mod time;
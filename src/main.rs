@@ -1,13 +1,63 @@
+use codecrafters_kafka::server::{self, BrokerConfig};
+use std::env;
+use std::io;
+use std::path::Path;
 use std::process;
 
-mod codec;
-mod protocol;
-mod server;
-mod state;
+const USAGE: &str = "usage:
+  kafka [start] [<server.properties>] [--override <key>=<value>]...
+  kafka format [--config <server.properties>] [--cluster-id <id>] [--ignore-formatted] [--override <key>=<value>]...";
 
 fn main() {
-    if let Err(err) = server::run() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let result = match args.first().map(String::as_str) {
+        Some("format") => format(&args[1..]),
+        Some("start") => start(&args[1..]),
+        _ => start(&args),
+    };
+    if let Err(err) = result {
         eprintln!("server error: {err}");
         process::exit(1);
     }
 }
+
+fn start(args: &[String]) -> io::Result<()> {
+    let mut config_path = None;
+    let mut overrides = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--override" => overrides.push(args.next().ok_or_else(usage_error)?.clone()),
+            path if config_path.is_none() && !path.starts_with('-') => config_path = Some(path),
+            _ => return Err(usage_error()),
+        }
+    }
+    server::run(load_config(config_path, &overrides)?)
+}
+
+fn format(args: &[String]) -> io::Result<()> {
+    let mut config_path = None;
+    let mut cluster_id = None;
+    let mut ignore_formatted = false;
+    let mut overrides = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--config" => config_path = Some(args.next().ok_or_else(usage_error)?.as_str()),
+            "--cluster-id" => cluster_id = Some(args.next().ok_or_else(usage_error)?.as_str()),
+            "--ignore-formatted" => ignore_formatted = true,
+            "--override" => overrides.push(args.next().ok_or_else(usage_error)?.clone()),
+            _ => return Err(usage_error()),
+        }
+    }
+    let config = load_config(config_path, &overrides)?;
+    server::format(&config, cluster_id, ignore_formatted)
+}
+
+fn load_config(path: Option<&str>, overrides: &[String]) -> io::Result<BrokerConfig> {
+    BrokerConfig::load(path.map(Path::new), env::vars(), overrides)
+}
+
+fn usage_error() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, USAGE)
+}
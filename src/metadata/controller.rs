@@ -0,0 +1,533 @@
+use super::loader::{metadata_dir, metadata_partition, METADATA_TOPIC};
+use super::records::{
+    ConfigRecord, PartitionRecord, ProducerIdsRecord, TopicRecord, TOPIC_RESOURCE_TYPE,
+};
+use super::snapshot::{self, SnapshotId};
+use super::{MetadataImage, MetadataRecord, TopicImage, Uuid};
+use crate::storage::batch::{self, BatchHeader, RecordBatchBuilder};
+use crate::storage::{LogManager, TopicPartition};
+use crate::time::Clock;
+use std::collections::BTreeMap;
+use std::io;
+use std::ops::Range;
+use std::sync::Arc;
+
+const MAX_TOPIC_NAME_LENGTH: usize = 249;
+
+/// Number of producer ids reserved by each [`MetadataController::allocate_producer_ids`].
+pub const PRODUCER_ID_BLOCK_SIZE: i64 = 1_000;
+
+/// Partition count requesting the controller's default, as in CreateTopics.
+pub const DEFAULT_PARTITIONS: i32 = -1;
+
+/// Applies topic changes made through this broker by writing them to the
+/// `__cluster_metadata` log and then to the in-memory image.
+///
+/// Records for one change are appended as a single batch and flushed before
+/// they are applied, so a change is either fully replayed after a restart or
+/// not at all.
+#[derive(Debug)]
+pub struct MetadataController {
+    image: MetadataImage,
+    node_id: i32,
+    default_partitions: i32,
+    clock: Arc<dyn Clock>,
+}
+
+impl MetadataController {
+    pub fn new(image: MetadataImage, node_id: i32, clock: Arc<dyn Clock>) -> Self {
+        Self {
+            image,
+            node_id,
+            default_partitions: 1,
+            clock,
+        }
+    }
+
+    /// Sets the partition count of topics created with [`DEFAULT_PARTITIONS`].
+    pub fn with_default_partitions(mut self, partitions: i32) -> Self {
+        self.default_partitions = partitions;
+        self
+    }
+
+    pub fn default_partitions(&self) -> i32 {
+        self.default_partitions
+    }
+
+    pub fn image(&self) -> &MetadataImage {
+        &self.image
+    }
+
+    /// Creates `name` with `partitions` partitions led by this node and the
+    /// given topic-level configs, returning the new topic id.
+    ///
+    /// A `partitions` of [`DEFAULT_PARTITIONS`] uses the configured default.
+    pub fn create_topic(
+        &mut self,
+        logs: &mut LogManager,
+        name: &str,
+        partitions: i32,
+        configs: &BTreeMap<String, String>,
+    ) -> io::Result<Uuid> {
+        validate_topic_name(name)?;
+        let partitions = match partitions {
+            DEFAULT_PARTITIONS => self.default_partitions,
+            partitions => partitions,
+        };
+        if self.image.topic(name).is_some() {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("topic '{name}' already exists"),
+            ));
+        }
+        if partitions <= 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("topic '{name}' needs at least one partition"),
+            ));
+        }
+        logs.default_config().with_overrides(configs)?;
+
+        let topic_id = Uuid::random()?;
+        let mut records = vec![MetadataRecord::Topic(TopicRecord {
+            name: name.to_string(),
+            topic_id,
+        })];
+        records.extend((0..partitions).map(|partition_id| {
+            MetadataRecord::Partition(PartitionRecord {
+                partition_id,
+                topic_id,
+                replicas: vec![self.node_id],
+                isr: vec![self.node_id],
+                removing_replicas: Vec::new(),
+                adding_replicas: Vec::new(),
+                leader: self.node_id,
+                leader_recovery_state: 0,
+                leader_epoch: 0,
+                partition_epoch: 0,
+                directories: Vec::new(),
+            })
+        }));
+        records.extend(
+            configs
+                .iter()
+                .map(|(key, value)| topic_config_record(name, key, Some(value.clone()))),
+        );
+        self.commit(logs, &records)?;
+
+        if !configs.is_empty() {
+            logs.set_topic_config(name, configs)?;
+        }
+        for partition in 0..partitions {
+            logs.get_or_create_log(&TopicPartition::new(name, partition))?;
+        }
+        Ok(topic_id)
+    }
+
+    /// Creates `name` as [`create_topic`](Self::create_topic) does unless the
+    /// image already has it, as brokers do for internal topics. Returns
+    /// whether the topic was created.
+    pub fn ensure_topic(
+        &mut self,
+        logs: &mut LogManager,
+        name: &str,
+        partitions: i32,
+        configs: &BTreeMap<String, String>,
+    ) -> io::Result<bool> {
+        if self.image.topic(name).is_some() {
+            return Ok(false);
+        }
+        self.create_topic(logs, name, partitions, configs)?;
+        Ok(true)
+    }
+
+    /// Deletes `name` together with its partition logs and configs.
+    pub fn delete_topic(&mut self, logs: &mut LogManager, name: &str) -> io::Result<()> {
+        let topic = self.existing_topic(name)?;
+        let topic_id = topic.id;
+        let partitions: Vec<i32> = topic.partitions.keys().copied().collect();
+
+        self.commit(logs, &[MetadataRecord::RemoveTopic { topic_id }])?;
+
+        for partition in partitions {
+            logs.delete_log(&TopicPartition::new(name, partition))?;
+        }
+        logs.remove_topic_config(name);
+        Ok(())
+    }
+
+    /// Sets or, for `None` values, removes topic-level configs of `name`.
+    ///
+    /// The resulting configs are validated before anything is written.
+    pub fn alter_topic_config(
+        &mut self,
+        logs: &mut LogManager,
+        name: &str,
+        changes: &BTreeMap<String, Option<String>>,
+    ) -> io::Result<()> {
+        self.existing_topic(name)?;
+        let mut configs = self.image.topic_config(name);
+        for (key, value) in changes {
+            match value {
+                Some(value) => configs.insert(key.clone(), value.clone()),
+                None => configs.remove(key),
+            };
+        }
+        logs.default_config().with_overrides(&configs)?;
+
+        let records: Vec<_> = changes
+            .iter()
+            .map(|(key, value)| topic_config_record(name, key, value.clone()))
+            .collect();
+        self.commit(logs, &records)?;
+
+        if configs.is_empty() {
+            logs.remove_topic_config(name);
+        } else {
+            logs.set_topic_config(name, &configs)?;
+        }
+        Ok(())
+    }
+
+    /// Reserves the next [`PRODUCER_ID_BLOCK_SIZE`] producer ids for this node
+    /// and returns them. The reservation is committed before it is returned, so
+    /// no id is handed out twice across restarts.
+    pub fn allocate_producer_ids(&mut self, logs: &mut LogManager) -> io::Result<Range<i64>> {
+        let first = self.image.next_producer_id();
+        let next_producer_id = first
+            .checked_add(PRODUCER_ID_BLOCK_SIZE)
+            .ok_or_else(|| io::Error::other("producer ids are exhausted"))?;
+        let broker_epoch = self
+            .image
+            .brokers()
+            .find(|broker| broker.broker_id == self.node_id)
+            .map_or(-1, |broker| broker.broker_epoch);
+        self.commit(
+            logs,
+            &[MetadataRecord::ProducerIds(ProducerIdsRecord {
+                broker_id: self.node_id,
+                broker_epoch,
+                next_producer_id,
+            })],
+        )?;
+        Ok(first..next_producer_id)
+    }
+
+    /// Writes a snapshot of the image and deletes older snapshots and the
+    /// metadata log segments it covers.
+    ///
+    /// Returns `None` when the latest snapshot is already up to date.
+    pub fn snapshot(&mut self, logs: &mut LogManager) -> io::Result<Option<SnapshotId>> {
+        let dir = metadata_dir(logs);
+        let tp = metadata_partition();
+        let offset = self.image.offset();
+        let Some(log) = logs.get_log_mut(&tp).filter(|_| offset >= 0) else {
+            return Ok(None);
+        };
+        if snapshot::latest_snapshot(&dir)?.is_some_and(|latest| latest.end_offset > offset) {
+            return Ok(None);
+        }
+
+        let epoch = match batch::split_batches(&log.read(offset, 1)?)?.first() {
+            Some(bytes) => BatchHeader::parse(bytes)?.partition_leader_epoch.max(0),
+            None => 0,
+        };
+        let id = snapshot::write_snapshot(&dir, &self.image, epoch, self.clock.now_ms())?;
+        snapshot::delete_snapshots_before(&dir, id)?;
+        let deleted = log.delete_segments_before(id.end_offset)?;
+        println!(
+            "wrote metadata snapshot {} and deleted {deleted} metadata log segments",
+            id.file_name()
+        );
+        Ok(Some(id))
+    }
+
+    fn existing_topic(&self, name: &str) -> io::Result<&TopicImage> {
+        self.image.topic(name).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("topic '{name}' does not exist"),
+            )
+        })
+    }
+
+    /// Appends `records` to the metadata log as one batch, flushes it and
+    /// applies the records to the image.
+    fn commit(&mut self, logs: &mut LogManager, records: &[MetadataRecord]) -> io::Result<()> {
+        let now_ms = self.clock.now_ms();
+        let batch = records
+            .iter()
+            .fold(RecordBatchBuilder::new(0), |builder, record| {
+                builder.record(now_ms, None, Some(&record.encode()))
+            })
+            .build();
+
+        let tp = metadata_partition();
+        let info = logs.append(&tp, &batch)?;
+        if let Some(log) = logs.get_log_mut(&tp) {
+            log.flush()?;
+        }
+        logs.checkpoint_recovery_points()?;
+
+        for (offset, record) in (info.first_offset..).zip(records) {
+            self.image.apply(offset, record);
+        }
+        Ok(())
+    }
+}
+
+fn topic_config_record(topic: &str, key: &str, value: Option<String>) -> MetadataRecord {
+    MetadataRecord::Config(ConfigRecord {
+        resource_type: TOPIC_RESOURCE_TYPE,
+        resource_name: topic.to_string(),
+        name: key.to_string(),
+        value,
+    })
+}
+
+/// Applies Kafka's topic naming rules.
+pub fn validate_topic_name(name: &str) -> io::Result<()> {
+    let legal_chars = name
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-'));
+    if name.is_empty()
+        || name == "."
+        || name == ".."
+        || name == METADATA_TOPIC
+        || name.len() > MAX_TOPIC_NAME_LENGTH
+        || !legal_chars
+    {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("invalid topic name '{name}'"),
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metadata::loader;
+    use crate::storage::config::{
+        CLEANUP_POLICY_CONFIG, RETENTION_MS_CONFIG, SEGMENT_BYTES_CONFIG,
+    };
+    use crate::storage::LogConfig;
+    use crate::time::SystemClock;
+    use std::path::Path;
+
+    fn open(dir: &Path) -> (LogManager, MetadataController) {
+        let mut logs =
+            LogManager::load(dir, LogConfig::default(), SystemClock::shared()).expect("load");
+        let image = loader::load(&mut logs).expect("metadata image");
+        (
+            logs,
+            MetadataController::new(image, 1, SystemClock::shared()),
+        )
+    }
+
+    fn configs(entries: &[(&str, &str)]) -> BTreeMap<String, String> {
+        entries
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn created_topics_survive_restart() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let topic_id = {
+            let (mut logs, mut controller) = open(dir.path());
+            let topic_id = controller
+                .create_topic(
+                    &mut logs,
+                    "orders",
+                    3,
+                    &configs(&[(CLEANUP_POLICY_CONFIG, "compact")]),
+                )
+                .expect("create");
+            assert!(logs.get_log(&TopicPartition::new("orders", 2)).is_some());
+            assert!(logs.config_for("orders").cleanup_policy.compact);
+            topic_id
+        };
+
+        let (mut logs, mut controller) = open(dir.path());
+
+        let orders = controller.image().topic("orders").expect("orders");
+        assert_eq!(orders.id, topic_id);
+        assert_eq!(orders.partitions.len(), 3);
+        assert_eq!(orders.partitions[&0].leader, 1);
+        assert!(logs.config_for("orders").cleanup_policy.compact);
+        let created = controller
+            .ensure_topic(&mut logs, "orders", 1, &BTreeMap::new())
+            .expect("ensure");
+        assert!(!created);
+    }
+
+    #[test]
+    fn create_topic_rejects_duplicates_and_bad_input() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let (mut logs, mut controller) = open(dir.path());
+        let no_configs = BTreeMap::new();
+        controller
+            .create_topic(&mut logs, "orders", 1, &no_configs)
+            .expect("create");
+
+        let err = controller
+            .create_topic(&mut logs, "orders", 1, &no_configs)
+            .expect_err("duplicate");
+        assert_eq!(err.kind(), io::ErrorKind::AlreadyExists);
+
+        for name in ["", "..", "has space", METADATA_TOPIC] {
+            let err = controller
+                .create_topic(&mut logs, name, 1, &no_configs)
+                .expect_err("invalid name");
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        }
+
+        let err = controller
+            .create_topic(
+                &mut logs,
+                "audit",
+                1,
+                &configs(&[(RETENTION_MS_CONFIG, "-5")]),
+            )
+            .expect_err("invalid config");
+        assert!(err.to_string().contains(RETENTION_MS_CONFIG));
+        assert!(controller.image().topic("audit").is_none());
+    }
+
+    #[test]
+    fn default_partition_count_applies_when_requested() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let (mut logs, controller) = open(dir.path());
+        let mut controller = controller.with_default_partitions(3);
+
+        let topic_id = controller
+            .create_topic(&mut logs, "orders", DEFAULT_PARTITIONS, &BTreeMap::new())
+            .expect("create");
+
+        let orders = controller.image().topic_by_id(&topic_id).expect("orders");
+        assert_eq!(orders.partitions.len(), 3);
+    }
+
+    #[test]
+    fn deleted_topics_stay_deleted_after_restart() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        {
+            let (mut logs, mut controller) = open(dir.path());
+            controller
+                .create_topic(&mut logs, "orders", 2, &BTreeMap::new())
+                .expect("create");
+            controller
+                .delete_topic(&mut logs, "orders")
+                .expect("delete");
+            assert!(!dir.path().join("orders-0").exists());
+
+            let err = controller
+                .delete_topic(&mut logs, "orders")
+                .expect_err("already deleted");
+            assert_eq!(err.kind(), io::ErrorKind::NotFound);
+        }
+
+        let (_logs, controller) = open(dir.path());
+        assert!(controller.image().topic("orders").is_none());
+    }
+
+    #[test]
+    fn producer_id_blocks_are_not_reused_after_restart() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        {
+            let (mut logs, mut controller) = open(dir.path());
+            let first = controller
+                .allocate_producer_ids(&mut logs)
+                .expect("allocate");
+            assert_eq!(first, 0..PRODUCER_ID_BLOCK_SIZE);
+        }
+
+        let (mut logs, mut controller) = open(dir.path());
+        let second = controller
+            .allocate_producer_ids(&mut logs)
+            .expect("allocate");
+        assert_eq!(second.start, PRODUCER_ID_BLOCK_SIZE);
+    }
+
+    #[test]
+    fn snapshot_replaces_covered_log_segments() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let no_configs = BTreeMap::new();
+        {
+            let (mut logs, mut controller) = open(dir.path());
+            logs.set_topic_config(
+                METADATA_TOPIC,
+                &configs(&[(RETENTION_MS_CONFIG, "-1"), (SEGMENT_BYTES_CONFIG, "200")]),
+            )
+            .expect("small metadata segments");
+            for name in ["orders", "payments", "audit"] {
+                controller
+                    .create_topic(&mut logs, name, 1, &no_configs)
+                    .expect("create");
+            }
+
+            let id = controller
+                .snapshot(&mut logs)
+                .expect("snapshot")
+                .expect("snapshot written");
+            assert_eq!(id.end_offset, controller.image().offset() + 1);
+            assert!(controller.snapshot(&mut logs).expect("snapshot").is_none());
+            let log = logs.get_log(&metadata_partition()).expect("metadata log");
+            assert!(log.log_start_offset() > 0);
+
+            controller
+                .delete_topic(&mut logs, "payments")
+                .expect("delete");
+        }
+
+        let (_logs, controller) = open(dir.path());
+        let names: Vec<_> = controller
+            .image()
+            .topics()
+            .map(|topic| topic.name.as_str())
+            .collect();
+        assert_eq!(names, ["audit", "orders"]);
+    }
+
+    #[test]
+    fn config_changes_are_persisted_and_applied() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        {
+            let (mut logs, mut controller) = open(dir.path());
+            controller
+                .create_topic(
+                    &mut logs,
+                    "orders",
+                    1,
+                    &configs(&[(RETENTION_MS_CONFIG, "1000")]),
+                )
+                .expect("create");
+
+            let changes = BTreeMap::from([
+                (RETENTION_MS_CONFIG.to_string(), None),
+                (
+                    CLEANUP_POLICY_CONFIG.to_string(),
+                    Some("compact,delete".to_string()),
+                ),
+            ]);
+            controller
+                .alter_topic_config(&mut logs, "orders", &changes)
+                .expect("alter");
+            assert_eq!(
+                logs.config_for("orders").retention_ms,
+                LogConfig::default().retention_ms
+            );
+        }
+
+        let (logs, controller) = open(dir.path());
+        assert_eq!(
+            controller.image().topic_config("orders"),
+            configs(&[(CLEANUP_POLICY_CONFIG, "compact,delete")])
+        );
+        let config = logs.config_for("orders");
+        assert!(config.cleanup_policy.compact && config.cleanup_policy.delete);
+    }
+}
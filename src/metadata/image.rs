@@ -0,0 +1,474 @@
+use super::records::{
+    ConfigRecord, FeatureLevelRecord, MetadataRecord, PartitionChangeRecord, PartitionRecord,
+    ProducerIdsRecord, RegisterBrokerRecord, TopicRecord, NO_LEADER_CHANGE, TOPIC_RESOURCE_TYPE,
+};
+use super::Uuid;
+use std::collections::BTreeMap;
+
+pub const METADATA_VERSION_FEATURE: &str = "metadata.version";
+
+/// In-memory view of the cluster built by applying metadata records in log order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MetadataImage {
+    offset: i64,
+    features: BTreeMap<String, i16>,
+    topics: BTreeMap<Uuid, TopicImage>,
+    topic_ids: BTreeMap<String, Uuid>,
+    configs: BTreeMap<ConfigResource, BTreeMap<String, String>>,
+    brokers: BTreeMap<i32, RegisterBrokerRecord>,
+    producer_ids: Option<ProducerIdsRecord>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TopicImage {
+    pub name: String,
+    pub id: Uuid,
+    pub partitions: BTreeMap<i32, PartitionRegistration>,
+}
+
+/// Current replica assignment and leadership of one partition.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PartitionRegistration {
+    pub replicas: Vec<i32>,
+    pub isr: Vec<i32>,
+    pub removing_replicas: Vec<i32>,
+    pub adding_replicas: Vec<i32>,
+    pub leader: i32,
+    pub leader_recovery_state: i8,
+    pub leader_epoch: i32,
+    pub partition_epoch: i32,
+    pub directories: Vec<Uuid>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ConfigResource {
+    pub resource_type: i8,
+    pub name: String,
+}
+
+impl Default for MetadataImage {
+    fn default() -> Self {
+        Self {
+            offset: -1,
+            features: BTreeMap::new(),
+            topics: BTreeMap::new(),
+            topic_ids: BTreeMap::new(),
+            configs: BTreeMap::new(),
+            brokers: BTreeMap::new(),
+            producer_ids: None,
+        }
+    }
+}
+
+impl MetadataImage {
+    /// Offset of the last record applied to the image, or `-1` for an empty image.
+    pub fn offset(&self) -> i64 {
+        self.offset
+    }
+
+    pub fn feature_level(&self, name: &str) -> Option<i16> {
+        self.features.get(name).copied()
+    }
+
+    pub fn features(&self) -> &BTreeMap<String, i16> {
+        &self.features
+    }
+
+    pub fn topic(&self, name: &str) -> Option<&TopicImage> {
+        self.topic_ids.get(name).and_then(|id| self.topics.get(id))
+    }
+
+    pub fn topic_by_id(&self, id: &Uuid) -> Option<&TopicImage> {
+        self.topics.get(id)
+    }
+
+    /// Every topic, ordered by name.
+    pub fn topics(&self) -> impl Iterator<Item = &TopicImage> {
+        self.topic_ids.values().filter_map(|id| self.topics.get(id))
+    }
+
+    /// Topic-level config overrides for `topic`; empty when it has none.
+    pub fn topic_config(&self, topic: &str) -> BTreeMap<String, String> {
+        self.configs
+            .get(&ConfigResource {
+                resource_type: TOPIC_RESOURCE_TYPE,
+                name: topic.to_string(),
+            })
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Registered brokers, with `fenced` kept up to date by fence records.
+    pub fn brokers(&self) -> impl Iterator<Item = &RegisterBrokerRecord> {
+        self.brokers.values()
+    }
+
+    /// First producer id not yet handed out to any broker.
+    pub fn next_producer_id(&self) -> i64 {
+        self.producer_ids
+            .as_ref()
+            .map_or(0, |record| record.next_producer_id)
+    }
+
+    /// Marks the image as reflecting the metadata log up to and including `offset`.
+    pub fn set_offset(&mut self, offset: i64) {
+        self.offset = offset;
+    }
+
+    /// Returns records that rebuild this image when applied to an empty one,
+    /// as written to metadata snapshots.
+    pub fn records(&self) -> Vec<MetadataRecord> {
+        let mut features: Vec<_> = self.features.iter().collect();
+        // metadata.version must come first; it determines how later records are read.
+        features.sort_by_key(|(name, _)| name.as_str() != METADATA_VERSION_FEATURE);
+        let mut records: Vec<_> = features
+            .into_iter()
+            .map(|(name, level)| {
+                MetadataRecord::FeatureLevel(FeatureLevelRecord {
+                    name: name.clone(),
+                    feature_level: *level,
+                })
+            })
+            .collect();
+
+        records.extend(
+            self.brokers
+                .values()
+                .map(|broker| MetadataRecord::RegisterBroker(broker.clone())),
+        );
+
+        for topic in self.topics() {
+            records.push(MetadataRecord::Topic(TopicRecord {
+                name: topic.name.clone(),
+                topic_id: topic.id,
+            }));
+            for (partition_id, partition) in &topic.partitions {
+                records.push(MetadataRecord::Partition(PartitionRecord {
+                    partition_id: *partition_id,
+                    topic_id: topic.id,
+                    replicas: partition.replicas.clone(),
+                    isr: partition.isr.clone(),
+                    removing_replicas: partition.removing_replicas.clone(),
+                    adding_replicas: partition.adding_replicas.clone(),
+                    leader: partition.leader,
+                    leader_recovery_state: partition.leader_recovery_state,
+                    leader_epoch: partition.leader_epoch,
+                    partition_epoch: partition.partition_epoch,
+                    directories: partition.directories.clone(),
+                }));
+            }
+        }
+
+        records.extend(self.producer_ids.clone().map(MetadataRecord::ProducerIds));
+
+        for (resource, configs) in &self.configs {
+            for (name, value) in configs {
+                records.push(MetadataRecord::Config(ConfigRecord {
+                    resource_type: resource.resource_type,
+                    resource_name: resource.name.clone(),
+                    name: name.clone(),
+                    value: Some(value.clone()),
+                }));
+            }
+        }
+        records
+    }
+
+    /// Applies `record`, read from the metadata log at `offset`.
+    pub fn apply(&mut self, offset: i64, record: &MetadataRecord) {
+        self.offset = offset;
+        match record {
+            MetadataRecord::RegisterBroker(record) => self.register_broker(record),
+            MetadataRecord::UnregisterBroker { broker_id, .. } => {
+                self.brokers.remove(broker_id);
+            }
+            MetadataRecord::Topic(record) => {
+                self.topic_ids.insert(record.name.clone(), record.topic_id);
+                self.topics.insert(
+                    record.topic_id,
+                    TopicImage {
+                        name: record.name.clone(),
+                        id: record.topic_id,
+                        partitions: BTreeMap::new(),
+                    },
+                );
+            }
+            MetadataRecord::Partition(record) => self.add_partition(record),
+            MetadataRecord::Config(record) => self.apply_config(record),
+            MetadataRecord::PartitionChange(record) => self.change_partition(record),
+            MetadataRecord::FenceBroker { broker_id, .. } => self.set_fenced(*broker_id, true),
+            MetadataRecord::UnfenceBroker { broker_id, .. } => self.set_fenced(*broker_id, false),
+            MetadataRecord::RemoveTopic { topic_id } => {
+                if let Some(topic) = self.topics.remove(topic_id) {
+                    self.topic_ids.remove(&topic.name);
+                    self.configs.remove(&ConfigResource {
+                        resource_type: TOPIC_RESOURCE_TYPE,
+                        name: topic.name,
+                    });
+                }
+            }
+            MetadataRecord::FeatureLevel(record) => {
+                self.features
+                    .insert(record.name.clone(), record.feature_level);
+            }
+            MetadataRecord::ProducerIds(record) => self.producer_ids = Some(record.clone()),
+            MetadataRecord::Unsupported { .. } => {}
+        }
+    }
+
+    fn register_broker(&mut self, record: &RegisterBrokerRecord) {
+        self.brokers.insert(record.broker_id, record.clone());
+    }
+
+    fn set_fenced(&mut self, broker_id: i32, fenced: bool) {
+        if let Some(broker) = self.brokers.get_mut(&broker_id) {
+            broker.fenced = fenced;
+        }
+    }
+
+    fn add_partition(&mut self, record: &PartitionRecord) {
+        let Some(topic) = self.topics.get_mut(&record.topic_id) else {
+            eprintln!(
+                "ignoring partition {} of unknown topic {}",
+                record.partition_id, record.topic_id
+            );
+            return;
+        };
+        topic.partitions.insert(
+            record.partition_id,
+            PartitionRegistration {
+                replicas: record.replicas.clone(),
+                isr: record.isr.clone(),
+                removing_replicas: record.removing_replicas.clone(),
+                adding_replicas: record.adding_replicas.clone(),
+                leader: record.leader,
+                leader_recovery_state: record.leader_recovery_state,
+                leader_epoch: record.leader_epoch,
+                partition_epoch: record.partition_epoch,
+                directories: record.directories.clone(),
+            },
+        );
+    }
+
+    fn change_partition(&mut self, record: &PartitionChangeRecord) {
+        let Some(partition) = self
+            .topics
+            .get_mut(&record.topic_id)
+            .and_then(|topic| topic.partitions.get_mut(&record.partition_id))
+        else {
+            eprintln!(
+                "ignoring change to unknown partition {} of topic {}",
+                record.partition_id, record.topic_id
+            );
+            return;
+        };
+
+        if let Some(isr) = &record.isr {
+            partition.isr = isr.clone();
+        }
+        if record.leader != NO_LEADER_CHANGE {
+            partition.leader = record.leader;
+            partition.leader_epoch += 1;
+        }
+        if let Some(replicas) = &record.replicas {
+            partition.replicas = replicas.clone();
+        }
+        if let Some(removing) = &record.removing_replicas {
+            partition.removing_replicas = removing.clone();
+        }
+        if let Some(adding) = &record.adding_replicas {
+            partition.adding_replicas = adding.clone();
+        }
+        if record.leader_recovery_state != -1 {
+            partition.leader_recovery_state = record.leader_recovery_state;
+        }
+        partition.partition_epoch += 1;
+    }
+
+    fn apply_config(&mut self, record: &ConfigRecord) {
+        let resource = ConfigResource {
+            resource_type: record.resource_type,
+            name: record.resource_name.clone(),
+        };
+        match &record.value {
+            Some(value) => {
+                self.configs
+                    .entry(resource)
+                    .or_default()
+                    .insert(record.name.clone(), value.clone());
+            }
+            None => {
+                if let Some(configs) = self.configs.get_mut(&resource) {
+                    configs.remove(&record.name);
+                    if configs.is_empty() {
+                        self.configs.remove(&resource);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn topic(name: &str, id: u8) -> MetadataRecord {
+        MetadataRecord::Topic(TopicRecord {
+            name: name.to_string(),
+            topic_id: Uuid([id; 16]),
+        })
+    }
+
+    fn partition(id: u8, partition_id: i32) -> MetadataRecord {
+        MetadataRecord::Partition(PartitionRecord {
+            partition_id,
+            topic_id: Uuid([id; 16]),
+            replicas: vec![1],
+            isr: vec![1],
+            removing_replicas: Vec::new(),
+            adding_replicas: Vec::new(),
+            leader: 1,
+            leader_recovery_state: 0,
+            leader_epoch: 0,
+            partition_epoch: 0,
+            directories: Vec::new(),
+        })
+    }
+
+    fn config(topic: &str, name: &str, value: Option<&str>) -> MetadataRecord {
+        MetadataRecord::Config(ConfigRecord {
+            resource_type: TOPIC_RESOURCE_TYPE,
+            resource_name: topic.to_string(),
+            name: name.to_string(),
+            value: value.map(str::to_string),
+        })
+    }
+
+    #[test]
+    fn topics_and_partitions_are_indexed_by_name_and_id() {
+        let mut image = MetadataImage::default();
+        let records = [
+            MetadataRecord::FeatureLevel(FeatureLevelRecord {
+                name: "metadata.version".to_string(),
+                feature_level: 20,
+            }),
+            topic("orders", 1),
+            partition(1, 0),
+            partition(1, 1),
+            topic("audit", 2),
+            partition(2, 0),
+        ];
+        for (offset, record) in records.iter().enumerate() {
+            image.apply(offset as i64, record);
+        }
+
+        assert_eq!(image.offset(), 5);
+        assert_eq!(image.feature_level("metadata.version"), Some(20));
+        let names: Vec<_> = image.topics().map(|topic| topic.name.as_str()).collect();
+        assert_eq!(names, ["audit", "orders"]);
+        let orders = image.topic("orders").expect("orders");
+        assert_eq!(orders.partitions.len(), 2);
+        assert_eq!(
+            image.topic_by_id(&Uuid([2; 16])).expect("audit").name,
+            "audit"
+        );
+    }
+
+    #[test]
+    fn partition_change_updates_leader_and_epochs() {
+        let mut image = MetadataImage::default();
+        image.apply(0, &topic("orders", 1));
+        image.apply(1, &partition(1, 0));
+
+        image.apply(
+            2,
+            &MetadataRecord::PartitionChange(PartitionChangeRecord {
+                partition_id: 0,
+                topic_id: Uuid([1; 16]),
+                isr: Some(vec![1, 2]),
+                leader: 2,
+                replicas: Some(vec![1, 2]),
+                removing_replicas: None,
+                adding_replicas: None,
+                leader_recovery_state: -1,
+            }),
+        );
+
+        let partition = &image.topic("orders").expect("orders").partitions[&0];
+        assert_eq!(partition.leader, 2);
+        assert_eq!(partition.leader_epoch, 1);
+        assert_eq!(partition.partition_epoch, 1);
+        assert_eq!(partition.isr, [1, 2]);
+    }
+
+    #[test]
+    fn remove_topic_drops_partitions_and_configs() {
+        let mut image = MetadataImage::default();
+        image.apply(0, &topic("orders", 1));
+        image.apply(1, &partition(1, 0));
+        image.apply(2, &config("orders", "cleanup.policy", Some("compact")));
+        image.apply(3, &config("orders", "retention.ms", Some("1000")));
+        image.apply(4, &config("orders", "retention.ms", None));
+        assert_eq!(
+            image.topic_config("orders"),
+            BTreeMap::from([("cleanup.policy".to_string(), "compact".to_string())])
+        );
+
+        image.apply(
+            5,
+            &MetadataRecord::RemoveTopic {
+                topic_id: Uuid([1; 16]),
+            },
+        );
+
+        assert!(image.topic("orders").is_none());
+        assert!(image.topic_config("orders").is_empty());
+    }
+
+    #[test]
+    fn records_rebuild_an_equal_image() {
+        let mut image = MetadataImage::default();
+        image.apply(
+            0,
+            &MetadataRecord::FeatureLevel(FeatureLevelRecord {
+                name: "kraft.version".to_string(),
+                feature_level: 1,
+            }),
+        );
+        image.apply(
+            1,
+            &MetadataRecord::FeatureLevel(FeatureLevelRecord {
+                name: METADATA_VERSION_FEATURE.to_string(),
+                feature_level: 20,
+            }),
+        );
+        image.apply(2, &topic("orders", 1));
+        image.apply(3, &partition(1, 0));
+        image.apply(4, &config("orders", "cleanup.policy", Some("compact")));
+        image.apply(
+            5,
+            &MetadataRecord::ProducerIds(ProducerIdsRecord {
+                broker_id: 1,
+                broker_epoch: -1,
+                next_producer_id: 1_000,
+            }),
+        );
+
+        let records = image.records();
+        assert_eq!(
+            records[0],
+            MetadataRecord::FeatureLevel(FeatureLevelRecord {
+                name: METADATA_VERSION_FEATURE.to_string(),
+                feature_level: 20,
+            })
+        );
+
+        let mut rebuilt = MetadataImage::default();
+        for record in &records {
+            rebuilt.apply(5, record);
+        }
+        assert_eq!(rebuilt, image);
+        assert_eq!(rebuilt.next_producer_id(), 1_000);
+    }
+}
@@ -0,0 +1,195 @@
+use super::snapshot;
+use super::{MetadataImage, MetadataRecord};
+use crate::storage::batch::{self, BatchHeader};
+use crate::storage::config::RETENTION_MS_CONFIG;
+use crate::storage::{Log, LogManager, TopicPartition};
+use std::collections::BTreeMap;
+use std::io;
+use std::path::PathBuf;
+
+pub const METADATA_TOPIC: &str = "__cluster_metadata";
+
+/// Maximum number of bytes read from the metadata log at a time while replaying.
+const REPLAY_READ_BYTES: usize = 1024 * 1024;
+
+/// The single partition of the metadata topic.
+pub fn metadata_partition() -> TopicPartition {
+    TopicPartition::new(METADATA_TOPIC, 0)
+}
+
+/// Directory holding the metadata log and its snapshots.
+pub fn metadata_dir(logs: &LogManager) -> PathBuf {
+    logs.log_dir().join(metadata_partition().dir_name())
+}
+
+/// Builds the metadata image from the latest snapshot, if any, and the tail of
+/// the `__cluster_metadata` log held by `logs`.
+///
+/// The metadata log is exempt from time-based retention, and the topic-level
+/// configs recorded in it are applied to the matching partition logs.
+pub fn load(logs: &mut LogManager) -> io::Result<MetadataImage> {
+    logs.set_topic_config(
+        METADATA_TOPIC,
+        &BTreeMap::from([(RETENTION_MS_CONFIG.to_string(), "-1".to_string())]),
+    )?;
+
+    let dir = metadata_dir(logs);
+    let mut image = match snapshot::latest_snapshot(&dir)? {
+        Some(id) => {
+            let image = snapshot::read_snapshot(&dir, id)?;
+            println!("loaded metadata snapshot {}", id.file_name());
+            image
+        }
+        None => MetadataImage::default(),
+    };
+    if let Some(log) = logs.get_log(&metadata_partition()) {
+        let from_offset = image.offset() + 1;
+        let applied = replay(&mut image, log, from_offset)?;
+        println!(
+            "replayed {applied} metadata records up to offset {}",
+            image.offset()
+        );
+    }
+
+    for topic in image.topics() {
+        let overrides = image.topic_config(&topic.name);
+        if !overrides.is_empty() {
+            logs.set_topic_config(&topic.name, &overrides)?;
+        }
+    }
+    Ok(image)
+}
+
+/// Applies every metadata record in `log` at or after `from_offset` to `image`,
+/// returning the number of records applied.
+///
+/// Control batches, such as leader change markers, carry no metadata and are
+/// skipped.
+pub fn replay(image: &mut MetadataImage, log: &Log, from_offset: i64) -> io::Result<usize> {
+    let mut applied = 0;
+    let mut offset = from_offset.max(log.log_start_offset());
+    while offset < log.log_end_offset() {
+        let bytes = log.read(offset, REPLAY_READ_BYTES)?;
+        let batches = batch::split_batches(&bytes)?;
+        let Some(last) = batches.last() else {
+            break;
+        };
+        let next_offset = BatchHeader::parse(last)?.last_offset() + 1;
+
+        for bytes in batches {
+            if BatchHeader::parse(bytes)?.is_control() {
+                continue;
+            }
+            for record in batch::read_records(bytes)? {
+                let Some(value) = record.value.filter(|_| record.offset >= offset) else {
+                    continue;
+                };
+                image.apply(record.offset, &MetadataRecord::decode(&value)?);
+                applied += 1;
+            }
+        }
+        offset = next_offset;
+    }
+    Ok(applied)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metadata::records::{
+        ConfigRecord, FeatureLevelRecord, PartitionRecord, TopicRecord, TOPIC_RESOURCE_TYPE,
+    };
+    use crate::metadata::Uuid;
+    use crate::storage::batch::RecordBatchBuilder;
+    use crate::storage::LogConfig;
+    use crate::time::SystemClock;
+
+    fn write_batch(logs: &mut LogManager, records: &[MetadataRecord]) {
+        let batch = records
+            .iter()
+            .fold(RecordBatchBuilder::new(0), |builder, record| {
+                builder.record(0, None, Some(&record.encode()))
+            })
+            .build();
+        logs.append(&metadata_partition(), &batch)
+            .expect("append metadata");
+    }
+
+    fn topic_records(name: &str, id: u8, partitions: i32) -> Vec<MetadataRecord> {
+        let topic_id = Uuid([id; 16]);
+        let mut records = vec![MetadataRecord::Topic(TopicRecord {
+            name: name.to_string(),
+            topic_id,
+        })];
+        for partition_id in 0..partitions {
+            records.push(MetadataRecord::Partition(PartitionRecord {
+                partition_id,
+                topic_id,
+                replicas: vec![1],
+                isr: vec![1],
+                removing_replicas: Vec::new(),
+                adding_replicas: Vec::new(),
+                leader: 1,
+                leader_recovery_state: 0,
+                leader_epoch: 0,
+                partition_epoch: 0,
+                directories: Vec::new(),
+            }));
+        }
+        records
+    }
+
+    #[test]
+    fn load_replays_metadata_log_and_applies_topic_configs() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        {
+            let mut logs =
+                LogManager::load(dir.path(), LogConfig::default(), SystemClock::shared())
+                    .expect("load");
+            write_batch(
+                &mut logs,
+                &[MetadataRecord::FeatureLevel(FeatureLevelRecord {
+                    name: "metadata.version".to_string(),
+                    feature_level: 20,
+                })],
+            );
+            write_batch(&mut logs, &topic_records("orders", 1, 3));
+            write_batch(
+                &mut logs,
+                &[MetadataRecord::Config(ConfigRecord {
+                    resource_type: TOPIC_RESOURCE_TYPE,
+                    resource_name: "orders".to_string(),
+                    name: "cleanup.policy".to_string(),
+                    value: Some("compact".to_string()),
+                })],
+            );
+            logs.shutdown().expect("shutdown");
+        }
+
+        let mut logs = LogManager::load(dir.path(), LogConfig::default(), SystemClock::shared())
+            .expect("reload");
+        let image = load(&mut logs).expect("metadata image");
+
+        assert_eq!(image.offset(), 5);
+        assert_eq!(image.feature_level("metadata.version"), Some(20));
+        let orders = image.topic("orders").expect("orders topic");
+        assert_eq!(orders.id, Uuid([1; 16]));
+        assert_eq!(orders.partitions.len(), 3);
+        assert!(logs.config_for("orders").cleanup_policy.compact);
+        assert_eq!(logs.config_for(METADATA_TOPIC).retention_ms, None);
+    }
+
+    #[test]
+    fn replay_starts_inside_a_batch() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let mut logs = LogManager::load(dir.path(), LogConfig::default(), SystemClock::shared())
+            .expect("load");
+        write_batch(&mut logs, &topic_records("orders", 1, 2));
+        let log = logs.get_log(&metadata_partition()).expect("metadata log");
+
+        let mut image = MetadataImage::default();
+        assert_eq!(replay(&mut image, log, 1).expect("replay"), 2);
+        assert!(image.topics().next().is_none());
+        assert_eq!(image.offset(), 2);
+    }
+}
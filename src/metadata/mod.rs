@@ -0,0 +1,11 @@
+pub mod controller;
+pub mod image;
+pub mod loader;
+pub mod records;
+pub mod snapshot;
+pub mod uuid;
+
+pub use controller::MetadataController;
+pub use image::{MetadataImage, PartitionRegistration, TopicImage};
+pub use records::MetadataRecord;
+pub use uuid::Uuid;
@@ -0,0 +1,664 @@
+use super::Uuid;
+use crate::codec::primitives;
+use std::io::{self, Cursor, Read};
+
+/// Version of the framing that precedes every metadata record value.
+const FRAME_VERSION: u32 = 1;
+
+pub const REGISTER_BROKER_RECORD: u32 = 0;
+pub const UNREGISTER_BROKER_RECORD: u32 = 1;
+pub const TOPIC_RECORD: u32 = 2;
+pub const PARTITION_RECORD: u32 = 3;
+pub const CONFIG_RECORD: u32 = 4;
+pub const PARTITION_CHANGE_RECORD: u32 = 5;
+pub const FENCE_BROKER_RECORD: u32 = 8;
+pub const UNFENCE_BROKER_RECORD: u32 = 9;
+pub const REMOVE_TOPIC_RECORD: u32 = 10;
+pub const FEATURE_LEVEL_RECORD: u32 = 12;
+pub const PRODUCER_IDS_RECORD: u32 = 15;
+
+/// `ConfigRecord` resource type for topic-level configs.
+pub const TOPIC_RESOURCE_TYPE: i8 = 2;
+/// `ConfigRecord` resource type for broker-level configs.
+pub const BROKER_RESOURCE_TYPE: i8 = 4;
+
+/// `PartitionChangeRecord` leader value meaning the leader did not change.
+pub const NO_LEADER_CHANGE: i32 = -2;
+
+/// A record from the KRaft `__cluster_metadata` log.
+///
+/// Each record is stored as the value of a log record, framed by a frame
+/// version, the record type and the record version, all as unsigned varints.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MetadataRecord {
+    RegisterBroker(RegisterBrokerRecord),
+    UnregisterBroker {
+        broker_id: i32,
+        broker_epoch: i64,
+    },
+    Topic(TopicRecord),
+    Partition(PartitionRecord),
+    Config(ConfigRecord),
+    PartitionChange(PartitionChangeRecord),
+    FenceBroker {
+        broker_id: i32,
+        broker_epoch: i64,
+    },
+    UnfenceBroker {
+        broker_id: i32,
+        broker_epoch: i64,
+    },
+    RemoveTopic {
+        topic_id: Uuid,
+    },
+    FeatureLevel(FeatureLevelRecord),
+    ProducerIds(ProducerIdsRecord),
+    /// A record type this broker does not interpret, kept verbatim.
+    Unsupported {
+        record_type: u32,
+        version: u32,
+        data: Vec<u8>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RegisterBrokerRecord {
+    pub broker_id: i32,
+    pub is_migrating_zk_broker: bool,
+    pub incarnation_id: Uuid,
+    pub broker_epoch: i64,
+    pub end_points: Vec<BrokerEndpoint>,
+    pub features: Vec<BrokerFeature>,
+    pub rack: Option<String>,
+    pub fenced: bool,
+    pub in_controlled_shutdown: bool,
+    pub log_dirs: Vec<Uuid>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BrokerEndpoint {
+    pub name: String,
+    pub host: String,
+    pub port: u16,
+    pub security_protocol: i16,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BrokerFeature {
+    pub name: String,
+    pub min_supported_version: i16,
+    pub max_supported_version: i16,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TopicRecord {
+    pub name: String,
+    pub topic_id: Uuid,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PartitionRecord {
+    pub partition_id: i32,
+    pub topic_id: Uuid,
+    pub replicas: Vec<i32>,
+    pub isr: Vec<i32>,
+    pub removing_replicas: Vec<i32>,
+    pub adding_replicas: Vec<i32>,
+    pub leader: i32,
+    pub leader_recovery_state: i8,
+    pub leader_epoch: i32,
+    pub partition_epoch: i32,
+    pub directories: Vec<Uuid>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigRecord {
+    pub resource_type: i8,
+    pub resource_name: String,
+    pub name: String,
+    /// The new value, or `None` to remove the config.
+    pub value: Option<String>,
+}
+
+/// An update to some fields of an existing partition; `None` fields are unchanged.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PartitionChangeRecord {
+    pub partition_id: i32,
+    pub topic_id: Uuid,
+    pub isr: Option<Vec<i32>>,
+    /// The new leader, or [`NO_LEADER_CHANGE`].
+    pub leader: i32,
+    pub replicas: Option<Vec<i32>>,
+    pub removing_replicas: Option<Vec<i32>>,
+    pub adding_replicas: Option<Vec<i32>>,
+    /// The new recovery state, or `-1` if unchanged.
+    pub leader_recovery_state: i8,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FeatureLevelRecord {
+    pub name: String,
+    pub feature_level: i16,
+}
+
+/// Reserves every producer id below `next_producer_id` for `broker_id`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProducerIdsRecord {
+    pub broker_id: i32,
+    pub broker_epoch: i64,
+    pub next_producer_id: i64,
+}
+
+impl MetadataRecord {
+    /// Decodes a framed metadata record from a log record value.
+    pub fn decode(value: &[u8]) -> io::Result<Self> {
+        let mut cursor = Cursor::new(value);
+        let frame_version = primitives::read_unsigned_varint(&mut cursor)?;
+        if frame_version > FRAME_VERSION {
+            return Err(invalid(format!(
+                "unsupported metadata frame version {frame_version}"
+            )));
+        }
+        let record_type = primitives::read_unsigned_varint(&mut cursor)?;
+        let version = primitives::read_unsigned_varint(&mut cursor)?;
+        let max_version = match record_type {
+            REGISTER_BROKER_RECORD => 3,
+            PARTITION_RECORD | PARTITION_CHANGE_RECORD => 2,
+            UNREGISTER_BROKER_RECORD
+            | TOPIC_RECORD
+            | CONFIG_RECORD
+            | FENCE_BROKER_RECORD
+            | UNFENCE_BROKER_RECORD
+            | REMOVE_TOPIC_RECORD
+            | FEATURE_LEVEL_RECORD
+            | PRODUCER_IDS_RECORD => 0,
+            _ => {
+                let mut data = Vec::new();
+                cursor.read_to_end(&mut data)?;
+                return Ok(Self::Unsupported {
+                    record_type,
+                    version,
+                    data,
+                });
+            }
+        };
+        if version > max_version {
+            return Err(invalid(format!(
+                "unsupported version {version} of metadata record type {record_type}"
+            )));
+        }
+
+        let cursor = &mut cursor;
+        let record = match record_type {
+            REGISTER_BROKER_RECORD => {
+                Self::RegisterBroker(decode_register_broker(cursor, version)?)
+            }
+            UNREGISTER_BROKER_RECORD => Self::UnregisterBroker {
+                broker_id: primitives::read_i32(cursor)?,
+                broker_epoch: primitives::read_i64(cursor)?,
+            },
+            TOPIC_RECORD => Self::Topic(TopicRecord {
+                name: primitives::read_compact_string(cursor)?,
+                topic_id: Uuid(primitives::read_uuid(cursor)?),
+            }),
+            PARTITION_RECORD => Self::Partition(decode_partition(cursor, version)?),
+            CONFIG_RECORD => Self::Config(ConfigRecord {
+                resource_type: primitives::read_i8(cursor)?,
+                resource_name: primitives::read_compact_string(cursor)?,
+                name: primitives::read_compact_string(cursor)?,
+                value: primitives::read_compact_nullable_string(cursor)?,
+            }),
+            PARTITION_CHANGE_RECORD => Self::PartitionChange(decode_partition_change(cursor)?),
+            FENCE_BROKER_RECORD => Self::FenceBroker {
+                broker_id: primitives::read_i32(cursor)?,
+                broker_epoch: primitives::read_i64(cursor)?,
+            },
+            UNFENCE_BROKER_RECORD => Self::UnfenceBroker {
+                broker_id: primitives::read_i32(cursor)?,
+                broker_epoch: primitives::read_i64(cursor)?,
+            },
+            REMOVE_TOPIC_RECORD => Self::RemoveTopic {
+                topic_id: Uuid(primitives::read_uuid(cursor)?),
+            },
+            FEATURE_LEVEL_RECORD => Self::FeatureLevel(FeatureLevelRecord {
+                name: primitives::read_compact_string(cursor)?,
+                feature_level: primitives::read_i16(cursor)?,
+            }),
+            PRODUCER_IDS_RECORD => Self::ProducerIds(ProducerIdsRecord {
+                broker_id: primitives::read_i32(cursor)?,
+                broker_epoch: primitives::read_i64(cursor)?,
+                next_producer_id: primitives::read_i64(cursor)?,
+            }),
+            _ => unreachable!("unsupported record types return early"),
+        };
+        if record_type != PARTITION_RECORD && record_type != PARTITION_CHANGE_RECORD {
+            primitives::read_tagged_fields(cursor)?;
+        }
+        Ok(record)
+    }
+
+    /// Encodes the record as a framed log record value.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut buffer = Vec::new();
+        primitives::write_unsigned_varint(&mut buffer, FRAME_VERSION);
+        let (record_type, version) = self.type_and_version();
+        primitives::write_unsigned_varint(&mut buffer, record_type);
+        primitives::write_unsigned_varint(&mut buffer, version);
+
+        match self {
+            Self::RegisterBroker(record) => encode_register_broker(&mut buffer, record),
+            Self::UnregisterBroker {
+                broker_id,
+                broker_epoch,
+            }
+            | Self::FenceBroker {
+                broker_id,
+                broker_epoch,
+            }
+            | Self::UnfenceBroker {
+                broker_id,
+                broker_epoch,
+            } => {
+                buffer.extend_from_slice(&broker_id.to_be_bytes());
+                buffer.extend_from_slice(&broker_epoch.to_be_bytes());
+            }
+            Self::Topic(record) => {
+                primitives::write_compact_string(&mut buffer, &record.name);
+                buffer.extend_from_slice(record.topic_id.as_bytes());
+            }
+            Self::Partition(record) => {
+                encode_partition(&mut buffer, record);
+                return buffer;
+            }
+            Self::Config(record) => {
+                buffer.push(record.resource_type as u8);
+                primitives::write_compact_string(&mut buffer, &record.resource_name);
+                primitives::write_compact_string(&mut buffer, &record.name);
+                primitives::write_compact_nullable_string(&mut buffer, record.value.as_deref());
+            }
+            Self::PartitionChange(record) => {
+                encode_partition_change(&mut buffer, record);
+                return buffer;
+            }
+            Self::RemoveTopic { topic_id } => buffer.extend_from_slice(topic_id.as_bytes()),
+            Self::FeatureLevel(record) => {
+                primitives::write_compact_string(&mut buffer, &record.name);
+                buffer.extend_from_slice(&record.feature_level.to_be_bytes());
+            }
+            Self::ProducerIds(record) => {
+                buffer.extend_from_slice(&record.broker_id.to_be_bytes());
+                buffer.extend_from_slice(&record.broker_epoch.to_be_bytes());
+                buffer.extend_from_slice(&record.next_producer_id.to_be_bytes());
+            }
+            Self::Unsupported { data, .. } => {
+                buffer.extend_from_slice(data);
+                return buffer;
+            }
+        }
+        primitives::write_tagged_fields(&mut buffer, &[]);
+        buffer
+    }
+
+    fn type_and_version(&self) -> (u32, u32) {
+        match self {
+            Self::RegisterBroker(_) => (REGISTER_BROKER_RECORD, 3),
+            Self::UnregisterBroker { .. } => (UNREGISTER_BROKER_RECORD, 0),
+            Self::Topic(_) => (TOPIC_RECORD, 0),
+            Self::Partition(record) => (PARTITION_RECORD, partition_version(record)),
+            Self::Config(_) => (CONFIG_RECORD, 0),
+            Self::PartitionChange(_) => (PARTITION_CHANGE_RECORD, 0),
+            Self::FenceBroker { .. } => (FENCE_BROKER_RECORD, 0),
+            Self::UnfenceBroker { .. } => (UNFENCE_BROKER_RECORD, 0),
+            Self::RemoveTopic { .. } => (REMOVE_TOPIC_RECORD, 0),
+            Self::FeatureLevel(_) => (FEATURE_LEVEL_RECORD, 0),
+            Self::ProducerIds(_) => (PRODUCER_IDS_RECORD, 0),
+            Self::Unsupported {
+                record_type,
+                version,
+                ..
+            } => (*record_type, *version),
+        }
+    }
+}
+
+fn decode_register_broker(
+    cursor: &mut Cursor<&[u8]>,
+    version: u32,
+) -> io::Result<RegisterBrokerRecord> {
+    let broker_id = primitives::read_i32(cursor)?;
+    let is_migrating_zk_broker = version >= 2 && primitives::read_bool(cursor)?;
+    let incarnation_id = Uuid(primitives::read_uuid(cursor)?);
+    let broker_epoch = primitives::read_i64(cursor)?;
+
+    let mut end_points = Vec::new();
+    for _ in 0..read_array_len(cursor)? {
+        end_points.push(BrokerEndpoint {
+            name: primitives::read_compact_string(cursor)?,
+            host: primitives::read_compact_string(cursor)?,
+            port: primitives::read_u16(cursor)?,
+            security_protocol: primitives::read_i16(cursor)?,
+        });
+        primitives::read_tagged_fields(cursor)?;
+    }
+
+    let mut features = Vec::new();
+    for _ in 0..read_array_len(cursor)? {
+        features.push(BrokerFeature {
+            name: primitives::read_compact_string(cursor)?,
+            min_supported_version: primitives::read_i16(cursor)?,
+            max_supported_version: primitives::read_i16(cursor)?,
+        });
+        primitives::read_tagged_fields(cursor)?;
+    }
+
+    let rack = primitives::read_compact_nullable_string(cursor)?;
+    let fenced = primitives::read_bool(cursor)?;
+    let in_controlled_shutdown = version >= 1 && primitives::read_bool(cursor)?;
+    let log_dirs = if version >= 3 {
+        read_uuid_array(cursor)?
+    } else {
+        Vec::new()
+    };
+
+    Ok(RegisterBrokerRecord {
+        broker_id,
+        is_migrating_zk_broker,
+        incarnation_id,
+        broker_epoch,
+        end_points,
+        features,
+        rack,
+        fenced,
+        in_controlled_shutdown,
+        log_dirs,
+    })
+}
+
+fn encode_register_broker(buffer: &mut Vec<u8>, record: &RegisterBrokerRecord) {
+    buffer.extend_from_slice(&record.broker_id.to_be_bytes());
+    buffer.push(u8::from(record.is_migrating_zk_broker));
+    buffer.extend_from_slice(record.incarnation_id.as_bytes());
+    buffer.extend_from_slice(&record.broker_epoch.to_be_bytes());
+
+    primitives::write_compact_array_len(buffer, Some(record.end_points.len()));
+    for endpoint in &record.end_points {
+        primitives::write_compact_string(buffer, &endpoint.name);
+        primitives::write_compact_string(buffer, &endpoint.host);
+        buffer.extend_from_slice(&endpoint.port.to_be_bytes());
+        buffer.extend_from_slice(&endpoint.security_protocol.to_be_bytes());
+        primitives::write_tagged_fields(buffer, &[]);
+    }
+
+    primitives::write_compact_array_len(buffer, Some(record.features.len()));
+    for feature in &record.features {
+        primitives::write_compact_string(buffer, &feature.name);
+        buffer.extend_from_slice(&feature.min_supported_version.to_be_bytes());
+        buffer.extend_from_slice(&feature.max_supported_version.to_be_bytes());
+        primitives::write_tagged_fields(buffer, &[]);
+    }
+
+    primitives::write_compact_nullable_string(buffer, record.rack.as_deref());
+    buffer.push(u8::from(record.fenced));
+    buffer.push(u8::from(record.in_controlled_shutdown));
+    write_uuid_array(buffer, &record.log_dirs);
+}
+
+fn partition_version(record: &PartitionRecord) -> u32 {
+    if record.directories.is_empty() {
+        0
+    } else {
+        1
+    }
+}
+
+fn decode_partition(cursor: &mut Cursor<&[u8]>, version: u32) -> io::Result<PartitionRecord> {
+    let mut record = PartitionRecord {
+        partition_id: primitives::read_i32(cursor)?,
+        topic_id: Uuid(primitives::read_uuid(cursor)?),
+        replicas: read_i32_array(cursor)?,
+        isr: read_i32_array(cursor)?,
+        removing_replicas: read_i32_array(cursor)?,
+        adding_replicas: read_i32_array(cursor)?,
+        leader: primitives::read_i32(cursor)?,
+        leader_recovery_state: 0,
+        leader_epoch: primitives::read_i32(cursor)?,
+        partition_epoch: primitives::read_i32(cursor)?,
+        directories: Vec::new(),
+    };
+    if version >= 1 {
+        record.directories = read_uuid_array(cursor)?;
+    }
+
+    for (tag, data) in primitives::read_tagged_fields(cursor)? {
+        if tag == 0 {
+            record.leader_recovery_state = primitives::read_i8(&mut Cursor::new(&data[..]))?;
+        }
+    }
+    Ok(record)
+}
+
+fn encode_partition(buffer: &mut Vec<u8>, record: &PartitionRecord) {
+    buffer.extend_from_slice(&record.partition_id.to_be_bytes());
+    buffer.extend_from_slice(record.topic_id.as_bytes());
+    write_i32_array(buffer, &record.replicas);
+    write_i32_array(buffer, &record.isr);
+    write_i32_array(buffer, &record.removing_replicas);
+    write_i32_array(buffer, &record.adding_replicas);
+    buffer.extend_from_slice(&record.leader.to_be_bytes());
+    buffer.extend_from_slice(&record.leader_epoch.to_be_bytes());
+    buffer.extend_from_slice(&record.partition_epoch.to_be_bytes());
+    if partition_version(record) >= 1 {
+        write_uuid_array(buffer, &record.directories);
+    }
+
+    let mut tagged = Vec::new();
+    if record.leader_recovery_state != 0 {
+        tagged.push((0, vec![record.leader_recovery_state as u8]));
+    }
+    primitives::write_tagged_fields(buffer, &tagged);
+}
+
+fn decode_partition_change(cursor: &mut Cursor<&[u8]>) -> io::Result<PartitionChangeRecord> {
+    let mut record = PartitionChangeRecord {
+        partition_id: primitives::read_i32(cursor)?,
+        topic_id: Uuid(primitives::read_uuid(cursor)?),
+        isr: None,
+        leader: NO_LEADER_CHANGE,
+        replicas: None,
+        removing_replicas: None,
+        adding_replicas: None,
+        leader_recovery_state: -1,
+    };
+
+    for (tag, data) in primitives::read_tagged_fields(cursor)? {
+        let field = &mut Cursor::new(&data[..]);
+        match tag {
+            0 => record.isr = Some(read_i32_array(field)?),
+            1 => record.leader = primitives::read_i32(field)?,
+            2 => record.replicas = Some(read_i32_array(field)?),
+            3 => record.removing_replicas = Some(read_i32_array(field)?),
+            4 => record.adding_replicas = Some(read_i32_array(field)?),
+            5 => record.leader_recovery_state = primitives::read_i8(field)?,
+            _ => {}
+        }
+    }
+    Ok(record)
+}
+
+fn encode_partition_change(buffer: &mut Vec<u8>, record: &PartitionChangeRecord) {
+    buffer.extend_from_slice(&record.partition_id.to_be_bytes());
+    buffer.extend_from_slice(record.topic_id.as_bytes());
+
+    let array_field = |values: &[i32]| {
+        let mut field = Vec::new();
+        write_i32_array(&mut field, values);
+        field
+    };
+    let mut tagged = Vec::new();
+    if let Some(isr) = &record.isr {
+        tagged.push((0, array_field(isr)));
+    }
+    if record.leader != NO_LEADER_CHANGE {
+        tagged.push((1, record.leader.to_be_bytes().to_vec()));
+    }
+    if let Some(replicas) = &record.replicas {
+        tagged.push((2, array_field(replicas)));
+    }
+    if let Some(removing) = &record.removing_replicas {
+        tagged.push((3, array_field(removing)));
+    }
+    if let Some(adding) = &record.adding_replicas {
+        tagged.push((4, array_field(adding)));
+    }
+    if record.leader_recovery_state != -1 {
+        tagged.push((5, vec![record.leader_recovery_state as u8]));
+    }
+    primitives::write_tagged_fields(buffer, &tagged);
+}
+
+fn read_array_len(cursor: &mut Cursor<&[u8]>) -> io::Result<usize> {
+    Ok(primitives::read_compact_array_len(cursor)?.unwrap_or(0))
+}
+
+fn read_i32_array(cursor: &mut Cursor<&[u8]>) -> io::Result<Vec<i32>> {
+    (0..read_array_len(cursor)?)
+        .map(|_| primitives::read_i32(cursor))
+        .collect()
+}
+
+fn write_i32_array(buffer: &mut Vec<u8>, values: &[i32]) {
+    primitives::write_compact_array_len(buffer, Some(values.len()));
+    for value in values {
+        buffer.extend_from_slice(&value.to_be_bytes());
+    }
+}
+
+fn read_uuid_array(cursor: &mut Cursor<&[u8]>) -> io::Result<Vec<Uuid>> {
+    (0..read_array_len(cursor)?)
+        .map(|_| primitives::read_uuid(cursor).map(Uuid))
+        .collect()
+}
+
+fn write_uuid_array(buffer: &mut Vec<u8>, values: &[Uuid]) {
+    primitives::write_compact_array_len(buffer, Some(values.len()));
+    for value in values {
+        buffer.extend_from_slice(value.as_bytes());
+    }
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn topic_id(byte: u8) -> Uuid {
+        Uuid([byte; 16])
+    }
+
+    #[test]
+    fn decodes_topic_record_written_by_kafka() {
+        let mut value = vec![0x01, 0x02, 0x00, 0x04];
+        value.extend_from_slice(b"bar");
+        value.extend_from_slice(&[0x71; 16]);
+        value.push(0x00);
+
+        let record = MetadataRecord::decode(&value).expect("decode");
+
+        assert_eq!(
+            record,
+            MetadataRecord::Topic(TopicRecord {
+                name: "bar".to_string(),
+                topic_id: topic_id(0x71),
+            })
+        );
+        assert_eq!(record.encode(), value);
+    }
+
+    #[test]
+    fn records_round_trip() {
+        let records = vec![
+            MetadataRecord::FeatureLevel(FeatureLevelRecord {
+                name: "metadata.version".to_string(),
+                feature_level: 20,
+            }),
+            MetadataRecord::Partition(PartitionRecord {
+                partition_id: 1,
+                topic_id: topic_id(3),
+                replicas: vec![1],
+                isr: vec![1],
+                removing_replicas: Vec::new(),
+                adding_replicas: Vec::new(),
+                leader: 1,
+                leader_recovery_state: 1,
+                leader_epoch: 4,
+                partition_epoch: 5,
+                directories: vec![topic_id(9)],
+            }),
+            MetadataRecord::PartitionChange(PartitionChangeRecord {
+                partition_id: 1,
+                topic_id: topic_id(3),
+                isr: Some(vec![1, 2]),
+                leader: 2,
+                replicas: None,
+                removing_replicas: None,
+                adding_replicas: Some(vec![2]),
+                leader_recovery_state: -1,
+            }),
+            MetadataRecord::Config(ConfigRecord {
+                resource_type: TOPIC_RESOURCE_TYPE,
+                resource_name: "orders".to_string(),
+                name: "cleanup.policy".to_string(),
+                value: None,
+            }),
+            MetadataRecord::RegisterBroker(RegisterBrokerRecord {
+                broker_id: 1,
+                is_migrating_zk_broker: false,
+                incarnation_id: topic_id(7),
+                broker_epoch: 12,
+                end_points: vec![BrokerEndpoint {
+                    name: "PLAINTEXT".to_string(),
+                    host: "localhost".to_string(),
+                    port: 9092,
+                    security_protocol: 0,
+                }],
+                features: vec![BrokerFeature {
+                    name: "metadata.version".to_string(),
+                    min_supported_version: 1,
+                    max_supported_version: 20,
+                }],
+                rack: None,
+                fenced: true,
+                in_controlled_shutdown: false,
+                log_dirs: vec![topic_id(8)],
+            }),
+            MetadataRecord::RemoveTopic {
+                topic_id: topic_id(3),
+            },
+            MetadataRecord::ProducerIds(ProducerIdsRecord {
+                broker_id: 1,
+                broker_epoch: 12,
+                next_producer_id: 2_000,
+            }),
+            MetadataRecord::Unsupported {
+                record_type: 21,
+                version: 0,
+                data: vec![0],
+            },
+        ];
+
+        for record in records {
+            let decoded = MetadataRecord::decode(&record.encode()).expect("decode");
+            assert_eq!(decoded, record);
+        }
+    }
+
+    #[test]
+    fn rejects_newer_record_versions() {
+        let value = [0x01, 0x02, 0x05, 0x00];
+        let err = MetadataRecord::decode(&value).expect_err("version 5 is unknown");
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,296 @@
+use super::{MetadataImage, MetadataRecord};
+use crate::codec::primitives;
+use crate::storage::batch::{self, BatchHeader, RecordBatchBuilder};
+use std::fs::{self, File};
+use std::io::{self, Cursor, Write};
+use std::path::Path;
+
+pub const SNAPSHOT_SUFFIX: &str = ".checkpoint";
+const PARTIAL_SNAPSHOT_SUFFIX: &str = ".checkpoint.part";
+
+const CONTROL_RECORD_VERSION: i16 = 0;
+const SNAPSHOT_HEADER_TYPE: i16 = 3;
+const SNAPSHOT_FOOTER_TYPE: i16 = 4;
+const RECORDS_PER_BATCH: usize = 1000;
+
+/// Identifies a snapshot by the offset of the first metadata log record it does
+/// not contain and the epoch of the last record it does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct SnapshotId {
+    pub end_offset: i64,
+    pub epoch: i32,
+}
+
+impl SnapshotId {
+    /// File name in Kafka's `<offset>-<epoch>.checkpoint` layout.
+    pub fn file_name(&self) -> String {
+        format!(
+            "{:020}-{:010}{SNAPSHOT_SUFFIX}",
+            self.end_offset, self.epoch
+        )
+    }
+
+    pub fn parse(file_name: &str) -> Option<Self> {
+        let (end_offset, epoch) = file_name.strip_suffix(SNAPSHOT_SUFFIX)?.split_once('-')?;
+        Some(Self {
+            end_offset: end_offset.parse().ok()?,
+            epoch: epoch.parse().ok()?,
+        })
+    }
+}
+
+/// Lists the complete snapshots in `dir`, oldest first.
+///
+/// Partially written snapshots left behind by a crash are deleted.
+pub fn list_snapshots(dir: &Path) -> io::Result<Vec<SnapshotId>> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(err),
+    };
+
+    let mut snapshots = Vec::new();
+    for entry in entries {
+        let entry = entry?;
+        let name = entry.file_name();
+        let Some(name) = name.to_str() else {
+            continue;
+        };
+        if name.ends_with(PARTIAL_SNAPSHOT_SUFFIX) {
+            fs::remove_file(entry.path())?;
+        } else if let Some(id) = SnapshotId::parse(name) {
+            snapshots.push(id);
+        }
+    }
+    snapshots.sort_unstable();
+    Ok(snapshots)
+}
+
+pub fn latest_snapshot(dir: &Path) -> io::Result<Option<SnapshotId>> {
+    Ok(list_snapshots(dir)?.pop())
+}
+
+/// Writes `image` as a snapshot of the metadata log up to and including
+/// `image.offset()`.
+///
+/// The snapshot is a header control batch, the image's records and a footer
+/// control batch. It is written to a `.part` file and renamed into place once
+/// fsynced, so a snapshot with its final name is always complete.
+pub fn write_snapshot(
+    dir: &Path,
+    image: &MetadataImage,
+    epoch: i32,
+    now_ms: i64,
+) -> io::Result<SnapshotId> {
+    let id = SnapshotId {
+        end_offset: image.offset() + 1,
+        epoch,
+    };
+
+    let mut header = Vec::new();
+    header.extend_from_slice(&CONTROL_RECORD_VERSION.to_be_bytes());
+    header.extend_from_slice(&now_ms.to_be_bytes());
+    primitives::write_tagged_fields(&mut header, &[]);
+    let mut contents = control_batch(0, SNAPSHOT_HEADER_TYPE, &header, now_ms);
+
+    let mut next_offset = 1;
+    for chunk in image.records().chunks(RECORDS_PER_BATCH) {
+        let batch = chunk
+            .iter()
+            .fold(RecordBatchBuilder::new(next_offset), |builder, record| {
+                builder.record(now_ms, None, Some(&record.encode()))
+            })
+            .build();
+        contents.extend_from_slice(&batch);
+        next_offset += chunk.len() as i64;
+    }
+
+    let mut footer = Vec::new();
+    footer.extend_from_slice(&CONTROL_RECORD_VERSION.to_be_bytes());
+    primitives::write_tagged_fields(&mut footer, &[]);
+    contents.extend_from_slice(&control_batch(
+        next_offset,
+        SNAPSHOT_FOOTER_TYPE,
+        &footer,
+        now_ms,
+    ));
+
+    fs::create_dir_all(dir)?;
+    let path = dir.join(id.file_name());
+    let partial = path.with_extension(&PARTIAL_SNAPSHOT_SUFFIX[1..]);
+    let mut file = File::create(&partial)?;
+    file.write_all(&contents)?;
+    file.sync_all()?;
+    fs::rename(&partial, &path)?;
+    Ok(id)
+}
+
+/// Loads the image stored in snapshot `id`.
+pub fn read_snapshot(dir: &Path, id: SnapshotId) -> io::Result<MetadataImage> {
+    let path = dir.join(id.file_name());
+    let contents = fs::read(&path)?;
+    let malformed = |reason: &str| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("malformed snapshot {}: {reason}", path.display()),
+        )
+    };
+
+    let batches = batch::split_batches(&contents)?;
+    let mut image = MetadataImage::default();
+    let mut saw_header = false;
+    let mut saw_footer = false;
+    for bytes in batches {
+        let header = BatchHeader::parse(bytes)?;
+        if batch::compute_crc(bytes) != header.crc {
+            return Err(malformed("checksum mismatch"));
+        }
+        if saw_footer {
+            return Err(malformed("records after the footer"));
+        }
+
+        if header.is_control() {
+            for record in batch::read_records(bytes)? {
+                match control_record_type(record.key.as_deref()) {
+                    Some(SNAPSHOT_HEADER_TYPE) if !saw_header => saw_header = true,
+                    Some(SNAPSHOT_FOOTER_TYPE) if saw_header => saw_footer = true,
+                    _ => return Err(malformed("unexpected control record")),
+                }
+            }
+            continue;
+        }
+
+        if !saw_header {
+            return Err(malformed("missing header"));
+        }
+        for record in batch::read_records(bytes)? {
+            if let Some(value) = record.value {
+                image.apply(id.end_offset - 1, &MetadataRecord::decode(&value)?);
+            }
+        }
+    }
+
+    if !saw_footer {
+        return Err(malformed("missing footer"));
+    }
+    image.set_offset(id.end_offset - 1);
+    Ok(image)
+}
+
+/// Deletes every snapshot older than `id`, returning how many were removed.
+pub fn delete_snapshots_before(dir: &Path, id: SnapshotId) -> io::Result<usize> {
+    let mut deleted = 0;
+    for older in list_snapshots(dir)?.into_iter().filter(|older| *older < id) {
+        fs::remove_file(dir.join(older.file_name()))?;
+        deleted += 1;
+    }
+    Ok(deleted)
+}
+
+fn control_batch(offset: i64, record_type: i16, value: &[u8], now_ms: i64) -> Vec<u8> {
+    let mut key = Vec::with_capacity(4);
+    key.extend_from_slice(&CONTROL_RECORD_VERSION.to_be_bytes());
+    key.extend_from_slice(&record_type.to_be_bytes());
+    RecordBatchBuilder::new(offset)
+        .control()
+        .record(now_ms, Some(&key), Some(value))
+        .build()
+}
+
+fn control_record_type(key: Option<&[u8]>) -> Option<i16> {
+    let mut cursor = Cursor::new(key?);
+    let _version = primitives::read_i16(&mut cursor).ok()?;
+    primitives::read_i16(&mut cursor).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metadata::records::ConfigRecord;
+    use crate::metadata::records::{TopicRecord, TOPIC_RESOURCE_TYPE};
+    use crate::metadata::Uuid;
+
+    fn sample_image() -> MetadataImage {
+        let mut image = MetadataImage::default();
+        image.apply(
+            7,
+            &MetadataRecord::Topic(TopicRecord {
+                name: "orders".to_string(),
+                topic_id: Uuid([4; 16]),
+            }),
+        );
+        image.apply(
+            8,
+            &MetadataRecord::Config(ConfigRecord {
+                resource_type: TOPIC_RESOURCE_TYPE,
+                resource_name: "orders".to_string(),
+                name: "retention.ms".to_string(),
+                value: Some("1000".to_string()),
+            }),
+        );
+        image
+    }
+
+    #[test]
+    fn file_names_follow_kafka_layout() {
+        let id = SnapshotId {
+            end_offset: 42,
+            epoch: 3,
+        };
+        assert_eq!(id.file_name(), "00000000000000000042-0000000003.checkpoint");
+        assert_eq!(SnapshotId::parse(&id.file_name()), Some(id));
+        assert_eq!(SnapshotId::parse("00000000000000000042.log"), None);
+    }
+
+    #[test]
+    fn written_snapshot_reads_back_the_same_image() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let image = sample_image();
+
+        let id = write_snapshot(dir.path(), &image, 2, 1_000).expect("write");
+
+        assert_eq!(id.end_offset, 9);
+        assert_eq!(latest_snapshot(dir.path()).expect("list"), Some(id));
+        assert_eq!(read_snapshot(dir.path(), id).expect("read"), image);
+    }
+
+    #[test]
+    fn truncated_snapshot_is_rejected() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let id = write_snapshot(dir.path(), &sample_image(), 0, 1_000).expect("write");
+        let path = dir.path().join(id.file_name());
+        let contents = fs::read(&path).expect("read file");
+        let footer_start = batch::split_batches(&contents)
+            .expect("split")
+            .iter()
+            .take(2)
+            .map(|batch| batch.len())
+            .sum::<usize>();
+        fs::write(&path, &contents[..footer_start]).expect("truncate");
+
+        let err = read_snapshot(dir.path(), id).expect_err("footer is missing");
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn older_and_partial_snapshots_are_removed() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let mut image = sample_image();
+        let first = write_snapshot(dir.path(), &image, 0, 1_000).expect("write");
+        image.set_offset(20);
+        let second = write_snapshot(dir.path(), &image, 0, 2_000).expect("write");
+        fs::write(
+            dir.path()
+                .join("00000000000000000030-0000000000.checkpoint.part"),
+            b"",
+        )
+        .expect("partial");
+
+        assert_eq!(list_snapshots(dir.path()).expect("list"), [first, second]);
+        assert_eq!(
+            delete_snapshots_before(dir.path(), second).expect("delete"),
+            1
+        );
+        assert_eq!(list_snapshots(dir.path()).expect("list"), [second]);
+    }
+}
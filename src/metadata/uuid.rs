@@ -0,0 +1,141 @@
+use std::fmt;
+use std::io;
+use std::str::FromStr;
+
+const BASE64_URL_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+/// A 128-bit identifier such as a topic id, printed the way Kafka prints it:
+/// URL-safe base64 without padding.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Uuid(pub [u8; 16]);
+
+impl Uuid {
+    pub const ZERO: Self = Self([0; 16]);
+    /// Reserved by Kafka as the topic id of `__cluster_metadata`.
+    pub const METADATA_TOPIC_ID: Self = Self([0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1]);
+
+    /// Generates a random version 4 UUID, skipping the reserved ids and ids
+    /// whose string form starts with `-`, which command-line tools would
+    /// mistake for a flag.
+    pub fn random() -> io::Result<Self> {
+        loop {
+            let mut bytes = [0_u8; 16];
+            getrandom::fill(&mut bytes).map_err(|err| io::Error::other(err.to_string()))?;
+            bytes[6] = (bytes[6] & 0x0F) | 0x40;
+            bytes[8] = (bytes[8] & 0x3F) | 0x80;
+
+            let uuid = Self(bytes);
+            if uuid != Self::ZERO
+                && uuid != Self::METADATA_TOPIC_ID
+                && !uuid.to_string().starts_with('-')
+            {
+                return Ok(uuid);
+            }
+        }
+    }
+
+    pub fn as_bytes(&self) -> &[u8; 16] {
+        &self.0
+    }
+}
+
+impl fmt::Display for Uuid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut bits = 0_u32;
+        let mut bit_count = 0;
+        for byte in self.0 {
+            bits = (bits << 8) | u32::from(byte);
+            bit_count += 8;
+            while bit_count >= 6 {
+                bit_count -= 6;
+                let index = (bits >> bit_count) & 0x3F;
+                write!(f, "{}", BASE64_URL_ALPHABET[index as usize] as char)?;
+            }
+        }
+        if bit_count > 0 {
+            let index = (bits << (6 - bit_count)) & 0x3F;
+            write!(f, "{}", BASE64_URL_ALPHABET[index as usize] as char)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Uuid {
+    type Err = io::Error;
+
+    /// Parses the 22-character base64 form produced by `Display`.
+    fn from_str(value: &str) -> io::Result<Self> {
+        let invalid = || {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("invalid uuid '{value}'"),
+            )
+        };
+        if value.len() != 22 {
+            return Err(invalid());
+        }
+
+        let mut bytes = [0_u8; 16];
+        let mut bits = 0_u32;
+        let mut bit_count = 0;
+        let mut filled = 0;
+        for c in value.bytes() {
+            let index = BASE64_URL_ALPHABET
+                .iter()
+                .position(|&symbol| symbol == c)
+                .ok_or_else(invalid)?;
+            bits = (bits << 6) | index as u32;
+            bit_count += 6;
+            if bit_count >= 8 {
+                bit_count -= 8;
+                bytes[filled] = (bits >> bit_count) as u8;
+                filled += 1;
+            }
+        }
+        if bits & ((1 << bit_count) - 1) != 0 {
+            return Err(invalid());
+        }
+        Ok(Self(bytes))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn displays_as_unpadded_url_safe_base64() {
+        assert_eq!(Uuid::ZERO.to_string(), "AAAAAAAAAAAAAAAAAAAAAA");
+
+        let mut bytes = [0_u8; 16];
+        bytes[0] = 0xFB;
+        bytes[15] = 0xFF;
+        assert_eq!(Uuid(bytes).to_string(), "-wAAAAAAAAAAAAAAAAAA_w");
+    }
+
+    #[test]
+    fn random_ids_are_distinct_and_printable() {
+        let first = Uuid::random().expect("random");
+        let second = Uuid::random().expect("random");
+
+        assert_ne!(first, second);
+        assert_eq!(first.as_bytes()[6] >> 4, 4);
+        assert!(!first.to_string().starts_with('-'));
+    }
+
+    #[test]
+    fn parses_its_display_form() {
+        let uuid = Uuid::random().expect("random");
+        assert_eq!(uuid.to_string().parse::<Uuid>().expect("parse"), uuid);
+
+        for invalid in [
+            "",
+            "AAAA",
+            "AAAAAAAAAAAAAAAAAAAAA!",
+            "AAAAAAAAAAAAAAAAAAAAAB",
+        ] {
+            assert!(invalid.parse::<Uuid>().is_err(), "{invalid}");
+        }
+    }
+}
@@ -0,0 +1,132 @@
+use super::header::{RequestHeader, ResponseHeader};
+use super::wire;
+use crate::codec::primitives;
+use crate::metadata::Uuid;
+use std::io::{self, Cursor};
+
+pub const CONSUMER_GROUP_DESCRIBE_KEY: i16 = 69;
+pub const MIN_VERSION: i16 = 0;
+pub const MAX_VERSION: i16 = 0;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConsumerGroupDescribeRequest {
+    pub header: RequestHeader,
+    pub group_ids: Vec<String>,
+    pub include_authorized_operations: bool,
+}
+
+impl ConsumerGroupDescribeRequest {
+    pub fn supports(api_version: i16) -> bool {
+        (MIN_VERSION..=MAX_VERSION).contains(&api_version)
+    }
+
+    pub fn decode(header: RequestHeader, cursor: &mut Cursor<&[u8]>) -> io::Result<Self> {
+        wire::skip_tagged_fields(cursor, true)?;
+
+        let group_ids = wire::read_array(cursor, true, |cursor| wire::read_string(cursor, true))?
+            .unwrap_or_default();
+        let include_authorized_operations = primitives::read_bool(cursor)?;
+        wire::skip_tagged_fields(cursor, true)?;
+
+        Ok(Self {
+            header,
+            group_ids,
+            include_authorized_operations,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConsumerGroupDescribeResponse {
+    pub header: ResponseHeader,
+    pub api_version: i16,
+    pub throttle_time_ms: i32,
+    pub groups: Vec<ConsumerGroupDescription>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConsumerGroupDescription {
+    pub error_code: i16,
+    pub error_message: Option<String>,
+    pub group_id: String,
+    pub group_state: String,
+    pub group_epoch: i32,
+    pub assignment_epoch: i32,
+    pub assignor_name: String,
+    pub members: Vec<ConsumerGroupMemberDescription>,
+    pub authorized_operations: i32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConsumerGroupMemberDescription {
+    pub member_id: String,
+    pub instance_id: Option<String>,
+    pub rack_id: Option<String>,
+    pub member_epoch: i32,
+    pub client_id: String,
+    pub client_host: String,
+    pub subscribed_topic_names: Vec<String>,
+    pub subscribed_topic_regex: Option<String>,
+    pub assignment: Vec<DescribedTopicPartitions>,
+    pub target_assignment: Vec<DescribedTopicPartitions>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DescribedTopicPartitions {
+    pub topic_id: Uuid,
+    pub topic_name: String,
+    pub partitions: Vec<i32>,
+}
+
+impl ConsumerGroupDescribeResponse {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut body = Vec::new();
+        body.extend_from_slice(&self.throttle_time_ms.to_be_bytes());
+        wire::write_array(&mut body, &self.groups, true, |buffer, group| {
+            buffer.extend_from_slice(&group.error_code.to_be_bytes());
+            wire::write_nullable_string(buffer, group.error_message.as_deref(), true);
+            wire::write_string(buffer, &group.group_id, true);
+            wire::write_string(buffer, &group.group_state, true);
+            buffer.extend_from_slice(&group.group_epoch.to_be_bytes());
+            buffer.extend_from_slice(&group.assignment_epoch.to_be_bytes());
+            wire::write_string(buffer, &group.assignor_name, true);
+            wire::write_array(buffer, &group.members, true, |buffer, member| {
+                wire::write_string(buffer, &member.member_id, true);
+                wire::write_nullable_string(buffer, member.instance_id.as_deref(), true);
+                wire::write_nullable_string(buffer, member.rack_id.as_deref(), true);
+                buffer.extend_from_slice(&member.member_epoch.to_be_bytes());
+                wire::write_string(buffer, &member.client_id, true);
+                wire::write_string(buffer, &member.client_host, true);
+                wire::write_array(
+                    buffer,
+                    &member.subscribed_topic_names,
+                    true,
+                    |buffer, name| {
+                        wire::write_string(buffer, name, true);
+                    },
+                );
+                wire::write_nullable_string(buffer, member.subscribed_topic_regex.as_deref(), true);
+                write_assignment(buffer, &member.assignment);
+                write_assignment(buffer, &member.target_assignment);
+                wire::write_empty_tagged_fields(buffer, true);
+            });
+            buffer.extend_from_slice(&group.authorized_operations.to_be_bytes());
+            wire::write_empty_tagged_fields(buffer, true);
+        });
+        wire::write_empty_tagged_fields(&mut body, true);
+
+        self.header.frame(true, &body)
+    }
+}
+
+fn write_assignment(buffer: &mut Vec<u8>, topics: &[DescribedTopicPartitions]) {
+    wire::write_array(buffer, topics, true, |buffer, topic| {
+        buffer.extend_from_slice(topic.topic_id.as_bytes());
+        wire::write_string(buffer, &topic.topic_name, true);
+        wire::write_array(buffer, &topic.partitions, true, |buffer, partition| {
+            buffer.extend_from_slice(&partition.to_be_bytes());
+        });
+        wire::write_empty_tagged_fields(buffer, true);
+    });
+    wire::write_empty_tagged_fields(buffer, true);
+}
@@ -0,0 +1,200 @@
+use super::header::{RequestHeader, ResponseHeader};
+use super::wire;
+use crate::codec::primitives;
+use crate::metadata::Uuid;
+use std::io::{self, Cursor};
+
+pub const CONSUMER_GROUP_HEARTBEAT_KEY: i16 = 68;
+pub const MIN_VERSION: i16 = 0;
+pub const MAX_VERSION: i16 = 0;
+
+/// Member epoch sent to join the group.
+pub const JOIN_GROUP_MEMBER_EPOCH: i32 = 0;
+/// Member epoch sent to leave the group.
+pub const LEAVE_GROUP_MEMBER_EPOCH: i32 = -1;
+/// Member epoch a static member sends to leave while keeping its place
+/// until its session expires.
+pub const LEAVE_GROUP_STATIC_MEMBER_EPOCH: i32 = -2;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConsumerGroupHeartbeatRequest {
+    pub header: RequestHeader,
+    pub group_id: String,
+    /// Empty when joining.
+    pub member_id: String,
+    pub member_epoch: i32,
+    pub instance_id: Option<String>,
+    pub rack_id: Option<String>,
+    /// `-1` if unchanged.
+    pub rebalance_timeout_ms: i32,
+    /// `None` if unchanged.
+    pub subscribed_topic_names: Option<Vec<String>>,
+    /// `None` if unchanged.
+    pub subscribed_topic_regex: Option<String>,
+    pub server_assignor: Option<String>,
+    /// Partitions the member owns, or `None` if unchanged.
+    pub topic_partitions: Option<Vec<ConsumerGroupTopicPartitions>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConsumerGroupTopicPartitions {
+    pub topic_id: Uuid,
+    pub partitions: Vec<i32>,
+}
+
+impl ConsumerGroupHeartbeatRequest {
+    pub fn supports(api_version: i16) -> bool {
+        (MIN_VERSION..=MAX_VERSION).contains(&api_version)
+    }
+
+    pub fn decode(header: RequestHeader, cursor: &mut Cursor<&[u8]>) -> io::Result<Self> {
+        wire::skip_tagged_fields(cursor, true)?;
+
+        let group_id = wire::read_string(cursor, true)?;
+        let member_id = wire::read_string(cursor, true)?;
+        let member_epoch = primitives::read_i32(cursor)?;
+        let instance_id = wire::read_nullable_string(cursor, true)?;
+        let rack_id = wire::read_nullable_string(cursor, true)?;
+        let rebalance_timeout_ms = primitives::read_i32(cursor)?;
+        let subscribed_topic_names =
+            wire::read_array(cursor, true, |cursor| wire::read_string(cursor, true))?;
+        let subscribed_topic_regex = wire::read_nullable_string(cursor, true)?;
+        let server_assignor = wire::read_nullable_string(cursor, true)?;
+        let topic_partitions = wire::read_array(cursor, true, |cursor| {
+            let topic_id = Uuid(primitives::read_uuid(cursor)?);
+            let partitions =
+                wire::read_array(cursor, true, primitives::read_i32)?.unwrap_or_default();
+            wire::skip_tagged_fields(cursor, true)?;
+            Ok(ConsumerGroupTopicPartitions {
+                topic_id,
+                partitions,
+            })
+        })?;
+        wire::skip_tagged_fields(cursor, true)?;
+
+        Ok(Self {
+            header,
+            group_id,
+            member_id,
+            member_epoch,
+            instance_id,
+            rack_id,
+            rebalance_timeout_ms,
+            subscribed_topic_names,
+            subscribed_topic_regex,
+            server_assignor,
+            topic_partitions,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConsumerGroupHeartbeatResponse {
+    pub header: ResponseHeader,
+    pub api_version: i16,
+    pub throttle_time_ms: i32,
+    pub error_code: i16,
+    pub error_message: Option<String>,
+    pub member_id: Option<String>,
+    pub member_epoch: i32,
+    pub heartbeat_interval_ms: i32,
+    /// The member's new assignment, or `None` if it did not change.
+    pub assignment: Option<Vec<ConsumerGroupTopicPartitions>>,
+}
+
+impl ConsumerGroupHeartbeatResponse {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut body = Vec::new();
+        body.extend_from_slice(&self.throttle_time_ms.to_be_bytes());
+        body.extend_from_slice(&self.error_code.to_be_bytes());
+        wire::write_nullable_string(&mut body, self.error_message.as_deref(), true);
+        wire::write_nullable_string(&mut body, self.member_id.as_deref(), true);
+        body.extend_from_slice(&self.member_epoch.to_be_bytes());
+        body.extend_from_slice(&self.heartbeat_interval_ms.to_be_bytes());
+        match &self.assignment {
+            Some(topics) => {
+                body.push(1);
+                write_topic_partitions(&mut body, topics);
+                wire::write_empty_tagged_fields(&mut body, true);
+            }
+            None => body.push(0xFF),
+        }
+        wire::write_empty_tagged_fields(&mut body, true);
+
+        self.header.frame(true, &body)
+    }
+}
+
+fn write_topic_partitions(buffer: &mut Vec<u8>, topics: &[ConsumerGroupTopicPartitions]) {
+    wire::write_array(buffer, topics, true, |buffer, topic| {
+        buffer.extend_from_slice(topic.topic_id.as_bytes());
+        wire::write_array(buffer, &topic.partitions, true, |buffer, partition| {
+            buffer.extend_from_slice(&partition.to_be_bytes());
+        });
+        wire::write_empty_tagged_fields(buffer, true);
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_a_join_with_a_regex_subscription() {
+        let header = RequestHeader {
+            request_api_key: CONSUMER_GROUP_HEARTBEAT_KEY,
+            request_api_version: 0,
+            correlation_id: 3,
+            client_id: Some("consumer".to_string()),
+        };
+        let mut body = vec![0];
+        primitives::write_compact_string(&mut body, "payments");
+        primitives::write_compact_string(&mut body, "");
+        body.extend_from_slice(&JOIN_GROUP_MEMBER_EPOCH.to_be_bytes());
+        primitives::write_compact_nullable_string(&mut body, None);
+        primitives::write_compact_nullable_string(&mut body, Some("rack-a"));
+        body.extend_from_slice(&30_000_i32.to_be_bytes());
+        primitives::write_compact_array_len(&mut body, None);
+        primitives::write_compact_nullable_string(&mut body, Some("orders-.*"));
+        primitives::write_compact_nullable_string(&mut body, Some("range"));
+        primitives::write_compact_array_len(&mut body, Some(1));
+        body.extend_from_slice(&[7; 16]);
+        primitives::write_compact_array_len(&mut body, Some(1));
+        body.extend_from_slice(&2_i32.to_be_bytes());
+        body.extend_from_slice(&[0, 0]);
+
+        let request =
+            ConsumerGroupHeartbeatRequest::decode(header, &mut Cursor::new(body.as_slice()))
+                .expect("decode");
+
+        assert_eq!(request.subscribed_topic_names, None);
+        assert_eq!(request.subscribed_topic_regex.as_deref(), Some("orders-.*"));
+        assert_eq!(request.server_assignor.as_deref(), Some("range"));
+        assert_eq!(
+            request.topic_partitions,
+            Some(vec![ConsumerGroupTopicPartitions {
+                topic_id: Uuid([7; 16]),
+                partitions: vec![2],
+            }])
+        );
+    }
+
+    #[test]
+    fn unchanged_assignment_is_encoded_as_null() {
+        let response = ConsumerGroupHeartbeatResponse {
+            header: ResponseHeader { correlation_id: 3 },
+            api_version: 0,
+            throttle_time_ms: 0,
+            error_code: 0,
+            error_message: None,
+            member_id: Some("m".to_string()),
+            member_epoch: 4,
+            heartbeat_interval_ms: 5_000,
+            assignment: None,
+        };
+
+        let bytes = response.to_bytes();
+
+        assert_eq!(&bytes[bytes.len() - 2..], &[0xFF, 0]);
+    }
+}
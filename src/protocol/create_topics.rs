@@ -0,0 +1,181 @@
+use super::header::{RequestHeader, ResponseHeader};
+use super::wire;
+use crate::codec::primitives;
+use crate::metadata::Uuid;
+use std::io::{self, Cursor};
+
+pub const CREATE_TOPICS_KEY: i16 = 19;
+/// Versions 0 and 1 predate the throttle time and are not served.
+pub const MIN_VERSION: i16 = 2;
+pub const MAX_VERSION: i16 = 7;
+const FIRST_FLEXIBLE_VERSION: i16 = 5;
+
+/// Partition count or replication factor that asks for the broker default.
+pub const DEFAULT_SETTING: i32 = -1;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CreateTopicsRequest {
+    pub header: RequestHeader,
+    pub topics: Vec<CreatableTopic>,
+    pub timeout_ms: i32,
+    /// Checks the request without creating anything.
+    pub validate_only: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CreatableTopic {
+    pub name: String,
+    pub num_partitions: i32,
+    pub replication_factor: i16,
+    /// Manual replica assignments; empty to let the broker place replicas.
+    pub assignments: Vec<CreatableReplicaAssignment>,
+    pub configs: Vec<CreatableTopicConfig>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CreatableReplicaAssignment {
+    pub partition_index: i32,
+    pub broker_ids: Vec<i32>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CreatableTopicConfig {
+    pub name: String,
+    pub value: Option<String>,
+}
+
+impl CreateTopicsRequest {
+    pub fn supports(api_version: i16) -> bool {
+        (MIN_VERSION..=MAX_VERSION).contains(&api_version)
+    }
+
+    pub fn decode(header: RequestHeader, cursor: &mut Cursor<&[u8]>) -> io::Result<Self> {
+        let flexible = header.request_api_version >= FIRST_FLEXIBLE_VERSION;
+        wire::skip_tagged_fields(cursor, flexible)?;
+
+        let topics = wire::read_array(cursor, flexible, |cursor| {
+            let name = wire::read_string(cursor, flexible)?;
+            let num_partitions = primitives::read_i32(cursor)?;
+            let replication_factor = primitives::read_i16(cursor)?;
+            let assignments = wire::read_array(cursor, flexible, |cursor| {
+                let partition_index = primitives::read_i32(cursor)?;
+                let broker_ids =
+                    wire::read_array(cursor, flexible, primitives::read_i32)?.unwrap_or_default();
+                wire::skip_tagged_fields(cursor, flexible)?;
+                Ok(CreatableReplicaAssignment {
+                    partition_index,
+                    broker_ids,
+                })
+            })?
+            .unwrap_or_default();
+            let configs = wire::read_array(cursor, flexible, |cursor| {
+                let name = wire::read_string(cursor, flexible)?;
+                let value = wire::read_nullable_string(cursor, flexible)?;
+                wire::skip_tagged_fields(cursor, flexible)?;
+                Ok(CreatableTopicConfig { name, value })
+            })?
+            .unwrap_or_default();
+            wire::skip_tagged_fields(cursor, flexible)?;
+            Ok(CreatableTopic {
+                name,
+                num_partitions,
+                replication_factor,
+                assignments,
+                configs,
+            })
+        })?
+        .unwrap_or_default();
+        let timeout_ms = primitives::read_i32(cursor)?;
+        let validate_only = primitives::read_bool(cursor)?;
+        wire::skip_tagged_fields(cursor, flexible)?;
+
+        Ok(Self {
+            header,
+            topics,
+            timeout_ms,
+            validate_only,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CreateTopicsResponse {
+    pub header: ResponseHeader,
+    pub api_version: i16,
+    pub throttle_time_ms: i32,
+    pub topics: Vec<CreatableTopicResult>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CreatableTopicResult {
+    pub name: String,
+    pub topic_id: Uuid,
+    pub error_code: i16,
+    pub error_message: Option<String>,
+    pub num_partitions: i32,
+    pub replication_factor: i16,
+}
+
+impl CreateTopicsResponse {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let version = self.api_version;
+        let flexible = version >= FIRST_FLEXIBLE_VERSION;
+        let mut body = Vec::new();
+        body.extend_from_slice(&self.throttle_time_ms.to_be_bytes());
+        wire::write_array(&mut body, &self.topics, flexible, |buffer, topic| {
+            wire::write_string(buffer, &topic.name, flexible);
+            if version >= 7 {
+                buffer.extend_from_slice(topic.topic_id.as_bytes());
+            }
+            buffer.extend_from_slice(&topic.error_code.to_be_bytes());
+            wire::write_nullable_string(buffer, topic.error_message.as_deref(), flexible);
+            if version >= 5 {
+                buffer.extend_from_slice(&topic.num_partitions.to_be_bytes());
+                buffer.extend_from_slice(&topic.replication_factor.to_be_bytes());
+                // Configs are left null; clients describe them separately.
+                wire::write_array_len(buffer, None, flexible);
+            }
+            wire::write_empty_tagged_fields(buffer, flexible);
+        });
+        wire::write_empty_tagged_fields(&mut body, flexible);
+
+        self.header.frame(flexible, &body)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_flexible_topics_with_configs() {
+        let header = RequestHeader {
+            request_api_key: CREATE_TOPICS_KEY,
+            request_api_version: 7,
+            correlation_id: 5,
+            client_id: Some("admin".to_string()),
+        };
+        let mut body = vec![0];
+        primitives::write_compact_array_len(&mut body, Some(1));
+        primitives::write_compact_string(&mut body, "orders");
+        body.extend_from_slice(&3_i32.to_be_bytes());
+        body.extend_from_slice(&(-1_i16).to_be_bytes());
+        primitives::write_compact_array_len(&mut body, Some(0));
+        primitives::write_compact_array_len(&mut body, Some(1));
+        primitives::write_compact_string(&mut body, "cleanup.policy");
+        primitives::write_compact_nullable_string(&mut body, Some("compact"));
+        body.extend_from_slice(&[0, 0]);
+        body.extend_from_slice(&30_000_i32.to_be_bytes());
+        body.push(1);
+        body.push(0);
+
+        let request =
+            CreateTopicsRequest::decode(header, &mut Cursor::new(body.as_slice())).expect("decode");
+
+        let topic = &request.topics[0];
+        assert_eq!((topic.name.as_str(), topic.num_partitions), ("orders", 3));
+        assert_eq!(topic.replication_factor, -1);
+        assert_eq!(topic.configs[0].value.as_deref(), Some("compact"));
+        assert!(request.validate_only);
+    }
+}
@@ -0,0 +1,66 @@
+use super::header::{RequestHeader, ResponseHeader};
+use super::wire;
+use std::io::{self, Cursor};
+
+pub const DELETE_GROUPS_KEY: i16 = 42;
+pub const MIN_VERSION: i16 = 0;
+pub const MAX_VERSION: i16 = 2;
+const FIRST_FLEXIBLE_VERSION: i16 = 2;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeleteGroupsRequest {
+    pub header: RequestHeader,
+    pub groups_names: Vec<String>,
+}
+
+impl DeleteGroupsRequest {
+    pub fn supports(api_version: i16) -> bool {
+        (MIN_VERSION..=MAX_VERSION).contains(&api_version)
+    }
+
+    pub fn decode(header: RequestHeader, cursor: &mut Cursor<&[u8]>) -> io::Result<Self> {
+        let flexible = header.request_api_version >= FIRST_FLEXIBLE_VERSION;
+        wire::skip_tagged_fields(cursor, flexible)?;
+
+        let groups_names = wire::read_array(cursor, flexible, |cursor| {
+            wire::read_string(cursor, flexible)
+        })?
+        .unwrap_or_default();
+        wire::skip_tagged_fields(cursor, flexible)?;
+
+        Ok(Self {
+            header,
+            groups_names,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeleteGroupsResponse {
+    pub header: ResponseHeader,
+    pub api_version: i16,
+    pub throttle_time_ms: i32,
+    pub results: Vec<DeletableGroupResult>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeletableGroupResult {
+    pub group_id: String,
+    pub error_code: i16,
+}
+
+impl DeleteGroupsResponse {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let flexible = self.api_version >= FIRST_FLEXIBLE_VERSION;
+        let mut body = Vec::new();
+        body.extend_from_slice(&self.throttle_time_ms.to_be_bytes());
+        wire::write_array(&mut body, &self.results, flexible, |buffer, result| {
+            wire::write_string(buffer, &result.group_id, flexible);
+            buffer.extend_from_slice(&result.error_code.to_be_bytes());
+            wire::write_empty_tagged_fields(buffer, flexible);
+        });
+        wire::write_empty_tagged_fields(&mut body, flexible);
+
+        self.header.frame(flexible, &body)
+    }
+}
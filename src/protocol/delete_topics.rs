@@ -0,0 +1,138 @@
+use super::header::{RequestHeader, ResponseHeader};
+use super::wire;
+use crate::codec::primitives;
+use crate::metadata::Uuid;
+use std::io::{self, Cursor};
+
+pub const DELETE_TOPICS_KEY: i16 = 20;
+/// Version 0 predates the throttle time and is not served.
+pub const MIN_VERSION: i16 = 1;
+pub const MAX_VERSION: i16 = 6;
+const FIRST_FLEXIBLE_VERSION: i16 = 4;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeleteTopicsRequest {
+    pub header: RequestHeader,
+    pub topics: Vec<DeleteTopicState>,
+    pub timeout_ms: i32,
+}
+
+/// A topic to delete, named before version 6 and by name or id since.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeleteTopicState {
+    pub name: Option<String>,
+    pub topic_id: Uuid,
+}
+
+impl DeleteTopicsRequest {
+    pub fn supports(api_version: i16) -> bool {
+        (MIN_VERSION..=MAX_VERSION).contains(&api_version)
+    }
+
+    pub fn decode(header: RequestHeader, cursor: &mut Cursor<&[u8]>) -> io::Result<Self> {
+        let version = header.request_api_version;
+        let flexible = version >= FIRST_FLEXIBLE_VERSION;
+        wire::skip_tagged_fields(cursor, flexible)?;
+
+        let topics = if version >= 6 {
+            wire::read_array(cursor, flexible, |cursor| {
+                let name = wire::read_nullable_string(cursor, flexible)?;
+                let topic_id = Uuid(primitives::read_uuid(cursor)?);
+                wire::skip_tagged_fields(cursor, flexible)?;
+                Ok(DeleteTopicState { name, topic_id })
+            })?
+        } else {
+            wire::read_array(cursor, flexible, |cursor| {
+                Ok(DeleteTopicState {
+                    name: Some(wire::read_string(cursor, flexible)?),
+                    topic_id: Uuid::ZERO,
+                })
+            })?
+        }
+        .unwrap_or_default();
+        let timeout_ms = primitives::read_i32(cursor)?;
+        wire::skip_tagged_fields(cursor, flexible)?;
+
+        Ok(Self {
+            header,
+            topics,
+            timeout_ms,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeleteTopicsResponse {
+    pub header: ResponseHeader,
+    pub api_version: i16,
+    pub throttle_time_ms: i32,
+    pub responses: Vec<DeletableTopicResult>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeletableTopicResult {
+    pub name: Option<String>,
+    pub topic_id: Uuid,
+    pub error_code: i16,
+    pub error_message: Option<String>,
+}
+
+impl DeleteTopicsResponse {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let version = self.api_version;
+        let flexible = version >= FIRST_FLEXIBLE_VERSION;
+        let mut body = Vec::new();
+        body.extend_from_slice(&self.throttle_time_ms.to_be_bytes());
+        wire::write_array(&mut body, &self.responses, flexible, |buffer, result| {
+            if version >= 6 {
+                wire::write_nullable_string(buffer, result.name.as_deref(), flexible);
+                buffer.extend_from_slice(result.topic_id.as_bytes());
+            } else {
+                wire::write_string(buffer, result.name.as_deref().unwrap_or_default(), flexible);
+            }
+            buffer.extend_from_slice(&result.error_code.to_be_bytes());
+            if version >= 5 {
+                wire::write_nullable_string(buffer, result.error_message.as_deref(), flexible);
+            }
+            wire::write_empty_tagged_fields(buffer, flexible);
+        });
+        wire::write_empty_tagged_fields(&mut body, flexible);
+
+        self.header.frame(flexible, &body)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_topics_by_name_and_by_id() {
+        let header = |version| RequestHeader {
+            request_api_key: DELETE_TOPICS_KEY,
+            request_api_version: version,
+            correlation_id: 2,
+            client_id: None,
+        };
+        let mut body = Vec::new();
+        wire::write_array_len(&mut body, Some(1), false);
+        wire::write_string(&mut body, "orders", false);
+        body.extend_from_slice(&5_000_i32.to_be_bytes());
+        let request = DeleteTopicsRequest::decode(header(3), &mut Cursor::new(body.as_slice()))
+            .expect("decode v3");
+        assert_eq!(request.topics[0].name.as_deref(), Some("orders"));
+
+        let mut body = vec![0];
+        primitives::write_compact_array_len(&mut body, Some(1));
+        primitives::write_compact_nullable_string(&mut body, None);
+        body.extend_from_slice(&[9; 16]);
+        body.push(0);
+        body.extend_from_slice(&5_000_i32.to_be_bytes());
+        body.push(0);
+        let request = DeleteTopicsRequest::decode(header(6), &mut Cursor::new(body.as_slice()))
+            .expect("decode v6");
+        assert_eq!(request.topics[0].name, None);
+        assert_eq!(request.topics[0].topic_id, Uuid([9; 16]));
+        assert_eq!(request.timeout_ms, 5_000);
+    }
+}
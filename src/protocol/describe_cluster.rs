@@ -0,0 +1,133 @@
+use super::header::{RequestHeader, ResponseHeader};
+use super::metadata::AUTHORIZED_OPERATIONS_OMITTED;
+use crate::codec::primitives;
+use std::io::{self, Cursor};
+
+pub const DESCRIBE_CLUSTER_KEY: i16 = 60;
+pub const MIN_VERSION: i16 = 0;
+pub const MAX_VERSION: i16 = 1;
+
+/// `endpoint_type` asking for broker listeners, the only kind served here.
+pub const BROKER_ENDPOINT_TYPE: i8 = 1;
+
+/// DescribeCluster is flexible in every version.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DescribeClusterRequest {
+    pub header: RequestHeader,
+    pub include_cluster_authorized_operations: bool,
+    pub endpoint_type: i8,
+}
+
+impl DescribeClusterRequest {
+    pub fn supports(api_version: i16) -> bool {
+        (MIN_VERSION..=MAX_VERSION).contains(&api_version)
+    }
+
+    pub fn decode(header: RequestHeader, cursor: &mut Cursor<&[u8]>) -> io::Result<Self> {
+        primitives::read_tagged_fields(cursor)?;
+        let include_cluster_authorized_operations = primitives::read_bool(cursor)?;
+        let endpoint_type = if header.request_api_version >= 1 {
+            primitives::read_i8(cursor)?
+        } else {
+            BROKER_ENDPOINT_TYPE
+        };
+        primitives::read_tagged_fields(cursor)?;
+
+        Ok(Self {
+            header,
+            include_cluster_authorized_operations,
+            endpoint_type,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DescribeClusterResponse {
+    pub header: ResponseHeader,
+    pub api_version: i16,
+    pub throttle_time_ms: i32,
+    pub error_code: i16,
+    pub error_message: Option<String>,
+    pub endpoint_type: i8,
+    pub cluster_id: String,
+    pub controller_id: i32,
+    pub brokers: Vec<DescribeClusterBroker>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DescribeClusterBroker {
+    pub broker_id: i32,
+    pub host: String,
+    pub port: i32,
+    pub rack: Option<String>,
+}
+
+impl DescribeClusterResponse {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut body = Vec::new();
+        body.extend_from_slice(&self.throttle_time_ms.to_be_bytes());
+        body.extend_from_slice(&self.error_code.to_be_bytes());
+        primitives::write_compact_nullable_string(&mut body, self.error_message.as_deref());
+        if self.api_version >= 1 {
+            body.extend_from_slice(&self.endpoint_type.to_be_bytes());
+        }
+        primitives::write_compact_string(&mut body, &self.cluster_id);
+        body.extend_from_slice(&self.controller_id.to_be_bytes());
+        primitives::write_compact_array_len(&mut body, Some(self.brokers.len()));
+        for broker in &self.brokers {
+            body.extend_from_slice(&broker.broker_id.to_be_bytes());
+            primitives::write_compact_string(&mut body, &broker.host);
+            body.extend_from_slice(&broker.port.to_be_bytes());
+            primitives::write_compact_nullable_string(&mut body, broker.rack.as_deref());
+            primitives::write_tagged_fields(&mut body, &[]);
+        }
+        body.extend_from_slice(&AUTHORIZED_OPERATIONS_OMITTED.to_be_bytes());
+        primitives::write_tagged_fields(&mut body, &[]);
+
+        self.header.frame(true, &body)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_endpoint_type_from_version_one() {
+        let header = RequestHeader {
+            request_api_key: DESCRIBE_CLUSTER_KEY,
+            request_api_version: 1,
+            correlation_id: 3,
+            client_id: None,
+        };
+        let body = [0, 1, 2, 0];
+
+        let request =
+            DescribeClusterRequest::decode(header, &mut Cursor::new(&body[..])).expect("decode");
+
+        assert!(request.include_cluster_authorized_operations);
+        assert_eq!(request.endpoint_type, 2);
+    }
+
+    #[test]
+    fn encodes_cluster_id_after_flexible_header() {
+        let response = DescribeClusterResponse {
+            header: ResponseHeader { correlation_id: 3 },
+            api_version: 0,
+            throttle_time_ms: 0,
+            error_code: 0,
+            error_message: None,
+            endpoint_type: BROKER_ENDPOINT_TYPE,
+            cluster_id: "abc".to_string(),
+            controller_id: 1,
+            brokers: Vec::new(),
+        };
+
+        let bytes = response.to_bytes();
+
+        assert_eq!(&bytes[4..8], &3_i32.to_be_bytes());
+        // Header tagged fields, throttle time, error code and null message.
+        assert_eq!(&bytes[8..16], &[0, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(&bytes[16..20], &[4, b'a', b'b', b'c']);
+    }
+}
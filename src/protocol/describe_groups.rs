@@ -0,0 +1,154 @@
+use super::header::{RequestHeader, ResponseHeader};
+use super::wire;
+use crate::codec::primitives;
+use std::io::{self, Cursor};
+
+pub const DESCRIBE_GROUPS_KEY: i16 = 15;
+pub const MIN_VERSION: i16 = 0;
+pub const MAX_VERSION: i16 = 5;
+const FIRST_FLEXIBLE_VERSION: i16 = 5;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DescribeGroupsRequest {
+    pub header: RequestHeader,
+    pub groups: Vec<String>,
+    pub include_authorized_operations: bool,
+}
+
+impl DescribeGroupsRequest {
+    pub fn supports(api_version: i16) -> bool {
+        (MIN_VERSION..=MAX_VERSION).contains(&api_version)
+    }
+
+    pub fn decode(header: RequestHeader, cursor: &mut Cursor<&[u8]>) -> io::Result<Self> {
+        let version = header.request_api_version;
+        let flexible = version >= FIRST_FLEXIBLE_VERSION;
+        wire::skip_tagged_fields(cursor, flexible)?;
+
+        let groups = wire::read_array(cursor, flexible, |cursor| {
+            wire::read_string(cursor, flexible)
+        })?
+        .unwrap_or_default();
+        let include_authorized_operations = version >= 3 && primitives::read_bool(cursor)?;
+        wire::skip_tagged_fields(cursor, flexible)?;
+
+        Ok(Self {
+            header,
+            groups,
+            include_authorized_operations,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DescribeGroupsResponse {
+    pub header: ResponseHeader,
+    pub api_version: i16,
+    pub throttle_time_ms: i32,
+    pub groups: Vec<DescribedGroup>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DescribedGroup {
+    pub error_code: i16,
+    pub group_id: String,
+    pub group_state: String,
+    pub protocol_type: String,
+    /// The selected protocol, once the group is stable.
+    pub protocol_data: String,
+    pub members: Vec<DescribedGroupMember>,
+    pub authorized_operations: i32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DescribedGroupMember {
+    pub member_id: String,
+    pub group_instance_id: Option<String>,
+    pub client_id: String,
+    pub client_host: String,
+    pub member_metadata: Vec<u8>,
+    pub member_assignment: Vec<u8>,
+}
+
+impl DescribeGroupsResponse {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let version = self.api_version;
+        let flexible = version >= FIRST_FLEXIBLE_VERSION;
+        let mut body = Vec::new();
+        if version >= 1 {
+            body.extend_from_slice(&self.throttle_time_ms.to_be_bytes());
+        }
+        wire::write_array(&mut body, &self.groups, flexible, |buffer, group| {
+            buffer.extend_from_slice(&group.error_code.to_be_bytes());
+            wire::write_string(buffer, &group.group_id, flexible);
+            wire::write_string(buffer, &group.group_state, flexible);
+            wire::write_string(buffer, &group.protocol_type, flexible);
+            wire::write_string(buffer, &group.protocol_data, flexible);
+            wire::write_array(buffer, &group.members, flexible, |buffer, member| {
+                wire::write_string(buffer, &member.member_id, flexible);
+                if version >= 4 {
+                    wire::write_nullable_string(
+                        buffer,
+                        member.group_instance_id.as_deref(),
+                        flexible,
+                    );
+                }
+                wire::write_string(buffer, &member.client_id, flexible);
+                wire::write_string(buffer, &member.client_host, flexible);
+                wire::write_bytes(buffer, &member.member_metadata, flexible);
+                wire::write_bytes(buffer, &member.member_assignment, flexible);
+                wire::write_empty_tagged_fields(buffer, flexible);
+            });
+            if version >= 3 {
+                buffer.extend_from_slice(&group.authorized_operations.to_be_bytes());
+            }
+            wire::write_empty_tagged_fields(buffer, flexible);
+        });
+        wire::write_empty_tagged_fields(&mut body, flexible);
+
+        self.header.frame(flexible, &body)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn version_zero_response_has_no_instance_ids_or_operations() {
+        let response = DescribeGroupsResponse {
+            header: ResponseHeader { correlation_id: 2 },
+            api_version: 0,
+            throttle_time_ms: 0,
+            groups: vec![DescribedGroup {
+                error_code: 0,
+                group_id: "g".to_string(),
+                group_state: "Stable".to_string(),
+                protocol_type: "consumer".to_string(),
+                protocol_data: "range".to_string(),
+                members: vec![DescribedGroupMember {
+                    member_id: "m".to_string(),
+                    group_instance_id: Some("pod-0".to_string()),
+                    client_id: "c".to_string(),
+                    client_host: "/10.0.0.1".to_string(),
+                    member_metadata: vec![1],
+                    member_assignment: vec![2, 3],
+                }],
+                authorized_operations: 0,
+            }],
+        };
+
+        let mut expected = vec![0, 0, 0, 1, 0, 0];
+        for value in ["g", "Stable", "consumer", "range"] {
+            wire::write_string(&mut expected, value, false);
+        }
+        expected.extend_from_slice(&1_i32.to_be_bytes());
+        for value in ["m", "c", "/10.0.0.1"] {
+            wire::write_string(&mut expected, value, false);
+        }
+        wire::write_bytes(&mut expected, &[1], false);
+        wire::write_bytes(&mut expected, &[2, 3], false);
+
+        assert_eq!(&response.to_bytes()[8..], expected.as_slice());
+    }
+}
@@ -0,0 +1,179 @@
+use super::header::{RequestHeader, ResponseHeader};
+use super::metadata::AUTHORIZED_OPERATIONS_OMITTED;
+use super::wire;
+use crate::codec::primitives;
+use crate::metadata::Uuid;
+use std::io::{self, Cursor};
+
+pub const DESCRIBE_TOPIC_PARTITIONS_KEY: i16 = 75;
+pub const MIN_VERSION: i16 = 0;
+pub const MAX_VERSION: i16 = 0;
+
+/// Marker byte of a null struct; a present struct is preceded by `1`.
+const NULL_STRUCT: i8 = -1;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DescribeTopicPartitionsRequest {
+    pub header: RequestHeader,
+    pub topics: Vec<String>,
+    pub response_partition_limit: i32,
+    pub cursor: Option<TopicPartitionCursor>,
+}
+
+/// The first partition to describe, as returned in a previous `next_cursor`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TopicPartitionCursor {
+    pub topic_name: String,
+    pub partition_index: i32,
+}
+
+impl DescribeTopicPartitionsRequest {
+    pub fn supports(api_version: i16) -> bool {
+        (MIN_VERSION..=MAX_VERSION).contains(&api_version)
+    }
+
+    pub fn decode(header: RequestHeader, cursor: &mut Cursor<&[u8]>) -> io::Result<Self> {
+        wire::skip_tagged_fields(cursor, true)?;
+
+        let topics = wire::read_array(cursor, true, |cursor| {
+            let name = wire::read_string(cursor, true)?;
+            wire::skip_tagged_fields(cursor, true)?;
+            Ok(name)
+        })?
+        .unwrap_or_default();
+        let response_partition_limit = primitives::read_i32(cursor)?;
+        let start = if primitives::read_i8(cursor)? == NULL_STRUCT {
+            None
+        } else {
+            let topic_name = wire::read_string(cursor, true)?;
+            let partition_index = primitives::read_i32(cursor)?;
+            wire::skip_tagged_fields(cursor, true)?;
+            Some(TopicPartitionCursor {
+                topic_name,
+                partition_index,
+            })
+        };
+        wire::skip_tagged_fields(cursor, true)?;
+
+        Ok(Self {
+            header,
+            topics,
+            response_partition_limit,
+            cursor: start,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DescribeTopicPartitionsResponse {
+    pub header: ResponseHeader,
+    pub api_version: i16,
+    pub throttle_time_ms: i32,
+    pub topics: Vec<DescribeTopicPartitionsResponseTopic>,
+    pub next_cursor: Option<TopicPartitionCursor>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DescribeTopicPartitionsResponseTopic {
+    pub error_code: i16,
+    pub name: Option<String>,
+    pub topic_id: Uuid,
+    pub is_internal: bool,
+    pub partitions: Vec<DescribeTopicPartitionsResponsePartition>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DescribeTopicPartitionsResponsePartition {
+    pub error_code: i16,
+    pub partition_index: i32,
+    pub leader_id: i32,
+    pub leader_epoch: i32,
+    pub replica_nodes: Vec<i32>,
+    pub isr_nodes: Vec<i32>,
+    pub eligible_leader_replicas: Vec<i32>,
+    pub last_known_elr: Vec<i32>,
+    pub offline_replicas: Vec<i32>,
+}
+
+impl DescribeTopicPartitionsResponse {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut body = Vec::new();
+        body.extend_from_slice(&self.throttle_time_ms.to_be_bytes());
+        wire::write_array(&mut body, &self.topics, true, |buffer, topic| {
+            buffer.extend_from_slice(&topic.error_code.to_be_bytes());
+            wire::write_nullable_string(buffer, topic.name.as_deref(), true);
+            buffer.extend_from_slice(topic.topic_id.as_bytes());
+            primitives::write_bool(buffer, topic.is_internal);
+            wire::write_array(buffer, &topic.partitions, true, |buffer, partition| {
+                buffer.extend_from_slice(&partition.error_code.to_be_bytes());
+                buffer.extend_from_slice(&partition.partition_index.to_be_bytes());
+                buffer.extend_from_slice(&partition.leader_id.to_be_bytes());
+                buffer.extend_from_slice(&partition.leader_epoch.to_be_bytes());
+                write_nodes(buffer, &partition.replica_nodes);
+                write_nodes(buffer, &partition.isr_nodes);
+                write_nodes(buffer, &partition.eligible_leader_replicas);
+                write_nodes(buffer, &partition.last_known_elr);
+                write_nodes(buffer, &partition.offline_replicas);
+                wire::write_empty_tagged_fields(buffer, true);
+            });
+            buffer.extend_from_slice(&AUTHORIZED_OPERATIONS_OMITTED.to_be_bytes());
+            wire::write_empty_tagged_fields(buffer, true);
+        });
+        match &self.next_cursor {
+            Some(next) => {
+                body.push(1);
+                wire::write_string(&mut body, &next.topic_name, true);
+                body.extend_from_slice(&next.partition_index.to_be_bytes());
+                wire::write_empty_tagged_fields(&mut body, true);
+            }
+            None => body.extend_from_slice(&NULL_STRUCT.to_be_bytes()),
+        }
+        wire::write_empty_tagged_fields(&mut body, true);
+
+        self.header.frame(true, &body)
+    }
+}
+
+fn write_nodes(buffer: &mut Vec<u8>, nodes: &[i32]) {
+    wire::write_array(buffer, nodes, true, |buffer, node| {
+        buffer.extend_from_slice(&node.to_be_bytes());
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_topics_and_cursor() {
+        let header = RequestHeader {
+            request_api_key: DESCRIBE_TOPIC_PARTITIONS_KEY,
+            request_api_version: 0,
+            correlation_id: 9,
+            client_id: None,
+        };
+        let mut body = vec![0];
+        primitives::write_compact_array_len(&mut body, Some(1));
+        primitives::write_compact_string(&mut body, "orders");
+        body.push(0);
+        body.extend_from_slice(&100_i32.to_be_bytes());
+        body.push(1);
+        primitives::write_compact_string(&mut body, "orders");
+        body.extend_from_slice(&2_i32.to_be_bytes());
+        body.extend_from_slice(&[0, 0]);
+
+        let request =
+            DescribeTopicPartitionsRequest::decode(header, &mut Cursor::new(body.as_slice()))
+                .expect("decode");
+
+        assert_eq!(request.topics, ["orders"]);
+        assert_eq!(request.response_partition_limit, 100);
+        assert_eq!(
+            request.cursor,
+            Some(TopicPartitionCursor {
+                topic_name: "orders".to_string(),
+                partition_index: 2,
+            })
+        );
+    }
+}
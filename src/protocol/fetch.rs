@@ -0,0 +1,343 @@
+use super::header::{RequestHeader, ResponseHeader};
+use super::wire;
+use crate::codec::primitives;
+use crate::metadata::Uuid;
+use std::io::{self, Cursor};
+
+pub const FETCH_KEY: i16 = 1;
+/// Versions before 4 have no isolation level or last stable offset.
+pub const MIN_VERSION: i16 = 4;
+pub const MAX_VERSION: i16 = 16;
+const FIRST_FLEXIBLE_VERSION: i16 = 12;
+/// First version that names topics by id instead of by name.
+pub const FIRST_TOPIC_ID_VERSION: i16 = 13;
+/// First version that carries the replica id in a tagged field only.
+const FIRST_REPLICA_STATE_VERSION: i16 = 15;
+
+/// Isolation levels: read up to the high watermark or the last stable offset.
+pub const READ_UNCOMMITTED: i8 = 0;
+pub const READ_COMMITTED: i8 = 1;
+/// Replica id sent by consumers.
+pub const CONSUMER_REPLICA_ID: i32 = -1;
+/// Leader epoch and offset sent when the client does not know them.
+pub const UNKNOWN_LEADER_EPOCH: i32 = -1;
+pub const UNKNOWN_OFFSET: i64 = -1;
+/// Preferred read replica reported when the leader should be fetched from.
+pub const NO_PREFERRED_READ_REPLICA: i32 = -1;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FetchRequest {
+    pub header: RequestHeader,
+    pub replica_id: i32,
+    pub max_wait_ms: i32,
+    pub min_bytes: i32,
+    pub max_bytes: i32,
+    pub isolation_level: i8,
+    pub session_id: i32,
+    pub session_epoch: i32,
+    pub topics: Vec<FetchTopic>,
+    pub forgotten_topics_data: Vec<ForgottenTopic>,
+    pub rack_id: String,
+}
+
+/// A topic named by `name` before version 13 and by `topic_id` from it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FetchTopic {
+    pub name: Option<String>,
+    pub topic_id: Uuid,
+    pub partitions: Vec<FetchPartition>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FetchPartition {
+    pub partition: i32,
+    pub current_leader_epoch: i32,
+    pub fetch_offset: i64,
+    pub last_fetched_epoch: i32,
+    pub log_start_offset: i64,
+    pub partition_max_bytes: i32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ForgottenTopic {
+    pub name: Option<String>,
+    pub topic_id: Uuid,
+    pub partitions: Vec<i32>,
+}
+
+impl FetchRequest {
+    pub fn supports(api_version: i16) -> bool {
+        (MIN_VERSION..=MAX_VERSION).contains(&api_version)
+    }
+
+    pub fn decode(header: RequestHeader, cursor: &mut Cursor<&[u8]>) -> io::Result<Self> {
+        let version = header.request_api_version;
+        let flexible = version >= FIRST_FLEXIBLE_VERSION;
+        wire::skip_tagged_fields(cursor, flexible)?;
+
+        let replica_id = if version < FIRST_REPLICA_STATE_VERSION {
+            primitives::read_i32(cursor)?
+        } else {
+            CONSUMER_REPLICA_ID
+        };
+        let max_wait_ms = primitives::read_i32(cursor)?;
+        let min_bytes = primitives::read_i32(cursor)?;
+        let max_bytes = primitives::read_i32(cursor)?;
+        let isolation_level = primitives::read_i8(cursor)?;
+        let (session_id, session_epoch) = if version >= 7 {
+            (primitives::read_i32(cursor)?, primitives::read_i32(cursor)?)
+        } else {
+            (0, -1)
+        };
+        let topics = wire::read_array(cursor, flexible, |cursor| {
+            let (name, topic_id) = read_topic(cursor, version, flexible)?;
+            let partitions = wire::read_array(cursor, flexible, |cursor| {
+                let partition = primitives::read_i32(cursor)?;
+                let current_leader_epoch = if version >= 9 {
+                    primitives::read_i32(cursor)?
+                } else {
+                    UNKNOWN_LEADER_EPOCH
+                };
+                let fetch_offset = primitives::read_i64(cursor)?;
+                let last_fetched_epoch = if version >= 12 {
+                    primitives::read_i32(cursor)?
+                } else {
+                    UNKNOWN_LEADER_EPOCH
+                };
+                let log_start_offset = if version >= 5 {
+                    primitives::read_i64(cursor)?
+                } else {
+                    UNKNOWN_OFFSET
+                };
+                let partition_max_bytes = primitives::read_i32(cursor)?;
+                wire::skip_tagged_fields(cursor, flexible)?;
+                Ok(FetchPartition {
+                    partition,
+                    current_leader_epoch,
+                    fetch_offset,
+                    last_fetched_epoch,
+                    log_start_offset,
+                    partition_max_bytes,
+                })
+            })?
+            .unwrap_or_default();
+            wire::skip_tagged_fields(cursor, flexible)?;
+            Ok(FetchTopic {
+                name,
+                topic_id,
+                partitions,
+            })
+        })?
+        .unwrap_or_default();
+        let forgotten_topics_data = if version >= 7 {
+            wire::read_array(cursor, flexible, |cursor| {
+                let (name, topic_id) = read_topic(cursor, version, flexible)?;
+                let partitions =
+                    wire::read_array(cursor, flexible, primitives::read_i32)?.unwrap_or_default();
+                wire::skip_tagged_fields(cursor, flexible)?;
+                Ok(ForgottenTopic {
+                    name,
+                    topic_id,
+                    partitions,
+                })
+            })?
+            .unwrap_or_default()
+        } else {
+            Vec::new()
+        };
+        let rack_id = if version >= 11 {
+            wire::read_string(cursor, flexible)?
+        } else {
+            String::new()
+        };
+        wire::skip_tagged_fields(cursor, flexible)?;
+
+        Ok(Self {
+            header,
+            replica_id,
+            max_wait_ms,
+            min_bytes,
+            max_bytes,
+            isolation_level,
+            session_id,
+            session_epoch,
+            topics,
+            forgotten_topics_data,
+            rack_id,
+        })
+    }
+
+    /// Whether only committed records may be returned.
+    pub fn read_committed(&self) -> bool {
+        self.isolation_level != READ_UNCOMMITTED
+    }
+}
+
+fn read_topic(
+    cursor: &mut Cursor<&[u8]>,
+    version: i16,
+    flexible: bool,
+) -> io::Result<(Option<String>, Uuid)> {
+    if version >= FIRST_TOPIC_ID_VERSION {
+        Ok((None, Uuid(primitives::read_uuid(cursor)?)))
+    } else {
+        Ok((Some(wire::read_string(cursor, flexible)?), Uuid::ZERO))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FetchResponse {
+    pub header: ResponseHeader,
+    pub api_version: i16,
+    pub throttle_time_ms: i32,
+    pub error_code: i16,
+    pub session_id: i32,
+    pub responses: Vec<FetchableTopicResponse>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FetchableTopicResponse {
+    pub name: Option<String>,
+    pub topic_id: Uuid,
+    pub partitions: Vec<FetchPartitionData>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FetchPartitionData {
+    pub partition_index: i32,
+    pub error_code: i16,
+    pub high_watermark: i64,
+    pub last_stable_offset: i64,
+    pub log_start_offset: i64,
+    /// `None` for read-uncommitted fetches, which never filter aborts.
+    pub aborted_transactions: Option<Vec<AbortedTransaction>>,
+    pub preferred_read_replica: i32,
+    pub records: Option<Vec<u8>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AbortedTransaction {
+    pub producer_id: i64,
+    pub first_offset: i64,
+}
+
+impl FetchResponse {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let version = self.api_version;
+        let flexible = version >= FIRST_FLEXIBLE_VERSION;
+        let mut body = Vec::new();
+        body.extend_from_slice(&self.throttle_time_ms.to_be_bytes());
+        if version >= 7 {
+            body.extend_from_slice(&self.error_code.to_be_bytes());
+            body.extend_from_slice(&self.session_id.to_be_bytes());
+        }
+        wire::write_array(&mut body, &self.responses, flexible, |buffer, topic| {
+            if version >= FIRST_TOPIC_ID_VERSION {
+                buffer.extend_from_slice(topic.topic_id.as_bytes());
+            } else {
+                wire::write_string(buffer, topic.name.as_deref().unwrap_or_default(), flexible);
+            }
+            wire::write_array(buffer, &topic.partitions, flexible, |buffer, partition| {
+                buffer.extend_from_slice(&partition.partition_index.to_be_bytes());
+                buffer.extend_from_slice(&partition.error_code.to_be_bytes());
+                buffer.extend_from_slice(&partition.high_watermark.to_be_bytes());
+                buffer.extend_from_slice(&partition.last_stable_offset.to_be_bytes());
+                if version >= 5 {
+                    buffer.extend_from_slice(&partition.log_start_offset.to_be_bytes());
+                }
+                match &partition.aborted_transactions {
+                    Some(aborted) => {
+                        wire::write_array(buffer, aborted, flexible, |buffer, aborted| {
+                            buffer.extend_from_slice(&aborted.producer_id.to_be_bytes());
+                            buffer.extend_from_slice(&aborted.first_offset.to_be_bytes());
+                            wire::write_empty_tagged_fields(buffer, flexible);
+                        })
+                    }
+                    None => wire::write_array_len(buffer, None, flexible),
+                }
+                if version >= 11 {
+                    buffer.extend_from_slice(&partition.preferred_read_replica.to_be_bytes());
+                }
+                match &partition.records {
+                    Some(records) => wire::write_bytes(buffer, records, flexible),
+                    None => wire::write_array_len(buffer, None, flexible),
+                }
+                wire::write_empty_tagged_fields(buffer, flexible);
+            });
+            wire::write_empty_tagged_fields(buffer, flexible);
+        });
+        wire::write_empty_tagged_fields(&mut body, flexible);
+
+        self.header.frame(flexible, &body)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header(api_version: i16) -> RequestHeader {
+        RequestHeader {
+            request_api_key: FETCH_KEY,
+            request_api_version: api_version,
+            correlation_id: 9,
+            client_id: None,
+        }
+    }
+
+    #[test]
+    fn decodes_topic_ids_and_forgotten_topics_from_version_thirteen() {
+        let mut body = vec![0];
+        body.extend_from_slice(&CONSUMER_REPLICA_ID.to_be_bytes());
+        body.extend_from_slice(&500_i32.to_be_bytes());
+        body.extend_from_slice(&1_i32.to_be_bytes());
+        body.extend_from_slice(&1024_i32.to_be_bytes());
+        body.push(1);
+        body.extend_from_slice(&7_i32.to_be_bytes());
+        body.extend_from_slice(&2_i32.to_be_bytes());
+        primitives::write_compact_array_len(&mut body, Some(1));
+        body.extend_from_slice(&[4; 16]);
+        primitives::write_compact_array_len(&mut body, Some(1));
+        body.extend_from_slice(&3_i32.to_be_bytes());
+        body.extend_from_slice(&6_i32.to_be_bytes());
+        body.extend_from_slice(&42_i64.to_be_bytes());
+        body.extend_from_slice(&5_i32.to_be_bytes());
+        body.extend_from_slice(&10_i64.to_be_bytes());
+        body.extend_from_slice(&512_i32.to_be_bytes());
+        body.extend_from_slice(&[0, 0]);
+        primitives::write_compact_array_len(&mut body, Some(1));
+        body.extend_from_slice(&[5; 16]);
+        primitives::write_compact_array_len(&mut body, Some(1));
+        body.extend_from_slice(&1_i32.to_be_bytes());
+        body.push(0);
+        primitives::write_compact_string(&mut body, "");
+        body.push(0);
+
+        let request =
+            FetchRequest::decode(header(13), &mut Cursor::new(body.as_slice())).expect("decode");
+
+        assert!(request.read_committed());
+        assert_eq!((request.session_id, request.session_epoch), (7, 2));
+        assert_eq!(request.topics[0].name, None);
+        assert_eq!(request.topics[0].topic_id, Uuid([4; 16]));
+        assert_eq!(
+            request.topics[0].partitions,
+            [FetchPartition {
+                partition: 3,
+                current_leader_epoch: 6,
+                fetch_offset: 42,
+                last_fetched_epoch: 5,
+                log_start_offset: 10,
+                partition_max_bytes: 512,
+            }]
+        );
+        assert_eq!(
+            request.forgotten_topics_data,
+            [ForgottenTopic {
+                name: None,
+                topic_id: Uuid([5; 16]),
+                partitions: vec![1],
+            }]
+        );
+    }
+}
@@ -0,0 +1,186 @@
+use super::header::{RequestHeader, ResponseHeader};
+use super::wire;
+use crate::codec::primitives;
+use std::io::{self, Cursor};
+
+pub const FIND_COORDINATOR_KEY: i16 = 10;
+pub const MIN_VERSION: i16 = 0;
+pub const MAX_VERSION: i16 = 4;
+const FIRST_FLEXIBLE_VERSION: i16 = 3;
+/// First version that looks up several keys at once.
+const FIRST_BATCHED_VERSION: i16 = 4;
+
+pub const GROUP_KEY_TYPE: i8 = 0;
+pub const TRANSACTION_KEY_TYPE: i8 = 1;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FindCoordinatorRequest {
+    pub header: RequestHeader,
+    pub key_type: i8,
+    /// Group ids or transactional ids; a single key before version 4.
+    pub keys: Vec<String>,
+}
+
+impl FindCoordinatorRequest {
+    pub fn supports(api_version: i16) -> bool {
+        (MIN_VERSION..=MAX_VERSION).contains(&api_version)
+    }
+
+    pub fn decode(header: RequestHeader, cursor: &mut Cursor<&[u8]>) -> io::Result<Self> {
+        let version = header.request_api_version;
+        let flexible = version >= FIRST_FLEXIBLE_VERSION;
+        wire::skip_tagged_fields(cursor, flexible)?;
+
+        let mut keys = Vec::new();
+        if version < FIRST_BATCHED_VERSION {
+            keys.push(wire::read_string(cursor, flexible)?);
+        }
+        let key_type = if version >= 1 {
+            primitives::read_i8(cursor)?
+        } else {
+            GROUP_KEY_TYPE
+        };
+        if version >= FIRST_BATCHED_VERSION {
+            keys = wire::read_array(cursor, flexible, |cursor| {
+                wire::read_string(cursor, flexible)
+            })?
+            .unwrap_or_default();
+        }
+        wire::skip_tagged_fields(cursor, flexible)?;
+
+        Ok(Self {
+            header,
+            key_type,
+            keys,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FindCoordinatorResponse {
+    pub header: ResponseHeader,
+    pub api_version: i16,
+    pub throttle_time_ms: i32,
+    /// One entry per requested key; versions before 4 encode only the first.
+    pub coordinators: Vec<Coordinator>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Coordinator {
+    pub key: String,
+    pub node_id: i32,
+    pub host: String,
+    pub port: i32,
+    pub error_code: i16,
+    pub error_message: Option<String>,
+}
+
+impl FindCoordinatorResponse {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let version = self.api_version;
+        let flexible = version >= FIRST_FLEXIBLE_VERSION;
+        let mut body = Vec::new();
+        if version >= 1 {
+            body.extend_from_slice(&self.throttle_time_ms.to_be_bytes());
+        }
+        if version >= FIRST_BATCHED_VERSION {
+            wire::write_array(
+                &mut body,
+                &self.coordinators,
+                flexible,
+                |buffer, coordinator| {
+                    wire::write_string(buffer, &coordinator.key, flexible);
+                    write_address(buffer, coordinator, flexible);
+                    buffer.extend_from_slice(&coordinator.error_code.to_be_bytes());
+                    wire::write_nullable_string(
+                        buffer,
+                        coordinator.error_message.as_deref(),
+                        flexible,
+                    );
+                    wire::write_empty_tagged_fields(buffer, flexible);
+                },
+            );
+        } else if let Some(coordinator) = self.coordinators.first() {
+            body.extend_from_slice(&coordinator.error_code.to_be_bytes());
+            if version >= 1 {
+                wire::write_nullable_string(
+                    &mut body,
+                    coordinator.error_message.as_deref(),
+                    flexible,
+                );
+            }
+            write_address(&mut body, coordinator, flexible);
+        }
+        wire::write_empty_tagged_fields(&mut body, flexible);
+
+        self.header.frame(flexible, &body)
+    }
+}
+
+fn write_address(buffer: &mut Vec<u8>, coordinator: &Coordinator, flexible: bool) {
+    buffer.extend_from_slice(&coordinator.node_id.to_be_bytes());
+    wire::write_string(buffer, &coordinator.host, flexible);
+    buffer.extend_from_slice(&coordinator.port.to_be_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header(api_version: i16) -> RequestHeader {
+        RequestHeader {
+            request_api_key: FIND_COORDINATOR_KEY,
+            request_api_version: api_version,
+            correlation_id: 4,
+            client_id: None,
+        }
+    }
+
+    #[test]
+    fn decodes_single_and_batched_keys() {
+        let mut body = Vec::new();
+        wire::write_string(&mut body, "payments", false);
+        let request = FindCoordinatorRequest::decode(header(0), &mut Cursor::new(body.as_slice()))
+            .expect("decode v0");
+        assert_eq!(request.keys, ["payments"]);
+        assert_eq!(request.key_type, GROUP_KEY_TYPE);
+
+        let mut body = vec![0, TRANSACTION_KEY_TYPE as u8];
+        primitives::write_compact_array_len(&mut body, Some(2));
+        primitives::write_compact_string(&mut body, "a");
+        primitives::write_compact_string(&mut body, "b");
+        body.push(0);
+        let request = FindCoordinatorRequest::decode(header(4), &mut Cursor::new(body.as_slice()))
+            .expect("decode v4");
+        assert_eq!(request.keys, ["a", "b"]);
+        assert_eq!(request.key_type, TRANSACTION_KEY_TYPE);
+    }
+
+    #[test]
+    fn version_zero_response_is_flat() {
+        let response = FindCoordinatorResponse {
+            header: ResponseHeader { correlation_id: 4 },
+            api_version: 0,
+            throttle_time_ms: 0,
+            coordinators: vec![Coordinator {
+                key: "payments".to_string(),
+                node_id: 1,
+                host: "h".to_string(),
+                port: 9092,
+                error_code: 0,
+                error_message: None,
+            }],
+        };
+
+        let bytes = response.to_bytes();
+
+        let mut expected = Vec::new();
+        expected.extend_from_slice(&4_i32.to_be_bytes());
+        expected.extend_from_slice(&0_i16.to_be_bytes());
+        expected.extend_from_slice(&1_i32.to_be_bytes());
+        expected.extend_from_slice(&1_i16.to_be_bytes());
+        expected.push(b'h');
+        expected.extend_from_slice(&9092_i32.to_be_bytes());
+        assert_eq!(&bytes[4..], expected.as_slice());
+    }
+}
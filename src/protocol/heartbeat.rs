@@ -0,0 +1,96 @@
+use super::header::{RequestHeader, ResponseHeader};
+use super::wire;
+use crate::codec::primitives;
+use std::io::{self, Cursor};
+
+pub const HEARTBEAT_KEY: i16 = 12;
+pub const MIN_VERSION: i16 = 0;
+pub const MAX_VERSION: i16 = 4;
+const FIRST_FLEXIBLE_VERSION: i16 = 4;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HeartbeatRequest {
+    pub header: RequestHeader,
+    pub group_id: String,
+    pub generation_id: i32,
+    pub member_id: String,
+    pub group_instance_id: Option<String>,
+}
+
+impl HeartbeatRequest {
+    pub fn supports(api_version: i16) -> bool {
+        (MIN_VERSION..=MAX_VERSION).contains(&api_version)
+    }
+
+    pub fn decode(header: RequestHeader, cursor: &mut Cursor<&[u8]>) -> io::Result<Self> {
+        let version = header.request_api_version;
+        let flexible = version >= FIRST_FLEXIBLE_VERSION;
+        wire::skip_tagged_fields(cursor, flexible)?;
+
+        let group_id = wire::read_string(cursor, flexible)?;
+        let generation_id = primitives::read_i32(cursor)?;
+        let member_id = wire::read_string(cursor, flexible)?;
+        let group_instance_id = if version >= 3 {
+            wire::read_nullable_string(cursor, flexible)?
+        } else {
+            None
+        };
+        wire::skip_tagged_fields(cursor, flexible)?;
+
+        Ok(Self {
+            header,
+            group_id,
+            generation_id,
+            member_id,
+            group_instance_id,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HeartbeatResponse {
+    pub header: ResponseHeader,
+    pub api_version: i16,
+    pub throttle_time_ms: i32,
+    pub error_code: i16,
+}
+
+impl HeartbeatResponse {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let flexible = self.api_version >= FIRST_FLEXIBLE_VERSION;
+        let mut body = Vec::new();
+        if self.api_version >= 1 {
+            body.extend_from_slice(&self.throttle_time_ms.to_be_bytes());
+        }
+        body.extend_from_slice(&self.error_code.to_be_bytes());
+        wire::write_empty_tagged_fields(&mut body, flexible);
+
+        self.header.frame(flexible, &body)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_group_instance_id_from_version_three() {
+        let header = RequestHeader {
+            request_api_key: HEARTBEAT_KEY,
+            request_api_version: 3,
+            correlation_id: 2,
+            client_id: None,
+        };
+        let mut body = Vec::new();
+        wire::write_string(&mut body, "payments", false);
+        body.extend_from_slice(&7_i32.to_be_bytes());
+        wire::write_string(&mut body, "member", false);
+        wire::write_nullable_string(&mut body, Some("pod-0"), false);
+
+        let request =
+            HeartbeatRequest::decode(header, &mut Cursor::new(body.as_slice())).expect("decode");
+
+        assert_eq!(request.generation_id, 7);
+        assert_eq!(request.group_instance_id.as_deref(), Some("pod-0"));
+    }
+}
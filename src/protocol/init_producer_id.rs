@@ -0,0 +1,104 @@
+use super::header::{RequestHeader, ResponseHeader};
+use super::wire;
+use crate::codec::primitives;
+use std::io::{self, Cursor};
+
+pub const INIT_PRODUCER_ID_KEY: i16 = 22;
+pub const MIN_VERSION: i16 = 0;
+pub const MAX_VERSION: i16 = 5;
+const FIRST_FLEXIBLE_VERSION: i16 = 2;
+
+pub const NO_PRODUCER_ID: i64 = -1;
+pub const NO_PRODUCER_EPOCH: i16 = -1;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InitProducerIdRequest {
+    pub header: RequestHeader,
+    /// `None` for idempotent producers that do not use transactions.
+    pub transactional_id: Option<String>,
+    pub transaction_timeout_ms: i32,
+    /// The producer's current id, or [`NO_PRODUCER_ID`] before v3 and on first init.
+    pub producer_id: i64,
+    pub producer_epoch: i16,
+}
+
+impl InitProducerIdRequest {
+    pub fn supports(api_version: i16) -> bool {
+        (MIN_VERSION..=MAX_VERSION).contains(&api_version)
+    }
+
+    pub fn decode(header: RequestHeader, cursor: &mut Cursor<&[u8]>) -> io::Result<Self> {
+        let version = header.request_api_version;
+        let flexible = version >= FIRST_FLEXIBLE_VERSION;
+        wire::skip_tagged_fields(cursor, flexible)?;
+
+        let transactional_id = wire::read_nullable_string(cursor, flexible)?;
+        let transaction_timeout_ms = primitives::read_i32(cursor)?;
+        let (producer_id, producer_epoch) = if version >= 3 {
+            (primitives::read_i64(cursor)?, primitives::read_i16(cursor)?)
+        } else {
+            (NO_PRODUCER_ID, NO_PRODUCER_EPOCH)
+        };
+        wire::skip_tagged_fields(cursor, flexible)?;
+
+        Ok(Self {
+            header,
+            transactional_id,
+            transaction_timeout_ms,
+            producer_id,
+            producer_epoch,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InitProducerIdResponse {
+    pub header: ResponseHeader,
+    pub api_version: i16,
+    pub throttle_time_ms: i32,
+    pub error_code: i16,
+    pub producer_id: i64,
+    pub producer_epoch: i16,
+}
+
+impl InitProducerIdResponse {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let flexible = self.api_version >= FIRST_FLEXIBLE_VERSION;
+        let mut body = Vec::new();
+        body.extend_from_slice(&self.throttle_time_ms.to_be_bytes());
+        body.extend_from_slice(&self.error_code.to_be_bytes());
+        body.extend_from_slice(&self.producer_id.to_be_bytes());
+        body.extend_from_slice(&self.producer_epoch.to_be_bytes());
+        wire::write_empty_tagged_fields(&mut body, flexible);
+
+        self.header.frame(flexible, &body)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_current_producer_from_version_three() {
+        let header = RequestHeader {
+            request_api_key: INIT_PRODUCER_ID_KEY,
+            request_api_version: 4,
+            correlation_id: 3,
+            client_id: Some("producer-1".to_string()),
+        };
+        let mut body = vec![0];
+        primitives::write_compact_nullable_string(&mut body, None);
+        body.extend_from_slice(&60_000_i32.to_be_bytes());
+        body.extend_from_slice(&1_000_i64.to_be_bytes());
+        body.extend_from_slice(&2_i16.to_be_bytes());
+        body.push(0);
+
+        let request = InitProducerIdRequest::decode(header, &mut Cursor::new(body.as_slice()))
+            .expect("decode");
+
+        assert_eq!(request.transactional_id, None);
+        assert_eq!(request.transaction_timeout_ms, 60_000);
+        assert_eq!((request.producer_id, request.producer_epoch), (1_000, 2));
+    }
+}
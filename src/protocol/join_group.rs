@@ -0,0 +1,207 @@
+use super::header::{RequestHeader, ResponseHeader};
+use super::wire;
+use crate::codec::primitives;
+use std::io::{self, Cursor};
+
+pub const JOIN_GROUP_KEY: i16 = 11;
+pub const MIN_VERSION: i16 = 0;
+pub const MAX_VERSION: i16 = 9;
+const FIRST_FLEXIBLE_VERSION: i16 = 6;
+/// First version whose clients retry with the member id the broker assigns.
+pub const FIRST_MEMBER_ID_REQUIRED_VERSION: i16 = 4;
+
+/// Member id a client sends before the coordinator has assigned it one.
+pub const UNKNOWN_MEMBER_ID: &str = "";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JoinGroupRequest {
+    pub header: RequestHeader,
+    pub group_id: String,
+    pub session_timeout_ms: i32,
+    pub rebalance_timeout_ms: i32,
+    pub member_id: String,
+    pub group_instance_id: Option<String>,
+    pub protocol_type: String,
+    pub protocols: Vec<JoinGroupRequestProtocol>,
+    pub reason: Option<String>,
+}
+
+/// An assignment protocol the member supports, in order of preference.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JoinGroupRequestProtocol {
+    pub name: String,
+    pub metadata: Vec<u8>,
+}
+
+impl JoinGroupRequest {
+    pub fn supports(api_version: i16) -> bool {
+        (MIN_VERSION..=MAX_VERSION).contains(&api_version)
+    }
+
+    pub fn decode(header: RequestHeader, cursor: &mut Cursor<&[u8]>) -> io::Result<Self> {
+        let version = header.request_api_version;
+        let flexible = version >= FIRST_FLEXIBLE_VERSION;
+        wire::skip_tagged_fields(cursor, flexible)?;
+
+        let group_id = wire::read_string(cursor, flexible)?;
+        let session_timeout_ms = primitives::read_i32(cursor)?;
+        // Version 0 rebalances within the session timeout.
+        let rebalance_timeout_ms = if version >= 1 {
+            primitives::read_i32(cursor)?
+        } else {
+            session_timeout_ms
+        };
+        let member_id = wire::read_string(cursor, flexible)?;
+        let group_instance_id = if version >= 5 {
+            wire::read_nullable_string(cursor, flexible)?
+        } else {
+            None
+        };
+        let protocol_type = wire::read_string(cursor, flexible)?;
+        let protocols = wire::read_array(cursor, flexible, |cursor| {
+            let name = wire::read_string(cursor, flexible)?;
+            let metadata = wire::read_bytes(cursor, flexible)?;
+            wire::skip_tagged_fields(cursor, flexible)?;
+            Ok(JoinGroupRequestProtocol { name, metadata })
+        })?
+        .unwrap_or_default();
+        let reason = if version >= 8 {
+            wire::read_nullable_string(cursor, flexible)?
+        } else {
+            None
+        };
+        wire::skip_tagged_fields(cursor, flexible)?;
+
+        Ok(Self {
+            header,
+            group_id,
+            session_timeout_ms,
+            rebalance_timeout_ms,
+            member_id,
+            group_instance_id,
+            protocol_type,
+            protocols,
+            reason,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JoinGroupResponse {
+    pub header: ResponseHeader,
+    pub api_version: i16,
+    pub throttle_time_ms: i32,
+    pub error_code: i16,
+    pub generation_id: i32,
+    pub protocol_type: Option<String>,
+    pub protocol_name: Option<String>,
+    pub leader: String,
+    pub skip_assignment: bool,
+    pub member_id: String,
+    /// Every member and its metadata, sent only to the leader.
+    pub members: Vec<JoinGroupResponseMember>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JoinGroupResponseMember {
+    pub member_id: String,
+    pub group_instance_id: Option<String>,
+    pub metadata: Vec<u8>,
+}
+
+impl JoinGroupResponse {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let version = self.api_version;
+        let flexible = version >= FIRST_FLEXIBLE_VERSION;
+        let mut body = Vec::new();
+        if version >= 2 {
+            body.extend_from_slice(&self.throttle_time_ms.to_be_bytes());
+        }
+        body.extend_from_slice(&self.error_code.to_be_bytes());
+        body.extend_from_slice(&self.generation_id.to_be_bytes());
+        if version >= 7 {
+            wire::write_nullable_string(&mut body, self.protocol_type.as_deref(), flexible);
+            wire::write_nullable_string(&mut body, self.protocol_name.as_deref(), flexible);
+        } else {
+            let protocol_name = self.protocol_name.as_deref().unwrap_or_default();
+            wire::write_string(&mut body, protocol_name, flexible);
+        }
+        wire::write_string(&mut body, &self.leader, flexible);
+        if version >= 9 {
+            primitives::write_bool(&mut body, self.skip_assignment);
+        }
+        wire::write_string(&mut body, &self.member_id, flexible);
+        wire::write_array(&mut body, &self.members, flexible, |buffer, member| {
+            wire::write_string(buffer, &member.member_id, flexible);
+            if version >= 5 {
+                wire::write_nullable_string(buffer, member.group_instance_id.as_deref(), flexible);
+            }
+            wire::write_bytes(buffer, &member.metadata, flexible);
+            wire::write_empty_tagged_fields(buffer, flexible);
+        });
+        wire::write_empty_tagged_fields(&mut body, flexible);
+
+        self.header.frame(flexible, &body)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header(api_version: i16) -> RequestHeader {
+        RequestHeader {
+            request_api_key: JOIN_GROUP_KEY,
+            request_api_version: api_version,
+            correlation_id: 6,
+            client_id: Some("consumer-1".to_string()),
+        }
+    }
+
+    #[test]
+    fn decodes_flexible_request_with_protocols() {
+        let mut body = vec![0];
+        primitives::write_compact_string(&mut body, "payments");
+        body.extend_from_slice(&10_000_i32.to_be_bytes());
+        body.extend_from_slice(&60_000_i32.to_be_bytes());
+        primitives::write_compact_string(&mut body, "");
+        primitives::write_compact_nullable_string(&mut body, Some("pod-0"));
+        primitives::write_compact_string(&mut body, "consumer");
+        primitives::write_compact_array_len(&mut body, Some(1));
+        primitives::write_compact_string(&mut body, "range");
+        wire::write_bytes(&mut body, &[1, 2, 3], true);
+        body.push(0);
+        primitives::write_compact_nullable_string(&mut body, None);
+        body.push(0);
+
+        let request =
+            JoinGroupRequest::decode(header(8), &mut Cursor::new(body.as_slice())).expect("decode");
+
+        assert_eq!(request.group_id, "payments");
+        assert_eq!(request.rebalance_timeout_ms, 60_000);
+        assert_eq!(request.group_instance_id.as_deref(), Some("pod-0"));
+        assert_eq!(
+            request.protocols,
+            [JoinGroupRequestProtocol {
+                name: "range".to_string(),
+                metadata: vec![1, 2, 3],
+            }]
+        );
+    }
+
+    #[test]
+    fn version_zero_request_rebalances_within_the_session_timeout() {
+        let mut body = Vec::new();
+        wire::write_string(&mut body, "payments", false);
+        body.extend_from_slice(&10_000_i32.to_be_bytes());
+        wire::write_string(&mut body, "", false);
+        wire::write_string(&mut body, "consumer", false);
+        body.extend_from_slice(&0_i32.to_be_bytes());
+
+        let request =
+            JoinGroupRequest::decode(header(0), &mut Cursor::new(body.as_slice())).expect("decode");
+
+        assert_eq!(request.rebalance_timeout_ms, 10_000);
+        assert!(request.protocols.is_empty());
+    }
+}
@@ -0,0 +1,150 @@
+use super::header::{RequestHeader, ResponseHeader};
+use super::wire;
+use std::io::{self, Cursor};
+
+pub const LEAVE_GROUP_KEY: i16 = 13;
+pub const MIN_VERSION: i16 = 0;
+pub const MAX_VERSION: i16 = 5;
+const FIRST_FLEXIBLE_VERSION: i16 = 4;
+/// First version that removes a batch of members.
+pub const FIRST_BATCHED_VERSION: i16 = 3;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LeaveGroupRequest {
+    pub header: RequestHeader,
+    pub group_id: String,
+    /// Members leaving; a single member before version 3.
+    pub members: Vec<LeavingMember>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LeavingMember {
+    pub member_id: String,
+    pub group_instance_id: Option<String>,
+    pub reason: Option<String>,
+}
+
+impl LeaveGroupRequest {
+    pub fn supports(api_version: i16) -> bool {
+        (MIN_VERSION..=MAX_VERSION).contains(&api_version)
+    }
+
+    pub fn decode(header: RequestHeader, cursor: &mut Cursor<&[u8]>) -> io::Result<Self> {
+        let version = header.request_api_version;
+        let flexible = version >= FIRST_FLEXIBLE_VERSION;
+        wire::skip_tagged_fields(cursor, flexible)?;
+
+        let group_id = wire::read_string(cursor, flexible)?;
+        let members = if version >= FIRST_BATCHED_VERSION {
+            wire::read_array(cursor, flexible, |cursor| {
+                let member_id = wire::read_string(cursor, flexible)?;
+                let group_instance_id = wire::read_nullable_string(cursor, flexible)?;
+                let reason = if version >= 5 {
+                    wire::read_nullable_string(cursor, flexible)?
+                } else {
+                    None
+                };
+                wire::skip_tagged_fields(cursor, flexible)?;
+                Ok(LeavingMember {
+                    member_id,
+                    group_instance_id,
+                    reason,
+                })
+            })?
+            .unwrap_or_default()
+        } else {
+            vec![LeavingMember {
+                member_id: wire::read_string(cursor, flexible)?,
+                group_instance_id: None,
+                reason: None,
+            }]
+        };
+        wire::skip_tagged_fields(cursor, flexible)?;
+
+        Ok(Self {
+            header,
+            group_id,
+            members,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LeaveGroupResponse {
+    pub header: ResponseHeader,
+    pub api_version: i16,
+    pub throttle_time_ms: i32,
+    pub error_code: i16,
+    /// Per-member results, sent from version 3.
+    pub members: Vec<LeaveGroupResponseMember>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LeaveGroupResponseMember {
+    pub member_id: String,
+    pub group_instance_id: Option<String>,
+    pub error_code: i16,
+}
+
+impl LeaveGroupResponse {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let version = self.api_version;
+        let flexible = version >= FIRST_FLEXIBLE_VERSION;
+        let mut body = Vec::new();
+        if version >= 1 {
+            body.extend_from_slice(&self.throttle_time_ms.to_be_bytes());
+        }
+        body.extend_from_slice(&self.error_code.to_be_bytes());
+        if version >= FIRST_BATCHED_VERSION {
+            wire::write_array(&mut body, &self.members, flexible, |buffer, member| {
+                wire::write_string(buffer, &member.member_id, flexible);
+                wire::write_nullable_string(buffer, member.group_instance_id.as_deref(), flexible);
+                buffer.extend_from_slice(&member.error_code.to_be_bytes());
+                wire::write_empty_tagged_fields(buffer, flexible);
+            });
+        }
+        wire::write_empty_tagged_fields(&mut body, flexible);
+
+        self.header.frame(flexible, &body)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codec::primitives;
+
+    fn header(api_version: i16) -> RequestHeader {
+        RequestHeader {
+            request_api_key: LEAVE_GROUP_KEY,
+            request_api_version: api_version,
+            correlation_id: 3,
+            client_id: None,
+        }
+    }
+
+    #[test]
+    fn decodes_single_and_batched_members() {
+        let mut body = Vec::new();
+        wire::write_string(&mut body, "payments", false);
+        wire::write_string(&mut body, "member-1", false);
+        let request = LeaveGroupRequest::decode(header(0), &mut Cursor::new(body.as_slice()))
+            .expect("decode v0");
+        assert_eq!(request.members[0].member_id, "member-1");
+
+        let mut body = vec![0];
+        primitives::write_compact_string(&mut body, "payments");
+        primitives::write_compact_array_len(&mut body, Some(1));
+        primitives::write_compact_string(&mut body, "");
+        primitives::write_compact_nullable_string(&mut body, Some("pod-2"));
+        primitives::write_compact_nullable_string(&mut body, Some("scaling down"));
+        body.extend_from_slice(&[0, 0]);
+        let request = LeaveGroupRequest::decode(header(5), &mut Cursor::new(body.as_slice()))
+            .expect("decode v5");
+        assert_eq!(
+            request.members[0].group_instance_id.as_deref(),
+            Some("pod-2")
+        );
+        assert_eq!(request.members[0].reason.as_deref(), Some("scaling down"));
+    }
+}
@@ -0,0 +1,132 @@
+use super::header::{RequestHeader, ResponseHeader};
+use super::wire;
+use std::io::{self, Cursor};
+
+pub const LIST_GROUPS_KEY: i16 = 16;
+pub const MIN_VERSION: i16 = 0;
+pub const MAX_VERSION: i16 = 5;
+const FIRST_FLEXIBLE_VERSION: i16 = 3;
+
+/// Type of groups using the JoinGroup/SyncGroup protocol.
+pub const CLASSIC_GROUP_TYPE: &str = "classic";
+/// Type of groups using the ConsumerGroupHeartbeat protocol.
+pub const CONSUMER_GROUP_TYPE: &str = "consumer";
+/// Type of groups using the ShareGroupHeartbeat protocol.
+pub const SHARE_GROUP_TYPE: &str = "share";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ListGroupsRequest {
+    pub header: RequestHeader,
+    /// States to list, matched case-insensitively; empty for every state.
+    pub states_filter: Vec<String>,
+    /// Group types to list; empty for every type.
+    pub types_filter: Vec<String>,
+}
+
+impl ListGroupsRequest {
+    pub fn supports(api_version: i16) -> bool {
+        (MIN_VERSION..=MAX_VERSION).contains(&api_version)
+    }
+
+    pub fn decode(header: RequestHeader, cursor: &mut Cursor<&[u8]>) -> io::Result<Self> {
+        let version = header.request_api_version;
+        let flexible = version >= FIRST_FLEXIBLE_VERSION;
+        wire::skip_tagged_fields(cursor, flexible)?;
+
+        let read_filter = |cursor: &mut Cursor<&[u8]>| {
+            wire::read_array(cursor, flexible, |cursor| {
+                wire::read_string(cursor, flexible)
+            })
+            .map(Option::unwrap_or_default)
+        };
+        let states_filter = if version >= 4 {
+            read_filter(cursor)?
+        } else {
+            Vec::new()
+        };
+        let types_filter = if version >= 5 {
+            read_filter(cursor)?
+        } else {
+            Vec::new()
+        };
+        wire::skip_tagged_fields(cursor, flexible)?;
+
+        Ok(Self {
+            header,
+            states_filter,
+            types_filter,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ListGroupsResponse {
+    pub header: ResponseHeader,
+    pub api_version: i16,
+    pub throttle_time_ms: i32,
+    pub error_code: i16,
+    pub groups: Vec<ListedGroup>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ListedGroup {
+    pub group_id: String,
+    pub protocol_type: String,
+    pub group_state: String,
+    pub group_type: String,
+}
+
+impl ListGroupsResponse {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let version = self.api_version;
+        let flexible = version >= FIRST_FLEXIBLE_VERSION;
+        let mut body = Vec::new();
+        if version >= 1 {
+            body.extend_from_slice(&self.throttle_time_ms.to_be_bytes());
+        }
+        body.extend_from_slice(&self.error_code.to_be_bytes());
+        wire::write_array(&mut body, &self.groups, flexible, |buffer, group| {
+            wire::write_string(buffer, &group.group_id, flexible);
+            wire::write_string(buffer, &group.protocol_type, flexible);
+            if version >= 4 {
+                wire::write_string(buffer, &group.group_state, flexible);
+            }
+            if version >= 5 {
+                wire::write_string(buffer, &group.group_type, flexible);
+            }
+            wire::write_empty_tagged_fields(buffer, flexible);
+        });
+        wire::write_empty_tagged_fields(&mut body, flexible);
+
+        self.header.frame(flexible, &body)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codec::primitives;
+
+    #[test]
+    fn decodes_state_and_type_filters() {
+        let header = RequestHeader {
+            request_api_key: LIST_GROUPS_KEY,
+            request_api_version: 5,
+            correlation_id: 1,
+            client_id: None,
+        };
+        let mut body = vec![0];
+        primitives::write_compact_array_len(&mut body, Some(2));
+        primitives::write_compact_string(&mut body, "Stable");
+        primitives::write_compact_string(&mut body, "empty");
+        primitives::write_compact_array_len(&mut body, Some(1));
+        primitives::write_compact_string(&mut body, "classic");
+        body.push(0);
+
+        let request =
+            ListGroupsRequest::decode(header, &mut Cursor::new(body.as_slice())).expect("decode");
+
+        assert_eq!(request.states_filter, ["Stable", "empty"]);
+        assert_eq!(request.types_filter, ["classic"]);
+    }
+}
@@ -0,0 +1,225 @@
+pub use super::fetch::{READ_COMMITTED, READ_UNCOMMITTED, UNKNOWN_LEADER_EPOCH, UNKNOWN_OFFSET};
+use super::header::{RequestHeader, ResponseHeader};
+use super::wire;
+use crate::codec::primitives;
+use std::io::{self, Cursor};
+
+pub const LIST_OFFSETS_KEY: i16 = 2;
+/// Version 0 returns a list of segment offsets per partition and is not served.
+pub const MIN_VERSION: i16 = 1;
+pub const MAX_VERSION: i16 = 8;
+const FIRST_FLEXIBLE_VERSION: i16 = 6;
+
+/// Asks for the offset the next appended record will receive.
+pub const LATEST_TIMESTAMP: i64 = -1;
+/// Asks for the first offset still in the log.
+pub const EARLIEST_TIMESTAMP: i64 = -2;
+/// Asks for the record with the largest timestamp (version 7+).
+pub const MAX_TIMESTAMP: i64 = -3;
+/// Asks for the first offset on local storage (version 8+).
+pub const EARLIEST_LOCAL_TIMESTAMP: i64 = -4;
+
+/// Timestamp reported when none was found, alongside the unknown offset
+/// and leader epoch.
+pub const UNKNOWN_TIMESTAMP: i64 = -1;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ListOffsetsRequest {
+    pub header: RequestHeader,
+    pub replica_id: i32,
+    pub isolation_level: i8,
+    pub topics: Vec<ListOffsetsRequestTopic>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ListOffsetsRequestTopic {
+    pub name: String,
+    pub partitions: Vec<ListOffsetsRequestPartition>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ListOffsetsRequestPartition {
+    pub partition_index: i32,
+    pub current_leader_epoch: i32,
+    /// A timestamp in milliseconds, or one of the special timestamps above.
+    pub timestamp: i64,
+}
+
+impl ListOffsetsRequest {
+    pub fn supports(api_version: i16) -> bool {
+        (MIN_VERSION..=MAX_VERSION).contains(&api_version)
+    }
+
+    pub fn decode(header: RequestHeader, cursor: &mut Cursor<&[u8]>) -> io::Result<Self> {
+        let version = header.request_api_version;
+        let flexible = version >= FIRST_FLEXIBLE_VERSION;
+        wire::skip_tagged_fields(cursor, flexible)?;
+
+        let replica_id = primitives::read_i32(cursor)?;
+        let isolation_level = if version >= 2 {
+            primitives::read_i8(cursor)?
+        } else {
+            READ_UNCOMMITTED
+        };
+        let topics = wire::read_array(cursor, flexible, |cursor| {
+            let name = wire::read_string(cursor, flexible)?;
+            let partitions = wire::read_array(cursor, flexible, |cursor| {
+                let partition_index = primitives::read_i32(cursor)?;
+                let current_leader_epoch = if version >= 4 {
+                    primitives::read_i32(cursor)?
+                } else {
+                    UNKNOWN_LEADER_EPOCH
+                };
+                let timestamp = primitives::read_i64(cursor)?;
+                wire::skip_tagged_fields(cursor, flexible)?;
+                Ok(ListOffsetsRequestPartition {
+                    partition_index,
+                    current_leader_epoch,
+                    timestamp,
+                })
+            })?
+            .unwrap_or_default();
+            wire::skip_tagged_fields(cursor, flexible)?;
+            Ok(ListOffsetsRequestTopic { name, partitions })
+        })?
+        .unwrap_or_default();
+        wire::skip_tagged_fields(cursor, flexible)?;
+
+        Ok(Self {
+            header,
+            replica_id,
+            isolation_level,
+            topics,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ListOffsetsResponse {
+    pub header: ResponseHeader,
+    pub api_version: i16,
+    pub throttle_time_ms: i32,
+    pub topics: Vec<ListOffsetsResponseTopic>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ListOffsetsResponseTopic {
+    pub name: String,
+    pub partitions: Vec<ListOffsetsResponsePartition>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ListOffsetsResponsePartition {
+    pub partition_index: i32,
+    pub error_code: i16,
+    pub timestamp: i64,
+    pub offset: i64,
+    pub leader_epoch: i32,
+}
+
+impl ListOffsetsResponse {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let version = self.api_version;
+        let flexible = version >= FIRST_FLEXIBLE_VERSION;
+        let mut body = Vec::new();
+        if version >= 2 {
+            body.extend_from_slice(&self.throttle_time_ms.to_be_bytes());
+        }
+        wire::write_array(&mut body, &self.topics, flexible, |buffer, topic| {
+            wire::write_string(buffer, &topic.name, flexible);
+            wire::write_array(buffer, &topic.partitions, flexible, |buffer, partition| {
+                buffer.extend_from_slice(&partition.partition_index.to_be_bytes());
+                buffer.extend_from_slice(&partition.error_code.to_be_bytes());
+                buffer.extend_from_slice(&partition.timestamp.to_be_bytes());
+                buffer.extend_from_slice(&partition.offset.to_be_bytes());
+                if version >= 4 {
+                    buffer.extend_from_slice(&partition.leader_epoch.to_be_bytes());
+                }
+                wire::write_empty_tagged_fields(buffer, flexible);
+            });
+            wire::write_empty_tagged_fields(buffer, flexible);
+        });
+        wire::write_empty_tagged_fields(&mut body, flexible);
+
+        self.header.frame(flexible, &body)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::convert::TryInto;
+
+    fn header(api_version: i16) -> RequestHeader {
+        RequestHeader {
+            request_api_key: LIST_OFFSETS_KEY,
+            request_api_version: api_version,
+            correlation_id: 9,
+            client_id: None,
+        }
+    }
+
+    #[test]
+    fn decodes_flexible_request_with_isolation_level() {
+        let mut body = vec![0];
+        body.extend_from_slice(&(-1_i32).to_be_bytes());
+        body.push(READ_COMMITTED as u8);
+        primitives::write_compact_array_len(&mut body, Some(1));
+        primitives::write_compact_string(&mut body, "orders");
+        primitives::write_compact_array_len(&mut body, Some(1));
+        body.extend_from_slice(&2_i32.to_be_bytes());
+        body.extend_from_slice(&5_i32.to_be_bytes());
+        body.extend_from_slice(&MAX_TIMESTAMP.to_be_bytes());
+        body.extend_from_slice(&[0, 0, 0]);
+
+        let request = ListOffsetsRequest::decode(header(7), &mut Cursor::new(body.as_slice()))
+            .expect("decode");
+
+        assert_eq!(request.replica_id, -1);
+        assert_eq!(request.isolation_level, READ_COMMITTED);
+        assert_eq!(request.topics[0].name, "orders");
+        assert_eq!(
+            request.topics[0].partitions,
+            [ListOffsetsRequestPartition {
+                partition_index: 2,
+                current_leader_epoch: 5,
+                timestamp: MAX_TIMESTAMP,
+            }]
+        );
+    }
+
+    #[test]
+    fn version_one_response_has_no_throttle_time_or_leader_epoch() {
+        let response = ListOffsetsResponse {
+            header: ResponseHeader { correlation_id: 9 },
+            api_version: 1,
+            throttle_time_ms: 0,
+            topics: vec![ListOffsetsResponseTopic {
+                name: "t".to_string(),
+                partitions: vec![ListOffsetsResponsePartition {
+                    partition_index: 0,
+                    error_code: 0,
+                    timestamp: UNKNOWN_TIMESTAMP,
+                    offset: 42,
+                    leader_epoch: 3,
+                }],
+            }],
+        };
+
+        let bytes = response.to_bytes();
+
+        let length = u32::from_be_bytes(bytes[0..4].try_into().unwrap()) as usize;
+        assert_eq!(length, bytes.len() - 4);
+        let mut expected = Vec::new();
+        expected.extend_from_slice(&9_i32.to_be_bytes());
+        expected.extend_from_slice(&1_i32.to_be_bytes());
+        expected.extend_from_slice(&1_i16.to_be_bytes());
+        expected.push(b't');
+        expected.extend_from_slice(&1_i32.to_be_bytes());
+        expected.extend_from_slice(&0_i32.to_be_bytes());
+        expected.extend_from_slice(&0_i16.to_be_bytes());
+        expected.extend_from_slice(&UNKNOWN_TIMESTAMP.to_be_bytes());
+        expected.extend_from_slice(&42_i64.to_be_bytes());
+        assert_eq!(&bytes[4..], expected.as_slice());
+    }
+}
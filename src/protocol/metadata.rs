@@ -0,0 +1,265 @@
+use super::header::{RequestHeader, ResponseHeader};
+use super::wire;
+use crate::codec::primitives;
+use crate::metadata::Uuid;
+use std::io::{self, Cursor};
+
+pub const METADATA_KEY: i16 = 3;
+pub const MIN_VERSION: i16 = 0;
+pub const MAX_VERSION: i16 = 12;
+const FIRST_FLEXIBLE_VERSION: i16 = 9;
+
+/// Marks `topic_authorized_operations` as not computed.
+pub const AUTHORIZED_OPERATIONS_OMITTED: i32 = i32::MIN;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MetadataRequest {
+    pub header: RequestHeader,
+    /// Topics to describe, or `None` for every topic.
+    pub topics: Option<Vec<MetadataRequestTopic>>,
+    pub allow_auto_topic_creation: bool,
+    pub include_topic_authorized_operations: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MetadataRequestTopic {
+    pub topic_id: Uuid,
+    pub name: Option<String>,
+}
+
+impl MetadataRequest {
+    pub fn supports(api_version: i16) -> bool {
+        (MIN_VERSION..=MAX_VERSION).contains(&api_version)
+    }
+
+    pub fn decode(header: RequestHeader, cursor: &mut Cursor<&[u8]>) -> io::Result<Self> {
+        let version = header.request_api_version;
+        let flexible = version >= FIRST_FLEXIBLE_VERSION;
+        wire::skip_tagged_fields(cursor, flexible)?;
+
+        let mut topics = wire::read_array(cursor, flexible, |cursor| {
+            let topic_id = if version >= 10 {
+                Uuid(primitives::read_uuid(cursor)?)
+            } else {
+                Uuid::ZERO
+            };
+            let name = if version >= 10 {
+                wire::read_nullable_string(cursor, flexible)?
+            } else {
+                Some(wire::read_string(cursor, flexible)?)
+            };
+            wire::skip_tagged_fields(cursor, flexible)?;
+            Ok(MetadataRequestTopic { topic_id, name })
+        })?;
+        // Version 0 has no null array and asks for every topic with an empty one.
+        if version == 0 && topics.as_ref().is_some_and(Vec::is_empty) {
+            topics = None;
+        }
+
+        let allow_auto_topic_creation = version < 4 || primitives::read_bool(cursor)?;
+        if (8..=10).contains(&version) {
+            let _include_cluster_authorized_operations = primitives::read_bool(cursor)?;
+        }
+        let include_topic_authorized_operations = version >= 8 && primitives::read_bool(cursor)?;
+        wire::skip_tagged_fields(cursor, flexible)?;
+
+        Ok(Self {
+            header,
+            topics,
+            allow_auto_topic_creation,
+            include_topic_authorized_operations,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MetadataResponse {
+    pub header: ResponseHeader,
+    pub api_version: i16,
+    pub throttle_time_ms: i32,
+    pub brokers: Vec<MetadataBroker>,
+    pub cluster_id: Option<String>,
+    pub controller_id: i32,
+    pub topics: Vec<MetadataResponseTopic>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MetadataBroker {
+    pub node_id: i32,
+    pub host: String,
+    pub port: i32,
+    pub rack: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MetadataResponseTopic {
+    pub error_code: i16,
+    pub name: Option<String>,
+    pub topic_id: Uuid,
+    pub is_internal: bool,
+    pub partitions: Vec<MetadataResponsePartition>,
+    pub topic_authorized_operations: i32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MetadataResponsePartition {
+    pub error_code: i16,
+    pub partition_index: i32,
+    pub leader_id: i32,
+    pub leader_epoch: i32,
+    pub replica_nodes: Vec<i32>,
+    pub isr_nodes: Vec<i32>,
+    pub offline_replicas: Vec<i32>,
+}
+
+impl MetadataResponse {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let version = self.api_version;
+        let flexible = version >= FIRST_FLEXIBLE_VERSION;
+        let mut body = Vec::new();
+
+        if version >= 3 {
+            body.extend_from_slice(&self.throttle_time_ms.to_be_bytes());
+        }
+        wire::write_array(&mut body, &self.brokers, flexible, |buffer, broker| {
+            buffer.extend_from_slice(&broker.node_id.to_be_bytes());
+            wire::write_string(buffer, &broker.host, flexible);
+            buffer.extend_from_slice(&broker.port.to_be_bytes());
+            if version >= 1 {
+                wire::write_nullable_string(buffer, broker.rack.as_deref(), flexible);
+            }
+            wire::write_empty_tagged_fields(buffer, flexible);
+        });
+        if version >= 2 {
+            wire::write_nullable_string(&mut body, self.cluster_id.as_deref(), flexible);
+        }
+        if version >= 1 {
+            body.extend_from_slice(&self.controller_id.to_be_bytes());
+        }
+        wire::write_array(&mut body, &self.topics, flexible, |buffer, topic| {
+            buffer.extend_from_slice(&topic.error_code.to_be_bytes());
+            if version >= 12 {
+                wire::write_nullable_string(buffer, topic.name.as_deref(), flexible);
+            } else {
+                wire::write_string(buffer, topic.name.as_deref().unwrap_or_default(), flexible);
+            }
+            if version >= 10 {
+                buffer.extend_from_slice(topic.topic_id.as_bytes());
+            }
+            if version >= 1 {
+                primitives::write_bool(buffer, topic.is_internal);
+            }
+            wire::write_array(buffer, &topic.partitions, flexible, |buffer, partition| {
+                buffer.extend_from_slice(&partition.error_code.to_be_bytes());
+                buffer.extend_from_slice(&partition.partition_index.to_be_bytes());
+                buffer.extend_from_slice(&partition.leader_id.to_be_bytes());
+                if version >= 7 {
+                    buffer.extend_from_slice(&partition.leader_epoch.to_be_bytes());
+                }
+                write_node_ids(buffer, &partition.replica_nodes, flexible);
+                write_node_ids(buffer, &partition.isr_nodes, flexible);
+                if version >= 5 {
+                    write_node_ids(buffer, &partition.offline_replicas, flexible);
+                }
+                wire::write_empty_tagged_fields(buffer, flexible);
+            });
+            if version >= 8 {
+                buffer.extend_from_slice(&topic.topic_authorized_operations.to_be_bytes());
+            }
+            wire::write_empty_tagged_fields(buffer, flexible);
+        });
+        if (8..=10).contains(&version) {
+            body.extend_from_slice(&AUTHORIZED_OPERATIONS_OMITTED.to_be_bytes());
+        }
+        wire::write_empty_tagged_fields(&mut body, flexible);
+
+        self.header.frame(flexible, &body)
+    }
+}
+
+fn write_node_ids(buffer: &mut Vec<u8>, ids: &[i32], flexible: bool) {
+    wire::write_array(buffer, ids, flexible, |buffer, id| {
+        buffer.extend_from_slice(&id.to_be_bytes())
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::convert::TryInto;
+
+    fn header(api_version: i16) -> RequestHeader {
+        RequestHeader {
+            request_api_key: METADATA_KEY,
+            request_api_version: api_version,
+            correlation_id: 5,
+            client_id: None,
+        }
+    }
+
+    #[test]
+    fn decodes_flexible_request_with_topic_ids() {
+        let mut body = vec![0];
+        primitives::write_compact_array_len(&mut body, Some(2));
+        body.extend_from_slice(&[0; 16]);
+        primitives::write_compact_string(&mut body, "orders");
+        body.push(0);
+        body.extend_from_slice(&[7; 16]);
+        primitives::write_compact_nullable_string(&mut body, None);
+        body.push(0);
+        body.extend_from_slice(&[1, 0, 0]);
+
+        let request =
+            MetadataRequest::decode(header(12), &mut Cursor::new(body.as_slice())).expect("decode");
+
+        let topics = request.topics.expect("explicit topics");
+        assert_eq!(topics[0].name.as_deref(), Some("orders"));
+        assert_eq!(topics[1].topic_id, Uuid([7; 16]));
+        assert_eq!(topics[1].name, None);
+        assert!(request.allow_auto_topic_creation);
+    }
+
+    #[test]
+    fn empty_version_zero_request_asks_for_all_topics() {
+        let body = 0_i32.to_be_bytes();
+
+        let request =
+            MetadataRequest::decode(header(0), &mut Cursor::new(body.as_slice())).expect("decode");
+
+        assert_eq!(request.topics, None);
+    }
+
+    #[test]
+    fn encodes_cluster_id_and_brokers() {
+        let response = MetadataResponse {
+            header: ResponseHeader { correlation_id: 5 },
+            api_version: 1,
+            throttle_time_ms: 0,
+            brokers: vec![MetadataBroker {
+                node_id: 1,
+                host: "localhost".to_string(),
+                port: 9092,
+                rack: None,
+            }],
+            cluster_id: Some("ignored before v2".to_string()),
+            controller_id: 1,
+            topics: Vec::new(),
+        };
+
+        let bytes = response.to_bytes();
+
+        let length = u32::from_be_bytes(bytes[0..4].try_into().unwrap()) as usize;
+        assert_eq!(length, bytes.len() - 4);
+        let mut expected = Vec::new();
+        expected.extend_from_slice(&5_i32.to_be_bytes());
+        expected.extend_from_slice(&1_i32.to_be_bytes());
+        expected.extend_from_slice(&1_i32.to_be_bytes());
+        expected.extend_from_slice(&9_i16.to_be_bytes());
+        expected.extend_from_slice(b"localhost");
+        expected.extend_from_slice(&9092_i32.to_be_bytes());
+        expected.extend_from_slice(&(-1_i16).to_be_bytes());
+        expected.extend_from_slice(&1_i32.to_be_bytes());
+        expected.extend_from_slice(&0_i32.to_be_bytes());
+        assert_eq!(&bytes[4..], expected.as_slice());
+    }
+}
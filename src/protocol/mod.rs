@@ -52,9 +52,75 @@ pub mod header {
             buffer.extend_from_slice(&self.correlation_id.to_be_bytes());
             buffer
         }
+
+        /// Prefixes `body` with this header and the total length. Flexible
+        /// responses use header version 1, which ends in a tagged field section.
+        pub fn frame(self, flexible: bool, body: &[u8]) -> Vec<u8> {
+            let mut payload = self.to_bytes();
+            if flexible {
+                payload.push(0);
+            }
+            payload.extend_from_slice(body);
+
+            let mut buffer = Vec::with_capacity(payload.len() + 4);
+            buffer.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+            buffer.extend_from_slice(&payload);
+            buffer
+        }
     }
 }
 
+/// Error codes shared by the APIs served here.
+pub mod error {
+    pub const UNKNOWN_SERVER_ERROR: i16 = -1;
+    pub const NONE: i16 = 0;
+    pub const OFFSET_OUT_OF_RANGE: i16 = 1;
+    pub const CORRUPT_MESSAGE: i16 = 2;
+    pub const UNKNOWN_TOPIC_OR_PARTITION: i16 = 3;
+    pub const OFFSET_METADATA_TOO_LARGE: i16 = 12;
+    pub const COORDINATOR_NOT_AVAILABLE: i16 = 15;
+    pub const NOT_COORDINATOR: i16 = 16;
+    pub const INVALID_TOPIC_EXCEPTION: i16 = 17;
+    pub const INVALID_REQUIRED_ACKS: i16 = 21;
+    pub const ILLEGAL_GENERATION: i16 = 22;
+    pub const INCONSISTENT_GROUP_PROTOCOL: i16 = 23;
+    pub const INVALID_GROUP_ID: i16 = 24;
+    pub const UNKNOWN_MEMBER_ID: i16 = 25;
+    pub const INVALID_SESSION_TIMEOUT: i16 = 26;
+    pub const REBALANCE_IN_PROGRESS: i16 = 27;
+    pub const UNSUPPORTED_VERSION: i16 = 35;
+    pub const TOPIC_ALREADY_EXISTS: i16 = 36;
+    pub const INVALID_PARTITIONS: i16 = 37;
+    pub const INVALID_REPLICATION_FACTOR: i16 = 38;
+    pub const INVALID_REPLICA_ASSIGNMENT: i16 = 39;
+    pub const INVALID_CONFIG: i16 = 40;
+    pub const INVALID_REQUEST: i16 = 42;
+    pub const OUT_OF_ORDER_SEQUENCE_NUMBER: i16 = 45;
+    pub const DUPLICATE_SEQUENCE_NUMBER: i16 = 46;
+    pub const INVALID_PRODUCER_EPOCH: i16 = 47;
+    pub const KAFKA_STORAGE_ERROR: i16 = 56;
+    pub const NON_EMPTY_GROUP: i16 = 68;
+    pub const GROUP_ID_NOT_FOUND: i16 = 69;
+    pub const FETCH_SESSION_ID_NOT_FOUND: i16 = 70;
+    pub const INVALID_FETCH_SESSION_EPOCH: i16 = 71;
+    pub const FENCED_LEADER_EPOCH: i16 = 74;
+    pub const UNKNOWN_LEADER_EPOCH: i16 = 76;
+    pub const MEMBER_ID_REQUIRED: i16 = 79;
+    pub const GROUP_MAX_SIZE_REACHED: i16 = 81;
+    pub const FENCED_INSTANCE_ID: i16 = 82;
+    pub const GROUP_SUBSCRIBED_TO_TOPIC: i16 = 86;
+    pub const UNKNOWN_TOPIC_ID: i16 = 100;
+    pub const FENCED_MEMBER_EPOCH: i16 = 110;
+    pub const UNRELEASED_INSTANCE_ID: i16 = 111;
+    pub const UNSUPPORTED_ASSIGNOR: i16 = 112;
+    pub const STALE_MEMBER_EPOCH: i16 = 113;
+    pub const MISMATCHED_ENDPOINT_TYPE: i16 = 114;
+    pub const INVALID_RECORD_STATE: i16 = 121;
+    pub const SHARE_SESSION_NOT_FOUND: i16 = 122;
+    pub const INVALID_SHARE_SESSION_EPOCH: i16 = 123;
+    pub const INVALID_REGULAR_EXPRESSION: i16 = 128;
+}
+
 pub mod api_versions {
     use super::api_version::ApiVersion;
     use super::header::ResponseHeader;
@@ -154,11 +220,197 @@ pub mod api_versions {
     }
 }
 
+pub mod consumer_group_describe;
+pub mod consumer_group_heartbeat;
+pub mod create_topics;
+pub mod delete_groups;
+pub mod delete_topics;
+pub mod describe_cluster;
+pub mod describe_groups;
+pub mod describe_topic_partitions;
+pub mod fetch;
+pub mod find_coordinator;
+pub mod heartbeat;
+pub mod init_producer_id;
+pub mod join_group;
+pub mod leave_group;
+pub mod list_groups;
+pub mod list_offsets;
+pub mod metadata;
+pub mod offset_commit;
+pub mod offset_delete;
+pub mod offset_fetch;
+pub mod produce;
+pub mod share_acknowledge;
+pub mod share_fetch;
+pub mod share_group_heartbeat;
+pub mod sync_group;
+pub mod wire;
+
 pub use api_version::ApiVersion;
 pub use api_versions::{ApiVersionsRequest, ApiVersionsResponse};
-pub use header::RequestHeader;
+pub use consumer_group_describe::{ConsumerGroupDescribeRequest, ConsumerGroupDescribeResponse};
+pub use consumer_group_heartbeat::{ConsumerGroupHeartbeatRequest, ConsumerGroupHeartbeatResponse};
+pub use create_topics::{CreateTopicsRequest, CreateTopicsResponse};
+pub use delete_groups::{DeleteGroupsRequest, DeleteGroupsResponse};
+pub use delete_topics::{DeleteTopicsRequest, DeleteTopicsResponse};
+pub use describe_cluster::{DescribeClusterRequest, DescribeClusterResponse};
+pub use describe_groups::{DescribeGroupsRequest, DescribeGroupsResponse};
+pub use describe_topic_partitions::{
+    DescribeTopicPartitionsRequest, DescribeTopicPartitionsResponse,
+};
+pub use fetch::{FetchRequest, FetchResponse};
+pub use find_coordinator::{FindCoordinatorRequest, FindCoordinatorResponse};
+pub use header::{RequestHeader, ResponseHeader};
+pub use heartbeat::{HeartbeatRequest, HeartbeatResponse};
+pub use init_producer_id::{InitProducerIdRequest, InitProducerIdResponse};
+pub use join_group::{JoinGroupRequest, JoinGroupResponse};
+pub use leave_group::{LeaveGroupRequest, LeaveGroupResponse};
+pub use list_groups::{ListGroupsRequest, ListGroupsResponse};
+pub use list_offsets::{ListOffsetsRequest, ListOffsetsResponse};
+pub use metadata::{MetadataRequest, MetadataResponse};
+pub use offset_commit::{OffsetCommitRequest, OffsetCommitResponse};
+pub use offset_delete::{OffsetDeleteRequest, OffsetDeleteResponse};
+pub use offset_fetch::{OffsetFetchRequest, OffsetFetchResponse};
+pub use produce::{ProduceRequest, ProduceResponse};
+pub use share_acknowledge::{ShareAcknowledgeRequest, ShareAcknowledgeResponse};
+pub use share_fetch::{ShareFetchRequest, ShareFetchResponse};
+pub use share_group_heartbeat::{ShareGroupHeartbeatRequest, ShareGroupHeartbeatResponse};
+pub use sync_group::{SyncGroupRequest, SyncGroupResponse};
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Request {
     ApiVersions(ApiVersionsRequest),
+    Metadata(MetadataRequest),
+    DescribeCluster(DescribeClusterRequest),
+    Fetch(FetchRequest),
+    ListOffsets(ListOffsetsRequest),
+    DescribeTopicPartitions(DescribeTopicPartitionsRequest),
+    CreateTopics(CreateTopicsRequest),
+    DeleteTopics(DeleteTopicsRequest),
+    FindCoordinator(FindCoordinatorRequest),
+    JoinGroup(JoinGroupRequest),
+    SyncGroup(SyncGroupRequest),
+    Heartbeat(HeartbeatRequest),
+    LeaveGroup(LeaveGroupRequest),
+    OffsetCommit(OffsetCommitRequest),
+    OffsetFetch(OffsetFetchRequest),
+    DescribeGroups(DescribeGroupsRequest),
+    ListGroups(ListGroupsRequest),
+    DeleteGroups(DeleteGroupsRequest),
+    OffsetDelete(OffsetDeleteRequest),
+    ConsumerGroupHeartbeat(ConsumerGroupHeartbeatRequest),
+    ConsumerGroupDescribe(ConsumerGroupDescribeRequest),
+    ShareGroupHeartbeat(ShareGroupHeartbeatRequest),
+    ShareFetch(ShareFetchRequest),
+    ShareAcknowledge(ShareAcknowledgeRequest),
+    InitProducerId(InitProducerIdRequest),
+    Produce(ProduceRequest),
+}
+
+impl Request {
+    /// Header the request was sent with, for logging and error responses.
+    pub fn header(&self) -> RequestHeader {
+        match self {
+            Self::ApiVersions(request) => RequestHeader {
+                request_api_key: request.api_key,
+                request_api_version: request.api_version,
+                correlation_id: request.correlation_id,
+                client_id: request.client_id.clone(),
+            },
+            Self::Metadata(request) => request.header.clone(),
+            Self::DescribeCluster(request) => request.header.clone(),
+            Self::Fetch(request) => request.header.clone(),
+            Self::ListOffsets(request) => request.header.clone(),
+            Self::DescribeTopicPartitions(request) => request.header.clone(),
+            Self::CreateTopics(request) => request.header.clone(),
+            Self::DeleteTopics(request) => request.header.clone(),
+            Self::FindCoordinator(request) => request.header.clone(),
+            Self::JoinGroup(request) => request.header.clone(),
+            Self::SyncGroup(request) => request.header.clone(),
+            Self::Heartbeat(request) => request.header.clone(),
+            Self::LeaveGroup(request) => request.header.clone(),
+            Self::OffsetCommit(request) => request.header.clone(),
+            Self::OffsetFetch(request) => request.header.clone(),
+            Self::DescribeGroups(request) => request.header.clone(),
+            Self::ListGroups(request) => request.header.clone(),
+            Self::DeleteGroups(request) => request.header.clone(),
+            Self::OffsetDelete(request) => request.header.clone(),
+            Self::ConsumerGroupHeartbeat(request) => request.header.clone(),
+            Self::ConsumerGroupDescribe(request) => request.header.clone(),
+            Self::ShareGroupHeartbeat(request) => request.header.clone(),
+            Self::ShareFetch(request) => request.header.clone(),
+            Self::ShareAcknowledge(request) => request.header.clone(),
+            Self::InitProducerId(request) => request.header.clone(),
+            Self::Produce(request) => request.header.clone(),
+        }
+    }
+
+    /// Whether the client waits for a response; `acks=0` produces get none.
+    pub fn expects_response(&self) -> bool {
+        !matches!(self, Self::Produce(request) if request.acks == produce::ACKS_NONE)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Response {
+    ApiVersions(ApiVersionsResponse),
+    Metadata(MetadataResponse),
+    DescribeCluster(DescribeClusterResponse),
+    Fetch(FetchResponse),
+    ListOffsets(ListOffsetsResponse),
+    DescribeTopicPartitions(DescribeTopicPartitionsResponse),
+    CreateTopics(CreateTopicsResponse),
+    DeleteTopics(DeleteTopicsResponse),
+    FindCoordinator(FindCoordinatorResponse),
+    JoinGroup(JoinGroupResponse),
+    SyncGroup(SyncGroupResponse),
+    Heartbeat(HeartbeatResponse),
+    LeaveGroup(LeaveGroupResponse),
+    OffsetCommit(OffsetCommitResponse),
+    OffsetFetch(OffsetFetchResponse),
+    DescribeGroups(DescribeGroupsResponse),
+    ListGroups(ListGroupsResponse),
+    DeleteGroups(DeleteGroupsResponse),
+    OffsetDelete(OffsetDeleteResponse),
+    ConsumerGroupHeartbeat(ConsumerGroupHeartbeatResponse),
+    ConsumerGroupDescribe(ConsumerGroupDescribeResponse),
+    ShareGroupHeartbeat(ShareGroupHeartbeatResponse),
+    ShareFetch(ShareFetchResponse),
+    ShareAcknowledge(ShareAcknowledgeResponse),
+    InitProducerId(InitProducerIdResponse),
+    Produce(ProduceResponse),
+}
+
+impl Response {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        match self {
+            Self::ApiVersions(response) => response.to_bytes(),
+            Self::Metadata(response) => response.to_bytes(),
+            Self::DescribeCluster(response) => response.to_bytes(),
+            Self::Fetch(response) => response.to_bytes(),
+            Self::ListOffsets(response) => response.to_bytes(),
+            Self::DescribeTopicPartitions(response) => response.to_bytes(),
+            Self::CreateTopics(response) => response.to_bytes(),
+            Self::DeleteTopics(response) => response.to_bytes(),
+            Self::FindCoordinator(response) => response.to_bytes(),
+            Self::JoinGroup(response) => response.to_bytes(),
+            Self::SyncGroup(response) => response.to_bytes(),
+            Self::Heartbeat(response) => response.to_bytes(),
+            Self::LeaveGroup(response) => response.to_bytes(),
+            Self::OffsetCommit(response) => response.to_bytes(),
+            Self::OffsetFetch(response) => response.to_bytes(),
+            Self::DescribeGroups(response) => response.to_bytes(),
+            Self::ListGroups(response) => response.to_bytes(),
+            Self::DeleteGroups(response) => response.to_bytes(),
+            Self::OffsetDelete(response) => response.to_bytes(),
+            Self::ConsumerGroupHeartbeat(response) => response.to_bytes(),
+            Self::ConsumerGroupDescribe(response) => response.to_bytes(),
+            Self::ShareGroupHeartbeat(response) => response.to_bytes(),
+            Self::ShareFetch(response) => response.to_bytes(),
+            Self::ShareAcknowledge(response) => response.to_bytes(),
+            Self::InitProducerId(response) => response.to_bytes(),
+            Self::Produce(response) => response.to_bytes(),
+        }
+    }
 }
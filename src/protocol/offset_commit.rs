@@ -0,0 +1,199 @@
+use super::header::{RequestHeader, ResponseHeader};
+use super::wire;
+use crate::codec::primitives;
+use std::io::{self, Cursor};
+
+pub const OFFSET_COMMIT_KEY: i16 = 8;
+/// Versions 0 and 1 carry per-partition commit timestamps and are not served.
+pub const MIN_VERSION: i16 = 2;
+pub const MAX_VERSION: i16 = 9;
+const FIRST_FLEXIBLE_VERSION: i16 = 8;
+
+/// Generation sent by clients committing outside of a group's generations,
+/// such as consumers with manually assigned partitions.
+pub const NO_GENERATION: i32 = -1;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OffsetCommitRequest {
+    pub header: RequestHeader,
+    pub group_id: String,
+    /// Generation of a classic group or epoch of a consumer group member.
+    pub generation_id_or_member_epoch: i32,
+    pub member_id: String,
+    pub group_instance_id: Option<String>,
+    pub topics: Vec<OffsetCommitRequestTopic>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OffsetCommitRequestTopic {
+    pub name: String,
+    pub partitions: Vec<OffsetCommitRequestPartition>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OffsetCommitRequestPartition {
+    pub partition_index: i32,
+    pub committed_offset: i64,
+    pub committed_leader_epoch: i32,
+    pub committed_metadata: Option<String>,
+}
+
+impl OffsetCommitRequest {
+    pub fn supports(api_version: i16) -> bool {
+        (MIN_VERSION..=MAX_VERSION).contains(&api_version)
+    }
+
+    pub fn decode(header: RequestHeader, cursor: &mut Cursor<&[u8]>) -> io::Result<Self> {
+        let version = header.request_api_version;
+        let flexible = version >= FIRST_FLEXIBLE_VERSION;
+        wire::skip_tagged_fields(cursor, flexible)?;
+
+        let group_id = wire::read_string(cursor, flexible)?;
+        let generation_id_or_member_epoch = primitives::read_i32(cursor)?;
+        let member_id = wire::read_string(cursor, flexible)?;
+        let group_instance_id = if version >= 7 {
+            wire::read_nullable_string(cursor, flexible)?
+        } else {
+            None
+        };
+        if version <= 4 {
+            // The retention time of versions 2-4 is ignored in favour of
+            // `offsets.retention.minutes`.
+            primitives::read_i64(cursor)?;
+        }
+        let topics = wire::read_array(cursor, flexible, |cursor| {
+            let name = wire::read_string(cursor, flexible)?;
+            let partitions = wire::read_array(cursor, flexible, |cursor| {
+                let partition_index = primitives::read_i32(cursor)?;
+                let committed_offset = primitives::read_i64(cursor)?;
+                let committed_leader_epoch = if version >= 6 {
+                    primitives::read_i32(cursor)?
+                } else {
+                    -1
+                };
+                let committed_metadata = wire::read_nullable_string(cursor, flexible)?;
+                wire::skip_tagged_fields(cursor, flexible)?;
+                Ok(OffsetCommitRequestPartition {
+                    partition_index,
+                    committed_offset,
+                    committed_leader_epoch,
+                    committed_metadata,
+                })
+            })?
+            .unwrap_or_default();
+            wire::skip_tagged_fields(cursor, flexible)?;
+            Ok(OffsetCommitRequestTopic { name, partitions })
+        })?
+        .unwrap_or_default();
+        wire::skip_tagged_fields(cursor, flexible)?;
+
+        Ok(Self {
+            header,
+            group_id,
+            generation_id_or_member_epoch,
+            member_id,
+            group_instance_id,
+            topics,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OffsetCommitResponse {
+    pub header: ResponseHeader,
+    pub api_version: i16,
+    pub throttle_time_ms: i32,
+    pub topics: Vec<OffsetCommitResponseTopic>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OffsetCommitResponseTopic {
+    pub name: String,
+    pub partitions: Vec<OffsetCommitResponsePartition>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OffsetCommitResponsePartition {
+    pub partition_index: i32,
+    pub error_code: i16,
+}
+
+impl OffsetCommitResponse {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let flexible = self.api_version >= FIRST_FLEXIBLE_VERSION;
+        let mut body = Vec::new();
+        if self.api_version >= 3 {
+            body.extend_from_slice(&self.throttle_time_ms.to_be_bytes());
+        }
+        wire::write_array(&mut body, &self.topics, flexible, |buffer, topic| {
+            wire::write_string(buffer, &topic.name, flexible);
+            wire::write_array(buffer, &topic.partitions, flexible, |buffer, partition| {
+                buffer.extend_from_slice(&partition.partition_index.to_be_bytes());
+                buffer.extend_from_slice(&partition.error_code.to_be_bytes());
+                wire::write_empty_tagged_fields(buffer, flexible);
+            });
+            wire::write_empty_tagged_fields(buffer, flexible);
+        });
+        wire::write_empty_tagged_fields(&mut body, flexible);
+
+        self.header.frame(flexible, &body)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_retention_time_and_leader_epochs_by_version() {
+        let header = |api_version| RequestHeader {
+            request_api_key: OFFSET_COMMIT_KEY,
+            request_api_version: api_version,
+            correlation_id: 4,
+            client_id: None,
+        };
+        let mut body = Vec::new();
+        wire::write_string(&mut body, "payments", false);
+        body.extend_from_slice(&NO_GENERATION.to_be_bytes());
+        wire::write_string(&mut body, "", false);
+        body.extend_from_slice(&(-1_i64).to_be_bytes());
+        body.extend_from_slice(&1_i32.to_be_bytes());
+        wire::write_string(&mut body, "orders", false);
+        body.extend_from_slice(&1_i32.to_be_bytes());
+        body.extend_from_slice(&2_i32.to_be_bytes());
+        body.extend_from_slice(&42_i64.to_be_bytes());
+        wire::write_nullable_string(&mut body, Some("checkpoint"), false);
+
+        let request = OffsetCommitRequest::decode(header(2), &mut Cursor::new(body.as_slice()))
+            .expect("decode v2");
+
+        let partition = &request.topics[0].partitions[0];
+        assert_eq!(request.generation_id_or_member_epoch, NO_GENERATION);
+        assert_eq!(partition.partition_index, 2);
+        assert_eq!(partition.committed_offset, 42);
+        assert_eq!(partition.committed_leader_epoch, -1);
+        assert_eq!(partition.committed_metadata.as_deref(), Some("checkpoint"));
+
+        let mut body = vec![0];
+        primitives::write_compact_string(&mut body, "payments");
+        body.extend_from_slice(&3_i32.to_be_bytes());
+        primitives::write_compact_string(&mut body, "member");
+        primitives::write_compact_nullable_string(&mut body, Some("pod-1"));
+        primitives::write_compact_array_len(&mut body, Some(1));
+        primitives::write_compact_string(&mut body, "orders");
+        primitives::write_compact_array_len(&mut body, Some(1));
+        body.extend_from_slice(&0_i32.to_be_bytes());
+        body.extend_from_slice(&7_i64.to_be_bytes());
+        body.extend_from_slice(&5_i32.to_be_bytes());
+        primitives::write_compact_nullable_string(&mut body, None);
+        body.extend_from_slice(&[0, 0, 0]);
+
+        let request = OffsetCommitRequest::decode(header(9), &mut Cursor::new(body.as_slice()))
+            .expect("decode v9");
+
+        let partition = &request.topics[0].partitions[0];
+        assert_eq!(request.group_instance_id.as_deref(), Some("pod-1"));
+        assert_eq!(partition.committed_leader_epoch, 5);
+        assert_eq!(partition.committed_metadata, None);
+    }
+}
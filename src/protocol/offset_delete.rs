@@ -0,0 +1,111 @@
+use super::header::{RequestHeader, ResponseHeader};
+use super::wire;
+use crate::codec::primitives;
+use std::io::{self, Cursor};
+
+pub const OFFSET_DELETE_KEY: i16 = 47;
+pub const MIN_VERSION: i16 = 0;
+pub const MAX_VERSION: i16 = 0;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OffsetDeleteRequest {
+    pub header: RequestHeader,
+    pub group_id: String,
+    pub topics: Vec<OffsetDeleteRequestTopic>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OffsetDeleteRequestTopic {
+    pub name: String,
+    pub partitions: Vec<i32>,
+}
+
+impl OffsetDeleteRequest {
+    pub fn supports(api_version: i16) -> bool {
+        (MIN_VERSION..=MAX_VERSION).contains(&api_version)
+    }
+
+    pub fn decode(header: RequestHeader, cursor: &mut Cursor<&[u8]>) -> io::Result<Self> {
+        let group_id = wire::read_string(cursor, false)?;
+        let topics = wire::read_array(cursor, false, |cursor| {
+            Ok(OffsetDeleteRequestTopic {
+                name: wire::read_string(cursor, false)?,
+                partitions: wire::read_array(cursor, false, primitives::read_i32)?
+                    .unwrap_or_default(),
+            })
+        })?
+        .unwrap_or_default();
+
+        Ok(Self {
+            header,
+            group_id,
+            topics,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OffsetDeleteResponse {
+    pub header: ResponseHeader,
+    pub api_version: i16,
+    pub error_code: i16,
+    pub throttle_time_ms: i32,
+    pub topics: Vec<OffsetDeleteResponseTopic>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OffsetDeleteResponseTopic {
+    pub name: String,
+    pub partitions: Vec<OffsetDeleteResponsePartition>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OffsetDeleteResponsePartition {
+    pub partition_index: i32,
+    pub error_code: i16,
+}
+
+impl OffsetDeleteResponse {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut body = Vec::new();
+        body.extend_from_slice(&self.error_code.to_be_bytes());
+        body.extend_from_slice(&self.throttle_time_ms.to_be_bytes());
+        wire::write_array(&mut body, &self.topics, false, |buffer, topic| {
+            wire::write_string(buffer, &topic.name, false);
+            wire::write_array(buffer, &topic.partitions, false, |buffer, partition| {
+                buffer.extend_from_slice(&partition.partition_index.to_be_bytes());
+                buffer.extend_from_slice(&partition.error_code.to_be_bytes());
+            });
+        });
+
+        self.header.frame(false, &body)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_partitions_per_topic() {
+        let header = RequestHeader {
+            request_api_key: OFFSET_DELETE_KEY,
+            request_api_version: 0,
+            correlation_id: 5,
+            client_id: None,
+        };
+        let mut body = Vec::new();
+        wire::write_string(&mut body, "payments", false);
+        body.extend_from_slice(&1_i32.to_be_bytes());
+        wire::write_string(&mut body, "orders", false);
+        body.extend_from_slice(&2_i32.to_be_bytes());
+        body.extend_from_slice(&0_i32.to_be_bytes());
+        body.extend_from_slice(&3_i32.to_be_bytes());
+
+        let request =
+            OffsetDeleteRequest::decode(header, &mut Cursor::new(body.as_slice())).expect("decode");
+
+        assert_eq!(request.group_id, "payments");
+        assert_eq!(request.topics[0].partitions, [0, 3]);
+    }
+}
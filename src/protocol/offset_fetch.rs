@@ -0,0 +1,246 @@
+use super::header::{RequestHeader, ResponseHeader};
+use super::wire;
+use crate::codec::primitives;
+use std::io::{self, Cursor};
+
+pub const OFFSET_FETCH_KEY: i16 = 9;
+/// Version 0 reads offsets from ZooKeeper and is not served.
+pub const MIN_VERSION: i16 = 1;
+pub const MAX_VERSION: i16 = 9;
+const FIRST_FLEXIBLE_VERSION: i16 = 6;
+/// First version that fetches offsets for a batch of groups.
+pub const FIRST_BATCHED_VERSION: i16 = 8;
+
+/// Offset and leader epoch reported for partitions without a committed offset.
+pub const NO_OFFSET: i64 = -1;
+pub const NO_LEADER_EPOCH: i32 = -1;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OffsetFetchRequest {
+    pub header: RequestHeader,
+    /// Groups to fetch; a single group before version 8.
+    pub groups: Vec<OffsetFetchRequestGroup>,
+    pub require_stable: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OffsetFetchRequestGroup {
+    pub group_id: String,
+    pub member_id: Option<String>,
+    pub member_epoch: i32,
+    /// Partitions to fetch, or `None` for every partition with an offset.
+    pub topics: Option<Vec<OffsetFetchRequestTopic>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OffsetFetchRequestTopic {
+    pub name: String,
+    pub partition_indexes: Vec<i32>,
+}
+
+impl OffsetFetchRequest {
+    pub fn supports(api_version: i16) -> bool {
+        (MIN_VERSION..=MAX_VERSION).contains(&api_version)
+    }
+
+    pub fn decode(header: RequestHeader, cursor: &mut Cursor<&[u8]>) -> io::Result<Self> {
+        let version = header.request_api_version;
+        let flexible = version >= FIRST_FLEXIBLE_VERSION;
+        wire::skip_tagged_fields(cursor, flexible)?;
+
+        let read_topics = |cursor: &mut Cursor<&[u8]>| {
+            wire::read_array(cursor, flexible, |cursor| {
+                let name = wire::read_string(cursor, flexible)?;
+                let partition_indexes =
+                    wire::read_array(cursor, flexible, primitives::read_i32)?.unwrap_or_default();
+                wire::skip_tagged_fields(cursor, flexible)?;
+                Ok(OffsetFetchRequestTopic {
+                    name,
+                    partition_indexes,
+                })
+            })
+        };
+        let groups = if version >= FIRST_BATCHED_VERSION {
+            wire::read_array(cursor, flexible, |cursor| {
+                let group_id = wire::read_string(cursor, flexible)?;
+                let (member_id, member_epoch) = if version >= 9 {
+                    (
+                        wire::read_nullable_string(cursor, flexible)?,
+                        primitives::read_i32(cursor)?,
+                    )
+                } else {
+                    (None, -1)
+                };
+                let topics = read_topics(cursor)?;
+                wire::skip_tagged_fields(cursor, flexible)?;
+                Ok(OffsetFetchRequestGroup {
+                    group_id,
+                    member_id,
+                    member_epoch,
+                    topics,
+                })
+            })?
+            .unwrap_or_default()
+        } else {
+            let group_id = wire::read_string(cursor, flexible)?;
+            vec![OffsetFetchRequestGroup {
+                group_id,
+                member_id: None,
+                member_epoch: -1,
+                topics: read_topics(cursor)?,
+            }]
+        };
+        let require_stable = version >= 7 && primitives::read_bool(cursor)?;
+        wire::skip_tagged_fields(cursor, flexible)?;
+
+        Ok(Self {
+            header,
+            groups,
+            require_stable,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OffsetFetchResponse {
+    pub header: ResponseHeader,
+    pub api_version: i16,
+    pub throttle_time_ms: i32,
+    /// One entry per requested group; only the first is sent before version 8.
+    pub groups: Vec<OffsetFetchResponseGroup>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OffsetFetchResponseGroup {
+    pub group_id: String,
+    pub topics: Vec<OffsetFetchResponseTopic>,
+    pub error_code: i16,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OffsetFetchResponseTopic {
+    pub name: String,
+    pub partitions: Vec<OffsetFetchResponsePartition>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OffsetFetchResponsePartition {
+    pub partition_index: i32,
+    pub committed_offset: i64,
+    pub committed_leader_epoch: i32,
+    pub metadata: Option<String>,
+    pub error_code: i16,
+}
+
+impl OffsetFetchResponse {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let version = self.api_version;
+        let flexible = version >= FIRST_FLEXIBLE_VERSION;
+        let mut body = Vec::new();
+        if version >= 3 {
+            body.extend_from_slice(&self.throttle_time_ms.to_be_bytes());
+        }
+        let write_topics = |buffer: &mut Vec<u8>, topics: &[OffsetFetchResponseTopic]| {
+            wire::write_array(buffer, topics, flexible, |buffer, topic| {
+                wire::write_string(buffer, &topic.name, flexible);
+                wire::write_array(buffer, &topic.partitions, flexible, |buffer, partition| {
+                    buffer.extend_from_slice(&partition.partition_index.to_be_bytes());
+                    buffer.extend_from_slice(&partition.committed_offset.to_be_bytes());
+                    if version >= 5 {
+                        buffer.extend_from_slice(&partition.committed_leader_epoch.to_be_bytes());
+                    }
+                    wire::write_nullable_string(buffer, partition.metadata.as_deref(), flexible);
+                    buffer.extend_from_slice(&partition.error_code.to_be_bytes());
+                    wire::write_empty_tagged_fields(buffer, flexible);
+                });
+                wire::write_empty_tagged_fields(buffer, flexible);
+            });
+        };
+        if version >= FIRST_BATCHED_VERSION {
+            wire::write_array(&mut body, &self.groups, flexible, |buffer, group| {
+                wire::write_string(buffer, &group.group_id, flexible);
+                write_topics(buffer, &group.topics);
+                buffer.extend_from_slice(&group.error_code.to_be_bytes());
+                wire::write_empty_tagged_fields(buffer, flexible);
+            });
+        } else {
+            let group = self.groups.first();
+            write_topics(&mut body, group.map_or(&[], |group| &group.topics));
+            if version >= 2 {
+                let error_code = group.map_or(0, |group| group.error_code);
+                body.extend_from_slice(&error_code.to_be_bytes());
+            }
+        }
+        wire::write_empty_tagged_fields(&mut body, flexible);
+
+        self.header.frame(flexible, &body)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header(api_version: i16) -> RequestHeader {
+        RequestHeader {
+            request_api_key: OFFSET_FETCH_KEY,
+            request_api_version: api_version,
+            correlation_id: 6,
+            client_id: None,
+        }
+    }
+
+    #[test]
+    fn decodes_single_and_batched_groups() {
+        let mut body = Vec::new();
+        wire::write_string(&mut body, "payments", false);
+        body.extend_from_slice(&(-1_i32).to_be_bytes());
+        let request = OffsetFetchRequest::decode(header(2), &mut Cursor::new(body.as_slice()))
+            .expect("decode v2");
+        assert_eq!(request.groups.len(), 1);
+        assert_eq!(request.groups[0].group_id, "payments");
+        assert_eq!(request.groups[0].topics, None);
+
+        let mut body = vec![0];
+        primitives::write_compact_array_len(&mut body, Some(1));
+        primitives::write_compact_string(&mut body, "payments");
+        primitives::write_compact_nullable_string(&mut body, Some("member"));
+        body.extend_from_slice(&4_i32.to_be_bytes());
+        primitives::write_compact_array_len(&mut body, Some(1));
+        primitives::write_compact_string(&mut body, "orders");
+        primitives::write_compact_array_len(&mut body, Some(2));
+        body.extend_from_slice(&0_i32.to_be_bytes());
+        body.extend_from_slice(&1_i32.to_be_bytes());
+        body.extend_from_slice(&[0, 0]);
+        body.push(1);
+        body.push(0);
+        let request = OffsetFetchRequest::decode(header(9), &mut Cursor::new(body.as_slice()))
+            .expect("decode v9");
+        let group = &request.groups[0];
+        assert_eq!(group.member_id.as_deref(), Some("member"));
+        assert_eq!(group.member_epoch, 4);
+        assert_eq!(
+            group.topics.as_deref().expect("topics")[0].partition_indexes,
+            [0, 1]
+        );
+        assert!(request.require_stable);
+    }
+
+    #[test]
+    fn version_one_response_omits_the_group_error() {
+        let response = OffsetFetchResponse {
+            header: ResponseHeader { correlation_id: 6 },
+            api_version: 1,
+            throttle_time_ms: 0,
+            groups: vec![OffsetFetchResponseGroup {
+                group_id: "payments".to_string(),
+                topics: Vec::new(),
+                error_code: 15,
+            }],
+        };
+
+        let bytes = response.to_bytes();
+
+        assert_eq!(&bytes[8..], &[0, 0, 0, 0]);
+    }
+}
@@ -0,0 +1,194 @@
+use super::header::{RequestHeader, ResponseHeader};
+use super::wire;
+use crate::codec::primitives;
+use std::io::{self, Cursor};
+
+pub const PRODUCE_KEY: i16 = 0;
+/// Versions before 3 carry message sets older than record batches.
+pub const MIN_VERSION: i16 = 3;
+pub const MAX_VERSION: i16 = 11;
+const FIRST_FLEXIBLE_VERSION: i16 = 9;
+
+/// Acknowledgement modes: none, the leader only, or all in-sync replicas.
+pub const ACKS_NONE: i16 = 0;
+pub const ACKS_LEADER: i16 = 1;
+pub const ACKS_ALL: i16 = -1;
+
+/// Reported while record timestamps are the producer's create time.
+pub const NO_LOG_APPEND_TIME: i64 = -1;
+pub const UNKNOWN_OFFSET: i64 = -1;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProduceRequest {
+    pub header: RequestHeader,
+    pub transactional_id: Option<String>,
+    pub acks: i16,
+    pub timeout_ms: i32,
+    pub topic_data: Vec<TopicProduceData>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TopicProduceData {
+    pub name: String,
+    pub partition_data: Vec<PartitionProduceData>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PartitionProduceData {
+    pub index: i32,
+    /// One or more record batches, as they will be stored.
+    pub records: Option<Vec<u8>>,
+}
+
+impl ProduceRequest {
+    pub fn supports(api_version: i16) -> bool {
+        (MIN_VERSION..=MAX_VERSION).contains(&api_version)
+    }
+
+    pub fn decode(header: RequestHeader, cursor: &mut Cursor<&[u8]>) -> io::Result<Self> {
+        let flexible = header.request_api_version >= FIRST_FLEXIBLE_VERSION;
+        wire::skip_tagged_fields(cursor, flexible)?;
+
+        let transactional_id = wire::read_nullable_string(cursor, flexible)?;
+        let acks = primitives::read_i16(cursor)?;
+        let timeout_ms = primitives::read_i32(cursor)?;
+        let topic_data = wire::read_array(cursor, flexible, |cursor| {
+            let name = wire::read_string(cursor, flexible)?;
+            let partition_data = wire::read_array(cursor, flexible, |cursor| {
+                let index = primitives::read_i32(cursor)?;
+                let records = wire::read_nullable_bytes(cursor, flexible)?;
+                wire::skip_tagged_fields(cursor, flexible)?;
+                Ok(PartitionProduceData { index, records })
+            })?
+            .unwrap_or_default();
+            wire::skip_tagged_fields(cursor, flexible)?;
+            Ok(TopicProduceData {
+                name,
+                partition_data,
+            })
+        })?
+        .unwrap_or_default();
+        wire::skip_tagged_fields(cursor, flexible)?;
+
+        Ok(Self {
+            header,
+            transactional_id,
+            acks,
+            timeout_ms,
+            topic_data,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProduceResponse {
+    pub header: ResponseHeader,
+    pub api_version: i16,
+    pub responses: Vec<TopicProduceResponse>,
+    pub throttle_time_ms: i32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TopicProduceResponse {
+    pub name: String,
+    pub partition_responses: Vec<PartitionProduceResponse>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PartitionProduceResponse {
+    pub index: i32,
+    pub error_code: i16,
+    pub base_offset: i64,
+    pub log_append_time_ms: i64,
+    pub log_start_offset: i64,
+    pub error_message: Option<String>,
+}
+
+impl ProduceResponse {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let version = self.api_version;
+        let flexible = version >= FIRST_FLEXIBLE_VERSION;
+        let mut body = Vec::new();
+        wire::write_array(&mut body, &self.responses, flexible, |buffer, topic| {
+            wire::write_string(buffer, &topic.name, flexible);
+            wire::write_array(
+                buffer,
+                &topic.partition_responses,
+                flexible,
+                |buffer, partition| {
+                    buffer.extend_from_slice(&partition.index.to_be_bytes());
+                    buffer.extend_from_slice(&partition.error_code.to_be_bytes());
+                    buffer.extend_from_slice(&partition.base_offset.to_be_bytes());
+                    buffer.extend_from_slice(&partition.log_append_time_ms.to_be_bytes());
+                    if version >= 5 {
+                        buffer.extend_from_slice(&partition.log_start_offset.to_be_bytes());
+                    }
+                    if version >= 8 {
+                        // Whole batches are rejected, so no record is singled out.
+                        wire::write_array_len(buffer, Some(0), flexible);
+                        wire::write_nullable_string(
+                            buffer,
+                            partition.error_message.as_deref(),
+                            flexible,
+                        );
+                    }
+                    wire::write_empty_tagged_fields(buffer, flexible);
+                },
+            );
+            wire::write_empty_tagged_fields(buffer, flexible);
+        });
+        body.extend_from_slice(&self.throttle_time_ms.to_be_bytes());
+        wire::write_empty_tagged_fields(&mut body, flexible);
+
+        self.header.frame(flexible, &body)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_flexible_request_with_null_records() {
+        let header = RequestHeader {
+            request_api_key: PRODUCE_KEY,
+            request_api_version: 9,
+            correlation_id: 4,
+            client_id: None,
+        };
+        let mut body = vec![0];
+        primitives::write_compact_nullable_string(&mut body, None);
+        body.extend_from_slice(&ACKS_ALL.to_be_bytes());
+        body.extend_from_slice(&1_500_i32.to_be_bytes());
+        primitives::write_compact_array_len(&mut body, Some(1));
+        primitives::write_compact_string(&mut body, "orders");
+        primitives::write_compact_array_len(&mut body, Some(2));
+        body.extend_from_slice(&0_i32.to_be_bytes());
+        primitives::write_compact_array_len(&mut body, Some(3));
+        body.extend_from_slice(&[7, 8, 9, 0]);
+        body.extend_from_slice(&1_i32.to_be_bytes());
+        primitives::write_compact_array_len(&mut body, None);
+        body.extend_from_slice(&[0, 0, 0]);
+
+        let request =
+            ProduceRequest::decode(header, &mut Cursor::new(body.as_slice())).expect("decode");
+
+        assert_eq!(request.transactional_id, None);
+        assert_eq!(request.acks, ACKS_ALL);
+        assert_eq!(request.timeout_ms, 1_500);
+        assert_eq!(request.topic_data[0].name, "orders");
+        assert_eq!(
+            request.topic_data[0].partition_data,
+            [
+                PartitionProduceData {
+                    index: 0,
+                    records: Some(vec![7, 8, 9]),
+                },
+                PartitionProduceData {
+                    index: 1,
+                    records: None,
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,166 @@
+use super::header::{RequestHeader, ResponseHeader};
+use super::share_fetch::{self, AcknowledgementBatch, LeaderIdAndEpoch, NodeEndpoint};
+use super::wire;
+use crate::codec::primitives;
+use crate::metadata::Uuid;
+use std::io::{self, Cursor};
+
+pub const SHARE_ACKNOWLEDGE_KEY: i16 = 79;
+pub const MIN_VERSION: i16 = 0;
+pub const MAX_VERSION: i16 = 0;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShareAcknowledgeRequest {
+    pub header: RequestHeader,
+    pub group_id: Option<String>,
+    pub member_id: Option<String>,
+    pub share_session_epoch: i32,
+    pub topics: Vec<ShareAcknowledgeTopic>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShareAcknowledgeTopic {
+    pub topic_id: Uuid,
+    pub partitions: Vec<ShareAcknowledgePartition>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShareAcknowledgePartition {
+    pub partition_index: i32,
+    pub acknowledgement_batches: Vec<AcknowledgementBatch>,
+}
+
+impl ShareAcknowledgeRequest {
+    pub fn supports(api_version: i16) -> bool {
+        (MIN_VERSION..=MAX_VERSION).contains(&api_version)
+    }
+
+    pub fn decode(header: RequestHeader, cursor: &mut Cursor<&[u8]>) -> io::Result<Self> {
+        wire::skip_tagged_fields(cursor, true)?;
+
+        let group_id = wire::read_nullable_string(cursor, true)?;
+        let member_id = wire::read_nullable_string(cursor, true)?;
+        let share_session_epoch = primitives::read_i32(cursor)?;
+        let topics = wire::read_array(cursor, true, |cursor| {
+            let topic_id = Uuid(primitives::read_uuid(cursor)?);
+            let partitions = wire::read_array(cursor, true, |cursor| {
+                let partition_index = primitives::read_i32(cursor)?;
+                let acknowledgement_batches = share_fetch::read_acknowledgement_batches(cursor)?;
+                wire::skip_tagged_fields(cursor, true)?;
+                Ok(ShareAcknowledgePartition {
+                    partition_index,
+                    acknowledgement_batches,
+                })
+            })?
+            .unwrap_or_default();
+            wire::skip_tagged_fields(cursor, true)?;
+            Ok(ShareAcknowledgeTopic {
+                topic_id,
+                partitions,
+            })
+        })?
+        .unwrap_or_default();
+        wire::skip_tagged_fields(cursor, true)?;
+
+        Ok(Self {
+            header,
+            group_id,
+            member_id,
+            share_session_epoch,
+            topics,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShareAcknowledgeResponse {
+    pub header: ResponseHeader,
+    pub api_version: i16,
+    pub throttle_time_ms: i32,
+    pub error_code: i16,
+    pub error_message: Option<String>,
+    pub responses: Vec<ShareAcknowledgeTopicResponse>,
+    pub node_endpoints: Vec<NodeEndpoint>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShareAcknowledgeTopicResponse {
+    pub topic_id: Uuid,
+    pub partitions: Vec<ShareAcknowledgePartitionResponse>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShareAcknowledgePartitionResponse {
+    pub partition_index: i32,
+    pub error_code: i16,
+    pub error_message: Option<String>,
+    pub current_leader: LeaderIdAndEpoch,
+}
+
+impl ShareAcknowledgeResponse {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut body = Vec::new();
+        body.extend_from_slice(&self.throttle_time_ms.to_be_bytes());
+        body.extend_from_slice(&self.error_code.to_be_bytes());
+        wire::write_nullable_string(&mut body, self.error_message.as_deref(), true);
+        wire::write_array(&mut body, &self.responses, true, |buffer, topic| {
+            buffer.extend_from_slice(topic.topic_id.as_bytes());
+            wire::write_array(buffer, &topic.partitions, true, |buffer, partition| {
+                buffer.extend_from_slice(&partition.partition_index.to_be_bytes());
+                buffer.extend_from_slice(&partition.error_code.to_be_bytes());
+                wire::write_nullable_string(buffer, partition.error_message.as_deref(), true);
+                share_fetch::write_leader(buffer, partition.current_leader);
+                wire::write_empty_tagged_fields(buffer, true);
+            });
+            wire::write_empty_tagged_fields(buffer, true);
+        });
+        share_fetch::write_node_endpoints(&mut body, &self.node_endpoints);
+        wire::write_empty_tagged_fields(&mut body, true);
+
+        self.header.frame(true, &body)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::share_fetch::acknowledge_type;
+
+    #[test]
+    fn decodes_a_single_type_for_a_range() {
+        let header = RequestHeader {
+            request_api_key: SHARE_ACKNOWLEDGE_KEY,
+            request_api_version: 0,
+            correlation_id: 12,
+            client_id: None,
+        };
+        let mut body = vec![0];
+        primitives::write_compact_nullable_string(&mut body, Some("jobs"));
+        primitives::write_compact_nullable_string(&mut body, Some("m-1"));
+        body.extend_from_slice(&3_i32.to_be_bytes());
+        primitives::write_compact_array_len(&mut body, Some(1));
+        body.extend_from_slice(&[4; 16]);
+        primitives::write_compact_array_len(&mut body, Some(1));
+        body.extend_from_slice(&1_i32.to_be_bytes());
+        primitives::write_compact_array_len(&mut body, Some(1));
+        body.extend_from_slice(&0_i64.to_be_bytes());
+        body.extend_from_slice(&9_i64.to_be_bytes());
+        primitives::write_compact_array_len(&mut body, Some(1));
+        body.push(acknowledge_type::REJECT as u8);
+        body.extend_from_slice(&[0, 0, 0, 0]);
+
+        let request = ShareAcknowledgeRequest::decode(header, &mut Cursor::new(body.as_slice()))
+            .expect("decode");
+
+        let partition = &request.topics[0].partitions[0];
+        assert_eq!(partition.partition_index, 1);
+        assert_eq!(
+            partition.acknowledgement_batches,
+            vec![AcknowledgementBatch {
+                first_offset: 0,
+                last_offset: 9,
+                acknowledge_types: vec![acknowledge_type::REJECT],
+            }]
+        );
+    }
+}
@@ -0,0 +1,355 @@
+use super::header::{RequestHeader, ResponseHeader};
+use super::wire;
+use crate::codec::primitives;
+use crate::metadata::Uuid;
+use std::io::{self, Cursor};
+
+pub const SHARE_FETCH_KEY: i16 = 78;
+pub const MIN_VERSION: i16 = 0;
+pub const MAX_VERSION: i16 = 0;
+
+/// Share session epoch sent to open a new session.
+pub const INITIAL_SHARE_SESSION_EPOCH: i32 = 0;
+/// Share session epoch sent to close the session.
+pub const FINAL_SHARE_SESSION_EPOCH: i32 = -1;
+
+/// Acknowledge types carried in an acknowledgement batch.
+pub mod acknowledge_type {
+    /// The offset has no record, for example after compaction.
+    pub const GAP: i8 = 0;
+    pub const ACCEPT: i8 = 1;
+    pub const RELEASE: i8 = 2;
+    pub const REJECT: i8 = 3;
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShareFetchRequest {
+    pub header: RequestHeader,
+    pub group_id: Option<String>,
+    pub member_id: Option<String>,
+    pub share_session_epoch: i32,
+    pub max_wait_ms: i32,
+    pub min_bytes: i32,
+    pub max_bytes: i32,
+    pub topics: Vec<ShareFetchTopic>,
+    pub forgotten_topics: Vec<ForgottenTopic>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShareFetchTopic {
+    pub topic_id: Uuid,
+    pub partitions: Vec<ShareFetchPartition>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShareFetchPartition {
+    pub partition_index: i32,
+    pub partition_max_bytes: i32,
+    pub acknowledgement_batches: Vec<AcknowledgementBatch>,
+}
+
+/// A range of offsets with either one acknowledge type for the whole range
+/// or one per offset.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AcknowledgementBatch {
+    pub first_offset: i64,
+    pub last_offset: i64,
+    pub acknowledge_types: Vec<i8>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ForgottenTopic {
+    pub topic_id: Uuid,
+    pub partitions: Vec<i32>,
+}
+
+impl ShareFetchRequest {
+    pub fn supports(api_version: i16) -> bool {
+        (MIN_VERSION..=MAX_VERSION).contains(&api_version)
+    }
+
+    pub fn decode(header: RequestHeader, cursor: &mut Cursor<&[u8]>) -> io::Result<Self> {
+        wire::skip_tagged_fields(cursor, true)?;
+
+        let group_id = wire::read_nullable_string(cursor, true)?;
+        let member_id = wire::read_nullable_string(cursor, true)?;
+        let share_session_epoch = primitives::read_i32(cursor)?;
+        let max_wait_ms = primitives::read_i32(cursor)?;
+        let min_bytes = primitives::read_i32(cursor)?;
+        let max_bytes = primitives::read_i32(cursor)?;
+        let topics = wire::read_array(cursor, true, |cursor| {
+            let topic_id = Uuid(primitives::read_uuid(cursor)?);
+            let partitions = wire::read_array(cursor, true, |cursor| {
+                let partition_index = primitives::read_i32(cursor)?;
+                let partition_max_bytes = primitives::read_i32(cursor)?;
+                let acknowledgement_batches = read_acknowledgement_batches(cursor)?;
+                wire::skip_tagged_fields(cursor, true)?;
+                Ok(ShareFetchPartition {
+                    partition_index,
+                    partition_max_bytes,
+                    acknowledgement_batches,
+                })
+            })?
+            .unwrap_or_default();
+            wire::skip_tagged_fields(cursor, true)?;
+            Ok(ShareFetchTopic {
+                topic_id,
+                partitions,
+            })
+        })?
+        .unwrap_or_default();
+        let forgotten_topics = wire::read_array(cursor, true, |cursor| {
+            let topic_id = Uuid(primitives::read_uuid(cursor)?);
+            let partitions =
+                wire::read_array(cursor, true, primitives::read_i32)?.unwrap_or_default();
+            wire::skip_tagged_fields(cursor, true)?;
+            Ok(ForgottenTopic {
+                topic_id,
+                partitions,
+            })
+        })?
+        .unwrap_or_default();
+        wire::skip_tagged_fields(cursor, true)?;
+
+        Ok(Self {
+            header,
+            group_id,
+            member_id,
+            share_session_epoch,
+            max_wait_ms,
+            min_bytes,
+            max_bytes,
+            topics,
+            forgotten_topics,
+        })
+    }
+}
+
+/// Reads the `AcknowledgementBatches` array shared by ShareFetch and
+/// ShareAcknowledge.
+pub fn read_acknowledgement_batches(
+    cursor: &mut Cursor<&[u8]>,
+) -> io::Result<Vec<AcknowledgementBatch>> {
+    Ok(wire::read_array(cursor, true, |cursor| {
+        let first_offset = primitives::read_i64(cursor)?;
+        let last_offset = primitives::read_i64(cursor)?;
+        let acknowledge_types =
+            wire::read_array(cursor, true, primitives::read_i8)?.unwrap_or_default();
+        wire::skip_tagged_fields(cursor, true)?;
+        Ok(AcknowledgementBatch {
+            first_offset,
+            last_offset,
+            acknowledge_types,
+        })
+    })?
+    .unwrap_or_default())
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShareFetchResponse {
+    pub header: ResponseHeader,
+    pub api_version: i16,
+    pub throttle_time_ms: i32,
+    pub error_code: i16,
+    pub error_message: Option<String>,
+    pub responses: Vec<ShareFetchTopicResponse>,
+    pub node_endpoints: Vec<NodeEndpoint>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShareFetchTopicResponse {
+    pub topic_id: Uuid,
+    pub partitions: Vec<ShareFetchPartitionData>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShareFetchPartitionData {
+    pub partition_index: i32,
+    pub error_code: i16,
+    pub error_message: Option<String>,
+    pub acknowledge_error_code: i16,
+    pub acknowledge_error_message: Option<String>,
+    pub current_leader: LeaderIdAndEpoch,
+    pub records: Option<Vec<u8>>,
+    pub acquired_records: Vec<AcquiredRecords>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LeaderIdAndEpoch {
+    pub leader_id: i32,
+    pub leader_epoch: i32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AcquiredRecords {
+    pub first_offset: i64,
+    pub last_offset: i64,
+    pub delivery_count: i16,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NodeEndpoint {
+    pub node_id: i32,
+    pub host: String,
+    pub port: i32,
+    pub rack: Option<String>,
+}
+
+impl ShareFetchResponse {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut body = Vec::new();
+        body.extend_from_slice(&self.throttle_time_ms.to_be_bytes());
+        body.extend_from_slice(&self.error_code.to_be_bytes());
+        wire::write_nullable_string(&mut body, self.error_message.as_deref(), true);
+        wire::write_array(&mut body, &self.responses, true, |buffer, topic| {
+            buffer.extend_from_slice(topic.topic_id.as_bytes());
+            wire::write_array(buffer, &topic.partitions, true, |buffer, partition| {
+                buffer.extend_from_slice(&partition.partition_index.to_be_bytes());
+                buffer.extend_from_slice(&partition.error_code.to_be_bytes());
+                wire::write_nullable_string(buffer, partition.error_message.as_deref(), true);
+                buffer.extend_from_slice(&partition.acknowledge_error_code.to_be_bytes());
+                wire::write_nullable_string(
+                    buffer,
+                    partition.acknowledge_error_message.as_deref(),
+                    true,
+                );
+                write_leader(buffer, partition.current_leader);
+                match &partition.records {
+                    Some(records) => wire::write_bytes(buffer, records, true),
+                    None => wire::write_array_len(buffer, None, true),
+                }
+                wire::write_array(
+                    buffer,
+                    &partition.acquired_records,
+                    true,
+                    |buffer, acquired| {
+                        buffer.extend_from_slice(&acquired.first_offset.to_be_bytes());
+                        buffer.extend_from_slice(&acquired.last_offset.to_be_bytes());
+                        buffer.extend_from_slice(&acquired.delivery_count.to_be_bytes());
+                        wire::write_empty_tagged_fields(buffer, true);
+                    },
+                );
+                wire::write_empty_tagged_fields(buffer, true);
+            });
+            wire::write_empty_tagged_fields(buffer, true);
+        });
+        write_node_endpoints(&mut body, &self.node_endpoints);
+        wire::write_empty_tagged_fields(&mut body, true);
+
+        self.header.frame(true, &body)
+    }
+}
+
+pub fn write_leader(buffer: &mut Vec<u8>, leader: LeaderIdAndEpoch) {
+    buffer.extend_from_slice(&leader.leader_id.to_be_bytes());
+    buffer.extend_from_slice(&leader.leader_epoch.to_be_bytes());
+    wire::write_empty_tagged_fields(buffer, true);
+}
+
+pub fn write_node_endpoints(buffer: &mut Vec<u8>, endpoints: &[NodeEndpoint]) {
+    wire::write_array(buffer, endpoints, true, |buffer, endpoint| {
+        buffer.extend_from_slice(&endpoint.node_id.to_be_bytes());
+        wire::write_string(buffer, &endpoint.host, true);
+        buffer.extend_from_slice(&endpoint.port.to_be_bytes());
+        wire::write_nullable_string(buffer, endpoint.rack.as_deref(), true);
+        wire::write_empty_tagged_fields(buffer, true);
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_acknowledgements_and_forgotten_topics() {
+        let header = RequestHeader {
+            request_api_key: SHARE_FETCH_KEY,
+            request_api_version: 0,
+            correlation_id: 9,
+            client_id: None,
+        };
+        let mut body = vec![0];
+        primitives::write_compact_nullable_string(&mut body, Some("jobs"));
+        primitives::write_compact_nullable_string(&mut body, Some("m-1"));
+        body.extend_from_slice(&2_i32.to_be_bytes());
+        body.extend_from_slice(&500_i32.to_be_bytes());
+        body.extend_from_slice(&1_i32.to_be_bytes());
+        body.extend_from_slice(&1024_i32.to_be_bytes());
+        primitives::write_compact_array_len(&mut body, Some(1));
+        body.extend_from_slice(&[4; 16]);
+        primitives::write_compact_array_len(&mut body, Some(1));
+        body.extend_from_slice(&0_i32.to_be_bytes());
+        body.extend_from_slice(&512_i32.to_be_bytes());
+        primitives::write_compact_array_len(&mut body, Some(1));
+        body.extend_from_slice(&10_i64.to_be_bytes());
+        body.extend_from_slice(&11_i64.to_be_bytes());
+        primitives::write_compact_array_len(&mut body, Some(2));
+        body.extend_from_slice(&[
+            acknowledge_type::ACCEPT as u8,
+            acknowledge_type::RELEASE as u8,
+        ]);
+        body.extend_from_slice(&[0, 0, 0]);
+        primitives::write_compact_array_len(&mut body, Some(1));
+        body.extend_from_slice(&[5; 16]);
+        primitives::write_compact_array_len(&mut body, Some(1));
+        body.extend_from_slice(&3_i32.to_be_bytes());
+        body.extend_from_slice(&[0, 0]);
+
+        let request =
+            ShareFetchRequest::decode(header, &mut Cursor::new(body.as_slice())).expect("decode");
+
+        assert_eq!(request.share_session_epoch, 2);
+        assert_eq!(
+            request.topics[0].partitions[0].acknowledgement_batches,
+            vec![AcknowledgementBatch {
+                first_offset: 10,
+                last_offset: 11,
+                acknowledge_types: vec![acknowledge_type::ACCEPT, acknowledge_type::RELEASE],
+            }]
+        );
+        assert_eq!(
+            request.forgotten_topics,
+            vec![ForgottenTopic {
+                topic_id: Uuid([5; 16]),
+                partitions: vec![3],
+            }]
+        );
+    }
+
+    #[test]
+    fn partitions_without_records_encode_null_records() {
+        let response = ShareFetchResponse {
+            header: ResponseHeader { correlation_id: 9 },
+            api_version: 0,
+            throttle_time_ms: 0,
+            error_code: 0,
+            error_message: None,
+            responses: vec![ShareFetchTopicResponse {
+                topic_id: Uuid([4; 16]),
+                partitions: vec![ShareFetchPartitionData {
+                    partition_index: 0,
+                    error_code: 0,
+                    error_message: None,
+                    acknowledge_error_code: 0,
+                    acknowledge_error_message: None,
+                    current_leader: LeaderIdAndEpoch {
+                        leader_id: 1,
+                        leader_epoch: 0,
+                    },
+                    records: None,
+                    acquired_records: Vec::new(),
+                }],
+            }],
+            node_endpoints: Vec::new(),
+        };
+
+        let bytes = response.to_bytes();
+
+        // ... leader (id, epoch, tags), null records, empty acquired records,
+        // partition tags, topic tags, empty endpoints, response tags.
+        assert_eq!(
+            &bytes[bytes.len() - 15..],
+            &[0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 1, 0, 0, 1, 0][..]
+        );
+    }
+}
@@ -0,0 +1,124 @@
+use super::consumer_group_heartbeat::ConsumerGroupTopicPartitions;
+use super::header::{RequestHeader, ResponseHeader};
+use super::wire;
+use crate::codec::primitives;
+use std::io::{self, Cursor};
+
+pub const SHARE_GROUP_HEARTBEAT_KEY: i16 = 76;
+pub const MIN_VERSION: i16 = 0;
+pub const MAX_VERSION: i16 = 0;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShareGroupHeartbeatRequest {
+    pub header: RequestHeader,
+    pub group_id: String,
+    /// Empty when joining.
+    pub member_id: String,
+    pub member_epoch: i32,
+    pub rack_id: Option<String>,
+    /// `None` if unchanged.
+    pub subscribed_topic_names: Option<Vec<String>>,
+}
+
+impl ShareGroupHeartbeatRequest {
+    pub fn supports(api_version: i16) -> bool {
+        (MIN_VERSION..=MAX_VERSION).contains(&api_version)
+    }
+
+    pub fn decode(header: RequestHeader, cursor: &mut Cursor<&[u8]>) -> io::Result<Self> {
+        wire::skip_tagged_fields(cursor, true)?;
+
+        let group_id = wire::read_string(cursor, true)?;
+        let member_id = wire::read_string(cursor, true)?;
+        let member_epoch = primitives::read_i32(cursor)?;
+        let rack_id = wire::read_nullable_string(cursor, true)?;
+        let subscribed_topic_names =
+            wire::read_array(cursor, true, |cursor| wire::read_string(cursor, true))?;
+        wire::skip_tagged_fields(cursor, true)?;
+
+        Ok(Self {
+            header,
+            group_id,
+            member_id,
+            member_epoch,
+            rack_id,
+            subscribed_topic_names,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShareGroupHeartbeatResponse {
+    pub header: ResponseHeader,
+    pub api_version: i16,
+    pub throttle_time_ms: i32,
+    pub error_code: i16,
+    pub error_message: Option<String>,
+    pub member_id: Option<String>,
+    pub member_epoch: i32,
+    pub heartbeat_interval_ms: i32,
+    /// The member's new assignment, or `None` if it did not change.
+    pub assignment: Option<Vec<ConsumerGroupTopicPartitions>>,
+}
+
+impl ShareGroupHeartbeatResponse {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut body = Vec::new();
+        body.extend_from_slice(&self.throttle_time_ms.to_be_bytes());
+        body.extend_from_slice(&self.error_code.to_be_bytes());
+        wire::write_nullable_string(&mut body, self.error_message.as_deref(), true);
+        wire::write_nullable_string(&mut body, self.member_id.as_deref(), true);
+        body.extend_from_slice(&self.member_epoch.to_be_bytes());
+        body.extend_from_slice(&self.heartbeat_interval_ms.to_be_bytes());
+        match &self.assignment {
+            Some(topics) => {
+                body.push(1);
+                wire::write_array(&mut body, topics, true, |buffer, topic| {
+                    buffer.extend_from_slice(topic.topic_id.as_bytes());
+                    wire::write_array(buffer, &topic.partitions, true, |buffer, partition| {
+                        buffer.extend_from_slice(&partition.to_be_bytes());
+                    });
+                    wire::write_empty_tagged_fields(buffer, true);
+                });
+                wire::write_empty_tagged_fields(&mut body, true);
+            }
+            None => body.push(0xFF),
+        }
+        wire::write_empty_tagged_fields(&mut body, true);
+
+        self.header.frame(true, &body)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_a_join_with_subscribed_topics() {
+        let header = RequestHeader {
+            request_api_key: SHARE_GROUP_HEARTBEAT_KEY,
+            request_api_version: 0,
+            correlation_id: 5,
+            client_id: Some("worker".to_string()),
+        };
+        let mut body = vec![0];
+        primitives::write_compact_string(&mut body, "jobs");
+        primitives::write_compact_string(&mut body, "");
+        body.extend_from_slice(&0_i32.to_be_bytes());
+        primitives::write_compact_nullable_string(&mut body, None);
+        primitives::write_compact_array_len(&mut body, Some(1));
+        primitives::write_compact_string(&mut body, "orders");
+        body.push(0);
+
+        let request = ShareGroupHeartbeatRequest::decode(header, &mut Cursor::new(body.as_slice()))
+            .expect("decode");
+
+        assert_eq!(request.group_id, "jobs");
+        assert_eq!(request.member_epoch, 0);
+        assert_eq!(
+            request.subscribed_topic_names,
+            Some(vec!["orders".to_string()])
+        );
+    }
+}
@@ -0,0 +1,162 @@
+use super::header::{RequestHeader, ResponseHeader};
+use super::wire;
+use crate::codec::primitives;
+use std::io::{self, Cursor};
+
+pub const SYNC_GROUP_KEY: i16 = 14;
+pub const MIN_VERSION: i16 = 0;
+pub const MAX_VERSION: i16 = 5;
+const FIRST_FLEXIBLE_VERSION: i16 = 4;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyncGroupRequest {
+    pub header: RequestHeader,
+    pub group_id: String,
+    pub generation_id: i32,
+    pub member_id: String,
+    pub group_instance_id: Option<String>,
+    /// Checked against the group's protocol when present (version 5+).
+    pub protocol_type: Option<String>,
+    pub protocol_name: Option<String>,
+    /// Assignments computed by the leader; empty for followers.
+    pub assignments: Vec<SyncGroupRequestAssignment>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyncGroupRequestAssignment {
+    pub member_id: String,
+    pub assignment: Vec<u8>,
+}
+
+impl SyncGroupRequest {
+    pub fn supports(api_version: i16) -> bool {
+        (MIN_VERSION..=MAX_VERSION).contains(&api_version)
+    }
+
+    pub fn decode(header: RequestHeader, cursor: &mut Cursor<&[u8]>) -> io::Result<Self> {
+        let version = header.request_api_version;
+        let flexible = version >= FIRST_FLEXIBLE_VERSION;
+        wire::skip_tagged_fields(cursor, flexible)?;
+
+        let group_id = wire::read_string(cursor, flexible)?;
+        let generation_id = primitives::read_i32(cursor)?;
+        let member_id = wire::read_string(cursor, flexible)?;
+        let group_instance_id = if version >= 3 {
+            wire::read_nullable_string(cursor, flexible)?
+        } else {
+            None
+        };
+        let (protocol_type, protocol_name) = if version >= 5 {
+            (
+                wire::read_nullable_string(cursor, flexible)?,
+                wire::read_nullable_string(cursor, flexible)?,
+            )
+        } else {
+            (None, None)
+        };
+        let assignments = wire::read_array(cursor, flexible, |cursor| {
+            let member_id = wire::read_string(cursor, flexible)?;
+            let assignment = wire::read_bytes(cursor, flexible)?;
+            wire::skip_tagged_fields(cursor, flexible)?;
+            Ok(SyncGroupRequestAssignment {
+                member_id,
+                assignment,
+            })
+        })?
+        .unwrap_or_default();
+        wire::skip_tagged_fields(cursor, flexible)?;
+
+        Ok(Self {
+            header,
+            group_id,
+            generation_id,
+            member_id,
+            group_instance_id,
+            protocol_type,
+            protocol_name,
+            assignments,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyncGroupResponse {
+    pub header: ResponseHeader,
+    pub api_version: i16,
+    pub throttle_time_ms: i32,
+    pub error_code: i16,
+    pub protocol_type: Option<String>,
+    pub protocol_name: Option<String>,
+    pub assignment: Vec<u8>,
+}
+
+impl SyncGroupResponse {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let version = self.api_version;
+        let flexible = version >= FIRST_FLEXIBLE_VERSION;
+        let mut body = Vec::new();
+        if version >= 1 {
+            body.extend_from_slice(&self.throttle_time_ms.to_be_bytes());
+        }
+        body.extend_from_slice(&self.error_code.to_be_bytes());
+        if version >= 5 {
+            wire::write_nullable_string(&mut body, self.protocol_type.as_deref(), flexible);
+            wire::write_nullable_string(&mut body, self.protocol_name.as_deref(), flexible);
+        }
+        wire::write_bytes(&mut body, &self.assignment, flexible);
+        wire::write_empty_tagged_fields(&mut body, flexible);
+
+        self.header.frame(flexible, &body)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_leader_assignments() {
+        let header = RequestHeader {
+            request_api_key: SYNC_GROUP_KEY,
+            request_api_version: 5,
+            correlation_id: 8,
+            client_id: None,
+        };
+        let mut body = vec![0];
+        primitives::write_compact_string(&mut body, "payments");
+        body.extend_from_slice(&3_i32.to_be_bytes());
+        primitives::write_compact_string(&mut body, "leader");
+        primitives::write_compact_nullable_string(&mut body, None);
+        primitives::write_compact_nullable_string(&mut body, Some("consumer"));
+        primitives::write_compact_nullable_string(&mut body, Some("range"));
+        primitives::write_compact_array_len(&mut body, Some(1));
+        primitives::write_compact_string(&mut body, "follower");
+        wire::write_bytes(&mut body, &[9], true);
+        body.extend_from_slice(&[0, 0]);
+
+        let request =
+            SyncGroupRequest::decode(header, &mut Cursor::new(body.as_slice())).expect("decode");
+
+        assert_eq!(request.generation_id, 3);
+        assert_eq!(request.protocol_name.as_deref(), Some("range"));
+        assert_eq!(request.assignments[0].member_id, "follower");
+        assert_eq!(request.assignments[0].assignment, [9]);
+    }
+
+    #[test]
+    fn version_zero_response_is_error_and_assignment() {
+        let response = SyncGroupResponse {
+            header: ResponseHeader { correlation_id: 8 },
+            api_version: 0,
+            throttle_time_ms: 0,
+            error_code: 27,
+            protocol_type: None,
+            protocol_name: None,
+            assignment: vec![1, 2],
+        };
+
+        let bytes = response.to_bytes();
+
+        assert_eq!(&bytes[8..], &[0, 27, 0, 0, 0, 2, 1, 2]);
+    }
+}
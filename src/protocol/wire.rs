@@ -0,0 +1,182 @@
+//! Encoding helpers for fields whose wire format depends on whether the
+//! message version is flexible (KIP-482): flexible versions use compact
+//! strings and arrays and end every structure with a tagged field section.
+
+use crate::codec::primitives;
+use std::convert::TryFrom;
+use std::io::{self, Cursor};
+
+pub fn read_string(cursor: &mut Cursor<&[u8]>, flexible: bool) -> io::Result<String> {
+    read_nullable_string(cursor, flexible)?
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "unexpected null string"))
+}
+
+pub fn read_nullable_string(
+    cursor: &mut Cursor<&[u8]>,
+    flexible: bool,
+) -> io::Result<Option<String>> {
+    if flexible {
+        primitives::read_compact_nullable_string(cursor)
+    } else {
+        primitives::read_nullable_string(cursor)
+    }
+}
+
+/// Reads an array length, returning `None` for a null array.
+pub fn read_array_len(cursor: &mut Cursor<&[u8]>, flexible: bool) -> io::Result<Option<usize>> {
+    if flexible {
+        return primitives::read_compact_array_len(cursor);
+    }
+    let length = primitives::read_i32(cursor)?;
+    Ok(usize::try_from(length).ok())
+}
+
+/// Reads `count` elements with `read`, where a null array yields `None`.
+pub fn read_array<T>(
+    cursor: &mut Cursor<&[u8]>,
+    flexible: bool,
+    mut read: impl FnMut(&mut Cursor<&[u8]>) -> io::Result<T>,
+) -> io::Result<Option<Vec<T>>> {
+    let Some(count) = read_array_len(cursor, flexible)? else {
+        return Ok(None);
+    };
+    let remaining = cursor
+        .get_ref()
+        .len()
+        .saturating_sub(cursor.position() as usize);
+    let mut items = Vec::with_capacity(count.min(remaining));
+    for _ in 0..count {
+        items.push(read(cursor)?);
+    }
+    Ok(Some(items))
+}
+
+/// Reads a non-null byte array.
+pub fn read_bytes(cursor: &mut Cursor<&[u8]>, flexible: bool) -> io::Result<Vec<u8>> {
+    read_nullable_bytes(cursor, flexible)?
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "unexpected null bytes"))
+}
+
+/// Reads a byte array, returning `None` for a null one.
+pub fn read_nullable_bytes(
+    cursor: &mut Cursor<&[u8]>,
+    flexible: bool,
+) -> io::Result<Option<Vec<u8>>> {
+    let Some(length) = read_array_len(cursor, flexible)? else {
+        return Ok(None);
+    };
+    let start = cursor.position() as usize;
+    let bytes = cursor
+        .get_ref()
+        .get(start..start.saturating_add(length))
+        .ok_or_else(|| io::Error::new(io::ErrorKind::UnexpectedEof, "bytes field is truncated"))?
+        .to_vec();
+    cursor.set_position((start + length) as u64);
+    Ok(Some(bytes))
+}
+
+/// Skips the tagged field section of a flexible structure.
+pub fn skip_tagged_fields(cursor: &mut Cursor<&[u8]>, flexible: bool) -> io::Result<()> {
+    if flexible {
+        primitives::read_tagged_fields(cursor)?;
+    }
+    Ok(())
+}
+
+pub fn write_string(buffer: &mut Vec<u8>, value: &str, flexible: bool) {
+    write_nullable_string(buffer, Some(value), flexible);
+}
+
+pub fn write_nullable_string(buffer: &mut Vec<u8>, value: Option<&str>, flexible: bool) {
+    if flexible {
+        primitives::write_compact_nullable_string(buffer, value);
+        return;
+    }
+    match value {
+        Some(value) => {
+            let length = i16::try_from(value.len()).expect("string exceeds i16::MAX bytes");
+            buffer.extend_from_slice(&length.to_be_bytes());
+            buffer.extend_from_slice(value.as_bytes());
+        }
+        None => buffer.extend_from_slice(&(-1_i16).to_be_bytes()),
+    }
+}
+
+/// Writes an array length, or the null marker for `None`.
+pub fn write_array_len(buffer: &mut Vec<u8>, length: Option<usize>, flexible: bool) {
+    if flexible {
+        primitives::write_compact_array_len(buffer, length);
+        return;
+    }
+    let length = length.map_or(-1, |length| {
+        i32::try_from(length).expect("array exceeds i32::MAX elements")
+    });
+    buffer.extend_from_slice(&length.to_be_bytes());
+}
+
+/// Writes the length of `items` followed by each item.
+pub fn write_array<T>(
+    buffer: &mut Vec<u8>,
+    items: &[T],
+    flexible: bool,
+    mut write: impl FnMut(&mut Vec<u8>, &T),
+) {
+    write_array_len(buffer, Some(items.len()), flexible);
+    for item in items {
+        write(buffer, item);
+    }
+}
+
+/// Writes a non-null byte array.
+pub fn write_bytes(buffer: &mut Vec<u8>, value: &[u8], flexible: bool) {
+    write_array_len(buffer, Some(value.len()), flexible);
+    buffer.extend_from_slice(value);
+}
+
+/// Writes an empty tagged field section for flexible versions.
+pub fn write_empty_tagged_fields(buffer: &mut Vec<u8>, flexible: bool) {
+    if flexible {
+        primitives::write_tagged_fields(buffer, &[]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strings_and_arrays_round_trip_in_both_encodings() {
+        for flexible in [false, true] {
+            let mut buffer = Vec::new();
+            write_string(&mut buffer, "orders", flexible);
+            write_nullable_string(&mut buffer, None, flexible);
+            write_array(&mut buffer, &[1_i32, 2], flexible, |buffer, value| {
+                buffer.extend_from_slice(&value.to_be_bytes())
+            });
+            write_array_len(&mut buffer, None, flexible);
+            write_bytes(&mut buffer, b"meta", flexible);
+            write_empty_tagged_fields(&mut buffer, flexible);
+
+            let mut cursor = Cursor::new(buffer.as_slice());
+            assert_eq!(
+                read_string(&mut cursor, flexible).expect("string"),
+                "orders"
+            );
+            assert_eq!(
+                read_nullable_string(&mut cursor, flexible).expect("null"),
+                None
+            );
+            assert_eq!(
+                read_array(&mut cursor, flexible, primitives::read_i32).expect("array"),
+                Some(vec![1, 2])
+            );
+            assert_eq!(
+                read_array_len(&mut cursor, flexible).expect("null array"),
+                None
+            );
+            assert_eq!(read_bytes(&mut cursor, flexible).expect("bytes"), b"meta");
+            skip_tagged_fields(&mut cursor, flexible).expect("tagged fields");
+            assert_eq!(cursor.position() as usize, buffer.len());
+        }
+    }
+}
@@ -0,0 +1,931 @@
+use super::quotas::ConnectionLimits;
+use crate::state::GroupConfig;
+use crate::storage::config::{
+    invalid_value, parse_positive, parse_value, CLEANUP_POLICY_CONFIG, DELETE_RETENTION_MS_CONFIG,
+    FLUSH_MESSAGES_CONFIG, FLUSH_MS_CONFIG, INDEX_INTERVAL_BYTES_CONFIG,
+    MIN_CLEANABLE_DIRTY_RATIO_CONFIG, RETENTION_BYTES_CONFIG, RETENTION_MS_CONFIG,
+    SEGMENT_BYTES_CONFIG, SEGMENT_INDEX_BYTES_CONFIG,
+};
+use crate::storage::meta_properties::parse_properties;
+use crate::storage::LogConfig;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+pub const NODE_ID_CONFIG: &str = "node.id";
+pub const LISTENERS_CONFIG: &str = "listeners";
+pub const ADVERTISED_LISTENERS_CONFIG: &str = "advertised.listeners";
+pub const LISTENER_SECURITY_PROTOCOL_MAP_CONFIG: &str = "listener.security.protocol.map";
+pub const LOG_DIRS_CONFIG: &str = "log.dirs";
+pub const LOG_DIR_CONFIG: &str = "log.dir";
+pub const NUM_PARTITIONS_CONFIG: &str = "num.partitions";
+pub const SOCKET_REQUEST_MAX_BYTES_CONFIG: &str = "socket.request.max.bytes";
+pub const LOG_RETENTION_MS_CONFIG: &str = "log.retention.ms";
+pub const LOG_RETENTION_HOURS_CONFIG: &str = "log.retention.hours";
+pub const LOG_RETENTION_MINUTES_CONFIG: &str = "log.retention.minutes";
+pub const LOG_FLUSH_SCHEDULER_INTERVAL_MS_CONFIG: &str = "log.flush.scheduler.interval.ms";
+pub const LOG_RETENTION_CHECK_INTERVAL_MS_CONFIG: &str = "log.retention.check.interval.ms";
+pub const LOG_CLEANER_BACKOFF_MS_CONFIG: &str = "log.cleaner.backoff.ms";
+pub const METADATA_SNAPSHOT_INTERVAL_MS_CONFIG: &str = "metadata.log.max.snapshot.interval.ms";
+pub const SHUTDOWN_DRAIN_TIMEOUT_MS_CONFIG: &str = "shutdown.drain.timeout.ms";
+pub const CONNECTIONS_MAX_IDLE_MS_CONFIG: &str = "connections.max.idle.ms";
+pub const MAX_CONNECTIONS_CONFIG: &str = "max.connections";
+pub const MAX_CONNECTIONS_PER_IP_CONFIG: &str = "max.connections.per.ip";
+pub const MAX_CONNECTIONS_PER_IP_OVERRIDES_CONFIG: &str = "max.connections.per.ip.overrides";
+pub const MAX_CONNECTION_CREATION_RATE_CONFIG: &str = "max.connection.creation.rate";
+pub const MAX_IN_FLIGHT_REQUESTS_PER_CONNECTION_CONFIG: &str =
+    "max.in.flight.requests.per.connection";
+pub const GROUP_MIN_SESSION_TIMEOUT_MS_CONFIG: &str = "group.min.session.timeout.ms";
+pub const GROUP_MAX_SESSION_TIMEOUT_MS_CONFIG: &str = "group.max.session.timeout.ms";
+pub const GROUP_MAX_SIZE_CONFIG: &str = "group.max.size";
+pub const GROUP_CONSUMER_SESSION_TIMEOUT_MS_CONFIG: &str = "group.consumer.session.timeout.ms";
+pub const GROUP_CONSUMER_HEARTBEAT_INTERVAL_MS_CONFIG: &str =
+    "group.consumer.heartbeat.interval.ms";
+pub const GROUP_SHARE_SESSION_TIMEOUT_MS_CONFIG: &str = "group.share.session.timeout.ms";
+pub const GROUP_SHARE_HEARTBEAT_INTERVAL_MS_CONFIG: &str = "group.share.heartbeat.interval.ms";
+pub const GROUP_SHARE_RECORD_LOCK_DURATION_MS_CONFIG: &str = "group.share.record.lock.duration.ms";
+pub const GROUP_SHARE_DELIVERY_COUNT_LIMIT_CONFIG: &str = "group.share.delivery.count.limit";
+pub const OFFSETS_TOPIC_NUM_PARTITIONS_CONFIG: &str = "offsets.topic.num.partitions";
+pub const OFFSETS_RETENTION_MINUTES_CONFIG: &str = "offsets.retention.minutes";
+pub const OFFSETS_RETENTION_CHECK_INTERVAL_MS_CONFIG: &str = "offsets.retention.check.interval.ms";
+pub const OFFSET_METADATA_MAX_BYTES_CONFIG: &str = "offset.metadata.max.bytes";
+
+/// Prefix of environment variables that override broker configs, as in
+/// `KAFKA_LOG_RETENTION_MS` for `log.retention.ms`.
+pub const ENV_PREFIX: &str = "KAFKA_";
+
+const DEFAULT_LISTENERS: &str = "PLAINTEXT://127.0.0.1:9092";
+const DEFAULT_SECURITY_PROTOCOL_MAP: &str =
+    "PLAINTEXT:PLAINTEXT,SSL:SSL,SASL_PLAINTEXT:SASL_PLAINTEXT,SASL_SSL:SASL_SSL";
+const DEFAULT_LOG_DIR: &str = "/tmp/kraft-combined-logs";
+
+/// Broker configs that set the default of a topic-level log config.
+const LOG_CONFIG_KEYS: &[(&str, &str)] = &[
+    ("log.segment.bytes", SEGMENT_BYTES_CONFIG),
+    ("log.index.size.max.bytes", SEGMENT_INDEX_BYTES_CONFIG),
+    ("log.index.interval.bytes", INDEX_INTERVAL_BYTES_CONFIG),
+    ("log.flush.interval.messages", FLUSH_MESSAGES_CONFIG),
+    ("log.flush.interval.ms", FLUSH_MS_CONFIG),
+    (LOG_RETENTION_MS_CONFIG, RETENTION_MS_CONFIG),
+    ("log.retention.bytes", RETENTION_BYTES_CONFIG),
+    ("log.cleanup.policy", CLEANUP_POLICY_CONFIG),
+    (
+        "log.cleaner.delete.retention.ms",
+        DELETE_RETENTION_MS_CONFIG,
+    ),
+    (
+        "log.cleaner.min.cleanable.ratio",
+        MIN_CLEANABLE_DIRTY_RATIO_CONFIG,
+    ),
+];
+
+const OTHER_KEYS: &[&str] = &[
+    NODE_ID_CONFIG,
+    LISTENERS_CONFIG,
+    ADVERTISED_LISTENERS_CONFIG,
+    LISTENER_SECURITY_PROTOCOL_MAP_CONFIG,
+    LOG_DIRS_CONFIG,
+    LOG_DIR_CONFIG,
+    NUM_PARTITIONS_CONFIG,
+    SOCKET_REQUEST_MAX_BYTES_CONFIG,
+    LOG_RETENTION_HOURS_CONFIG,
+    LOG_RETENTION_MINUTES_CONFIG,
+    LOG_FLUSH_SCHEDULER_INTERVAL_MS_CONFIG,
+    LOG_RETENTION_CHECK_INTERVAL_MS_CONFIG,
+    LOG_CLEANER_BACKOFF_MS_CONFIG,
+    METADATA_SNAPSHOT_INTERVAL_MS_CONFIG,
+    SHUTDOWN_DRAIN_TIMEOUT_MS_CONFIG,
+    CONNECTIONS_MAX_IDLE_MS_CONFIG,
+    MAX_CONNECTIONS_CONFIG,
+    MAX_CONNECTIONS_PER_IP_CONFIG,
+    MAX_CONNECTIONS_PER_IP_OVERRIDES_CONFIG,
+    MAX_CONNECTION_CREATION_RATE_CONFIG,
+    MAX_IN_FLIGHT_REQUESTS_PER_CONNECTION_CONFIG,
+    GROUP_MIN_SESSION_TIMEOUT_MS_CONFIG,
+    GROUP_MAX_SESSION_TIMEOUT_MS_CONFIG,
+    GROUP_MAX_SIZE_CONFIG,
+    GROUP_CONSUMER_SESSION_TIMEOUT_MS_CONFIG,
+    GROUP_CONSUMER_HEARTBEAT_INTERVAL_MS_CONFIG,
+    GROUP_SHARE_SESSION_TIMEOUT_MS_CONFIG,
+    GROUP_SHARE_HEARTBEAT_INTERVAL_MS_CONFIG,
+    GROUP_SHARE_RECORD_LOCK_DURATION_MS_CONFIG,
+    GROUP_SHARE_DELIVERY_COUNT_LIMIT_CONFIG,
+    OFFSETS_TOPIC_NUM_PARTITIONS_CONFIG,
+    OFFSETS_RETENTION_MINUTES_CONFIG,
+    OFFSETS_RETENTION_CHECK_INTERVAL_MS_CONFIG,
+    OFFSET_METADATA_MAX_BYTES_CONFIG,
+];
+
+/// How clients authenticate and encrypt traffic on a listener.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SecurityProtocol {
+    Plaintext,
+    Ssl,
+    SaslPlaintext,
+    SaslSsl,
+}
+
+impl SecurityProtocol {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Plaintext => "PLAINTEXT",
+            Self::Ssl => "SSL",
+            Self::SaslPlaintext => "SASL_PLAINTEXT",
+            Self::SaslSsl => "SASL_SSL",
+        }
+    }
+
+    fn parse(name: &str) -> Option<Self> {
+        [
+            Self::Plaintext,
+            Self::Ssl,
+            Self::SaslPlaintext,
+            Self::SaslSsl,
+        ]
+        .into_iter()
+        .find(|protocol| protocol.name().eq_ignore_ascii_case(name))
+    }
+}
+
+/// Where a listener accepts connections.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ListenerAddress {
+    /// Host name or IP address, empty to bind every IPv4 interface, and port.
+    Tcp { host: String, port: u16 },
+    /// Path of a Unix domain socket, written as `NAME:///path/to/socket`.
+    Unix(PathBuf),
+}
+
+impl ListenerAddress {
+    /// Whether both addresses would bind the same port or socket path.
+    fn conflicts_with(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Tcp { port, .. }, Self::Tcp { port: other, .. }) => *port != 0 && port == other,
+            (Self::Unix(path), Self::Unix(other)) => path == other,
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for ListenerAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Tcp { host, port } if host.contains(':') => write!(f, "[{host}]:{port}"),
+            Self::Tcp { host, port } => write!(f, "{host}:{port}"),
+            Self::Unix(path) => write!(f, "unix:{}", path.display()),
+        }
+    }
+}
+
+/// A listener address such as `PLAINTEXT://localhost:9092`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Endpoint {
+    pub listener_name: String,
+    pub address: ListenerAddress,
+    pub security_protocol: SecurityProtocol,
+}
+
+impl Endpoint {
+    /// Parses a comma-separated list of `NAME://host:port` entries, where an
+    /// IPv6 host is written in brackets and `NAME:///path` is a Unix domain
+    /// socket, taking each listener's security protocol from `protocols`.
+    pub fn parse_list(
+        key: &str,
+        value: &str,
+        protocols: &BTreeMap<String, SecurityProtocol>,
+    ) -> io::Result<Vec<Self>> {
+        let mut endpoints: Vec<Self> = Vec::new();
+        for entry in value
+            .split(',')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+        {
+            let (listener_name, address) =
+                Self::parse(entry).ok_or_else(|| invalid_value(key, value))?;
+            let security_protocol = *protocols.get(&listener_name).ok_or_else(|| {
+                invalid_config(
+                    LISTENER_SECURITY_PROTOCOL_MAP_CONFIG,
+                    &format!(
+                        "no security protocol is mapped for listener {listener_name} of {key}"
+                    ),
+                )
+            })?;
+            let endpoint = Self {
+                listener_name,
+                address,
+                security_protocol,
+            };
+            if endpoints
+                .iter()
+                .any(|other| other.listener_name == endpoint.listener_name)
+            {
+                return Err(invalid_config(
+                    key,
+                    &format!("listener name {} is used twice", endpoint.listener_name),
+                ));
+            }
+            endpoints.push(endpoint);
+        }
+        if endpoints.is_empty() {
+            return Err(invalid_config(key, "at least one listener is required"));
+        }
+        Ok(endpoints)
+    }
+
+    fn parse(entry: &str) -> Option<(String, ListenerAddress)> {
+        let (name, address) = entry.split_once("://")?;
+        let valid_name = !name.is_empty()
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+        if !valid_name {
+            return None;
+        }
+        let name = name.to_ascii_uppercase();
+        if address.starts_with('/') {
+            return Some((name, ListenerAddress::Unix(PathBuf::from(address))));
+        }
+        let (host, port) = address.rsplit_once(':')?;
+        let host = match host.strip_prefix('[') {
+            Some(bracketed) => bracketed.strip_suffix(']')?,
+            None => host,
+        };
+        let address = ListenerAddress::Tcp {
+            host: host.to_string(),
+            port: port.parse().ok()?,
+        };
+        Some((name, address))
+    }
+
+    /// Address to bind, using the IPv4 wildcard for an empty host; `[::]`
+    /// binds every IPv6 interface and, on dual-stack hosts, IPv4 as well.
+    pub fn bind_address(&self) -> String {
+        match &self.address {
+            ListenerAddress::Tcp { host, port } if host.is_empty() => format!("0.0.0.0:{port}"),
+            address => address.to_string(),
+        }
+    }
+}
+
+/// Settings of a broker, read from a `server.properties` style file, the
+/// environment and command-line overrides.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BrokerConfig {
+    pub node_id: i32,
+    pub listeners: Vec<Endpoint>,
+    pub advertised_listeners: Vec<Endpoint>,
+    pub log_dir: PathBuf,
+    /// Partition count of topics created without an explicit count.
+    pub num_partitions: i32,
+    /// Defaults for topic-level log configs.
+    pub log_config: LogConfig,
+    /// Largest request the broker accepts before closing the connection.
+    pub socket_request_max_bytes: usize,
+    pub log_flush_check_interval: Duration,
+    pub log_retention_check_interval: Duration,
+    pub log_cleaner_backoff: Duration,
+    pub metadata_snapshot_interval: Duration,
+    /// How long shutdown waits for in-flight requests before giving up on them.
+    pub shutdown_drain_timeout: Duration,
+    /// Connections without a request for this long are closed.
+    pub connections_max_idle: Duration,
+    pub connection_limits: ConnectionLimits,
+    /// Requests read from one connection before their responses are written.
+    pub max_in_flight_requests_per_connection: usize,
+    pub group_config: GroupConfig,
+    pub offsets_retention_check_interval: Duration,
+}
+
+impl Default for BrokerConfig {
+    fn default() -> Self {
+        Self::from_properties(&BTreeMap::new()).expect("default configs are valid")
+    }
+}
+
+impl BrokerConfig {
+    /// Builds the config from `path`, if given, then `env` variables with the
+    /// [`ENV_PREFIX`], then `--override` style `key=value` strings, each source
+    /// taking precedence over the previous one.
+    pub fn load(
+        path: Option<&Path>,
+        env: impl IntoIterator<Item = (String, String)>,
+        overrides: &[String],
+    ) -> io::Result<Self> {
+        let mut properties = match path {
+            Some(path) => parse_properties(&fs::read_to_string(path).map_err(|err| {
+                io::Error::new(
+                    err.kind(),
+                    format!("cannot read config file {}: {err}", path.display()),
+                )
+            })?),
+            None => BTreeMap::new(),
+        };
+        properties.extend(env_properties(env));
+        for entry in overrides {
+            let (key, value) = entry.split_once('=').ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("override '{entry}' is not of the form key=value"),
+                )
+            })?;
+            properties.insert(key.trim().to_string(), value.trim().to_string());
+        }
+        Self::from_properties(&properties)
+    }
+
+    /// Validates `properties` and fills in defaults for missing keys.
+    ///
+    /// Unknown keys are reported and ignored; invalid values are errors naming
+    /// the offending key.
+    pub fn from_properties(properties: &BTreeMap<String, String>) -> io::Result<Self> {
+        for key in properties.keys().filter(|key| !is_known_key(key)) {
+            eprintln!("ignoring unknown configuration {key}");
+        }
+        let get = |key: &str| properties.get(key).map(String::as_str);
+
+        let node_id = match get(NODE_ID_CONFIG) {
+            Some(value) => parse_value(NODE_ID_CONFIG, value)?,
+            None => 1,
+        };
+        if node_id < 0 {
+            return Err(invalid_value(NODE_ID_CONFIG, &node_id.to_string()));
+        }
+
+        let protocols = parse_security_protocol_map(
+            get(LISTENER_SECURITY_PROTOCOL_MAP_CONFIG).unwrap_or(DEFAULT_SECURITY_PROTOCOL_MAP),
+        )?;
+        let listeners = Endpoint::parse_list(
+            LISTENERS_CONFIG,
+            get(LISTENERS_CONFIG).unwrap_or(DEFAULT_LISTENERS),
+            &protocols,
+        )?;
+        for (index, listener) in listeners.iter().enumerate() {
+            let shared = listeners[..index]
+                .iter()
+                .find(|other| listener.address.conflicts_with(&other.address));
+            if let Some(other) = shared {
+                return Err(invalid_config(
+                    LISTENERS_CONFIG,
+                    &format!(
+                        "listeners {} and {} both use {}",
+                        other.listener_name, listener.listener_name, listener.address
+                    ),
+                ));
+            }
+        }
+        let advertised_listeners = match get(ADVERTISED_LISTENERS_CONFIG) {
+            Some(value) => Endpoint::parse_list(ADVERTISED_LISTENERS_CONFIG, value, &protocols)?,
+            None => listeners.clone(),
+        };
+        for advertised in &advertised_listeners {
+            if !listeners
+                .iter()
+                .any(|listener| listener.listener_name == advertised.listener_name)
+            {
+                return Err(invalid_config(
+                    ADVERTISED_LISTENERS_CONFIG,
+                    &format!(
+                        "listener {} is not in {LISTENERS_CONFIG}",
+                        advertised.listener_name
+                    ),
+                ));
+            }
+            let wildcard = matches!(
+                &advertised.address,
+                ListenerAddress::Tcp { host, .. } if matches!(host.as_str(), "" | "0.0.0.0" | "::")
+            );
+            if wildcard {
+                return Err(invalid_config(
+                    ADVERTISED_LISTENERS_CONFIG,
+                    &format!(
+                        "listener {} needs a routable host to advertise",
+                        advertised.listener_name
+                    ),
+                ));
+            }
+        }
+
+        let log_dirs = get(LOG_DIRS_CONFIG)
+            .map(|value| (LOG_DIRS_CONFIG, value))
+            .or_else(|| get(LOG_DIR_CONFIG).map(|value| (LOG_DIR_CONFIG, value)));
+        let log_dir = match log_dirs {
+            Some((key, value)) => {
+                let dirs: Vec<&str> = value
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|dir| !dir.is_empty())
+                    .collect();
+                match dirs.as_slice() {
+                    [dir] => PathBuf::from(dir),
+                    [] => return Err(invalid_value(key, value)),
+                    _ => {
+                        return Err(invalid_config(
+                            key,
+                            "only a single log directory is supported",
+                        ))
+                    }
+                }
+            }
+            None => PathBuf::from(DEFAULT_LOG_DIR),
+        };
+
+        let millis = |key: &str, default: u64| -> io::Result<Duration> {
+            match get(key) {
+                Some(value) => parse_positive(key, value).map(Duration::from_millis),
+                None => Ok(Duration::from_millis(default)),
+            }
+        };
+
+        Ok(Self {
+            node_id,
+            listeners,
+            advertised_listeners,
+            log_dir,
+            num_partitions: match get(NUM_PARTITIONS_CONFIG) {
+                Some(value) => parse_positive(NUM_PARTITIONS_CONFIG, value)?,
+                None => 1,
+            },
+            log_config: log_config(properties)?,
+            socket_request_max_bytes: match get(SOCKET_REQUEST_MAX_BYTES_CONFIG) {
+                Some(value) => parse_positive(SOCKET_REQUEST_MAX_BYTES_CONFIG, value)?,
+                None => 100 * 1024 * 1024,
+            },
+            log_flush_check_interval: millis(LOG_FLUSH_SCHEDULER_INTERVAL_MS_CONFIG, 1_000)?,
+            log_retention_check_interval: millis(
+                LOG_RETENTION_CHECK_INTERVAL_MS_CONFIG,
+                5 * 60 * 1_000,
+            )?,
+            log_cleaner_backoff: millis(LOG_CLEANER_BACKOFF_MS_CONFIG, 15_000)?,
+            metadata_snapshot_interval: millis(
+                METADATA_SNAPSHOT_INTERVAL_MS_CONFIG,
+                60 * 60 * 1_000,
+            )?,
+            shutdown_drain_timeout: millis(SHUTDOWN_DRAIN_TIMEOUT_MS_CONFIG, 30_000)?,
+            connections_max_idle: millis(CONNECTIONS_MAX_IDLE_MS_CONFIG, 10 * 60 * 1_000)?,
+            connection_limits: connection_limits(properties)?,
+            max_in_flight_requests_per_connection: match get(
+                MAX_IN_FLIGHT_REQUESTS_PER_CONNECTION_CONFIG,
+            ) {
+                Some(value) => parse_positive(MAX_IN_FLIGHT_REQUESTS_PER_CONNECTION_CONFIG, value)?,
+                None => 5,
+            },
+            group_config: group_config(properties)?,
+            offsets_retention_check_interval: millis(
+                OFFSETS_RETENTION_CHECK_INTERVAL_MS_CONFIG,
+                10 * 60 * 1_000,
+            )?,
+        })
+    }
+}
+
+/// Reads the `group.*` limits and `offsets.*` settings of the group
+/// coordinator.
+fn group_config(properties: &BTreeMap<String, String>) -> io::Result<GroupConfig> {
+    let mut config = GroupConfig::default();
+    if let Some(value) = properties.get(GROUP_MIN_SESSION_TIMEOUT_MS_CONFIG) {
+        config.min_session_timeout_ms = parse_positive(GROUP_MIN_SESSION_TIMEOUT_MS_CONFIG, value)?;
+    }
+    if let Some(value) = properties.get(GROUP_MAX_SESSION_TIMEOUT_MS_CONFIG) {
+        config.max_session_timeout_ms = parse_positive(GROUP_MAX_SESSION_TIMEOUT_MS_CONFIG, value)?;
+    }
+    if let Some(value) = properties.get(GROUP_MAX_SIZE_CONFIG) {
+        config.max_size = parse_positive(GROUP_MAX_SIZE_CONFIG, value)?;
+    }
+    if let Some(value) = properties.get(GROUP_CONSUMER_SESSION_TIMEOUT_MS_CONFIG) {
+        config.consumer_session_timeout_ms =
+            parse_positive(GROUP_CONSUMER_SESSION_TIMEOUT_MS_CONFIG, value)?;
+    }
+    if let Some(value) = properties.get(GROUP_CONSUMER_HEARTBEAT_INTERVAL_MS_CONFIG) {
+        config.consumer_heartbeat_interval_ms =
+            parse_positive(GROUP_CONSUMER_HEARTBEAT_INTERVAL_MS_CONFIG, value)?;
+    }
+    if let Some(value) = properties.get(GROUP_SHARE_SESSION_TIMEOUT_MS_CONFIG) {
+        config.share_session_timeout_ms =
+            parse_positive(GROUP_SHARE_SESSION_TIMEOUT_MS_CONFIG, value)?;
+    }
+    if let Some(value) = properties.get(GROUP_SHARE_HEARTBEAT_INTERVAL_MS_CONFIG) {
+        config.share_heartbeat_interval_ms =
+            parse_positive(GROUP_SHARE_HEARTBEAT_INTERVAL_MS_CONFIG, value)?;
+    }
+    if let Some(value) = properties.get(GROUP_SHARE_RECORD_LOCK_DURATION_MS_CONFIG) {
+        config.share_record_lock_duration_ms =
+            parse_positive(GROUP_SHARE_RECORD_LOCK_DURATION_MS_CONFIG, value)?;
+    }
+    if let Some(value) = properties.get(GROUP_SHARE_DELIVERY_COUNT_LIMIT_CONFIG) {
+        config.share_delivery_count_limit =
+            parse_positive(GROUP_SHARE_DELIVERY_COUNT_LIMIT_CONFIG, value)?;
+    }
+    if let Some(value) = properties.get(OFFSETS_TOPIC_NUM_PARTITIONS_CONFIG) {
+        config.offsets_topic_partitions =
+            parse_positive(OFFSETS_TOPIC_NUM_PARTITIONS_CONFIG, value)?;
+    }
+    if let Some(value) = properties.get(OFFSETS_RETENTION_MINUTES_CONFIG) {
+        let minutes: i64 = parse_positive(OFFSETS_RETENTION_MINUTES_CONFIG, value)?;
+        config.offsets_retention_ms = minutes
+            .checked_mul(60 * 1_000)
+            .ok_or_else(|| invalid_value(OFFSETS_RETENTION_MINUTES_CONFIG, value))?;
+    }
+    if let Some(value) = properties.get(OFFSET_METADATA_MAX_BYTES_CONFIG) {
+        config.offset_metadata_max_bytes = parse_value(OFFSET_METADATA_MAX_BYTES_CONFIG, value)?;
+    }
+    if config.min_session_timeout_ms > config.max_session_timeout_ms {
+        return Err(invalid_config(
+            GROUP_MAX_SESSION_TIMEOUT_MS_CONFIG,
+            &format!(
+                "must not be below {GROUP_MIN_SESSION_TIMEOUT_MS_CONFIG}={}",
+                config.min_session_timeout_ms
+            ),
+        ));
+    }
+    if config.consumer_heartbeat_interval_ms >= config.consumer_session_timeout_ms {
+        return Err(invalid_config(
+            GROUP_CONSUMER_HEARTBEAT_INTERVAL_MS_CONFIG,
+            &format!(
+                "must be below {GROUP_CONSUMER_SESSION_TIMEOUT_MS_CONFIG}={}",
+                config.consumer_session_timeout_ms
+            ),
+        ));
+    }
+    if config.share_heartbeat_interval_ms >= config.share_session_timeout_ms {
+        return Err(invalid_config(
+            GROUP_SHARE_HEARTBEAT_INTERVAL_MS_CONFIG,
+            &format!(
+                "must be below {GROUP_SHARE_SESSION_TIMEOUT_MS_CONFIG}={}",
+                config.share_session_timeout_ms
+            ),
+        ));
+    }
+    Ok(config)
+}
+
+/// Reads the `max.connection*` limits, where per-IP overrides are written as
+/// `ip:count` pairs.
+fn connection_limits(properties: &BTreeMap<String, String>) -> io::Result<ConnectionLimits> {
+    let mut limits = ConnectionLimits::default();
+    if let Some(value) = properties.get(MAX_CONNECTIONS_CONFIG) {
+        limits.max_connections = parse_positive(MAX_CONNECTIONS_CONFIG, value)?;
+    }
+    if let Some(value) = properties.get(MAX_CONNECTIONS_PER_IP_CONFIG) {
+        limits.max_connections_per_ip = parse_value(MAX_CONNECTIONS_PER_IP_CONFIG, value)?;
+    }
+    if let Some(value) = properties.get(MAX_CONNECTIONS_PER_IP_OVERRIDES_CONFIG) {
+        let key = MAX_CONNECTIONS_PER_IP_OVERRIDES_CONFIG;
+        for entry in value
+            .split(',')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+        {
+            let (ip, count) = entry
+                .rsplit_once(':')
+                .ok_or_else(|| invalid_value(key, value))?;
+            let ip = ip
+                .trim_start_matches('[')
+                .trim_end_matches(']')
+                .parse()
+                .map_err(|_| invalid_config(key, &format!("{ip} is not an IP address")))?;
+            limits
+                .max_connections_per_ip_overrides
+                .insert(ip, parse_value(key, count)?);
+        }
+    }
+    if let Some(value) = properties.get(MAX_CONNECTION_CREATION_RATE_CONFIG) {
+        limits.max_connection_creation_rate =
+            parse_positive(MAX_CONNECTION_CREATION_RATE_CONFIG, value)?;
+    }
+    if limits.max_connections_per_ip == 0 && limits.max_connections_per_ip_overrides.is_empty() {
+        return Err(invalid_config(
+            MAX_CONNECTIONS_PER_IP_CONFIG,
+            &format!(
+                "0 refuses every client unless {MAX_CONNECTIONS_PER_IP_OVERRIDES_CONFIG} is set"
+            ),
+        ));
+    }
+    Ok(limits)
+}
+
+/// Translates the `log.*` broker configs into a default [`LogConfig`].
+///
+/// `log.retention.ms` takes precedence over `log.retention.minutes`, which
+/// takes precedence over `log.retention.hours`.
+fn log_config(properties: &BTreeMap<String, String>) -> io::Result<LogConfig> {
+    let mut config = LogConfig::default();
+    for (broker_key, topic_key) in LOG_CONFIG_KEYS {
+        if let Some(value) = properties.get(*broker_key) {
+            config = apply_log_config(config, broker_key, topic_key, value)?;
+        }
+    }
+
+    if !properties.contains_key(LOG_RETENTION_MS_CONFIG) {
+        let coarse_retention = [
+            (LOG_RETENTION_MINUTES_CONFIG, 60 * 1_000),
+            (LOG_RETENTION_HOURS_CONFIG, 60 * 60 * 1_000),
+        ]
+        .into_iter()
+        .find_map(|(key, unit_ms)| Some((key, properties.get(key)?, unit_ms)));
+        if let Some((key, value, unit_ms)) = coarse_retention {
+            let retention: i64 = parse_value(key, value)?;
+            let retention_ms = match retention {
+                -1 => -1,
+                retention => retention
+                    .checked_mul(unit_ms)
+                    .ok_or_else(|| invalid_value(key, value))?,
+            };
+            config = apply_log_config(config, key, RETENTION_MS_CONFIG, &retention_ms.to_string())
+                .map_err(|_| invalid_value(key, value))?;
+        }
+    }
+    Ok(config)
+}
+
+fn apply_log_config(
+    config: LogConfig,
+    broker_key: &str,
+    topic_key: &str,
+    value: &str,
+) -> io::Result<LogConfig> {
+    config
+        .with_overrides(&BTreeMap::from([(
+            topic_key.to_string(),
+            value.to_string(),
+        )]))
+        .map_err(|_| invalid_value(broker_key, value))
+}
+
+/// Parses a comma-separated list of `LISTENER_NAME:PROTOCOL` pairs.
+fn parse_security_protocol_map(value: &str) -> io::Result<BTreeMap<String, SecurityProtocol>> {
+    let key = LISTENER_SECURITY_PROTOCOL_MAP_CONFIG;
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            let (name, protocol) = entry
+                .split_once(':')
+                .ok_or_else(|| invalid_value(key, value))?;
+            let protocol = SecurityProtocol::parse(protocol.trim())
+                .ok_or_else(|| invalid_value(key, value))?;
+            Ok((name.trim().to_ascii_uppercase(), protocol))
+        })
+        .collect()
+}
+
+fn is_known_key(key: &str) -> bool {
+    OTHER_KEYS.contains(&key)
+        || LOG_CONFIG_KEYS
+            .iter()
+            .any(|(broker_key, _)| *broker_key == key)
+}
+
+/// Maps `KAFKA_`-prefixed environment variables to config keys: the rest of
+/// the name is lower-cased, `___` becomes `-`, `__` becomes `_` and `_`
+/// becomes `.`.
+fn env_properties(
+    env: impl IntoIterator<Item = (String, String)>,
+) -> impl Iterator<Item = (String, String)> {
+    env.into_iter().filter_map(|(name, value)| {
+        let key = name
+            .strip_prefix(ENV_PREFIX)?
+            .to_ascii_lowercase()
+            .replace("___", "-")
+            .replace("__", "\0")
+            .replace('_', ".")
+            .replace('\0', "_");
+        is_known_key(&key).then_some((key, value))
+    })
+}
+
+fn invalid_config(key: &str, reason: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidInput,
+        format!("invalid configuration {key}: {reason}"),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn properties(entries: &[(&str, &str)]) -> BTreeMap<String, String> {
+        entries
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn later_sources_override_earlier_ones() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let path = dir.path().join("server.properties");
+        fs::write(
+            &path,
+            "# broker\nnode.id=3\nnum.partitions=4\nlog.dirs=/data/kafka\nlisteners=PLAINTEXT://:9093\nadvertised.listeners=PLAINTEXT://broker-3:9093\n",
+        )
+        .expect("write config");
+        let env = [
+            ("KAFKA_NUM_PARTITIONS".to_string(), "6".to_string()),
+            ("KAFKA_NODE_ID".to_string(), "5".to_string()),
+            ("KAFKA_HOME".to_string(), "/opt/kafka".to_string()),
+            ("PATH".to_string(), "/usr/bin".to_string()),
+        ];
+
+        let config =
+            BrokerConfig::load(Some(&path), env, &["node.id=7".to_string()]).expect("load config");
+
+        assert_eq!(config.node_id, 7);
+        assert_eq!(config.num_partitions, 6);
+        assert_eq!(config.log_dir, PathBuf::from("/data/kafka"));
+        assert_eq!(config.listeners[0].bind_address(), "0.0.0.0:9093");
+        assert_eq!(
+            config.advertised_listeners[0].address.to_string(),
+            "broker-3:9093"
+        );
+    }
+
+    #[test]
+    fn env_names_map_to_dotted_keys() {
+        let env = [
+            ("KAFKA_LOG_RETENTION_MS".to_string(), "1000".to_string()),
+            ("KAFKA_LOG_CLEANUP__POLICY".to_string(), "x".to_string()),
+        ];
+        let keys: Vec<_> = env_properties(env).map(|(key, _)| key).collect();
+        assert_eq!(keys, ["log.retention.ms"]);
+    }
+
+    #[test]
+    fn log_configs_become_topic_defaults() {
+        let config = BrokerConfig::from_properties(&properties(&[
+            ("log.retention.hours", "2"),
+            ("log.retention.minutes", "30"),
+            ("log.segment.bytes", "1048576"),
+            ("log.cleanup.policy", "compact"),
+        ]))
+        .expect("valid config");
+
+        assert_eq!(config.log_config.retention_ms, Some(30 * 60 * 1_000));
+        assert_eq!(config.log_config.segment_bytes, 1_048_576);
+        assert!(config.log_config.cleanup_policy.compact);
+
+        let config = BrokerConfig::from_properties(&properties(&[
+            ("log.retention.hours", "-1"),
+            ("log.retention.ms", "500"),
+        ]))
+        .expect("valid config");
+        assert_eq!(config.log_config.retention_ms, Some(500));
+    }
+
+    #[test]
+    fn invalid_values_name_the_key() {
+        for (key, value) in [
+            (NODE_ID_CONFIG, "one"),
+            (LISTENERS_CONFIG, "localhost:9092"),
+            (LISTENERS_CONFIG, "PLAINTEXT://:9092,SSL://:9092"),
+            (LISTENERS_CONFIG, "EXTERNAL://:9092"),
+            (
+                LISTENERS_CONFIG,
+                "PLAINTEXT:///tmp/k.sock,SSL:///tmp/k.sock",
+            ),
+            (LISTENER_SECURITY_PROTOCOL_MAP_CONFIG, "PLAINTEXT:TLS"),
+            (ADVERTISED_LISTENERS_CONFIG, "PLAINTEXT://0.0.0.0:9092"),
+            (ADVERTISED_LISTENERS_CONFIG, "EXTERNAL://broker:9092"),
+            (LOG_DIRS_CONFIG, "/a,/b"),
+            (NUM_PARTITIONS_CONFIG, "0"),
+            (LOG_RETENTION_MS_CONFIG, "-5"),
+            (LOG_RETENTION_HOURS_CONFIG, "soon"),
+            ("log.cleanup.policy", "archive"),
+            (LOG_CLEANER_BACKOFF_MS_CONFIG, "0"),
+            (MAX_CONNECTIONS_PER_IP_CONFIG, "0"),
+            (MAX_CONNECTIONS_PER_IP_OVERRIDES_CONFIG, "broker-1:5"),
+            (MAX_CONNECTION_CREATION_RATE_CONFIG, "0"),
+            (MAX_IN_FLIGHT_REQUESTS_PER_CONNECTION_CONFIG, "0"),
+            (GROUP_MAX_SESSION_TIMEOUT_MS_CONFIG, "5000"),
+            (GROUP_MAX_SIZE_CONFIG, "0"),
+            (GROUP_CONSUMER_SESSION_TIMEOUT_MS_CONFIG, "0"),
+            (GROUP_CONSUMER_HEARTBEAT_INTERVAL_MS_CONFIG, "60000"),
+            (GROUP_SHARE_HEARTBEAT_INTERVAL_MS_CONFIG, "60000"),
+            (GROUP_SHARE_DELIVERY_COUNT_LIMIT_CONFIG, "0"),
+            (OFFSETS_RETENTION_MINUTES_CONFIG, "-5"),
+        ] {
+            let err = BrokerConfig::from_properties(&properties(&[(key, value)]))
+                .expect_err("invalid config");
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput, "{key}={value}");
+            assert!(err.to_string().contains(key), "{err} should name {key}");
+        }
+
+        let err = BrokerConfig::load(None, [], &["node.id".to_string()]).expect_err("no value");
+        assert!(err.to_string().contains("key=value"));
+    }
+
+    #[test]
+    fn ipv6_listener_hosts_are_bracketed() {
+        let protocols =
+            parse_security_protocol_map(DEFAULT_SECURITY_PROTOCOL_MAP).expect("default map");
+        let endpoints =
+            Endpoint::parse_list(LISTENERS_CONFIG, "plaintext://[::1]:9092", &protocols)
+                .expect("valid listener");
+
+        assert_eq!(endpoints[0].listener_name, "PLAINTEXT");
+        assert_eq!(
+            endpoints[0].address,
+            ListenerAddress::Tcp {
+                host: "::1".to_string(),
+                port: 9092
+            }
+        );
+        assert_eq!(endpoints[0].bind_address(), "[::1]:9092");
+    }
+
+    #[test]
+    fn listeners_take_their_protocol_from_the_map() {
+        let config = BrokerConfig::from_properties(&properties(&[
+            (
+                LISTENERS_CONFIG,
+                "INTERNAL://:9092,EXTERNAL://:9093,SASL_SSL://:9094",
+            ),
+            (
+                ADVERTISED_LISTENERS_CONFIG,
+                "INTERNAL://broker-1.local:9092,EXTERNAL://203.0.113.7:19093",
+            ),
+            (
+                LISTENER_SECURITY_PROTOCOL_MAP_CONFIG,
+                "INTERNAL:PLAINTEXT,EXTERNAL:plaintext,SASL_SSL:SASL_SSL",
+            ),
+        ]))
+        .expect("valid config");
+
+        let protocols: Vec<_> = config
+            .listeners
+            .iter()
+            .map(|listener| (listener.listener_name.as_str(), listener.security_protocol))
+            .collect();
+        assert_eq!(
+            protocols,
+            [
+                ("INTERNAL", SecurityProtocol::Plaintext),
+                ("EXTERNAL", SecurityProtocol::Plaintext),
+                ("SASL_SSL", SecurityProtocol::SaslSsl),
+            ]
+        );
+        let external = &config.advertised_listeners[1];
+        assert_eq!(external.address.to_string(), "203.0.113.7:19093");
+    }
+
+    #[test]
+    fn unix_socket_listeners_are_absolute_paths() {
+        let config = BrokerConfig::from_properties(&properties(&[
+            (
+                LISTENERS_CONFIG,
+                "PLAINTEXT://[::]:9092,SIDECAR:///run/kafka/broker.sock",
+            ),
+            (
+                ADVERTISED_LISTENERS_CONFIG,
+                "PLAINTEXT://broker-1:9092,SIDECAR:///run/kafka/broker.sock",
+            ),
+            (
+                LISTENER_SECURITY_PROTOCOL_MAP_CONFIG,
+                "PLAINTEXT:PLAINTEXT,SIDECAR:PLAINTEXT",
+            ),
+        ]))
+        .expect("valid config");
+
+        assert_eq!(config.listeners[0].bind_address(), "[::]:9092");
+        assert_eq!(
+            config.listeners[1].address,
+            ListenerAddress::Unix(PathBuf::from("/run/kafka/broker.sock"))
+        );
+        assert_eq!(
+            config.listeners[1].bind_address(),
+            "unix:/run/kafka/broker.sock"
+        );
+    }
+
+    #[test]
+    fn connection_limits_accept_ipv6_overrides() {
+        let config = BrokerConfig::from_properties(&properties(&[
+            (CONNECTIONS_MAX_IDLE_MS_CONFIG, "5000"),
+            (MAX_CONNECTIONS_CONFIG, "100"),
+            (MAX_CONNECTIONS_PER_IP_CONFIG, "0"),
+            (
+                MAX_CONNECTIONS_PER_IP_OVERRIDES_CONFIG,
+                "127.0.0.1:20, [::1]:10",
+            ),
+        ]))
+        .expect("valid config");
+
+        assert_eq!(config.connections_max_idle, Duration::from_secs(5));
+        let limits = &config.connection_limits;
+        assert_eq!(limits.max_connections, 100);
+        assert_eq!(limits.max_connections_per_ip, 0);
+        let overrides: Vec<_> = limits
+            .max_connections_per_ip_overrides
+            .iter()
+            .map(|(ip, count)| (ip.to_string(), *count))
+            .collect();
+        assert_eq!(
+            overrides,
+            [("127.0.0.1".to_string(), 20), ("::1".to_string(), 10)]
+        );
+    }
+}
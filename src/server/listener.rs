@@ -0,0 +1,281 @@
+use super::config::{Endpoint, ListenerAddress};
+use std::io::{self, Read, Write};
+use std::net::{IpAddr, Shutdown, TcpListener, TcpStream};
+#[cfg(unix)]
+use std::os::unix::{
+    fs::FileTypeExt,
+    net::{UnixListener, UnixStream},
+};
+#[cfg(unix)]
+use std::path::PathBuf;
+use std::time::Duration;
+#[cfg(unix)]
+use std::{fs, path::Path};
+
+/// A bound socket that client connections are accepted from.
+#[derive(Debug)]
+pub enum Listener {
+    Tcp(TcpListener),
+    /// Removes its socket file when dropped.
+    #[cfg(unix)]
+    Unix {
+        listener: UnixListener,
+        path: PathBuf,
+    },
+}
+
+impl Listener {
+    /// Binds the address of `endpoint`.
+    pub fn bind(endpoint: &Endpoint) -> io::Result<Self> {
+        match &endpoint.address {
+            ListenerAddress::Tcp { .. } => {
+                Ok(Self::Tcp(TcpListener::bind(endpoint.bind_address())?))
+            }
+            #[cfg(unix)]
+            ListenerAddress::Unix(path) => {
+                remove_stale_socket(path)?;
+                Ok(Self::Unix {
+                    listener: UnixListener::bind(path)?,
+                    path: path.clone(),
+                })
+            }
+            #[cfg(not(unix))]
+            ListenerAddress::Unix(_) => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "unix domain sockets are not supported on this platform",
+            )),
+        }
+    }
+
+    /// Address the listener is bound to, with the actual port when port 0
+    /// was requested.
+    pub fn local_address(&self) -> io::Result<String> {
+        match self {
+            Self::Tcp(listener) => Ok(listener.local_addr()?.to_string()),
+            #[cfg(unix)]
+            Self::Unix { path, .. } => Ok(format!("unix:{}", path.display())),
+        }
+    }
+
+    /// Makes [`Listener::accept`] return `WouldBlock` instead of waiting, so
+    /// the accept loop can notice a shutdown.
+    pub fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
+        match self {
+            Self::Tcp(listener) => listener.set_nonblocking(nonblocking),
+            #[cfg(unix)]
+            Self::Unix { listener, .. } => listener.set_nonblocking(nonblocking),
+        }
+    }
+
+    /// Waits for the next connection, returning it with a description of the
+    /// peer for logging.
+    pub fn accept(&self) -> io::Result<(Connection, String)> {
+        match self {
+            Self::Tcp(listener) => {
+                let (stream, peer) = listener.accept()?;
+                Ok((Connection::Tcp(stream), peer.to_string()))
+            }
+            #[cfg(unix)]
+            Self::Unix { listener, path } => {
+                let (stream, _) = listener.accept()?;
+                Ok((Connection::Unix(stream), format!("unix:{}", path.display())))
+            }
+        }
+    }
+}
+
+#[cfg(unix)]
+impl Drop for Listener {
+    fn drop(&mut self) {
+        if let Self::Unix { path, .. } = self {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+/// Removes a socket file left behind by a broker that did not shut down
+/// cleanly, refusing to touch files that are not sockets or that another
+/// process is still accepting connections on.
+#[cfg(unix)]
+fn remove_stale_socket(path: &Path) -> io::Result<()> {
+    let metadata = match fs::symlink_metadata(path) {
+        Ok(metadata) => metadata,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(err) => return Err(err),
+    };
+    if !metadata.file_type().is_socket() {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("{} exists and is not a socket", path.display()),
+        ));
+    }
+    if UnixStream::connect(path).is_ok() {
+        return Err(io::Error::new(
+            io::ErrorKind::AddrInUse,
+            format!("{} is in use by another process", path.display()),
+        ));
+    }
+    fs::remove_file(path)
+}
+
+/// An accepted client connection.
+#[derive(Debug)]
+pub enum Connection {
+    Tcp(TcpStream),
+    #[cfg(unix)]
+    Unix(UnixStream),
+}
+
+impl Connection {
+    pub fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
+        match self {
+            Self::Tcp(stream) => stream.set_nonblocking(nonblocking),
+            #[cfg(unix)]
+            Self::Unix(stream) => stream.set_nonblocking(nonblocking),
+        }
+    }
+
+    /// A second handle to the same socket, for writing while another thread
+    /// reads.
+    pub fn try_clone(&self) -> io::Result<Self> {
+        match self {
+            Self::Tcp(stream) => stream.try_clone().map(Self::Tcp),
+            #[cfg(unix)]
+            Self::Unix(stream) => stream.try_clone().map(Self::Unix),
+        }
+    }
+
+    /// Makes blocked and future reads see end of stream, while writes still
+    /// go through.
+    pub fn shutdown_read(&self) -> io::Result<()> {
+        match self {
+            Self::Tcp(stream) => stream.shutdown(Shutdown::Read),
+            #[cfg(unix)]
+            Self::Unix(stream) => stream.shutdown(Shutdown::Read),
+        }
+    }
+
+    /// Makes reads fail with `WouldBlock` or `TimedOut` after `timeout`.
+    pub fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        match self {
+            Self::Tcp(stream) => stream.set_read_timeout(timeout),
+            #[cfg(unix)]
+            Self::Unix(stream) => stream.set_read_timeout(timeout),
+        }
+    }
+
+    /// IP address of the client, or `None` for a Unix domain socket.
+    pub fn peer_ip(&self) -> Option<IpAddr> {
+        match self {
+            Self::Tcp(stream) => stream.peer_addr().ok().map(|addr| addr.ip()),
+            #[cfg(unix)]
+            Self::Unix(_) => None,
+        }
+    }
+}
+
+impl Read for Connection {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Self::Tcp(stream) => stream.read(buf),
+            #[cfg(unix)]
+            Self::Unix(stream) => stream.read(buf),
+        }
+    }
+}
+
+impl Write for Connection {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Self::Tcp(stream) => stream.write(buf),
+            #[cfg(unix)]
+            Self::Unix(stream) => stream.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Self::Tcp(stream) => stream.flush(),
+            #[cfg(unix)]
+            Self::Unix(stream) => stream.flush(),
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::server::SecurityProtocol;
+    use std::thread;
+
+    fn endpoint(address: ListenerAddress) -> Endpoint {
+        Endpoint {
+            listener_name: "PLAINTEXT".to_string(),
+            address,
+            security_protocol: SecurityProtocol::Plaintext,
+        }
+    }
+
+    fn echo_once(listener: Listener) -> thread::JoinHandle<()> {
+        thread::spawn(move || {
+            let (mut connection, _) = listener.accept().expect("accept");
+            let mut buf = [0; 4];
+            connection.read_exact(&mut buf).expect("read");
+            connection.write_all(&buf).expect("write");
+        })
+    }
+
+    #[test]
+    fn unix_listener_replaces_stale_socket_and_cleans_up() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let path = dir.path().join("broker.sock");
+        drop(UnixListener::bind(&path).expect("stale socket"));
+        assert!(path.exists());
+
+        let listener =
+            Listener::bind(&endpoint(ListenerAddress::Unix(path.clone()))).expect("bind");
+        let server = echo_once(listener);
+
+        let mut client = UnixStream::connect(&path).expect("connect");
+        client.write_all(b"ping").expect("write");
+        let mut reply = [0; 4];
+        client.read_exact(&mut reply).expect("read");
+        assert_eq!(&reply, b"ping");
+        server.join().expect("server thread");
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn unix_listener_refuses_live_socket_and_regular_files() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let path = dir.path().join("broker.sock");
+        let _live = Listener::bind(&endpoint(ListenerAddress::Unix(path.clone()))).expect("bind");
+        let err = Listener::bind(&endpoint(ListenerAddress::Unix(path))).expect_err("in use");
+        assert_eq!(err.kind(), io::ErrorKind::AddrInUse);
+
+        let file = dir.path().join("data.log");
+        fs::write(&file, b"keep").expect("write file");
+        let err = Listener::bind(&endpoint(ListenerAddress::Unix(file.clone()))).expect_err("file");
+        assert_eq!(err.kind(), io::ErrorKind::AlreadyExists);
+        assert_eq!(fs::read(&file).expect("read file"), b"keep");
+    }
+
+    #[test]
+    fn tcp_listener_accepts_ipv6_connections() {
+        let listener = Listener::bind(&endpoint(ListenerAddress::Tcp {
+            host: "::1".to_string(),
+            port: 0,
+        }))
+        .expect("bind");
+        let address = listener.local_address().expect("local address");
+        assert!(address.starts_with("[::1]:"), "{address}");
+        let server = echo_once(listener);
+
+        let mut client = TcpStream::connect(&address).expect("connect");
+        client.write_all(b"pong").expect("write");
+        let mut reply = [0; 4];
+        client.read_exact(&mut reply).expect("read");
+        assert_eq!(&reply, b"pong");
+        server.join().expect("server thread");
+    }
+}
@@ -0,0 +1,43 @@
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// A monotonically increasing count shared between threads.
+#[derive(Debug, Default)]
+pub struct Counter(AtomicU64);
+
+impl Counter {
+    pub fn increment(&self) {
+        self.0.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn get(&self) -> u64 {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Counts of connections the broker closed, refused or throttled.
+#[derive(Debug, Default)]
+pub struct ConnectionMetrics {
+    /// Connections closed after `connections.max.idle.ms` without a request.
+    pub idle_closed: Counter,
+    /// Connections refused because `max.connections` was reached.
+    pub rejected_max_connections: Counter,
+    /// Connections refused because their IP reached its connection limit.
+    pub rejected_per_ip: Counter,
+    /// Times an acceptor paused for `max.connection.creation.rate`.
+    pub creation_throttled: Counter,
+}
+
+impl fmt::Display for ConnectionMetrics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "idle-connections-closed={} connections-rejected-max={} \
+             connections-rejected-per-ip={} connection-creation-throttled={}",
+            self.idle_closed.get(),
+            self.rejected_max_connections.get(),
+            self.rejected_per_ip.get(),
+            self.creation_throttled.get()
+        )
+    }
+}
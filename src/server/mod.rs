@@ -1,54 +1,563 @@
+pub mod config;
+pub mod listener;
+pub mod metrics;
+pub mod quotas;
+pub mod shutdown;
+
+pub use config::{BrokerConfig, Endpoint, ListenerAddress, SecurityProtocol};
+pub use listener::{Connection, Listener};
+pub use metrics::ConnectionMetrics;
+pub use quotas::{ConnectionLimits, ConnectionQuotas, ConnectionSlot, QuotaViolation};
+pub use shutdown::{InFlight, ShutdownSignal};
+
 use crate::codec::KafkaCodec;
+use crate::metadata::{self, MetadataController, Uuid};
 use crate::protocol::Request;
-use crate::state::ApiRegistry;
+use crate::state::offsets::OFFSETS_TOPIC;
+use crate::state::{BrokerEndpoint, BrokerInfo, GroupCoordinator, RequestContext, RequestHandler};
+use crate::storage::config::CLEANUP_POLICY_CONFIG;
+use crate::storage::{LogManager, MetaProperties, TopicPartition};
+use crate::time::{Scheduler, SystemClock};
+use std::collections::BTreeMap;
 use std::io::{self, Read, Write};
-use std::net::TcpListener;
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How often a listener with no pending connections checks for shutdown.
+const ACCEPT_POLL_INTERVAL: Duration = Duration::from_millis(50);
+/// How often parked fetches are checked for an elapsed `max_wait_ms`.
+const FETCH_EXPIRATION_INTERVAL: Duration = Duration::from_millis(10);
+/// How often rebalance and session timeouts of consumer groups are checked.
+const GROUP_EXPIRATION_INTERVAL: Duration = Duration::from_millis(200);
 
-const LISTEN_ADDR: &str = "127.0.0.1:9092";
+/// Formats the configured log directory with `meta.properties` for this node,
+/// generating a cluster id when none is given.
+pub fn format(
+    config: &BrokerConfig,
+    cluster_id: Option<&str>,
+    ignore_formatted: bool,
+) -> io::Result<()> {
+    let cluster_id = match cluster_id {
+        Some(cluster_id) => cluster_id.to_string(),
+        None => Uuid::random()?.to_string(),
+    };
+    let properties = MetaProperties::format(
+        &config.log_dir,
+        &cluster_id,
+        config.node_id,
+        ignore_formatted,
+    )?;
+    println!(
+        "formatted {} for node {} in cluster {} (directory id {})",
+        config.log_dir.display(),
+        properties.node_id,
+        properties.cluster_id,
+        properties.directory_id
+    );
+    Ok(())
+}
 
-pub fn run() -> io::Result<()> {
-    println!("starting tcp listener on {LISTEN_ADDR}");
-    let registry = ApiRegistry::default();
+/// Serves the configured listeners until SIGTERM or SIGINT, then stops
+/// accepting, waits for in-flight connections and shuts the logs down cleanly.
+pub fn run(config: BrokerConfig) -> io::Result<()> {
+    let properties = MetaProperties::verify(&config.log_dir, config.node_id)?;
+    let shutdown = ShutdownSignal::new();
+    shutdown.register_signals()?;
+    println!(
+        "starting node {} of cluster {}",
+        properties.node_id, properties.cluster_id
+    );
+    let mut logs = LogManager::load(
+        &config.log_dir,
+        config.log_config.clone(),
+        SystemClock::shared(),
+    )?;
+    println!(
+        "loaded {} partition logs from {}",
+        logs.logs().count(),
+        config.log_dir.display()
+    );
+    let image = metadata::loader::load(&mut logs)?;
+    let mut controller = MetadataController::new(image, config.node_id, SystemClock::shared())
+        .with_default_partitions(config.num_partitions);
+    println!(
+        "loaded metadata image with {} topics at offset {}",
+        controller.image().topics().count(),
+        controller.image().offset()
+    );
+    create_internal_topics(&config, &mut controller, &mut logs)?;
+    let (appended, appends) = mpsc::channel();
+    logs.notify_appends(appended);
+    let logs = Arc::new(Mutex::new(logs));
+    let controller = Arc::new(Mutex::new(controller));
+    let mut scheduler = start_log_tasks(&config, &logs)?;
+    schedule_metadata_snapshots(&config, &mut scheduler, &logs, &controller)?;
+    let coordinator = GroupCoordinator::new(
+        config.group_config.clone(),
+        Arc::clone(&logs),
+        SystemClock::shared(),
+    );
+    println!(
+        "loaded committed offsets of {} groups",
+        coordinator.load_offsets()?
+    );
+    schedule_group_expiration(&config, &mut scheduler, &coordinator)?;
 
-    //
-    let listener = TcpListener::bind(LISTEN_ADDR)?;
-    println!("listener bound on {LISTEN_ADDR}");
+    let mut listeners = Vec::with_capacity(config.listeners.len());
+    for endpoint in &config.listeners {
+        let listen_addr = endpoint.bind_address();
+        println!(
+            "starting {} listener {} on {listen_addr}",
+            endpoint.security_protocol.name(),
+            endpoint.listener_name
+        );
+        let listener = Listener::bind(endpoint).map_err(|err| {
+            io::Error::new(
+                err.kind(),
+                format!(
+                    "cannot bind listener {} on {listen_addr}: {err}",
+                    endpoint.listener_name
+                ),
+            )
+        })?;
+        println!("listener {} bound on {listen_addr}", endpoint.listener_name);
+        if endpoint.security_protocol != SecurityProtocol::Plaintext {
+            eprintln!(
+                "listener {} uses security protocol {}, which is not supported yet; its connections will be closed",
+                endpoint.listener_name,
+                endpoint.security_protocol.name()
+            );
+        }
+        listeners.push((endpoint.clone(), listener));
+    }
+    let handler = Arc::new(RequestHandler::new(
+        BrokerInfo {
+            cluster_id: properties.cluster_id,
+            node_id: properties.node_id,
+            endpoints: config
+                .advertised_listeners
+                .iter()
+                .map(|endpoint| match &endpoint.address {
+                    ListenerAddress::Tcp { host, port } => BrokerEndpoint {
+                        listener_name: endpoint.listener_name.clone(),
+                        host: host.clone(),
+                        port: i32::from(*port),
+                    },
+                    // Sidecar clients on the socket get its path with no port.
+                    ListenerAddress::Unix(path) => BrokerEndpoint {
+                        listener_name: endpoint.listener_name.clone(),
+                        host: path.display().to_string(),
+                        port: 0,
+                    },
+                })
+                .collect(),
+        },
+        Arc::clone(&controller),
+        Arc::clone(&logs),
+        Arc::clone(&coordinator),
+        SystemClock::shared(),
+    ));
+    schedule_fetch_completion(&mut scheduler, &handler, appends)?;
 
-    for stream in listener.incoming() {
-        match stream {
-            Ok(mut stream) => {
-                let peer = stream
-                    .peer_addr()
-                    .map(|addr| addr.to_string())
-                    .unwrap_or_else(|_| "<unknown>".into());
-                println!("accepted connection from {peer}");
+    let in_flight = InFlight::new();
+    let metrics = Arc::new(ConnectionMetrics::default());
+    let quotas = ConnectionQuotas::new(
+        config.connection_limits.clone(),
+        SystemClock::shared(),
+        Arc::clone(&metrics),
+    );
+    let acceptors = listeners
+        .into_iter()
+        .map(|(endpoint, listener)| {
+            let listener_name = endpoint.listener_name;
+            let acceptor = Acceptor {
+                listener,
+                security_protocol: endpoint.security_protocol,
+                context: RequestContext {
+                    listener_name: listener_name.clone(),
+                    client_host: String::new(),
+                },
+                handler: Arc::clone(&handler),
+                settings: ConnectionSettings {
+                    max_request_bytes: config.socket_request_max_bytes,
+                    max_in_flight_requests: config.max_in_flight_requests_per_connection,
+                },
+                idle_timeout: config.connections_max_idle,
+                quotas: Arc::clone(&quotas),
+                metrics: Arc::clone(&metrics),
+                shutdown: shutdown.clone(),
+                in_flight: in_flight.clone(),
+            };
+            thread::Builder::new()
+                .name(format!("acceptor-{listener_name}"))
+                .spawn(move || acceptor.run())
+        })
+        .collect::<io::Result<Vec<_>>>()?;
+    for acceptor in acceptors {
+        if acceptor.join().is_err() {
+            eprintln!("listener thread panicked");
+        }
+    }
+
+    in_flight.stop_reading();
+    println!(
+        "shutting down: waiting up to {:?} for {} in-flight connections",
+        config.shutdown_drain_timeout,
+        in_flight.count()
+    );
+    let abandoned = in_flight.wait_idle(config.shutdown_drain_timeout);
+    if abandoned > 0 {
+        eprintln!("abandoning {abandoned} connections that did not finish in time");
+    }
+    println!("connection metrics: {metrics}");
+    scheduler.shutdown();
+    drop(handler);
+    drop(coordinator);
+    shutdown_logs(&logs)?;
+    println!("shutdown complete");
+    Ok(())
+}
+
+/// Registers the offsets topic with the controller, so it has metadata records
+/// and appears in the image like any other topic.
+fn create_internal_topics(
+    config: &BrokerConfig,
+    controller: &mut MetadataController,
+    logs: &mut LogManager,
+) -> io::Result<()> {
+    let configs = BTreeMap::from([(CLEANUP_POLICY_CONFIG.to_string(), "compact".to_string())]);
+    let partitions = config.group_config.offsets_topic_partitions;
+    if controller.ensure_topic(logs, OFFSETS_TOPIC, partitions, &configs)? {
+        println!("created internal topic {OFFSETS_TOPIC} with {partitions} partitions");
+    }
+    Ok(())
+}
 
-                if let Err(err) = handle_connection(&mut stream, &registry) {
-                    eprintln!("connection error from {peer}: {err}");
+/// Accepts connections on one listener until shutdown, serving each on its
+/// own thread.
+struct Acceptor {
+    listener: Listener,
+    /// Connections are closed on accept unless this is PLAINTEXT, the only
+    /// protocol the broker speaks.
+    security_protocol: SecurityProtocol,
+    context: RequestContext,
+    handler: Arc<RequestHandler>,
+    settings: ConnectionSettings,
+    idle_timeout: Duration,
+    quotas: Arc<ConnectionQuotas>,
+    metrics: Arc<ConnectionMetrics>,
+    shutdown: ShutdownSignal,
+    in_flight: InFlight,
+}
+
+impl Acceptor {
+    fn run(self) {
+        if let Err(err) = self.listener.set_nonblocking(true) {
+            eprintln!("listener {} error: {err}", self.context.listener_name);
+            return;
+        }
+        while !self.shutdown.is_triggered() {
+            match self.listener.accept() {
+                Ok((_stream, peer)) if self.security_protocol != SecurityProtocol::Plaintext => {
+                    eprintln!(
+                        "closing connection from {peer} on listener {}: security protocol {} is not supported, only {}",
+                        self.context.listener_name,
+                        self.security_protocol.name(),
+                        SecurityProtocol::Plaintext.name()
+                    );
+                }
+                Ok((stream, peer)) => {
+                    let throttle = self.quotas.record_creation();
+                    match self.quotas.acquire(stream.peer_ip()) {
+                        Ok(slot) => {
+                            println!(
+                                "accepted connection from {peer} on listener {}",
+                                self.context.listener_name
+                            );
+                            if let Err(err) = self.serve(stream, peer, slot) {
+                                eprintln!("cannot serve connection: {err}");
+                            }
+                        }
+                        Err(violation) => {
+                            eprintln!("closing connection from {peer}: {violation}");
+                        }
+                    }
+                    if !throttle.is_zero() {
+                        eprintln!(
+                            "listener {} reached max.connection.creation.rate, pausing accepts for {throttle:?}",
+                            self.context.listener_name
+                        );
+                        self.pause(throttle);
+                    }
+                }
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => {
+                    thread::sleep(ACCEPT_POLL_INTERVAL);
                 }
+                Err(err) => eprintln!("listener {} error: {err}", self.context.listener_name),
+            }
+        }
+        println!("listener {} stopped accepting", self.context.listener_name);
+    }
+
+    /// Sleeps for `duration` unless shutdown starts first.
+    fn pause(&self, duration: Duration) {
+        let deadline = Instant::now() + duration;
+        while !self.shutdown.is_triggered() {
+            let now = Instant::now();
+            if now >= deadline {
+                break;
             }
-            Err(err) => eprintln!("listener error: {err}"),
+            thread::sleep(ACCEPT_POLL_INTERVAL.min(deadline - now));
         }
     }
 
+    fn serve(&self, mut stream: Connection, peer: String, slot: ConnectionSlot) -> io::Result<()> {
+        stream.set_nonblocking(false)?;
+        stream.set_read_timeout(Some(self.idle_timeout))?;
+        let guard = self.in_flight.start_connection(&stream)?;
+        let context = RequestContext {
+            client_host: stream
+                .peer_ip()
+                .map_or_else(|| peer.clone(), |ip| format!("/{ip}")),
+            ..self.context.clone()
+        };
+        let handler = Arc::clone(&self.handler);
+        let metrics = Arc::clone(&self.metrics);
+        let settings = self.settings;
+        let idle_timeout = self.idle_timeout;
+        thread::Builder::new()
+            .name(format!("connection-{peer}"))
+            .spawn(move || {
+                let _guards = (guard, slot);
+                match serve_connection(&mut stream, &context, &handler, settings) {
+                    Ok(()) => {}
+                    Err(err) if is_timeout(&err) => {
+                        metrics.idle_closed.increment();
+                        println!(
+                            "closing connection from {peer} after {idle_timeout:?} without a request"
+                        );
+                    }
+                    Err(err) => eprintln!("connection error from {peer}: {err}"),
+                }
+            })?;
+        Ok(())
+    }
+}
+
+fn is_timeout(err: &io::Error) -> bool {
+    matches!(
+        err.kind(),
+        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+    )
+}
+
+fn start_log_tasks(config: &BrokerConfig, logs: &Arc<Mutex<LogManager>>) -> io::Result<Scheduler> {
+    let mut scheduler = Scheduler::new();
+    let flush_logs = Arc::clone(logs);
+    scheduler.schedule("log-flusher", config.log_flush_check_interval, move || {
+        let mut logs = flush_logs.lock().expect("log manager lock poisoned");
+        if let Err(err) = logs.flush_dirty_logs() {
+            eprintln!("log flush error: {err}");
+        }
+    })?;
+
+    let retention_logs = Arc::clone(logs);
+    scheduler.schedule(
+        "log-retention",
+        config.log_retention_check_interval,
+        move || {
+            let mut logs = retention_logs.lock().expect("log manager lock poisoned");
+            logs.delete_old_segments();
+        },
+    )?;
+
+    let cleaner_logs = Arc::clone(logs);
+    scheduler.schedule("log-cleaner", config.log_cleaner_backoff, move || {
+        let mut logs = cleaner_logs.lock().expect("log manager lock poisoned");
+        if let Err(err) = logs.clean_logs() {
+            eprintln!("log cleaner error: {err}");
+        }
+    })?;
+    Ok(scheduler)
+}
+
+fn schedule_metadata_snapshots(
+    config: &BrokerConfig,
+    scheduler: &mut Scheduler,
+    logs: &Arc<Mutex<LogManager>>,
+    controller: &Arc<Mutex<MetadataController>>,
+) -> io::Result<()> {
+    let logs = Arc::clone(logs);
+    let controller = Arc::clone(controller);
+    scheduler.schedule(
+        "metadata-snapshot",
+        config.metadata_snapshot_interval,
+        move || {
+            let mut controller = controller
+                .lock()
+                .expect("metadata controller lock poisoned");
+            let mut logs = logs.lock().expect("log manager lock poisoned");
+            if let Err(err) = controller.snapshot(&mut logs) {
+                eprintln!("metadata snapshot error: {err}");
+            }
+        },
+    )
+}
+
+/// Completes parked fetches as appends arrive, on a thread that ends when
+/// the logs shut down, and expires the ones whose wait has passed.
+fn schedule_fetch_completion(
+    scheduler: &mut Scheduler,
+    handler: &Arc<RequestHandler>,
+    appends: mpsc::Receiver<TopicPartition>,
+) -> io::Result<()> {
+    let completing = Arc::clone(handler);
+    thread::Builder::new()
+        .name("fetch-completion".to_string())
+        .spawn(move || completing.complete_delayed_fetches(appends))?;
+    let handler = Arc::clone(handler);
+    scheduler.schedule("fetch-expiration", FETCH_EXPIRATION_INTERVAL, move || {
+        handler.expire_delayed_fetches();
+    })
+}
+
+/// Completes joins and syncs whose rebalance timed out and removes members
+/// whose session expired.
+fn schedule_group_expiration(
+    config: &BrokerConfig,
+    scheduler: &mut Scheduler,
+    coordinator: &Arc<GroupCoordinator>,
+) -> io::Result<()> {
+    let expiring = Arc::clone(coordinator);
+    scheduler.schedule("group-expiration", GROUP_EXPIRATION_INTERVAL, move || {
+        expiring.expire_delayed();
+    })?;
+    let coordinator = Arc::clone(coordinator);
+    scheduler.schedule(
+        "offset-expiration",
+        config.offsets_retention_check_interval,
+        move || {
+            if let Err(err) = coordinator.expire_offsets() {
+                eprintln!("offset expiration error: {err}");
+            }
+        },
+    )
+}
+
+/// Shuts the logs down in place: abandoned connections may still hold the
+/// request handler, and their later requests fail instead of writing.
+fn shutdown_logs(logs: &Mutex<LogManager>) -> io::Result<()> {
+    logs.lock().expect("log manager lock poisoned").shutdown()
+}
+
+fn handle_connection(
+    stream: &mut impl ReadWrite,
+    context: &RequestContext,
+    handler: &RequestHandler,
+    max_request_bytes: usize,
+) -> io::Result<()> {
+    let request = KafkaCodec::read_request_with_limit(stream, max_request_bytes)?;
+    log_request(context, &request);
+    let expects_response = request.expects_response();
+    let response = handler.handle(context, request);
+
+    if expects_response {
+        KafkaCodec::write_response(stream, &response)?;
+        println!("response sent");
+    }
     Ok(())
 }
 
-fn handle_connection(stream: &mut impl ReadWrite, registry: &ApiRegistry) -> io::Result<()> {
-    let request = KafkaCodec::read_request(stream)?;
-    let response = match request {
-        Request::ApiVersions(request) => {
-            println!(
-                "processing ApiVersions request key={} version={} correlation={}",
-                request.api_key, request.api_version, request.correlation_id
-            );
-            registry.handle_versions(request)
+fn log_request(context: &RequestContext, request: &Request) {
+    let header = request.header();
+    println!(
+        "processing request key={} version={} correlation={} on {}",
+        header.request_api_key,
+        header.request_api_version,
+        header.correlation_id,
+        context.listener_name
+    );
+}
+
+/// Serves requests on `stream` until the client disconnects. With more than
+/// one request allowed in flight, requests are read ahead of the one being
+/// handled.
+fn serve_connection(
+    stream: &mut Connection,
+    context: &RequestContext,
+    handler: &Arc<RequestHandler>,
+    settings: ConnectionSettings,
+) -> io::Result<()> {
+    if settings.max_in_flight_requests > 1 {
+        let writer = stream.try_clone()?;
+        return pipeline_requests(stream, writer, context, handler, settings);
+    }
+    loop {
+        match handle_connection(stream, context, handler, settings.max_request_bytes) {
+            Ok(()) => {}
+            Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(()),
+            Err(err) => return Err(err),
         }
+    }
+}
+
+/// Limits applied to every request on a connection.
+#[derive(Debug, Clone, Copy)]
+struct ConnectionSettings {
+    max_request_bytes: usize,
+    max_in_flight_requests: usize,
+}
+
+/// Reads requests ahead of a single worker that handles them one at a time
+/// and writes each response, so requests on a connection take effect in the
+/// order they were sent.
+fn pipeline_requests(
+    reader: &mut impl Read,
+    mut writer: impl Write + Send + 'static,
+    context: &RequestContext,
+    handler: &Arc<RequestHandler>,
+    settings: ConnectionSettings,
+) -> io::Result<()> {
+    // The worker holds the request it is handling while the queue holds the
+    // rest, so a full queue means max_in_flight_requests are outstanding.
+    let (queue, pending) =
+        mpsc::sync_channel::<Request>(settings.max_in_flight_requests.saturating_sub(1));
+    let worker = {
+        let handler = Arc::clone(handler);
+        let context = context.clone();
+        thread::Builder::new()
+            .name(format!("requests-{}", context.listener_name))
+            .spawn(move || -> io::Result<()> {
+                for request in pending {
+                    log_request(&context, &request);
+                    let expects_response = request.expects_response();
+                    let response = handler.handle(&context, request);
+                    if expects_response {
+                        KafkaCodec::write_response(&mut writer, &response)?;
+                    }
+                }
+                Ok(())
+            })?
     };
 
-    KafkaCodec::write_response(stream, &response)?;
-    println!("response sent");
-    Ok(())
+    let read_result = loop {
+        let request = match KafkaCodec::read_request_with_limit(reader, settings.max_request_bytes)
+        {
+            Ok(request) => request,
+            Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => break Ok(()),
+            Err(err) => break Err(err),
+        };
+        if queue.send(request).is_err() {
+            // The worker failed; its error is returned below.
+            break Ok(());
+        }
+    };
+    drop(queue);
+    let write_result = worker
+        .join()
+        .unwrap_or_else(|_| Err(io::Error::other("request worker panicked")));
+    read_result.and(write_result)
 }
 
 trait ReadWrite: Read + Write {}
@@ -58,8 +567,18 @@ impl<T: Read + Write> ReadWrite for T {}
 mod tests {
     use super::*;
     use crate::codec::MessageFramer;
+    use crate::metadata::MetadataImage;
+    use crate::protocol::offset_commit::NO_GENERATION;
+    use crate::protocol::wire;
+    use crate::state::GroupConfig;
+    use crate::storage::manager::CLEAN_SHUTDOWN_FILE;
+    use crate::storage::LogConfig;
     use std::convert::{TryFrom, TryInto};
     use std::io::{self, Cursor, Read, Write};
+    use std::path::Path;
+
+    const NODE_ID: i32 = 1;
+    const MAX_REQUEST_BYTES: usize = 1024;
 
     struct MockStream {
         input: Cursor<Vec<u8>>,
@@ -92,13 +611,28 @@ mod tests {
         }
     }
 
+    #[derive(Clone, Default)]
+    struct SharedOutput(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedOutput {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
     #[test]
     fn handle_connection_writes_successful_response() {
         let request = build_request(18, 4, 7, Some("client"));
         let mut stream = MockStream::new(request);
-        let registry = ApiRegistry::default();
+        let handler = test_handler();
 
-        handle_connection(&mut stream, &registry).expect("handle_connection should succeed");
+        handle_connection(&mut stream, &test_context(), &handler, MAX_REQUEST_BYTES)
+            .expect("handle_connection should succeed");
 
         let response = stream.output;
         assert!(response.len() > 4);
@@ -120,9 +654,10 @@ mod tests {
     fn handle_connection_rejects_unknown_api_key() {
         let request = build_request(7, 0, 13, None);
         let mut stream = MockStream::new(request);
-        let registry = ApiRegistry::default();
+        let handler = test_handler();
 
-        handle_connection(&mut stream, &registry).expect("handle_connection should succeed");
+        handle_connection(&mut stream, &test_context(), &handler, MAX_REQUEST_BYTES)
+            .expect("handle_connection should succeed");
 
         let response = stream.output;
 
@@ -133,6 +668,353 @@ mod tests {
         assert_eq!(version_count, 0);
     }
 
+    #[cfg(unix)]
+    #[test]
+    fn serves_requests_over_unix_socket() {
+        use std::os::unix::net::UnixStream;
+
+        let dir = tempfile::tempdir().expect("tempdir");
+        let path = dir.path().join("kafka.sock");
+        let listener = Listener::bind(&Endpoint {
+            listener_name: "PLAINTEXT".to_string(),
+            address: ListenerAddress::Unix(path.clone()),
+            security_protocol: SecurityProtocol::Plaintext,
+        })
+        .expect("bind unix listener");
+        let handler = test_handler();
+        let server = thread::spawn(move || {
+            let (mut connection, _) = listener.accept().expect("accept");
+            handle_connection(
+                &mut connection,
+                &test_context(),
+                &handler,
+                MAX_REQUEST_BYTES,
+            )
+        });
+
+        let mut client = UnixStream::connect(&path).expect("connect");
+        client
+            .write_all(&build_request(18, 4, 21, Some("sidecar")))
+            .expect("send request");
+        let response = MessageFramer::read(&mut client).expect("read response");
+        server
+            .join()
+            .expect("server thread")
+            .expect("handle connection");
+
+        let correlation_id = i32::from_be_bytes(response[0..4].try_into().unwrap());
+        assert_eq!(correlation_id, 21);
+    }
+
+    struct TestAcceptor {
+        address: String,
+        shutdown: ShutdownSignal,
+        in_flight: InFlight,
+        metrics: Arc<ConnectionMetrics>,
+        thread: thread::JoinHandle<()>,
+    }
+
+    fn start_acceptor(
+        handler: RequestHandler,
+        limits: ConnectionLimits,
+        idle_timeout: Duration,
+    ) -> TestAcceptor {
+        start_acceptor_on(SecurityProtocol::Plaintext, handler, limits, idle_timeout)
+    }
+
+    fn start_acceptor_on(
+        security_protocol: SecurityProtocol,
+        handler: RequestHandler,
+        limits: ConnectionLimits,
+        idle_timeout: Duration,
+    ) -> TestAcceptor {
+        let listener = Listener::bind(&Endpoint {
+            listener_name: security_protocol.name().to_string(),
+            address: ListenerAddress::Tcp {
+                host: "127.0.0.1".to_string(),
+                port: 0,
+            },
+            security_protocol,
+        })
+        .expect("bind listener");
+        let address = listener.local_address().expect("local address");
+        let shutdown = ShutdownSignal::new();
+        let in_flight = InFlight::new();
+        let metrics = Arc::new(ConnectionMetrics::default());
+        let acceptor = Acceptor {
+            listener,
+            security_protocol,
+            context: test_context(),
+            handler: Arc::new(handler),
+            settings: ConnectionSettings {
+                max_request_bytes: MAX_REQUEST_BYTES,
+                max_in_flight_requests: 2,
+            },
+            idle_timeout,
+            quotas: ConnectionQuotas::new(limits, SystemClock::shared(), Arc::clone(&metrics)),
+            metrics: Arc::clone(&metrics),
+            shutdown: shutdown.clone(),
+            in_flight: in_flight.clone(),
+        };
+        TestAcceptor {
+            address,
+            shutdown,
+            in_flight,
+            metrics,
+            thread: thread::spawn(move || acceptor.run()),
+        }
+    }
+
+    #[test]
+    fn acceptor_drains_connections_after_shutdown() {
+        let TestAcceptor {
+            address,
+            shutdown,
+            in_flight,
+            thread: acceptor,
+            ..
+        } = start_acceptor(
+            test_handler(),
+            ConnectionLimits::default(),
+            Duration::from_secs(5),
+        );
+
+        let request = build_request(18, 4, 3, None);
+        let mut client = std::net::TcpStream::connect(&address).expect("connect");
+        client.write_all(&request[..6]).expect("send request start");
+        while in_flight.count() == 0 {
+            thread::sleep(Duration::from_millis(5));
+        }
+        shutdown.trigger();
+        acceptor.join().expect("acceptor thread");
+        client.write_all(&request[6..]).expect("send request end");
+
+        let response = MessageFramer::read(&mut client).expect("read response");
+        assert_eq!(i32::from_be_bytes(response[0..4].try_into().unwrap()), 3);
+
+        in_flight.stop_reading();
+        let mut buf = [0; 1];
+        assert_eq!(client.read(&mut buf).expect("closed by broker"), 0);
+        assert_eq!(in_flight.wait_idle(Duration::from_secs(5)), 0);
+        assert!(std::net::TcpStream::connect(&address).is_err());
+    }
+
+    #[test]
+    fn logs_shut_down_cleanly_despite_abandoned_connections() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let (handler, logs) = test_handler_in(dir.path(), &["orders"]);
+        let TestAcceptor {
+            address,
+            shutdown,
+            in_flight,
+            thread: acceptor,
+            ..
+        } = start_acceptor(handler, ConnectionLimits::default(), Duration::from_secs(5));
+
+        let request = offset_commit_request(4, 42);
+        let mut client = std::net::TcpStream::connect(&address).expect("connect");
+        client.write_all(&request[..6]).expect("send request start");
+        while in_flight.count() == 0 {
+            thread::sleep(Duration::from_millis(5));
+        }
+        shutdown.trigger();
+        acceptor.join().expect("acceptor thread");
+        assert_eq!(in_flight.wait_idle(Duration::from_millis(20)), 1);
+
+        shutdown_logs(&logs).expect("shut down logs");
+        assert!(dir.path().join(CLEAN_SHUTDOWN_FILE).exists());
+
+        // The abandoned connection's request fails instead of writing.
+        client.write_all(&request[6..]).expect("send request end");
+        let response = MessageFramer::read(&mut client).expect("read response");
+        assert_eq!(i32::from_be_bytes(response[0..4].try_into().unwrap()), 4);
+        assert_ne!(i16::from_be_bytes(response[24..26].try_into().unwrap()), 0);
+        drop(client);
+        assert_eq!(in_flight.wait_idle(Duration::from_secs(5)), 0);
+    }
+
+    #[test]
+    fn acceptor_enforces_per_ip_limit_and_idle_timeout() {
+        let acceptor = start_acceptor(
+            test_handler(),
+            ConnectionLimits {
+                max_connections_per_ip: 1,
+                ..ConnectionLimits::default()
+            },
+            Duration::from_millis(100),
+        );
+
+        let mut idle = std::net::TcpStream::connect(&acceptor.address).expect("connect");
+        let mut refused = std::net::TcpStream::connect(&acceptor.address).expect("connect");
+        let mut buf = [0; 1];
+        assert_eq!(refused.read(&mut buf).expect("closed by broker"), 0);
+        assert_eq!(idle.read(&mut buf).expect("closed when idle"), 0);
+        assert_eq!(acceptor.in_flight.wait_idle(Duration::from_secs(5)), 0);
+
+        acceptor.shutdown.trigger();
+        acceptor.thread.join().expect("acceptor thread");
+        assert_eq!(acceptor.metrics.rejected_per_ip.get(), 1);
+        assert_eq!(acceptor.metrics.idle_closed.get(), 1);
+    }
+
+    #[test]
+    fn acceptor_closes_connections_on_unsupported_protocols() {
+        let acceptor = start_acceptor_on(
+            SecurityProtocol::Ssl,
+            test_handler(),
+            ConnectionLimits::default(),
+            Duration::from_secs(5),
+        );
+
+        let mut client = std::net::TcpStream::connect(&acceptor.address).expect("connect");
+        // Closed before the request is read; the write may or may not land.
+        let _ = client.write_all(&build_request(18, 4, 1, Some("client")));
+        let mut buf = [0; 1];
+        assert!(client.read(&mut buf).map_or(true, |read| read == 0));
+        assert_eq!(acceptor.in_flight.count(), 0);
+
+        acceptor.shutdown.trigger();
+        acceptor.thread.join().expect("acceptor thread");
+    }
+
+    #[test]
+    fn pipelined_responses_follow_request_order() {
+        let mut input = Vec::new();
+        for correlation_id in 1..=6 {
+            input.extend(build_request(18, 4, correlation_id, Some("client")));
+        }
+        let output = SharedOutput::default();
+        let settings = ConnectionSettings {
+            max_request_bytes: MAX_REQUEST_BYTES,
+            max_in_flight_requests: 3,
+        };
+
+        pipeline_requests(
+            &mut Cursor::new(input),
+            output.clone(),
+            &test_context(),
+            &Arc::new(test_handler()),
+            settings,
+        )
+        .expect("serve pipelined requests");
+
+        let output = output.0.lock().unwrap().clone();
+        let mut responses = Cursor::new(output);
+        let correlation_ids: Vec<i32> = (0..6)
+            .map(|_| {
+                let response = MessageFramer::read(&mut responses).expect("response");
+                i32::from_be_bytes(response[0..4].try_into().unwrap())
+            })
+            .collect();
+        assert_eq!(correlation_ids, [1, 2, 3, 4, 5, 6]);
+    }
+
+    #[test]
+    fn pipelined_requests_take_effect_in_order() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let mut create = Vec::new();
+        wire::write_array_len(&mut create, Some(1), false);
+        wire::write_string(&mut create, "orders", false);
+        create.extend_from_slice(&1_i32.to_be_bytes());
+        create.extend_from_slice(&(-1_i16).to_be_bytes());
+        wire::write_array_len(&mut create, Some(0), false);
+        wire::write_array_len(&mut create, Some(0), false);
+        create.extend_from_slice(&30_000_i32.to_be_bytes());
+        create.push(0);
+
+        let mut input = build_request_with_body(19, 2, 1, &create);
+        input.extend(build_request_with_body(19, 2, 2, &create));
+        let output = SharedOutput::default();
+        let settings = ConnectionSettings {
+            max_request_bytes: MAX_REQUEST_BYTES,
+            max_in_flight_requests: 2,
+        };
+
+        pipeline_requests(
+            &mut Cursor::new(input),
+            output.clone(),
+            &test_context(),
+            &Arc::new(test_handler_in(dir.path(), &[]).0),
+            settings,
+        )
+        .expect("serve pipelined requests");
+
+        // Each response holds the single topic "orders", whose error code
+        // starts 20 bytes in.
+        let output = output.0.lock().unwrap().clone();
+        let mut responses = Cursor::new(output);
+        let error_codes: Vec<i16> = (0..2)
+            .map(|_| {
+                let response = MessageFramer::read(&mut responses).expect("response");
+                i16::from_be_bytes(response[20..22].try_into().unwrap())
+            })
+            .collect();
+        assert_eq!(error_codes, [0, 36]);
+    }
+
+    fn test_handler() -> RequestHandler {
+        // No partitions are served, so the log directory need not outlive the handler.
+        let dir = tempfile::tempdir().expect("tempdir");
+        test_handler_in(dir.path(), &[]).0
+    }
+
+    fn test_handler_in(dir: &Path, topics: &[&str]) -> (RequestHandler, Arc<Mutex<LogManager>>) {
+        let mut controller =
+            MetadataController::new(MetadataImage::default(), NODE_ID, SystemClock::shared());
+        let mut logs =
+            LogManager::load(dir, LogConfig::default(), SystemClock::shared()).expect("load logs");
+        for topic in topics {
+            controller
+                .create_topic(&mut logs, topic, 1, &BTreeMap::new())
+                .expect("create topic");
+        }
+        let logs = Arc::new(Mutex::new(logs));
+        let handler = RequestHandler::new(
+            BrokerInfo {
+                cluster_id: "MkU3OEVBNTcwNTJENDM2Qg".to_string(),
+                node_id: NODE_ID,
+                endpoints: vec![BrokerEndpoint {
+                    listener_name: "PLAINTEXT".to_string(),
+                    host: "localhost".to_string(),
+                    port: 9092,
+                }],
+            },
+            Arc::new(Mutex::new(controller)),
+            Arc::clone(&logs),
+            GroupCoordinator::new(
+                GroupConfig::default(),
+                Arc::clone(&logs),
+                SystemClock::shared(),
+            ),
+            SystemClock::shared(),
+        );
+        (handler, logs)
+    }
+
+    /// An OffsetCommit v2 of `offset` for partition 0 of "orders" outside of
+    /// any group generation.
+    fn offset_commit_request(correlation_id: i32, offset: i64) -> Vec<u8> {
+        let mut body = Vec::new();
+        wire::write_string(&mut body, "payments", false);
+        body.extend_from_slice(&NO_GENERATION.to_be_bytes());
+        wire::write_string(&mut body, "", false);
+        body.extend_from_slice(&(-1_i64).to_be_bytes());
+        wire::write_array_len(&mut body, Some(1), false);
+        wire::write_string(&mut body, "orders", false);
+        wire::write_array_len(&mut body, Some(1), false);
+        body.extend_from_slice(&0_i32.to_be_bytes());
+        body.extend_from_slice(&offset.to_be_bytes());
+        wire::write_nullable_string(&mut body, None, false);
+        build_request_with_body(8, 2, correlation_id, &body)
+    }
+
+    fn test_context() -> RequestContext {
+        RequestContext {
+            listener_name: "PLAINTEXT".to_string(),
+            client_host: "/127.0.0.1".to_string(),
+        }
+    }
+
     fn build_request(
         api_key: i16,
         api_version: i16,
@@ -157,4 +1039,20 @@ mod tests {
 
         MessageFramer::frame(&payload).expect("frame should succeed")
     }
+
+    fn build_request_with_body(
+        api_key: i16,
+        api_version: i16,
+        correlation_id: i32,
+        body: &[u8],
+    ) -> Vec<u8> {
+        let mut payload = Vec::new();
+        payload.extend_from_slice(&api_key.to_be_bytes());
+        payload.extend_from_slice(&api_version.to_be_bytes());
+        payload.extend_from_slice(&correlation_id.to_be_bytes());
+        wire::write_nullable_string(&mut payload, Some("client"), false);
+        payload.extend_from_slice(body);
+
+        MessageFramer::frame(&payload).expect("frame should succeed")
+    }
 }
@@ -0,0 +1,250 @@
+use super::metrics::ConnectionMetrics;
+use crate::time::Clock;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::fmt;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Window over which `max.connection.creation.rate` is measured.
+const CREATION_RATE_WINDOW_MS: i64 = 1_000;
+
+/// Limits on how many connections the broker holds and how fast it opens them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConnectionLimits {
+    pub max_connections: usize,
+    pub max_connections_per_ip: usize,
+    /// Per-IP replacements for `max_connections_per_ip`.
+    pub max_connections_per_ip_overrides: BTreeMap<IpAddr, usize>,
+    /// Connections accepted per second before the acceptors pause.
+    pub max_connection_creation_rate: usize,
+}
+
+impl Default for ConnectionLimits {
+    fn default() -> Self {
+        Self {
+            max_connections: i32::MAX as usize,
+            max_connections_per_ip: i32::MAX as usize,
+            max_connections_per_ip_overrides: BTreeMap::new(),
+            max_connection_creation_rate: i32::MAX as usize,
+        }
+    }
+}
+
+impl ConnectionLimits {
+    fn max_for(&self, ip: &IpAddr) -> usize {
+        self.max_connections_per_ip_overrides
+            .get(ip)
+            .copied()
+            .unwrap_or(self.max_connections_per_ip)
+    }
+}
+
+/// The limit that refused a connection.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum QuotaViolation {
+    MaxConnections { limit: usize },
+    MaxConnectionsPerIp { ip: IpAddr, limit: usize },
+}
+
+impl fmt::Display for QuotaViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MaxConnections { limit } => {
+                write!(f, "broker already has max.connections={limit} connections")
+            }
+            Self::MaxConnectionsPerIp { ip, limit } => {
+                write!(f, "{ip} already has its limit of {limit} connections")
+            }
+        }
+    }
+}
+
+/// Tracks open connections against [`ConnectionLimits`], shared by every
+/// listener.
+#[derive(Debug)]
+pub struct ConnectionQuotas {
+    limits: ConnectionLimits,
+    clock: Arc<dyn Clock>,
+    metrics: Arc<ConnectionMetrics>,
+    state: Mutex<QuotaState>,
+}
+
+#[derive(Debug, Default)]
+struct QuotaState {
+    total: usize,
+    per_ip: HashMap<IpAddr, usize>,
+    /// Accept times within the last rate window, oldest first.
+    recent_creations: VecDeque<i64>,
+}
+
+impl ConnectionQuotas {
+    pub fn new(
+        limits: ConnectionLimits,
+        clock: Arc<dyn Clock>,
+        metrics: Arc<ConnectionMetrics>,
+    ) -> Arc<Self> {
+        Arc::new(Self {
+            limits,
+            clock,
+            metrics,
+            state: Mutex::default(),
+        })
+    }
+
+    /// Reserves a slot for a connection from `ip`, which is `None` for Unix
+    /// domain sockets, until the returned slot is dropped.
+    pub fn acquire(self: &Arc<Self>, ip: Option<IpAddr>) -> Result<ConnectionSlot, QuotaViolation> {
+        let mut state = self.state.lock().expect("connection quota lock poisoned");
+        if state.total >= self.limits.max_connections {
+            self.metrics.rejected_max_connections.increment();
+            return Err(QuotaViolation::MaxConnections {
+                limit: self.limits.max_connections,
+            });
+        }
+        if let Some(ip) = ip {
+            let limit = self.limits.max_for(&ip);
+            if state.per_ip.get(&ip).copied().unwrap_or(0) >= limit {
+                self.metrics.rejected_per_ip.increment();
+                return Err(QuotaViolation::MaxConnectionsPerIp { ip, limit });
+            }
+            *state.per_ip.entry(ip).or_default() += 1;
+        }
+        state.total += 1;
+        Ok(ConnectionSlot {
+            quotas: Arc::clone(self),
+            ip,
+        })
+    }
+
+    /// Records that a connection was accepted, returning how long the
+    /// acceptor should pause to stay within the creation rate.
+    pub fn record_creation(&self) -> Duration {
+        let now_ms = self.clock.now_ms();
+        let mut state = self.state.lock().expect("connection quota lock poisoned");
+        let recent = &mut state.recent_creations;
+        while recent
+            .front()
+            .is_some_and(|&created| created <= now_ms - CREATION_RATE_WINDOW_MS)
+        {
+            recent.pop_front();
+        }
+        recent.push_back(now_ms);
+
+        let rate = self.limits.max_connection_creation_rate;
+        if recent.len() <= rate {
+            return Duration::ZERO;
+        }
+        self.metrics.creation_throttled.increment();
+        // Wait until enough creations leave the window to get back to the rate.
+        let expires_ms = recent[recent.len() - rate - 1] + CREATION_RATE_WINDOW_MS;
+        Duration::from_millis((expires_ms - now_ms).max(0) as u64)
+    }
+
+    pub fn count(&self) -> usize {
+        self.state
+            .lock()
+            .expect("connection quota lock poisoned")
+            .total
+    }
+
+    fn release(&self, ip: Option<IpAddr>) {
+        let mut state = self.state.lock().expect("connection quota lock poisoned");
+        state.total -= 1;
+        if let Some(ip) = ip {
+            if let Some(count) = state.per_ip.get_mut(&ip) {
+                *count -= 1;
+                if *count == 0 {
+                    state.per_ip.remove(&ip);
+                }
+            }
+        }
+    }
+}
+
+/// A connection counted against the quotas while alive.
+#[derive(Debug)]
+pub struct ConnectionSlot {
+    quotas: Arc<ConnectionQuotas>,
+    ip: Option<IpAddr>,
+}
+
+impl Drop for ConnectionSlot {
+    fn drop(&mut self) {
+        self.quotas.release(self.ip);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::time::MockClock;
+
+    fn quotas(limits: ConnectionLimits, clock: Arc<MockClock>) -> Arc<ConnectionQuotas> {
+        ConnectionQuotas::new(limits, clock, Arc::new(ConnectionMetrics::default()))
+    }
+
+    #[test]
+    fn enforces_broker_and_per_ip_limits() {
+        let local: IpAddr = "127.0.0.1".parse().unwrap();
+        let trusted: IpAddr = "10.0.0.5".parse().unwrap();
+        let other: IpAddr = "10.0.0.6".parse().unwrap();
+        let quotas = quotas(
+            ConnectionLimits {
+                max_connections: 4,
+                max_connections_per_ip: 1,
+                max_connections_per_ip_overrides: BTreeMap::from([(trusted, 2)]),
+                ..ConnectionLimits::default()
+            },
+            MockClock::new(0),
+        );
+
+        let first = quotas.acquire(Some(local)).expect("first connection");
+        assert_eq!(
+            quotas.acquire(Some(local)).expect_err("per-ip limit"),
+            QuotaViolation::MaxConnectionsPerIp {
+                ip: local,
+                limit: 1
+            }
+        );
+        let _trusted = [
+            quotas.acquire(Some(trusted)).expect("override"),
+            quotas.acquire(Some(trusted)).expect("override"),
+        ];
+        let _unix = quotas.acquire(None).expect("unix socket");
+        assert_eq!(
+            quotas.acquire(Some(other)).expect_err("broker limit"),
+            QuotaViolation::MaxConnections { limit: 4 }
+        );
+
+        drop(first);
+        assert_eq!(quotas.count(), 3);
+        quotas.acquire(Some(local)).expect("slot was released");
+        assert_eq!(quotas.metrics.rejected_per_ip.get(), 1);
+        assert_eq!(quotas.metrics.rejected_max_connections.get(), 1);
+    }
+
+    #[test]
+    fn throttles_connections_over_the_creation_rate() {
+        let clock = MockClock::new(10_000);
+        let quotas = quotas(
+            ConnectionLimits {
+                max_connection_creation_rate: 2,
+                ..ConnectionLimits::default()
+            },
+            clock.clone(),
+        );
+
+        assert_eq!(quotas.record_creation(), Duration::ZERO);
+        clock.advance(300);
+        assert_eq!(quotas.record_creation(), Duration::ZERO);
+        clock.advance(100);
+        assert_eq!(quotas.record_creation(), Duration::from_millis(600));
+
+        clock.advance(600);
+        assert_eq!(quotas.record_creation(), Duration::from_millis(300));
+        clock.advance(1_000);
+        assert_eq!(quotas.record_creation(), Duration::ZERO);
+        assert_eq!(quotas.metrics.creation_throttled.get(), 2);
+    }
+}
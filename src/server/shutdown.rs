@@ -0,0 +1,174 @@
+use super::listener::Connection;
+use signal_hook::consts::{SIGINT, SIGTERM};
+use signal_hook::flag;
+use std::collections::HashMap;
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+/// Exit status when a second signal arrives while the broker is draining.
+const FORCED_EXIT_STATUS: i32 = 1;
+
+/// Flag raised when the broker should stop accepting connections and exit.
+#[derive(Debug, Clone, Default)]
+pub struct ShutdownSignal {
+    triggered: Arc<AtomicBool>,
+}
+
+impl ShutdownSignal {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Raises the flag on SIGTERM or SIGINT. A second signal while shutting
+    /// down exits immediately, for operators who do not want to wait.
+    pub fn register_signals(&self) -> io::Result<()> {
+        for signal in [SIGTERM, SIGINT] {
+            flag::register_conditional_shutdown(
+                signal,
+                FORCED_EXIT_STATUS,
+                Arc::clone(&self.triggered),
+            )?;
+            flag::register(signal, Arc::clone(&self.triggered))?;
+        }
+        Ok(())
+    }
+
+    pub fn trigger(&self) {
+        self.triggered.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_triggered(&self) -> bool {
+        self.triggered.load(Ordering::SeqCst)
+    }
+}
+
+/// Counts connections that are being served, so shutdown can wait for their
+/// in-flight requests.
+#[derive(Debug, Clone, Default)]
+pub struct InFlight {
+    state: Arc<(Mutex<InFlightState>, Condvar)>,
+}
+
+#[derive(Debug, Default)]
+struct InFlightState {
+    count: usize,
+    next_id: u64,
+    /// Handles used to stop reading from connections at shutdown.
+    connections: HashMap<u64, Connection>,
+}
+
+impl InFlight {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Marks one connection as active until the returned guard is dropped.
+    pub fn start(&self) -> InFlightGuard {
+        let (state, _) = &*self.state;
+        state.lock().expect("in-flight lock poisoned").count += 1;
+        InFlightGuard {
+            in_flight: self.clone(),
+            id: None,
+        }
+    }
+
+    /// Like [`InFlight::start`], and lets [`InFlight::stop_reading`] end
+    /// `connection`'s request loop.
+    pub fn start_connection(&self, connection: &Connection) -> io::Result<InFlightGuard> {
+        let handle = connection.try_clone()?;
+        let (state, _) = &*self.state;
+        let mut state = state.lock().expect("in-flight lock poisoned");
+        let id = state.next_id;
+        state.next_id += 1;
+        state.count += 1;
+        state.connections.insert(id, handle);
+        Ok(InFlightGuard {
+            in_flight: self.clone(),
+            id: Some(id),
+        })
+    }
+
+    /// Closes the read side of every connection, so each finishes the
+    /// requests it has already read and then ends.
+    pub fn stop_reading(&self) {
+        let (state, _) = &*self.state;
+        let state = state.lock().expect("in-flight lock poisoned");
+        for connection in state.connections.values() {
+            let _ = connection.shutdown_read();
+        }
+    }
+
+    pub fn count(&self) -> usize {
+        let (state, _) = &*self.state;
+        state.lock().expect("in-flight lock poisoned").count
+    }
+
+    /// Waits up to `timeout` for every connection to finish, returning the
+    /// number still active afterwards.
+    pub fn wait_idle(&self, timeout: Duration) -> usize {
+        let deadline = Instant::now() + timeout;
+        let (state, finished) = &*self.state;
+        let mut state = state.lock().expect("in-flight lock poisoned");
+        while state.count > 0 {
+            let now = Instant::now();
+            if now >= deadline {
+                break;
+            }
+            state = finished
+                .wait_timeout(state, deadline - now)
+                .expect("in-flight lock poisoned")
+                .0;
+        }
+        state.count
+    }
+}
+
+/// Keeps a connection counted in [`InFlight`] while alive.
+#[derive(Debug)]
+pub struct InFlightGuard {
+    in_flight: InFlight,
+    id: Option<u64>,
+}
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        let (state, finished) = &*self.in_flight.state;
+        let mut state = state.lock().expect("in-flight lock poisoned");
+        state.count -= 1;
+        if let Some(id) = self.id {
+            state.connections.remove(&id);
+        }
+        finished.notify_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn wait_idle_returns_once_connections_finish() {
+        let in_flight = InFlight::new();
+        let guard = in_flight.start();
+        let worker = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(20));
+            drop(guard);
+        });
+
+        assert_eq!(in_flight.wait_idle(Duration::from_secs(5)), 0);
+        worker.join().expect("worker thread");
+    }
+
+    #[test]
+    fn wait_idle_gives_up_after_timeout() {
+        let in_flight = InFlight::new();
+        let _stuck = in_flight.start();
+        drop(in_flight.start());
+
+        assert_eq!(in_flight.wait_idle(Duration::from_millis(20)), 1);
+        assert_eq!(in_flight.count(), 1);
+    }
+}
@@ -0,0 +1,221 @@
+use crate::storage::TopicPartition;
+use std::collections::{BTreeMap, BTreeSet};
+
+pub const UNIFORM_ASSIGNOR: &str = "uniform";
+pub const RANGE_ASSIGNOR: &str = "range";
+/// Assignor used when no member of the group asks for one.
+pub const DEFAULT_ASSIGNOR: &str = UNIFORM_ASSIGNOR;
+
+/// Partitions assigned to each member, by member id.
+pub type GroupAssignment = BTreeMap<String, BTreeSet<TopicPartition>>;
+
+/// Computes the target assignment of a consumer group on the coordinator.
+pub trait PartitionAssignor: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// Assigns the partitions of `topics`, given as partition counts, to the
+    /// members subscribed to them. Every member of `subscriptions` gets an
+    /// entry. `current` is the previous target assignment.
+    fn assign(
+        &self,
+        subscriptions: &BTreeMap<String, BTreeSet<String>>,
+        topics: &BTreeMap<String, i32>,
+        current: &GroupAssignment,
+    ) -> GroupAssignment;
+}
+
+/// The assignor called `name`, if this coordinator has one.
+pub fn assignor(name: &str) -> Option<&'static dyn PartitionAssignor> {
+    match name {
+        UNIFORM_ASSIGNOR => Some(&UniformAssignor),
+        RANGE_ASSIGNOR => Some(&RangeAssignor),
+        _ => None,
+    }
+}
+
+/// Spreads every subscribed partition evenly over the members, keeping
+/// partitions where they were assigned before as far as balance allows.
+pub struct UniformAssignor;
+
+impl PartitionAssignor for UniformAssignor {
+    fn name(&self) -> &'static str {
+        UNIFORM_ASSIGNOR
+    }
+
+    fn assign(
+        &self,
+        subscriptions: &BTreeMap<String, BTreeSet<String>>,
+        topics: &BTreeMap<String, i32>,
+        current: &GroupAssignment,
+    ) -> GroupAssignment {
+        let mut assignment = empty_assignment(subscriptions);
+        let partitions: Vec<TopicPartition> = topics
+            .iter()
+            .filter(|(topic, _)| subscriptions.values().any(|topics| topics.contains(*topic)))
+            .flat_map(|(topic, count)| {
+                (0..*count).map(move |partition| TopicPartition::new(topic.clone(), partition))
+            })
+            .collect();
+        let subscribed_members = subscriptions
+            .values()
+            .filter(|topics| !topics.is_empty())
+            .count();
+        if subscribed_members == 0 {
+            return assignment;
+        }
+
+        // Members first keep up to their minimum share of what they had.
+        let min_quota = partitions.len() / subscribed_members;
+        let mut unassigned: BTreeSet<&TopicPartition> = partitions.iter().collect();
+        for (member_id, owned) in current {
+            let (Some(topics), Some(assigned)) =
+                (subscriptions.get(member_id), assignment.get_mut(member_id))
+            else {
+                continue;
+            };
+            for tp in owned {
+                if assigned.len() >= min_quota {
+                    break;
+                }
+                if topics.contains(&tp.topic) && unassigned.remove(tp) {
+                    assigned.insert(tp.clone());
+                }
+            }
+        }
+
+        // The rest go to the least loaded subscribed member, preferring the
+        // previous owner on ties.
+        for tp in unassigned {
+            let previous_owner = current
+                .iter()
+                .find(|(_, owned)| owned.contains(tp))
+                .map(|(member_id, _)| member_id.as_str());
+            let target = subscriptions
+                .iter()
+                .filter(|(_, topics)| topics.contains(&tp.topic))
+                .map(|(member_id, _)| member_id)
+                .min_by_key(|member_id| {
+                    (
+                        assignment.get(*member_id).map_or(0, BTreeSet::len),
+                        Some(member_id.as_str()) != previous_owner,
+                    )
+                })
+                .cloned();
+            if let Some(member_id) = target {
+                assignment.entry(member_id).or_default().insert(tp.clone());
+            }
+        }
+        assignment
+    }
+}
+
+/// Gives each subscribed member a contiguous range of every topic's
+/// partitions, ordered by member id, with the first members taking one extra
+/// partition when the count does not divide evenly.
+pub struct RangeAssignor;
+
+impl PartitionAssignor for RangeAssignor {
+    fn name(&self) -> &'static str {
+        RANGE_ASSIGNOR
+    }
+
+    fn assign(
+        &self,
+        subscriptions: &BTreeMap<String, BTreeSet<String>>,
+        topics: &BTreeMap<String, i32>,
+        _current: &GroupAssignment,
+    ) -> GroupAssignment {
+        let mut assignment = empty_assignment(subscriptions);
+        for (topic, count) in topics {
+            let members: Vec<&String> = subscriptions
+                .iter()
+                .filter(|(_, topics)| topics.contains(topic))
+                .map(|(member_id, _)| member_id)
+                .collect();
+            if members.is_empty() {
+                continue;
+            }
+            let share = *count as usize / members.len();
+            let extra = *count as usize % members.len();
+            let mut next = 0;
+            for (index, member_id) in members.into_iter().enumerate() {
+                let take = share + usize::from(index < extra);
+                let assigned = assignment.entry(member_id.clone()).or_default();
+                for partition in next..next + take {
+                    assigned.insert(TopicPartition::new(topic.clone(), partition as i32));
+                }
+                next += take;
+            }
+        }
+        assignment
+    }
+}
+
+fn empty_assignment(subscriptions: &BTreeMap<String, BTreeSet<String>>) -> GroupAssignment {
+    subscriptions
+        .keys()
+        .map(|member_id| (member_id.clone(), BTreeSet::new()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn subscriptions(members: &[(&str, &[&str])]) -> BTreeMap<String, BTreeSet<String>> {
+        members
+            .iter()
+            .map(|(member_id, topics)| {
+                let topics = topics.iter().map(|topic| topic.to_string()).collect();
+                (member_id.to_string(), topics)
+            })
+            .collect()
+    }
+
+    fn partitions(assignment: &GroupAssignment, member_id: &str) -> Vec<(String, i32)> {
+        assignment[member_id]
+            .iter()
+            .map(|tp| (tp.topic.clone(), tp.partition))
+            .collect()
+    }
+
+    #[test]
+    fn range_splits_each_topic_into_contiguous_ranges() {
+        let members = subscriptions(&[("a", &["orders", "audit"]), ("b", &["orders"])]);
+        let topics = BTreeMap::from([("orders".to_string(), 3), ("audit".to_string(), 2)]);
+
+        let assignment = RangeAssignor.assign(&members, &topics, &GroupAssignment::new());
+
+        assert_eq!(
+            partitions(&assignment, "a"),
+            [
+                ("audit".to_string(), 0),
+                ("audit".to_string(), 1),
+                ("orders".to_string(), 0),
+                ("orders".to_string(), 1)
+            ]
+        );
+        assert_eq!(partitions(&assignment, "b"), [("orders".to_string(), 2)]);
+    }
+
+    #[test]
+    fn uniform_balances_and_keeps_previous_owners() {
+        let topics = BTreeMap::from([("orders".to_string(), 4)]);
+        let first = UniformAssignor.assign(
+            &subscriptions(&[("a", &["orders"])]),
+            &topics,
+            &GroupAssignment::new(),
+        );
+        assert_eq!(first["a"].len(), 4);
+
+        let second = UniformAssignor.assign(
+            &subscriptions(&[("a", &["orders"]), ("b", &["orders"])]),
+            &topics,
+            &first,
+        );
+
+        assert_eq!(second["a"].len(), 2);
+        assert_eq!(second["b"].len(), 2);
+        assert!(second["a"].is_subset(&first["a"]));
+    }
+}
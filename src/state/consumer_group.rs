@@ -0,0 +1,393 @@
+use super::assignor::{self, GroupAssignment, DEFAULT_ASSIGNOR};
+use crate::metadata::Uuid;
+use crate::storage::TopicPartition;
+use regex::Regex;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+/// Id and partition count of a topic, as seen when a heartbeat is handled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TopicMetadata {
+    pub id: Uuid,
+    pub partitions: i32,
+}
+
+/// Stage of a group using the consumer rebalance protocol (KIP-848).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConsumerGroupState {
+    Empty,
+    /// The group epoch moved past the target assignment, which must be
+    /// recomputed.
+    Assigning,
+    /// Members are still converging on the target assignment.
+    Reconciling,
+    Stable,
+}
+
+impl ConsumerGroupState {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Empty => "Empty",
+            Self::Assigning => "Assigning",
+            Self::Reconciling => "Reconciling",
+            Self::Stable => "Stable",
+        }
+    }
+}
+
+/// Where a member is in reconciling its target assignment.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MemberState {
+    /// The member owns its whole target assignment at the current epoch.
+    Stable,
+    /// The member must give up partitions before it moves to the new epoch.
+    UnrevokedPartitions,
+    /// The member moved to the new epoch but waits for partitions other
+    /// members still own.
+    UnreleasedPartitions,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConsumerMember {
+    pub member_id: String,
+    pub instance_id: Option<String>,
+    pub rack_id: Option<String>,
+    pub client_id: String,
+    pub client_host: String,
+    pub member_epoch: i32,
+    /// Epoch before the member's last epoch change, restored when a static
+    /// member that left temporarily comes back.
+    pub previous_member_epoch: i32,
+    pub rebalance_timeout_ms: i32,
+    pub subscribed_topic_names: Vec<String>,
+    pub subscribed_topic_regex: Option<String>,
+    pub server_assignor: Option<String>,
+    pub state: MemberState,
+    /// Partitions the member may consume.
+    pub assigned: BTreeSet<TopicPartition>,
+    /// Partitions the member was asked to give up and still owns.
+    pub pending_revocation: BTreeSet<TopicPartition>,
+    /// When the member is fenced if it has not revoked its partitions yet.
+    pub revocation_deadline_ms: Option<i64>,
+    /// Bumped on every heartbeat, so a pending session expiry can tell it was
+    /// superseded.
+    pub heartbeats: u64,
+}
+
+impl ConsumerMember {
+    pub fn new(member_id: &str) -> Self {
+        Self {
+            member_id: member_id.to_string(),
+            instance_id: None,
+            rack_id: None,
+            client_id: String::new(),
+            client_host: String::new(),
+            member_epoch: 0,
+            previous_member_epoch: 0,
+            rebalance_timeout_ms: 0,
+            subscribed_topic_names: Vec::new(),
+            subscribed_topic_regex: None,
+            server_assignor: None,
+            state: MemberState::Stable,
+            assigned: BTreeSet::new(),
+            pending_revocation: BTreeSet::new(),
+            revocation_deadline_ms: None,
+            heartbeats: 0,
+        }
+    }
+
+    /// Topics the member subscribes to by name or pattern, among `topics`.
+    pub fn subscribed_topics(&self, topics: &BTreeMap<String, TopicMetadata>) -> BTreeSet<String> {
+        let regex = self.subscription_regex();
+        topics
+            .keys()
+            .filter(|topic| {
+                self.subscribed_topic_names.contains(topic)
+                    || regex.as_ref().is_some_and(|regex| regex.is_match(topic))
+            })
+            .cloned()
+            .collect()
+    }
+
+    pub fn is_subscribed_to(&self, topic: &str) -> bool {
+        self.subscribed_topic_names.iter().any(|name| name == topic)
+            || self
+                .subscription_regex()
+                .is_some_and(|regex| regex.is_match(topic))
+    }
+
+    /// The subscription pattern, anchored so it must match whole topic names
+    /// as Java patterns do.
+    fn subscription_regex(&self) -> Option<Regex> {
+        let pattern = self.subscribed_topic_regex.as_deref()?;
+        Regex::new(&format!("^(?:{pattern})$")).ok()
+    }
+}
+
+/// Whether `pattern` is a valid subscription regex.
+pub fn is_valid_regex(pattern: &str) -> bool {
+    Regex::new(pattern).is_ok()
+}
+
+/// A group using the consumer rebalance protocol: the coordinator computes a
+/// target assignment whenever the group epoch moves, and each member
+/// converges on its share through heartbeats, revoking partitions before it
+/// is given ones another member still owns.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ConsumerGroup {
+    /// Bumped whenever membership, subscriptions or subscribed topics change.
+    pub group_epoch: i32,
+    /// Group epoch the target assignment was computed at.
+    pub assignment_epoch: i32,
+    pub members: BTreeMap<String, ConsumerMember>,
+    /// Current member id of each static member, by instance id.
+    pub static_members: HashMap<String, String>,
+    pub target_assignment: GroupAssignment,
+    /// Partition counts of the subscribed topics at the last epoch bump.
+    pub subscribed_topics: BTreeMap<String, i32>,
+    /// Assignor that computed the target assignment.
+    pub assignor_name: String,
+}
+
+impl ConsumerGroup {
+    pub fn state(&self) -> ConsumerGroupState {
+        if self.members.is_empty() {
+            ConsumerGroupState::Empty
+        } else if self.group_epoch > self.assignment_epoch {
+            ConsumerGroupState::Assigning
+        } else if self.members.values().any(|member| {
+            member.member_epoch != self.assignment_epoch || member.state != MemberState::Stable
+        }) {
+            ConsumerGroupState::Reconciling
+        } else {
+            ConsumerGroupState::Stable
+        }
+    }
+
+    pub fn is_subscribed_to(&self, topic: &str) -> bool {
+        self.members
+            .values()
+            .any(|member| member.is_subscribed_to(topic))
+    }
+
+    /// Removes a member and bumps the group epoch so its partitions are
+    /// reassigned.
+    pub fn remove_member(&mut self, member_id: &str) -> Option<ConsumerMember> {
+        let member = self.members.remove(member_id)?;
+        if let Some(instance_id) = &member.instance_id {
+            if self.static_members.get(instance_id) == Some(&member.member_id) {
+                self.static_members.remove(instance_id);
+            }
+        }
+        self.target_assignment.remove(member_id);
+        self.group_epoch += 1;
+        Some(member)
+    }
+
+    /// Moves a static member that left temporarily to `new_member_id`,
+    /// keeping its assignment and epoch.
+    pub fn replace_static_member(
+        &mut self,
+        instance_id: &str,
+        new_member_id: &str,
+    ) -> Option<&mut ConsumerMember> {
+        let old_member_id = self.static_members.get(instance_id)?.clone();
+        let mut member = self.members.remove(&old_member_id)?;
+        member.member_id = new_member_id.to_string();
+        member.member_epoch = member.previous_member_epoch;
+        if let Some(target) = self.target_assignment.remove(&old_member_id) {
+            self.target_assignment
+                .insert(new_member_id.to_string(), target);
+        }
+        self.static_members
+            .insert(instance_id.to_string(), new_member_id.to_string());
+        self.members.insert(new_member_id.to_string(), member);
+        self.members.get_mut(new_member_id)
+    }
+
+    /// Recomputes the target assignment if the group epoch moved past it,
+    /// bumping the epoch first if the subscribed topics changed in `topics`.
+    pub fn update_target_assignment(&mut self, topics: &BTreeMap<String, TopicMetadata>) {
+        let subscriptions: BTreeMap<String, BTreeSet<String>> = self
+            .members
+            .iter()
+            .map(|(member_id, member)| (member_id.clone(), member.subscribed_topics(topics)))
+            .collect();
+        let subscribed_topics: BTreeMap<String, i32> = subscriptions
+            .values()
+            .flatten()
+            .map(|topic| (topic.clone(), topics[topic].partitions))
+            .collect();
+        // One bump covers both membership and topic changes.
+        if subscribed_topics != self.subscribed_topics {
+            self.subscribed_topics = subscribed_topics;
+            if self.group_epoch <= self.assignment_epoch {
+                self.group_epoch += 1;
+            }
+        }
+        if self.group_epoch <= self.assignment_epoch {
+            return;
+        }
+
+        let assignor = assignor::assignor(&self.preferred_assignor())
+            .or_else(|| assignor::assignor(DEFAULT_ASSIGNOR))
+            .expect("the default assignor exists");
+        self.target_assignment = assignor.assign(
+            &subscriptions,
+            &self.subscribed_topics,
+            &self.target_assignment,
+        );
+        self.assignor_name = assignor.name().to_string();
+        self.assignment_epoch = self.group_epoch;
+    }
+
+    /// Moves a member towards its target assignment, given the partitions it
+    /// reported owning, if any, and returns whether its assignment changed.
+    ///
+    /// Partitions leaving the member are revoked first, at the old epoch.
+    /// Once the member no longer owns them it moves to the assignment epoch
+    /// and gets every target partition no other member still owns.
+    pub fn reconcile(
+        &mut self,
+        member_id: &str,
+        owned: Option<&BTreeSet<TopicPartition>>,
+        now_ms: i64,
+    ) -> bool {
+        let target = self
+            .target_assignment
+            .get(member_id)
+            .cloned()
+            .unwrap_or_default();
+        let owned_elsewhere: BTreeSet<TopicPartition> = self
+            .members
+            .values()
+            .filter(|member| member.member_id != member_id)
+            .flat_map(|member| member.assigned.iter().chain(&member.pending_revocation))
+            .cloned()
+            .collect();
+        let assignment_epoch = self.assignment_epoch;
+        let Some(member) = self.members.get_mut(member_id) else {
+            return false;
+        };
+        let before = member.assigned.clone();
+
+        if member.state == MemberState::UnrevokedPartitions {
+            let revoked = owned.is_some_and(|owned| owned.is_disjoint(&member.pending_revocation));
+            if !revoked {
+                return false;
+            }
+            member.pending_revocation.clear();
+            member.revocation_deadline_ms = None;
+        } else if member.member_epoch == assignment_epoch && member.state == MemberState::Stable {
+            return false;
+        }
+
+        let revoke: BTreeSet<TopicPartition> =
+            member.assigned.difference(&target).cloned().collect();
+        if !revoke.is_empty() {
+            member.assigned.retain(|tp| target.contains(tp));
+            member.pending_revocation = revoke;
+            member.revocation_deadline_ms =
+                Some(now_ms + i64::from(member.rebalance_timeout_ms.max(0)));
+            member.state = MemberState::UnrevokedPartitions;
+        } else {
+            if member.member_epoch != assignment_epoch {
+                member.previous_member_epoch = member.member_epoch;
+                member.member_epoch = assignment_epoch;
+            }
+            member.assigned = target
+                .iter()
+                .filter(|tp| member.assigned.contains(*tp) || !owned_elsewhere.contains(*tp))
+                .cloned()
+                .collect();
+            member.state = if member.assigned == target {
+                MemberState::Stable
+            } else {
+                MemberState::UnreleasedPartitions
+            };
+        }
+        member.assigned != before
+    }
+
+    /// Assignor most members ask for, or the default one.
+    fn preferred_assignor(&self) -> String {
+        let mut votes: BTreeMap<&str, usize> = BTreeMap::new();
+        for member in self.members.values() {
+            if let Some(name) = &member.server_assignor {
+                *votes.entry(name.as_str()).or_default() += 1;
+            }
+        }
+        votes
+            .into_iter()
+            .max_by(|(left_name, left), (right_name, right)| {
+                left.cmp(right).then(right_name.cmp(left_name))
+            })
+            .map_or_else(
+                || DEFAULT_ASSIGNOR.to_string(),
+                |(name, _)| name.to_string(),
+            )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn topics() -> BTreeMap<String, TopicMetadata> {
+        BTreeMap::from([
+            (
+                "orders".to_string(),
+                TopicMetadata {
+                    id: Uuid([1; 16]),
+                    partitions: 2,
+                },
+            ),
+            (
+                "orders-eu".to_string(),
+                TopicMetadata {
+                    id: Uuid([2; 16]),
+                    partitions: 1,
+                },
+            ),
+        ])
+    }
+
+    fn join(group: &mut ConsumerGroup, member_id: &str) {
+        let mut member = ConsumerMember::new(member_id);
+        member.subscribed_topic_regex = Some("orders.*".to_string());
+        group.members.insert(member_id.to_string(), member);
+        group.group_epoch += 1;
+        group.update_target_assignment(&topics());
+        group.reconcile(member_id, None, 0);
+    }
+
+    #[test]
+    fn partitions_move_only_after_their_owner_revokes_them() {
+        let mut group = ConsumerGroup::default();
+        join(&mut group, "a");
+        assert_eq!(group.members["a"].assigned.len(), 3);
+        assert_eq!(group.state(), ConsumerGroupState::Stable);
+
+        join(&mut group, "b");
+        assert!(group.members["b"].assigned.is_empty());
+        assert_eq!(group.members["b"].state, MemberState::UnreleasedPartitions);
+        assert_eq!(group.state(), ConsumerGroupState::Reconciling);
+
+        // "a" is asked to revoke at its old epoch...
+        assert!(group.reconcile("a", None, 0));
+        let a = &group.members["a"];
+        assert_eq!(a.state, MemberState::UnrevokedPartitions);
+        assert_eq!(a.member_epoch, 1);
+        let kept = a.assigned.clone();
+
+        // ...and moves on once it reports owning only what it kept.
+        group.reconcile("a", Some(&kept), 0);
+        assert_eq!(group.members["a"].member_epoch, group.assignment_epoch);
+        group.reconcile("b", None, 0);
+        assert_eq!(group.members["b"].state, MemberState::Stable);
+        assert_eq!(
+            group.members["a"].assigned.len() + group.members["b"].assigned.len(),
+            3
+        );
+        assert_eq!(group.state(), ConsumerGroupState::Stable);
+    }
+}
@@ -0,0 +1,2569 @@
+use super::assignor;
+use super::consumer_group::{self, ConsumerGroup, ConsumerMember, TopicMetadata};
+use super::group::{
+    Group, GroupOverview, GroupState, GroupSummary, JoinResult, Member, SyncResult,
+    CONSUMER_PROTOCOL_TYPE,
+};
+use super::offsets::{self, OffsetAndMetadata};
+use super::purgatory::{DelayedOperation, Purgatory};
+use super::share_group::{DeliveryLimits, ShareGroup, ShareMember, SharePartition, ShareSession};
+use crate::metadata::Uuid;
+use crate::protocol::consumer_group_heartbeat::{
+    JOIN_GROUP_MEMBER_EPOCH, LEAVE_GROUP_MEMBER_EPOCH, LEAVE_GROUP_STATIC_MEMBER_EPOCH,
+};
+use crate::protocol::error;
+use crate::protocol::join_group::JoinGroupRequestProtocol;
+use crate::protocol::share_fetch::{
+    AcquiredRecords, FINAL_SHARE_SESSION_EPOCH, INITIAL_SHARE_SESSION_EPOCH,
+};
+use crate::storage::{LogManager, TopicPartition};
+use crate::time::Clock;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::io;
+use std::sync::mpsc::{self, Receiver};
+use std::sync::{Arc, Mutex, MutexGuard, Weak};
+
+pub const DEFAULT_MIN_SESSION_TIMEOUT_MS: i32 = 6_000;
+pub const DEFAULT_MAX_SESSION_TIMEOUT_MS: i32 = 30 * 60 * 1_000;
+pub const DEFAULT_OFFSETS_TOPIC_PARTITIONS: i32 = 50;
+pub const DEFAULT_OFFSETS_RETENTION_MS: i64 = 7 * 24 * 60 * 60 * 1_000;
+pub const DEFAULT_OFFSET_METADATA_MAX_BYTES: usize = 4_096;
+pub const DEFAULT_CONSUMER_SESSION_TIMEOUT_MS: i32 = 45_000;
+pub const DEFAULT_CONSUMER_HEARTBEAT_INTERVAL_MS: i32 = 5_000;
+pub const DEFAULT_SHARE_SESSION_TIMEOUT_MS: i32 = 45_000;
+pub const DEFAULT_SHARE_HEARTBEAT_INTERVAL_MS: i32 = 5_000;
+pub const DEFAULT_SHARE_RECORD_LOCK_DURATION_MS: i32 = 30_000;
+pub const DEFAULT_SHARE_DELIVERY_COUNT_LIMIT: i16 = 5;
+
+/// Limits on the groups the coordinator accepts and how their offsets are
+/// kept.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GroupConfig {
+    pub min_session_timeout_ms: i32,
+    pub max_session_timeout_ms: i32,
+    /// Members a group may hold, as `group.max.size`.
+    pub max_size: usize,
+    /// Partitions of the offsets topic that groups are spread over.
+    pub offsets_topic_partitions: i32,
+    /// How long offsets of an empty group are kept.
+    pub offsets_retention_ms: i64,
+    /// Longest metadata string accepted with a committed offset.
+    pub offset_metadata_max_bytes: usize,
+    /// Session timeout of members using the consumer rebalance protocol.
+    pub consumer_session_timeout_ms: i32,
+    /// How often members using the consumer rebalance protocol heartbeat.
+    pub consumer_heartbeat_interval_ms: i32,
+    /// Session timeout of share group members.
+    pub share_session_timeout_ms: i32,
+    /// How often share group members heartbeat.
+    pub share_heartbeat_interval_ms: i32,
+    /// How long a share group member holds the records it fetched.
+    pub share_record_lock_duration_ms: i32,
+    /// Deliveries of a record to share group members before it is archived.
+    pub share_delivery_count_limit: i16,
+}
+
+impl Default for GroupConfig {
+    fn default() -> Self {
+        Self {
+            min_session_timeout_ms: DEFAULT_MIN_SESSION_TIMEOUT_MS,
+            max_session_timeout_ms: DEFAULT_MAX_SESSION_TIMEOUT_MS,
+            max_size: i32::MAX as usize,
+            offsets_topic_partitions: DEFAULT_OFFSETS_TOPIC_PARTITIONS,
+            offsets_retention_ms: DEFAULT_OFFSETS_RETENTION_MS,
+            offset_metadata_max_bytes: DEFAULT_OFFSET_METADATA_MAX_BYTES,
+            consumer_session_timeout_ms: DEFAULT_CONSUMER_SESSION_TIMEOUT_MS,
+            consumer_heartbeat_interval_ms: DEFAULT_CONSUMER_HEARTBEAT_INTERVAL_MS,
+            share_session_timeout_ms: DEFAULT_SHARE_SESSION_TIMEOUT_MS,
+            share_heartbeat_interval_ms: DEFAULT_SHARE_HEARTBEAT_INTERVAL_MS,
+            share_record_lock_duration_ms: DEFAULT_SHARE_RECORD_LOCK_DURATION_MS,
+            share_delivery_count_limit: DEFAULT_SHARE_DELIVERY_COUNT_LIMIT,
+        }
+    }
+}
+
+/// A member's JoinGroup call.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JoinParams {
+    pub group_id: String,
+    /// Empty for a member joining for the first time.
+    pub member_id: String,
+    pub group_instance_id: Option<String>,
+    pub client_id: String,
+    pub client_host: String,
+    pub session_timeout_ms: i32,
+    pub rebalance_timeout_ms: i32,
+    pub protocol_type: String,
+    pub protocols: Vec<JoinGroupRequestProtocol>,
+    /// Whether a new member must retry with the id it is given before it
+    /// counts as joined (JoinGroup version 4+).
+    pub require_known_member_id: bool,
+}
+
+/// A member's SyncGroup call.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyncParams {
+    pub group_id: String,
+    pub generation_id: i32,
+    pub member_id: String,
+    pub group_instance_id: Option<String>,
+    pub protocol_type: Option<String>,
+    pub protocol_name: Option<String>,
+    /// The leader's assignment for each member; empty for followers.
+    pub assignments: HashMap<String, Vec<u8>>,
+}
+
+/// A member's OffsetCommit call, or a commit from outside the group when
+/// the generation is negative.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommitParams {
+    pub group_id: String,
+    pub generation_id: i32,
+    pub member_id: String,
+    pub group_instance_id: Option<String>,
+    pub offsets: Vec<OffsetCommit>,
+}
+
+/// One partition's offset in a commit.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OffsetCommit {
+    pub partition: TopicPartition,
+    pub offset: i64,
+    pub leader_epoch: i32,
+    pub metadata: Option<String>,
+}
+
+/// A member leaving the group, named by its member id, by its group
+/// instance id, or by both.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MemberIdentity {
+    /// Empty to name a static member by its instance id alone.
+    pub member_id: String,
+    pub group_instance_id: Option<String>,
+}
+
+/// A member's ConsumerGroupHeartbeat call.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConsumerHeartbeatParams {
+    pub group_id: String,
+    /// Empty for a member joining for the first time.
+    pub member_id: String,
+    pub member_epoch: i32,
+    pub instance_id: Option<String>,
+    pub rack_id: Option<String>,
+    pub client_id: String,
+    pub client_host: String,
+    /// `-1` if unchanged.
+    pub rebalance_timeout_ms: i32,
+    /// `None` if unchanged.
+    pub subscribed_topic_names: Option<Vec<String>>,
+    /// `None` if unchanged; empty to drop the pattern.
+    pub subscribed_topic_regex: Option<String>,
+    pub server_assignor: Option<String>,
+    /// Partitions the member owns, or `None` if unchanged.
+    pub owned_partitions: Option<BTreeSet<TopicPartition>>,
+}
+
+/// What a ConsumerGroupHeartbeat or ShareGroupHeartbeat call returns to the
+/// member.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConsumerHeartbeatResult {
+    pub member_id: String,
+    pub member_epoch: i32,
+    pub heartbeat_interval_ms: i32,
+    /// The partitions the member may consume, or `None` if they did not
+    /// change.
+    pub assignment: Option<BTreeSet<TopicPartition>>,
+}
+
+/// A member's ShareGroupHeartbeat call.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShareHeartbeatParams {
+    pub group_id: String,
+    /// Empty for a member joining for the first time.
+    pub member_id: String,
+    pub member_epoch: i32,
+    pub rack_id: Option<String>,
+    pub client_id: String,
+    pub client_host: String,
+    /// `None` if unchanged.
+    pub subscribed_topic_names: Option<Vec<String>>,
+}
+
+/// A share group member's acknowledgement of a range of offsets.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShareAcknowledgement {
+    pub partition: TopicPartition,
+    pub first_offset: i64,
+    pub last_offset: i64,
+    /// One type for the whole range, or one per offset.
+    pub acknowledge_types: Vec<i8>,
+}
+
+/// A share group member's ShareFetch call.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShareFetchParams {
+    pub group_id: String,
+    pub member_id: String,
+    pub share_session_epoch: i32,
+    /// Partitions added to the share session.
+    pub partitions: Vec<TopicPartition>,
+    /// Partitions removed from the share session.
+    pub forgotten: Vec<TopicPartition>,
+    pub acknowledgements: Vec<ShareAcknowledgement>,
+    pub max_bytes: usize,
+}
+
+/// A share group member's ShareAcknowledge call.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShareAcknowledgeParams {
+    pub group_id: String,
+    pub member_id: String,
+    pub share_session_epoch: i32,
+    pub acknowledgements: Vec<ShareAcknowledgement>,
+}
+
+/// Records a ShareFetch call acquired from one partition, or why it could
+/// not.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShareFetchedPartition {
+    pub partition: TopicPartition,
+    pub error_code: i16,
+    pub records: Vec<u8>,
+    pub acquired: Vec<AcquiredRecords>,
+}
+
+/// What a ShareFetch call returns to the member.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ShareFetchResult {
+    pub partitions: Vec<ShareFetchedPartition>,
+    /// Outcome of the acknowledgements sent with the call, by partition.
+    pub acknowledge_errors: BTreeMap<TopicPartition, i16>,
+}
+
+/// Identifies a member, or a pending member, across purgatory keys.
+type MemberKey = (String, String);
+
+/// A response that is either ready or sent once the group moves on.
+enum Reply<T> {
+    Now(T),
+    Later(Receiver<T>),
+}
+
+/// A session timer to (re)start once the groups lock is released.
+struct SessionExpiry {
+    key: MemberKey,
+    timeout_ms: i64,
+    heartbeats: u64,
+}
+
+impl SessionExpiry {
+    fn for_member(group_id: &str, member: &Member) -> Self {
+        Self {
+            key: (group_id.to_string(), member.member_id.clone()),
+            timeout_ms: i64::from(member.session_timeout_ms),
+            heartbeats: member.heartbeats,
+        }
+    }
+
+    fn for_consumer_member(group_id: &str, member: &ConsumerMember, timeout_ms: i32) -> Self {
+        Self {
+            key: (group_id.to_string(), member.member_id.clone()),
+            timeout_ms: i64::from(timeout_ms),
+            heartbeats: member.heartbeats,
+        }
+    }
+
+    fn for_share_member(group_id: &str, member: &ShareMember, timeout_ms: i32) -> Self {
+        Self {
+            key: (group_id.to_string(), member.member_id.clone()),
+            timeout_ms: i64::from(timeout_ms),
+            heartbeats: member.heartbeats,
+        }
+    }
+}
+
+/// Runs the classic consumer group protocol: members join, the coordinator
+/// picks a leader and a protocol, the leader computes an assignment that
+/// SyncGroup hands out, and heartbeats keep sessions alive.
+///
+/// JoinGroup and SyncGroup block the calling thread until the rebalance
+/// reaches the point where they can be answered. Rebalance and session
+/// timeouts are parked in purgatories; [`GroupCoordinator::expire_delayed`]
+/// must be called periodically to enforce them.
+///
+/// Static members, which carry a `group.instance.id`, keep their place and
+/// assignment when they restart within their session timeout; the id they
+/// held before the restart is fenced.
+///
+/// Groups may instead use the consumer rebalance protocol, where members
+/// only send ConsumerGroupHeartbeat and the coordinator computes the
+/// assignment itself. An empty group switches to whichever protocol the next
+/// member uses.
+///
+/// Share groups (KIP-932) also heartbeat with the coordinator, but their
+/// members fetch from shared partitions: each record is acquired by one
+/// member under a time-limited lock and redelivered unless acknowledged.
+/// Share delivery state is kept in memory only.
+///
+/// Committed offsets are cached per group and written to the compacted
+/// `__consumer_offsets` topic. The groups lock is taken before the logs lock
+/// whenever both are held.
+#[derive(Debug)]
+pub struct GroupCoordinator {
+    config: GroupConfig,
+    clock: Arc<dyn Clock>,
+    logs: Arc<Mutex<LogManager>>,
+    groups: Mutex<HashMap<String, Group>>,
+    joins: Purgatory<String, DelayedJoin>,
+    sessions: Purgatory<MemberKey, DelayedHeartbeat>,
+}
+
+impl GroupCoordinator {
+    pub fn new(
+        config: GroupConfig,
+        logs: Arc<Mutex<LogManager>>,
+        clock: Arc<dyn Clock>,
+    ) -> Arc<Self> {
+        Arc::new(Self {
+            config,
+            logs,
+            groups: Mutex::new(HashMap::new()),
+            joins: Purgatory::new("rebalance", Arc::clone(&clock)),
+            sessions: Purgatory::new("heartbeat", Arc::clone(&clock)),
+            clock,
+        })
+    }
+
+    /// Loads the committed offsets from the offsets topic into empty groups,
+    /// returning how many groups have offsets.
+    pub fn load_offsets(&self) -> io::Result<usize> {
+        let loaded = {
+            let mut logs = self.logs.lock().expect("log manager lock poisoned");
+            offsets::load(&mut logs)?
+        };
+        let count = loaded.len();
+        let mut groups = self.lock();
+        for (group_id, offsets) in loaded {
+            groups
+                .entry(group_id.clone())
+                .or_insert_with(|| Group::new(&group_id))
+                .offsets = offsets;
+        }
+        Ok(count)
+    }
+
+    /// Adds or refreshes a member and waits until the rebalance it joins
+    /// completes.
+    pub fn join_group(self: &Arc<Self>, params: JoinParams) -> JoinResult {
+        let group_id = params.group_id.clone();
+        let member_id = params.member_id.clone();
+        let (reply, expiry) = self.begin_join(params);
+        if let Some(expiry) = expiry {
+            self.restart_session(expiry);
+        }
+        match reply {
+            Reply::Now(result) => result,
+            Reply::Later(receiver) => {
+                self.complete_join_when_ready(&group_id);
+                receiver
+                    .recv()
+                    .unwrap_or_else(|_| JoinResult::error(&member_id, error::UNKNOWN_MEMBER_ID))
+            }
+        }
+    }
+
+    /// Hands out the member's assignment, waiting for the leader's if the
+    /// group is still completing its rebalance.
+    pub fn sync_group(self: &Arc<Self>, params: SyncParams) -> SyncResult {
+        let (reply, expiry) = {
+            let mut groups = self.lock();
+            match begin_sync(&mut groups, params) {
+                Ok((reply, expiry)) => (reply, Some(expiry)),
+                Err(error_code) => (Reply::Now(SyncResult::error(error_code)), None),
+            }
+        };
+        if let Some(expiry) = expiry {
+            self.restart_session(expiry);
+        }
+        match reply {
+            Reply::Now(result) => result,
+            Reply::Later(receiver) => receiver
+                .recv()
+                .unwrap_or_else(|_| SyncResult::error(error::REBALANCE_IN_PROGRESS)),
+        }
+    }
+
+    /// Keeps a member's session alive, telling it whether it must rejoin.
+    pub fn heartbeat(
+        self: &Arc<Self>,
+        group_id: &str,
+        generation_id: i32,
+        member_id: &str,
+        group_instance_id: Option<&str>,
+    ) -> i16 {
+        let (error_code, expiry) = {
+            let mut groups = self.lock();
+            let Some(group) = groups.get_mut(group_id) else {
+                return error::UNKNOWN_MEMBER_ID;
+            };
+            match group.state {
+                GroupState::Dead => return error::COORDINATOR_NOT_AVAILABLE,
+                GroupState::Empty => return error::UNKNOWN_MEMBER_ID,
+                _ => {}
+            }
+            if group.is_static_member_fenced(member_id, group_instance_id) {
+                return error::FENCED_INSTANCE_ID;
+            }
+            let state = group.state;
+            let group_generation = group.generation_id;
+            let Some(member) = group.members.get_mut(member_id) else {
+                return error::UNKNOWN_MEMBER_ID;
+            };
+            if generation_id != group_generation {
+                return error::ILLEGAL_GENERATION;
+            }
+            member.record_heartbeat();
+            let error_code = if state == GroupState::PreparingRebalance {
+                error::REBALANCE_IN_PROGRESS
+            } else {
+                error::NONE
+            };
+            (error_code, SessionExpiry::for_member(group_id, member))
+        };
+        self.restart_session(expiry);
+        error_code
+    }
+
+    /// Removes members from a group, returning an error for the whole
+    /// request or one per member. Static members may be named by their
+    /// instance id alone.
+    pub fn leave_group(
+        self: &Arc<Self>,
+        group_id: &str,
+        members: &[MemberIdentity],
+    ) -> Result<Vec<i16>, i16> {
+        let results = {
+            let mut groups = self.lock();
+            let Some(group) = groups.get_mut(group_id) else {
+                return Ok(vec![error::UNKNOWN_MEMBER_ID; members.len()]);
+            };
+            if group.state == GroupState::Dead {
+                return Err(error::COORDINATOR_NOT_AVAILABLE);
+            }
+            members
+                .iter()
+                .map(|leaving| {
+                    let group_instance_id = leaving.group_instance_id.as_deref();
+                    let member_id = match group_instance_id {
+                        Some(instance_id) if leaving.member_id.is_empty() => group
+                            .static_members
+                            .get(instance_id)
+                            .cloned()
+                            .unwrap_or_default(),
+                        _ => leaving.member_id.clone(),
+                    };
+                    if group.pending_members.remove(&member_id) {
+                        error::NONE
+                    } else if group.is_static_member_fenced(&member_id, group_instance_id) {
+                        error::FENCED_INSTANCE_ID
+                    } else if group.remove_member(&member_id).is_some() {
+                        error::NONE
+                    } else {
+                        error::UNKNOWN_MEMBER_ID
+                    }
+                })
+                .collect()
+        };
+        self.complete_join_when_ready(group_id);
+        Ok(results)
+    }
+
+    /// Handles a heartbeat of a member using the consumer rebalance protocol:
+    /// the member joins, leaves or updates its subscription, the target
+    /// assignment is recomputed against `topics` if the group epoch moved,
+    /// and the member's partitions move one step towards its target.
+    pub fn consumer_group_heartbeat(
+        self: &Arc<Self>,
+        params: ConsumerHeartbeatParams,
+        topics: &BTreeMap<String, TopicMetadata>,
+    ) -> Result<ConsumerHeartbeatResult, i16> {
+        validate_consumer_heartbeat(&params)?;
+        let (result, expiry) = {
+            let mut groups = self.lock();
+            self.begin_consumer_heartbeat(&mut groups, params, topics)?
+        };
+        if let Some(expiry) = expiry {
+            self.restart_session(expiry);
+        }
+        Ok(result)
+    }
+
+    /// Handles a heartbeat of a share group member: the member joins, leaves
+    /// or updates its subscription, and gets its share of the subscribed
+    /// partitions in `topics`.
+    pub fn share_group_heartbeat(
+        self: &Arc<Self>,
+        params: ShareHeartbeatParams,
+        topics: &BTreeMap<String, TopicMetadata>,
+    ) -> Result<ConsumerHeartbeatResult, i16> {
+        if params.group_id.is_empty() {
+            return Err(error::INVALID_GROUP_ID);
+        }
+        let joining = params.member_epoch == JOIN_GROUP_MEMBER_EPOCH;
+        if (joining && params.subscribed_topic_names.is_none())
+            || (!joining && params.member_id.is_empty())
+        {
+            return Err(error::INVALID_REQUEST);
+        }
+        let (result, expiry) = {
+            let mut groups = self.lock();
+            self.begin_share_heartbeat(&mut groups, params, topics)?
+        };
+        if let Some(expiry) = expiry {
+            self.restart_session(expiry);
+        }
+        Ok(result)
+    }
+
+    /// Applies a share group member's acknowledgements, moves its share
+    /// session along, and acquires records for it from the session's
+    /// partitions. Closing the session releases every record it holds.
+    pub fn share_fetch(&self, params: ShareFetchParams) -> Result<ShareFetchResult, i16> {
+        let now_ms = self.clock.now_ms();
+        let limits = self.delivery_limits();
+        let initial = params.share_session_epoch == INITIAL_SHARE_SESSION_EPOCH;
+        if initial && !params.acknowledgements.is_empty() {
+            return Err(error::INVALID_REQUEST);
+        }
+        let mut groups = self.lock();
+        let share = share_group_mut(&mut groups, &params.group_id)?;
+        let member = share
+            .members
+            .get_mut(&params.member_id)
+            .ok_or(error::UNKNOWN_MEMBER_ID)?;
+        update_share_session(member, params.share_session_epoch)?;
+        let mut partitions = BTreeSet::new();
+        if let Some(session) = &mut member.session {
+            session.partitions.extend(params.partitions);
+            for tp in &params.forgotten {
+                session.partitions.remove(tp);
+            }
+            partitions = session.partitions.clone();
+        }
+
+        let acknowledge_errors = acknowledge_share_records(
+            share,
+            &params.member_id,
+            &params.acknowledgements,
+            now_ms,
+            limits,
+        );
+        let mut result = ShareFetchResult {
+            partitions: Vec::new(),
+            acknowledge_errors,
+        };
+        if params.share_session_epoch == FINAL_SHARE_SESSION_EPOCH {
+            share.release_member(&params.member_id, limits);
+            return Ok(result);
+        }
+
+        let logs = self.logs.lock().expect("log manager lock poisoned");
+        let mut remaining = params.max_bytes;
+        for tp in partitions {
+            let Some(log) = logs.get_log(&tp) else {
+                result.partitions.push(ShareFetchedPartition {
+                    partition: tp,
+                    error_code: error::UNKNOWN_TOPIC_OR_PARTITION,
+                    records: Vec::new(),
+                    acquired: Vec::new(),
+                });
+                continue;
+            };
+            // Share groups start from the latest offset.
+            let partition = share
+                .partitions
+                .entry(tp.clone())
+                .or_insert_with(|| SharePartition::new(log.high_watermark()));
+            if remaining == 0 {
+                continue;
+            }
+            match partition.acquire(&params.member_id, log, remaining, now_ms, limits) {
+                Ok((records, acquired)) => {
+                    if acquired.is_empty() {
+                        continue;
+                    }
+                    remaining = remaining.saturating_sub(records.len());
+                    result.partitions.push(ShareFetchedPartition {
+                        partition: tp,
+                        error_code: error::NONE,
+                        records,
+                        acquired,
+                    });
+                }
+                Err(err) => {
+                    eprintln!(
+                        "cannot read {tp} for share group {}: {err}",
+                        params.group_id
+                    );
+                    result.partitions.push(ShareFetchedPartition {
+                        partition: tp,
+                        error_code: error::KAFKA_STORAGE_ERROR,
+                        records: Vec::new(),
+                        acquired: Vec::new(),
+                    });
+                }
+            }
+        }
+        Ok(result)
+    }
+
+    /// Applies a share group member's acknowledgements and moves its share
+    /// session along, returning an error code per acknowledged partition.
+    pub fn share_acknowledge(
+        &self,
+        params: ShareAcknowledgeParams,
+    ) -> Result<BTreeMap<TopicPartition, i16>, i16> {
+        // Sessions are only opened by ShareFetch.
+        if params.share_session_epoch == INITIAL_SHARE_SESSION_EPOCH {
+            return Err(error::INVALID_SHARE_SESSION_EPOCH);
+        }
+        let now_ms = self.clock.now_ms();
+        let limits = self.delivery_limits();
+        let mut groups = self.lock();
+        let share = share_group_mut(&mut groups, &params.group_id)?;
+        let member = share
+            .members
+            .get_mut(&params.member_id)
+            .ok_or(error::UNKNOWN_MEMBER_ID)?;
+        update_share_session(member, params.share_session_epoch)?;
+        let errors = acknowledge_share_records(
+            share,
+            &params.member_id,
+            &params.acknowledgements,
+            now_ms,
+            limits,
+        );
+        if params.share_session_epoch == FINAL_SHARE_SESSION_EPOCH {
+            share.release_member(&params.member_id, limits);
+        }
+        Ok(errors)
+    }
+
+    /// Stores a member's offsets, returning an error for the whole request or
+    /// one per offset. A commit also counts as a heartbeat of the member.
+    pub fn commit_offsets(self: &Arc<Self>, params: CommitParams) -> Result<Vec<i16>, i16> {
+        let (results, expiry) = {
+            let mut groups = self.lock();
+            if !groups.contains_key(&params.group_id) {
+                // Only commits from outside any generation create a group.
+                if params.generation_id >= 0 {
+                    return Err(error::ILLEGAL_GENERATION);
+                }
+                groups.insert(params.group_id.clone(), Group::new(&params.group_id));
+            }
+            let group = groups
+                .get_mut(&params.group_id)
+                .expect("group was just looked up");
+            let expiry = validate_commit(group, &params)?;
+            (self.store_offsets(group, params.offsets), expiry)
+        };
+        if let Some(expiry) = expiry {
+            self.restart_session(expiry);
+        }
+        Ok(results)
+    }
+
+    /// Committed offsets of `partitions`, or of every partition the group has
+    /// an offset for when `None`. Partitions without one map to `None`.
+    pub fn fetch_offsets(
+        &self,
+        group_id: &str,
+        partitions: Option<Vec<TopicPartition>>,
+    ) -> Result<Vec<(TopicPartition, Option<OffsetAndMetadata>)>, i16> {
+        let groups = self.lock();
+        let group = groups.get(group_id);
+        if group.is_some_and(|group| group.state == GroupState::Dead) {
+            return Err(error::COORDINATOR_NOT_AVAILABLE);
+        }
+        Ok(match partitions {
+            Some(partitions) => partitions
+                .into_iter()
+                .map(|tp| {
+                    let offset = group.and_then(|group| group.offsets.get(&tp)).cloned();
+                    (tp, offset)
+                })
+                .collect(),
+            None => group
+                .map(|group| {
+                    group
+                        .offsets
+                        .iter()
+                        .map(|(tp, offset)| (tp.clone(), Some(offset.clone())))
+                        .collect()
+                })
+                .unwrap_or_default(),
+        })
+    }
+
+    /// Deletes the offsets empty groups kept past `offsets.retention.minutes`
+    /// and forgets groups left with nothing, returning how many offsets
+    /// expired.
+    pub fn expire_offsets(&self) -> io::Result<usize> {
+        let now_ms = self.clock.now_ms();
+        let mut groups = self.lock();
+        let mut expired = 0;
+        for group in groups.values_mut() {
+            let tombstones: Vec<(TopicPartition, Option<OffsetAndMetadata>)> = group
+                .expired_offsets(now_ms, self.config.offsets_retention_ms)
+                .into_iter()
+                .map(|tp| (tp, None))
+                .collect();
+            if tombstones.is_empty() {
+                continue;
+            }
+            self.append_offsets(&group.group_id, &tombstones, now_ms)?;
+            for (tp, _) in &tombstones {
+                group.offsets.remove(tp);
+            }
+            println!(
+                "expired {} offsets of group {}",
+                tombstones.len(),
+                group.group_id
+            );
+            expired += tombstones.len();
+        }
+        groups.retain(|_, group| !group.is_unused());
+        Ok(expired)
+    }
+
+    /// Groups whose state is named in `states_filter`, ignoring case, or
+    /// every group when the filter is empty, ordered by id.
+    pub fn list_groups(&self, states_filter: &[String]) -> Vec<GroupOverview> {
+        let groups = self.lock();
+        let mut listed: Vec<GroupOverview> = groups
+            .values()
+            .filter(|group| {
+                states_filter.is_empty()
+                    || states_filter
+                        .iter()
+                        .any(|state| state.eq_ignore_ascii_case(group.state_name()))
+            })
+            .map(Group::overview)
+            .collect();
+        listed.sort_by(|left, right| left.group_id.cmp(&right.group_id));
+        listed
+    }
+
+    /// Members and state of a classic group, or `None` if there is none.
+    pub fn describe_group(&self, group_id: &str) -> Option<GroupSummary> {
+        self.lock()
+            .get(group_id)
+            .filter(|group| group.is_classic())
+            .map(Group::summary)
+    }
+
+    /// A group using the consumer rebalance protocol, or `None` if there is
+    /// none.
+    pub fn describe_consumer_group(&self, group_id: &str) -> Option<ConsumerGroup> {
+        self.lock()
+            .get(group_id)
+            .and_then(|group| group.consumer.clone())
+    }
+
+    /// Deletes empty groups along with their committed offsets, returning an
+    /// error code per group.
+    pub fn delete_groups(&self, group_ids: &[String]) -> Vec<i16> {
+        let now_ms = self.clock.now_ms();
+        let mut groups = self.lock();
+        group_ids
+            .iter()
+            .map(|group_id| {
+                if group_id.is_empty() {
+                    return error::INVALID_GROUP_ID;
+                }
+                let Some(group) = groups.get_mut(group_id) else {
+                    return error::GROUP_ID_NOT_FOUND;
+                };
+                if group.state == GroupState::Dead {
+                    return error::GROUP_ID_NOT_FOUND;
+                }
+                if !group.is_empty() {
+                    return error::NON_EMPTY_GROUP;
+                }
+                let tombstones: Vec<(TopicPartition, Option<OffsetAndMetadata>)> =
+                    group.offsets.keys().map(|tp| (tp.clone(), None)).collect();
+                if !tombstones.is_empty() {
+                    if let Err(err) = self.append_offsets(group_id, &tombstones, now_ms) {
+                        eprintln!("cannot delete offsets of group {group_id}: {err}");
+                        return error::NOT_COORDINATOR;
+                    }
+                }
+                group.state = GroupState::Dead;
+                groups.remove(group_id);
+                error::NONE
+            })
+            .collect()
+    }
+
+    /// Deletes the committed offsets of `partitions`, returning an error for
+    /// the whole request or one per partition. Offsets of topics the group's
+    /// members are still subscribed to are kept.
+    pub fn delete_offsets(
+        &self,
+        group_id: &str,
+        partitions: Vec<TopicPartition>,
+    ) -> Result<Vec<i16>, i16> {
+        let now_ms = self.clock.now_ms();
+        let mut groups = self.lock();
+        let group = groups
+            .get_mut(group_id)
+            .filter(|group| group.state != GroupState::Dead)
+            .ok_or(error::GROUP_ID_NOT_FOUND)?;
+        // Only consumer subscriptions tell which topics are still in use.
+        if !group.members.is_empty()
+            && group.protocol_type.as_deref() != Some(CONSUMER_PROTOCOL_TYPE)
+        {
+            return Err(error::NON_EMPTY_GROUP);
+        }
+        let results: Vec<i16> = partitions
+            .iter()
+            .map(|tp| {
+                if group.is_subscribed_to(&tp.topic) {
+                    error::GROUP_SUBSCRIBED_TO_TOPIC
+                } else {
+                    error::NONE
+                }
+            })
+            .collect();
+        let tombstones: Vec<(TopicPartition, Option<OffsetAndMetadata>)> = partitions
+            .into_iter()
+            .zip(&results)
+            .filter(|(tp, result)| **result == error::NONE && group.offsets.contains_key(tp))
+            .map(|(tp, _)| (tp, None))
+            .collect();
+        if tombstones.is_empty() {
+            return Ok(results);
+        }
+        if let Err(err) = self.append_offsets(group_id, &tombstones, now_ms) {
+            eprintln!("cannot delete offsets of group {group_id}: {err}");
+            return Err(error::NOT_COORDINATOR);
+        }
+        for (tp, _) in &tombstones {
+            group.offsets.remove(tp);
+        }
+        Ok(results)
+    }
+
+    /// Enforces rebalance and session timeouts that have passed, returning
+    /// how many fired.
+    pub fn expire_delayed(&self) -> usize {
+        self.joins.expire() + self.sessions.expire()
+    }
+
+    /// Current state of a group, or `None` if it does not exist.
+    pub fn group_state(&self, group_id: &str) -> Option<GroupState> {
+        self.lock().get(group_id).map(|group| group.state)
+    }
+
+    fn begin_join(
+        self: &Arc<Self>,
+        params: JoinParams,
+    ) -> (Reply<JoinResult>, Option<SessionExpiry>) {
+        let fail = |error_code| {
+            (
+                Reply::Now(JoinResult::error(&params.member_id, error_code)),
+                None,
+            )
+        };
+        if params.group_id.is_empty() {
+            return fail(error::INVALID_GROUP_ID);
+        }
+        if params.session_timeout_ms < self.config.min_session_timeout_ms
+            || params.session_timeout_ms > self.config.max_session_timeout_ms
+        {
+            return fail(error::INVALID_SESSION_TIMEOUT);
+        }
+
+        let mut groups = self.lock();
+        let is_new_member = params.member_id.is_empty();
+        if !groups.contains_key(&params.group_id) {
+            if !is_new_member {
+                return fail(error::UNKNOWN_MEMBER_ID);
+            }
+            groups.insert(params.group_id.clone(), Group::new(&params.group_id));
+        }
+        let group = groups
+            .get_mut(&params.group_id)
+            .expect("group was just looked up");
+        if group.state == GroupState::Dead {
+            return fail(error::COORDINATOR_NOT_AVAILABLE);
+        }
+        if group.share.is_some() {
+            return fail(error::INCONSISTENT_GROUP_PROTOCOL);
+        }
+        if group.consumer.is_some() {
+            if !group.is_empty() {
+                return fail(error::INCONSISTENT_GROUP_PROTOCOL);
+            }
+            group.consumer = None;
+        }
+        if !group.supports_protocols(&params.protocol_type, &params.protocols) {
+            return fail(error::INCONSISTENT_GROUP_PROTOCOL);
+        }
+
+        if is_new_member {
+            // Static members are named after their instance, others after
+            // their client.
+            let prefix = params
+                .group_instance_id
+                .as_deref()
+                .unwrap_or(&params.client_id);
+            let member_id = match Uuid::random() {
+                Ok(uuid) => format!("{prefix}-{uuid}"),
+                Err(err) => {
+                    eprintln!("cannot generate a member id: {err}");
+                    return fail(error::UNKNOWN_SERVER_ERROR);
+                }
+            };
+            if let Some(instance_id) = params.group_instance_id.clone() {
+                if group.static_members.contains_key(&instance_id) {
+                    return self.rejoin_static_member(group, &instance_id, member_id, params);
+                }
+                return (self.add_member(group, member_id, params), None);
+            }
+            if params.require_known_member_id {
+                // The client retries with this id; until it does, the id
+                // only holds up the rebalance for one session timeout.
+                group.pending_members.insert(member_id.clone());
+                let expiry = SessionExpiry {
+                    key: (params.group_id.clone(), member_id.clone()),
+                    timeout_ms: i64::from(params.session_timeout_ms),
+                    heartbeats: 0,
+                };
+                return (
+                    Reply::Now(JoinResult::error(&member_id, error::MEMBER_ID_REQUIRED)),
+                    Some(expiry),
+                );
+            }
+            return (self.add_member(group, member_id, params), None);
+        }
+        if group.pending_members.remove(&params.member_id) {
+            let member_id = params.member_id.clone();
+            return (self.add_member(group, member_id, params), None);
+        }
+
+        if group.is_static_member_fenced(&params.member_id, params.group_instance_id.as_deref()) {
+            return fail(error::FENCED_INSTANCE_ID);
+        }
+        let state = group.state;
+        let is_leader = group.is_leader(&params.member_id);
+        let Some(member) = group.members.get_mut(&params.member_id) else {
+            return fail(error::UNKNOWN_MEMBER_ID);
+        };
+        let unchanged = member.protocols == params.protocols;
+        let (sender, receiver) = mpsc::channel();
+        match state {
+            GroupState::Empty | GroupState::Dead => return fail(error::UNKNOWN_MEMBER_ID),
+            // Followers that rejoin without changes get the current generation
+            // back; anything else starts a new rebalance.
+            GroupState::CompletingRebalance if unchanged => {
+                return (
+                    Reply::Now(group.current_join_result(&params.member_id)),
+                    None,
+                )
+            }
+            GroupState::Stable if unchanged && !is_leader => {
+                return (
+                    Reply::Now(group.current_join_result(&params.member_id)),
+                    None,
+                )
+            }
+            _ => {
+                update_member(member, params);
+                member.awaiting_join = Some(sender);
+            }
+        }
+        if state != GroupState::PreparingRebalance {
+            group.prepare_rebalance();
+        }
+        (Reply::Later(receiver), None)
+    }
+
+    /// Applies a consumer heartbeat under the groups lock, returning the
+    /// member's renewed session unless it left.
+    fn begin_consumer_heartbeat(
+        &self,
+        groups: &mut HashMap<String, Group>,
+        params: ConsumerHeartbeatParams,
+        topics: &BTreeMap<String, TopicMetadata>,
+    ) -> Result<(ConsumerHeartbeatResult, Option<SessionExpiry>), i16> {
+        let now_ms = self.clock.now_ms();
+        if !groups.contains_key(&params.group_id) {
+            if params.member_epoch != JOIN_GROUP_MEMBER_EPOCH {
+                return Err(error::GROUP_ID_NOT_FOUND);
+            }
+            groups.insert(params.group_id.clone(), Group::new(&params.group_id));
+        }
+        let group = groups
+            .get_mut(&params.group_id)
+            .expect("group was just looked up");
+        if group.state == GroupState::Dead {
+            return Err(error::COORDINATOR_NOT_AVAILABLE);
+        }
+        if group.consumer.is_none() {
+            // Only an empty classic group may switch protocols.
+            if group.share.is_some() || !group.is_empty() || !group.pending_members.is_empty() {
+                return Err(error::GROUP_ID_NOT_FOUND);
+            }
+            group.consumer = Some(ConsumerGroup::default());
+        }
+        let interval_ms = self.config.consumer_heartbeat_interval_ms;
+        let consumer = group
+            .consumer
+            .as_mut()
+            .expect("group uses the consumer protocol");
+
+        if matches!(
+            params.member_epoch,
+            LEAVE_GROUP_MEMBER_EPOCH | LEAVE_GROUP_STATIC_MEMBER_EPOCH
+        ) {
+            let result = leave_consumer_group(consumer, &params, interval_ms)?;
+            if consumer.members.is_empty() {
+                group.empty_since_ms = Some(now_ms);
+            }
+            return Ok((result, None));
+        }
+
+        let mut epoch_bumped = false;
+        let member_id = if params.member_epoch == JOIN_GROUP_MEMBER_EPOCH {
+            let existing = params
+                .instance_id
+                .as_ref()
+                .and_then(|instance_id| consumer.static_members.get(instance_id))
+                .cloned();
+            if let Some(existing) = existing {
+                // The instance may only come back once it left with the
+                // static leave epoch.
+                if consumer.members[&existing].member_epoch != LEAVE_GROUP_STATIC_MEMBER_EPOCH {
+                    return Err(error::UNRELEASED_INSTANCE_ID);
+                }
+                let member_id = new_consumer_member_id(&params.member_id)?;
+                let instance_id = params.instance_id.as_deref().unwrap_or_default();
+                consumer.replace_static_member(instance_id, &member_id);
+                member_id
+            } else if consumer.members.contains_key(&params.member_id) {
+                params.member_id.clone()
+            } else {
+                if consumer.members.len() >= self.config.max_size {
+                    return Err(error::GROUP_MAX_SIZE_REACHED);
+                }
+                let member_id = new_consumer_member_id(&params.member_id)?;
+                let mut member = ConsumerMember::new(&member_id);
+                member.instance_id = params.instance_id.clone();
+                if let Some(instance_id) = &params.instance_id {
+                    consumer
+                        .static_members
+                        .insert(instance_id.clone(), member_id.clone());
+                }
+                consumer.members.insert(member_id.clone(), member);
+                epoch_bumped = true;
+                member_id
+            }
+        } else {
+            let member = consumer
+                .members
+                .get(&params.member_id)
+                .ok_or(error::UNKNOWN_MEMBER_ID)?;
+            if member.instance_id.is_some() && member.instance_id != params.instance_id {
+                return Err(error::FENCED_INSTANCE_ID);
+            }
+            // A member that missed the response moving it to a new epoch may
+            // retry from the previous one, as long as it owns nothing new.
+            let retried = params.member_epoch == member.previous_member_epoch
+                && params
+                    .owned_partitions
+                    .as_ref()
+                    .is_some_and(|owned| owned.is_subset(&member.assigned));
+            if params.member_epoch != member.member_epoch && !retried {
+                return Err(error::FENCED_MEMBER_EPOCH);
+            }
+            let revoked = params
+                .owned_partitions
+                .as_ref()
+                .is_some_and(|owned| owned.is_disjoint(&member.pending_revocation));
+            if !revoked
+                && member
+                    .revocation_deadline_ms
+                    .is_some_and(|deadline_ms| now_ms >= deadline_ms)
+            {
+                eprintln!(
+                    "member {} of group {} did not revoke its partitions in time",
+                    params.member_id, params.group_id
+                );
+                consumer.remove_member(&params.member_id);
+                return Err(error::FENCED_MEMBER_EPOCH);
+            }
+            params.member_id.clone()
+        };
+
+        let member = consumer
+            .members
+            .get_mut(&member_id)
+            .expect("member was just looked up");
+        if params.rack_id.is_some() {
+            member.rack_id = params.rack_id;
+        }
+        member.client_id = params.client_id;
+        member.client_host = params.client_host;
+        if params.rebalance_timeout_ms >= 0 {
+            member.rebalance_timeout_ms = params.rebalance_timeout_ms;
+        }
+        if let Some(names) = params.subscribed_topic_names {
+            epoch_bumped |= names != member.subscribed_topic_names;
+            member.subscribed_topic_names = names;
+        }
+        if let Some(pattern) = params.subscribed_topic_regex {
+            let pattern = Some(pattern).filter(|pattern| !pattern.is_empty());
+            epoch_bumped |= pattern != member.subscribed_topic_regex;
+            member.subscribed_topic_regex = pattern;
+        }
+        if params.server_assignor.is_some() {
+            epoch_bumped |= params.server_assignor != member.server_assignor;
+            member.server_assignor = params.server_assignor;
+        }
+        member.heartbeats += 1;
+        if epoch_bumped {
+            consumer.group_epoch += 1;
+        }
+
+        consumer.update_target_assignment(topics);
+        let changed = consumer.reconcile(&member_id, params.owned_partitions.as_ref(), now_ms);
+        let member = &consumer.members[&member_id];
+        let result = ConsumerHeartbeatResult {
+            member_id: member_id.clone(),
+            member_epoch: member.member_epoch,
+            heartbeat_interval_ms: interval_ms,
+            // Joining members get their assignment even if it is empty.
+            assignment: (changed || params.member_epoch == JOIN_GROUP_MEMBER_EPOCH)
+                .then(|| member.assigned.clone()),
+        };
+        let expiry = SessionExpiry::for_consumer_member(
+            &group.group_id,
+            member,
+            self.config.consumer_session_timeout_ms,
+        );
+        Ok((result, Some(expiry)))
+    }
+
+    /// Applies a share group heartbeat under the groups lock, returning the
+    /// member's renewed session unless it left.
+    fn begin_share_heartbeat(
+        &self,
+        groups: &mut HashMap<String, Group>,
+        params: ShareHeartbeatParams,
+        topics: &BTreeMap<String, TopicMetadata>,
+    ) -> Result<(ConsumerHeartbeatResult, Option<SessionExpiry>), i16> {
+        let now_ms = self.clock.now_ms();
+        if !groups.contains_key(&params.group_id) {
+            if params.member_epoch != JOIN_GROUP_MEMBER_EPOCH {
+                return Err(error::GROUP_ID_NOT_FOUND);
+            }
+            groups.insert(params.group_id.clone(), Group::new(&params.group_id));
+        }
+        let group = groups
+            .get_mut(&params.group_id)
+            .expect("group was just looked up");
+        if group.state == GroupState::Dead {
+            return Err(error::COORDINATOR_NOT_AVAILABLE);
+        }
+        if group.share.is_none() {
+            // Groups holding members or offsets keep their type.
+            if !group.is_unused() {
+                return Err(error::GROUP_ID_NOT_FOUND);
+            }
+            group.consumer = None;
+            group.share = Some(ShareGroup::default());
+        }
+        let limits = self.delivery_limits();
+        let interval_ms = self.config.share_heartbeat_interval_ms;
+        let share = group.share.as_mut().expect("group is a share group");
+
+        if params.member_epoch == LEAVE_GROUP_MEMBER_EPOCH {
+            share
+                .remove_member(&params.member_id, limits)
+                .ok_or(error::UNKNOWN_MEMBER_ID)?;
+            if share.members.is_empty() {
+                group.empty_since_ms = Some(now_ms);
+            }
+            let result = ConsumerHeartbeatResult {
+                member_id: params.member_id,
+                member_epoch: LEAVE_GROUP_MEMBER_EPOCH,
+                heartbeat_interval_ms: interval_ms,
+                assignment: None,
+            };
+            return Ok((result, None));
+        }
+
+        let mut epoch_bumped = false;
+        let member_id = if params.member_epoch == JOIN_GROUP_MEMBER_EPOCH
+            && !share.members.contains_key(&params.member_id)
+        {
+            if share.members.len() >= self.config.max_size {
+                return Err(error::GROUP_MAX_SIZE_REACHED);
+            }
+            let member_id = new_consumer_member_id(&params.member_id)?;
+            share
+                .members
+                .insert(member_id.clone(), ShareMember::new(&member_id));
+            epoch_bumped = true;
+            member_id
+        } else {
+            let member = share
+                .members
+                .get(&params.member_id)
+                .ok_or(error::UNKNOWN_MEMBER_ID)?;
+            if params.member_epoch != JOIN_GROUP_MEMBER_EPOCH
+                && params.member_epoch != member.member_epoch
+            {
+                return Err(error::FENCED_MEMBER_EPOCH);
+            }
+            params.member_id.clone()
+        };
+
+        let member = share
+            .members
+            .get_mut(&member_id)
+            .expect("member was just looked up");
+        if params.rack_id.is_some() {
+            member.rack_id = params.rack_id;
+        }
+        member.client_id = params.client_id;
+        member.client_host = params.client_host;
+        if let Some(names) = params.subscribed_topic_names {
+            epoch_bumped |= names != member.subscribed_topic_names;
+            member.subscribed_topic_names = names;
+        }
+        member.heartbeats += 1;
+        if epoch_bumped {
+            share.group_epoch += 1;
+        }
+
+        share.update_target_assignment(topics);
+        let changed = share.reconcile(&member_id);
+        let member = &share.members[&member_id];
+        let result = ConsumerHeartbeatResult {
+            member_id: member_id.clone(),
+            member_epoch: member.member_epoch,
+            heartbeat_interval_ms: interval_ms,
+            assignment: (changed || params.member_epoch == JOIN_GROUP_MEMBER_EPOCH)
+                .then(|| member.assigned.clone()),
+        };
+        let expiry = SessionExpiry::for_share_member(
+            &group.group_id,
+            member,
+            self.config.share_session_timeout_ms,
+        );
+        Ok((result, Some(expiry)))
+    }
+
+    /// Hands a restarted static member's place in the group to its new
+    /// member id. If the group is stable and the member's protocols did not
+    /// change, it gets the current generation back without a rebalance.
+    fn rejoin_static_member(
+        &self,
+        group: &mut Group,
+        group_instance_id: &str,
+        member_id: String,
+        params: JoinParams,
+    ) -> (Reply<JoinResult>, Option<SessionExpiry>) {
+        let state = group.state;
+        let Some(member) = group.replace_static_member(group_instance_id, &member_id) else {
+            return (
+                Reply::Now(JoinResult::error(&member_id, error::UNKNOWN_MEMBER_ID)),
+                None,
+            );
+        };
+        let unchanged = member.protocols == params.protocols;
+        update_member(member, params);
+        if state == GroupState::Stable && unchanged {
+            member.record_heartbeat();
+            let expiry = SessionExpiry::for_member(&group.group_id, &group.members[&member_id]);
+            let mut result = group.current_join_result(&member_id);
+            result.skip_assignment = group.is_leader(&member_id);
+            return (Reply::Now(result), Some(expiry));
+        }
+
+        let (sender, receiver) = mpsc::channel();
+        member.awaiting_join = Some(sender);
+        if state != GroupState::PreparingRebalance {
+            group.prepare_rebalance();
+        }
+        (Reply::Later(receiver), None)
+    }
+
+    fn add_member(
+        &self,
+        group: &mut Group,
+        member_id: String,
+        params: JoinParams,
+    ) -> Reply<JoinResult> {
+        if group.members.len() >= self.config.max_size {
+            return Reply::Now(JoinResult::error(&member_id, error::GROUP_MAX_SIZE_REACHED));
+        }
+        let (sender, receiver) = mpsc::channel();
+        if group.members.is_empty() {
+            group.protocol_type = Some(params.protocol_type.clone());
+        }
+        let mut member = Member {
+            member_id: member_id.clone(),
+            group_instance_id: None,
+            client_id: String::new(),
+            client_host: String::new(),
+            session_timeout_ms: 0,
+            rebalance_timeout_ms: 0,
+            protocol_type: String::new(),
+            protocols: Vec::new(),
+            assignment: Vec::new(),
+            awaiting_join: Some(sender),
+            awaiting_sync: None,
+            heartbeats: 0,
+        };
+        update_member(&mut member, params);
+        if let Some(instance_id) = &member.group_instance_id {
+            group
+                .static_members
+                .insert(instance_id.clone(), member_id.clone());
+        }
+        group.members.insert(member_id, member);
+        if group.state != GroupState::PreparingRebalance {
+            group.prepare_rebalance();
+        }
+        Reply::Later(receiver)
+    }
+
+    /// Parks a delayed join for the group's rebalance if none is parked yet,
+    /// or else retries the parked one.
+    fn complete_join_when_ready(self: &Arc<Self>, group_id: &str) {
+        let timeout_ms = {
+            let mut groups = self.lock();
+            match groups.get_mut(group_id) {
+                Some(group)
+                    if group.state == GroupState::PreparingRebalance && !group.join_watched =>
+                {
+                    group.join_watched = true;
+                    Some(group.rebalance_timeout_ms())
+                }
+                _ => None,
+            }
+        };
+        let key = group_id.to_string();
+        if let Some(timeout_ms) = timeout_ms {
+            let join = DelayedJoin {
+                coordinator: Arc::downgrade(self),
+                group_id: key.clone(),
+            };
+            self.joins
+                .try_complete_else_watch(join, vec![key], timeout_ms);
+        } else {
+            self.joins.check_and_complete(&key);
+        }
+    }
+
+    /// Completes the rebalance of `group_id` if every member has rejoined.
+    fn try_complete_join(self: &Arc<Self>, group_id: &str) -> bool {
+        let expiries = {
+            let mut groups = self.lock();
+            let Some(group) = groups.get_mut(group_id) else {
+                return true;
+            };
+            if group.state != GroupState::PreparingRebalance {
+                return true;
+            }
+            if !group.all_members_joined() {
+                return false;
+            }
+            complete_join(group, self.clock.now_ms())
+        };
+        for expiry in expiries {
+            self.restart_session(expiry);
+        }
+        true
+    }
+
+    /// Completes the rebalance of `group_id` without the members that did
+    /// not rejoin in time.
+    fn force_complete_join(self: &Arc<Self>, group_id: &str) {
+        let expiries = {
+            let mut groups = self.lock();
+            match groups.get_mut(group_id) {
+                Some(group) if group.state == GroupState::PreparingRebalance => {
+                    complete_join(group, self.clock.now_ms())
+                }
+                _ => return,
+            }
+        };
+        for expiry in expiries {
+            self.restart_session(expiry);
+        }
+    }
+
+    /// Replaces the member's session timer with one counting from now.
+    fn restart_session(self: &Arc<Self>, expiry: SessionExpiry) {
+        // Completes the previous timer, which sees the heartbeat count moved.
+        self.sessions.check_and_complete(&expiry.key);
+        let heartbeat = DelayedHeartbeat {
+            coordinator: Arc::downgrade(self),
+            key: expiry.key.clone(),
+            heartbeats: expiry.heartbeats,
+        };
+        self.sessions
+            .try_complete_else_watch(heartbeat, vec![expiry.key], expiry.timeout_ms);
+    }
+
+    /// Whether the member heartbeated, or left, since the timer started.
+    fn heartbeat_satisfied(&self, (group_id, member_id): &MemberKey, heartbeats: u64) -> bool {
+        let groups = self.lock();
+        let Some(group) = groups.get(group_id) else {
+            return true;
+        };
+        if let Some(consumer) = &group.consumer {
+            return consumer
+                .members
+                .get(member_id)
+                .map_or(true, |member| member.heartbeats != heartbeats);
+        }
+        if let Some(share) = &group.share {
+            return share
+                .members
+                .get(member_id)
+                .map_or(true, |member| member.heartbeats != heartbeats);
+        }
+        match group.members.get(member_id) {
+            Some(member) => member.heartbeats != heartbeats,
+            None => !group.pending_members.contains(member_id),
+        }
+    }
+
+    /// Removes a member whose session timed out, unless it is blocked waiting
+    /// for a response, in which case its session starts over.
+    fn expire_session(self: &Arc<Self>, key: &MemberKey, heartbeats: u64) {
+        let (group_id, member_id) = key;
+        let restart = {
+            let mut groups = self.lock();
+            let Some(group) = groups.get_mut(group_id) else {
+                return;
+            };
+            if let Some(consumer) = &mut group.consumer {
+                if consumer
+                    .members
+                    .get(member_id)
+                    .is_some_and(|member| member.heartbeats == heartbeats)
+                {
+                    eprintln!("member {member_id} of group {group_id} timed out");
+                    consumer.remove_member(member_id);
+                    if consumer.members.is_empty() {
+                        group.empty_since_ms = Some(self.clock.now_ms());
+                    }
+                }
+                return;
+            }
+            if let Some(share) = &mut group.share {
+                if share
+                    .members
+                    .get(member_id)
+                    .is_some_and(|member| member.heartbeats == heartbeats)
+                {
+                    eprintln!("member {member_id} of group {group_id} timed out");
+                    share.remove_member(member_id, self.delivery_limits());
+                    if share.members.is_empty() {
+                        group.empty_since_ms = Some(self.clock.now_ms());
+                    }
+                }
+                return;
+            }
+            match group.members.get(member_id) {
+                Some(member) if member.heartbeats != heartbeats => return,
+                Some(member) if member.is_awaiting_response() => {
+                    Some(SessionExpiry::for_member(group_id, member))
+                }
+                Some(_) => {
+                    eprintln!("member {member_id} of group {group_id} timed out");
+                    group.remove_member(member_id);
+                    None
+                }
+                None => {
+                    group.pending_members.remove(member_id);
+                    None
+                }
+            }
+        };
+        match restart {
+            Some(expiry) => self.restart_session(expiry),
+            None => self.complete_join_when_ready(group_id),
+        }
+    }
+
+    /// Writes the accepted offsets to the offsets topic and caches them,
+    /// returning an error code per commit.
+    fn store_offsets(&self, group: &mut Group, commits: Vec<OffsetCommit>) -> Vec<i16> {
+        let now_ms = self.clock.now_ms();
+        let mut results = Vec::with_capacity(commits.len());
+        let mut accepted = Vec::with_capacity(commits.len());
+        for commit in commits {
+            let metadata = commit.metadata.unwrap_or_default();
+            if metadata.len() > self.config.offset_metadata_max_bytes {
+                results.push(error::OFFSET_METADATA_TOO_LARGE);
+                continue;
+            }
+            results.push(error::NONE);
+            let offset = OffsetAndMetadata {
+                offset: commit.offset,
+                leader_epoch: commit.leader_epoch,
+                metadata,
+                commit_timestamp_ms: now_ms,
+            };
+            accepted.push((commit.partition, Some(offset)));
+        }
+        if accepted.is_empty() {
+            return results;
+        }
+
+        if let Err(err) = self.append_offsets(&group.group_id, &accepted, now_ms) {
+            eprintln!("cannot store offsets of group {}: {err}", group.group_id);
+            for result in results.iter_mut().filter(|result| **result == error::NONE) {
+                *result = error::NOT_COORDINATOR;
+            }
+            return results;
+        }
+        for (tp, offset) in accepted {
+            if let Some(offset) = offset {
+                group.offsets.insert(tp, offset);
+            }
+        }
+        results
+    }
+
+    fn append_offsets(
+        &self,
+        group_id: &str,
+        records: &[(TopicPartition, Option<OffsetAndMetadata>)],
+        now_ms: i64,
+    ) -> io::Result<()> {
+        let batch = offsets::offset_commit_batch(group_id, records, now_ms);
+        let tp = offsets::offsets_partition(group_id, self.config.offsets_topic_partitions);
+        let mut logs = self.logs.lock().expect("log manager lock poisoned");
+        logs.append(&tp, &batch).map(|_| ())
+    }
+
+    fn delivery_limits(&self) -> DeliveryLimits {
+        DeliveryLimits {
+            record_lock_duration_ms: i64::from(self.config.share_record_lock_duration_ms),
+            delivery_count_limit: self.config.share_delivery_count_limit,
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<String, Group>> {
+        self.groups.lock().expect("group coordinator lock poisoned")
+    }
+}
+
+fn update_member(member: &mut Member, params: JoinParams) {
+    member.group_instance_id = params.group_instance_id;
+    member.client_id = params.client_id;
+    member.client_host = params.client_host;
+    member.session_timeout_ms = params.session_timeout_ms;
+    member.rebalance_timeout_ms = params.rebalance_timeout_ms;
+    member.protocol_type = params.protocol_type;
+    member.protocols = params.protocols;
+}
+
+/// Rejects heartbeats that are malformed regardless of the group's state.
+fn validate_consumer_heartbeat(params: &ConsumerHeartbeatParams) -> Result<(), i16> {
+    if params.group_id.is_empty() {
+        return Err(error::INVALID_GROUP_ID);
+    }
+    let joining = params.member_epoch == JOIN_GROUP_MEMBER_EPOCH;
+    if joining
+        && (params.rebalance_timeout_ms < 0
+            || (params.subscribed_topic_names.is_none() && params.subscribed_topic_regex.is_none()))
+    {
+        return Err(error::INVALID_REQUEST);
+    }
+    if !joining && params.member_id.is_empty() {
+        return Err(error::INVALID_REQUEST);
+    }
+    if params.member_epoch == LEAVE_GROUP_STATIC_MEMBER_EPOCH && params.instance_id.is_none() {
+        return Err(error::INVALID_REQUEST);
+    }
+    if params
+        .subscribed_topic_regex
+        .as_deref()
+        .is_some_and(|pattern| !consumer_group::is_valid_regex(pattern))
+    {
+        return Err(error::INVALID_REGULAR_EXPRESSION);
+    }
+    if params
+        .server_assignor
+        .as_deref()
+        .is_some_and(|name| assignor::assignor(name).is_none())
+    {
+        return Err(error::UNSUPPORTED_ASSIGNOR);
+    }
+    Ok(())
+}
+
+/// Removes a member that sent the leave epoch. A static member sending the
+/// static leave epoch keeps its place and assignment until its session
+/// expires, so it can come back under a new member id.
+fn leave_consumer_group(
+    consumer: &mut ConsumerGroup,
+    params: &ConsumerHeartbeatParams,
+    heartbeat_interval_ms: i32,
+) -> Result<ConsumerHeartbeatResult, i16> {
+    let member = consumer
+        .members
+        .get_mut(&params.member_id)
+        .ok_or(error::UNKNOWN_MEMBER_ID)?;
+    if member.instance_id.is_some() && member.instance_id != params.instance_id {
+        return Err(error::FENCED_INSTANCE_ID);
+    }
+    if params.member_epoch == LEAVE_GROUP_STATIC_MEMBER_EPOCH {
+        if member.member_epoch != LEAVE_GROUP_STATIC_MEMBER_EPOCH {
+            member.previous_member_epoch = member.member_epoch;
+            member.member_epoch = LEAVE_GROUP_STATIC_MEMBER_EPOCH;
+        }
+    } else {
+        consumer.remove_member(&params.member_id);
+    }
+    Ok(ConsumerHeartbeatResult {
+        member_id: params.member_id.clone(),
+        member_epoch: params.member_epoch,
+        heartbeat_interval_ms,
+        assignment: None,
+    })
+}
+
+/// The id a joining member asked for, or a new random one.
+fn new_consumer_member_id(requested: &str) -> Result<String, i16> {
+    if !requested.is_empty() {
+        return Ok(requested.to_string());
+    }
+    Uuid::random().map(|uuid| uuid.to_string()).map_err(|err| {
+        eprintln!("cannot generate a member id: {err}");
+        error::UNKNOWN_SERVER_ERROR
+    })
+}
+
+/// The share group `group_id`, or `GROUP_ID_NOT_FOUND` if there is none.
+fn share_group_mut<'a>(
+    groups: &'a mut HashMap<String, Group>,
+    group_id: &str,
+) -> Result<&'a mut ShareGroup, i16> {
+    groups
+        .get_mut(group_id)
+        .and_then(|group| group.share.as_mut())
+        .ok_or(error::GROUP_ID_NOT_FOUND)
+}
+
+/// Checks a ShareFetch or ShareAcknowledge epoch against the member's share
+/// session, opening, advancing or closing the session.
+fn update_share_session(member: &mut ShareMember, epoch: i32) -> Result<(), i16> {
+    match epoch {
+        INITIAL_SHARE_SESSION_EPOCH => member.session = Some(ShareSession::new()),
+        FINAL_SHARE_SESSION_EPOCH => {
+            member
+                .session
+                .take()
+                .ok_or(error::SHARE_SESSION_NOT_FOUND)?;
+        }
+        _ => {
+            let session = member
+                .session
+                .as_mut()
+                .ok_or(error::SHARE_SESSION_NOT_FOUND)?;
+            if session.next_epoch != epoch {
+                return Err(error::INVALID_SHARE_SESSION_EPOCH);
+            }
+            session.bump_epoch();
+        }
+    }
+    Ok(())
+}
+
+/// Applies acknowledgements in order, returning the first error of each
+/// partition, or `NONE`.
+fn acknowledge_share_records(
+    share: &mut ShareGroup,
+    member_id: &str,
+    acknowledgements: &[ShareAcknowledgement],
+    now_ms: i64,
+    limits: DeliveryLimits,
+) -> BTreeMap<TopicPartition, i16> {
+    let mut errors = BTreeMap::new();
+    for ack in acknowledgements {
+        let result = match share.partitions.get_mut(&ack.partition) {
+            Some(partition) => partition.acknowledge(
+                member_id,
+                ack.first_offset,
+                ack.last_offset,
+                &ack.acknowledge_types,
+                now_ms,
+                limits,
+            ),
+            None => Err(error::INVALID_RECORD_STATE),
+        };
+        let error_code = errors.entry(ack.partition.clone()).or_insert(error::NONE);
+        if *error_code == error::NONE {
+            *error_code = result.err().unwrap_or(error::NONE);
+        }
+    }
+    errors
+}
+
+fn complete_join(group: &mut Group, now_ms: i64) -> Vec<SessionExpiry> {
+    let group_id = group.group_id.clone();
+    let expiries = group
+        .complete_join()
+        .into_iter()
+        .map(|member| SessionExpiry::for_member(&group_id, member))
+        .collect();
+    if group.state == GroupState::Empty {
+        group.empty_since_ms = Some(now_ms);
+    }
+    expiries
+}
+
+/// Checks that a commit comes from a current member, or from outside the
+/// group while it is empty, returning the member's renewed session.
+fn validate_commit(group: &mut Group, params: &CommitParams) -> Result<Option<SessionExpiry>, i16> {
+    if group.state == GroupState::Dead {
+        return Err(error::COORDINATOR_NOT_AVAILABLE);
+    }
+    if group.share.is_some() {
+        // Share groups do not commit offsets.
+        return Err(error::GROUP_ID_NOT_FOUND);
+    }
+    if params.generation_id < 0 && group.is_empty() {
+        return Ok(None);
+    }
+    if let Some(consumer) = &group.consumer {
+        // Members of consumer groups commit with their member epoch.
+        let member = consumer
+            .members
+            .get(&params.member_id)
+            .ok_or(error::UNKNOWN_MEMBER_ID)?;
+        return match params.generation_id.cmp(&member.member_epoch) {
+            std::cmp::Ordering::Greater => Err(error::FENCED_MEMBER_EPOCH),
+            std::cmp::Ordering::Less => Err(error::STALE_MEMBER_EPOCH),
+            std::cmp::Ordering::Equal => Ok(None),
+        };
+    }
+    if group.is_static_member_fenced(&params.member_id, params.group_instance_id.as_deref()) {
+        return Err(error::FENCED_INSTANCE_ID);
+    }
+    let member = group
+        .members
+        .get_mut(&params.member_id)
+        .ok_or(error::UNKNOWN_MEMBER_ID)?;
+    if params.generation_id != group.generation_id {
+        return Err(error::ILLEGAL_GENERATION);
+    }
+    if group.state == GroupState::CompletingRebalance {
+        return Err(error::REBALANCE_IN_PROGRESS);
+    }
+    member.record_heartbeat();
+    Ok(Some(SessionExpiry::for_member(&group.group_id, member)))
+}
+
+fn begin_sync(
+    groups: &mut HashMap<String, Group>,
+    params: SyncParams,
+) -> Result<(Reply<SyncResult>, SessionExpiry), i16> {
+    let group = groups
+        .get_mut(&params.group_id)
+        .ok_or(error::UNKNOWN_MEMBER_ID)?;
+    if group.state == GroupState::Dead {
+        return Err(error::COORDINATOR_NOT_AVAILABLE);
+    }
+    if group.is_static_member_fenced(&params.member_id, params.group_instance_id.as_deref()) {
+        return Err(error::FENCED_INSTANCE_ID);
+    }
+    if !group.members.contains_key(&params.member_id) {
+        return Err(error::UNKNOWN_MEMBER_ID);
+    }
+    if params.generation_id != group.generation_id {
+        return Err(error::ILLEGAL_GENERATION);
+    }
+    let mismatched = |requested: &Option<String>, current: &Option<String>| {
+        requested.is_some() && requested != current
+    };
+    if mismatched(&params.protocol_type, &group.protocol_type)
+        || mismatched(&params.protocol_name, &group.protocol_name)
+    {
+        return Err(error::INCONSISTENT_GROUP_PROTOCOL);
+    }
+
+    let is_leader = group.is_leader(&params.member_id);
+    let state = group.state;
+    let (protocol_type, protocol_name) = (group.protocol_type.clone(), group.protocol_name.clone());
+    let member = group
+        .members
+        .get_mut(&params.member_id)
+        .expect("member was just looked up");
+    member.record_heartbeat();
+    let expiry = SessionExpiry::for_member(&params.group_id, member);
+    let reply = match state {
+        GroupState::Empty | GroupState::Dead => return Err(error::UNKNOWN_MEMBER_ID),
+        GroupState::PreparingRebalance => return Err(error::REBALANCE_IN_PROGRESS),
+        GroupState::Stable => Reply::Now(SyncResult {
+            error_code: error::NONE,
+            protocol_type,
+            protocol_name,
+            assignment: member.assignment.clone(),
+        }),
+        GroupState::CompletingRebalance => {
+            let (sender, receiver) = mpsc::channel();
+            member.awaiting_sync = Some(sender);
+            if is_leader {
+                group.complete_sync(params.assignments);
+            }
+            Reply::Later(receiver)
+        }
+    };
+    Ok((reply, expiry))
+}
+
+/// Waits for every member of a rebalancing group to rejoin, up to the
+/// largest rebalance timeout among them.
+#[derive(Debug)]
+struct DelayedJoin {
+    coordinator: Weak<GroupCoordinator>,
+    group_id: String,
+}
+
+impl DelayedOperation for DelayedJoin {
+    fn try_complete(&mut self) -> bool {
+        match self.coordinator.upgrade() {
+            Some(coordinator) => coordinator.try_complete_join(&self.group_id),
+            None => true,
+        }
+    }
+
+    fn on_expiration(&mut self) {
+        if let Some(coordinator) = self.coordinator.upgrade() {
+            coordinator.force_complete_join(&self.group_id);
+        }
+    }
+}
+
+/// Expires a member's session unless it heartbeats before the timeout.
+#[derive(Debug)]
+struct DelayedHeartbeat {
+    coordinator: Weak<GroupCoordinator>,
+    key: MemberKey,
+    /// The member's heartbeat count when the timer started.
+    heartbeats: u64,
+}
+
+impl DelayedOperation for DelayedHeartbeat {
+    fn try_complete(&mut self) -> bool {
+        match self.coordinator.upgrade() {
+            Some(coordinator) => coordinator.heartbeat_satisfied(&self.key, self.heartbeats),
+            None => true,
+        }
+    }
+
+    fn on_expiration(&mut self) {
+        if let Some(coordinator) = self.coordinator.upgrade() {
+            coordinator.expire_session(&self.key, self.heartbeats);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::share_fetch::acknowledge_type;
+    use crate::storage::batch::RecordBatchBuilder;
+    use crate::storage::LogConfig;
+    use crate::time::MockClock;
+    use std::path::Path;
+    use std::thread;
+    use std::time::Duration;
+
+    const GROUP: &str = "payments";
+
+    fn open_coordinator(dir: &Path, clock: Arc<MockClock>) -> Arc<GroupCoordinator> {
+        let logs = LogManager::load(dir, LogConfig::default(), clock.clone()).expect("load logs");
+        GroupCoordinator::new(GroupConfig::default(), Arc::new(Mutex::new(logs)), clock)
+    }
+
+    fn join_params(member_id: &str, protocols: &[&str]) -> JoinParams {
+        JoinParams {
+            group_id: GROUP.to_string(),
+            member_id: member_id.to_string(),
+            group_instance_id: None,
+            client_id: "consumer".to_string(),
+            client_host: "/127.0.0.1".to_string(),
+            session_timeout_ms: 10_000,
+            rebalance_timeout_ms: 30_000,
+            protocol_type: "consumer".to_string(),
+            protocols: protocols
+                .iter()
+                .map(|name| JoinGroupRequestProtocol {
+                    name: name.to_string(),
+                    metadata: name.as_bytes().to_vec(),
+                })
+                .collect(),
+            require_known_member_id: false,
+        }
+    }
+
+    fn sync_params(result: &JoinResult, assignments: &[(&str, &[u8])]) -> SyncParams {
+        SyncParams {
+            group_id: GROUP.to_string(),
+            generation_id: result.generation_id,
+            member_id: result.member_id.clone(),
+            group_instance_id: None,
+            protocol_type: None,
+            protocol_name: None,
+            assignments: assignments
+                .iter()
+                .map(|(member_id, assignment)| (member_id.to_string(), assignment.to_vec()))
+                .collect(),
+        }
+    }
+
+    fn leaving(member_id: &str) -> MemberIdentity {
+        MemberIdentity {
+            member_id: member_id.to_string(),
+            group_instance_id: None,
+        }
+    }
+
+    fn consumer_params(member_id: &str, member_epoch: i32) -> ConsumerHeartbeatParams {
+        ConsumerHeartbeatParams {
+            group_id: GROUP.to_string(),
+            member_id: member_id.to_string(),
+            member_epoch,
+            instance_id: None,
+            rack_id: None,
+            client_id: "consumer".to_string(),
+            client_host: "/127.0.0.1".to_string(),
+            rebalance_timeout_ms: 30_000,
+            subscribed_topic_names: None,
+            subscribed_topic_regex: Some("orders.*".to_string()),
+            server_assignor: None,
+            owned_partitions: None,
+        }
+    }
+
+    fn topic_metadata(topics: &[(&str, i32)]) -> BTreeMap<String, TopicMetadata> {
+        topics
+            .iter()
+            .enumerate()
+            .map(|(index, (name, partitions))| {
+                let metadata = TopicMetadata {
+                    id: Uuid([index as u8 + 1; 16]),
+                    partitions: *partitions,
+                };
+                (name.to_string(), metadata)
+            })
+            .collect()
+    }
+
+    /// Waits until the group reaches `state`, for calls made on other threads.
+    fn wait_for_state(coordinator: &GroupCoordinator, state: GroupState) {
+        for _ in 0..500 {
+            if coordinator.group_state(GROUP) == Some(state) {
+                return;
+            }
+            thread::sleep(Duration::from_millis(2));
+        }
+        panic!("group never reached {state:?}");
+    }
+
+    #[test]
+    fn members_join_elect_a_leader_and_sync_assignments() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let coordinator = open_coordinator(dir.path(), MockClock::new(0));
+
+        let mut required = join_params("", &["range"]);
+        required.require_known_member_id = true;
+        let retry = coordinator.join_group(required);
+        assert_eq!(retry.error_code, error::MEMBER_ID_REQUIRED);
+        assert!(retry.member_id.starts_with("consumer-"));
+
+        let leader = coordinator.join_group(join_params(&retry.member_id, &["range"]));
+        assert_eq!(leader.error_code, error::NONE);
+        assert_eq!(leader.generation_id, 1);
+        assert_eq!(leader.leader_id, retry.member_id);
+        assert_eq!(leader.protocol_name.as_deref(), Some("range"));
+        assert_eq!(leader.members.len(), 1);
+        let synced = coordinator.sync_group(sync_params(&leader, &[(&leader.member_id, b"p0")]));
+        assert_eq!(synced.assignment, b"p0");
+        assert_eq!(coordinator.group_state(GROUP), Some(GroupState::Stable));
+
+        // A second member forces a rebalance that waits for the leader.
+        let follower = {
+            let coordinator = Arc::clone(&coordinator);
+            thread::spawn(move || coordinator.join_group(join_params("", &["roundrobin", "range"])))
+        };
+        wait_for_state(&coordinator, GroupState::PreparingRebalance);
+        assert_eq!(
+            coordinator.heartbeat(GROUP, leader.generation_id, &leader.member_id, None),
+            error::REBALANCE_IN_PROGRESS
+        );
+        let leader = coordinator.join_group(join_params(&leader.member_id, &["range"]));
+        let follower = follower.join().expect("follower thread");
+        assert_eq!((leader.generation_id, follower.generation_id), (2, 2));
+        assert_eq!(follower.protocol_name.as_deref(), Some("range"));
+        assert_eq!(leader.members.len(), 2);
+        assert!(follower.members.is_empty());
+
+        let follower_sync = {
+            let coordinator = Arc::clone(&coordinator);
+            let params = sync_params(&follower, &[]);
+            thread::spawn(move || coordinator.sync_group(params))
+        };
+        let assignments: [(&str, &[u8]); 2] =
+            [(&leader.member_id, b"p0"), (&follower.member_id, b"p1")];
+        coordinator.sync_group(sync_params(&leader, &assignments));
+        assert_eq!(follower_sync.join().expect("sync thread").assignment, b"p1");
+        assert_eq!(
+            coordinator.heartbeat(GROUP, follower.generation_id, &follower.member_id, None),
+            error::NONE
+        );
+    }
+
+    #[test]
+    fn stale_generations_and_unknown_members_are_fenced() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let coordinator = open_coordinator(dir.path(), MockClock::new(0));
+        let joined = coordinator.join_group(join_params("", &["range"]));
+
+        assert_eq!(
+            coordinator.heartbeat(GROUP, joined.generation_id - 1, &joined.member_id, None),
+            error::ILLEGAL_GENERATION
+        );
+        assert_eq!(
+            coordinator.heartbeat(GROUP, joined.generation_id, "intruder", None),
+            error::UNKNOWN_MEMBER_ID
+        );
+        let mut stale = sync_params(&joined, &[]);
+        stale.generation_id += 1;
+        assert_eq!(
+            coordinator.sync_group(stale).error_code,
+            error::ILLEGAL_GENERATION
+        );
+        assert_eq!(
+            coordinator
+                .join_group(join_params("", &["sticky"]))
+                .error_code,
+            error::INCONSISTENT_GROUP_PROTOCOL
+        );
+        let mut short = join_params("", &["range"]);
+        short.session_timeout_ms = 1_000;
+        assert_eq!(
+            coordinator.join_group(short).error_code,
+            error::INVALID_SESSION_TIMEOUT
+        );
+
+        assert_eq!(
+            coordinator.leave_group(GROUP, &[leaving(&joined.member_id), leaving("intruder")]),
+            Ok(vec![error::NONE, error::UNKNOWN_MEMBER_ID])
+        );
+        assert_eq!(coordinator.group_state(GROUP), Some(GroupState::Empty));
+    }
+
+    #[test]
+    fn silent_members_are_removed_after_their_session_timeout() {
+        let clock = MockClock::new(0);
+        let dir = tempfile::tempdir().expect("tempdir");
+        let coordinator = open_coordinator(dir.path(), clock.clone());
+        let first = coordinator.join_group(join_params("", &["range"]));
+        coordinator.sync_group(sync_params(&first, &[]));
+
+        // A second member joins; the first never rejoins, so the rebalance
+        // completes without it once the rebalance timeout passes.
+        let second = {
+            let coordinator = Arc::clone(&coordinator);
+            thread::spawn(move || coordinator.join_group(join_params("", &["range"])))
+        };
+        wait_for_state(&coordinator, GroupState::PreparingRebalance);
+        clock.advance(9_000);
+        assert_eq!(
+            coordinator.heartbeat(GROUP, first.generation_id, &first.member_id, None),
+            error::REBALANCE_IN_PROGRESS
+        );
+        clock.advance(21_000);
+        coordinator.expire_delayed();
+        let second = second.join().expect("join thread");
+        assert_eq!(second.generation_id, 2);
+        assert_eq!(second.leader_id, second.member_id);
+        assert_eq!(
+            coordinator.heartbeat(GROUP, second.generation_id, &first.member_id, None),
+            error::UNKNOWN_MEMBER_ID
+        );
+
+        coordinator.sync_group(sync_params(&second, &[]));
+        clock.advance(10_000);
+        coordinator.expire_delayed();
+        wait_for_state(&coordinator, GroupState::Empty);
+    }
+
+    #[test]
+    fn static_members_rejoin_without_a_rebalance_and_fence_old_ids() {
+        let clock = MockClock::new(0);
+        let dir = tempfile::tempdir().expect("tempdir");
+        let coordinator = open_coordinator(dir.path(), clock.clone());
+        let static_params = |member_id: &str| JoinParams {
+            group_instance_id: Some("pod-0".to_string()),
+            ..join_params(member_id, &["range"])
+        };
+        let first = coordinator.join_group(static_params(""));
+        assert!(first.member_id.starts_with("pod-0-"));
+        coordinator.sync_group(sync_params(&first, &[(&first.member_id, b"p0")]));
+
+        // A restart within the session timeout keeps the generation and the
+        // assignment under a new member id.
+        clock.advance(5_000);
+        let restarted = coordinator.join_group(static_params(""));
+        assert_eq!(restarted.error_code, error::NONE);
+        assert_ne!(restarted.member_id, first.member_id);
+        assert_eq!(restarted.generation_id, first.generation_id);
+        assert_eq!(restarted.leader_id, restarted.member_id);
+        assert!(restarted.skip_assignment);
+        assert_eq!(coordinator.group_state(GROUP), Some(GroupState::Stable));
+        let synced = coordinator.sync_group(sync_params(&restarted, &[]));
+        assert_eq!(synced.assignment, b"p0");
+
+        assert_eq!(
+            coordinator.heartbeat(GROUP, first.generation_id, &first.member_id, Some("pod-0")),
+            error::FENCED_INSTANCE_ID
+        );
+        let stale_sync = SyncParams {
+            group_instance_id: Some("pod-0".to_string()),
+            ..sync_params(&first, &[])
+        };
+        assert_eq!(
+            coordinator.sync_group(stale_sync).error_code,
+            error::FENCED_INSTANCE_ID
+        );
+
+        // The old id's session timer no longer applies.
+        clock.advance(6_000);
+        coordinator.expire_delayed();
+        assert_eq!(coordinator.group_state(GROUP), Some(GroupState::Stable));
+
+        let by_instance = |member_id: &str| MemberIdentity {
+            member_id: member_id.to_string(),
+            group_instance_id: Some("pod-0".to_string()),
+        };
+        assert_eq!(
+            coordinator.leave_group(GROUP, &[by_instance(&first.member_id)]),
+            Ok(vec![error::FENCED_INSTANCE_ID])
+        );
+        assert_eq!(
+            coordinator.leave_group(GROUP, &[by_instance(""), by_instance("")]),
+            Ok(vec![error::NONE, error::UNKNOWN_MEMBER_ID])
+        );
+        wait_for_state(&coordinator, GroupState::Empty);
+    }
+
+    #[test]
+    fn consumer_group_members_revoke_partitions_before_others_get_them() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let coordinator = open_coordinator(dir.path(), MockClock::new(0));
+        let topics = topic_metadata(&[("orders", 2), ("orders-eu", 2), ("audit", 1)]);
+        let heartbeat = |member_id: &str, epoch, owned: Option<&BTreeSet<TopicPartition>>| {
+            let params = ConsumerHeartbeatParams {
+                owned_partitions: owned.cloned(),
+                ..consumer_params(member_id, epoch)
+            };
+            coordinator
+                .consumer_group_heartbeat(params, &topics)
+                .expect("heartbeat")
+        };
+
+        let first = heartbeat("", 0, None);
+        assert_eq!(first.member_epoch, 1);
+        let owned = first.assignment.clone().expect("assignment");
+        assert_eq!(owned.len(), 4);
+        assert_eq!(
+            coordinator
+                .join_group(join_params("", &["range"]))
+                .error_code,
+            error::INCONSISTENT_GROUP_PROTOCOL
+        );
+
+        // The second member waits for partitions the first still owns.
+        let second = heartbeat("", 0, None);
+        assert_eq!(second.member_epoch, 2);
+        assert_eq!(second.assignment, Some(BTreeSet::new()));
+        let revoking = heartbeat(&first.member_id, 1, Some(&owned));
+        assert_eq!(revoking.member_epoch, 1);
+        let kept = revoking.assignment.expect("partitions to keep");
+        assert_eq!(kept.len(), 2);
+        let moved = heartbeat(&first.member_id, 1, Some(&kept));
+        assert_eq!((moved.member_epoch, moved.assignment), (2, None));
+        let released = heartbeat(&second.member_id, 2, Some(&BTreeSet::new()));
+        let released = released.assignment.expect("released partitions");
+        assert_eq!(released.len(), 2);
+        assert!(released.is_disjoint(&kept));
+        let listed = coordinator.list_groups(&[]);
+        assert_eq!(
+            (listed[0].state, listed[0].group_type),
+            ("Stable", "consumer")
+        );
+
+        // Commits carry the member epoch.
+        let commit = |generation_id| CommitParams {
+            group_id: GROUP.to_string(),
+            generation_id,
+            member_id: first.member_id.clone(),
+            group_instance_id: None,
+            offsets: Vec::new(),
+        };
+        assert_eq!(
+            coordinator.commit_offsets(commit(1)),
+            Err(error::STALE_MEMBER_EPOCH)
+        );
+        assert_eq!(
+            coordinator.commit_offsets(commit(3)),
+            Err(error::FENCED_MEMBER_EPOCH)
+        );
+        assert_eq!(coordinator.commit_offsets(commit(2)), Ok(Vec::new()));
+        assert_eq!(
+            coordinator.consumer_group_heartbeat(consumer_params(&first.member_id, 5), &topics),
+            Err(error::FENCED_MEMBER_EPOCH)
+        );
+        let bad_regex = ConsumerHeartbeatParams {
+            subscribed_topic_regex: Some("orders(".to_string()),
+            ..consumer_params("", 0)
+        };
+        assert_eq!(
+            coordinator.consumer_group_heartbeat(bad_regex, &topics),
+            Err(error::INVALID_REGULAR_EXPRESSION)
+        );
+
+        // A new topic matching the subscription bumps the group epoch.
+        let grown = topic_metadata(&[
+            ("orders", 2),
+            ("orders-eu", 2),
+            ("audit", 1),
+            ("orders-us", 1),
+        ]);
+        let params = ConsumerHeartbeatParams {
+            owned_partitions: Some(kept.clone()),
+            ..consumer_params(&first.member_id, 2)
+        };
+        let bumped = coordinator
+            .consumer_group_heartbeat(params, &grown)
+            .expect("heartbeat");
+        assert_eq!(bumped.member_epoch, 3);
+        let group = coordinator
+            .describe_consumer_group(GROUP)
+            .expect("describe");
+        assert_eq!(
+            (group.group_epoch, group.assignor_name.as_str()),
+            (3, "uniform")
+        );
+
+        for member_id in [&first.member_id, &second.member_id] {
+            let left = coordinator
+                .consumer_group_heartbeat(consumer_params(member_id, -1), &grown)
+                .expect("leave");
+            assert_eq!(left.member_epoch, -1);
+        }
+        // Once empty, the group may switch back to the classic protocol.
+        let joined = coordinator.join_group(join_params("", &["range"]));
+        assert_eq!(joined.error_code, error::NONE);
+        assert_eq!(coordinator.describe_consumer_group(GROUP), None);
+    }
+
+    #[test]
+    fn share_group_members_split_records_under_acquisition_locks() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let clock = MockClock::new(0);
+        let coordinator = open_coordinator(dir.path(), clock.clone());
+        let topics = topic_metadata(&[("orders", 1)]);
+        let orders = TopicPartition::new("orders", 0);
+        let heartbeat = |member_id: &str, epoch| {
+            let params = ShareHeartbeatParams {
+                group_id: GROUP.to_string(),
+                member_id: member_id.to_string(),
+                member_epoch: epoch,
+                rack_id: None,
+                client_id: "worker".to_string(),
+                client_host: "/127.0.0.1".to_string(),
+                subscribed_topic_names: Some(vec!["orders".to_string()]),
+            };
+            coordinator
+                .share_group_heartbeat(params, &topics)
+                .expect("heartbeat")
+        };
+        let fetch = |member_id: &str, epoch, acknowledgements: Vec<ShareAcknowledgement>| {
+            coordinator.share_fetch(ShareFetchParams {
+                group_id: GROUP.to_string(),
+                member_id: member_id.to_string(),
+                share_session_epoch: epoch,
+                partitions: vec![orders.clone()],
+                forgotten: Vec::new(),
+                acknowledgements,
+                max_bytes: 1 << 20,
+            })
+        };
+        let ack = |first_offset, last_offset, acknowledge_types: &[i8]| ShareAcknowledgement {
+            partition: orders.clone(),
+            first_offset,
+            last_offset,
+            acknowledge_types: acknowledge_types.to_vec(),
+        };
+
+        // One partition is shared by both members.
+        let first = heartbeat("", 0);
+        let second = heartbeat("", 0);
+        assert_eq!(second.member_epoch, 2);
+        assert_eq!(second.assignment, Some(BTreeSet::from([orders.clone()])));
+        assert_eq!(heartbeat(&first.member_id, 1).assignment, None);
+        assert_eq!(
+            coordinator
+                .join_group(join_params("", &["range"]))
+                .error_code,
+            error::INCONSISTENT_GROUP_PROTOCOL
+        );
+
+        // The share-partition starts at the latest offset when first fetched.
+        coordinator
+            .logs
+            .lock()
+            .expect("logs")
+            .get_or_create_log(&orders)
+            .expect("create log");
+        let opened = fetch(&first.member_id, 0, Vec::new()).expect("fetch");
+        assert!(opened.partitions.is_empty());
+        {
+            let mut logs = coordinator.logs.lock().expect("logs");
+            let batch = RecordBatchBuilder::new(0)
+                .record(0, None, Some(b"a"))
+                .record(0, None, Some(b"b"))
+                .build();
+            logs.append(&orders, &batch).expect("append");
+        }
+        let fetched = fetch(&first.member_id, 1, Vec::new()).expect("fetch");
+        assert_eq!(
+            fetched.partitions[0].acquired,
+            vec![AcquiredRecords {
+                first_offset: 0,
+                last_offset: 1,
+                delivery_count: 1,
+            }]
+        );
+        assert!(fetch(&second.member_id, 0, Vec::new())
+            .expect("fetch")
+            .partitions
+            .is_empty());
+        assert_eq!(
+            fetch(&second.member_id, 5, Vec::new()),
+            Err(error::INVALID_SHARE_SESSION_EPOCH)
+        );
+
+        let acknowledged = coordinator
+            .share_acknowledge(ShareAcknowledgeParams {
+                group_id: GROUP.to_string(),
+                member_id: first.member_id.clone(),
+                share_session_epoch: 2,
+                acknowledgements: vec![ack(
+                    0,
+                    1,
+                    &[acknowledge_type::ACCEPT, acknowledge_type::RELEASE],
+                )],
+            })
+            .expect("acknowledge");
+        assert_eq!(acknowledged[&orders], error::NONE);
+
+        // The released record goes to the other member, and back once its
+        // lock expires.
+        let redelivered = fetch(&second.member_id, 1, Vec::new()).expect("fetch");
+        assert_eq!(redelivered.partitions[0].acquired[0].first_offset, 1);
+        assert_eq!(redelivered.partitions[0].acquired[0].delivery_count, 2);
+        clock.advance(i64::from(DEFAULT_SHARE_RECORD_LOCK_DURATION_MS));
+        let expired = fetch(&first.member_id, 3, Vec::new()).expect("fetch");
+        assert_eq!(expired.partitions[0].acquired[0].delivery_count, 3);
+        let late = fetch(
+            &second.member_id,
+            2,
+            vec![ack(1, 1, &[acknowledge_type::ACCEPT])],
+        )
+        .expect("fetch");
+        assert_eq!(
+            late.acknowledge_errors[&orders],
+            error::INVALID_RECORD_STATE
+        );
+
+        let closed = fetch(
+            &first.member_id,
+            FINAL_SHARE_SESSION_EPOCH,
+            vec![ack(1, 1, &[acknowledge_type::ACCEPT])],
+        )
+        .expect("fetch");
+        assert_eq!(closed.acknowledge_errors[&orders], error::NONE);
+        let groups = coordinator.lock();
+        let partition = &groups[GROUP]
+            .share
+            .as_ref()
+            .expect("share group")
+            .partitions[&orders];
+        assert_eq!(partition.start_offset, 2);
+    }
+
+    #[test]
+    fn groups_are_listed_described_and_deleted_once_empty() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let coordinator = open_coordinator(dir.path(), MockClock::new(0));
+        let orders = TopicPartition::new("orders", 0);
+        let audit = TopicPartition::new("audit", 0);
+        let commit = CommitParams {
+            group_id: GROUP.to_string(),
+            generation_id: -1,
+            member_id: String::new(),
+            group_instance_id: None,
+            offsets: [&orders, &audit]
+                .into_iter()
+                .map(|tp| OffsetCommit {
+                    partition: tp.clone(),
+                    offset: 4,
+                    leader_epoch: 0,
+                    metadata: None,
+                })
+                .collect(),
+        };
+        coordinator.commit_offsets(commit).expect("commit");
+
+        // A consumer subscription: version, then the topic names.
+        let mut subscription = 0_i16.to_be_bytes().to_vec();
+        subscription.extend_from_slice(&1_i32.to_be_bytes());
+        crate::protocol::wire::write_string(&mut subscription, "orders", false);
+        let mut params = join_params("", &["range"]);
+        params.protocols[0].metadata = subscription.clone();
+        let joined = coordinator.join_group(params);
+        coordinator.sync_group(sync_params(&joined, &[(&joined.member_id, b"assigned")]));
+
+        let listed = coordinator.list_groups(&["stable".to_string()]);
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].protocol_type, "consumer");
+        assert!(coordinator.list_groups(&["Empty".to_string()]).is_empty());
+        let summary = coordinator.describe_group(GROUP).expect("describe");
+        assert_eq!(summary.protocol_name, "range");
+        assert_eq!(summary.members[0].client_host, "/127.0.0.1");
+        assert_eq!(summary.members[0].metadata, subscription);
+        assert_eq!(summary.members[0].assignment, b"assigned");
+
+        assert_eq!(
+            coordinator.delete_groups(&[GROUP.to_string(), "missing".to_string()]),
+            [error::NON_EMPTY_GROUP, error::GROUP_ID_NOT_FOUND]
+        );
+        assert_eq!(
+            coordinator.delete_offsets(GROUP, vec![orders.clone(), audit.clone()]),
+            Ok(vec![error::GROUP_SUBSCRIBED_TO_TOPIC, error::NONE])
+        );
+        assert_eq!(
+            coordinator.fetch_offsets(GROUP, None).expect("fetch").len(),
+            1
+        );
+
+        coordinator
+            .leave_group(GROUP, &[leaving(&joined.member_id)])
+            .expect("leave");
+        assert_eq!(
+            coordinator.delete_groups(&[GROUP.to_string()]),
+            [error::NONE]
+        );
+        assert_eq!(coordinator.describe_group(GROUP), None);
+        assert_eq!(
+            coordinator.delete_offsets(GROUP, vec![orders]),
+            Err(error::GROUP_ID_NOT_FOUND)
+        );
+        drop(coordinator);
+
+        let coordinator = open_coordinator(dir.path(), MockClock::new(0));
+        assert_eq!(coordinator.load_offsets().expect("load offsets"), 0);
+    }
+
+    #[test]
+    fn offsets_survive_restarts_and_expire_once_the_group_is_empty() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let clock = MockClock::new(1_000);
+        let orders = TopicPartition::new("orders", 0);
+        let audit = TopicPartition::new("audit", 0);
+        let commit = |generation_id, member_id: &str, offset, metadata: &str| CommitParams {
+            group_id: GROUP.to_string(),
+            generation_id,
+            member_id: member_id.to_string(),
+            group_instance_id: None,
+            offsets: vec![OffsetCommit {
+                partition: orders.clone(),
+                offset,
+                leader_epoch: 3,
+                metadata: Some(metadata.to_string()),
+            }],
+        };
+        {
+            let coordinator = open_coordinator(dir.path(), clock.clone());
+            assert_eq!(
+                coordinator.commit_offsets(commit(2, "member", 5, "")),
+                Err(error::ILLEGAL_GENERATION)
+            );
+            assert_eq!(
+                coordinator.commit_offsets(commit(-1, "", 5, "")),
+                Ok(vec![error::NONE])
+            );
+            assert_eq!(
+                coordinator.commit_offsets(commit(-1, "", 6, &"x".repeat(5_000))),
+                Ok(vec![error::OFFSET_METADATA_TOO_LARGE])
+            );
+
+            // Once the group has members, only they may commit.
+            let joined = coordinator.join_group(join_params("", &["range"]));
+            coordinator.sync_group(sync_params(&joined, &[]));
+            assert_eq!(
+                coordinator.commit_offsets(commit(-1, "", 6, "")),
+                Err(error::UNKNOWN_MEMBER_ID)
+            );
+            assert_eq!(
+                coordinator.commit_offsets(commit(joined.generation_id, &joined.member_id, 8, "m")),
+                Ok(vec![error::NONE])
+            );
+            coordinator
+                .leave_group(GROUP, &[leaving(&joined.member_id)])
+                .expect("leave");
+            assert_eq!(coordinator.expire_offsets().expect("expire"), 0);
+        }
+
+        let coordinator = open_coordinator(dir.path(), clock.clone());
+        assert_eq!(coordinator.load_offsets().expect("load offsets"), 1);
+        let stored = OffsetAndMetadata {
+            offset: 8,
+            leader_epoch: 3,
+            metadata: "m".to_string(),
+            commit_timestamp_ms: 1_000,
+        };
+        assert_eq!(
+            coordinator.fetch_offsets(GROUP, Some(vec![orders.clone(), audit.clone()])),
+            Ok(vec![(orders.clone(), Some(stored)), (audit, None)])
+        );
+
+        clock.advance(DEFAULT_OFFSETS_RETENTION_MS - 1);
+        assert_eq!(coordinator.expire_offsets().expect("expire"), 0);
+        clock.advance(1);
+        assert_eq!(coordinator.expire_offsets().expect("expire"), 1);
+        assert_eq!(coordinator.group_state(GROUP), None);
+        assert_eq!(coordinator.fetch_offsets(GROUP, None), Ok(Vec::new()));
+        drop(coordinator);
+
+        let coordinator = open_coordinator(dir.path(), clock);
+        assert_eq!(coordinator.load_offsets().expect("load offsets"), 0);
+    }
+}
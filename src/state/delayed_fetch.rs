@@ -0,0 +1,216 @@
+use super::purgatory::DelayedOperation;
+use crate::protocol::error;
+use crate::protocol::fetch::{
+    FetchPartitionData, FetchRequest, NO_PREFERRED_READ_REPLICA, UNKNOWN_OFFSET,
+};
+use crate::storage::batch::{self, BatchHeader};
+use crate::storage::{Log, LogManager, TopicPartition};
+use std::collections::BTreeMap;
+use std::io;
+use std::sync::mpsc::Sender;
+use std::sync::{Arc, Mutex};
+
+/// What a Fetch read for each of its partitions.
+pub type FetchedPartitions = Vec<(TopicPartition, FetchPartitionData)>;
+
+/// Fetch position and limits a client sent for one partition.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FetchPosition {
+    pub fetch_offset: i64,
+    pub log_start_offset: i64,
+    pub partition_max_bytes: i32,
+}
+
+/// Request-wide settings of a Fetch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FetchParams {
+    pub replica_id: i32,
+    pub read_committed: bool,
+    pub min_bytes: i32,
+    pub max_bytes: i32,
+}
+
+impl FetchParams {
+    pub fn new(request: &FetchRequest) -> Self {
+        Self {
+            replica_id: request.replica_id,
+            read_committed: request.read_committed(),
+            min_bytes: request.min_bytes,
+            max_bytes: request.max_bytes,
+        }
+    }
+}
+
+/// A long-poll Fetch parked until its partitions hold `min_bytes` of records
+/// or its `max_wait_ms` passes, keyed by those partitions.
+#[derive(Debug)]
+pub struct DelayedFetch {
+    logs: Arc<Mutex<LogManager>>,
+    params: FetchParams,
+    partitions: Vec<(TopicPartition, FetchPosition)>,
+    /// Partitions answered with an error instead of being read.
+    errors: BTreeMap<TopicPartition, i16>,
+    respond: Sender<FetchedPartitions>,
+}
+
+impl DelayedFetch {
+    pub fn new(
+        logs: Arc<Mutex<LogManager>>,
+        params: FetchParams,
+        partitions: Vec<(TopicPartition, FetchPosition)>,
+        errors: BTreeMap<TopicPartition, i16>,
+        respond: Sender<FetchedPartitions>,
+    ) -> Self {
+        Self {
+            logs,
+            params,
+            partitions,
+            errors,
+            respond,
+        }
+    }
+
+    /// Sends whatever the partitions hold now, however little.
+    pub fn complete(&mut self) {
+        let fetched = self.read();
+        // The handler is gone only if its connection thread died.
+        let _ = self.respond.send(fetched);
+    }
+
+    fn read(&self) -> FetchedPartitions {
+        let logs = self.logs.lock().expect("log manager lock poisoned");
+        let mut remaining_bytes = usize::try_from(self.params.max_bytes).unwrap_or_default();
+        self.partitions
+            .iter()
+            .map(|(tp, position)| {
+                let data = match (self.errors.get(tp), logs.get_log(tp)) {
+                    (Some(&error_code), _) => fetch_partition_error(tp.partition, error_code),
+                    (None, Some(log)) => read_partition(
+                        log,
+                        &self.params,
+                        tp.partition,
+                        position,
+                        &mut remaining_bytes,
+                    ),
+                    (None, None) => {
+                        fetch_partition_error(tp.partition, error::UNKNOWN_TOPIC_OR_PARTITION)
+                    }
+                };
+                (tp.clone(), data)
+            })
+            .collect()
+    }
+}
+
+impl DelayedOperation for DelayedFetch {
+    /// Completes once `min_bytes` are available or a partition fails.
+    fn try_complete(&mut self) -> bool {
+        let fetched = self.read();
+        let failed = fetched
+            .iter()
+            .any(|(_, data)| data.error_code != error::NONE);
+        let available: usize = fetched
+            .iter()
+            .filter_map(|(_, data)| data.records.as_ref())
+            .map(Vec::len)
+            .sum();
+        if !failed && available < usize::try_from(self.params.min_bytes).unwrap_or_default() {
+            return false;
+        }
+        let _ = self.respond.send(fetched);
+        true
+    }
+
+    fn on_expiration(&mut self) {
+        self.complete();
+    }
+}
+
+/// Reads one Fetch partition, charging what it returns to `remaining_bytes`.
+/// Only the first partition with data may exceed the budget, so a consumer
+/// stuck behind a large batch can still make progress.
+fn read_partition(
+    log: &Log,
+    params: &FetchParams,
+    partition_index: i32,
+    position: &FetchPosition,
+    remaining_bytes: &mut usize,
+) -> FetchPartitionData {
+    let mut data = fetch_partition_error(partition_index, error::NONE);
+    data.high_watermark = log.high_watermark();
+    data.last_stable_offset = log.last_stable_offset();
+    data.log_start_offset = log.log_start_offset();
+    if params.read_committed {
+        // Aborted transactions are not tracked, so there is nothing to filter.
+        data.aborted_transactions = Some(Vec::new());
+    }
+
+    let fetch_offset = position.fetch_offset;
+    if fetch_offset < log.log_start_offset() || fetch_offset > log.log_end_offset() {
+        data.error_code = error::OFFSET_OUT_OF_RANGE;
+        return data;
+    }
+    let upper_bound = if params.replica_id >= 0 {
+        log.log_end_offset()
+    } else if params.read_committed {
+        log.last_stable_offset()
+    } else {
+        log.high_watermark()
+    };
+    let partition_max_bytes = usize::try_from(position.partition_max_bytes).unwrap_or_default();
+    let max_bytes = partition_max_bytes.min(*remaining_bytes);
+    let request_limit = usize::try_from(params.max_bytes).unwrap_or_default();
+    let first_with_data = *remaining_bytes == request_limit;
+    if fetch_offset >= upper_bound || (max_bytes == 0 && !first_with_data) {
+        data.records = Some(Vec::new());
+        return data;
+    }
+
+    let records = log
+        .read(fetch_offset, max_bytes)
+        .and_then(|records| records_below(records, upper_bound));
+    match records {
+        Ok(records) if records.len() > max_bytes && !first_with_data => {
+            data.records = Some(Vec::new());
+        }
+        Ok(records) => {
+            *remaining_bytes = remaining_bytes.saturating_sub(records.len());
+            data.records = Some(records);
+        }
+        Err(err) => {
+            eprintln!(
+                "cannot read offset {fetch_offset} from {}: {err}",
+                log.dir().display()
+            );
+            data.error_code = error::KAFKA_STORAGE_ERROR;
+        }
+    }
+    data
+}
+
+/// Drops the batches that start at or after `upper_bound`, which the fetch
+/// may not see yet.
+fn records_below(mut records: Vec<u8>, upper_bound: i64) -> io::Result<Vec<u8>> {
+    let mut end = 0;
+    for batch in batch::split_batches(&records)? {
+        if BatchHeader::parse(batch)?.base_offset >= upper_bound {
+            break;
+        }
+        end += batch.len();
+    }
+    records.truncate(end);
+    Ok(records)
+}
+
+pub fn fetch_partition_error(partition_index: i32, error_code: i16) -> FetchPartitionData {
+    FetchPartitionData {
+        partition_index,
+        error_code,
+        high_watermark: UNKNOWN_OFFSET,
+        last_stable_offset: UNKNOWN_OFFSET,
+        log_start_offset: UNKNOWN_OFFSET,
+        aborted_transactions: None,
+        preferred_read_replica: NO_PREFERRED_READ_REPLICA,
+        records: None,
+    }
+}
@@ -0,0 +1,366 @@
+use super::delayed_fetch::FetchPosition;
+use crate::protocol::error;
+use crate::storage::TopicPartition;
+use crate::time::Clock;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+
+/// Session id of a fetch that does not use a session.
+pub const INVALID_SESSION_ID: i32 = 0;
+/// Epoch of a full fetch that creates a new session.
+pub const INITIAL_EPOCH: i32 = 0;
+/// Epoch of a full fetch that closes any session and does not start one.
+pub const FINAL_EPOCH: i32 = -1;
+
+/// Default number of sessions kept, as `max.incremental.fetch.session.cache.slots`.
+pub const DEFAULT_CACHE_SLOTS: usize = 1_000;
+/// How long a session must go unused before any new session may evict it.
+pub const DEFAULT_EVICTION_MS: i64 = 120_000;
+
+/// What a fetch returned for one partition, as far as sessions care.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FetchedPartition {
+    pub high_watermark: i64,
+    pub log_start_offset: i64,
+    pub error_code: i16,
+    pub has_records: bool,
+}
+
+/// The partitions a fetch request covers once its session is applied.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FetchContext {
+    /// Session to return to the client, or [`INVALID_SESSION_ID`].
+    pub session_id: i32,
+    /// Whether the response may leave out unchanged partitions.
+    pub incremental: bool,
+    pub partitions: BTreeMap<TopicPartition, FetchPosition>,
+}
+
+#[derive(Debug)]
+struct CachedPartition {
+    position: FetchPosition,
+    /// Last values sent to the client, to spot partitions that changed.
+    high_watermark: Option<i64>,
+    log_start_offset: Option<i64>,
+}
+
+#[derive(Debug)]
+struct FetchSession {
+    /// Epoch the next incremental fetch must carry.
+    next_epoch: i32,
+    last_used_ms: i64,
+    partitions: BTreeMap<TopicPartition, CachedPartition>,
+}
+
+/// Bounded cache of incremental fetch sessions (KIP-227), which let clients
+/// list only the partitions that changed since their previous fetch.
+#[derive(Debug)]
+pub struct FetchSessionCache {
+    max_slots: usize,
+    eviction_ms: i64,
+    clock: Arc<dyn Clock>,
+    sessions: HashMap<i32, FetchSession>,
+}
+
+impl FetchSessionCache {
+    pub fn new(max_slots: usize, eviction_ms: i64, clock: Arc<dyn Clock>) -> Self {
+        Self {
+            max_slots,
+            eviction_ms,
+            clock,
+            sessions: HashMap::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.sessions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sessions.is_empty()
+    }
+
+    /// Resolves the partitions of a fetch from its session fields, returning
+    /// `FETCH_SESSION_ID_NOT_FOUND` or `INVALID_FETCH_SESSION_EPOCH` when an
+    /// incremental fetch does not match a cached session.
+    ///
+    /// Full fetches (epoch 0 or -1) close the named session; epoch 0 then
+    /// starts a new one if the cache has or can make room.
+    pub fn new_context(
+        &mut self,
+        session_id: i32,
+        epoch: i32,
+        partitions: Vec<(TopicPartition, FetchPosition)>,
+        forgotten: &[TopicPartition],
+    ) -> Result<FetchContext, i16> {
+        let now_ms = self.clock.now_ms();
+        if epoch == INITIAL_EPOCH || epoch == FINAL_EPOCH {
+            if session_id != INVALID_SESSION_ID {
+                self.sessions.remove(&session_id);
+            }
+            let partitions: BTreeMap<_, _> = partitions.into_iter().collect();
+            let session_id = if epoch == INITIAL_EPOCH {
+                self.create_session(&partitions, now_ms)
+            } else {
+                INVALID_SESSION_ID
+            };
+            return Ok(FetchContext {
+                session_id,
+                incremental: false,
+                partitions,
+            });
+        }
+
+        let session = self
+            .sessions
+            .get_mut(&session_id)
+            .ok_or(error::FETCH_SESSION_ID_NOT_FOUND)?;
+        if session.next_epoch != epoch {
+            return Err(error::INVALID_FETCH_SESSION_EPOCH);
+        }
+        for (tp, position) in partitions {
+            session
+                .partitions
+                .entry(tp)
+                .and_modify(|cached| cached.position = position)
+                .or_insert(CachedPartition {
+                    position,
+                    high_watermark: None,
+                    log_start_offset: None,
+                });
+        }
+        for tp in forgotten {
+            session.partitions.remove(tp);
+        }
+        session.next_epoch = next_epoch(epoch);
+        session.last_used_ms = now_ms;
+        Ok(FetchContext {
+            session_id,
+            incremental: true,
+            partitions: session
+                .partitions
+                .iter()
+                .map(|(tp, cached)| (tp.clone(), cached.position))
+                .collect(),
+        })
+    }
+
+    /// Remembers what the client learns about `tp` and returns whether the
+    /// partition belongs in the response: always for full fetches, and for
+    /// incremental ones only when it has records, an error or new offsets.
+    pub fn should_respond(
+        &mut self,
+        context: &FetchContext,
+        tp: &TopicPartition,
+        fetched: &FetchedPartition,
+    ) -> bool {
+        let cached = self
+            .sessions
+            .get_mut(&context.session_id)
+            .and_then(|session| session.partitions.get_mut(tp));
+        let Some(cached) = cached else {
+            return !context.incremental;
+        };
+        let changed = cached.high_watermark != Some(fetched.high_watermark)
+            || cached.log_start_offset != Some(fetched.log_start_offset);
+        cached.high_watermark = Some(fetched.high_watermark);
+        cached.log_start_offset = Some(fetched.log_start_offset);
+        !context.incremental || changed || fetched.has_records || fetched.error_code != error::NONE
+    }
+
+    fn create_session(
+        &mut self,
+        partitions: &BTreeMap<TopicPartition, FetchPosition>,
+        now_ms: i64,
+    ) -> i32 {
+        if self.max_slots == 0
+            || (self.sessions.len() >= self.max_slots && !self.evict_for(partitions.len(), now_ms))
+        {
+            return INVALID_SESSION_ID;
+        }
+        let Some(session_id) = self.allocate_id() else {
+            return INVALID_SESSION_ID;
+        };
+        self.sessions.insert(
+            session_id,
+            FetchSession {
+                next_epoch: next_epoch(INITIAL_EPOCH),
+                last_used_ms: now_ms,
+                partitions: partitions
+                    .iter()
+                    .map(|(tp, position)| {
+                        let cached = CachedPartition {
+                            position: *position,
+                            high_watermark: None,
+                            log_start_offset: None,
+                        };
+                        (tp.clone(), cached)
+                    })
+                    .collect(),
+            },
+        );
+        session_id
+    }
+
+    /// Makes room for a session of `size` partitions by evicting the least
+    /// recently used session if it is stale, or else the smallest session if
+    /// it is smaller than the new one. Returns whether a session was evicted.
+    fn evict_for(&mut self, size: usize, now_ms: i64) -> bool {
+        let stale = self
+            .sessions
+            .iter()
+            .filter(|(_, session)| now_ms - session.last_used_ms >= self.eviction_ms)
+            .min_by_key(|(_, session)| session.last_used_ms)
+            .map(|(&id, _)| id);
+        let victim = stale.or_else(|| {
+            self.sessions
+                .iter()
+                .filter(|(_, session)| session.partitions.len() < size)
+                .min_by_key(|(_, session)| (session.partitions.len(), session.last_used_ms))
+                .map(|(&id, _)| id)
+        });
+        match victim {
+            Some(id) => {
+                self.sessions.remove(&id);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Picks an unused random id in `1..i32::MAX`, as Kafka does, so clients
+    /// cannot guess the sessions of others; `None` if randomness fails.
+    fn allocate_id(&self) -> Option<i32> {
+        loop {
+            let mut bytes = [0_u8; 4];
+            getrandom::fill(&mut bytes).ok()?;
+            let id = i32::from_be_bytes(bytes) & i32::MAX;
+            if (1..i32::MAX).contains(&id) && !self.sessions.contains_key(&id) {
+                return Some(id);
+            }
+        }
+    }
+}
+
+/// Epoch after `epoch`, wrapping back to 1 so it never reaches the special
+/// initial and final epochs.
+fn next_epoch(epoch: i32) -> i32 {
+    if epoch == i32::MAX {
+        1
+    } else {
+        epoch + 1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::time::MockClock;
+
+    fn position(fetch_offset: i64) -> FetchPosition {
+        FetchPosition {
+            fetch_offset,
+            log_start_offset: -1,
+            partition_max_bytes: 1_048_576,
+        }
+    }
+
+    fn fetched(high_watermark: i64, has_records: bool) -> FetchedPartition {
+        FetchedPartition {
+            high_watermark,
+            log_start_offset: 0,
+            error_code: error::NONE,
+            has_records,
+        }
+    }
+
+    fn tp(partition: i32) -> TopicPartition {
+        TopicPartition::new("orders", partition)
+    }
+
+    #[test]
+    fn incremental_fetches_update_the_session() {
+        let mut cache = FetchSessionCache::new(10, DEFAULT_EVICTION_MS, MockClock::new(0));
+        let full = cache
+            .new_context(
+                INVALID_SESSION_ID,
+                INITIAL_EPOCH,
+                vec![(tp(0), position(0)), (tp(1), position(0))],
+                &[],
+            )
+            .expect("full fetch");
+        assert!(!full.incremental);
+        assert!(full.session_id > INVALID_SESSION_ID);
+        for partition in [0, 1] {
+            assert!(cache.should_respond(&full, &tp(partition), &fetched(5, true)));
+        }
+
+        let incremental = cache
+            .new_context(full.session_id, 1, vec![(tp(2), position(7))], &[tp(0)])
+            .expect("incremental fetch");
+        assert!(incremental.incremental);
+        let partitions: Vec<_> = incremental.partitions.keys().cloned().collect();
+        assert_eq!(partitions, [tp(1), tp(2)]);
+        assert!(!cache.should_respond(&incremental, &tp(1), &fetched(5, false)));
+        assert!(cache.should_respond(&incremental, &tp(1), &fetched(6, false)));
+        assert!(cache.should_respond(&incremental, &tp(2), &fetched(9, false)));
+
+        assert_eq!(
+            cache.new_context(full.session_id, 1, Vec::new(), &[]),
+            Err(error::INVALID_FETCH_SESSION_EPOCH)
+        );
+        assert_eq!(
+            cache.new_context(full.session_id + 1, 1, Vec::new(), &[]),
+            Err(error::FETCH_SESSION_ID_NOT_FOUND)
+        );
+
+        let closed = cache
+            .new_context(
+                full.session_id,
+                FINAL_EPOCH,
+                vec![(tp(0), position(0))],
+                &[],
+            )
+            .expect("sessionless fetch");
+        assert_eq!(closed.session_id, INVALID_SESSION_ID);
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn full_cache_evicts_stale_then_smaller_sessions() {
+        let clock = MockClock::new(0);
+        let mut cache = FetchSessionCache::new(2, 1_000, clock.clone());
+        let open = |cache: &mut FetchSessionCache, size: i32| {
+            let partitions = (0..size).map(|p| (tp(p), position(0))).collect();
+            cache
+                .new_context(INVALID_SESSION_ID, INITIAL_EPOCH, partitions, &[])
+                .expect("full fetch")
+                .session_id
+        };
+
+        let old = open(&mut cache, 5);
+        clock.advance(600);
+        let small = open(&mut cache, 2);
+        clock.advance(600);
+
+        // `old` is stale, so even a small session may replace it.
+        let replacement = open(&mut cache, 1);
+        assert_ne!(replacement, INVALID_SESSION_ID);
+        assert_eq!(
+            cache.new_context(old, 1, Vec::new(), &[]),
+            Err(error::FETCH_SESSION_ID_NOT_FOUND)
+        );
+
+        // Nothing is stale; a 3-partition session evicts the 1-partition one.
+        assert_ne!(open(&mut cache, 3), INVALID_SESSION_ID);
+        assert!(cache.new_context(small, 1, Vec::new(), &[]).is_ok());
+        assert_eq!(
+            cache.new_context(replacement, 1, Vec::new(), &[]),
+            Err(error::FETCH_SESSION_ID_NOT_FOUND)
+        );
+
+        // A session no larger than any cached one gets no session id.
+        assert_eq!(open(&mut cache, 2), INVALID_SESSION_ID);
+        assert_eq!(cache.len(), 2);
+    }
+}
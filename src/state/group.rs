@@ -0,0 +1,666 @@
+use super::consumer_group::ConsumerGroup;
+use super::offsets::OffsetAndMetadata;
+use super::share_group::ShareGroup;
+use crate::codec::primitives;
+use crate::protocol::error;
+use crate::protocol::join_group::{JoinGroupRequestProtocol, JoinGroupResponseMember};
+use crate::protocol::list_groups::{CLASSIC_GROUP_TYPE, CONSUMER_GROUP_TYPE, SHARE_GROUP_TYPE};
+use crate::protocol::wire;
+use crate::storage::TopicPartition;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::{self, Cursor};
+use std::sync::mpsc::Sender;
+
+/// Protocol type of groups whose members use the consumer's embedded
+/// subscription format.
+pub const CONSUMER_PROTOCOL_TYPE: &str = "consumer";
+
+/// Stage of a group in the classic rebalance protocol.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GroupState {
+    /// No members; the group only holds committed offsets, if any.
+    Empty,
+    /// Waiting for every member to rejoin.
+    PreparingRebalance,
+    /// Waiting for the leader to send the new assignment.
+    CompletingRebalance,
+    Stable,
+    /// Removed; requests for it fail until it is recreated.
+    Dead,
+}
+
+impl GroupState {
+    /// Name of the state as Kafka reports it in ListGroups and
+    /// DescribeGroups.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Empty => "Empty",
+            Self::PreparingRebalance => "PreparingRebalance",
+            Self::CompletingRebalance => "CompletingRebalance",
+            Self::Stable => "Stable",
+            Self::Dead => "Dead",
+        }
+    }
+}
+
+/// A group as listed by ListGroups.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GroupOverview {
+    pub group_id: String,
+    pub protocol_type: String,
+    pub state: &'static str,
+    pub group_type: &'static str,
+}
+
+/// A group as described by DescribeGroups.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GroupSummary {
+    pub state: GroupState,
+    pub protocol_type: String,
+    /// The selected protocol; empty unless the group is stable.
+    pub protocol_name: String,
+    pub members: Vec<MemberSummary>,
+}
+
+impl GroupSummary {
+    /// How a group that does not exist is described.
+    pub fn dead() -> Self {
+        Self {
+            state: GroupState::Dead,
+            protocol_type: String::new(),
+            protocol_name: String::new(),
+            members: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MemberSummary {
+    pub member_id: String,
+    pub group_instance_id: Option<String>,
+    pub client_id: String,
+    pub client_host: String,
+    pub metadata: Vec<u8>,
+    pub assignment: Vec<u8>,
+}
+
+/// Generation, protocol and leader a JoinGroup call returns.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JoinResult {
+    pub error_code: i16,
+    pub generation_id: i32,
+    pub protocol_type: Option<String>,
+    pub protocol_name: Option<String>,
+    pub leader_id: String,
+    pub member_id: String,
+    /// Every member's metadata for the chosen protocol, for the leader only.
+    pub members: Vec<JoinGroupResponseMember>,
+    /// Whether the leader should keep the current assignment instead of
+    /// computing one, because it is a static member rejoining a stable group.
+    pub skip_assignment: bool,
+}
+
+impl JoinResult {
+    pub fn error(member_id: &str, error_code: i16) -> Self {
+        Self {
+            error_code,
+            generation_id: -1,
+            protocol_type: None,
+            protocol_name: None,
+            leader_id: String::new(),
+            member_id: member_id.to_string(),
+            members: Vec::new(),
+            skip_assignment: false,
+        }
+    }
+}
+
+/// Assignment a SyncGroup call returns.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyncResult {
+    pub error_code: i16,
+    pub protocol_type: Option<String>,
+    pub protocol_name: Option<String>,
+    pub assignment: Vec<u8>,
+}
+
+impl SyncResult {
+    pub fn error(error_code: i16) -> Self {
+        Self {
+            error_code,
+            protocol_type: None,
+            protocol_name: None,
+            assignment: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Member {
+    pub member_id: String,
+    pub group_instance_id: Option<String>,
+    pub client_id: String,
+    /// Address the member connected from, as "/ip".
+    pub client_host: String,
+    pub session_timeout_ms: i32,
+    pub rebalance_timeout_ms: i32,
+    pub protocol_type: String,
+    pub protocols: Vec<JoinGroupRequestProtocol>,
+    pub assignment: Vec<u8>,
+    /// Where to send the JoinGroup response while the member waits for one.
+    pub awaiting_join: Option<Sender<JoinResult>>,
+    /// Where to send the SyncGroup response while the member waits for one.
+    pub awaiting_sync: Option<Sender<SyncResult>>,
+    /// Bumped on every heartbeat, so a pending session expiry can tell it was
+    /// superseded.
+    pub heartbeats: u64,
+}
+
+impl Member {
+    /// Metadata the member sent for `protocol`.
+    fn metadata(&self, protocol: &str) -> Vec<u8> {
+        self.protocols
+            .iter()
+            .find(|candidate| candidate.name == protocol)
+            .map(|candidate| candidate.metadata.clone())
+            .unwrap_or_default()
+    }
+
+    /// Whether the session must not expire, because the member is blocked
+    /// in a JoinGroup or SyncGroup call and cannot heartbeat.
+    pub fn is_awaiting_response(&self) -> bool {
+        self.awaiting_join.is_some() || self.awaiting_sync.is_some()
+    }
+
+    pub fn record_heartbeat(&mut self) -> u64 {
+        self.heartbeats += 1;
+        self.heartbeats
+    }
+}
+
+/// A group using the classic JoinGroup/SyncGroup protocol, the consumer
+/// rebalance protocol once a member joined it through ConsumerGroupHeartbeat,
+/// or a share group once a member joined it through ShareGroupHeartbeat.
+#[derive(Debug)]
+pub struct Group {
+    pub group_id: String,
+    pub state: GroupState,
+    pub generation_id: i32,
+    pub protocol_type: Option<String>,
+    pub protocol_name: Option<String>,
+    pub leader_id: Option<String>,
+    pub members: BTreeMap<String, Member>,
+    /// Ids handed out with `MEMBER_ID_REQUIRED` whose owners have not
+    /// joined with them yet.
+    pub pending_members: HashSet<String>,
+    /// Current member id of each static member, by group instance id.
+    pub static_members: HashMap<String, String>,
+    /// Whether a delayed join is parked for the current rebalance.
+    pub join_watched: bool,
+    pub offsets: BTreeMap<TopicPartition, OffsetAndMetadata>,
+    /// When the last member left, or `None` if the group never had members
+    /// since the broker started.
+    pub empty_since_ms: Option<i64>,
+    /// Members and assignment under the consumer rebalance protocol; `None`
+    /// for classic groups, which keep `state` and `members` instead.
+    pub consumer: Option<ConsumerGroup>,
+    /// Members and record delivery state of a share group.
+    pub share: Option<ShareGroup>,
+}
+
+impl Group {
+    pub fn new(group_id: &str) -> Self {
+        Self {
+            group_id: group_id.to_string(),
+            state: GroupState::Empty,
+            generation_id: 0,
+            protocol_type: None,
+            protocol_name: None,
+            leader_id: None,
+            members: BTreeMap::new(),
+            pending_members: HashSet::new(),
+            static_members: HashMap::new(),
+            join_watched: false,
+            offsets: BTreeMap::new(),
+            empty_since_ms: None,
+            consumer: None,
+            share: None,
+        }
+    }
+
+    /// Whether the group has no members under any protocol.
+    pub fn is_empty(&self) -> bool {
+        self.state == GroupState::Empty
+            && self
+                .consumer
+                .as_ref()
+                .map_or(true, |consumer| consumer.members.is_empty())
+            && self
+                .share
+                .as_ref()
+                .map_or(true, |share| share.members.is_empty())
+    }
+
+    /// Name of the group's state under the protocol it uses.
+    pub fn state_name(&self) -> &'static str {
+        match (&self.consumer, &self.share) {
+            (Some(consumer), _) => consumer.state().name(),
+            (None, Some(share)) => share.state().name(),
+            (None, None) => self.state.name(),
+        }
+    }
+
+    pub fn group_type(&self) -> &'static str {
+        if self.consumer.is_some() {
+            CONSUMER_GROUP_TYPE
+        } else if self.share.is_some() {
+            SHARE_GROUP_TYPE
+        } else {
+            CLASSIC_GROUP_TYPE
+        }
+    }
+
+    /// Whether the group uses the classic protocol.
+    pub fn is_classic(&self) -> bool {
+        self.consumer.is_none() && self.share.is_none()
+    }
+
+    pub fn is_leader(&self, member_id: &str) -> bool {
+        self.leader_id.as_deref() == Some(member_id)
+    }
+
+    /// Whether `group_instance_id` belongs to a static member that has since
+    /// rejoined under an id other than `member_id`.
+    pub fn is_static_member_fenced(
+        &self,
+        member_id: &str,
+        group_instance_id: Option<&str>,
+    ) -> bool {
+        group_instance_id
+            .and_then(|instance_id| self.static_members.get(instance_id))
+            .is_some_and(|current| current != member_id)
+    }
+
+    /// Moves the static member `group_instance_id` to `new_member_id`,
+    /// keeping its assignment and failing calls still waiting under its old
+    /// id with `FENCED_INSTANCE_ID`.
+    pub fn replace_static_member(
+        &mut self,
+        group_instance_id: &str,
+        new_member_id: &str,
+    ) -> Option<&mut Member> {
+        let old_member_id = self.static_members.get(group_instance_id)?.clone();
+        let mut member = self.members.remove(&old_member_id)?;
+        if let Some(respond) = member.awaiting_join.take() {
+            let _ = respond.send(JoinResult::error(&old_member_id, error::FENCED_INSTANCE_ID));
+        }
+        if let Some(respond) = member.awaiting_sync.take() {
+            let _ = respond.send(SyncResult::error(error::FENCED_INSTANCE_ID));
+        }
+        if self.is_leader(&old_member_id) {
+            self.leader_id = Some(new_member_id.to_string());
+        }
+        member.member_id = new_member_id.to_string();
+        self.static_members
+            .insert(group_instance_id.to_string(), new_member_id.to_string());
+        self.members.insert(new_member_id.to_string(), member);
+        self.members.get_mut(new_member_id)
+    }
+
+    /// Whether a member with these protocols may join: it must use the
+    /// group's protocol type and share a protocol with every other member.
+    pub fn supports_protocols(
+        &self,
+        protocol_type: &str,
+        protocols: &[JoinGroupRequestProtocol],
+    ) -> bool {
+        if protocol_type.is_empty() || protocols.is_empty() {
+            return false;
+        }
+        if self.members.is_empty() {
+            return true;
+        }
+        self.protocol_type.as_deref() == Some(protocol_type)
+            && protocols
+                .iter()
+                .any(|protocol| self.candidate_protocols().contains(protocol.name.as_str()))
+    }
+
+    /// Whether a delayed join can complete: every member has rejoined and no
+    /// member told to retry with its new id is still missing.
+    pub fn all_members_joined(&self) -> bool {
+        self.pending_members.is_empty()
+            && self
+                .members
+                .values()
+                .all(|member| member.awaiting_join.is_some())
+    }
+
+    /// How long members have to rejoin during a rebalance.
+    pub fn rebalance_timeout_ms(&self) -> i64 {
+        self.members
+            .values()
+            .map(|member| i64::from(member.rebalance_timeout_ms))
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Offsets that outlived `retention_ms` at `now_ms` while the group was
+    /// empty, counting from their commit or the group becoming empty,
+    /// whichever is later.
+    pub fn expired_offsets(&self, now_ms: i64, retention_ms: i64) -> Vec<TopicPartition> {
+        if !self.is_empty() {
+            return Vec::new();
+        }
+        self.offsets
+            .iter()
+            .filter(|(_, offset)| {
+                let since = offset
+                    .commit_timestamp_ms
+                    .max(self.empty_since_ms.unwrap_or(i64::MIN));
+                now_ms.saturating_sub(since) >= retention_ms
+            })
+            .map(|(tp, _)| tp.clone())
+            .collect()
+    }
+
+    pub fn overview(&self) -> GroupOverview {
+        GroupOverview {
+            group_id: self.group_id.clone(),
+            protocol_type: match (&self.consumer, &self.share) {
+                (Some(_), _) => CONSUMER_PROTOCOL_TYPE.to_string(),
+                (None, Some(_)) => SHARE_GROUP_TYPE.to_string(),
+                (None, None) => self.protocol_type.clone().unwrap_or_default(),
+            },
+            state: self.state_name(),
+            group_type: self.group_type(),
+        }
+    }
+
+    /// The group's members; their metadata and assignments are only known
+    /// once the group is stable.
+    pub fn summary(&self) -> GroupSummary {
+        let stable = self.state == GroupState::Stable;
+        let protocol_name = match &self.protocol_name {
+            Some(protocol_name) if stable => protocol_name.clone(),
+            _ => String::new(),
+        };
+        GroupSummary {
+            state: self.state,
+            protocol_type: self.protocol_type.clone().unwrap_or_default(),
+            members: self
+                .members
+                .values()
+                .map(|member| MemberSummary {
+                    member_id: member.member_id.clone(),
+                    group_instance_id: member.group_instance_id.clone(),
+                    client_id: member.client_id.clone(),
+                    client_host: member.client_host.clone(),
+                    metadata: if stable {
+                        member.metadata(&protocol_name)
+                    } else {
+                        Vec::new()
+                    },
+                    assignment: if stable {
+                        member.assignment.clone()
+                    } else {
+                        Vec::new()
+                    },
+                })
+                .collect(),
+            protocol_name,
+        }
+    }
+
+    /// Whether any member may be consuming `topic`, as listed in the
+    /// members' consumer subscriptions. Until a protocol is selected, or if
+    /// a subscription cannot be read, every topic counts.
+    pub fn is_subscribed_to(&self, topic: &str) -> bool {
+        if let Some(consumer) = &self.consumer {
+            return consumer.is_subscribed_to(topic);
+        }
+        if let Some(share) = &self.share {
+            return share.is_subscribed_to(topic);
+        }
+        if self.members.is_empty() {
+            return false;
+        }
+        let Some(protocol_name) = self
+            .protocol_name
+            .as_deref()
+            .filter(|_| self.protocol_type.as_deref() == Some(CONSUMER_PROTOCOL_TYPE))
+        else {
+            return true;
+        };
+        self.members.values().any(|member| {
+            subscribed_topics(&member.metadata(protocol_name)).map_or(true, |topics| {
+                topics.iter().any(|subscribed| subscribed == topic)
+            })
+        })
+    }
+
+    /// Whether the group holds neither members, offsets nor share delivery
+    /// state and can be forgotten.
+    pub fn is_unused(&self) -> bool {
+        self.is_empty()
+            && self.members.is_empty()
+            && self.pending_members.is_empty()
+            && self.offsets.is_empty()
+            && self
+                .share
+                .as_ref()
+                .map_or(true, |share| share.partitions.is_empty())
+    }
+
+    /// Moves the group to `PreparingRebalance`, failing any SyncGroup calls
+    /// waiting for the assignment of the generation being abandoned.
+    pub fn prepare_rebalance(&mut self) {
+        if self.state == GroupState::CompletingRebalance {
+            for member in self.members.values_mut() {
+                member.assignment.clear();
+                if let Some(respond) = member.awaiting_sync.take() {
+                    let _ = respond.send(SyncResult::error(error::REBALANCE_IN_PROGRESS));
+                }
+            }
+        }
+        self.state = GroupState::PreparingRebalance;
+    }
+
+    /// Removes a member, failing its pending calls, and rebalances the rest.
+    pub fn remove_member(&mut self, member_id: &str) -> Option<Member> {
+        let mut member = self.members.remove(member_id)?;
+        self.forget_static_member(&member);
+        if let Some(respond) = member.awaiting_join.take() {
+            let _ = respond.send(JoinResult::error(member_id, error::UNKNOWN_MEMBER_ID));
+        }
+        if let Some(respond) = member.awaiting_sync.take() {
+            let _ = respond.send(SyncResult::error(error::UNKNOWN_MEMBER_ID));
+        }
+        if self.is_leader(member_id) {
+            self.leader_id = None;
+        }
+        if matches!(
+            self.state,
+            GroupState::Stable | GroupState::CompletingRebalance
+        ) {
+            self.prepare_rebalance();
+        }
+        Some(member)
+    }
+
+    /// Starts the next generation with the members that rejoined, answering
+    /// their JoinGroup calls. Returns the members whose sessions restart.
+    pub fn complete_join(&mut self) -> Vec<&Member> {
+        self.join_watched = false;
+        let missing: Vec<String> = self
+            .members
+            .values()
+            .filter(|member| member.awaiting_join.is_none())
+            .map(|member| member.member_id.clone())
+            .collect();
+        for member_id in missing {
+            if let Some(member) = self.members.remove(&member_id) {
+                self.forget_static_member(&member);
+            }
+            if self.is_leader(&member_id) {
+                self.leader_id = None;
+            }
+        }
+
+        self.generation_id += 1;
+        if self.members.is_empty() {
+            self.state = GroupState::Empty;
+            self.protocol_type = None;
+            self.protocol_name = None;
+            self.leader_id = None;
+            return Vec::new();
+        }
+        let protocol_name = self.select_protocol();
+        let leader_id = match self.leader_id.take() {
+            Some(leader_id) => leader_id,
+            None => self.members.keys().next().cloned().unwrap_or_default(),
+        };
+        let members: Vec<JoinGroupResponseMember> = self
+            .members
+            .values()
+            .map(|member| JoinGroupResponseMember {
+                member_id: member.member_id.clone(),
+                group_instance_id: member.group_instance_id.clone(),
+                metadata: member.metadata(&protocol_name),
+            })
+            .collect();
+        self.protocol_name = Some(protocol_name);
+        self.leader_id = Some(leader_id.clone());
+        self.state = GroupState::CompletingRebalance;
+
+        for member in self.members.values_mut() {
+            member.assignment.clear();
+            member.record_heartbeat();
+            if let Some(respond) = member.awaiting_join.take() {
+                let _ = respond.send(JoinResult {
+                    error_code: error::NONE,
+                    generation_id: self.generation_id,
+                    protocol_type: self.protocol_type.clone(),
+                    protocol_name: self.protocol_name.clone(),
+                    leader_id: leader_id.clone(),
+                    member_id: member.member_id.clone(),
+                    members: if member.member_id == leader_id {
+                        members.clone()
+                    } else {
+                        Vec::new()
+                    },
+                    skip_assignment: false,
+                });
+            }
+        }
+        self.members.values().collect()
+    }
+
+    /// The current generation as seen by `member_id`, for members that
+    /// rejoin without changing anything.
+    pub fn current_join_result(&self, member_id: &str) -> JoinResult {
+        let protocol_name = self.protocol_name.clone().unwrap_or_default();
+        JoinResult {
+            error_code: error::NONE,
+            generation_id: self.generation_id,
+            protocol_type: self.protocol_type.clone(),
+            protocol_name: self.protocol_name.clone(),
+            leader_id: self.leader_id.clone().unwrap_or_default(),
+            member_id: member_id.to_string(),
+            members: if self.is_leader(member_id) {
+                self.members
+                    .values()
+                    .map(|member| JoinGroupResponseMember {
+                        member_id: member.member_id.clone(),
+                        group_instance_id: member.group_instance_id.clone(),
+                        metadata: member.metadata(&protocol_name),
+                    })
+                    .collect()
+            } else {
+                Vec::new()
+            },
+            skip_assignment: false,
+        }
+    }
+
+    /// Stores the leader's assignment, making the group stable and answering
+    /// every SyncGroup call that waited for it.
+    pub fn complete_sync(&mut self, mut assignments: HashMap<String, Vec<u8>>) {
+        self.state = GroupState::Stable;
+        for member in self.members.values_mut() {
+            member.assignment = assignments.remove(&member.member_id).unwrap_or_default();
+            if let Some(respond) = member.awaiting_sync.take() {
+                let _ = respond.send(SyncResult {
+                    error_code: error::NONE,
+                    protocol_type: self.protocol_type.clone(),
+                    protocol_name: self.protocol_name.clone(),
+                    assignment: member.assignment.clone(),
+                });
+            }
+        }
+    }
+
+    fn forget_static_member(&mut self, member: &Member) {
+        if let Some(instance_id) = &member.group_instance_id {
+            if self.static_members.get(instance_id) == Some(&member.member_id) {
+                self.static_members.remove(instance_id);
+            }
+        }
+    }
+
+    /// Protocols every member supports.
+    fn candidate_protocols(&self) -> HashSet<&str> {
+        let mut members = self.members.values();
+        let Some(first) = members.next() else {
+            return HashSet::new();
+        };
+        let mut candidates: HashSet<&str> = first
+            .protocols
+            .iter()
+            .map(|protocol| protocol.name.as_str())
+            .collect();
+        for member in members {
+            candidates.retain(|name| {
+                member
+                    .protocols
+                    .iter()
+                    .any(|protocol| protocol.name == *name)
+            });
+        }
+        candidates
+    }
+
+    /// Picks the common protocol most members list first among the common
+    /// ones, breaking ties by name.
+    fn select_protocol(&self) -> String {
+        let candidates = self.candidate_protocols();
+        let mut votes: BTreeMap<&str, usize> = BTreeMap::new();
+        for member in self.members.values() {
+            if let Some(choice) = member
+                .protocols
+                .iter()
+                .find(|protocol| candidates.contains(protocol.name.as_str()))
+            {
+                *votes.entry(choice.name.as_str()).or_default() += 1;
+            }
+        }
+        votes
+            .into_iter()
+            .max_by(|(left_name, left), (right_name, right)| {
+                left.cmp(right).then(right_name.cmp(left_name))
+            })
+            .map(|(name, _)| name.to_string())
+            .unwrap_or_default()
+    }
+}
+
+/// Topics listed in a consumer protocol subscription, which starts with a
+/// version followed by the topic names in every version.
+fn subscribed_topics(metadata: &[u8]) -> io::Result<Vec<String>> {
+    let mut cursor = Cursor::new(metadata);
+    primitives::read_i16(&mut cursor)?;
+    wire::read_array(&mut cursor, false, |cursor| {
+        wire::read_string(cursor, false)
+    })
+    .map(Option::unwrap_or_default)
+}
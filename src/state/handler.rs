@@ -0,0 +1,3450 @@
+use super::consumer_group::TopicMetadata;
+use super::coordinator::{
+    CommitParams, ConsumerHeartbeatParams, GroupCoordinator, JoinParams, MemberIdentity,
+    OffsetCommit, ShareAcknowledgeParams, ShareAcknowledgement, ShareFetchParams,
+    ShareHeartbeatParams, SyncParams,
+};
+use super::delayed_fetch::{fetch_partition_error, DelayedFetch, FetchParams, FetchPosition};
+use super::fetch_session::{
+    FetchSessionCache, FetchedPartition, DEFAULT_CACHE_SLOTS, DEFAULT_EVICTION_MS,
+    INVALID_SESSION_ID,
+};
+use super::group::GroupSummary;
+use super::offsets::OFFSETS_TOPIC;
+use super::purgatory::Purgatory;
+use super::ApiRegistry;
+use crate::metadata::controller;
+use crate::metadata::{MetadataController, MetadataImage, TopicImage, Uuid};
+use crate::protocol::consumer_group_describe::{
+    ConsumerGroupDescription, ConsumerGroupMemberDescription, DescribedTopicPartitions,
+};
+use crate::protocol::consumer_group_heartbeat::ConsumerGroupTopicPartitions;
+use crate::protocol::create_topics::{CreatableTopic, CreatableTopicResult, DEFAULT_SETTING};
+use crate::protocol::delete_groups::DeletableGroupResult;
+use crate::protocol::delete_topics::{DeletableTopicResult, DeleteTopicState};
+use crate::protocol::describe_cluster::{DescribeClusterBroker, BROKER_ENDPOINT_TYPE};
+use crate::protocol::describe_groups::{DescribedGroup, DescribedGroupMember};
+use crate::protocol::describe_topic_partitions::{
+    DescribeTopicPartitionsResponsePartition, DescribeTopicPartitionsResponseTopic,
+    TopicPartitionCursor,
+};
+use crate::protocol::fetch::{
+    FetchPartitionData, FetchableTopicResponse, FIRST_TOPIC_ID_VERSION, UNKNOWN_LEADER_EPOCH,
+};
+use crate::protocol::find_coordinator::{Coordinator, GROUP_KEY_TYPE, TRANSACTION_KEY_TYPE};
+use crate::protocol::init_producer_id::{NO_PRODUCER_EPOCH, NO_PRODUCER_ID};
+use crate::protocol::join_group::FIRST_MEMBER_ID_REQUIRED_VERSION;
+use crate::protocol::leave_group::{LeaveGroupResponseMember, FIRST_BATCHED_VERSION};
+use crate::protocol::list_groups::ListedGroup;
+use crate::protocol::list_offsets::{
+    ListOffsetsRequestPartition, ListOffsetsResponsePartition, ListOffsetsResponseTopic,
+    EARLIEST_LOCAL_TIMESTAMP, EARLIEST_TIMESTAMP, LATEST_TIMESTAMP, MAX_TIMESTAMP, READ_COMMITTED,
+    UNKNOWN_OFFSET, UNKNOWN_TIMESTAMP,
+};
+use crate::protocol::metadata::{
+    MetadataBroker, MetadataRequestTopic, MetadataResponsePartition, MetadataResponseTopic,
+    AUTHORIZED_OPERATIONS_OMITTED,
+};
+use crate::protocol::offset_commit::{OffsetCommitResponsePartition, OffsetCommitResponseTopic};
+use crate::protocol::offset_delete::{OffsetDeleteResponsePartition, OffsetDeleteResponseTopic};
+use crate::protocol::offset_fetch::{
+    OffsetFetchRequestGroup, OffsetFetchResponseGroup, OffsetFetchResponsePartition,
+    OffsetFetchResponseTopic, NO_LEADER_EPOCH, NO_OFFSET,
+};
+use crate::protocol::produce::{
+    PartitionProduceResponse, TopicProduceResponse, ACKS_ALL, ACKS_LEADER, ACKS_NONE,
+    NO_LOG_APPEND_TIME,
+};
+use crate::protocol::share_acknowledge::{
+    ShareAcknowledgePartitionResponse, ShareAcknowledgeTopicResponse,
+};
+use crate::protocol::share_fetch::{
+    AcknowledgementBatch, LeaderIdAndEpoch, ShareFetchPartitionData, ShareFetchTopicResponse,
+};
+use crate::protocol::{
+    error, ConsumerGroupDescribeRequest, ConsumerGroupDescribeResponse,
+    ConsumerGroupHeartbeatRequest, ConsumerGroupHeartbeatResponse, CreateTopicsRequest,
+    CreateTopicsResponse, DeleteGroupsRequest, DeleteGroupsResponse, DeleteTopicsRequest,
+    DeleteTopicsResponse, DescribeClusterRequest, DescribeClusterResponse, DescribeGroupsRequest,
+    DescribeGroupsResponse, DescribeTopicPartitionsRequest, DescribeTopicPartitionsResponse,
+    FetchRequest, FetchResponse, FindCoordinatorRequest, FindCoordinatorResponse, HeartbeatRequest,
+    HeartbeatResponse, InitProducerIdRequest, InitProducerIdResponse, JoinGroupRequest,
+    JoinGroupResponse, LeaveGroupRequest, LeaveGroupResponse, ListGroupsRequest,
+    ListGroupsResponse, ListOffsetsRequest, ListOffsetsResponse, MetadataRequest, MetadataResponse,
+    OffsetCommitRequest, OffsetCommitResponse, OffsetDeleteRequest, OffsetDeleteResponse,
+    OffsetFetchRequest, OffsetFetchResponse, ProduceRequest, ProduceResponse, Request, Response,
+    ResponseHeader, ShareAcknowledgeRequest, ShareAcknowledgeResponse, ShareFetchRequest,
+    ShareFetchResponse, ShareGroupHeartbeatRequest, ShareGroupHeartbeatResponse, SyncGroupRequest,
+    SyncGroupResponse,
+};
+use crate::storage::batch;
+use crate::storage::index::TimestampOffset;
+use crate::storage::{Log, LogManager, SequenceError, TopicPartition};
+use crate::time::Clock;
+use std::collections::{BTreeMap, BTreeSet};
+use std::io;
+use std::ops::Range;
+use std::sync::mpsc::{self, Receiver};
+use std::sync::{Arc, Mutex};
+
+/// Identity of this broker and the addresses clients should connect to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BrokerInfo {
+    pub cluster_id: String,
+    pub node_id: i32,
+    pub endpoints: Vec<BrokerEndpoint>,
+}
+
+/// Address advertised to clients connected through the named listener.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BrokerEndpoint {
+    pub listener_name: String,
+    pub host: String,
+    pub port: i32,
+}
+
+/// Where a request came from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequestContext {
+    /// Listener the connection was accepted on.
+    pub listener_name: String,
+    /// Address of the client as Kafka reports it, such as "/127.0.0.1".
+    pub client_host: String,
+}
+
+/// Answers decoded requests from the broker's identity, the metadata image,
+/// the local logs and the group coordinator. Topic changes go through the
+/// metadata controller.
+pub struct RequestHandler {
+    registry: ApiRegistry,
+    broker: BrokerInfo,
+    controller: Arc<Mutex<MetadataController>>,
+    logs: Arc<Mutex<LogManager>>,
+    coordinator: Arc<GroupCoordinator>,
+    /// Producer ids reserved by the controller and not yet handed out.
+    producer_ids: Mutex<Range<i64>>,
+    fetch_sessions: Mutex<FetchSessionCache>,
+    /// Long-poll fetches waiting for records on their partitions.
+    delayed_fetches: Purgatory<TopicPartition, DelayedFetch>,
+}
+
+impl RequestHandler {
+    pub fn new(
+        broker: BrokerInfo,
+        controller: Arc<Mutex<MetadataController>>,
+        logs: Arc<Mutex<LogManager>>,
+        coordinator: Arc<GroupCoordinator>,
+        clock: Arc<dyn Clock>,
+    ) -> Self {
+        Self {
+            registry: ApiRegistry::default(),
+            broker,
+            controller,
+            logs,
+            coordinator,
+            producer_ids: Mutex::new(0..0),
+            fetch_sessions: Mutex::new(FetchSessionCache::new(
+                DEFAULT_CACHE_SLOTS,
+                DEFAULT_EVICTION_MS,
+                Arc::clone(&clock),
+            )),
+            delayed_fetches: Purgatory::new("fetch", clock),
+        }
+    }
+
+    /// Completes parked fetches on each partition `appends` announces, until
+    /// the log manager stops announcing them.
+    pub fn complete_delayed_fetches(&self, appends: Receiver<TopicPartition>) {
+        for tp in appends {
+            self.delayed_fetches.check_and_complete(&tp);
+        }
+    }
+
+    /// Answers parked fetches whose `max_wait_ms` has passed, returning how
+    /// many expired.
+    pub fn expire_delayed_fetches(&self) -> usize {
+        self.delayed_fetches.expire()
+    }
+
+    pub fn handle(&self, context: &RequestContext, request: Request) -> Response {
+        match request {
+            Request::ApiVersions(request) => {
+                Response::ApiVersions(self.registry.handle_versions(request))
+            }
+            Request::Metadata(request) => {
+                Response::Metadata(self.handle_metadata(context, request))
+            }
+            Request::DescribeCluster(request) => {
+                Response::DescribeCluster(self.handle_describe_cluster(context, request))
+            }
+            Request::Fetch(request) => Response::Fetch(self.handle_fetch(request)),
+            Request::ListOffsets(request) => {
+                Response::ListOffsets(self.handle_list_offsets(request))
+            }
+            Request::DescribeTopicPartitions(request) => {
+                Response::DescribeTopicPartitions(self.handle_describe_topic_partitions(request))
+            }
+            Request::CreateTopics(request) => {
+                Response::CreateTopics(self.handle_create_topics(request))
+            }
+            Request::DeleteTopics(request) => {
+                Response::DeleteTopics(self.handle_delete_topics(request))
+            }
+            Request::FindCoordinator(request) => {
+                Response::FindCoordinator(self.handle_find_coordinator(context, request))
+            }
+            Request::JoinGroup(request) => {
+                Response::JoinGroup(self.handle_join_group(context, request))
+            }
+            Request::SyncGroup(request) => Response::SyncGroup(self.handle_sync_group(request)),
+            Request::Heartbeat(request) => Response::Heartbeat(self.handle_heartbeat(request)),
+            Request::LeaveGroup(request) => Response::LeaveGroup(self.handle_leave_group(request)),
+            Request::OffsetCommit(request) => {
+                Response::OffsetCommit(self.handle_offset_commit(request))
+            }
+            Request::OffsetFetch(request) => {
+                Response::OffsetFetch(self.handle_offset_fetch(request))
+            }
+            Request::DescribeGroups(request) => {
+                Response::DescribeGroups(self.handle_describe_groups(request))
+            }
+            Request::ListGroups(request) => Response::ListGroups(self.handle_list_groups(request)),
+            Request::DeleteGroups(request) => {
+                Response::DeleteGroups(self.handle_delete_groups(request))
+            }
+            Request::OffsetDelete(request) => {
+                Response::OffsetDelete(self.handle_offset_delete(request))
+            }
+            Request::ConsumerGroupHeartbeat(request) => Response::ConsumerGroupHeartbeat(
+                self.handle_consumer_group_heartbeat(context, request),
+            ),
+            Request::ConsumerGroupDescribe(request) => {
+                Response::ConsumerGroupDescribe(self.handle_consumer_group_describe(request))
+            }
+            Request::ShareGroupHeartbeat(request) => {
+                Response::ShareGroupHeartbeat(self.handle_share_group_heartbeat(context, request))
+            }
+            Request::ShareFetch(request) => Response::ShareFetch(self.handle_share_fetch(request)),
+            Request::ShareAcknowledge(request) => {
+                Response::ShareAcknowledge(self.handle_share_acknowledge(request))
+            }
+            Request::InitProducerId(request) => {
+                Response::InitProducerId(self.handle_init_producer_id(request))
+            }
+            Request::Produce(request) => Response::Produce(self.handle_produce(request)),
+        }
+    }
+
+    /// The endpoint advertised for the listener a request arrived on, so
+    /// clients keep using the same listener; `None` if it is not advertised.
+    fn advertised_endpoint(&self, context: &RequestContext) -> Option<&BrokerEndpoint> {
+        self.broker
+            .endpoints
+            .iter()
+            .find(|endpoint| endpoint.listener_name == context.listener_name)
+    }
+
+    fn handle_metadata(
+        &self,
+        context: &RequestContext,
+        request: MetadataRequest,
+    ) -> MetadataResponse {
+        let controller = self
+            .controller
+            .lock()
+            .expect("metadata controller lock poisoned");
+        let image = controller.image();
+        let topics = match &request.topics {
+            Some(requested) => requested
+                .iter()
+                .map(|topic| describe_requested_topic(image, topic))
+                .collect(),
+            None => image.topics().map(describe_topic).collect(),
+        };
+
+        MetadataResponse {
+            header: ResponseHeader {
+                correlation_id: request.header.correlation_id,
+            },
+            api_version: request.header.request_api_version,
+            throttle_time_ms: 0,
+            brokers: self
+                .advertised_endpoint(context)
+                .map(|endpoint| MetadataBroker {
+                    node_id: self.broker.node_id,
+                    host: endpoint.host.clone(),
+                    port: endpoint.port,
+                    rack: None,
+                })
+                .into_iter()
+                .collect(),
+            cluster_id: Some(self.broker.cluster_id.clone()),
+            controller_id: self.broker.node_id,
+            topics,
+        }
+    }
+
+    fn handle_describe_cluster(
+        &self,
+        context: &RequestContext,
+        request: DescribeClusterRequest,
+    ) -> DescribeClusterResponse {
+        let mut response = DescribeClusterResponse {
+            header: ResponseHeader {
+                correlation_id: request.header.correlation_id,
+            },
+            api_version: request.header.request_api_version,
+            throttle_time_ms: 0,
+            error_code: error::NONE,
+            error_message: None,
+            endpoint_type: request.endpoint_type,
+            cluster_id: self.broker.cluster_id.clone(),
+            controller_id: self.broker.node_id,
+            brokers: self
+                .advertised_endpoint(context)
+                .map(|endpoint| DescribeClusterBroker {
+                    broker_id: self.broker.node_id,
+                    host: endpoint.host.clone(),
+                    port: endpoint.port,
+                    rack: None,
+                })
+                .into_iter()
+                .collect(),
+        };
+        if request.endpoint_type != BROKER_ENDPOINT_TYPE {
+            response.error_code = error::MISMATCHED_ENDPOINT_TYPE;
+            response.error_message = Some(format!(
+                "the request was sent to a broker endpoint but endpoint type {} was wanted",
+                request.endpoint_type
+            ));
+            response.brokers.clear();
+        }
+        response
+    }
+
+    /// Serves each partition from its log, up to the high watermark or, for
+    /// read-committed fetches, the last stable offset. `max_bytes` bounds the
+    /// whole response except for the first batch returned.
+    ///
+    /// The request's session fields pick the partitions to serve: an
+    /// incremental fetch adds to and forgets from its cached session, and
+    /// its response leaves out partitions with nothing new.
+    fn handle_fetch(&self, request: FetchRequest) -> FetchResponse {
+        let version = request.header.request_api_version;
+        let mut response = FetchResponse {
+            header: ResponseHeader {
+                correlation_id: request.header.correlation_id,
+            },
+            api_version: version,
+            throttle_time_ms: 0,
+            error_code: error::NONE,
+            session_id: INVALID_SESSION_ID,
+            responses: Vec::new(),
+        };
+        let mut unknown = Vec::new();
+        let mut partitions = Vec::new();
+        let mut forgotten = Vec::new();
+        let mut errors = BTreeMap::new();
+        // Topics and leader epochs are resolved first so the two locks are
+        // never held together.
+        let topic_ids: BTreeMap<String, Uuid> = {
+            let controller = self
+                .controller
+                .lock()
+                .expect("metadata controller lock poisoned");
+            let image = controller.image();
+            for topic in &request.topics {
+                let found = match &topic.name {
+                    Some(name) => image.topic(name),
+                    None => image.topic_by_id(&topic.topic_id),
+                };
+                let Some(found) = found else {
+                    let error_code = if version >= FIRST_TOPIC_ID_VERSION {
+                        error::UNKNOWN_TOPIC_ID
+                    } else {
+                        error::UNKNOWN_TOPIC_OR_PARTITION
+                    };
+                    for partition in &topic.partitions {
+                        let data = fetch_partition_error(partition.partition, error_code);
+                        unknown.push((topic.name.clone(), topic.topic_id, data));
+                    }
+                    continue;
+                };
+                for partition in &topic.partitions {
+                    let tp = TopicPartition::new(found.name.clone(), partition.partition);
+                    let error_code = match found.partitions.get(&partition.partition) {
+                        Some(registration) => leader_epoch_error(
+                            partition.current_leader_epoch,
+                            registration.leader_epoch,
+                        ),
+                        None => error::UNKNOWN_TOPIC_OR_PARTITION,
+                    };
+                    if error_code != error::NONE {
+                        errors.insert(tp.clone(), error_code);
+                    }
+                    let position = FetchPosition {
+                        fetch_offset: partition.fetch_offset,
+                        log_start_offset: partition.log_start_offset,
+                        partition_max_bytes: partition.partition_max_bytes,
+                    };
+                    partitions.push((tp, position));
+                }
+            }
+            for topic in &request.forgotten_topics_data {
+                let found = match &topic.name {
+                    Some(name) => image.topic(name),
+                    None => image.topic_by_id(&topic.topic_id),
+                };
+                if let Some(found) = found {
+                    forgotten.extend(
+                        topic
+                            .partitions
+                            .iter()
+                            .map(|&partition| TopicPartition::new(found.name.clone(), partition)),
+                    );
+                }
+            }
+            image
+                .topics()
+                .map(|topic| (topic.name.clone(), topic.id))
+                .collect()
+        };
+
+        let context = self
+            .fetch_sessions
+            .lock()
+            .expect("fetch session cache lock poisoned")
+            .new_context(
+                request.session_id,
+                request.session_epoch,
+                partitions,
+                &forgotten,
+            );
+        let context = match context {
+            Ok(context) => context,
+            Err(error_code) => {
+                response.error_code = error_code;
+                return response;
+            }
+        };
+
+        // A fetch that cannot be answered yet is parked, and this connection
+        // waits for whichever of an append or its expiry completes it.
+        let (respond, fetched) = mpsc::channel();
+        let mut fetch = DelayedFetch::new(
+            Arc::clone(&self.logs),
+            FetchParams::new(&request),
+            context
+                .partitions
+                .iter()
+                .map(|(tp, position)| (tp.clone(), *position))
+                .collect(),
+            errors,
+            respond,
+        );
+        if request.max_wait_ms > 0 && !context.partitions.is_empty() {
+            let keys = context.partitions.keys().cloned().collect();
+            self.delayed_fetches.try_complete_else_watch(
+                fetch,
+                keys,
+                i64::from(request.max_wait_ms),
+            );
+        } else {
+            fetch.complete();
+        }
+        let fetched = fetched.recv().unwrap_or_default();
+
+        let mut sessions = self
+            .fetch_sessions
+            .lock()
+            .expect("fetch session cache lock poisoned");
+        for (tp, data) in fetched {
+            let summary = FetchedPartition {
+                high_watermark: data.high_watermark,
+                log_start_offset: data.log_start_offset,
+                error_code: data.error_code,
+                has_records: data
+                    .records
+                    .as_ref()
+                    .is_some_and(|records| !records.is_empty()),
+            };
+            if sessions.should_respond(&context, &tp, &summary) {
+                let topic_id = topic_ids.get(&tp.topic).copied().unwrap_or(Uuid::ZERO);
+                push_fetched(
+                    &mut response.responses,
+                    Some(tp.topic.clone()),
+                    topic_id,
+                    data,
+                );
+            }
+        }
+        for (name, topic_id, data) in unknown {
+            push_fetched(&mut response.responses, name, topic_id, data);
+        }
+        response.session_id = context.session_id;
+        response
+    }
+
+    fn handle_list_offsets(&self, request: ListOffsetsRequest) -> ListOffsetsResponse {
+        // Leader epochs are read first so the two locks are never held together.
+        let leader_epochs: Vec<Vec<Option<i32>>> = {
+            let controller = self
+                .controller
+                .lock()
+                .expect("metadata controller lock poisoned");
+            let image = controller.image();
+            request
+                .topics
+                .iter()
+                .map(|topic| {
+                    let found = image.topic(&topic.name);
+                    topic
+                        .partitions
+                        .iter()
+                        .map(|partition| {
+                            let registration = found?.partitions.get(&partition.partition_index)?;
+                            Some(registration.leader_epoch)
+                        })
+                        .collect()
+                })
+                .collect()
+        };
+
+        let logs = self.logs.lock().expect("log manager lock poisoned");
+        let topics = request
+            .topics
+            .iter()
+            .zip(leader_epochs)
+            .map(|(topic, epochs)| ListOffsetsResponseTopic {
+                name: topic.name.clone(),
+                partitions: topic
+                    .partitions
+                    .iter()
+                    .zip(epochs)
+                    .map(|(partition, leader_epoch)| {
+                        let tp = TopicPartition::new(topic.name.clone(), partition.partition_index);
+                        match (leader_epoch, logs.get_log(&tp)) {
+                            (Some(leader_epoch), Some(log)) => {
+                                list_offset(log, &request, partition, leader_epoch)
+                            }
+                            _ => list_offset_error(partition, error::UNKNOWN_TOPIC_OR_PARTITION),
+                        }
+                    })
+                    .collect(),
+            })
+            .collect();
+
+        ListOffsetsResponse {
+            header: ResponseHeader {
+                correlation_id: request.header.correlation_id,
+            },
+            api_version: request.header.request_api_version,
+            throttle_time_ms: 0,
+            topics,
+        }
+    }
+
+    /// Describes the requested topics, or all of them when none are named,
+    /// in name order from the cursor on. Once `response_partition_limit`
+    /// partitions are described the rest is left to `next_cursor`.
+    fn handle_describe_topic_partitions(
+        &self,
+        request: DescribeTopicPartitionsRequest,
+    ) -> DescribeTopicPartitionsResponse {
+        let controller = self
+            .controller
+            .lock()
+            .expect("metadata controller lock poisoned");
+        let image = controller.image();
+        let mut names: Vec<String> = if request.topics.is_empty() {
+            image.topics().map(|topic| topic.name.clone()).collect()
+        } else {
+            request.topics.clone()
+        };
+        names.sort();
+        names.dedup();
+        if let Some(cursor) = &request.cursor {
+            names.retain(|name| *name >= cursor.topic_name);
+        }
+
+        let mut remaining = usize::try_from(request.response_partition_limit)
+            .unwrap_or_default()
+            .min(MAX_DESCRIBED_PARTITIONS);
+        let mut topics = Vec::new();
+        let mut next_cursor = None;
+        for name in names {
+            let Some(topic) = image.topic(&name) else {
+                topics.push(DescribeTopicPartitionsResponseTopic {
+                    error_code: error::UNKNOWN_TOPIC_OR_PARTITION,
+                    name: Some(name),
+                    topic_id: Uuid::ZERO,
+                    is_internal: false,
+                    partitions: Vec::new(),
+                });
+                continue;
+            };
+            let first_partition = match &request.cursor {
+                Some(cursor) if cursor.topic_name == name => cursor.partition_index,
+                _ => 0,
+            };
+            let mut partitions = Vec::new();
+            for (&partition_index, partition) in topic.partitions.range(first_partition..) {
+                if remaining == 0 {
+                    next_cursor = Some(TopicPartitionCursor {
+                        topic_name: name.clone(),
+                        partition_index,
+                    });
+                    break;
+                }
+                remaining -= 1;
+                partitions.push(DescribeTopicPartitionsResponsePartition {
+                    error_code: error::NONE,
+                    partition_index,
+                    leader_id: partition.leader,
+                    leader_epoch: partition.leader_epoch,
+                    replica_nodes: partition.replicas.clone(),
+                    isr_nodes: partition.isr.clone(),
+                    eligible_leader_replicas: Vec::new(),
+                    last_known_elr: Vec::new(),
+                    offline_replicas: Vec::new(),
+                });
+            }
+            if next_cursor.is_some() && partitions.is_empty() {
+                break;
+            }
+            topics.push(DescribeTopicPartitionsResponseTopic {
+                error_code: error::NONE,
+                name: Some(name),
+                topic_id: topic.id,
+                is_internal: topic.name == OFFSETS_TOPIC,
+                partitions,
+            });
+            if next_cursor.is_some() {
+                break;
+            }
+        }
+
+        DescribeTopicPartitionsResponse {
+            header: ResponseHeader {
+                correlation_id: request.header.correlation_id,
+            },
+            api_version: request.header.request_api_version,
+            throttle_time_ms: 0,
+            topics,
+            next_cursor,
+        }
+    }
+
+    /// Creates topics through the metadata controller, one result per topic.
+    /// Topics named more than once in the request are all refused.
+    fn handle_create_topics(&self, request: CreateTopicsRequest) -> CreateTopicsResponse {
+        let mut controller = self
+            .controller
+            .lock()
+            .expect("metadata controller lock poisoned");
+        let mut logs = self.logs.lock().expect("log manager lock poisoned");
+        let mut occurrences: BTreeMap<&str, usize> = BTreeMap::new();
+        for topic in &request.topics {
+            *occurrences.entry(&topic.name).or_default() += 1;
+        }
+
+        let topics = request
+            .topics
+            .iter()
+            .map(|topic| {
+                let created = if occurrences[topic.name.as_str()] > 1 {
+                    Err((
+                        error::INVALID_REQUEST,
+                        format!("topic '{}' is listed more than once", topic.name),
+                    ))
+                } else {
+                    create_topic(
+                        &mut controller,
+                        &mut logs,
+                        self.broker.node_id,
+                        topic,
+                        request.validate_only,
+                    )
+                };
+                match created {
+                    Ok((topic_id, num_partitions)) => CreatableTopicResult {
+                        name: topic.name.clone(),
+                        topic_id,
+                        error_code: error::NONE,
+                        error_message: None,
+                        num_partitions,
+                        replication_factor: 1,
+                    },
+                    Err((error_code, message)) => CreatableTopicResult {
+                        name: topic.name.clone(),
+                        topic_id: Uuid::ZERO,
+                        error_code,
+                        error_message: Some(message),
+                        num_partitions: -1,
+                        replication_factor: -1,
+                    },
+                }
+            })
+            .collect();
+
+        CreateTopicsResponse {
+            header: ResponseHeader {
+                correlation_id: request.header.correlation_id,
+            },
+            api_version: request.header.request_api_version,
+            throttle_time_ms: 0,
+            topics,
+        }
+    }
+
+    /// Deletes topics named or identified in the request through the metadata
+    /// controller.
+    fn handle_delete_topics(&self, request: DeleteTopicsRequest) -> DeleteTopicsResponse {
+        let mut controller = self
+            .controller
+            .lock()
+            .expect("metadata controller lock poisoned");
+        let mut logs = self.logs.lock().expect("log manager lock poisoned");
+        let responses = request
+            .topics
+            .into_iter()
+            .map(
+                |topic| match delete_topic(&mut controller, &mut logs, &topic) {
+                    Ok((name, topic_id)) => DeletableTopicResult {
+                        name: Some(name),
+                        topic_id,
+                        error_code: error::NONE,
+                        error_message: None,
+                    },
+                    Err((error_code, message)) => DeletableTopicResult {
+                        name: topic.name,
+                        topic_id: topic.topic_id,
+                        error_code,
+                        error_message: Some(message),
+                    },
+                },
+            )
+            .collect();
+
+        DeleteTopicsResponse {
+            header: ResponseHeader {
+                correlation_id: request.header.correlation_id,
+            },
+            api_version: request.header.request_api_version,
+            throttle_time_ms: 0,
+            responses,
+        }
+    }
+
+    /// Appends each partition's batches to its log. Every replica is this
+    /// broker, so `acks=-1` is satisfied as soon as the leader has appended.
+    fn handle_produce(&self, request: ProduceRequest) -> ProduceResponse {
+        // Partitions are resolved first so the two locks are never held together.
+        let resolved: Vec<Vec<i16>> = {
+            let controller = self
+                .controller
+                .lock()
+                .expect("metadata controller lock poisoned");
+            let image = controller.image();
+            request
+                .topic_data
+                .iter()
+                .map(|topic| {
+                    let found = image.topic(&topic.name);
+                    topic
+                        .partition_data
+                        .iter()
+                        .map(|partition| {
+                            if ![ACKS_ALL, ACKS_LEADER, ACKS_NONE].contains(&request.acks) {
+                                error::INVALID_REQUIRED_ACKS
+                            } else if topic.name == OFFSETS_TOPIC {
+                                error::INVALID_TOPIC_EXCEPTION
+                            } else if found.is_some_and(|found| {
+                                found.partitions.contains_key(&partition.index)
+                            }) {
+                                error::NONE
+                            } else {
+                                error::UNKNOWN_TOPIC_OR_PARTITION
+                            }
+                        })
+                        .collect()
+                })
+                .collect()
+        };
+
+        let mut logs = self.logs.lock().expect("log manager lock poisoned");
+        let responses = request
+            .topic_data
+            .iter()
+            .zip(resolved)
+            .map(|(topic, errors)| TopicProduceResponse {
+                name: topic.name.clone(),
+                partition_responses: topic
+                    .partition_data
+                    .iter()
+                    .zip(errors)
+                    .map(|(partition, error_code)| {
+                        if error_code != error::NONE {
+                            return produce_error(partition.index, error_code, None);
+                        }
+                        let tp = TopicPartition::new(topic.name.clone(), partition.index);
+                        produce_partition(
+                            &mut logs,
+                            &tp,
+                            partition.records.as_deref().unwrap_or_default(),
+                        )
+                    })
+                    .collect(),
+            })
+            .collect();
+
+        ProduceResponse {
+            header: ResponseHeader {
+                correlation_id: request.header.correlation_id,
+            },
+            api_version: request.header.request_api_version,
+            responses,
+            throttle_time_ms: 0,
+        }
+    }
+
+    /// Every group is coordinated by this broker, reached through the
+    /// listener the request arrived on.
+    fn handle_find_coordinator(
+        &self,
+        context: &RequestContext,
+        request: FindCoordinatorRequest,
+    ) -> FindCoordinatorResponse {
+        let endpoint = self.advertised_endpoint(context);
+        let coordinators = request
+            .keys
+            .into_iter()
+            .map(|key| {
+                let mut coordinator = Coordinator {
+                    key,
+                    node_id: -1,
+                    host: String::new(),
+                    port: -1,
+                    error_code: error::NONE,
+                    error_message: None,
+                };
+                let failure = match (request.key_type, endpoint) {
+                    (GROUP_KEY_TYPE, Some(endpoint)) => {
+                        coordinator.node_id = self.broker.node_id;
+                        coordinator.host = endpoint.host.clone();
+                        coordinator.port = endpoint.port;
+                        None
+                    }
+                    (GROUP_KEY_TYPE, None) => Some((
+                        error::COORDINATOR_NOT_AVAILABLE,
+                        format!("listener {} is not advertised", context.listener_name),
+                    )),
+                    (TRANSACTION_KEY_TYPE, _) => Some((
+                        error::COORDINATOR_NOT_AVAILABLE,
+                        "transactions are not supported".to_string(),
+                    )),
+                    (key_type, _) => Some((
+                        error::INVALID_REQUEST,
+                        format!("unknown coordinator key type {key_type}"),
+                    )),
+                };
+                if let Some((error_code, message)) = failure {
+                    coordinator.error_code = error_code;
+                    coordinator.error_message = Some(message);
+                }
+                coordinator
+            })
+            .collect();
+
+        FindCoordinatorResponse {
+            header: ResponseHeader {
+                correlation_id: request.header.correlation_id,
+            },
+            api_version: request.header.request_api_version,
+            throttle_time_ms: 0,
+            coordinators,
+        }
+    }
+
+    fn handle_join_group(
+        &self,
+        context: &RequestContext,
+        request: JoinGroupRequest,
+    ) -> JoinGroupResponse {
+        let version = request.header.request_api_version;
+        let result = self.coordinator.join_group(JoinParams {
+            group_id: request.group_id,
+            member_id: request.member_id,
+            group_instance_id: request.group_instance_id,
+            client_id: request.header.client_id.unwrap_or_default(),
+            client_host: context.client_host.clone(),
+            session_timeout_ms: request.session_timeout_ms,
+            rebalance_timeout_ms: request.rebalance_timeout_ms,
+            protocol_type: request.protocol_type,
+            protocols: request.protocols,
+            require_known_member_id: version >= FIRST_MEMBER_ID_REQUIRED_VERSION,
+        });
+
+        JoinGroupResponse {
+            header: ResponseHeader {
+                correlation_id: request.header.correlation_id,
+            },
+            api_version: version,
+            throttle_time_ms: 0,
+            error_code: result.error_code,
+            generation_id: result.generation_id,
+            protocol_type: result.protocol_type,
+            protocol_name: result.protocol_name,
+            leader: result.leader_id,
+            skip_assignment: result.skip_assignment,
+            member_id: result.member_id,
+            members: result.members,
+        }
+    }
+
+    fn handle_sync_group(&self, request: SyncGroupRequest) -> SyncGroupResponse {
+        let result = self.coordinator.sync_group(SyncParams {
+            group_id: request.group_id,
+            generation_id: request.generation_id,
+            member_id: request.member_id,
+            group_instance_id: request.group_instance_id,
+            protocol_type: request.protocol_type,
+            protocol_name: request.protocol_name,
+            assignments: request
+                .assignments
+                .into_iter()
+                .map(|assignment| (assignment.member_id, assignment.assignment))
+                .collect(),
+        });
+
+        SyncGroupResponse {
+            header: ResponseHeader {
+                correlation_id: request.header.correlation_id,
+            },
+            api_version: request.header.request_api_version,
+            throttle_time_ms: 0,
+            error_code: result.error_code,
+            protocol_type: result.protocol_type,
+            protocol_name: result.protocol_name,
+            assignment: result.assignment,
+        }
+    }
+
+    fn handle_heartbeat(&self, request: HeartbeatRequest) -> HeartbeatResponse {
+        HeartbeatResponse {
+            header: ResponseHeader {
+                correlation_id: request.header.correlation_id,
+            },
+            api_version: request.header.request_api_version,
+            throttle_time_ms: 0,
+            error_code: self.coordinator.heartbeat(
+                &request.group_id,
+                request.generation_id,
+                &request.member_id,
+                request.group_instance_id.as_deref(),
+            ),
+        }
+    }
+
+    fn handle_leave_group(&self, request: LeaveGroupRequest) -> LeaveGroupResponse {
+        let version = request.header.request_api_version;
+        let leaving: Vec<MemberIdentity> = request
+            .members
+            .iter()
+            .map(|member| MemberIdentity {
+                member_id: member.member_id.clone(),
+                group_instance_id: member.group_instance_id.clone(),
+            })
+            .collect();
+        let (error_code, members) = match self.coordinator.leave_group(&request.group_id, &leaving)
+        {
+            Ok(results) => (
+                error::NONE,
+                request
+                    .members
+                    .into_iter()
+                    .zip(results)
+                    .map(|(member, error_code)| LeaveGroupResponseMember {
+                        member_id: member.member_id,
+                        group_instance_id: member.group_instance_id,
+                        error_code,
+                    })
+                    .collect(),
+            ),
+            Err(error_code) => (error_code, Vec::new()),
+        };
+        // Before batching, the single member's result is the request's.
+        let error_code = match members.first() {
+            Some(member) if version < FIRST_BATCHED_VERSION && error_code == error::NONE => {
+                member.error_code
+            }
+            _ => error_code,
+        };
+
+        LeaveGroupResponse {
+            header: ResponseHeader {
+                correlation_id: request.header.correlation_id,
+            },
+            api_version: version,
+            throttle_time_ms: 0,
+            error_code,
+            members,
+        }
+    }
+
+    fn handle_offset_commit(&self, request: OffsetCommitRequest) -> OffsetCommitResponse {
+        // Partitions are checked first so the controller lock is released
+        // before the coordinator writes to the offsets topic.
+        let known: Vec<Vec<bool>> = {
+            let controller = self
+                .controller
+                .lock()
+                .expect("metadata controller lock poisoned");
+            let image = controller.image();
+            request
+                .topics
+                .iter()
+                .map(|topic| {
+                    let found = image.topic(&topic.name);
+                    topic
+                        .partitions
+                        .iter()
+                        .map(|partition| {
+                            found.is_some_and(|found| {
+                                found.partitions.contains_key(&partition.partition_index)
+                            })
+                        })
+                        .collect()
+                })
+                .collect()
+        };
+
+        let offsets = request
+            .topics
+            .iter()
+            .zip(&known)
+            .flat_map(|(topic, known)| {
+                topic
+                    .partitions
+                    .iter()
+                    .zip(known)
+                    .filter(|(_, known)| **known)
+                    .map(|(partition, _)| OffsetCommit {
+                        partition: TopicPartition::new(
+                            topic.name.clone(),
+                            partition.partition_index,
+                        ),
+                        offset: partition.committed_offset,
+                        leader_epoch: partition.committed_leader_epoch,
+                        metadata: partition.committed_metadata.clone(),
+                    })
+            })
+            .collect();
+        let mut results = self
+            .coordinator
+            .commit_offsets(CommitParams {
+                group_id: request.group_id.clone(),
+                generation_id: request.generation_id_or_member_epoch,
+                member_id: request.member_id.clone(),
+                group_instance_id: request.group_instance_id.clone(),
+                offsets,
+            })
+            .map(Vec::into_iter);
+        let topics = request
+            .topics
+            .iter()
+            .zip(known)
+            .map(|(topic, known)| OffsetCommitResponseTopic {
+                name: topic.name.clone(),
+                partitions: topic
+                    .partitions
+                    .iter()
+                    .zip(known)
+                    .map(|(partition, known)| OffsetCommitResponsePartition {
+                        partition_index: partition.partition_index,
+                        error_code: match &mut results {
+                            _ if !known => error::UNKNOWN_TOPIC_OR_PARTITION,
+                            Ok(results) => results.next().unwrap_or(error::UNKNOWN_SERVER_ERROR),
+                            Err(error_code) => *error_code,
+                        },
+                    })
+                    .collect(),
+            })
+            .collect();
+
+        OffsetCommitResponse {
+            header: ResponseHeader {
+                correlation_id: request.header.correlation_id,
+            },
+            api_version: request.header.request_api_version,
+            throttle_time_ms: 0,
+            topics,
+        }
+    }
+
+    fn handle_offset_fetch(&self, request: OffsetFetchRequest) -> OffsetFetchResponse {
+        OffsetFetchResponse {
+            header: ResponseHeader {
+                correlation_id: request.header.correlation_id,
+            },
+            api_version: request.header.request_api_version,
+            throttle_time_ms: 0,
+            groups: request
+                .groups
+                .into_iter()
+                .map(|group| self.fetch_group_offsets(group))
+                .collect(),
+        }
+    }
+
+    /// Committed offsets of one OffsetFetch group. Group errors are repeated
+    /// on each partition for versions without a group error code.
+    fn fetch_group_offsets(&self, group: OffsetFetchRequestGroup) -> OffsetFetchResponseGroup {
+        let partitions: Option<Vec<TopicPartition>> = group.topics.map(|topics| {
+            topics
+                .into_iter()
+                .flat_map(|topic| {
+                    topic
+                        .partition_indexes
+                        .into_iter()
+                        .map(move |partition| TopicPartition::new(topic.name.clone(), partition))
+                })
+                .collect()
+        });
+        let (fetched, error_code) = match self
+            .coordinator
+            .fetch_offsets(&group.group_id, partitions.clone())
+        {
+            Ok(fetched) => (fetched, error::NONE),
+            Err(error_code) => (
+                partitions
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(|tp| (tp, None))
+                    .collect(),
+                error_code,
+            ),
+        };
+
+        let mut topics: Vec<OffsetFetchResponseTopic> = Vec::new();
+        for (tp, offset) in fetched {
+            let partition = OffsetFetchResponsePartition {
+                partition_index: tp.partition,
+                committed_offset: offset.as_ref().map_or(NO_OFFSET, |offset| offset.offset),
+                committed_leader_epoch: offset
+                    .as_ref()
+                    .map_or(NO_LEADER_EPOCH, |offset| offset.leader_epoch),
+                metadata: Some(offset.map(|offset| offset.metadata).unwrap_or_default()),
+                error_code,
+            };
+            match topics.last_mut() {
+                Some(topic) if topic.name == tp.topic => topic.partitions.push(partition),
+                _ => topics.push(OffsetFetchResponseTopic {
+                    name: tp.topic,
+                    partitions: vec![partition],
+                }),
+            }
+        }
+        OffsetFetchResponseGroup {
+            group_id: group.group_id,
+            topics,
+            error_code,
+        }
+    }
+
+    fn handle_describe_groups(&self, request: DescribeGroupsRequest) -> DescribeGroupsResponse {
+        let groups = request
+            .groups
+            .into_iter()
+            .map(|group_id| {
+                // Groups that do not exist are described as dead; consumer
+                // groups are only described by ConsumerGroupDescribe.
+                let (error_code, summary) = match self.coordinator.describe_group(&group_id) {
+                    Some(summary) => (error::NONE, summary),
+                    None if self
+                        .coordinator
+                        .describe_consumer_group(&group_id)
+                        .is_some() =>
+                    {
+                        (error::GROUP_ID_NOT_FOUND, GroupSummary::dead())
+                    }
+                    None => (error::NONE, GroupSummary::dead()),
+                };
+                DescribedGroup {
+                    error_code,
+                    group_id,
+                    group_state: summary.state.name().to_string(),
+                    protocol_type: summary.protocol_type,
+                    protocol_data: summary.protocol_name,
+                    members: summary
+                        .members
+                        .into_iter()
+                        .map(|member| DescribedGroupMember {
+                            member_id: member.member_id,
+                            group_instance_id: member.group_instance_id,
+                            client_id: member.client_id,
+                            client_host: member.client_host,
+                            member_metadata: member.metadata,
+                            member_assignment: member.assignment,
+                        })
+                        .collect(),
+                    authorized_operations: AUTHORIZED_OPERATIONS_OMITTED,
+                }
+            })
+            .collect();
+
+        DescribeGroupsResponse {
+            header: ResponseHeader {
+                correlation_id: request.header.correlation_id,
+            },
+            api_version: request.header.request_api_version,
+            throttle_time_ms: 0,
+            groups,
+        }
+    }
+
+    fn handle_list_groups(&self, request: ListGroupsRequest) -> ListGroupsResponse {
+        let groups = self
+            .coordinator
+            .list_groups(&request.states_filter)
+            .into_iter()
+            .filter(|group| {
+                request.types_filter.is_empty()
+                    || request
+                        .types_filter
+                        .iter()
+                        .any(|group_type| group_type.eq_ignore_ascii_case(group.group_type))
+            })
+            .map(|group| ListedGroup {
+                group_id: group.group_id,
+                protocol_type: group.protocol_type,
+                group_state: group.state.to_string(),
+                group_type: group.group_type.to_string(),
+            })
+            .collect();
+
+        ListGroupsResponse {
+            header: ResponseHeader {
+                correlation_id: request.header.correlation_id,
+            },
+            api_version: request.header.request_api_version,
+            throttle_time_ms: 0,
+            error_code: error::NONE,
+            groups,
+        }
+    }
+
+    fn handle_consumer_group_heartbeat(
+        &self,
+        context: &RequestContext,
+        request: ConsumerGroupHeartbeatRequest,
+    ) -> ConsumerGroupHeartbeatResponse {
+        let topics = self.topic_metadata();
+        // Partitions of topics that no longer exist are not owned anymore.
+        let owned_partitions = request.topic_partitions.map(|owned| {
+            owned
+                .into_iter()
+                .filter_map(|owned| {
+                    let (name, _) = topics
+                        .iter()
+                        .find(|(_, topic)| topic.id == owned.topic_id)?;
+                    Some((name.clone(), owned.partitions))
+                })
+                .flat_map(|(name, partitions)| {
+                    partitions
+                        .into_iter()
+                        .map(move |partition| TopicPartition::new(name.clone(), partition))
+                })
+                .collect()
+        });
+        let result = self.coordinator.consumer_group_heartbeat(
+            ConsumerHeartbeatParams {
+                group_id: request.group_id,
+                member_id: request.member_id,
+                member_epoch: request.member_epoch,
+                instance_id: request.instance_id,
+                rack_id: request.rack_id,
+                client_id: request.header.client_id.unwrap_or_default(),
+                client_host: context.client_host.clone(),
+                rebalance_timeout_ms: request.rebalance_timeout_ms,
+                subscribed_topic_names: request.subscribed_topic_names,
+                subscribed_topic_regex: request.subscribed_topic_regex,
+                server_assignor: request.server_assignor,
+                owned_partitions,
+            },
+            &topics,
+        );
+
+        let mut response = ConsumerGroupHeartbeatResponse {
+            header: ResponseHeader {
+                correlation_id: request.header.correlation_id,
+            },
+            api_version: request.header.request_api_version,
+            throttle_time_ms: 0,
+            error_code: error::NONE,
+            error_message: None,
+            member_id: None,
+            member_epoch: 0,
+            heartbeat_interval_ms: 0,
+            assignment: None,
+        };
+        match result {
+            Ok(result) => {
+                response.member_id = Some(result.member_id);
+                response.member_epoch = result.member_epoch;
+                response.heartbeat_interval_ms = result.heartbeat_interval_ms;
+                response.assignment = result.assignment.map(|assigned| {
+                    partitions_by_topic(&assigned, &topics)
+                        .into_iter()
+                        .map(|topic| ConsumerGroupTopicPartitions {
+                            topic_id: topic.topic_id,
+                            partitions: topic.partitions,
+                        })
+                        .collect()
+                });
+            }
+            Err(error_code) => response.error_code = error_code,
+        }
+        response
+    }
+
+    fn handle_consumer_group_describe(
+        &self,
+        request: ConsumerGroupDescribeRequest,
+    ) -> ConsumerGroupDescribeResponse {
+        let topics = self.topic_metadata();
+        let groups = request
+            .group_ids
+            .into_iter()
+            .map(
+                |group_id| match self.coordinator.describe_consumer_group(&group_id) {
+                    Some(group) => ConsumerGroupDescription {
+                        error_code: error::NONE,
+                        error_message: None,
+                        group_id,
+                        group_state: group.state().name().to_string(),
+                        group_epoch: group.group_epoch,
+                        assignment_epoch: group.assignment_epoch,
+                        assignor_name: group.assignor_name.clone(),
+                        members: group
+                            .members
+                            .values()
+                            .map(|member| ConsumerGroupMemberDescription {
+                                member_id: member.member_id.clone(),
+                                instance_id: member.instance_id.clone(),
+                                rack_id: member.rack_id.clone(),
+                                member_epoch: member.member_epoch,
+                                client_id: member.client_id.clone(),
+                                client_host: member.client_host.clone(),
+                                subscribed_topic_names: member.subscribed_topic_names.clone(),
+                                subscribed_topic_regex: member.subscribed_topic_regex.clone(),
+                                assignment: partitions_by_topic(&member.assigned, &topics),
+                                target_assignment: group
+                                    .target_assignment
+                                    .get(&member.member_id)
+                                    .map(|target| partitions_by_topic(target, &topics))
+                                    .unwrap_or_default(),
+                            })
+                            .collect(),
+                        authorized_operations: AUTHORIZED_OPERATIONS_OMITTED,
+                    },
+                    None => ConsumerGroupDescription {
+                        error_code: error::GROUP_ID_NOT_FOUND,
+                        error_message: Some(format!("group {group_id} is not a consumer group")),
+                        group_id,
+                        group_state: String::new(),
+                        group_epoch: 0,
+                        assignment_epoch: 0,
+                        assignor_name: String::new(),
+                        members: Vec::new(),
+                        authorized_operations: AUTHORIZED_OPERATIONS_OMITTED,
+                    },
+                },
+            )
+            .collect();
+
+        ConsumerGroupDescribeResponse {
+            header: ResponseHeader {
+                correlation_id: request.header.correlation_id,
+            },
+            api_version: request.header.request_api_version,
+            throttle_time_ms: 0,
+            groups,
+        }
+    }
+
+    fn handle_share_group_heartbeat(
+        &self,
+        context: &RequestContext,
+        request: ShareGroupHeartbeatRequest,
+    ) -> ShareGroupHeartbeatResponse {
+        let topics = self.topic_metadata();
+        let result = self.coordinator.share_group_heartbeat(
+            ShareHeartbeatParams {
+                group_id: request.group_id,
+                member_id: request.member_id,
+                member_epoch: request.member_epoch,
+                rack_id: request.rack_id,
+                client_id: request.header.client_id.unwrap_or_default(),
+                client_host: context.client_host.clone(),
+                subscribed_topic_names: request.subscribed_topic_names,
+            },
+            &topics,
+        );
+
+        let mut response = ShareGroupHeartbeatResponse {
+            header: ResponseHeader {
+                correlation_id: request.header.correlation_id,
+            },
+            api_version: request.header.request_api_version,
+            throttle_time_ms: 0,
+            error_code: error::NONE,
+            error_message: None,
+            member_id: None,
+            member_epoch: 0,
+            heartbeat_interval_ms: 0,
+            assignment: None,
+        };
+        match result {
+            Ok(result) => {
+                response.member_id = Some(result.member_id);
+                response.member_epoch = result.member_epoch;
+                response.heartbeat_interval_ms = result.heartbeat_interval_ms;
+                response.assignment = result.assignment.map(|assigned| {
+                    partitions_by_topic(&assigned, &topics)
+                        .into_iter()
+                        .map(|topic| ConsumerGroupTopicPartitions {
+                            topic_id: topic.topic_id,
+                            partitions: topic.partitions,
+                        })
+                        .collect()
+                });
+            }
+            Err(error_code) => response.error_code = error_code,
+        }
+        response
+    }
+
+    /// Acknowledges and acquires records for a share group member. Fetches
+    /// answer at once with whatever is available rather than waiting for
+    /// `max_wait_ms`.
+    fn handle_share_fetch(&self, request: ShareFetchRequest) -> ShareFetchResponse {
+        let topics = self.topic_metadata();
+        let names = topic_names_by_id(&topics);
+        let mut entries: Vec<(Uuid, ShareFetchPartitionData)> = Vec::new();
+        let mut partitions = Vec::new();
+        let mut acknowledgements = Vec::new();
+        for topic in &request.topics {
+            let Some(name) = names.get(&topic.topic_id) else {
+                for partition in &topic.partitions {
+                    entries.push((
+                        topic.topic_id,
+                        share_fetch_partition(partition.partition_index, error::UNKNOWN_TOPIC_ID),
+                    ));
+                }
+                continue;
+            };
+            for partition in &topic.partitions {
+                let tp = TopicPartition::new(name.clone(), partition.partition_index);
+                acknowledgements.extend(share_acknowledgements(
+                    &tp,
+                    &partition.acknowledgement_batches,
+                ));
+                partitions.push(tp);
+            }
+        }
+        let forgotten = request
+            .forgotten_topics
+            .iter()
+            .filter_map(|topic| Some((names.get(&topic.topic_id)?, &topic.partitions)))
+            .flat_map(|(name, partitions)| {
+                partitions
+                    .iter()
+                    .map(|partition| TopicPartition::new(name.clone(), *partition))
+            })
+            .collect();
+        let result = self.coordinator.share_fetch(ShareFetchParams {
+            group_id: request.group_id.unwrap_or_default(),
+            member_id: request.member_id.unwrap_or_default(),
+            share_session_epoch: request.share_session_epoch,
+            partitions,
+            forgotten,
+            acknowledgements,
+            max_bytes: usize::try_from(request.max_bytes).unwrap_or_default(),
+        });
+
+        let mut response = ShareFetchResponse {
+            header: ResponseHeader {
+                correlation_id: request.header.correlation_id,
+            },
+            api_version: request.header.request_api_version,
+            throttle_time_ms: 0,
+            error_code: error::NONE,
+            error_message: None,
+            responses: Vec::new(),
+            node_endpoints: Vec::new(),
+        };
+        let mut result = match result {
+            Ok(result) => result,
+            Err(error_code) => {
+                response.error_code = error_code;
+                return response;
+            }
+        };
+        {
+            let controller = self
+                .controller
+                .lock()
+                .expect("metadata controller lock poisoned");
+            let image = controller.image();
+            for fetched in result.partitions {
+                let topic_id = topics[&fetched.partition.topic].id;
+                let mut data =
+                    share_fetch_partition(fetched.partition.partition, fetched.error_code);
+                data.current_leader = current_leader(image, &fetched.partition);
+                data.acknowledge_error_code = result
+                    .acknowledge_errors
+                    .remove(&fetched.partition)
+                    .unwrap_or(error::NONE);
+                if !fetched.acquired.is_empty() {
+                    data.records = Some(fetched.records);
+                }
+                data.acquired_records = fetched.acquired;
+                entries.push((topic_id, data));
+            }
+            for (tp, error_code) in result.acknowledge_errors {
+                let mut data = share_fetch_partition(tp.partition, error::NONE);
+                data.current_leader = current_leader(image, &tp);
+                data.acknowledge_error_code = error_code;
+                entries.push((topics[&tp.topic].id, data));
+            }
+        }
+        response.responses = group_by_topic_id(entries)
+            .into_iter()
+            .map(|(topic_id, partitions)| ShareFetchTopicResponse {
+                topic_id,
+                partitions,
+            })
+            .collect();
+        response
+    }
+
+    fn handle_share_acknowledge(
+        &self,
+        request: ShareAcknowledgeRequest,
+    ) -> ShareAcknowledgeResponse {
+        let topics = self.topic_metadata();
+        let names = topic_names_by_id(&topics);
+        let mut entries: Vec<(Uuid, ShareAcknowledgePartitionResponse)> = Vec::new();
+        let mut acknowledgements = Vec::new();
+        for topic in &request.topics {
+            let Some(name) = names.get(&topic.topic_id) else {
+                for partition in &topic.partitions {
+                    entries.push((
+                        topic.topic_id,
+                        ShareAcknowledgePartitionResponse {
+                            partition_index: partition.partition_index,
+                            error_code: error::UNKNOWN_TOPIC_ID,
+                            error_message: None,
+                            current_leader: NO_LEADER,
+                        },
+                    ));
+                }
+                continue;
+            };
+            for partition in &topic.partitions {
+                let tp = TopicPartition::new(name.clone(), partition.partition_index);
+                acknowledgements.extend(share_acknowledgements(
+                    &tp,
+                    &partition.acknowledgement_batches,
+                ));
+            }
+        }
+        let result = self.coordinator.share_acknowledge(ShareAcknowledgeParams {
+            group_id: request.group_id.unwrap_or_default(),
+            member_id: request.member_id.unwrap_or_default(),
+            share_session_epoch: request.share_session_epoch,
+            acknowledgements,
+        });
+
+        let mut response = ShareAcknowledgeResponse {
+            header: ResponseHeader {
+                correlation_id: request.header.correlation_id,
+            },
+            api_version: request.header.request_api_version,
+            throttle_time_ms: 0,
+            error_code: error::NONE,
+            error_message: None,
+            responses: Vec::new(),
+            node_endpoints: Vec::new(),
+        };
+        let errors = match result {
+            Ok(errors) => errors,
+            Err(error_code) => {
+                response.error_code = error_code;
+                return response;
+            }
+        };
+        {
+            let controller = self
+                .controller
+                .lock()
+                .expect("metadata controller lock poisoned");
+            let image = controller.image();
+            for (tp, error_code) in errors {
+                entries.push((
+                    topics[&tp.topic].id,
+                    ShareAcknowledgePartitionResponse {
+                        partition_index: tp.partition,
+                        error_code,
+                        error_message: None,
+                        current_leader: current_leader(image, &tp),
+                    },
+                ));
+            }
+        }
+        response.responses = group_by_topic_id(entries)
+            .into_iter()
+            .map(|(topic_id, partitions)| ShareAcknowledgeTopicResponse {
+                topic_id,
+                partitions,
+            })
+            .collect();
+        response
+    }
+
+    /// Id and partition count of every topic, taken under the controller
+    /// lock so the coordinator can be called without it.
+    fn topic_metadata(&self) -> BTreeMap<String, TopicMetadata> {
+        let controller = self
+            .controller
+            .lock()
+            .expect("metadata controller lock poisoned");
+        controller
+            .image()
+            .topics()
+            .map(|topic| {
+                let metadata = TopicMetadata {
+                    id: topic.id,
+                    partitions: topic.partitions.len() as i32,
+                };
+                (topic.name.clone(), metadata)
+            })
+            .collect()
+    }
+
+    fn handle_delete_groups(&self, request: DeleteGroupsRequest) -> DeleteGroupsResponse {
+        let results = self.coordinator.delete_groups(&request.groups_names);
+
+        DeleteGroupsResponse {
+            header: ResponseHeader {
+                correlation_id: request.header.correlation_id,
+            },
+            api_version: request.header.request_api_version,
+            throttle_time_ms: 0,
+            results: request
+                .groups_names
+                .into_iter()
+                .zip(results)
+                .map(|(group_id, error_code)| DeletableGroupResult {
+                    group_id,
+                    error_code,
+                })
+                .collect(),
+        }
+    }
+
+    fn handle_offset_delete(&self, request: OffsetDeleteRequest) -> OffsetDeleteResponse {
+        let known: Vec<Vec<bool>> = {
+            let controller = self
+                .controller
+                .lock()
+                .expect("metadata controller lock poisoned");
+            let image = controller.image();
+            request
+                .topics
+                .iter()
+                .map(|topic| {
+                    let found = image.topic(&topic.name);
+                    topic
+                        .partitions
+                        .iter()
+                        .map(|partition| {
+                            found.is_some_and(|found| found.partitions.contains_key(partition))
+                        })
+                        .collect()
+                })
+                .collect()
+        };
+
+        let partitions = request
+            .topics
+            .iter()
+            .zip(&known)
+            .flat_map(|(topic, known)| {
+                topic
+                    .partitions
+                    .iter()
+                    .zip(known)
+                    .filter(|(_, known)| **known)
+                    .map(|(partition, _)| TopicPartition::new(topic.name.clone(), *partition))
+            })
+            .collect();
+        let (error_code, topics) = match self
+            .coordinator
+            .delete_offsets(&request.group_id, partitions)
+        {
+            Ok(results) => {
+                let mut results = results.into_iter();
+                let topics = request
+                    .topics
+                    .into_iter()
+                    .zip(known)
+                    .map(|(topic, known)| OffsetDeleteResponseTopic {
+                        name: topic.name,
+                        partitions: topic
+                            .partitions
+                            .into_iter()
+                            .zip(known)
+                            .map(|(partition_index, known)| OffsetDeleteResponsePartition {
+                                partition_index,
+                                error_code: if known {
+                                    results.next().unwrap_or(error::UNKNOWN_SERVER_ERROR)
+                                } else {
+                                    error::UNKNOWN_TOPIC_OR_PARTITION
+                                },
+                            })
+                            .collect(),
+                    })
+                    .collect();
+                (error::NONE, topics)
+            }
+            Err(error_code) => (error_code, Vec::new()),
+        };
+
+        OffsetDeleteResponse {
+            header: ResponseHeader {
+                correlation_id: request.header.correlation_id,
+            },
+            api_version: request.header.request_api_version,
+            error_code,
+            throttle_time_ms: 0,
+            topics,
+        }
+    }
+
+    /// Gives idempotent producers a fresh producer id at epoch 0. A producer
+    /// re-initializing with its current id also gets a new one, as in Kafka.
+    fn handle_init_producer_id(&self, request: InitProducerIdRequest) -> InitProducerIdResponse {
+        let mut response = InitProducerIdResponse {
+            header: ResponseHeader {
+                correlation_id: request.header.correlation_id,
+            },
+            api_version: request.header.request_api_version,
+            throttle_time_ms: 0,
+            error_code: error::NONE,
+            producer_id: NO_PRODUCER_ID,
+            producer_epoch: NO_PRODUCER_EPOCH,
+        };
+        if request.transactional_id.is_some() {
+            // Matches FindCoordinator, which has no transaction coordinator to offer.
+            response.error_code = error::COORDINATOR_NOT_AVAILABLE;
+            return response;
+        }
+
+        match self.next_producer_id() {
+            Ok(producer_id) => {
+                response.producer_id = producer_id;
+                response.producer_epoch = 0;
+            }
+            Err(err) => {
+                eprintln!("cannot allocate a producer id: {err}");
+                response.error_code = error::UNKNOWN_SERVER_ERROR;
+            }
+        }
+        response
+    }
+
+    /// Takes the next reserved producer id, reserving a new block through the
+    /// metadata controller when the current one is used up.
+    fn next_producer_id(&self) -> io::Result<i64> {
+        let mut producer_ids = self.producer_ids.lock().expect("producer id lock poisoned");
+        if producer_ids.is_empty() {
+            let mut controller = self
+                .controller
+                .lock()
+                .expect("metadata controller lock poisoned");
+            let mut logs = self.logs.lock().expect("log manager lock poisoned");
+            *producer_ids = controller.allocate_producer_ids(&mut logs)?;
+        }
+        Ok(producer_ids.next().expect("block is not empty"))
+    }
+}
+
+/// Appends one Produce partition's batches in order, stopping at the first
+/// rejected batch. A duplicate batch is acknowledged with the offset it was
+/// first written at, as Kafka does, so a retrying producer moves on.
+fn produce_partition(
+    logs: &mut LogManager,
+    tp: &TopicPartition,
+    records: &[u8],
+) -> PartitionProduceResponse {
+    let batches = match batch::split_batches(records) {
+        Ok(batches) if !batches.is_empty() => batches,
+        Ok(_) => {
+            let message = "no record batches to append".to_string();
+            return produce_error(tp.partition, error::CORRUPT_MESSAGE, Some(message));
+        }
+        Err(err) => {
+            return produce_error(tp.partition, error::CORRUPT_MESSAGE, Some(err.to_string()));
+        }
+    };
+    // Every batch is checked before any is appended, so a corrupt batch
+    // rejects the whole partition's records.
+    if let Err(err) = batches
+        .iter()
+        .try_for_each(|batch| batch::validate(batch).map(drop))
+    {
+        return produce_error(tp.partition, error::CORRUPT_MESSAGE, Some(err.to_string()));
+    }
+    let mut response = produce_error(tp.partition, error::NONE, None);
+    for batch in batches {
+        let first_offset = match logs.append(tp, batch) {
+            Ok(info) => info.first_offset,
+            Err(err) => match SequenceError::from_io_error(&err) {
+                Some(SequenceError::Duplicate(found)) => found.first_offset(),
+                Some(sequence_error) => {
+                    response.error_code = sequence_error.error_code();
+                    response.error_message = Some(sequence_error.to_string());
+                    break;
+                }
+                None if err.kind() == io::ErrorKind::InvalidData => {
+                    response.error_code = error::CORRUPT_MESSAGE;
+                    response.error_message = Some(err.to_string());
+                    break;
+                }
+                None => {
+                    eprintln!("cannot append to {tp}: {err}");
+                    response.error_code = error::KAFKA_STORAGE_ERROR;
+                    break;
+                }
+            },
+        };
+        if response.base_offset == UNKNOWN_OFFSET {
+            response.base_offset = first_offset;
+        }
+    }
+    if response.error_code != error::NONE {
+        response.base_offset = UNKNOWN_OFFSET;
+    }
+    if let Some(log) = logs.get_log(tp) {
+        response.log_start_offset = log.log_start_offset();
+    }
+    response
+}
+
+fn produce_error(
+    index: i32,
+    error_code: i16,
+    error_message: Option<String>,
+) -> PartitionProduceResponse {
+    PartitionProduceResponse {
+        index,
+        error_code,
+        base_offset: UNKNOWN_OFFSET,
+        log_append_time_ms: NO_LOG_APPEND_TIME,
+        log_start_offset: UNKNOWN_OFFSET,
+        error_message,
+    }
+}
+
+/// Resolves one ListOffsets partition against its log.
+fn list_offset(
+    log: &Log,
+    request: &ListOffsetsRequest,
+    partition: &ListOffsetsRequestPartition,
+    leader_epoch: i32,
+) -> ListOffsetsResponsePartition {
+    // Followers may see the whole log; consumers only what their isolation
+    // level lets them fetch.
+    let fetchable_offset = if request.replica_id >= 0 {
+        log.log_end_offset()
+    } else if request.isolation_level == READ_COMMITTED {
+        log.last_stable_offset()
+    } else {
+        log.high_watermark()
+    };
+    match find_offset(log, partition.timestamp, fetchable_offset) {
+        Ok(found) => ListOffsetsResponsePartition {
+            partition_index: partition.partition_index,
+            error_code: error::NONE,
+            timestamp: found.map_or(UNKNOWN_TIMESTAMP, |found| found.timestamp),
+            offset: found.map_or(UNKNOWN_OFFSET, |found| found.offset),
+            leader_epoch: if found.is_some() {
+                leader_epoch
+            } else {
+                UNKNOWN_LEADER_EPOCH
+            },
+        },
+        Err(err) => {
+            eprintln!(
+                "cannot look up timestamp {} in {}: {err}",
+                partition.timestamp,
+                log.dir().display()
+            );
+            list_offset_error(partition, error::KAFKA_STORAGE_ERROR)
+        }
+    }
+}
+
+/// Maps a ListOffsets timestamp to an offset: the special timestamps name a
+/// position in the log, and any other value finds the first record at or
+/// after it. Results at or past `fetchable_offset` are not reported.
+fn find_offset(
+    log: &Log,
+    timestamp: i64,
+    fetchable_offset: i64,
+) -> io::Result<Option<TimestampOffset>> {
+    let at = |offset| TimestampOffset {
+        timestamp: UNKNOWN_TIMESTAMP,
+        offset,
+    };
+    let found = match timestamp {
+        LATEST_TIMESTAMP => return Ok(Some(at(fetchable_offset))),
+        // Without tiered storage every segment is local.
+        EARLIEST_TIMESTAMP | EARLIEST_LOCAL_TIMESTAMP => {
+            return Ok(Some(at(log.log_start_offset())))
+        }
+        MAX_TIMESTAMP => log.max_timestamp_offset(),
+        timestamp => log.offset_for_timestamp(timestamp)?,
+    };
+    Ok(found.filter(|found| found.offset < fetchable_offset))
+}
+
+fn list_offset_error(
+    partition: &ListOffsetsRequestPartition,
+    error_code: i16,
+) -> ListOffsetsResponsePartition {
+    ListOffsetsResponsePartition {
+        partition_index: partition.partition_index,
+        error_code,
+        timestamp: UNKNOWN_TIMESTAMP,
+        offset: UNKNOWN_OFFSET,
+        leader_epoch: UNKNOWN_LEADER_EPOCH,
+    }
+}
+
+fn describe_requested_topic(
+    image: &MetadataImage,
+    requested: &MetadataRequestTopic,
+) -> MetadataResponseTopic {
+    let found = match &requested.name {
+        Some(name) => image.topic(name),
+        None => image.topic_by_id(&requested.topic_id),
+    };
+    if let Some(topic) = found {
+        return describe_topic(topic);
+    }
+
+    MetadataResponseTopic {
+        error_code: if requested.name.is_some() {
+            error::UNKNOWN_TOPIC_OR_PARTITION
+        } else {
+            error::UNKNOWN_TOPIC_ID
+        },
+        name: requested.name.clone(),
+        topic_id: requested.topic_id,
+        is_internal: false,
+        partitions: Vec::new(),
+        topic_authorized_operations: AUTHORIZED_OPERATIONS_OMITTED,
+    }
+}
+
+fn describe_topic(topic: &TopicImage) -> MetadataResponseTopic {
+    MetadataResponseTopic {
+        error_code: error::NONE,
+        name: Some(topic.name.clone()),
+        topic_id: topic.id,
+        is_internal: topic.name == OFFSETS_TOPIC,
+        partitions: topic
+            .partitions
+            .iter()
+            .map(|(&partition_index, partition)| MetadataResponsePartition {
+                error_code: error::NONE,
+                partition_index,
+                leader_id: partition.leader,
+                leader_epoch: partition.leader_epoch,
+                replica_nodes: partition.replicas.clone(),
+                isr_nodes: partition.isr.clone(),
+                offline_replicas: Vec::new(),
+            })
+            .collect(),
+        topic_authorized_operations: AUTHORIZED_OPERATIONS_OMITTED,
+    }
+}
+
+/// Checks one CreateTopics entry and, unless `validate_only`, creates it,
+/// returning the topic id and partition count or an error code and message.
+/// Every replica must be placed on this broker.
+fn create_topic(
+    controller: &mut MetadataController,
+    logs: &mut LogManager,
+    node_id: i32,
+    topic: &CreatableTopic,
+    validate_only: bool,
+) -> Result<(Uuid, i32), (i16, String)> {
+    let name = topic.name.as_str();
+    controller::validate_topic_name(name)
+        .map_err(|err| (error::INVALID_TOPIC_EXCEPTION, err.to_string()))?;
+    if controller.image().topic(name).is_some() {
+        return Err((
+            error::TOPIC_ALREADY_EXISTS,
+            format!("topic '{name}' already exists"),
+        ));
+    }
+    let replication_factor = i32::from(topic.replication_factor);
+    if replication_factor != DEFAULT_SETTING && replication_factor != 1 {
+        return Err((
+            error::INVALID_REPLICATION_FACTOR,
+            format!(
+                "replication factor {replication_factor} is larger than the 1 available broker"
+            ),
+        ));
+    }
+
+    let partitions = if !topic.assignments.is_empty() {
+        if topic.num_partitions != DEFAULT_SETTING || replication_factor != DEFAULT_SETTING {
+            return Err((
+                error::INVALID_REQUEST,
+                "assignments cannot be combined with a partition count or replication factor"
+                    .to_string(),
+            ));
+        }
+        let placed_here = topic.assignments.iter().all(|assignment| {
+            (0..topic.assignments.len() as i32).contains(&assignment.partition_index)
+                && assignment.broker_ids == [node_id]
+        });
+        let indexes: BTreeSet<i32> = topic
+            .assignments
+            .iter()
+            .map(|assignment| assignment.partition_index)
+            .collect();
+        if !placed_here || indexes.len() != topic.assignments.len() {
+            return Err((
+                error::INVALID_REPLICA_ASSIGNMENT,
+                format!("partitions must be numbered from 0 and placed on broker {node_id}"),
+            ));
+        }
+        topic.assignments.len() as i32
+    } else if topic.num_partitions == DEFAULT_SETTING {
+        controller.default_partitions()
+    } else if topic.num_partitions <= 0 {
+        return Err((
+            error::INVALID_PARTITIONS,
+            format!("topic '{name}' needs at least one partition"),
+        ));
+    } else {
+        topic.num_partitions
+    };
+
+    let mut configs = BTreeMap::new();
+    for config in &topic.configs {
+        let Some(value) = &config.value else {
+            return Err((
+                error::INVALID_CONFIG,
+                format!("config '{}' has no value", config.name),
+            ));
+        };
+        configs.insert(config.name.clone(), value.clone());
+    }
+    logs.default_config()
+        .with_overrides(&configs)
+        .map_err(|err| (error::INVALID_CONFIG, err.to_string()))?;
+
+    if validate_only {
+        return Ok((Uuid::ZERO, partitions));
+    }
+    controller
+        .create_topic(logs, name, partitions, &configs)
+        .map(|topic_id| (topic_id, partitions))
+        .map_err(|err| (error::UNKNOWN_SERVER_ERROR, err.to_string()))
+}
+
+/// Deletes one DeleteTopics entry, returning its name and id or an error
+/// code and message.
+fn delete_topic(
+    controller: &mut MetadataController,
+    logs: &mut LogManager,
+    topic: &DeleteTopicState,
+) -> Result<(String, Uuid), (i16, String)> {
+    let image = controller.image();
+    let found = match (&topic.name, topic.topic_id) {
+        (Some(_), topic_id) if topic_id != Uuid::ZERO => {
+            return Err((
+                error::INVALID_REQUEST,
+                "topic is given by both name and id".to_string(),
+            ));
+        }
+        (Some(name), _) => image.topic(name).ok_or_else(|| {
+            (
+                error::UNKNOWN_TOPIC_OR_PARTITION,
+                format!("topic '{name}' does not exist"),
+            )
+        })?,
+        (None, topic_id) => image.topic_by_id(&topic_id).ok_or_else(|| {
+            (
+                error::UNKNOWN_TOPIC_ID,
+                format!("topic id {topic_id} does not exist"),
+            )
+        })?,
+    };
+    let (name, topic_id) = (found.name.clone(), found.id);
+    if name == OFFSETS_TOPIC {
+        return Err((
+            error::INVALID_REQUEST,
+            format!("internal topic '{name}' cannot be deleted"),
+        ));
+    }
+    controller
+        .delete_topic(logs, &name)
+        .map_err(|err| (error::UNKNOWN_SERVER_ERROR, err.to_string()))?;
+    Ok((name, topic_id))
+}
+
+/// Checks the leader epoch a client fetched with against the current one;
+/// an unknown epoch skips the check.
+fn leader_epoch_error(requested: i32, current: i32) -> i16 {
+    if requested == UNKNOWN_LEADER_EPOCH || requested == current {
+        error::NONE
+    } else if requested < current {
+        error::FENCED_LEADER_EPOCH
+    } else {
+        error::UNKNOWN_LEADER_EPOCH
+    }
+}
+
+/// Adds a partition to the response, after the previous one if it belongs
+/// to the same topic.
+fn push_fetched(
+    responses: &mut Vec<FetchableTopicResponse>,
+    name: Option<String>,
+    topic_id: Uuid,
+    data: FetchPartitionData,
+) {
+    match responses.last_mut() {
+        Some(last) if last.name == name && last.topic_id == topic_id => last.partitions.push(data),
+        _ => responses.push(FetchableTopicResponse {
+            name,
+            topic_id,
+            partitions: vec![data],
+        }),
+    }
+}
+
+/// Leader reported for partitions the broker does not know.
+const NO_LEADER: LeaderIdAndEpoch = LeaderIdAndEpoch {
+    leader_id: -1,
+    leader_epoch: -1,
+};
+
+fn topic_names_by_id(topics: &BTreeMap<String, TopicMetadata>) -> BTreeMap<Uuid, String> {
+    topics
+        .iter()
+        .map(|(name, topic)| (topic.id, name.clone()))
+        .collect()
+}
+
+fn current_leader(image: &MetadataImage, tp: &TopicPartition) -> LeaderIdAndEpoch {
+    image
+        .topic(&tp.topic)
+        .and_then(|topic| topic.partitions.get(&tp.partition))
+        .map_or(NO_LEADER, |partition| LeaderIdAndEpoch {
+            leader_id: partition.leader,
+            leader_epoch: partition.leader_epoch,
+        })
+}
+
+fn share_fetch_partition(partition_index: i32, error_code: i16) -> ShareFetchPartitionData {
+    ShareFetchPartitionData {
+        partition_index,
+        error_code,
+        error_message: None,
+        acknowledge_error_code: error::NONE,
+        acknowledge_error_message: None,
+        current_leader: NO_LEADER,
+        records: None,
+        acquired_records: Vec::new(),
+    }
+}
+
+fn share_acknowledgements(
+    tp: &TopicPartition,
+    batches: &[AcknowledgementBatch],
+) -> Vec<ShareAcknowledgement> {
+    batches
+        .iter()
+        .map(|batch| ShareAcknowledgement {
+            partition: tp.clone(),
+            first_offset: batch.first_offset,
+            last_offset: batch.last_offset,
+            acknowledge_types: batch.acknowledge_types.clone(),
+        })
+        .collect()
+}
+
+/// Collects per-partition entries under their topic ids, in id order.
+fn group_by_topic_id<T>(entries: Vec<(Uuid, T)>) -> BTreeMap<Uuid, Vec<T>> {
+    let mut grouped: BTreeMap<Uuid, Vec<T>> = BTreeMap::new();
+    for (topic_id, entry) in entries {
+        grouped.entry(topic_id).or_default().push(entry);
+    }
+    grouped
+}
+
+/// Groups `partitions` by topic, leaving out topics that no longer exist.
+fn partitions_by_topic(
+    partitions: &BTreeSet<TopicPartition>,
+    topics: &BTreeMap<String, TopicMetadata>,
+) -> Vec<DescribedTopicPartitions> {
+    let mut grouped: Vec<DescribedTopicPartitions> = Vec::new();
+    for tp in partitions {
+        let Some(topic) = topics.get(&tp.topic) else {
+            continue;
+        };
+        match grouped.last_mut() {
+            Some(last) if last.topic_name == tp.topic => last.partitions.push(tp.partition),
+            _ => grouped.push(DescribedTopicPartitions {
+                topic_id: topic.id,
+                topic_name: tp.topic.clone(),
+                partitions: vec![tp.partition],
+            }),
+        }
+    }
+    grouped
+}
+
+/// Most partitions described in one DescribeTopicPartitions response, as the
+/// default `max.request.partition.size.limit`.
+const MAX_DESCRIBED_PARTITIONS: usize = 2_000;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metadata::controller::PRODUCER_ID_BLOCK_SIZE;
+    use crate::metadata::records::{PartitionRecord, TopicRecord};
+    use crate::metadata::MetadataRecord;
+    use crate::protocol::create_topics::CreatableTopicConfig;
+    use crate::protocol::describe_topic_partitions::DESCRIBE_TOPIC_PARTITIONS_KEY;
+    use crate::protocol::fetch::{
+        FetchPartition, FetchTopic, ForgottenTopic, CONSUMER_REPLICA_ID, FETCH_KEY,
+        READ_UNCOMMITTED,
+    };
+    use crate::protocol::leave_group::LeavingMember;
+    use crate::protocol::list_offsets::ListOffsetsRequestTopic;
+    use crate::protocol::offset_commit::{
+        OffsetCommitRequestPartition, OffsetCommitRequestTopic, NO_GENERATION,
+    };
+    use crate::protocol::offset_fetch::OffsetFetchRequestTopic;
+    use crate::protocol::produce::{PartitionProduceData, TopicProduceData, PRODUCE_KEY};
+    use crate::protocol::share_acknowledge::{ShareAcknowledgePartition, ShareAcknowledgeTopic};
+    use crate::protocol::share_fetch::{acknowledge_type, ShareFetchPartition, ShareFetchTopic};
+    use crate::protocol::RequestHeader;
+    use crate::state::GroupConfig;
+    use crate::storage::batch::{self, BatchHeader, RecordBatchBuilder};
+    use crate::storage::LogConfig;
+    use crate::time::SystemClock;
+    use std::thread;
+    use std::time::Duration;
+
+    /// A handler whose image holds `orders` with one partition and
+    /// `payments` with two, led by node 4.
+    fn handler_with_logs(logs: LogManager) -> RequestHandler {
+        let mut image = MetadataImage::default();
+        let topics = [("orders", Uuid([2; 16]), 1), ("payments", Uuid([3; 16]), 2)];
+        let mut offset = 0;
+        for (name, topic_id, partitions) in topics {
+            image.apply(
+                offset,
+                &MetadataRecord::Topic(TopicRecord {
+                    name: name.to_string(),
+                    topic_id,
+                }),
+            );
+            for partition_id in 0..partitions {
+                offset += 1;
+                image.apply(
+                    offset,
+                    &MetadataRecord::Partition(PartitionRecord {
+                        partition_id,
+                        topic_id,
+                        replicas: vec![4],
+                        isr: vec![4],
+                        removing_replicas: Vec::new(),
+                        adding_replicas: Vec::new(),
+                        leader: 4,
+                        leader_recovery_state: 0,
+                        leader_epoch: 6,
+                        partition_epoch: 0,
+                        directories: Vec::new(),
+                    }),
+                );
+            }
+            offset += 1;
+        }
+        let controller = MetadataController::new(image, 4, SystemClock::shared());
+        let logs = Arc::new(Mutex::new(logs));
+        RequestHandler::new(
+            BrokerInfo {
+                cluster_id: "MkU3OEVBNTcwNTJENDM2Qg".to_string(),
+                node_id: 4,
+                endpoints: vec![
+                    BrokerEndpoint {
+                        listener_name: "INTERNAL".to_string(),
+                        host: "localhost".to_string(),
+                        port: 9092,
+                    },
+                    BrokerEndpoint {
+                        listener_name: "EXTERNAL".to_string(),
+                        host: "broker.example.com".to_string(),
+                        port: 19092,
+                    },
+                ],
+            },
+            Arc::new(Mutex::new(controller)),
+            Arc::clone(&logs),
+            GroupCoordinator::new(GroupConfig::default(), logs, SystemClock::shared()),
+            SystemClock::shared(),
+        )
+    }
+
+    fn handler() -> RequestHandler {
+        // Tests using this handler serve no partitions, so the directory may go.
+        let dir = tempfile::tempdir().expect("tempdir");
+        handler_with_logs(
+            LogManager::load(dir.path(), LogConfig::default(), SystemClock::shared())
+                .expect("load logs"),
+        )
+    }
+
+    fn context(listener_name: &str) -> RequestContext {
+        RequestContext {
+            listener_name: listener_name.to_string(),
+            client_host: "/127.0.0.1".to_string(),
+        }
+    }
+
+    fn header(api_key: i16, api_version: i16) -> RequestHeader {
+        RequestHeader {
+            request_api_key: api_key,
+            request_api_version: api_version,
+            correlation_id: 11,
+            client_id: None,
+        }
+    }
+
+    #[test]
+    fn metadata_reports_cluster_identity_and_topics() {
+        let request = MetadataRequest {
+            header: header(3, 12),
+            topics: Some(vec![
+                MetadataRequestTopic {
+                    topic_id: Uuid::ZERO,
+                    name: Some("orders".to_string()),
+                },
+                MetadataRequestTopic {
+                    topic_id: Uuid::ZERO,
+                    name: Some("missing".to_string()),
+                },
+                MetadataRequestTopic {
+                    topic_id: Uuid([9; 16]),
+                    name: None,
+                },
+            ]),
+            allow_auto_topic_creation: false,
+            include_topic_authorized_operations: false,
+        };
+
+        let Response::Metadata(response) =
+            handler().handle(&context("INTERNAL"), Request::Metadata(request))
+        else {
+            panic!("expected a metadata response");
+        };
+
+        assert_eq!(
+            response.cluster_id.as_deref(),
+            Some("MkU3OEVBNTcwNTJENDM2Qg")
+        );
+        assert_eq!(response.controller_id, 4);
+        assert_eq!(response.brokers[0].node_id, 4);
+        assert_eq!(response.brokers[0].port, 9092);
+        let errors: Vec<_> = response
+            .topics
+            .iter()
+            .map(|topic| topic.error_code)
+            .collect();
+        assert_eq!(
+            errors,
+            [
+                error::NONE,
+                error::UNKNOWN_TOPIC_OR_PARTITION,
+                error::UNKNOWN_TOPIC_ID
+            ]
+        );
+        let partition = &response.topics[0].partitions[0];
+        assert_eq!((partition.leader_id, partition.leader_epoch), (4, 6));
+    }
+
+    #[test]
+    fn describe_cluster_rejects_controller_endpoint_type() {
+        let handler = handler();
+        let request = |endpoint_type| {
+            Request::DescribeCluster(DescribeClusterRequest {
+                header: header(60, 1),
+                include_cluster_authorized_operations: false,
+                endpoint_type,
+            })
+        };
+
+        let Response::DescribeCluster(response) =
+            handler.handle(&context("INTERNAL"), request(BROKER_ENDPOINT_TYPE))
+        else {
+            panic!("expected a describe cluster response");
+        };
+        assert_eq!(response.error_code, error::NONE);
+        assert_eq!(response.cluster_id, "MkU3OEVBNTcwNTJENDM2Qg");
+        assert_eq!(response.brokers[0].broker_id, 4);
+
+        let Response::DescribeCluster(response) = handler.handle(&context("INTERNAL"), request(2))
+        else {
+            panic!("expected a describe cluster response");
+        };
+        assert_eq!(response.error_code, error::MISMATCHED_ENDPOINT_TYPE);
+        assert!(response.brokers.is_empty());
+    }
+    #[test]
+    fn describe_topic_partitions_pages_through_partitions_with_a_cursor() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let handler = handler_with_logs(
+            LogManager::load(dir.path(), LogConfig::default(), SystemClock::shared())
+                .expect("load logs"),
+        );
+        let describe = |topics: &[&str], cursor| {
+            let request = DescribeTopicPartitionsRequest {
+                header: header(DESCRIBE_TOPIC_PARTITIONS_KEY, 0),
+                topics: topics.iter().map(|topic| topic.to_string()).collect(),
+                response_partition_limit: 2,
+                cursor,
+            };
+            let Response::DescribeTopicPartitions(response) = handler.handle(
+                &context("INTERNAL"),
+                Request::DescribeTopicPartitions(request),
+            ) else {
+                panic!("expected a describe topic partitions response");
+            };
+            let described: Vec<(Option<String>, i16, Vec<i32>)> = response
+                .topics
+                .iter()
+                .map(|topic| {
+                    let partitions = topic
+                        .partitions
+                        .iter()
+                        .map(|partition| partition.partition_index)
+                        .collect();
+                    (topic.name.clone(), topic.error_code, partitions)
+                })
+                .collect();
+            (described, response.next_cursor)
+        };
+
+        let (described, next_cursor) = describe(&[], None);
+        assert_eq!(
+            described,
+            [
+                (Some("orders".to_string()), error::NONE, vec![0]),
+                (Some("payments".to_string()), error::NONE, vec![0]),
+            ]
+        );
+        let cursor = TopicPartitionCursor {
+            topic_name: "payments".to_string(),
+            partition_index: 1,
+        };
+        assert_eq!(next_cursor, Some(cursor.clone()));
+
+        let (described, next_cursor) = describe(&["payments", "missing"], Some(cursor));
+        assert_eq!(
+            described,
+            [(Some("payments".to_string()), error::NONE, vec![1])]
+        );
+        assert_eq!(next_cursor, None);
+
+        let (described, _) = describe(&["missing"], None);
+        assert_eq!(
+            described,
+            [(
+                Some("missing".to_string()),
+                error::UNKNOWN_TOPIC_OR_PARTITION,
+                Vec::new()
+            )]
+        );
+    }
+
+    #[test]
+    fn fetch_reads_batches_by_topic_name_or_id() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let mut logs = LogManager::load(dir.path(), LogConfig::default(), SystemClock::shared())
+            .expect("load logs");
+        let orders = TopicPartition::new("orders", 0);
+        for value in [b"a", b"b", b"c"] {
+            let batch = RecordBatchBuilder::new(0)
+                .record(1_000, None, Some(value))
+                .build();
+            logs.append(&orders, &batch).expect("append");
+        }
+        let handler = handler_with_logs(logs);
+        let fetch = |version, topics| {
+            let request = FetchRequest {
+                header: header(FETCH_KEY, version),
+                replica_id: CONSUMER_REPLICA_ID,
+                max_wait_ms: 0,
+                min_bytes: 1,
+                max_bytes: 1 << 20,
+                isolation_level: READ_UNCOMMITTED,
+                session_id: INVALID_SESSION_ID,
+                session_epoch: -1,
+                topics,
+                forgotten_topics_data: Vec::new(),
+                rack_id: String::new(),
+            };
+            let Response::Fetch(response) =
+                handler.handle(&context("INTERNAL"), Request::Fetch(request))
+            else {
+                panic!("expected a fetch response");
+            };
+            response
+        };
+        let partition = |fetch_offset, current_leader_epoch| FetchPartition {
+            partition: 0,
+            current_leader_epoch,
+            fetch_offset,
+            last_fetched_epoch: -1,
+            log_start_offset: -1,
+            partition_max_bytes: 1 << 20,
+        };
+
+        let response = fetch(
+            12,
+            vec![FetchTopic {
+                name: Some("orders".to_string()),
+                topic_id: Uuid::ZERO,
+                partitions: vec![partition(1, 6)],
+            }],
+        );
+        let data = &response.responses[0].partitions[0];
+        assert_eq!(response.responses[0].name.as_deref(), Some("orders"));
+        assert_eq!((data.error_code, data.high_watermark), (error::NONE, 3));
+        let records = data.records.as_deref().expect("records");
+        let offsets: Vec<i64> = batch::split_batches(records)
+            .expect("split")
+            .into_iter()
+            .map(|batch| BatchHeader::parse(batch).expect("parse").base_offset)
+            .collect();
+        assert_eq!(offsets, [1, 2]);
+
+        let response = fetch(
+            13,
+            vec![
+                FetchTopic {
+                    name: None,
+                    topic_id: Uuid([2; 16]),
+                    partitions: vec![partition(4, -1)],
+                },
+                FetchTopic {
+                    name: None,
+                    topic_id: Uuid([9; 16]),
+                    partitions: vec![partition(0, -1)],
+                },
+            ],
+        );
+        let errors: Vec<(Uuid, i16)> = response
+            .responses
+            .iter()
+            .flat_map(|topic| {
+                topic
+                    .partitions
+                    .iter()
+                    .map(|partition| (topic.topic_id, partition.error_code))
+            })
+            .collect();
+        assert_eq!(
+            errors,
+            [
+                (Uuid([2; 16]), error::OFFSET_OUT_OF_RANGE),
+                (Uuid([9; 16]), error::UNKNOWN_TOPIC_ID),
+            ]
+        );
+
+        let response = fetch(
+            13,
+            vec![FetchTopic {
+                name: None,
+                topic_id: Uuid([2; 16]),
+                partitions: vec![partition(0, 5)],
+            }],
+        );
+        assert_eq!(
+            response.responses[0].partitions[0].error_code,
+            error::FENCED_LEADER_EPOCH
+        );
+    }
+
+    #[test]
+    fn incremental_fetches_only_return_changed_partitions() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let mut logs = LogManager::load(dir.path(), LogConfig::default(), SystemClock::shared())
+            .expect("load logs");
+        let orders = TopicPartition::new("orders", 0);
+        let batch = RecordBatchBuilder::new(0)
+            .record(1_000, None, Some(b"a"))
+            .build();
+        logs.append(&orders, &batch).expect("append");
+        let handler = handler_with_logs(logs);
+        let fetch = |session_id, session_epoch, fetch_offset: Option<i64>, forgotten: bool| {
+            let topic_id = Uuid([2; 16]);
+            let request = FetchRequest {
+                header: header(FETCH_KEY, 13),
+                replica_id: CONSUMER_REPLICA_ID,
+                max_wait_ms: 0,
+                min_bytes: 1,
+                max_bytes: 1 << 20,
+                isolation_level: READ_UNCOMMITTED,
+                session_id,
+                session_epoch,
+                topics: fetch_offset
+                    .map(|fetch_offset| FetchTopic {
+                        name: None,
+                        topic_id,
+                        partitions: vec![FetchPartition {
+                            partition: 0,
+                            current_leader_epoch: 6,
+                            fetch_offset,
+                            last_fetched_epoch: -1,
+                            log_start_offset: -1,
+                            partition_max_bytes: 1 << 20,
+                        }],
+                    })
+                    .into_iter()
+                    .collect(),
+                forgotten_topics_data: if forgotten {
+                    vec![ForgottenTopic {
+                        name: None,
+                        topic_id,
+                        partitions: vec![0],
+                    }]
+                } else {
+                    Vec::new()
+                },
+                rack_id: String::new(),
+            };
+            let Response::Fetch(response) =
+                handler.handle(&context("INTERNAL"), Request::Fetch(request))
+            else {
+                panic!("expected a fetch response");
+            };
+            let fetched: Vec<(i64, usize)> = response
+                .responses
+                .iter()
+                .flat_map(|topic| &topic.partitions)
+                .map(|partition| {
+                    let records = partition.records.as_ref().map_or(0, Vec::len);
+                    (partition.high_watermark, records)
+                })
+                .collect();
+            (response.error_code, response.session_id, fetched)
+        };
+
+        let (error_code, session_id, fetched) = fetch(INVALID_SESSION_ID, 0, Some(0), false);
+        assert_eq!(error_code, error::NONE);
+        assert_ne!(session_id, INVALID_SESSION_ID);
+        assert_eq!(fetched, [(1, batch.len())]);
+
+        // Caught up and unchanged, so the partition is left out.
+        assert_eq!(
+            fetch(session_id, 1, Some(1), false),
+            (error::NONE, session_id, Vec::new())
+        );
+
+        let next = RecordBatchBuilder::new(0)
+            .record(2_000, None, Some(b"b"))
+            .build();
+        handler
+            .logs
+            .lock()
+            .expect("logs lock")
+            .append(&orders, &next)
+            .expect("append");
+        // The session remembers the position, so the partition need not be sent.
+        assert_eq!(
+            fetch(session_id, 2, None, false),
+            (error::NONE, session_id, vec![(2, next.len())])
+        );
+
+        assert_eq!(
+            fetch(session_id, 2, None, false),
+            (
+                error::INVALID_FETCH_SESSION_EPOCH,
+                INVALID_SESSION_ID,
+                Vec::new()
+            )
+        );
+        assert_eq!(
+            fetch(session_id + 1, 1, None, false),
+            (
+                error::FETCH_SESSION_ID_NOT_FOUND,
+                INVALID_SESSION_ID,
+                Vec::new()
+            )
+        );
+
+        // Once forgotten, new records are no longer returned.
+        handler
+            .logs
+            .lock()
+            .expect("logs lock")
+            .append(&orders, &next)
+            .expect("append");
+        assert_eq!(
+            fetch(session_id, 3, None, true),
+            (error::NONE, session_id, Vec::new())
+        );
+    }
+
+    #[test]
+    fn long_poll_fetches_wait_for_appends_or_expire() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let mut logs = LogManager::load(dir.path(), LogConfig::default(), SystemClock::shared())
+            .expect("load logs");
+        let orders = TopicPartition::new("orders", 0);
+        logs.get_or_create_log(&orders).expect("create log");
+        let (appended, appends) = mpsc::channel();
+        logs.notify_appends(appended);
+        let handler = handler_with_logs(logs);
+        let fetch = |max_wait_ms| {
+            let request = FetchRequest {
+                header: header(FETCH_KEY, 12),
+                replica_id: CONSUMER_REPLICA_ID,
+                max_wait_ms,
+                min_bytes: 1,
+                max_bytes: 1 << 20,
+                isolation_level: READ_UNCOMMITTED,
+                session_id: INVALID_SESSION_ID,
+                session_epoch: -1,
+                topics: vec![FetchTopic {
+                    name: Some("orders".to_string()),
+                    topic_id: Uuid::ZERO,
+                    partitions: vec![FetchPartition {
+                        partition: 0,
+                        current_leader_epoch: 6,
+                        fetch_offset: 0,
+                        last_fetched_epoch: -1,
+                        log_start_offset: -1,
+                        partition_max_bytes: 1 << 20,
+                    }],
+                }],
+                forgotten_topics_data: Vec::new(),
+                rack_id: String::new(),
+            };
+            let Response::Fetch(response) =
+                handler.handle(&context("INTERNAL"), Request::Fetch(request))
+            else {
+                panic!("expected a fetch response");
+            };
+            let partition = &response.responses[0].partitions[0];
+            (
+                partition.error_code,
+                partition.records.clone().unwrap_or_default(),
+            )
+        };
+        let wait_until_parked = || {
+            while handler.delayed_fetches.delayed() == 0 {
+                thread::sleep(Duration::from_millis(1));
+            }
+        };
+
+        thread::scope(|scope| {
+            let expiring = scope.spawn(|| fetch(20));
+            wait_until_parked();
+            while handler.expire_delayed_fetches() == 0 {
+                thread::sleep(Duration::from_millis(5));
+            }
+            // Nothing arrived during the wait, so nothing is returned.
+            assert_eq!(
+                expiring.join().expect("fetch thread"),
+                (error::NONE, Vec::new())
+            );
+
+            scope.spawn(|| handler.complete_delayed_fetches(appends));
+            let waiting = scope.spawn(|| fetch(60_000));
+            wait_until_parked();
+            let batch = RecordBatchBuilder::new(0)
+                .record(1_000, None, Some(b"a"))
+                .build();
+            handler
+                .logs
+                .lock()
+                .expect("logs lock")
+                .append(&orders, &batch)
+                .expect("append");
+            assert_eq!(waiting.join().expect("fetch thread"), (error::NONE, batch));
+            // Ends the completion thread.
+            handler
+                .logs
+                .lock()
+                .expect("logs lock")
+                .shutdown()
+                .expect("shutdown");
+        });
+    }
+
+    #[test]
+    fn list_offsets_resolves_special_and_real_timestamps() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let mut logs = LogManager::load(dir.path(), LogConfig::default(), SystemClock::shared())
+            .expect("load logs");
+        let orders = TopicPartition::new("orders", 0);
+        for timestamp in [1_000, 3_000, 2_000] {
+            let batch = RecordBatchBuilder::new(0)
+                .record(timestamp, None, Some(b"value"))
+                .build();
+            logs.append(&orders, &batch).expect("append");
+        }
+        let handler = handler_with_logs(logs);
+        let list_offsets = |isolation_level, timestamps: &[i64]| {
+            let request = ListOffsetsRequest {
+                header: header(2, 8),
+                replica_id: -1,
+                isolation_level,
+                topics: vec![ListOffsetsRequestTopic {
+                    name: "orders".to_string(),
+                    partitions: timestamps
+                        .iter()
+                        .map(|&timestamp| ListOffsetsRequestPartition {
+                            partition_index: 0,
+                            current_leader_epoch: 6,
+                            timestamp,
+                        })
+                        .collect(),
+                }],
+            };
+            let Response::ListOffsets(response) =
+                handler.handle(&context("INTERNAL"), Request::ListOffsets(request))
+            else {
+                panic!("expected a list offsets response");
+            };
+            response.topics[0]
+                .partitions
+                .iter()
+                .map(|partition| (partition.error_code, partition.timestamp, partition.offset))
+                .collect::<Vec<_>>()
+        };
+
+        let timestamps = [
+            LATEST_TIMESTAMP,
+            EARLIEST_TIMESTAMP,
+            EARLIEST_LOCAL_TIMESTAMP,
+            MAX_TIMESTAMP,
+            500,
+            2_500,
+            9_999,
+        ];
+        let expected = [
+            (error::NONE, UNKNOWN_TIMESTAMP, 3),
+            (error::NONE, UNKNOWN_TIMESTAMP, 0),
+            (error::NONE, UNKNOWN_TIMESTAMP, 0),
+            (error::NONE, 3_000, 1),
+            (error::NONE, 1_000, 0),
+            (error::NONE, 3_000, 1),
+            (error::NONE, UNKNOWN_TIMESTAMP, UNKNOWN_OFFSET),
+        ];
+        assert_eq!(list_offsets(READ_UNCOMMITTED, &timestamps), expected);
+        assert_eq!(list_offsets(READ_COMMITTED, &timestamps), expected);
+
+        let request = ListOffsetsRequest {
+            header: header(2, 8),
+            replica_id: -1,
+            isolation_level: READ_UNCOMMITTED,
+            topics: vec![ListOffsetsRequestTopic {
+                name: "orders".to_string(),
+                partitions: vec![ListOffsetsRequestPartition {
+                    partition_index: 1,
+                    current_leader_epoch: -1,
+                    timestamp: LATEST_TIMESTAMP,
+                }],
+            }],
+        };
+        let Response::ListOffsets(response) =
+            handler.handle(&context("INTERNAL"), Request::ListOffsets(request))
+        else {
+            panic!("expected a list offsets response");
+        };
+        assert_eq!(
+            response.topics[0].partitions[0].error_code,
+            error::UNKNOWN_TOPIC_OR_PARTITION
+        );
+    }
+
+    #[test]
+    fn topics_are_created_and_deleted_through_the_controller() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let handler = handler_with_logs(
+            LogManager::load(dir.path(), LogConfig::default(), SystemClock::shared())
+                .expect("load logs"),
+        );
+        let creatable = |name: &str, num_partitions| CreatableTopic {
+            name: name.to_string(),
+            num_partitions,
+            replication_factor: -1,
+            assignments: Vec::new(),
+            configs: vec![CreatableTopicConfig {
+                name: "cleanup.policy".to_string(),
+                value: Some("compact".to_string()),
+            }],
+        };
+        let create = |topics, validate_only| {
+            let request = CreateTopicsRequest {
+                header: header(19, 7),
+                topics,
+                timeout_ms: 30_000,
+                validate_only,
+            };
+            let Response::CreateTopics(response) =
+                handler.handle(&context("INTERNAL"), Request::CreateTopics(request))
+            else {
+                panic!("expected a create topics response");
+            };
+            response.topics
+        };
+
+        let checked = create(vec![creatable("audit", 2)], true);
+        assert_eq!(
+            (checked[0].error_code, checked[0].num_partitions),
+            (error::NONE, 2)
+        );
+        let created = create(
+            vec![
+                creatable("audit", 2),
+                creatable("orders", 1),
+                creatable("bad name", 1),
+                creatable("empty", 0),
+            ],
+            false,
+        );
+        let errors: Vec<i16> = created.iter().map(|topic| topic.error_code).collect();
+        assert_eq!(
+            errors,
+            [
+                error::NONE,
+                error::TOPIC_ALREADY_EXISTS,
+                error::INVALID_TOPIC_EXCEPTION,
+                error::INVALID_PARTITIONS,
+            ]
+        );
+        let audit_id = created[0].topic_id;
+        assert_ne!(audit_id, Uuid::ZERO);
+        {
+            let controller = handler.controller.lock().expect("controller lock");
+            let audit = controller.image().topic_by_id(&audit_id).expect("audit");
+            assert_eq!(audit.partitions.len(), 2);
+        }
+
+        let request = DeleteTopicsRequest {
+            header: header(20, 6),
+            topics: vec![
+                DeleteTopicState {
+                    name: None,
+                    topic_id: audit_id,
+                },
+                DeleteTopicState {
+                    name: Some("missing".to_string()),
+                    topic_id: Uuid::ZERO,
+                },
+            ],
+            timeout_ms: 30_000,
+        };
+        let Response::DeleteTopics(response) =
+            handler.handle(&context("INTERNAL"), Request::DeleteTopics(request))
+        else {
+            panic!("expected a delete topics response");
+        };
+        let results: Vec<(Option<&str>, i16)> = response
+            .responses
+            .iter()
+            .map(|result| (result.name.as_deref(), result.error_code))
+            .collect();
+        assert_eq!(
+            results,
+            [
+                (Some("audit"), error::NONE),
+                (Some("missing"), error::UNKNOWN_TOPIC_OR_PARTITION),
+            ]
+        );
+        let logs = handler.logs.lock().unwrap();
+        assert!(logs.get_log(&TopicPartition::new("audit", 0)).is_none());
+    }
+
+    #[test]
+    fn brokers_are_advertised_on_the_listener_of_the_request() {
+        let handler = handler();
+        let metadata = || {
+            Request::Metadata(MetadataRequest {
+                header: header(3, 12),
+                topics: Some(Vec::new()),
+                allow_auto_topic_creation: false,
+                include_topic_authorized_operations: false,
+            })
+        };
+
+        let Response::Metadata(response) = handler.handle(&context("EXTERNAL"), metadata()) else {
+            panic!("expected a metadata response");
+        };
+        assert_eq!(response.brokers[0].host, "broker.example.com");
+        assert_eq!(response.brokers[0].port, 19092);
+
+        let Response::Metadata(response) = handler.handle(&context("REPLICATION"), metadata())
+        else {
+            panic!("expected a metadata response");
+        };
+        assert!(response.brokers.is_empty());
+    }
+
+    #[test]
+    fn find_coordinator_answers_group_keys_with_this_broker() {
+        let request = |key_type| {
+            Request::FindCoordinator(FindCoordinatorRequest {
+                header: header(10, 4),
+                key_type,
+                keys: vec!["payments".to_string()],
+            })
+        };
+        let handler = handler();
+
+        let Response::FindCoordinator(response) =
+            handler.handle(&context("EXTERNAL"), request(GROUP_KEY_TYPE))
+        else {
+            panic!("expected a find coordinator response");
+        };
+        let coordinator = &response.coordinators[0];
+        assert_eq!(coordinator.error_code, error::NONE);
+        assert_eq!(coordinator.node_id, 4);
+        assert_eq!(
+            (coordinator.host.as_str(), coordinator.port),
+            ("broker.example.com", 19092)
+        );
+
+        let Response::FindCoordinator(response) =
+            handler.handle(&context("EXTERNAL"), request(TRANSACTION_KEY_TYPE))
+        else {
+            panic!("expected a find coordinator response");
+        };
+        assert_eq!(
+            response.coordinators[0].error_code,
+            error::COORDINATOR_NOT_AVAILABLE
+        );
+    }
+
+    #[test]
+    fn version_zero_leave_group_reports_the_member_error() {
+        let Response::LeaveGroup(response) = handler().handle(
+            &context("INTERNAL"),
+            Request::LeaveGroup(LeaveGroupRequest {
+                header: header(13, 0),
+                group_id: "payments".to_string(),
+                members: vec![LeavingMember {
+                    member_id: "gone".to_string(),
+                    group_instance_id: None,
+                    reason: None,
+                }],
+            }),
+        ) else {
+            panic!("expected a leave group response");
+        };
+
+        assert_eq!(response.error_code, error::UNKNOWN_MEMBER_ID);
+    }
+
+    #[test]
+    fn consumer_group_assignments_are_sent_by_topic_id() {
+        let handler = handler();
+        let Response::ConsumerGroupHeartbeat(joined) = handler.handle(
+            &context("INTERNAL"),
+            Request::ConsumerGroupHeartbeat(ConsumerGroupHeartbeatRequest {
+                header: header(68, 0),
+                group_id: "payments".to_string(),
+                member_id: String::new(),
+                member_epoch: 0,
+                instance_id: None,
+                rack_id: None,
+                rebalance_timeout_ms: 30_000,
+                subscribed_topic_names: Some(vec!["orders".to_string()]),
+                subscribed_topic_regex: None,
+                server_assignor: Some("range".to_string()),
+                topic_partitions: Some(Vec::new()),
+            }),
+        ) else {
+            panic!("expected a consumer group heartbeat response");
+        };
+        assert_eq!(joined.error_code, error::NONE);
+        assert_eq!(joined.member_epoch, 1);
+        assert_eq!(
+            joined.assignment,
+            Some(vec![ConsumerGroupTopicPartitions {
+                topic_id: Uuid([2; 16]),
+                partitions: vec![0],
+            }])
+        );
+
+        let Response::ConsumerGroupDescribe(described) = handler.handle(
+            &context("INTERNAL"),
+            Request::ConsumerGroupDescribe(ConsumerGroupDescribeRequest {
+                header: header(69, 0),
+                group_ids: vec!["payments".to_string(), "missing".to_string()],
+                include_authorized_operations: false,
+            }),
+        ) else {
+            panic!("expected a consumer group describe response");
+        };
+        let group = &described.groups[0];
+        assert_eq!(
+            (group.group_state.as_str(), group.assignor_name.as_str()),
+            ("Stable", "range")
+        );
+        assert_eq!(
+            group.members[0].member_id,
+            joined.member_id.expect("member id")
+        );
+        assert_eq!(group.members[0].assignment[0].topic_name, "orders");
+        assert_eq!(described.groups[1].error_code, error::GROUP_ID_NOT_FOUND);
+    }
+
+    #[test]
+    fn share_fetch_acquires_records_by_topic_id() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let mut logs = LogManager::load(dir.path(), LogConfig::default(), SystemClock::shared())
+            .expect("load logs");
+        let orders = TopicPartition::new("orders", 0);
+        logs.get_or_create_log(&orders).expect("create log");
+        let handler = handler_with_logs(logs);
+        let Response::ShareGroupHeartbeat(joined) = handler.handle(
+            &context("INTERNAL"),
+            Request::ShareGroupHeartbeat(ShareGroupHeartbeatRequest {
+                header: header(76, 0),
+                group_id: "jobs".to_string(),
+                member_id: String::new(),
+                member_epoch: 0,
+                rack_id: None,
+                subscribed_topic_names: Some(vec!["orders".to_string()]),
+            }),
+        ) else {
+            panic!("expected a share group heartbeat response");
+        };
+        assert_eq!(joined.error_code, error::NONE);
+        let member_id = joined.member_id.expect("member id");
+        let share_fetch = |epoch, topic_id| {
+            let request = ShareFetchRequest {
+                header: header(78, 0),
+                group_id: Some("jobs".to_string()),
+                member_id: Some(member_id.clone()),
+                share_session_epoch: epoch,
+                max_wait_ms: 0,
+                min_bytes: 1,
+                max_bytes: 1 << 20,
+                topics: vec![ShareFetchTopic {
+                    topic_id,
+                    partitions: vec![ShareFetchPartition {
+                        partition_index: 0,
+                        partition_max_bytes: 1 << 20,
+                        acknowledgement_batches: Vec::new(),
+                    }],
+                }],
+                forgotten_topics: Vec::new(),
+            };
+            let Response::ShareFetch(response) =
+                handler.handle(&context("INTERNAL"), Request::ShareFetch(request))
+            else {
+                panic!("expected a share fetch response");
+            };
+            response
+        };
+
+        assert!(share_fetch(0, Uuid([2; 16])).responses.is_empty());
+        let batch = RecordBatchBuilder::new(0)
+            .record(0, None, Some(b"job"))
+            .build();
+        handler
+            .logs
+            .lock()
+            .expect("logs")
+            .append(&orders, &batch)
+            .expect("append");
+        let fetched = share_fetch(1, Uuid([2; 16]));
+        assert_eq!(fetched.responses[0].topic_id, Uuid([2; 16]));
+        let partition = &fetched.responses[0].partitions[0];
+        assert_eq!(
+            partition.current_leader,
+            LeaderIdAndEpoch {
+                leader_id: 4,
+                leader_epoch: 6,
+            }
+        );
+        assert_eq!(partition.records.as_deref(), Some(batch.as_slice()));
+        assert_eq!(partition.acquired_records[0].delivery_count, 1);
+        let unknown = share_fetch(2, Uuid([9; 16]));
+        assert_eq!(
+            unknown.responses[0].partitions[0].error_code,
+            error::UNKNOWN_TOPIC_ID
+        );
+
+        let Response::ShareAcknowledge(acknowledged) = handler.handle(
+            &context("INTERNAL"),
+            Request::ShareAcknowledge(ShareAcknowledgeRequest {
+                header: header(79, 0),
+                group_id: Some("jobs".to_string()),
+                member_id: Some(member_id.clone()),
+                share_session_epoch: 3,
+                topics: vec![ShareAcknowledgeTopic {
+                    topic_id: Uuid([2; 16]),
+                    partitions: vec![ShareAcknowledgePartition {
+                        partition_index: 0,
+                        acknowledgement_batches: vec![AcknowledgementBatch {
+                            first_offset: 0,
+                            last_offset: 0,
+                            acknowledge_types: vec![acknowledge_type::ACCEPT],
+                        }],
+                    }],
+                }],
+            }),
+        ) else {
+            panic!("expected a share acknowledge response");
+        };
+        assert_eq!(acknowledged.error_code, error::NONE);
+        assert_eq!(
+            acknowledged.responses[0].partitions[0].error_code,
+            error::NONE
+        );
+    }
+
+    #[test]
+    fn init_producer_id_hands_out_distinct_ids() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let handler = handler_with_logs(
+            LogManager::load(dir.path(), LogConfig::default(), SystemClock::shared())
+                .expect("load logs"),
+        );
+        let init = |transactional_id: Option<&str>| {
+            let Response::InitProducerId(response) = handler.handle(
+                &context("INTERNAL"),
+                Request::InitProducerId(InitProducerIdRequest {
+                    header: header(22, 4),
+                    transactional_id: transactional_id.map(str::to_string),
+                    transaction_timeout_ms: 60_000,
+                    producer_id: NO_PRODUCER_ID,
+                    producer_epoch: NO_PRODUCER_EPOCH,
+                }),
+            ) else {
+                panic!("expected an init producer id response");
+            };
+            response
+        };
+
+        let first = init(None);
+        let second = init(None);
+        assert_eq!(first.error_code, error::NONE);
+        assert_eq!((first.producer_id, first.producer_epoch), (0, 0));
+        assert_eq!(second.producer_id, 1);
+        let controller = handler.controller.lock().expect("controller");
+        assert_eq!(
+            controller.image().next_producer_id(),
+            PRODUCER_ID_BLOCK_SIZE
+        );
+        drop(controller);
+
+        let transactional = init(Some("payments-tx"));
+        assert_eq!(transactional.error_code, error::COORDINATOR_NOT_AVAILABLE);
+        assert_eq!(transactional.producer_id, NO_PRODUCER_ID);
+    }
+
+    #[test]
+    fn committed_offsets_are_fetched_back_per_group() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let handler = handler_with_logs(
+            LogManager::load(dir.path(), LogConfig::default(), SystemClock::shared())
+                .expect("load logs"),
+        );
+        let commit = OffsetCommitRequest {
+            header: header(8, 9),
+            group_id: "payments".to_string(),
+            generation_id_or_member_epoch: NO_GENERATION,
+            member_id: String::new(),
+            group_instance_id: None,
+            topics: vec![OffsetCommitRequestTopic {
+                name: "orders".to_string(),
+                partitions: [0, 5]
+                    .into_iter()
+                    .map(|partition_index| OffsetCommitRequestPartition {
+                        partition_index,
+                        committed_offset: 42,
+                        committed_leader_epoch: 6,
+                        committed_metadata: None,
+                    })
+                    .collect(),
+            }],
+        };
+        let Response::OffsetCommit(response) =
+            handler.handle(&context("INTERNAL"), Request::OffsetCommit(commit))
+        else {
+            panic!("expected an offset commit response");
+        };
+        let errors: Vec<i16> = response.topics[0]
+            .partitions
+            .iter()
+            .map(|partition| partition.error_code)
+            .collect();
+        assert_eq!(errors, [error::NONE, error::UNKNOWN_TOPIC_OR_PARTITION]);
+
+        let fetch = OffsetFetchRequest {
+            header: header(9, 9),
+            groups: ["payments", "unknown"]
+                .into_iter()
+                .map(|group_id| OffsetFetchRequestGroup {
+                    group_id: group_id.to_string(),
+                    member_id: None,
+                    member_epoch: -1,
+                    topics: Some(vec![OffsetFetchRequestTopic {
+                        name: "orders".to_string(),
+                        partition_indexes: vec![0],
+                    }]),
+                })
+                .collect(),
+            require_stable: false,
+        };
+        let Response::OffsetFetch(response) =
+            handler.handle(&context("INTERNAL"), Request::OffsetFetch(fetch))
+        else {
+            panic!("expected an offset fetch response");
+        };
+        let offsets: Vec<(i64, i32)> = response
+            .groups
+            .iter()
+            .map(|group| {
+                let partition = &group.topics[0].partitions[0];
+                (partition.committed_offset, partition.committed_leader_epoch)
+            })
+            .collect();
+        assert_eq!(offsets, [(42, 6), (NO_OFFSET, NO_LEADER_EPOCH)]);
+    }
+
+    #[test]
+    fn produce_reports_sequence_errors_per_partition() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let logs = LogManager::load(dir.path(), LogConfig::default(), SystemClock::shared())
+            .expect("load logs");
+        let handler = handler_with_logs(logs);
+        let produce = |partitions: Vec<(i32, i32)>| {
+            let partition_data = partitions
+                .into_iter()
+                .map(|(index, base_sequence)| PartitionProduceData {
+                    index,
+                    records: Some(
+                        RecordBatchBuilder::new(0)
+                            .producer(7, 0, base_sequence)
+                            .record(1_000, None, Some(b"v"))
+                            .build(),
+                    ),
+                })
+                .collect();
+            let request = ProduceRequest {
+                header: header(PRODUCE_KEY, 9),
+                transactional_id: None,
+                acks: ACKS_ALL,
+                timeout_ms: 1_000,
+                topic_data: vec![TopicProduceData {
+                    name: "orders".to_string(),
+                    partition_data,
+                }],
+            };
+            let Response::Produce(response) =
+                handler.handle(&context("INTERNAL"), Request::Produce(request))
+            else {
+                panic!("expected a produce response");
+            };
+            response.responses[0]
+                .partition_responses
+                .iter()
+                .map(|partition| (partition.index, partition.error_code, partition.base_offset))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            produce(vec![(0, 0), (9, 0)]),
+            [
+                (0, error::NONE, 0),
+                (9, error::UNKNOWN_TOPIC_OR_PARTITION, UNKNOWN_OFFSET)
+            ]
+        );
+        assert_eq!(produce(vec![(0, 1)]), [(0, error::NONE, 1)]);
+        // A retried batch is acknowledged at its original offset.
+        assert_eq!(produce(vec![(0, 0)]), [(0, error::NONE, 0)]);
+        assert_eq!(
+            produce(vec![(0, 5)]),
+            [(0, error::OUT_OF_ORDER_SEQUENCE_NUMBER, UNKNOWN_OFFSET)]
+        );
+        let logs = handler.logs.lock().expect("log manager lock poisoned");
+        let log = logs
+            .get_log(&TopicPartition::new("orders", 0))
+            .expect("log");
+        assert_eq!(log.log_end_offset(), 2);
+    }
+
+    #[test]
+    fn produce_rejects_batches_with_a_bad_crc() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let logs = LogManager::load(dir.path(), LogConfig::default(), SystemClock::shared())
+            .expect("load logs");
+        let handler = handler_with_logs(logs);
+        let mut records = RecordBatchBuilder::new(0)
+            .record(1_000, None, Some(b"good"))
+            .build();
+        let mut corrupt = RecordBatchBuilder::new(1)
+            .record(1_000, None, Some(b"bad"))
+            .build();
+        let last = corrupt.len() - 1;
+        corrupt[last] ^= 0xFF;
+        records.extend_from_slice(&corrupt);
+        let request = ProduceRequest {
+            header: header(PRODUCE_KEY, 9),
+            transactional_id: None,
+            acks: ACKS_ALL,
+            timeout_ms: 1_000,
+            topic_data: vec![TopicProduceData {
+                name: "orders".to_string(),
+                partition_data: vec![PartitionProduceData {
+                    index: 0,
+                    records: Some(records),
+                }],
+            }],
+        };
+
+        let Response::Produce(response) =
+            handler.handle(&context("INTERNAL"), Request::Produce(request))
+        else {
+            panic!("expected a produce response");
+        };
+        let partition = &response.responses[0].partition_responses[0];
+        assert_eq!(partition.error_code, error::CORRUPT_MESSAGE);
+        assert_eq!(partition.base_offset, UNKNOWN_OFFSET);
+        let logs = handler.logs.lock().expect("log manager lock poisoned");
+        let log_end_offset = logs
+            .get_log(&TopicPartition::new("orders", 0))
+            .map_or(0, Log::log_end_offset);
+        assert_eq!(log_end_offset, 0);
+    }
+}
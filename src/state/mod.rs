@@ -1,3 +1,39 @@
+pub mod assignor;
+pub mod consumer_group;
+pub mod coordinator;
+pub mod delayed_fetch;
+pub mod fetch_session;
+pub mod group;
+pub mod handler;
+pub mod offsets;
+pub mod purgatory;
+pub mod share_group;
+
+pub use assignor::{PartitionAssignor, RangeAssignor, UniformAssignor};
+pub use consumer_group::{
+    ConsumerGroup, ConsumerGroupState, ConsumerMember, MemberState, TopicMetadata,
+};
+pub use coordinator::{
+    CommitParams, ConsumerHeartbeatParams, ConsumerHeartbeatResult, GroupConfig, GroupCoordinator,
+    JoinParams, MemberIdentity, OffsetCommit, SyncParams,
+};
+pub use fetch_session::{FetchContext, FetchSessionCache};
+pub use group::{GroupOverview, GroupState, GroupSummary, JoinResult, MemberSummary, SyncResult};
+pub use handler::{BrokerEndpoint, BrokerInfo, RequestContext, RequestHandler};
+pub use offsets::OffsetAndMetadata;
+pub use purgatory::{DelayedOperation, Purgatory};
+pub use share_group::{
+    DeliveryLimits, RecordState, ShareGroup, ShareGroupState, ShareMember, SharePartition,
+    ShareSession,
+};
+
+use crate::protocol::{
+    consumer_group_describe, consumer_group_heartbeat, create_topics, delete_groups, delete_topics,
+    describe_cluster, describe_groups, describe_topic_partitions, fetch, find_coordinator,
+    heartbeat, init_producer_id, join_group, leave_group, list_groups, list_offsets, metadata,
+    offset_commit, offset_delete, offset_fetch, produce, share_acknowledge, share_fetch,
+    share_group_heartbeat, sync_group,
+};
 use crate::protocol::{ApiVersion, ApiVersionsRequest, ApiVersionsResponse};
 
 const API_VERSIONS_KEY: i16 = 18;
@@ -28,9 +64,129 @@ impl Default for ApiRegistry {
     fn default() -> Self {
         Self {
             supported: vec![
+                ApiVersion::new(
+                    produce::PRODUCE_KEY,
+                    produce::MIN_VERSION,
+                    produce::MAX_VERSION,
+                ),
+                ApiVersion::new(fetch::FETCH_KEY, fetch::MIN_VERSION, fetch::MAX_VERSION),
+                ApiVersion::new(
+                    list_offsets::LIST_OFFSETS_KEY,
+                    list_offsets::MIN_VERSION,
+                    list_offsets::MAX_VERSION,
+                ),
+                ApiVersion::new(
+                    metadata::METADATA_KEY,
+                    metadata::MIN_VERSION,
+                    metadata::MAX_VERSION,
+                ),
+                ApiVersion::new(
+                    offset_commit::OFFSET_COMMIT_KEY,
+                    offset_commit::MIN_VERSION,
+                    offset_commit::MAX_VERSION,
+                ),
+                ApiVersion::new(
+                    offset_fetch::OFFSET_FETCH_KEY,
+                    offset_fetch::MIN_VERSION,
+                    offset_fetch::MAX_VERSION,
+                ),
+                ApiVersion::new(
+                    find_coordinator::FIND_COORDINATOR_KEY,
+                    find_coordinator::MIN_VERSION,
+                    find_coordinator::MAX_VERSION,
+                ),
+                ApiVersion::new(
+                    join_group::JOIN_GROUP_KEY,
+                    join_group::MIN_VERSION,
+                    join_group::MAX_VERSION,
+                ),
+                ApiVersion::new(
+                    heartbeat::HEARTBEAT_KEY,
+                    heartbeat::MIN_VERSION,
+                    heartbeat::MAX_VERSION,
+                ),
+                ApiVersion::new(
+                    leave_group::LEAVE_GROUP_KEY,
+                    leave_group::MIN_VERSION,
+                    leave_group::MAX_VERSION,
+                ),
+                ApiVersion::new(
+                    sync_group::SYNC_GROUP_KEY,
+                    sync_group::MIN_VERSION,
+                    sync_group::MAX_VERSION,
+                ),
+                ApiVersion::new(
+                    describe_groups::DESCRIBE_GROUPS_KEY,
+                    describe_groups::MIN_VERSION,
+                    describe_groups::MAX_VERSION,
+                ),
+                ApiVersion::new(
+                    list_groups::LIST_GROUPS_KEY,
+                    list_groups::MIN_VERSION,
+                    list_groups::MAX_VERSION,
+                ),
                 ApiVersion::new(17, 0, 4),
                 ApiVersion::new(18, 0, 4),
-                ApiVersion::new(19, 0, 4),
+                ApiVersion::new(
+                    create_topics::CREATE_TOPICS_KEY,
+                    create_topics::MIN_VERSION,
+                    create_topics::MAX_VERSION,
+                ),
+                ApiVersion::new(
+                    delete_topics::DELETE_TOPICS_KEY,
+                    delete_topics::MIN_VERSION,
+                    delete_topics::MAX_VERSION,
+                ),
+                ApiVersion::new(
+                    init_producer_id::INIT_PRODUCER_ID_KEY,
+                    init_producer_id::MIN_VERSION,
+                    init_producer_id::MAX_VERSION,
+                ),
+                ApiVersion::new(
+                    delete_groups::DELETE_GROUPS_KEY,
+                    delete_groups::MIN_VERSION,
+                    delete_groups::MAX_VERSION,
+                ),
+                ApiVersion::new(
+                    offset_delete::OFFSET_DELETE_KEY,
+                    offset_delete::MIN_VERSION,
+                    offset_delete::MAX_VERSION,
+                ),
+                ApiVersion::new(
+                    describe_cluster::DESCRIBE_CLUSTER_KEY,
+                    describe_cluster::MIN_VERSION,
+                    describe_cluster::MAX_VERSION,
+                ),
+                ApiVersion::new(
+                    consumer_group_heartbeat::CONSUMER_GROUP_HEARTBEAT_KEY,
+                    consumer_group_heartbeat::MIN_VERSION,
+                    consumer_group_heartbeat::MAX_VERSION,
+                ),
+                ApiVersion::new(
+                    consumer_group_describe::CONSUMER_GROUP_DESCRIBE_KEY,
+                    consumer_group_describe::MIN_VERSION,
+                    consumer_group_describe::MAX_VERSION,
+                ),
+                ApiVersion::new(
+                    describe_topic_partitions::DESCRIBE_TOPIC_PARTITIONS_KEY,
+                    describe_topic_partitions::MIN_VERSION,
+                    describe_topic_partitions::MAX_VERSION,
+                ),
+                ApiVersion::new(
+                    share_group_heartbeat::SHARE_GROUP_HEARTBEAT_KEY,
+                    share_group_heartbeat::MIN_VERSION,
+                    share_group_heartbeat::MAX_VERSION,
+                ),
+                ApiVersion::new(
+                    share_fetch::SHARE_FETCH_KEY,
+                    share_fetch::MIN_VERSION,
+                    share_fetch::MAX_VERSION,
+                ),
+                ApiVersion::new(
+                    share_acknowledge::SHARE_ACKNOWLEDGE_KEY,
+                    share_acknowledge::MIN_VERSION,
+                    share_acknowledge::MAX_VERSION,
+                ),
             ],
         }
     }
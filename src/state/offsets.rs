@@ -0,0 +1,244 @@
+use crate::codec::primitives;
+use crate::protocol::wire;
+use crate::storage::batch::{self, BatchHeader, RecordBatchBuilder};
+use crate::storage::config::CLEANUP_POLICY_CONFIG;
+use crate::storage::{Log, LogManager, TopicPartition};
+use std::collections::{BTreeMap, HashMap};
+use std::io::{self, Cursor};
+
+pub const OFFSETS_TOPIC: &str = "__consumer_offsets";
+
+/// Key versions 0 and 1 hold a committed offset; version 2 holds group
+/// metadata, which is not written here.
+const OFFSET_COMMIT_KEY_VERSION: i16 = 1;
+const GROUP_METADATA_KEY_VERSION: i16 = 2;
+const OFFSET_COMMIT_VALUE_VERSION: i16 = 3;
+
+/// Maximum number of bytes read from an offsets partition at a time while
+/// loading.
+const LOAD_READ_BYTES: usize = 1024 * 1024;
+
+/// A committed offset as stored in the offsets topic.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OffsetAndMetadata {
+    pub offset: i64,
+    pub leader_epoch: i32,
+    pub metadata: String,
+    pub commit_timestamp_ms: i64,
+}
+
+/// Committed offsets of each group.
+pub type GroupOffsets = HashMap<String, BTreeMap<TopicPartition, OffsetAndMetadata>>;
+
+/// Partition of the offsets topic holding the offsets of `group_id`, placed
+/// by the Java hash code of the id as Kafka does.
+pub fn offsets_partition(group_id: &str, partitions: i32) -> TopicPartition {
+    let hash = group_id.encode_utf16().fold(0_i32, |hash, unit| {
+        hash.wrapping_mul(31).wrapping_add(i32::from(unit))
+    });
+    TopicPartition::new(OFFSETS_TOPIC, (hash & i32::MAX) % partitions.max(1))
+}
+
+/// Encodes one batch of offset commits of `group_id`, with `None` values
+/// written as tombstones that delete the offset.
+pub fn offset_commit_batch(
+    group_id: &str,
+    offsets: &[(TopicPartition, Option<OffsetAndMetadata>)],
+    timestamp_ms: i64,
+) -> Vec<u8> {
+    offsets
+        .iter()
+        .fold(RecordBatchBuilder::new(0), |builder, (tp, offset)| {
+            let value = offset.as_ref().map(encode_value);
+            builder.record(
+                timestamp_ms,
+                Some(&encode_key(group_id, tp)),
+                value.as_deref(),
+            )
+        })
+        .build()
+}
+
+/// Reads the committed offsets of every group from the offsets topic.
+///
+/// The topic is compacted, so only the latest commit of each partition and
+/// the tombstones of expired offsets survive cleaning.
+pub fn load(logs: &mut LogManager) -> io::Result<GroupOffsets> {
+    logs.set_topic_config(
+        OFFSETS_TOPIC,
+        &BTreeMap::from([(CLEANUP_POLICY_CONFIG.to_string(), "compact".to_string())]),
+    )?;
+
+    let mut offsets = GroupOffsets::new();
+    for (tp, log) in logs.logs() {
+        if tp.topic == OFFSETS_TOPIC {
+            replay(&mut offsets, log)?;
+        }
+    }
+    offsets.retain(|_, partitions| !partitions.is_empty());
+    Ok(offsets)
+}
+
+/// Applies every offset commit and tombstone in `log` to `offsets`.
+fn replay(offsets: &mut GroupOffsets, log: &Log) -> io::Result<()> {
+    let mut offset = log.log_start_offset();
+    while offset < log.log_end_offset() {
+        let bytes = log.read(offset, LOAD_READ_BYTES)?;
+        let batches = batch::split_batches(&bytes)?;
+        let Some(last) = batches.last() else {
+            break;
+        };
+        let next_offset = BatchHeader::parse(last)?.last_offset() + 1;
+
+        for bytes in batches {
+            if BatchHeader::parse(bytes)?.is_control() {
+                continue;
+            }
+            for record in batch::read_records(bytes)? {
+                let Some(key) = record.key.filter(|_| record.offset >= offset) else {
+                    continue;
+                };
+                let Some((group_id, tp)) = decode_key(&key)? else {
+                    continue;
+                };
+                let partitions = offsets.entry(group_id).or_default();
+                match record.value {
+                    Some(value) => {
+                        partitions.insert(tp, decode_value(&value)?);
+                    }
+                    None => {
+                        partitions.remove(&tp);
+                    }
+                }
+            }
+        }
+        offset = next_offset;
+    }
+    Ok(())
+}
+
+fn encode_key(group_id: &str, tp: &TopicPartition) -> Vec<u8> {
+    let mut buffer = OFFSET_COMMIT_KEY_VERSION.to_be_bytes().to_vec();
+    wire::write_string(&mut buffer, group_id, false);
+    wire::write_string(&mut buffer, &tp.topic, false);
+    buffer.extend_from_slice(&tp.partition.to_be_bytes());
+    buffer
+}
+
+/// Decodes an offset commit key, or `None` for group metadata keys.
+fn decode_key(key: &[u8]) -> io::Result<Option<(String, TopicPartition)>> {
+    let mut cursor = Cursor::new(key);
+    match primitives::read_i16(&mut cursor)? {
+        0 | OFFSET_COMMIT_KEY_VERSION => {
+            let group_id = wire::read_string(&mut cursor, false)?;
+            let topic = wire::read_string(&mut cursor, false)?;
+            let partition = primitives::read_i32(&mut cursor)?;
+            Ok(Some((group_id, TopicPartition::new(topic, partition))))
+        }
+        GROUP_METADATA_KEY_VERSION => Ok(None),
+        version => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("unknown offsets topic key version {version}"),
+        )),
+    }
+}
+
+fn encode_value(offset: &OffsetAndMetadata) -> Vec<u8> {
+    let mut buffer = OFFSET_COMMIT_VALUE_VERSION.to_be_bytes().to_vec();
+    buffer.extend_from_slice(&offset.offset.to_be_bytes());
+    buffer.extend_from_slice(&offset.leader_epoch.to_be_bytes());
+    wire::write_string(&mut buffer, &offset.metadata, false);
+    buffer.extend_from_slice(&offset.commit_timestamp_ms.to_be_bytes());
+    buffer
+}
+
+/// Decodes an offset commit value of any version up to 3; the expire
+/// timestamp of version 1 is ignored.
+fn decode_value(value: &[u8]) -> io::Result<OffsetAndMetadata> {
+    let mut cursor = Cursor::new(value);
+    let version = primitives::read_i16(&mut cursor)?;
+    if !(0..=OFFSET_COMMIT_VALUE_VERSION).contains(&version) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("unknown offset commit value version {version}"),
+        ));
+    }
+    let offset = primitives::read_i64(&mut cursor)?;
+    let leader_epoch = if version >= 3 {
+        primitives::read_i32(&mut cursor)?
+    } else {
+        -1
+    };
+    let metadata = wire::read_string(&mut cursor, false)?;
+    let commit_timestamp_ms = primitives::read_i64(&mut cursor)?;
+    Ok(OffsetAndMetadata {
+        offset,
+        leader_epoch,
+        metadata,
+        commit_timestamp_ms,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::LogConfig;
+    use crate::time::SystemClock;
+
+    fn committed(offset: i64) -> OffsetAndMetadata {
+        OffsetAndMetadata {
+            offset,
+            leader_epoch: 2,
+            metadata: format!("at {offset}"),
+            commit_timestamp_ms: 1_000 + offset,
+        }
+    }
+
+    #[test]
+    fn groups_are_spread_like_kafka() {
+        assert_eq!(offsets_partition("payments", 50).partition, 13);
+        assert_eq!(offsets_partition("", 50).partition, 0);
+    }
+
+    #[test]
+    fn load_replays_commits_and_tombstones() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let orders = TopicPartition::new("orders", 0);
+        let audit = TopicPartition::new("audit", 3);
+        {
+            let mut logs =
+                LogManager::load(dir.path(), LogConfig::default(), SystemClock::shared())
+                    .expect("load");
+            let tp = offsets_partition("payments", 50);
+            let commits = [
+                offset_commit_batch(
+                    "payments",
+                    &[
+                        (orders.clone(), Some(committed(5))),
+                        (audit.clone(), Some(committed(1))),
+                    ],
+                    0,
+                ),
+                offset_commit_batch("payments", &[(orders.clone(), Some(committed(9)))], 0),
+                offset_commit_batch("payments", &[(audit.clone(), None)], 0),
+                offset_commit_batch("expired", &[(audit.clone(), Some(committed(3)))], 0),
+                offset_commit_batch("expired", &[(audit.clone(), None)], 0),
+            ];
+            for batch in commits {
+                logs.append(&tp, &batch).expect("append");
+            }
+            logs.shutdown().expect("shutdown");
+        }
+
+        let mut logs = LogManager::load(dir.path(), LogConfig::default(), SystemClock::shared())
+            .expect("reload");
+        let offsets = load(&mut logs).expect("load offsets");
+
+        assert_eq!(offsets.len(), 1);
+        assert_eq!(
+            offsets["payments"],
+            BTreeMap::from([(orders, committed(9))])
+        );
+        assert!(logs.config_for(OFFSETS_TOPIC).cleanup_policy.compact);
+    }
+}
@@ -0,0 +1,362 @@
+use crate::time::Clock;
+use std::collections::{BTreeSet, HashMap};
+use std::hash::Hash;
+use std::sync::{Arc, Mutex};
+
+/// A request that cannot be answered yet, such as a long-poll Fetch waiting
+/// for `min_bytes` or an acks=all Produce waiting for replication.
+///
+/// The purgatory owns the operation while it waits and calls exactly one of
+/// a successful [`DelayedOperation::try_complete`] or
+/// [`DelayedOperation::on_expiration`], which must send the response.
+pub trait DelayedOperation: Send {
+    /// Sends the response if the operation's condition now holds, returning
+    /// whether it did.
+    fn try_complete(&mut self) -> bool;
+
+    /// Sends the best response available once the timeout has passed.
+    fn on_expiration(&mut self);
+}
+
+/// Parks delayed operations on keys, usually topic-partitions, until a
+/// change to one of those keys lets them complete or their timeout passes.
+///
+/// No thread waits on a parked operation: producers of new data call
+/// [`Purgatory::check_and_complete`] for the keys they changed, and a reaper
+/// calls [`Purgatory::expire`] periodically.
+#[derive(Debug)]
+pub struct Purgatory<K, O> {
+    name: String,
+    clock: Arc<dyn Clock>,
+    state: Mutex<PurgatoryState<K, O>>,
+}
+
+#[derive(Debug)]
+struct PurgatoryState<K, O> {
+    next_id: u64,
+    operations: HashMap<u64, Parked<K, O>>,
+    watchers: HashMap<K, BTreeSet<u64>>,
+    /// Deadline and id of every parked operation, earliest first.
+    deadlines: BTreeSet<(i64, u64)>,
+}
+
+#[derive(Debug)]
+struct Parked<K, O> {
+    /// `None` while a caller is checking the operation outside the lock.
+    operation: Option<O>,
+    /// Set when a key changed while the operation was being checked, so the
+    /// caller checking it retries before parking it again.
+    recheck: bool,
+    keys: Vec<K>,
+    deadline_ms: i64,
+}
+
+impl<K: Clone + Eq + Hash, O: DelayedOperation> Purgatory<K, O> {
+    pub fn new(name: &str, clock: Arc<dyn Clock>) -> Self {
+        Self {
+            name: name.to_string(),
+            clock,
+            state: Mutex::new(PurgatoryState {
+                next_id: 0,
+                operations: HashMap::new(),
+                watchers: HashMap::new(),
+                deadlines: BTreeSet::new(),
+            }),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Completes `operation` now if it can; otherwise parks it on `keys` for
+    /// up to `timeout_ms`. Returns whether it completed without being parked.
+    ///
+    /// The operation is checked again once it is watching its keys, so a
+    /// change made between the first check and parking is not missed.
+    pub fn try_complete_else_watch(&self, mut operation: O, keys: Vec<K>, timeout_ms: i64) -> bool {
+        if operation.try_complete() {
+            return true;
+        }
+        let deadline_ms = self.clock.now_ms().saturating_add(timeout_ms.max(0));
+        let id = {
+            let mut state = self.lock();
+            let id = state.next_id;
+            state.next_id += 1;
+            for key in &keys {
+                state.watchers.entry(key.clone()).or_default().insert(id);
+            }
+            state.deadlines.insert((deadline_ms, id));
+            state.operations.insert(
+                id,
+                Parked {
+                    operation: None,
+                    recheck: false,
+                    keys,
+                    deadline_ms,
+                },
+            );
+            id
+        };
+        self.complete_or_park(id, operation)
+    }
+
+    /// Retries every operation parked on `key`, to be called after new data
+    /// is appended or the high watermark moves. Returns how many completed.
+    ///
+    /// An operation another caller is checking at the time is retried by that
+    /// caller instead.
+    pub fn check_and_complete(&self, key: &K) -> usize {
+        let checked: Vec<(u64, O)> = {
+            let mut state = self.lock();
+            let ids: Vec<u64> = match state.watchers.get(key) {
+                Some(ids) => ids.iter().copied().collect(),
+                None => return 0,
+            };
+            ids.into_iter()
+                .filter_map(|id| {
+                    let parked = state.operations.get_mut(&id)?;
+                    match parked.operation.take() {
+                        Some(operation) => Some((id, operation)),
+                        // Whoever is checking it now retries once done.
+                        None => {
+                            parked.recheck = true;
+                            None
+                        }
+                    }
+                })
+                .collect()
+        };
+
+        checked
+            .into_iter()
+            .map(|(id, operation)| self.complete_or_park(id, operation))
+            .filter(|&done| done)
+            .count()
+    }
+
+    /// Checks an operation taken out of its slot, retrying while keys change
+    /// under it, then forgets it if it completed or parks it again otherwise.
+    /// Returns whether it completed.
+    fn complete_or_park(&self, id: u64, mut operation: O) -> bool {
+        loop {
+            // Checked without holding the lock, so operations may take their
+            // time or park further operations.
+            let done = operation.try_complete();
+            let mut state = self.lock();
+            if done {
+                state.remove(id);
+                return true;
+            }
+            let Some(parked) = state.operations.get_mut(&id) else {
+                return false;
+            };
+            if !std::mem::take(&mut parked.recheck) {
+                parked.operation = Some(operation);
+                return false;
+            }
+        }
+    }
+
+    /// Expires every operation whose timeout has passed, returning how many
+    /// expired.
+    pub fn expire(&self) -> usize {
+        let now_ms = self.clock.now_ms();
+        let expired: Vec<O> = {
+            let mut state = self.lock();
+            // Operations being checked elsewhere expire on a later pass.
+            let due: Vec<u64> = state
+                .deadlines
+                .iter()
+                .take_while(|(deadline_ms, _)| *deadline_ms <= now_ms)
+                .map(|&(_, id)| id)
+                .filter(|id| {
+                    state
+                        .operations
+                        .get(id)
+                        .is_some_and(|parked| parked.operation.is_some())
+                })
+                .collect();
+            due.into_iter().filter_map(|id| state.remove(id)).collect()
+        };
+
+        let count = expired.len();
+        for mut operation in expired {
+            operation.on_expiration();
+        }
+        count
+    }
+
+    /// Number of operations waiting.
+    pub fn delayed(&self) -> usize {
+        self.lock().operations.len()
+    }
+
+    /// Number of keys with at least one operation waiting on them.
+    pub fn watched_keys(&self) -> usize {
+        self.lock().watchers.len()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, PurgatoryState<K, O>> {
+        self.state.lock().expect("purgatory lock poisoned")
+    }
+}
+
+impl<K: Clone + Eq + Hash, O> PurgatoryState<K, O> {
+    /// Forgets a parked operation, returning it unless it is being checked.
+    fn remove(&mut self, id: u64) -> Option<O> {
+        let parked = self.operations.remove(&id)?;
+        self.deadlines.remove(&(parked.deadline_ms, id));
+        for key in &parked.keys {
+            if let Some(ids) = self.watchers.get_mut(key) {
+                ids.remove(&id);
+                if ids.is_empty() {
+                    self.watchers.remove(key);
+                }
+            }
+        }
+        parked.operation
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::TopicPartition;
+    use crate::time::MockClock;
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::mpsc;
+
+    /// Stands in for a long-poll fetch that waits until `min_bytes` are
+    /// available on its partition.
+    struct TestFetch {
+        available: Arc<AtomicU64>,
+        min_bytes: u64,
+        respond: mpsc::Sender<(u64, bool)>,
+        /// Taken and run by the next check just after it reads `available`,
+        /// to make data arrive while an operation is being checked.
+        on_check: Arc<Mutex<Option<OnCheck>>>,
+    }
+
+    type OnCheck = Box<dyn FnOnce() + Send>;
+
+    impl DelayedOperation for TestFetch {
+        fn try_complete(&mut self) -> bool {
+            let available = self.available.load(Ordering::SeqCst);
+            let on_check = self.on_check.lock().unwrap().take();
+            if let Some(on_check) = on_check {
+                on_check();
+            }
+            if available < self.min_bytes {
+                return false;
+            }
+            self.respond.send((available, false)).unwrap();
+            true
+        }
+
+        fn on_expiration(&mut self) {
+            let available = self.available.load(Ordering::SeqCst);
+            self.respond.send((available, true)).unwrap();
+        }
+    }
+
+    fn tp(partition: i32) -> TopicPartition {
+        TopicPartition {
+            topic: "orders".to_string(),
+            partition,
+        }
+    }
+
+    #[test]
+    fn completes_parked_operations_when_their_key_changes() {
+        let clock = MockClock::new(0);
+        let purgatory = Purgatory::new("fetch", clock.clone());
+        let available = Arc::new(AtomicU64::new(10));
+        let (respond, responses) = mpsc::channel();
+        let fetch = |min_bytes| TestFetch {
+            available: Arc::clone(&available),
+            min_bytes,
+            respond: respond.clone(),
+            on_check: Arc::default(),
+        };
+
+        assert!(purgatory.try_complete_else_watch(fetch(5), vec![tp(0)], 500));
+        assert_eq!(responses.try_recv(), Ok((10, false)));
+
+        assert!(!purgatory.try_complete_else_watch(fetch(100), vec![tp(0), tp(1)], 500));
+        assert_eq!((purgatory.delayed(), purgatory.watched_keys()), (1, 2));
+        assert_eq!(purgatory.check_and_complete(&tp(0)), 0);
+        assert!(responses.try_recv().is_err());
+
+        available.store(150, Ordering::SeqCst);
+        assert_eq!(purgatory.check_and_complete(&tp(1)), 1);
+        assert_eq!(responses.try_recv(), Ok((150, false)));
+        assert_eq!((purgatory.delayed(), purgatory.watched_keys()), (0, 0));
+
+        clock.advance(1_000);
+        assert_eq!(purgatory.expire(), 0);
+        assert!(responses.try_recv().is_err());
+    }
+
+    #[test]
+    fn expires_operations_once_their_timeout_passes() {
+        let clock = MockClock::new(1_000);
+        let purgatory = Purgatory::new("produce", clock.clone());
+        let available = Arc::new(AtomicU64::new(0));
+        let (respond, responses) = mpsc::channel();
+        for (partition, timeout_ms) in [(0, 100), (1, 300)] {
+            let fetch = TestFetch {
+                available: Arc::clone(&available),
+                min_bytes: 1,
+                respond: respond.clone(),
+                on_check: Arc::default(),
+            };
+            purgatory.try_complete_else_watch(fetch, vec![tp(partition)], timeout_ms);
+        }
+
+        clock.advance(99);
+        assert_eq!(purgatory.expire(), 0);
+        clock.advance(1);
+        assert_eq!(purgatory.expire(), 1);
+        assert_eq!(responses.try_recv(), Ok((0, true)));
+        assert_eq!(purgatory.check_and_complete(&tp(0)), 0);
+
+        clock.advance(200);
+        assert_eq!(purgatory.expire(), 1);
+        assert_eq!(responses.try_recv(), Ok((0, true)));
+        assert_eq!((purgatory.delayed(), purgatory.watched_keys()), (0, 0));
+    }
+
+    #[test]
+    fn data_arriving_during_a_check_is_not_missed() {
+        let purgatory = Arc::new(Purgatory::new("fetch", MockClock::new(0)));
+        let available = Arc::new(AtomicU64::new(0));
+        let on_check: Arc<Mutex<Option<OnCheck>>> = Arc::default();
+        let (respond, responses) = mpsc::channel();
+        let fetch = |min_bytes| TestFetch {
+            available: Arc::clone(&available),
+            min_bytes,
+            respond: respond.clone(),
+            on_check: Arc::clone(&on_check),
+        };
+        let arrive_during_next_check = |bytes| {
+            let (purgatory, available) = (Arc::clone(&purgatory), Arc::clone(&available));
+            *on_check.lock().unwrap() = Some(Box::new(move || {
+                available.fetch_add(bytes, Ordering::SeqCst);
+                purgatory.check_and_complete(&tp(0));
+            }));
+        };
+
+        // The data arrives before the fetch is watching its partition.
+        arrive_during_next_check(10);
+        assert!(purgatory.try_complete_else_watch(fetch(10), vec![tp(0)], 500));
+        assert_eq!(responses.try_recv(), Ok((10, false)));
+
+        // The data arrives while the parked fetch is being checked.
+        assert!(!purgatory.try_complete_else_watch(fetch(20), vec![tp(0)], 500));
+        arrive_during_next_check(10);
+        assert_eq!(purgatory.check_and_complete(&tp(0)), 1);
+        assert_eq!(responses.try_recv(), Ok((20, false)));
+        assert_eq!((purgatory.delayed(), purgatory.watched_keys()), (0, 0));
+    }
+}
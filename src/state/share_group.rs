@@ -0,0 +1,545 @@
+use super::consumer_group::TopicMetadata;
+use crate::protocol::error;
+use crate::protocol::share_fetch::{acknowledge_type, AcquiredRecords};
+use crate::storage::batch::{self, BatchHeader};
+use crate::storage::{Log, TopicPartition};
+use std::collections::{BTreeMap, BTreeSet};
+use std::io;
+
+/// Stage of a share group (KIP-932).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShareGroupState {
+    Empty,
+    Stable,
+}
+
+impl ShareGroupState {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Empty => "Empty",
+            Self::Stable => "Stable",
+        }
+    }
+}
+
+/// How long records stay acquired and how often they are delivered before
+/// they are archived.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DeliveryLimits {
+    pub record_lock_duration_ms: i64,
+    pub delivery_count_limit: i16,
+}
+
+/// A share session: the partitions a member fetches from and the epoch its
+/// next ShareFetch or ShareAcknowledge must carry.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShareSession {
+    pub next_epoch: i32,
+    pub partitions: BTreeSet<TopicPartition>,
+}
+
+impl ShareSession {
+    pub fn new() -> Self {
+        Self {
+            next_epoch: 1,
+            partitions: BTreeSet::new(),
+        }
+    }
+
+    /// Moves to the epoch the next request must carry, skipping the initial
+    /// and final epochs on overflow.
+    pub fn bump_epoch(&mut self) {
+        self.next_epoch = self.next_epoch.checked_add(1).unwrap_or(1);
+    }
+}
+
+impl Default for ShareSession {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShareMember {
+    pub member_id: String,
+    pub rack_id: Option<String>,
+    pub client_id: String,
+    pub client_host: String,
+    pub member_epoch: i32,
+    pub subscribed_topic_names: Vec<String>,
+    /// Partitions the member may fetch from, possibly alongside others.
+    pub assigned: BTreeSet<TopicPartition>,
+    pub session: Option<ShareSession>,
+    /// Bumped on every heartbeat, so a pending session expiry can tell it was
+    /// superseded.
+    pub heartbeats: u64,
+}
+
+impl ShareMember {
+    pub fn new(member_id: &str) -> Self {
+        Self {
+            member_id: member_id.to_string(),
+            rack_id: None,
+            client_id: String::new(),
+            client_host: String::new(),
+            member_epoch: 0,
+            subscribed_topic_names: Vec::new(),
+            assigned: BTreeSet::new(),
+            session: None,
+            heartbeats: 0,
+        }
+    }
+}
+
+/// A group whose members consume records cooperatively rather than owning
+/// partitions: each record is acquired by one member at a time under a lock
+/// and must be acknowledged before the lock expires.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ShareGroup {
+    /// Bumped whenever membership, subscriptions or subscribed topics change.
+    pub group_epoch: i32,
+    /// Group epoch the target assignment was computed at.
+    pub assignment_epoch: i32,
+    pub members: BTreeMap<String, ShareMember>,
+    pub target_assignment: BTreeMap<String, BTreeSet<TopicPartition>>,
+    /// Partition counts of the subscribed topics at the last epoch bump.
+    pub subscribed_topics: BTreeMap<String, i32>,
+    /// Delivery state of each partition the group has fetched from.
+    pub partitions: BTreeMap<TopicPartition, SharePartition>,
+}
+
+impl ShareGroup {
+    pub fn state(&self) -> ShareGroupState {
+        if self.members.is_empty() {
+            ShareGroupState::Empty
+        } else {
+            ShareGroupState::Stable
+        }
+    }
+
+    pub fn is_subscribed_to(&self, topic: &str) -> bool {
+        self.members.values().any(|member| {
+            member
+                .subscribed_topic_names
+                .iter()
+                .any(|name| name == topic)
+        })
+    }
+
+    /// Removes a member, making the records it holds available again, and
+    /// bumps the group epoch so its partitions are reassigned.
+    pub fn remove_member(
+        &mut self,
+        member_id: &str,
+        limits: DeliveryLimits,
+    ) -> Option<ShareMember> {
+        let member = self.members.remove(member_id)?;
+        self.release_member(member_id, limits);
+        self.target_assignment.remove(member_id);
+        self.group_epoch += 1;
+        Some(member)
+    }
+
+    /// Makes the records `member_id` holds available to other members.
+    pub fn release_member(&mut self, member_id: &str, limits: DeliveryLimits) {
+        for partition in self.partitions.values_mut() {
+            partition.release(member_id, limits);
+        }
+    }
+
+    /// Recomputes the target assignment if the group epoch moved past it,
+    /// bumping the epoch first if the subscribed topics changed in `topics`.
+    ///
+    /// Every partition of a subscribed topic goes to at least one of its
+    /// subscribers; when a topic has more subscribers than partitions, its
+    /// partitions are shared.
+    pub fn update_target_assignment(&mut self, topics: &BTreeMap<String, TopicMetadata>) {
+        let subscribed_topics: BTreeMap<String, i32> = self
+            .members
+            .values()
+            .flat_map(|member| &member.subscribed_topic_names)
+            .filter_map(|topic| Some((topic.clone(), topics.get(topic)?.partitions)))
+            .collect();
+        if subscribed_topics != self.subscribed_topics {
+            self.subscribed_topics = subscribed_topics;
+            if self.group_epoch <= self.assignment_epoch {
+                self.group_epoch += 1;
+            }
+        }
+        if self.group_epoch <= self.assignment_epoch {
+            return;
+        }
+
+        let mut target: BTreeMap<String, BTreeSet<TopicPartition>> = self
+            .members
+            .keys()
+            .map(|member_id| (member_id.clone(), BTreeSet::new()))
+            .collect();
+        for (topic, &partitions) in &self.subscribed_topics {
+            let subscribers: Vec<&String> = self
+                .members
+                .values()
+                .filter(|member| member.subscribed_topic_names.contains(topic))
+                .map(|member| &member.member_id)
+                .collect();
+            let partitions = usize::try_from(partitions).unwrap_or_default();
+            if subscribers.is_empty() || partitions == 0 {
+                continue;
+            }
+            for slot in 0..partitions.max(subscribers.len()) {
+                let tp = TopicPartition::new(topic.clone(), (slot % partitions) as i32);
+                let member_id = subscribers[slot % subscribers.len()];
+                target
+                    .get_mut(member_id)
+                    .expect("every member has a target")
+                    .insert(tp);
+            }
+        }
+        self.target_assignment = target;
+        self.assignment_epoch = self.group_epoch;
+    }
+
+    /// Moves a member to the target assignment, returning whether its
+    /// assignment changed. Shared partitions need no revocation, so the move
+    /// happens at once.
+    pub fn reconcile(&mut self, member_id: &str) -> bool {
+        let target = self
+            .target_assignment
+            .get(member_id)
+            .cloned()
+            .unwrap_or_default();
+        let assignment_epoch = self.assignment_epoch;
+        let Some(member) = self.members.get_mut(member_id) else {
+            return false;
+        };
+        member.member_epoch = assignment_epoch;
+        if member.assigned == target {
+            return false;
+        }
+        member.assigned = target;
+        true
+    }
+}
+
+/// Where a record is in its delivery.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecordState {
+    /// Waiting to be acquired, for the first time or again.
+    Available,
+    /// Locked by one member until it acknowledges it or the lock expires.
+    Acquired,
+    Acknowledged,
+    /// Rejected, delivered too often, or not a record at all.
+    Archived,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InFlightRecord {
+    pub state: RecordState,
+    pub delivery_count: i16,
+    /// Member holding the acquisition lock.
+    pub owner: Option<String>,
+    pub lock_deadline_ms: i64,
+}
+
+/// Delivery state of one partition in a share group.
+///
+/// Records between the share-partition start offset and `end_offset` are
+/// in flight; records before the start offset are all acknowledged or
+/// archived, and records from `end_offset` on were never acquired.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SharePartition {
+    pub start_offset: i64,
+    pub end_offset: i64,
+    pub records: BTreeMap<i64, InFlightRecord>,
+}
+
+impl SharePartition {
+    pub fn new(start_offset: i64) -> Self {
+        Self {
+            start_offset,
+            end_offset: start_offset,
+            records: BTreeMap::new(),
+        }
+    }
+
+    /// Acquires records for `member_id`: available records first, then new
+    /// ones up to the high watermark. Returns the batches holding them, read
+    /// from `log` up to `max_bytes`, and the acquired offset ranges; batches
+    /// may hold other records, which the client skips.
+    pub fn acquire(
+        &mut self,
+        member_id: &str,
+        log: &Log,
+        max_bytes: usize,
+        now_ms: i64,
+        limits: DeliveryLimits,
+    ) -> io::Result<(Vec<u8>, Vec<AcquiredRecords>)> {
+        self.expire_locks(now_ms, limits);
+        let log_start_offset = log.log_start_offset();
+        if self.start_offset < log_start_offset {
+            // Retention removed records that were never delivered.
+            self.records = self.records.split_off(&log_start_offset);
+            self.start_offset = log_start_offset;
+            self.end_offset = self.end_offset.max(log_start_offset);
+        }
+
+        let high_watermark = log.high_watermark();
+        let first_available = self
+            .records
+            .iter()
+            .find(|(_, record)| record.state == RecordState::Available)
+            .map(|(offset, _)| *offset);
+        let from = first_available.unwrap_or(self.end_offset);
+        if from >= high_watermark {
+            return Ok((Vec::new(), Vec::new()));
+        }
+
+        let bytes = log.read(from, max_bytes)?;
+        let mut records = Vec::new();
+        let mut acquired: Vec<AcquiredRecords> = Vec::new();
+        for batch in batch::split_batches(&bytes)? {
+            let header = BatchHeader::parse(batch)?;
+            if header.base_offset >= high_watermark {
+                break;
+            }
+            let mut batch_acquired = false;
+            for offset in header.base_offset.max(self.start_offset)..=header.last_offset() {
+                if offset >= self.end_offset {
+                    let state = if header.is_control() {
+                        RecordState::Archived
+                    } else {
+                        RecordState::Available
+                    };
+                    self.records.insert(
+                        offset,
+                        InFlightRecord {
+                            state,
+                            delivery_count: 0,
+                            owner: None,
+                            lock_deadline_ms: 0,
+                        },
+                    );
+                }
+                let Some(record) = self
+                    .records
+                    .get_mut(&offset)
+                    .filter(|record| record.state == RecordState::Available)
+                else {
+                    continue;
+                };
+                record.state = RecordState::Acquired;
+                record.delivery_count += 1;
+                record.owner = Some(member_id.to_string());
+                record.lock_deadline_ms = now_ms + limits.record_lock_duration_ms;
+                push_acquired(&mut acquired, offset, record.delivery_count);
+                batch_acquired = true;
+            }
+            self.end_offset = self.end_offset.max(header.last_offset() + 1);
+            if batch_acquired {
+                records.extend_from_slice(batch);
+            }
+        }
+        self.advance_start_offset();
+        Ok((records, acquired))
+    }
+
+    /// Applies a member's acknowledgement of `first_offset..=last_offset`,
+    /// with one acknowledge type for the whole range or one per offset.
+    /// Nothing changes unless every record in the range is acquired by
+    /// `member_id`.
+    pub fn acknowledge(
+        &mut self,
+        member_id: &str,
+        first_offset: i64,
+        last_offset: i64,
+        acknowledge_types: &[i8],
+        now_ms: i64,
+        limits: DeliveryLimits,
+    ) -> Result<(), i16> {
+        let per_offset = acknowledge_types.len() > 1;
+        if first_offset > last_offset
+            || acknowledge_types.is_empty()
+            || (per_offset
+                && i64::try_from(acknowledge_types.len()).ok()
+                    != Some(last_offset - first_offset + 1))
+            || acknowledge_types
+                .iter()
+                .any(|kind| !(acknowledge_type::GAP..=acknowledge_type::REJECT).contains(kind))
+        {
+            return Err(error::INVALID_REQUEST);
+        }
+        self.expire_locks(now_ms, limits);
+        for offset in first_offset..=last_offset {
+            let held = self.records.get(&offset).is_some_and(|record| {
+                record.state == RecordState::Acquired && record.owner.as_deref() == Some(member_id)
+            });
+            if !held {
+                return Err(error::INVALID_RECORD_STATE);
+            }
+        }
+
+        for (index, (_, record)) in self
+            .records
+            .range_mut(first_offset..=last_offset)
+            .enumerate()
+        {
+            let kind = if per_offset {
+                acknowledge_types[index]
+            } else {
+                acknowledge_types[0]
+            };
+            match kind {
+                acknowledge_type::ACCEPT => {
+                    record.state = RecordState::Acknowledged;
+                    record.owner = None;
+                }
+                acknowledge_type::RELEASE => release_record(record, limits),
+                _ => {
+                    record.state = RecordState::Archived;
+                    record.owner = None;
+                }
+            }
+        }
+        self.advance_start_offset();
+        Ok(())
+    }
+
+    /// Makes the records `member_id` holds available again.
+    pub fn release(&mut self, member_id: &str, limits: DeliveryLimits) {
+        for record in self.records.values_mut() {
+            if record.state == RecordState::Acquired && record.owner.as_deref() == Some(member_id) {
+                release_record(record, limits);
+            }
+        }
+        self.advance_start_offset();
+    }
+
+    /// Releases records whose acquisition lock expired.
+    pub fn expire_locks(&mut self, now_ms: i64, limits: DeliveryLimits) {
+        for record in self.records.values_mut() {
+            if record.state == RecordState::Acquired && record.lock_deadline_ms <= now_ms {
+                release_record(record, limits);
+            }
+        }
+        self.advance_start_offset();
+    }
+
+    /// Moves the start offset past the records at the front that are done
+    /// with.
+    fn advance_start_offset(&mut self) {
+        while let Some(entry) = self.records.first_entry() {
+            if !matches!(
+                entry.get().state,
+                RecordState::Acknowledged | RecordState::Archived
+            ) {
+                return;
+            }
+            self.start_offset = *entry.key() + 1;
+            entry.remove();
+        }
+        self.start_offset = self.end_offset;
+    }
+}
+
+/// Returns an acquired record to the available ones, or archives it once it
+/// reached the delivery count limit.
+fn release_record(record: &mut InFlightRecord, limits: DeliveryLimits) {
+    record.owner = None;
+    record.state = if record.delivery_count >= limits.delivery_count_limit {
+        RecordState::Archived
+    } else {
+        RecordState::Available
+    };
+}
+
+/// Adds `offset` to the last range if it extends it with the same delivery
+/// count, or starts a new range.
+fn push_acquired(acquired: &mut Vec<AcquiredRecords>, offset: i64, delivery_count: i16) {
+    if let Some(last) = acquired.last_mut() {
+        if last.last_offset + 1 == offset && last.delivery_count == delivery_count {
+            last.last_offset = offset;
+            return;
+        }
+    }
+    acquired.push(AcquiredRecords {
+        first_offset: offset,
+        last_offset: offset,
+        delivery_count,
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::batch::RecordBatchBuilder;
+    use crate::storage::LogConfig;
+    use crate::time::MockClock;
+
+    const LIMITS: DeliveryLimits = DeliveryLimits {
+        record_lock_duration_ms: 30_000,
+        delivery_count_limit: 2,
+    };
+
+    #[test]
+    fn records_are_redelivered_until_acknowledged_or_archived() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let clock = MockClock::new(0);
+        let mut log = Log::open(dir.path(), LogConfig::default(), clock).expect("open log");
+        let batch = RecordBatchBuilder::new(0)
+            .record(0, None, Some(b"a"))
+            .record(0, None, Some(b"b"))
+            .record(0, None, Some(b"c"))
+            .build();
+        log.append(&batch).expect("append");
+        let mut partition = SharePartition::new(0);
+
+        let (records, acquired) = partition
+            .acquire("m-1", &log, 1 << 20, 0, LIMITS)
+            .expect("acquire");
+        assert_eq!(records, batch);
+        assert_eq!(
+            acquired,
+            vec![AcquiredRecords {
+                first_offset: 0,
+                last_offset: 2,
+                delivery_count: 1,
+            }]
+        );
+        let (_, nothing) = partition
+            .acquire("m-2", &log, 1 << 20, 0, LIMITS)
+            .expect("acquire");
+        assert!(nothing.is_empty());
+        assert_eq!(
+            partition.acknowledge("m-2", 0, 0, &[acknowledge_type::ACCEPT], 0, LIMITS),
+            Err(error::INVALID_RECORD_STATE)
+        );
+
+        let types = [
+            acknowledge_type::ACCEPT,
+            acknowledge_type::RELEASE,
+            acknowledge_type::ACCEPT,
+        ];
+        partition
+            .acknowledge("m-1", 0, 2, &types, 0, LIMITS)
+            .expect("acknowledge");
+        assert_eq!(partition.start_offset, 1);
+
+        // The released record goes to the next member; once its lock expires
+        // it has been delivered as often as allowed and is archived.
+        let (_, acquired) = partition
+            .acquire("m-2", &log, 1 << 20, 0, LIMITS)
+            .expect("acquire");
+        assert_eq!(
+            acquired,
+            vec![AcquiredRecords {
+                first_offset: 1,
+                last_offset: 1,
+                delivery_count: 2,
+            }]
+        );
+        partition.expire_locks(30_000, LIMITS);
+        assert_eq!(partition.start_offset, 3);
+        assert!(partition.records.is_empty());
+    }
+}
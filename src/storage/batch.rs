@@ -0,0 +1,534 @@
+use crate::codec::primitives;
+use std::io::{self, Cursor, Read};
+
+/// Size of the `base_offset` and `batch_length` fields that precede every batch.
+pub const LOG_OVERHEAD: usize = 12;
+
+/// Size of a v2 record batch header including the log overhead.
+pub const BATCH_HEADER_SIZE: usize = 61;
+
+pub const CURRENT_MAGIC: i8 = 2;
+const CRC_OFFSET: usize = 17;
+const ATTRIBUTES_OFFSET: usize = 21;
+const COMPRESSION_CODEC_MASK: i16 = 0x07;
+const TIMESTAMP_TYPE_MASK: i16 = 0x08;
+const CONTROL_FLAG_MASK: i16 = 0x20;
+const NO_PRODUCER_ID: i64 = -1;
+const NO_PRODUCER_EPOCH: i16 = -1;
+const NO_SEQUENCE: i32 = -1;
+/// Fewest bytes a record takes: its length, attributes, timestamp and offset
+/// deltas, key and value lengths and header count, one byte each.
+const MIN_RECORD_SIZE: usize = 7;
+/// Fewest bytes a record header takes: its key and value lengths.
+const MIN_HEADER_SIZE: usize = 2;
+
+/// Header fields of a v2 record batch as stored on disk and on the wire.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BatchHeader {
+    pub base_offset: i64,
+    pub batch_length: i32,
+    pub partition_leader_epoch: i32,
+    pub magic: i8,
+    pub crc: u32,
+    pub attributes: i16,
+    pub last_offset_delta: i32,
+    pub base_timestamp: i64,
+    pub max_timestamp: i64,
+    pub producer_id: i64,
+    pub producer_epoch: i16,
+    pub base_sequence: i32,
+    pub records_count: i32,
+}
+
+impl BatchHeader {
+    /// Parses the header at the start of `bytes`.
+    pub fn parse(bytes: &[u8]) -> io::Result<Self> {
+        if bytes.len() < BATCH_HEADER_SIZE {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "record batch header is truncated",
+            ));
+        }
+
+        let mut cursor = Cursor::new(bytes);
+        let base_offset = primitives::read_i64(&mut cursor)?;
+        let batch_length = primitives::read_i32(&mut cursor)?;
+        let partition_leader_epoch = primitives::read_i32(&mut cursor)?;
+        let mut magic = [0_u8; 1];
+        cursor.read_exact(&mut magic)?;
+        let crc = primitives::read_i32(&mut cursor)? as u32;
+
+        Ok(Self {
+            base_offset,
+            batch_length,
+            partition_leader_epoch,
+            magic: magic[0] as i8,
+            crc,
+            attributes: primitives::read_i16(&mut cursor)?,
+            last_offset_delta: primitives::read_i32(&mut cursor)?,
+            base_timestamp: primitives::read_i64(&mut cursor)?,
+            max_timestamp: primitives::read_i64(&mut cursor)?,
+            producer_id: primitives::read_i64(&mut cursor)?,
+            producer_epoch: primitives::read_i16(&mut cursor)?,
+            base_sequence: primitives::read_i32(&mut cursor)?,
+            records_count: primitives::read_i32(&mut cursor)?,
+        })
+    }
+
+    /// Offset of the last record in the batch.
+    pub fn last_offset(&self) -> i64 {
+        self.base_offset + i64::from(self.last_offset_delta)
+    }
+
+    /// Total number of bytes the batch occupies, including the log overhead.
+    pub fn total_size(&self) -> usize {
+        LOG_OVERHEAD + self.batch_length.max(0) as usize
+    }
+
+    pub fn is_compressed(&self) -> bool {
+        self.attributes & COMPRESSION_CODEC_MASK != 0
+    }
+
+    /// Returns `true` for transaction marker batches.
+    pub fn is_control(&self) -> bool {
+        self.attributes & CONTROL_FLAG_MASK != 0
+    }
+
+    /// Returns `true` when the broker stamped the batch with its append time,
+    /// which then applies to every record in it.
+    pub fn is_log_append_time(&self) -> bool {
+        self.attributes & TIMESTAMP_TYPE_MASK != 0
+    }
+}
+
+/// Offset of the first record in the batch at the start of `bytes` that
+/// carries the batch's max timestamp. Compressed batches are not decoded and
+/// report their last offset.
+pub fn offset_of_max_timestamp(bytes: &[u8]) -> io::Result<i64> {
+    let header = BatchHeader::parse(bytes)?;
+    if header.is_log_append_time() {
+        return Ok(header.base_offset);
+    }
+    if header.is_compressed() || header.records_count <= 1 {
+        return Ok(header.last_offset());
+    }
+    let found = read_records(bytes)?
+        .into_iter()
+        .find(|record| record.timestamp == header.max_timestamp);
+    Ok(found.map_or(header.last_offset(), |record| record.offset))
+}
+
+/// A single record inside a batch, with offsets and timestamps made absolute.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Record {
+    pub offset: i64,
+    pub timestamp: i64,
+    pub key: Option<Vec<u8>>,
+    pub value: Option<Vec<u8>>,
+    pub headers: Vec<RecordHeader>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecordHeader {
+    pub key: String,
+    pub value: Option<Vec<u8>>,
+}
+
+/// Decodes every record in the batch at the start of `bytes`.
+pub fn read_records(bytes: &[u8]) -> io::Result<Vec<Record>> {
+    let header = BatchHeader::parse(bytes)?;
+    if header.is_compressed() {
+        return Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "compressed record batches are not supported",
+        ));
+    }
+    let end = header.total_size().clamp(BATCH_HEADER_SIZE, bytes.len());
+    let mut cursor = Cursor::new(&bytes[BATCH_HEADER_SIZE..end]);
+    let records_count = bounded_count(&cursor, header.records_count, MIN_RECORD_SIZE, "record")?;
+    let mut records = Vec::with_capacity(records_count);
+
+    for _ in 0..records_count {
+        let _length = primitives::read_varint(&mut cursor)?;
+        let mut attributes = [0_u8; 1];
+        cursor.read_exact(&mut attributes)?;
+        let timestamp_delta = primitives::read_varlong(&mut cursor)?;
+        let offset_delta = primitives::read_varint(&mut cursor)?;
+        let key = read_varint_bytes(&mut cursor)?;
+        let value = read_varint_bytes(&mut cursor)?;
+
+        let header_count = primitives::read_varint(&mut cursor)?;
+        let header_count = bounded_count(&cursor, header_count, MIN_HEADER_SIZE, "header")?;
+        let mut headers = Vec::with_capacity(header_count);
+        for _ in 0..header_count {
+            let key = read_varint_bytes(&mut cursor)?.unwrap_or_default();
+            let key = String::from_utf8(key)
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+            let value = read_varint_bytes(&mut cursor)?;
+            headers.push(RecordHeader { key, value });
+        }
+
+        records.push(Record {
+            offset: header.base_offset + i64::from(offset_delta),
+            timestamp: header.base_timestamp + timestamp_delta,
+            key,
+            value,
+            headers,
+        });
+    }
+
+    Ok(records)
+}
+
+/// Checks that `batch` is a single well-formed v2 batch: its length matches
+/// the payload, its magic is current, its CRC matches its contents and its
+/// records count fits the payload. Failures are `InvalidData` errors.
+pub fn validate(batch: &[u8]) -> io::Result<BatchHeader> {
+    let header = BatchHeader::parse(batch)?;
+    let invalid = |message: String| Err(io::Error::new(io::ErrorKind::InvalidData, message));
+    if header.total_size() != batch.len() {
+        return invalid("record batch length does not match its payload".to_string());
+    }
+    if header.magic != CURRENT_MAGIC {
+        return invalid(format!("unsupported record batch magic {}", header.magic));
+    }
+    let crc = compute_crc(batch);
+    if crc != header.crc {
+        return invalid(format!(
+            "record batch CRC {:#010x} does not match its contents ({crc:#010x})",
+            header.crc
+        ));
+    }
+    // Compressed records may take less than a byte each, so only the sign of
+    // their count can be checked without decoding them.
+    if header.is_compressed() {
+        if header.records_count < 0 {
+            return invalid(format!("record count {} is negative", header.records_count));
+        }
+    } else {
+        let payload = Cursor::new(&batch[BATCH_HEADER_SIZE..]);
+        bounded_count(&payload, header.records_count, MIN_RECORD_SIZE, "record")?;
+    }
+    Ok(header)
+}
+
+/// Splits a buffer of consecutive batches, such as a segment's contents, into
+/// one slice per batch.
+pub fn split_batches(mut bytes: &[u8]) -> io::Result<Vec<&[u8]>> {
+    let mut batches = Vec::new();
+    while !bytes.is_empty() {
+        let size = BatchHeader::parse(bytes)?.total_size();
+        if size < BATCH_HEADER_SIZE || size > bytes.len() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "record batch length does not match its payload",
+            ));
+        }
+        let (batch, rest) = bytes.split_at(size);
+        batches.push(batch);
+        bytes = rest;
+    }
+    Ok(batches)
+}
+
+/// Overwrites the base offset of the batch at the start of `batch`.
+///
+/// The base offset is not covered by the CRC, so this does not invalidate the batch.
+pub fn set_base_offset(batch: &mut [u8], base_offset: i64) {
+    batch[0..8].copy_from_slice(&base_offset.to_be_bytes());
+}
+
+/// Computes the CRC-32C checksum a v2 batch declares for its contents.
+pub fn compute_crc(batch: &[u8]) -> u32 {
+    crc32c::crc32c(&batch[ATTRIBUTES_OFFSET..])
+}
+
+/// Encodes records into a v2 record batch.
+#[derive(Debug, Clone)]
+pub struct RecordBatchBuilder {
+    base_offset: i64,
+    partition_leader_epoch: i32,
+    attributes: i16,
+    producer_id: i64,
+    producer_epoch: i16,
+    base_sequence: i32,
+    last_offset_delta: Option<i32>,
+    records: Vec<PendingRecord>,
+}
+
+#[derive(Debug, Clone)]
+struct PendingRecord {
+    offset_delta: i32,
+    timestamp: i64,
+    key: Option<Vec<u8>>,
+    value: Option<Vec<u8>>,
+    headers: Vec<RecordHeader>,
+}
+
+impl RecordBatchBuilder {
+    pub fn new(base_offset: i64) -> Self {
+        Self {
+            base_offset,
+            partition_leader_epoch: -1,
+            attributes: 0,
+            producer_id: NO_PRODUCER_ID,
+            producer_epoch: NO_PRODUCER_EPOCH,
+            base_sequence: NO_SEQUENCE,
+            last_offset_delta: None,
+            records: Vec::new(),
+        }
+    }
+
+    /// Starts a batch that replaces the one described by `header`, keeping its
+    /// offset range, leader epoch and producer fields so that a subset of its
+    /// records can be written back with their original offsets.
+    pub fn rewrite(header: &BatchHeader) -> Self {
+        Self {
+            base_offset: header.base_offset,
+            partition_leader_epoch: header.partition_leader_epoch,
+            attributes: header.attributes & !COMPRESSION_CODEC_MASK,
+            producer_id: header.producer_id,
+            producer_epoch: header.producer_epoch,
+            base_sequence: header.base_sequence,
+            last_offset_delta: Some(header.last_offset_delta),
+            records: Vec::new(),
+        }
+    }
+
+    pub fn partition_leader_epoch(mut self, epoch: i32) -> Self {
+        self.partition_leader_epoch = epoch;
+        self
+    }
+
+    pub fn producer(mut self, producer_id: i64, producer_epoch: i16, base_sequence: i32) -> Self {
+        self.producer_id = producer_id;
+        self.producer_epoch = producer_epoch;
+        self.base_sequence = base_sequence;
+        self
+    }
+
+    /// Marks the batch as a control batch, such as a transaction marker or a
+    /// snapshot header.
+    pub fn control(mut self) -> Self {
+        self.attributes |= CONTROL_FLAG_MASK;
+        self
+    }
+
+    pub fn record(self, timestamp: i64, key: Option<&[u8]>, value: Option<&[u8]>) -> Self {
+        self.record_with_headers(timestamp, key, value, Vec::new())
+    }
+
+    pub fn record_with_headers(
+        mut self,
+        timestamp: i64,
+        key: Option<&[u8]>,
+        value: Option<&[u8]>,
+        headers: Vec<RecordHeader>,
+    ) -> Self {
+        self.records.push(PendingRecord {
+            offset_delta: self.records.len() as i32,
+            timestamp,
+            key: key.map(<[u8]>::to_vec),
+            value: value.map(<[u8]>::to_vec),
+            headers,
+        });
+        self
+    }
+
+    /// Adds a decoded record at its original offset.
+    pub fn existing_record(mut self, record: &Record) -> Self {
+        self.records.push(PendingRecord {
+            offset_delta: (record.offset - self.base_offset) as i32,
+            timestamp: record.timestamp,
+            key: record.key.clone(),
+            value: record.value.clone(),
+            headers: record.headers.clone(),
+        });
+        self
+    }
+
+    pub fn build(self) -> Vec<u8> {
+        let base_timestamp = self.records.first().map_or(0, |record| record.timestamp);
+        let max_timestamp = self
+            .records
+            .iter()
+            .map(|record| record.timestamp)
+            .max()
+            .unwrap_or(base_timestamp);
+        let last_offset_delta = self
+            .last_offset_delta
+            .unwrap_or_else(|| self.records.last().map_or(0, |record| record.offset_delta));
+
+        let mut body = Vec::new();
+        for pending in &self.records {
+            let mut record = vec![0];
+            primitives::write_varlong(&mut record, pending.timestamp - base_timestamp);
+            primitives::write_varint(&mut record, pending.offset_delta);
+            write_varint_bytes(&mut record, pending.key.as_deref());
+            write_varint_bytes(&mut record, pending.value.as_deref());
+            primitives::write_varint(&mut record, pending.headers.len() as i32);
+            for header in &pending.headers {
+                write_varint_bytes(&mut record, Some(header.key.as_bytes()));
+                write_varint_bytes(&mut record, header.value.as_deref());
+            }
+
+            primitives::write_varint(&mut body, record.len() as i32);
+            body.extend_from_slice(&record);
+        }
+
+        let mut batch = Vec::with_capacity(BATCH_HEADER_SIZE + body.len());
+        batch.extend_from_slice(&self.base_offset.to_be_bytes());
+        let batch_length = (BATCH_HEADER_SIZE - LOG_OVERHEAD + body.len()) as i32;
+        batch.extend_from_slice(&batch_length.to_be_bytes());
+        batch.extend_from_slice(&self.partition_leader_epoch.to_be_bytes());
+        batch.push(CURRENT_MAGIC as u8);
+        batch.extend_from_slice(&0_u32.to_be_bytes());
+        batch.extend_from_slice(&self.attributes.to_be_bytes());
+        batch.extend_from_slice(&last_offset_delta.to_be_bytes());
+        batch.extend_from_slice(&base_timestamp.to_be_bytes());
+        batch.extend_from_slice(&max_timestamp.to_be_bytes());
+        batch.extend_from_slice(&self.producer_id.to_be_bytes());
+        batch.extend_from_slice(&self.producer_epoch.to_be_bytes());
+        batch.extend_from_slice(&self.base_sequence.to_be_bytes());
+        batch.extend_from_slice(&(self.records.len() as i32).to_be_bytes());
+        batch.extend_from_slice(&body);
+
+        let crc = compute_crc(&batch);
+        batch[CRC_OFFSET..ATTRIBUTES_OFFSET].copy_from_slice(&crc.to_be_bytes());
+        batch
+    }
+}
+
+/// Checks a count read from a batch against the bytes left after `cursor`,
+/// so a corrupt count fails instead of sizing an allocation.
+fn bounded_count(
+    cursor: &Cursor<&[u8]>,
+    count: i32,
+    min_size: usize,
+    what: &str,
+) -> io::Result<usize> {
+    let remaining = cursor.get_ref().len() - cursor.position() as usize;
+    match usize::try_from(count) {
+        Ok(count) if count <= remaining / min_size => Ok(count),
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{what} count {count} does not fit the remaining {remaining} bytes"),
+        )),
+    }
+}
+
+fn read_varint_bytes(cursor: &mut Cursor<&[u8]>) -> io::Result<Option<Vec<u8>>> {
+    let length = primitives::read_varint(cursor)?;
+    if length < 0 {
+        return Ok(None);
+    }
+
+    let remaining = cursor.get_ref().len() - cursor.position() as usize;
+    if length as usize > remaining {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            format!("field of {length} bytes overruns the record batch"),
+        ));
+    }
+    let mut buffer = vec![0_u8; length as usize];
+    cursor.read_exact(&mut buffer)?;
+    Ok(Some(buffer))
+}
+
+fn write_varint_bytes(buffer: &mut Vec<u8>, value: Option<&[u8]>) {
+    match value {
+        Some(bytes) => {
+            primitives::write_varint(buffer, bytes.len() as i32);
+            buffer.extend_from_slice(bytes);
+        }
+        None => primitives::write_varint(buffer, -1),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builder_output_parses_back() {
+        let batch = RecordBatchBuilder::new(42)
+            .record(1_000, Some(b"k1"), Some(b"v1"))
+            .record(1_005, None, Some(b"v2"))
+            .record(1_002, Some(b"k3"), None)
+            .build();
+
+        let header = BatchHeader::parse(&batch).expect("header should parse");
+        assert_eq!(header.base_offset, 42);
+        assert_eq!(header.last_offset(), 44);
+        assert_eq!(header.total_size(), batch.len());
+        assert_eq!(header.max_timestamp, 1_005);
+        assert_eq!(header.records_count, 3);
+        assert_eq!(header.crc, compute_crc(&batch));
+
+        let records = read_records(&batch).expect("records should decode");
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[1].offset, 43);
+        assert_eq!(records[1].timestamp, 1_005);
+        assert_eq!(records[1].key, None);
+        assert_eq!(records[2].value, None);
+    }
+
+    #[test]
+    fn set_base_offset_keeps_crc_valid() {
+        let mut batch = RecordBatchBuilder::new(0)
+            .record(1, Some(b"k"), Some(b"v"))
+            .build();
+
+        set_base_offset(&mut batch, 17);
+
+        let header = BatchHeader::parse(&batch).expect("header should parse");
+        assert_eq!(header.base_offset, 17);
+        assert_eq!(header.crc, compute_crc(&batch));
+    }
+
+    #[test]
+    fn rewritten_batch_keeps_original_offsets() {
+        let original = RecordBatchBuilder::new(10)
+            .producer(5, 0, 0)
+            .record(1_000, Some(b"a"), Some(b"1"))
+            .record(1_001, Some(b"b"), Some(b"2"))
+            .record(1_002, Some(b"c"), Some(b"3"))
+            .build();
+        let header = BatchHeader::parse(&original).expect("header should parse");
+        let records = read_records(&original).expect("records should decode");
+
+        let rewritten = RecordBatchBuilder::rewrite(&header)
+            .existing_record(&records[1])
+            .build();
+
+        let rewritten_header = BatchHeader::parse(&rewritten).expect("header should parse");
+        assert_eq!(rewritten_header.base_offset, 10);
+        assert_eq!(rewritten_header.last_offset(), 12);
+        assert_eq!(rewritten_header.producer_id, 5);
+        assert_eq!(rewritten_header.records_count, 1);
+        let kept = read_records(&rewritten).expect("records should decode");
+        assert_eq!(kept, vec![records[1].clone()]);
+
+        let mut both = original.clone();
+        both.extend_from_slice(&rewritten);
+        assert_eq!(split_batches(&both).expect("split").len(), 2);
+    }
+
+    #[test]
+    fn read_records_rejects_counts_past_the_payload() {
+        let mut batch = RecordBatchBuilder::new(0)
+            .record(1, Some(b"k"), Some(b"v"))
+            .build();
+        batch[57..61].copy_from_slice(&i32::MAX.to_be_bytes());
+
+        let err = read_records(&batch).expect_err("count should be rejected");
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn parse_rejects_truncated_header() {
+        let batch = RecordBatchBuilder::new(0).record(1, None, None).build();
+        let err = BatchHeader::parse(&batch[..20]).expect_err("parse should fail");
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+    }
+}
@@ -0,0 +1,128 @@
+use super::TopicPartition;
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+const CURRENT_VERSION: i32 = 0;
+
+/// A Kafka offset checkpoint file such as `recovery-point-offset-checkpoint`.
+///
+/// The format is a version line, an entry count line and one
+/// `topic partition offset` line per entry.
+#[derive(Debug, Clone)]
+pub struct OffsetCheckpointFile {
+    path: PathBuf,
+}
+
+impl OffsetCheckpointFile {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Reads the checkpoint, returning an empty map if the file does not exist.
+    pub fn read(&self) -> io::Result<BTreeMap<TopicPartition, i64>> {
+        let contents = match fs::read_to_string(&self.path) {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(BTreeMap::new()),
+            Err(err) => return Err(err),
+        };
+
+        let mut lines = contents.lines();
+        let version: i32 = self.parse_field(lines.next(), "version")?;
+        if version != CURRENT_VERSION {
+            return Err(self.malformed(&format!("unsupported version {version}")));
+        }
+        let expected: usize = self.parse_field(lines.next(), "entry count")?;
+
+        let mut offsets = BTreeMap::new();
+        for line in lines.filter(|line| !line.trim().is_empty()) {
+            let mut fields = line.split_whitespace();
+            let topic = fields
+                .next()
+                .ok_or_else(|| self.malformed("missing topic"))?;
+            let partition = self.parse_field(fields.next(), "partition")?;
+            let offset = self.parse_field(fields.next(), "offset")?;
+            offsets.insert(TopicPartition::new(topic, partition), offset);
+        }
+
+        if offsets.len() != expected {
+            return Err(self.malformed(&format!(
+                "expected {expected} entries but found {}",
+                offsets.len()
+            )));
+        }
+        Ok(offsets)
+    }
+
+    /// Atomically replaces the checkpoint with `offsets`.
+    pub fn write(&self, offsets: &BTreeMap<TopicPartition, i64>) -> io::Result<()> {
+        let mut contents = format!("{CURRENT_VERSION}\n{}\n", offsets.len());
+        for (tp, offset) in offsets {
+            contents.push_str(&format!("{} {} {offset}\n", tp.topic, tp.partition));
+        }
+
+        let tmp = self.path.with_extension("tmp");
+        let mut file = File::create(&tmp)?;
+        file.write_all(contents.as_bytes())?;
+        file.sync_all()?;
+        fs::rename(&tmp, &self.path)
+    }
+
+    fn parse_field<T: std::str::FromStr>(&self, field: Option<&str>, name: &str) -> io::Result<T> {
+        field
+            .and_then(|value| value.trim().parse().ok())
+            .ok_or_else(|| self.malformed(&format!("invalid {name}")))
+    }
+
+    fn malformed(&self, reason: &str) -> io::Error {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("malformed checkpoint {}: {reason}", self.path.display()),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn write_then_read_round_trips() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let checkpoint =
+            OffsetCheckpointFile::new(dir.path().join("recovery-point-offset-checkpoint"));
+        let mut offsets = BTreeMap::new();
+        offsets.insert(TopicPartition::new("orders", 0), 42);
+        offsets.insert(TopicPartition::new("orders", 1), 7);
+
+        checkpoint.write(&offsets).expect("write");
+
+        let contents = fs::read_to_string(checkpoint.path()).expect("read file");
+        assert_eq!(contents, "0\n2\norders 0 42\norders 1 7\n");
+        assert_eq!(checkpoint.read().expect("read"), offsets);
+    }
+
+    #[test]
+    fn missing_file_reads_as_empty() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let checkpoint = OffsetCheckpointFile::new(dir.path().join("absent"));
+        assert!(checkpoint.read().expect("read").is_empty());
+    }
+
+    #[test]
+    fn mismatched_entry_count_is_rejected() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let path = dir.path().join("checkpoint");
+        fs::write(&path, "0\n2\norders 0 42\n").expect("write");
+
+        let err = OffsetCheckpointFile::new(path)
+            .read()
+            .expect_err("read should fail");
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
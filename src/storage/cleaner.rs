@@ -0,0 +1,360 @@
+use super::batch::{self, BatchHeader, Record, RecordBatchBuilder};
+use super::Log;
+use std::collections::HashMap;
+use std::io;
+
+pub const CLEANER_CHECKPOINT_FILE: &str = "cleaner-offset-checkpoint";
+
+/// Outcome of compacting one log.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CleanerStats {
+    /// Offset up to which the log is now compacted; the next pass treats records
+    /// from here on as dirty.
+    pub end_offset: i64,
+    pub segments_rewritten: usize,
+    pub records_removed: usize,
+    pub bytes_before: u64,
+    pub bytes_after: u64,
+}
+
+/// Share of the closed segments that holds records the cleaner has not seen yet.
+///
+/// A closed segment counts as dirty once any of its offsets is at or past
+/// `first_dirty_offset`.
+pub fn dirty_ratio(log: &Log, first_dirty_offset: i64) -> f64 {
+    let (clean, dirty) = closed_segment_ranges(log).into_iter().fold(
+        (0_u64, 0_u64),
+        |(clean, dirty), (_, end, size)| {
+            if end > first_dirty_offset {
+                (clean, dirty + size)
+            } else {
+                (clean + size, dirty)
+            }
+        },
+    );
+    if clean + dirty == 0 {
+        return 0.0;
+    }
+    dirty as f64 / (clean + dirty) as f64
+}
+
+/// Compacts the closed segments of `log`, keeping only the latest record for
+/// each key.
+///
+/// Keys are collected from records at or after `first_dirty_offset`; older
+/// records for those keys are removed everywhere in the closed segments.
+/// Tombstones are kept until the segment holding them is older than
+/// `delete.retention.ms` relative to `now_ms`, so consumers reading from the
+/// start have a chance to observe the delete. Records without a key and
+/// transaction markers are always kept, and so are compressed batches, whose
+/// records are not decoded.
+///
+/// Returns `None` without touching the log when its dirty ratio is below
+/// `min.cleanable.dirty.ratio` or there is nothing to clean.
+pub fn clean(
+    log: &mut Log,
+    first_dirty_offset: i64,
+    now_ms: i64,
+) -> io::Result<Option<CleanerStats>> {
+    let first_dirty_offset = first_dirty_offset.max(log.log_start_offset());
+    let end_offset = log.active_segment_base_offset();
+    if first_dirty_offset >= end_offset
+        || dirty_ratio(log, first_dirty_offset) < log.config().min_cleanable_dirty_ratio
+    {
+        return Ok(None);
+    }
+
+    let segments = closed_segment_ranges(log);
+    let mut latest_offsets = HashMap::new();
+    for (base_offset, end, _) in &segments {
+        if *end <= first_dirty_offset {
+            continue;
+        }
+        for_each_record(&read_segment(log, *base_offset)?, |record| {
+            if let Some(key) = &record.key {
+                if record.offset >= first_dirty_offset {
+                    latest_offsets.insert(key.clone(), record.offset);
+                }
+            }
+        })?;
+    }
+
+    let delete_horizon_ms = now_ms - log.config().delete_retention_ms;
+    let mut stats = CleanerStats {
+        end_offset,
+        segments_rewritten: 0,
+        records_removed: 0,
+        bytes_before: 0,
+        bytes_after: 0,
+    };
+    for (base_offset, _, _) in segments {
+        let retain_tombstones = log
+            .segments()
+            .find(|segment| segment.base_offset() == base_offset)
+            .map_or(Ok(true), |segment| {
+                segment
+                    .largest_timestamp()
+                    .map(|timestamp| timestamp >= delete_horizon_ms)
+            })?;
+        let contents = read_segment(log, base_offset)?;
+        let mut cleaned = Vec::with_capacity(contents.len());
+
+        for bytes in batch::split_batches(&contents)? {
+            let header = BatchHeader::parse(bytes)?;
+            if header.is_control() || header.is_compressed() {
+                cleaned.extend_from_slice(bytes);
+                continue;
+            }
+
+            let records = batch::read_records(bytes)?;
+            let retained: Vec<&Record> = records
+                .iter()
+                .filter(|record| should_retain(record, &latest_offsets, retain_tombstones))
+                .collect();
+            stats.records_removed += records.len() - retained.len();
+            if retained.len() == records.len() {
+                cleaned.extend_from_slice(bytes);
+            } else if !retained.is_empty() {
+                let builder = retained
+                    .into_iter()
+                    .fold(RecordBatchBuilder::rewrite(&header), |builder, record| {
+                        builder.existing_record(record)
+                    });
+                cleaned.extend_from_slice(&builder.build());
+            }
+        }
+
+        stats.bytes_before += contents.len() as u64;
+        stats.bytes_after += cleaned.len() as u64;
+        if cleaned.len() != contents.len() {
+            log.replace_segment(base_offset, &cleaned)?;
+            stats.segments_rewritten += 1;
+        }
+    }
+
+    Ok(Some(stats))
+}
+
+fn should_retain(
+    record: &Record,
+    latest_offsets: &HashMap<Vec<u8>, i64>,
+    retain_tombstones: bool,
+) -> bool {
+    let Some(key) = &record.key else {
+        return true;
+    };
+    let superseded = latest_offsets
+        .get(key)
+        .is_some_and(|latest| *latest > record.offset);
+    !superseded && (record.value.is_some() || retain_tombstones)
+}
+
+/// Returns `(base_offset, end_offset, size)` for every closed segment, where
+/// `end_offset` is the base offset of the following segment.
+fn closed_segment_ranges(log: &Log) -> Vec<(i64, i64, u64)> {
+    let segments: Vec<_> = log.segments().collect();
+    segments
+        .windows(2)
+        .map(|pair| {
+            (
+                pair[0].base_offset(),
+                pair[1].base_offset(),
+                u64::from(pair[0].size()),
+            )
+        })
+        .collect()
+}
+
+fn read_segment(log: &Log, base_offset: i64) -> io::Result<Vec<u8>> {
+    log.segments()
+        .find(|segment| segment.base_offset() == base_offset)
+        .map_or(Ok(Vec::new()), |segment| segment.read_all())
+}
+
+fn for_each_record(contents: &[u8], mut visit: impl FnMut(&Record)) -> io::Result<()> {
+    for bytes in batch::split_batches(contents)? {
+        let header = BatchHeader::parse(bytes)?;
+        if header.is_control() || header.is_compressed() {
+            continue;
+        }
+        for record in batch::read_records(bytes)? {
+            visit(&record);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::config::{CleanupPolicy, LogConfig};
+    use crate::time::{Clock, MockClock};
+
+    fn compact_config() -> LogConfig {
+        LogConfig {
+            segment_bytes: 256,
+            index_interval_bytes: 64,
+            cleanup_policy: CleanupPolicy::COMPACT,
+            delete_retention_ms: 1_000,
+            min_cleanable_dirty_ratio: 0.0,
+            ..LogConfig::default()
+        }
+    }
+
+    fn put(log: &mut Log, timestamp: i64, key: &str, value: Option<&str>) {
+        let batch = RecordBatchBuilder::new(0)
+            .record(timestamp, Some(key.as_bytes()), value.map(str::as_bytes))
+            .build();
+        log.append(&batch).expect("append");
+    }
+
+    /// Every record in the log as `(offset, key, value)`.
+    fn read_all(log: &Log) -> Vec<(i64, String, Option<String>)> {
+        let mut records = Vec::new();
+        for segment in log.segments() {
+            let contents = segment.read_all().expect("read segment");
+            for_each_record(&contents, |record| {
+                records.push((
+                    record.offset,
+                    String::from_utf8(record.key.clone().unwrap_or_default()).expect("utf8"),
+                    record
+                        .value
+                        .clone()
+                        .map(|value| String::from_utf8(value).expect("utf8")),
+                ));
+            })
+            .expect("decode");
+        }
+        records
+    }
+
+    #[test]
+    fn compaction_keeps_latest_record_per_key() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let clock = MockClock::new(0);
+        let mut log = Log::open(dir.path(), compact_config(), clock.clone()).expect("open");
+        for round in 0..5 {
+            for key in ["a", "b", "c"] {
+                put(&mut log, round, key, Some(&format!("{key}{round}")));
+            }
+        }
+        put(&mut log, 5, "z", Some("active"));
+        let end_offset = log.active_segment_base_offset();
+        let log_end_offset = log.log_end_offset();
+
+        let stats = clean(&mut log, 0, clock.now_ms())
+            .expect("clean")
+            .expect("log is dirty");
+
+        assert_eq!(stats.end_offset, end_offset);
+        assert!(stats.segments_rewritten > 0);
+        assert!(stats.bytes_after < stats.bytes_before);
+        let cleaned: Vec<_> = read_all(&log)
+            .into_iter()
+            .filter(|(offset, _, _)| *offset < end_offset)
+            .collect();
+        let keys: Vec<_> = cleaned.iter().map(|(_, key, _)| key.as_str()).collect();
+        assert_eq!(keys.iter().filter(|key| **key == "a").count(), 1);
+        assert!(cleaned.windows(2).all(|pair| pair[0].0 < pair[1].0));
+        assert_eq!(log.log_end_offset(), log_end_offset);
+
+        let last_b = read_all(&log)
+            .into_iter()
+            .rfind(|(_, key, _)| key == "b")
+            .expect("b retained");
+        let fetched = log.read(last_b.0, 1).expect("read by offset");
+        let header = BatchHeader::parse(&fetched).expect("header");
+        assert!(header.base_offset <= last_b.0 && last_b.0 <= header.last_offset());
+    }
+
+    #[test]
+    fn tombstones_are_removed_after_delete_retention() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let clock = MockClock::new(10_000);
+        let mut log = Log::open(dir.path(), compact_config(), clock.clone()).expect("open");
+        put(&mut log, 10_000, "a", Some("value"));
+        put(&mut log, 10_000, "a", None);
+        for i in 0..10 {
+            put(&mut log, 10_000, "filler", Some(&i.to_string()));
+        }
+
+        clean(&mut log, 0, clock.now_ms())
+            .expect("clean")
+            .expect("log is dirty");
+        let remaining = read_all(&log);
+        assert!(remaining
+            .iter()
+            .any(|(_, key, value)| key == "a" && value.is_none()));
+        assert!(!remaining
+            .iter()
+            .any(|(_, key, value)| key == "a" && value.is_some()));
+
+        clock.advance(1_001);
+        clean(&mut log, 0, clock.now_ms())
+            .expect("clean")
+            .expect("log is dirty");
+        assert!(!read_all(&log).iter().any(|(_, key, _)| key == "a"));
+    }
+
+    #[test]
+    fn compressed_batches_are_kept_unchanged() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let clock = MockClock::new(0);
+        let mut log = Log::open(dir.path(), compact_config(), clock.clone()).expect("open");
+        // Flag a plain batch as gzip; its records are never decoded.
+        let mut compressed = RecordBatchBuilder::new(0)
+            .record(0, Some(b"a"), Some(b"old"))
+            .build();
+        compressed[22] |= 1;
+        let crc = batch::compute_crc(&compressed);
+        compressed[17..21].copy_from_slice(&crc.to_be_bytes());
+        log.append(&compressed).expect("append compressed");
+        for round in 0..10 {
+            put(&mut log, round, "a", Some(&round.to_string()));
+        }
+
+        let stats = clean(&mut log, 0, clock.now_ms())
+            .expect("clean")
+            .expect("log is dirty");
+
+        assert!(stats.records_removed > 0);
+        let first = log.read(0, 1).expect("read compressed batch");
+        assert_eq!(first[..compressed.len()], compressed[..]);
+    }
+
+    #[test]
+    fn clean_logs_below_dirty_ratio_are_skipped() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let config = LogConfig {
+            min_cleanable_dirty_ratio: 0.5,
+            ..compact_config()
+        };
+        let mut log = Log::open(dir.path(), config, MockClock::new(0)).expect("open");
+        for i in 0..20 {
+            put(&mut log, 0, "k", Some(&i.to_string()));
+        }
+        let end_offset = log.active_segment_base_offset();
+
+        assert!((dirty_ratio(&log, 0) - 1.0).abs() < f64::EPSILON);
+        assert_eq!(dirty_ratio(&log, end_offset), 0.0);
+        assert!(clean(&mut log, end_offset, 0).expect("clean").is_none());
+        assert!(clean(&mut log, 0, 0).expect("clean").is_some());
+    }
+
+    #[test]
+    fn interrupted_swap_is_discarded_on_open() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let mut log = Log::open(dir.path(), compact_config(), MockClock::new(0)).expect("open");
+        for i in 0..20 {
+            put(&mut log, 0, "k", Some(&i.to_string()));
+        }
+        drop(log);
+        let leftover = dir.path().join("00000000000000000000.log.cleaned");
+        std::fs::write(&leftover, b"partial").expect("write leftover");
+
+        let log = Log::open(dir.path(), compact_config(), MockClock::new(0)).expect("reopen");
+
+        assert!(!leftover.exists());
+        assert_eq!(log.log_end_offset(), 20);
+    }
+}
@@ -0,0 +1,266 @@
+use std::collections::BTreeMap;
+use std::io;
+use std::str::FromStr;
+
+pub const SEGMENT_BYTES_CONFIG: &str = "segment.bytes";
+pub const SEGMENT_INDEX_BYTES_CONFIG: &str = "segment.index.bytes";
+pub const INDEX_INTERVAL_BYTES_CONFIG: &str = "index.interval.bytes";
+pub const FLUSH_MESSAGES_CONFIG: &str = "flush.messages";
+pub const FLUSH_MS_CONFIG: &str = "flush.ms";
+pub const RETENTION_MS_CONFIG: &str = "retention.ms";
+pub const RETENTION_BYTES_CONFIG: &str = "retention.bytes";
+pub const CLEANUP_POLICY_CONFIG: &str = "cleanup.policy";
+pub const DELETE_RETENTION_MS_CONFIG: &str = "delete.retention.ms";
+pub const MIN_CLEANABLE_DIRTY_RATIO_CONFIG: &str = "min.cleanable.dirty.ratio";
+
+/// Which of retention-based deletion and key-based compaction apply to a log.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CleanupPolicy {
+    pub delete: bool,
+    pub compact: bool,
+}
+
+impl CleanupPolicy {
+    pub const DELETE: Self = Self {
+        delete: true,
+        compact: false,
+    };
+    pub const COMPACT: Self = Self {
+        delete: false,
+        compact: true,
+    };
+
+    /// Parses a comma-separated list such as `compact,delete`.
+    fn parse(key: &str, value: &str) -> io::Result<Self> {
+        let mut policy = Self {
+            delete: false,
+            compact: false,
+        };
+        for name in value.split(',').map(str::trim) {
+            match name {
+                "delete" => policy.delete = true,
+                "compact" => policy.compact = true,
+                _ => return Err(invalid_value(key, value)),
+            }
+        }
+        Ok(policy)
+    }
+}
+
+/// Per-partition settings that control how a [`Log`](super::Log) lays out its segments.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogConfig {
+    /// Maximum size of a segment's `.log` file before a new segment is rolled.
+    pub segment_bytes: u64,
+    /// Approximate number of log bytes between two offset index entries.
+    pub index_interval_bytes: u32,
+    /// Maximum size of a segment's `.index` and `.timeindex` files.
+    pub segment_index_bytes: usize,
+    /// Number of appended messages after which the log is fsynced, or `None` to
+    /// leave flushing to the operating system.
+    pub flush_messages: Option<u64>,
+    /// Maximum time in milliseconds unflushed messages may sit in the page cache,
+    /// or `None` to leave flushing to the operating system.
+    pub flush_ms: Option<u64>,
+    /// Age in milliseconds after which a segment is deleted, or `None` to keep
+    /// segments regardless of age.
+    pub retention_ms: Option<i64>,
+    /// Maximum size of the partition log before its oldest segments are deleted,
+    /// or `None` for no size limit.
+    pub retention_bytes: Option<u64>,
+    /// Whether old segments are deleted, compacted, or both.
+    pub cleanup_policy: CleanupPolicy,
+    /// How long tombstones are kept in compacted segments before they are removed.
+    pub delete_retention_ms: i64,
+    /// Share of uncompacted bytes a log needs before the cleaner compacts it.
+    pub min_cleanable_dirty_ratio: f64,
+}
+
+impl LogConfig {
+    /// Returns a copy of this config with the given topic-level overrides applied.
+    ///
+    /// Keys that do not affect the log are ignored; invalid values are reported
+    /// with the offending key.
+    pub fn with_overrides(&self, overrides: &BTreeMap<String, String>) -> io::Result<Self> {
+        let mut config = self.clone();
+        for (key, value) in overrides {
+            match key.as_str() {
+                SEGMENT_BYTES_CONFIG => config.segment_bytes = parse_positive(key, value)?,
+                SEGMENT_INDEX_BYTES_CONFIG => {
+                    config.segment_index_bytes = parse_positive(key, value)?
+                }
+                INDEX_INTERVAL_BYTES_CONFIG => {
+                    config.index_interval_bytes = parse_value(key, value)?
+                }
+                FLUSH_MESSAGES_CONFIG => config.flush_messages = parse_flush_interval(key, value)?,
+                FLUSH_MS_CONFIG => config.flush_ms = parse_flush_interval(key, value)?,
+                RETENTION_MS_CONFIG => config.retention_ms = parse_retention(key, value)?,
+                RETENTION_BYTES_CONFIG => {
+                    config.retention_bytes =
+                        parse_retention(key, value)?.map(|bytes: i64| bytes as u64)
+                }
+                CLEANUP_POLICY_CONFIG => config.cleanup_policy = CleanupPolicy::parse(key, value)?,
+                DELETE_RETENTION_MS_CONFIG => {
+                    config.delete_retention_ms = parse_non_negative(key, value)?
+                }
+                MIN_CLEANABLE_DIRTY_RATIO_CONFIG => {
+                    config.min_cleanable_dirty_ratio = parse_ratio(key, value)?
+                }
+                _ => {}
+            }
+        }
+        Ok(config)
+    }
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        Self {
+            segment_bytes: 1024 * 1024 * 1024,
+            index_interval_bytes: 4096,
+            segment_index_bytes: 10 * 1024 * 1024,
+            flush_messages: None,
+            flush_ms: None,
+            retention_ms: Some(7 * 24 * 60 * 60 * 1000),
+            retention_bytes: None,
+            cleanup_policy: CleanupPolicy::DELETE,
+            delete_retention_ms: 24 * 60 * 60 * 1000,
+            min_cleanable_dirty_ratio: 0.5,
+        }
+    }
+}
+
+pub(crate) fn parse_value<T: FromStr>(key: &str, value: &str) -> io::Result<T> {
+    value.trim().parse().map_err(|_| invalid_value(key, value))
+}
+
+pub(crate) fn parse_positive<T: FromStr + PartialOrd + Default>(
+    key: &str,
+    value: &str,
+) -> io::Result<T> {
+    let parsed: T = parse_value(key, value)?;
+    if parsed <= T::default() {
+        return Err(invalid_value(key, value));
+    }
+    Ok(parsed)
+}
+
+pub(crate) fn invalid_value(key: &str, value: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidInput,
+        format!("invalid value '{value}' for configuration {key}"),
+    )
+}
+
+/// Parses a flush interval, treating Kafka's `Long.MAX_VALUE` default as "never".
+fn parse_flush_interval(key: &str, value: &str) -> io::Result<Option<u64>> {
+    let parsed: u64 = parse_positive(key, value)?;
+    Ok((parsed < i64::MAX as u64).then_some(parsed))
+}
+
+/// Parses a retention limit where `-1` disables the limit.
+fn parse_retention(key: &str, value: &str) -> io::Result<Option<i64>> {
+    match parse_value::<i64>(key, value)? {
+        -1 => Ok(None),
+        limit if limit >= 0 => Ok(Some(limit)),
+        _ => Err(invalid_value(key, value)),
+    }
+}
+
+fn parse_non_negative(key: &str, value: &str) -> io::Result<i64> {
+    let parsed: i64 = parse_value(key, value)?;
+    if parsed < 0 {
+        return Err(invalid_value(key, value));
+    }
+    Ok(parsed)
+}
+
+fn parse_ratio(key: &str, value: &str) -> io::Result<f64> {
+    let parsed: f64 = parse_value(key, value)?;
+    if !(0.0..=1.0).contains(&parsed) {
+        return Err(invalid_value(key, value));
+    }
+    Ok(parsed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn overrides(entries: &[(&str, &str)]) -> BTreeMap<String, String> {
+        entries
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn overrides_set_flush_policy() {
+        let config = LogConfig::default()
+            .with_overrides(&overrides(&[
+                (FLUSH_MESSAGES_CONFIG, "10"),
+                (FLUSH_MS_CONFIG, "500"),
+                ("cleanup.policy", "delete"),
+            ]))
+            .expect("valid overrides");
+
+        assert_eq!(config.flush_messages, Some(10));
+        assert_eq!(config.flush_ms, Some(500));
+    }
+
+    #[test]
+    fn long_max_flush_interval_means_os_managed() {
+        let config = LogConfig::default()
+            .with_overrides(&overrides(&[(FLUSH_MS_CONFIG, "9223372036854775807")]))
+            .expect("valid overrides");
+
+        assert_eq!(config.flush_ms, None);
+    }
+
+    #[test]
+    fn negative_one_disables_retention() {
+        let config = LogConfig::default()
+            .with_overrides(&overrides(&[
+                (RETENTION_MS_CONFIG, "-1"),
+                (RETENTION_BYTES_CONFIG, "1048576"),
+            ]))
+            .expect("valid overrides");
+
+        assert_eq!(config.retention_ms, None);
+        assert_eq!(config.retention_bytes, Some(1_048_576));
+
+        let err = LogConfig::default()
+            .with_overrides(&overrides(&[(RETENTION_BYTES_CONFIG, "-2")]))
+            .expect_err("negative size is rejected");
+        assert!(err.to_string().contains(RETENTION_BYTES_CONFIG));
+    }
+
+    #[test]
+    fn cleanup_policy_accepts_combined_policies() {
+        let config = LogConfig::default()
+            .with_overrides(&overrides(&[
+                (CLEANUP_POLICY_CONFIG, "compact, delete"),
+                (MIN_CLEANABLE_DIRTY_RATIO_CONFIG, "0.1"),
+            ]))
+            .expect("valid overrides");
+
+        assert!(config.cleanup_policy.compact);
+        assert!(config.cleanup_policy.delete);
+        assert_eq!(config.min_cleanable_dirty_ratio, 0.1);
+
+        let err = LogConfig::default()
+            .with_overrides(&overrides(&[(CLEANUP_POLICY_CONFIG, "archive")]))
+            .expect_err("unknown policy is rejected");
+        assert!(err.to_string().contains(CLEANUP_POLICY_CONFIG));
+    }
+
+    #[test]
+    fn invalid_value_names_the_key() {
+        let err = LogConfig::default()
+            .with_overrides(&overrides(&[(FLUSH_MESSAGES_CONFIG, "0")]))
+            .expect_err("zero is rejected");
+
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert!(err.to_string().contains(FLUSH_MESSAGES_CONFIG));
+    }
+}
@@ -0,0 +1,454 @@
+use memmap2::MmapMut;
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// A fixed-width, memory-mapped index file.
+///
+/// The file is preallocated to `max_size` while open and trimmed back to the
+/// valid entries when dropped, so a file left at its full size indicates that the
+/// broker did not shut down cleanly.
+#[derive(Debug)]
+struct MmapIndex {
+    path: PathBuf,
+    file: File,
+    mmap: MmapMut,
+    entry_size: usize,
+    entries: usize,
+    max_entries: usize,
+}
+
+impl MmapIndex {
+    fn open(path: &Path, entry_size: usize, max_size: usize) -> io::Result<Self> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+        let existing_len = file.metadata()?.len() as usize;
+        let max_entries = (max_size / entry_size).max(1);
+        let entries = (existing_len / entry_size).min(max_entries);
+        file.set_len((max_entries * entry_size) as u64)?;
+
+        // SAFETY: the index file is owned by this broker process and is only
+        // resized through this struct while the mapping is not being accessed.
+        let mmap = unsafe { MmapMut::map_mut(&file)? };
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            file,
+            mmap,
+            entry_size,
+            entries,
+            max_entries,
+        })
+    }
+
+    fn entry(&self, slot: usize) -> &[u8] {
+        let start = slot * self.entry_size;
+        &self.mmap[start..start + self.entry_size]
+    }
+
+    fn push(&mut self, entry: &[u8]) -> io::Result<()> {
+        if self.is_full() {
+            return Err(io::Error::other(format!(
+                "index {} is full",
+                self.path.display()
+            )));
+        }
+
+        let start = self.entries * self.entry_size;
+        self.mmap[start..start + self.entry_size].copy_from_slice(entry);
+        self.entries += 1;
+        Ok(())
+    }
+
+    fn truncate_entries(&mut self, entries: usize) {
+        let entries = entries.min(self.entries);
+        let start = entries * self.entry_size;
+        let end = self.entries * self.entry_size;
+        self.mmap[start..end].fill(0);
+        self.entries = entries;
+    }
+
+    fn is_full(&self) -> bool {
+        self.entries >= self.max_entries
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        self.mmap.flush()
+    }
+
+    /// Returns the slot of the largest entry whose key is `<= target`.
+    fn floor_slot(&self, target: i64, key: impl Fn(&[u8]) -> i64) -> Option<usize> {
+        if self.entries == 0 || key(self.entry(0)) > target {
+            return None;
+        }
+
+        let (mut low, mut high) = (0, self.entries - 1);
+        while low < high {
+            let mid = (low + high).div_ceil(2);
+            if key(self.entry(mid)) <= target {
+                low = mid;
+            } else {
+                high = mid - 1;
+            }
+        }
+        Some(low)
+    }
+}
+
+impl Drop for MmapIndex {
+    fn drop(&mut self) {
+        let _ = self.mmap.flush();
+        let _ = self.file.set_len((self.entries * self.entry_size) as u64);
+    }
+}
+
+/// Entry in an [`OffsetIndex`]: the last offset of a batch and its file position.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OffsetPosition {
+    pub offset: i64,
+    pub position: u32,
+}
+
+/// Sparse mapping from offsets to byte positions within a segment's `.log` file.
+#[derive(Debug)]
+pub struct OffsetIndex {
+    inner: MmapIndex,
+    base_offset: i64,
+}
+
+impl OffsetIndex {
+    const ENTRY_SIZE: usize = 8;
+
+    pub fn open(path: &Path, base_offset: i64, max_size: usize) -> io::Result<Self> {
+        Ok(Self {
+            inner: MmapIndex::open(path, Self::ENTRY_SIZE, max_size)?,
+            base_offset,
+        })
+    }
+
+    /// Finds the entry with the largest offset `<= target`, or the segment start.
+    pub fn lookup(&self, target: i64) -> OffsetPosition {
+        self.inner
+            .floor_slot(target, |entry| self.offset_of(entry))
+            .map(|slot| self.parse(self.inner.entry(slot)))
+            .unwrap_or(OffsetPosition {
+                offset: self.base_offset,
+                position: 0,
+            })
+    }
+
+    pub fn append(&mut self, offset: i64, position: u32) -> io::Result<()> {
+        if let Some(last) = self.last_entry() {
+            if offset <= last.offset {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!(
+                        "offset {offset} is not larger than last indexed {}",
+                        last.offset
+                    ),
+                ));
+            }
+        }
+
+        let relative = self.relative_offset(offset)?;
+        let mut entry = [0_u8; Self::ENTRY_SIZE];
+        entry[0..4].copy_from_slice(&relative.to_be_bytes());
+        entry[4..8].copy_from_slice(&position.to_be_bytes());
+        self.inner.push(&entry)
+    }
+
+    /// Removes every entry with an offset `>= offset`.
+    pub fn truncate_to(&mut self, offset: i64) {
+        let keep = match self
+            .inner
+            .floor_slot(offset - 1, |entry| self.offset_of(entry))
+        {
+            Some(slot) => slot + 1,
+            None => 0,
+        };
+        self.inner.truncate_entries(keep);
+    }
+
+    pub fn reset(&mut self) {
+        self.inner.truncate_entries(0);
+    }
+
+    pub fn last_entry(&self) -> Option<OffsetPosition> {
+        self.inner
+            .entries
+            .checked_sub(1)
+            .map(|slot| self.parse(self.inner.entry(slot)))
+    }
+
+    pub fn entries(&self) -> usize {
+        self.inner.entries
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.inner.is_full()
+    }
+
+    pub fn flush(&self) -> io::Result<()> {
+        self.inner.flush()
+    }
+
+    /// Verifies that entries are strictly increasing and within `log_size`.
+    pub fn sanity_check(&self, log_size: u32) -> io::Result<()> {
+        let mut previous: Option<OffsetPosition> = None;
+        for slot in 0..self.inner.entries {
+            let entry = self.parse(self.inner.entry(slot));
+            let ordered = previous.map_or(true, |prev| {
+                entry.offset > prev.offset && entry.position >= prev.position
+            });
+            if !ordered || entry.position >= log_size {
+                return Err(corrupt(&self.inner.path, slot));
+            }
+            previous = Some(entry);
+        }
+        Ok(())
+    }
+
+    fn offset_of(&self, entry: &[u8]) -> i64 {
+        self.base_offset + i64::from(u32::from_be_bytes(entry[0..4].try_into().unwrap()))
+    }
+
+    fn parse(&self, entry: &[u8]) -> OffsetPosition {
+        OffsetPosition {
+            offset: self.offset_of(entry),
+            position: u32::from_be_bytes(entry[4..8].try_into().unwrap()),
+        }
+    }
+
+    fn relative_offset(&self, offset: i64) -> io::Result<u32> {
+        u32::try_from(offset - self.base_offset).map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("offset {offset} is outside segment {}", self.base_offset),
+            )
+        })
+    }
+}
+
+/// Entry in a [`TimeIndex`]: a timestamp and the offset it was first reached at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimestampOffset {
+    pub timestamp: i64,
+    pub offset: i64,
+}
+
+/// Sparse mapping from timestamps to offsets within a segment.
+#[derive(Debug)]
+pub struct TimeIndex {
+    inner: MmapIndex,
+    base_offset: i64,
+}
+
+impl TimeIndex {
+    const ENTRY_SIZE: usize = 12;
+
+    pub fn open(path: &Path, base_offset: i64, max_size: usize) -> io::Result<Self> {
+        Ok(Self {
+            inner: MmapIndex::open(path, Self::ENTRY_SIZE, max_size)?,
+            base_offset,
+        })
+    }
+
+    /// Finds the entry with the largest timestamp `<= timestamp`, or the segment start.
+    pub fn lookup(&self, timestamp: i64) -> TimestampOffset {
+        self.inner
+            .floor_slot(timestamp, Self::timestamp_of)
+            .map(|slot| self.parse(self.inner.entry(slot)))
+            .unwrap_or(TimestampOffset {
+                timestamp: -1,
+                offset: self.base_offset,
+            })
+    }
+
+    /// Appends an entry if `timestamp` is larger than the last indexed timestamp.
+    pub fn maybe_append(&mut self, timestamp: i64, offset: i64) -> io::Result<()> {
+        if let Some(last) = self.last_entry() {
+            if timestamp <= last.timestamp {
+                return Ok(());
+            }
+        }
+
+        let relative = u32::try_from(offset - self.base_offset).map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("offset {offset} is outside segment {}", self.base_offset),
+            )
+        })?;
+        let mut entry = [0_u8; Self::ENTRY_SIZE];
+        entry[0..8].copy_from_slice(&timestamp.to_be_bytes());
+        entry[8..12].copy_from_slice(&relative.to_be_bytes());
+        self.inner.push(&entry)
+    }
+
+    /// Removes every entry pointing at an offset `>= offset`.
+    pub fn truncate_to(&mut self, offset: i64) {
+        let mut keep = self.inner.entries;
+        while keep > 0 && self.parse(self.inner.entry(keep - 1)).offset >= offset {
+            keep -= 1;
+        }
+        self.inner.truncate_entries(keep);
+    }
+
+    pub fn reset(&mut self) {
+        self.inner.truncate_entries(0);
+    }
+
+    pub fn last_entry(&self) -> Option<TimestampOffset> {
+        self.inner
+            .entries
+            .checked_sub(1)
+            .map(|slot| self.parse(self.inner.entry(slot)))
+    }
+
+    pub fn entries(&self) -> usize {
+        self.inner.entries
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.inner.is_full()
+    }
+
+    pub fn flush(&self) -> io::Result<()> {
+        self.inner.flush()
+    }
+
+    /// Verifies that timestamps and offsets are strictly increasing.
+    pub fn sanity_check(&self) -> io::Result<()> {
+        let mut previous: Option<TimestampOffset> = None;
+        for slot in 0..self.inner.entries {
+            let entry = self.parse(self.inner.entry(slot));
+            let ordered = previous.map_or(true, |prev| {
+                entry.timestamp > prev.timestamp && entry.offset >= prev.offset
+            });
+            if !ordered {
+                return Err(corrupt(&self.inner.path, slot));
+            }
+            previous = Some(entry);
+        }
+        Ok(())
+    }
+
+    fn timestamp_of(entry: &[u8]) -> i64 {
+        i64::from_be_bytes(entry[0..8].try_into().unwrap())
+    }
+
+    fn parse(&self, entry: &[u8]) -> TimestampOffset {
+        TimestampOffset {
+            timestamp: Self::timestamp_of(entry),
+            offset: self.base_offset
+                + i64::from(u32::from_be_bytes(entry[8..12].try_into().unwrap())),
+        }
+    }
+}
+
+fn corrupt(path: &Path, slot: usize) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("index {} is corrupt at entry {slot}", path.display()),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn offset_index_lookup_returns_floor_entry() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let mut index =
+            OffsetIndex::open(&dir.path().join("0.index"), 100, 1024).expect("open index");
+
+        index.append(104, 0).expect("append");
+        index.append(110, 4096).expect("append");
+        index.append(125, 8192).expect("append");
+
+        assert_eq!(index.lookup(99).position, 0);
+        assert_eq!(index.lookup(100).offset, 100);
+        assert_eq!(index.lookup(109).position, 0);
+        assert_eq!(index.lookup(110).position, 4096);
+        assert_eq!(index.lookup(124).position, 4096);
+        assert_eq!(index.lookup(500).position, 8192);
+    }
+
+    #[test]
+    fn offset_index_rejects_non_increasing_offsets() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let mut index =
+            OffsetIndex::open(&dir.path().join("0.index"), 0, 1024).expect("open index");
+
+        index.append(10, 0).expect("append");
+        let err = index.append(10, 100).expect_err("append should fail");
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn offset_index_is_trimmed_on_drop_and_reloaded() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let path = dir.path().join("0.index");
+        {
+            let mut index = OffsetIndex::open(&path, 0, 1024).expect("open index");
+            index.append(5, 0).expect("append");
+            index.append(9, 100).expect("append");
+            assert_eq!(std::fs::metadata(&path).unwrap().len(), 1024);
+        }
+
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), 16);
+        let index = OffsetIndex::open(&path, 0, 1024).expect("reopen index");
+        assert_eq!(index.entries(), 2);
+        assert_eq!(index.lookup(9).position, 100);
+        index.sanity_check(200).expect("index should be valid");
+    }
+
+    #[test]
+    fn offset_index_detects_preallocated_garbage() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let path = dir.path().join("0.index");
+        std::fs::write(&path, vec![0_u8; 64]).expect("write index");
+
+        let index = OffsetIndex::open(&path, 0, 1024).expect("open index");
+        let err = index
+            .sanity_check(1_000)
+            .expect_err("index should be corrupt");
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn offset_index_truncate_drops_later_entries() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let mut index =
+            OffsetIndex::open(&dir.path().join("0.index"), 0, 1024).expect("open index");
+        index.append(5, 0).expect("append");
+        index.append(9, 100).expect("append");
+        index.append(20, 200).expect("append");
+
+        index.truncate_to(9);
+
+        assert_eq!(index.entries(), 1);
+        assert_eq!(index.last_entry().map(|entry| entry.offset), Some(5));
+    }
+
+    #[test]
+    fn time_index_skips_non_increasing_timestamps() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let mut index =
+            TimeIndex::open(&dir.path().join("0.timeindex"), 50, 1200).expect("open index");
+
+        index.maybe_append(1_000, 52).expect("append");
+        index.maybe_append(1_000, 55).expect("append");
+        index.maybe_append(2_000, 60).expect("append");
+
+        assert_eq!(index.entries(), 2);
+        assert_eq!(index.lookup(999).offset, 50);
+        assert_eq!(index.lookup(1_500).offset, 52);
+        assert_eq!(index.lookup(2_500).offset, 60);
+        index.sanity_check().expect("index should be valid");
+    }
+}
@@ -0,0 +1,847 @@
+use super::batch::{self, BatchHeader};
+use super::config::LogConfig;
+use super::index::TimestampOffset;
+use super::producer_state::ProducerStateManager;
+use super::segment::{self, LogSegment, CLEANED_FILE_SUFFIX, LOG_FILE_SUFFIX};
+use crate::time::Clock;
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Bytes read at a time while replaying batches into the producer state.
+const PRODUCER_STATE_REPLAY_BYTES: usize = 1024 * 1024;
+
+/// Offsets assigned to a batch appended through [`Log::append`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LogAppendInfo {
+    pub first_offset: i64,
+    pub last_offset: i64,
+    pub max_timestamp: i64,
+}
+
+/// The segmented, append-only log of a single partition.
+#[derive(Debug)]
+pub struct Log {
+    dir: PathBuf,
+    config: LogConfig,
+    segments: BTreeMap<i64, LogSegment>,
+    next_offset: i64,
+    recovery_point: i64,
+    unflushed_messages: u64,
+    last_flush_ms: i64,
+    producer_state: ProducerStateManager,
+    clock: Arc<dyn Clock>,
+}
+
+impl Log {
+    /// Opens the partition directory `dir`, loading every segment it contains
+    /// and the producer state up to the log end offset.
+    pub fn open(dir: &Path, config: LogConfig, clock: Arc<dyn Clock>) -> io::Result<Self> {
+        fs::create_dir_all(dir)?;
+
+        let mut segments = BTreeMap::new();
+        for base_offset in Self::segment_base_offsets(dir)? {
+            segments.insert(base_offset, LogSegment::open(dir, base_offset, &config)?);
+        }
+        if segments.is_empty() {
+            segments.insert(0, LogSegment::open(dir, 0, &config)?);
+        }
+
+        let mut log = Self {
+            dir: dir.to_path_buf(),
+            config,
+            segments,
+            next_offset: 0,
+            recovery_point: 0,
+            unflushed_messages: 0,
+            last_flush_ms: clock.now_ms(),
+            producer_state: ProducerStateManager::new(dir),
+            clock,
+        };
+        log.next_offset = log.active_segment().next_offset()?;
+        log.recovery_point = log.next_offset;
+        if let Err(err) = log.load_producer_state() {
+            // A torn tail is truncated by recovery, which reloads the state.
+            eprintln!("cannot load producer state of {}: {err}", dir.display());
+        }
+        Ok(log)
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn config(&self) -> &LogConfig {
+        &self.config
+    }
+
+    /// Replaces the log's configuration; it applies to subsequent appends and flushes.
+    pub fn update_config(&mut self, config: LogConfig) {
+        self.config = config;
+    }
+
+    /// Offset the next appended record will receive.
+    pub fn log_end_offset(&self) -> i64 {
+        self.next_offset
+    }
+
+    /// Offset of the first record still retained by the log.
+    pub fn log_start_offset(&self) -> i64 {
+        self.segments.keys().next().copied().unwrap_or(0)
+    }
+
+    /// Offset below which records are visible to consumers. With a single
+    /// replica every appended record is committed, so it is the log end offset.
+    pub fn high_watermark(&self) -> i64 {
+        self.log_end_offset()
+    }
+
+    /// Offset below which no transaction is still open, the limit for
+    /// `read_committed` consumers. Transactions are not supported yet, so it
+    /// matches the high watermark.
+    pub fn last_stable_offset(&self) -> i64 {
+        self.high_watermark()
+    }
+
+    /// Offset up to which the log is known to be flushed to disk.
+    pub fn recovery_point(&self) -> i64 {
+        self.recovery_point
+    }
+
+    /// Sequence numbers of the idempotent producers that wrote to this log.
+    pub fn producer_state(&self) -> &ProducerStateManager {
+        &self.producer_state
+    }
+
+    /// Snapshots the producer state at the log end offset, so the next open
+    /// does not have to replay the log.
+    pub fn take_producer_snapshot(&self) -> io::Result<()> {
+        self.producer_state.take_snapshot(self.next_offset)
+    }
+
+    pub fn segments(&self) -> impl Iterator<Item = &LogSegment> {
+        self.segments.values()
+    }
+
+    /// Base offset of the segment currently being appended to.
+    pub fn active_segment_base_offset(&self) -> i64 {
+        self.active_segment().base_offset()
+    }
+
+    /// Replaces the contents of the closed segment starting at `base_offset`
+    /// with `contents`, which must hold the same offset range with some records
+    /// removed.
+    ///
+    /// The new contents are written and fsynced to a `.log.cleaned` file that is
+    /// then renamed over the original, so a crash leaves either the old or the
+    /// new segment in place.
+    pub fn replace_segment(&mut self, base_offset: i64, contents: &[u8]) -> io::Result<()> {
+        if base_offset == self.active_segment_base_offset() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "the active segment cannot be replaced",
+            ));
+        }
+        let Some(old) = self.segments.remove(&base_offset) else {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("no segment starts at offset {base_offset}"),
+            ));
+        };
+
+        let log_path = old.log_path().to_path_buf();
+        let cleaned_path = self
+            .dir
+            .join(segment::segment_file_name(base_offset, CLEANED_FILE_SUFFIX));
+        let mut cleaned = File::create(&cleaned_path)?;
+        cleaned.write_all(contents)?;
+        cleaned.sync_all()?;
+        drop(cleaned);
+
+        old.delete_indexes()?;
+        fs::rename(&cleaned_path, &log_path)?;
+        let segment = LogSegment::open(&self.dir, base_offset, &self.config)?;
+        self.segments.insert(base_offset, segment);
+        Ok(())
+    }
+
+    /// Assigns offsets to `batch`, starting at the log end offset, and appends it
+    /// to the active segment, rolling a new segment first if needed.
+    ///
+    /// Malformed batches, with a bad length, magic, CRC or records count, fail
+    /// with an `InvalidData` error. Batches from idempotent producers are then
+    /// checked against the producer state; a rejected batch fails with an
+    /// `InvalidInput` error wrapping a [`SequenceError`](super::SequenceError).
+    ///
+    /// The log is flushed afterwards if `flush.messages` unflushed messages have
+    /// accumulated.
+    pub fn append(&mut self, batch: &[u8]) -> io::Result<LogAppendInfo> {
+        batch::validate(batch)?;
+        let mut batch = batch.to_vec();
+        // The CRC does not cover the base offset, so rewriting it keeps the
+        // batch valid.
+        batch::set_base_offset(&mut batch, self.next_offset);
+        let header = BatchHeader::parse(&batch)?;
+        self.producer_state
+            .check(&header)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+
+        self.maybe_roll(batch.len())?;
+        self.active_segment_mut().append(&batch)?;
+        self.producer_state.update(&header);
+        self.next_offset = header.last_offset() + 1;
+        self.unflushed_messages += header.records_count.max(0) as u64;
+
+        if let Some(flush_messages) = self.config.flush_messages {
+            if self.unflushed_messages >= flush_messages {
+                self.flush()?;
+            }
+        }
+
+        Ok(LogAppendInfo {
+            first_offset: header.base_offset,
+            last_offset: header.last_offset(),
+            max_timestamp: header.max_timestamp,
+        })
+    }
+
+    /// Reads batches starting at `offset`, as served to Fetch requests.
+    ///
+    /// Returns an empty buffer when `offset` equals the log end offset and an
+    /// `InvalidInput` error when it lies outside the log.
+    pub fn read(&self, offset: i64, max_bytes: usize) -> io::Result<Vec<u8>> {
+        if offset == self.next_offset {
+            return Ok(Vec::new());
+        }
+        if offset < self.log_start_offset() || offset > self.next_offset {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "offset {offset} is out of range [{}, {}]",
+                    self.log_start_offset(),
+                    self.next_offset
+                ),
+            ));
+        }
+
+        let floor = self
+            .segments
+            .range(..=offset)
+            .next_back()
+            .map_or(offset, |(base_offset, _)| *base_offset);
+        for segment in self.segments.range(floor..).map(|(_, segment)| segment) {
+            if let Some(bytes) = segment.read(offset, max_bytes)? {
+                return Ok(bytes);
+            }
+        }
+        Ok(Vec::new())
+    }
+
+    /// Finds the earliest record whose timestamp is `>= timestamp`.
+    pub fn offset_for_timestamp(&self, timestamp: i64) -> io::Result<Option<TimestampOffset>> {
+        for segment in self.segments.values() {
+            if segment.max_timestamp() < timestamp {
+                continue;
+            }
+            if let Some(found) = segment.find_offset_by_timestamp(timestamp)? {
+                return Ok(Some(found));
+            }
+        }
+        Ok(None)
+    }
+
+    /// The largest timestamp in the log and the offset of its record, taking
+    /// the earliest record on ties; `None` if no record carries a timestamp.
+    pub fn max_timestamp_offset(&self) -> Option<TimestampOffset> {
+        self.segments
+            .values()
+            .filter(|segment| segment.max_timestamp() >= 0)
+            .map(|segment| TimestampOffset {
+                timestamp: segment.max_timestamp(),
+                offset: segment.offset_of_max_timestamp(),
+            })
+            .max_by_key(|found| (found.timestamp, Reverse(found.offset)))
+    }
+
+    /// Fsyncs every segment that may hold data past the recovery point and
+    /// advances the recovery point to the log end offset.
+    pub fn flush(&mut self) -> io::Result<()> {
+        let first_unflushed = self
+            .segments
+            .range(..=self.recovery_point)
+            .next_back()
+            .map_or(self.log_start_offset(), |(base_offset, _)| *base_offset);
+        for segment in self.segments.range_mut(first_unflushed..).map(|(_, s)| s) {
+            segment.flush()?;
+        }
+
+        self.recovery_point = self.next_offset;
+        self.unflushed_messages = 0;
+        self.last_flush_ms = self.clock.now_ms();
+        Ok(())
+    }
+
+    /// Flushes the log if it holds unflushed messages older than `flush.ms`.
+    ///
+    /// Returns `true` if a flush happened.
+    pub fn flush_if_due(&mut self) -> io::Result<bool> {
+        let Some(flush_ms) = self.config.flush_ms else {
+            return Ok(false);
+        };
+        let elapsed = self.clock.now_ms() - self.last_flush_ms;
+        if self.unflushed_messages == 0 || elapsed < flush_ms as i64 {
+            return Ok(false);
+        }
+
+        self.flush()?;
+        Ok(true)
+    }
+
+    /// Number of messages appended since the last flush.
+    pub fn unflushed_messages(&self) -> u64 {
+        self.unflushed_messages
+    }
+
+    /// Validates every segment that may hold data written after `recovery_point`,
+    /// truncating the log at the first invalid batch.
+    ///
+    /// Segments that follow a truncated segment are deleted, since their contents
+    /// can no longer be contiguous with the log.
+    pub fn recover(&mut self, recovery_point: i64) -> io::Result<()> {
+        let start = self
+            .segments
+            .range(..=recovery_point)
+            .next_back()
+            .map_or(self.log_start_offset(), |(base_offset, _)| *base_offset);
+        let unflushed: Vec<i64> = self
+            .segments
+            .range(start..)
+            .map(|(base, _)| *base)
+            .collect();
+
+        for (position, base_offset) in unflushed.iter().enumerate() {
+            let segment = self
+                .segments
+                .get_mut(base_offset)
+                .expect("segment listed above");
+            let truncated = segment.recover()?;
+            if truncated == 0 {
+                continue;
+            }
+
+            eprintln!(
+                "truncated {truncated} invalid bytes from {}",
+                segment.log_path().display()
+            );
+            for later in &unflushed[position + 1..] {
+                if let Some(segment) = self.segments.remove(later) {
+                    eprintln!(
+                        "deleting segment {} after truncation",
+                        segment.log_path().display()
+                    );
+                    segment.delete()?;
+                }
+            }
+            break;
+        }
+
+        self.next_offset = self.active_segment().next_offset()?;
+        self.recovery_point = self.next_offset;
+        self.load_producer_state()
+    }
+
+    /// Deletes the oldest segments that exceed `retention.ms` or `retention.bytes`,
+    /// advancing the log start offset. Returns the number of segments deleted.
+    ///
+    /// Logs whose cleanup policy does not include `delete` are left untouched.
+    pub fn delete_old_segments(&mut self) -> io::Result<usize> {
+        let mut deleted = 0;
+        if !self.config.cleanup_policy.delete {
+            return Ok(deleted);
+        }
+
+        if let Some(retention_ms) = self.config.retention_ms {
+            let cutoff = self.clock.now_ms() - retention_ms;
+            let mut timestamps = BTreeMap::new();
+            for (base_offset, segment) in &self.segments {
+                timestamps.insert(*base_offset, segment.largest_timestamp()?);
+            }
+            deleted += self.delete_oldest_segments("retention time", |segment| {
+                timestamps[&segment.base_offset()] < cutoff
+            })?;
+        }
+
+        if let Some(retention_bytes) = self.config.retention_bytes {
+            let mut excess = self.size() as i64 - retention_bytes as i64;
+            deleted += self.delete_oldest_segments("retention size", |segment| {
+                let size = i64::from(segment.size());
+                if excess - size < 0 {
+                    return false;
+                }
+                excess -= size;
+                true
+            })?;
+        }
+
+        Ok(deleted)
+    }
+
+    /// Deletes the closed segments whose records all lie below `offset`, such as
+    /// metadata log segments covered by a snapshot. Returns the number deleted.
+    pub fn delete_segments_before(&mut self, offset: i64) -> io::Result<usize> {
+        let bases: Vec<i64> = self.segments.keys().copied().collect();
+        let covered: Vec<i64> = bases
+            .windows(2)
+            .filter(|pair| pair[1] <= offset)
+            .map(|pair| pair[0])
+            .collect();
+        self.delete_oldest_segments("log start offset", |segment| {
+            covered.contains(&segment.base_offset())
+        })
+    }
+
+    /// Total size in bytes of every segment's `.log` file.
+    pub fn size(&self) -> u64 {
+        self.segments
+            .values()
+            .map(|segment| u64::from(segment.size()))
+            .sum()
+    }
+
+    /// Deletes segments from the start of the log for as long as `should_delete`
+    /// accepts them. The active segment is rolled first if it has to go, so the
+    /// log always keeps a segment to append to.
+    fn delete_oldest_segments(
+        &mut self,
+        reason: &str,
+        mut should_delete: impl FnMut(&LogSegment) -> bool,
+    ) -> io::Result<usize> {
+        let active_base = self.active_segment().base_offset();
+        let mut doomed = Vec::new();
+        for segment in self.segments.values() {
+            let empty_active = segment.base_offset() == active_base && segment.size() == 0;
+            if empty_active || !should_delete(segment) {
+                break;
+            }
+            doomed.push(segment.base_offset());
+        }
+
+        if doomed.last() == Some(&active_base) {
+            let segment = LogSegment::open(&self.dir, self.next_offset, &self.config)?;
+            self.segments.insert(self.next_offset, segment);
+        }
+
+        for base_offset in &doomed {
+            let segment = self
+                .segments
+                .remove(base_offset)
+                .expect("segment listed above");
+            println!(
+                "deleting segment {} due to {reason} breach",
+                segment.log_path().display()
+            );
+            segment.delete()?;
+        }
+
+        self.recovery_point = self.recovery_point.max(self.log_start_offset());
+        Ok(doomed.len())
+    }
+
+    fn maybe_roll(&mut self, incoming: usize) -> io::Result<()> {
+        let active = self.active_segment();
+        let would_overflow = active.size() > 0
+            && u64::from(active.size()) + incoming as u64 > self.config.segment_bytes;
+        if would_overflow || active.indexes_full() {
+            let base_offset = self.next_offset;
+            self.producer_state.take_snapshot(base_offset)?;
+            let segment = LogSegment::open(&self.dir, base_offset, &self.config)?;
+            self.segments.insert(base_offset, segment);
+        }
+        Ok(())
+    }
+
+    /// Loads the latest producer snapshot and replays the batches after it.
+    fn load_producer_state(&mut self) -> io::Result<()> {
+        let (mut state, snapshot_offset) = ProducerStateManager::load(&self.dir, self.next_offset)?;
+        let mut offset = snapshot_offset.unwrap_or(0).max(self.log_start_offset());
+        while offset < self.next_offset {
+            let bytes = self.read(offset, PRODUCER_STATE_REPLAY_BYTES)?;
+            let batches = batch::split_batches(&bytes)?;
+            if batches.is_empty() {
+                break;
+            }
+            for bytes in batches {
+                let header = BatchHeader::parse(bytes)?;
+                if header.base_offset >= offset {
+                    state.update(&header);
+                }
+                offset = offset.max(header.last_offset() + 1);
+            }
+        }
+        self.producer_state = state;
+        Ok(())
+    }
+
+    fn active_segment(&self) -> &LogSegment {
+        self.segments
+            .values()
+            .next_back()
+            .expect("log always has an active segment")
+    }
+
+    fn active_segment_mut(&mut self) -> &mut LogSegment {
+        self.segments
+            .values_mut()
+            .next_back()
+            .expect("log always has an active segment")
+    }
+
+    fn segment_base_offsets(dir: &Path) -> io::Result<Vec<i64>> {
+        let mut offsets = Vec::new();
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            let name = entry.file_name();
+            let Some(name) = name.to_str() else {
+                continue;
+            };
+            if name.ends_with(CLEANED_FILE_SUFFIX) {
+                // Left behind by a compaction that crashed before its swap.
+                fs::remove_file(entry.path())?;
+            } else if let Some(offset) = segment::parse_base_offset(name, LOG_FILE_SUFFIX) {
+                offsets.push(offset);
+            }
+        }
+        offsets.sort_unstable();
+        Ok(offsets)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::batch::RecordBatchBuilder;
+    use crate::storage::SequenceError;
+    use crate::time::{MockClock, SystemClock};
+
+    fn test_config() -> LogConfig {
+        LogConfig {
+            segment_bytes: 512,
+            index_interval_bytes: 128,
+            ..LogConfig::default()
+        }
+    }
+
+    fn append_records(log: &mut Log, count: usize) {
+        for i in 0..count {
+            let batch = RecordBatchBuilder::new(0)
+                .record(10_000 + i as i64 * 100, Some(b"key"), Some(&[7_u8; 40]))
+                .build();
+            log.append(&batch).expect("append");
+        }
+    }
+
+    #[test]
+    fn append_assigns_offsets_and_rolls_segments() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let mut log = Log::open(dir.path(), test_config(), SystemClock::shared()).expect("open");
+
+        append_records(&mut log, 20);
+
+        assert_eq!(log.log_end_offset(), 20);
+        assert!(log.segments().count() > 1);
+        for (expected, segment) in log.segments().zip(log.segments().skip(1)) {
+            assert!(segment.base_offset() > expected.base_offset());
+        }
+    }
+
+    #[test]
+    fn read_finds_offsets_across_segments() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let mut log = Log::open(dir.path(), test_config(), SystemClock::shared()).expect("open");
+        append_records(&mut log, 20);
+
+        for offset in 0..20 {
+            let bytes = log.read(offset, 1).expect("read");
+            let header = BatchHeader::parse(&bytes).expect("header");
+            assert_eq!(header.base_offset, offset);
+        }
+
+        assert!(log.read(20, 1024).expect("read at end").is_empty());
+        let err = log.read(21, 1024).expect_err("read past end");
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn offset_for_timestamp_spans_segments() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let mut log = Log::open(dir.path(), test_config(), SystemClock::shared()).expect("open");
+        append_records(&mut log, 20);
+
+        let found = log
+            .offset_for_timestamp(11_250)
+            .expect("lookup")
+            .expect("timestamp in log");
+        assert_eq!(found.offset, 13);
+        assert_eq!(found.timestamp, 11_300);
+
+        assert!(log.offset_for_timestamp(99_999).expect("lookup").is_none());
+        let latest = log.max_timestamp_offset().expect("max timestamp");
+        assert_eq!((latest.timestamp, latest.offset), (11_900, 19));
+    }
+
+    #[test]
+    fn max_timestamp_offset_points_at_the_earliest_record_with_it() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let mut log = Log::open(dir.path(), test_config(), SystemClock::shared()).expect("open");
+        append_records(&mut log, 2);
+        let batch = RecordBatchBuilder::new(0)
+            .record(20_000, None, Some(b"first"))
+            .record(15_000, None, Some(b"second"))
+            .record(20_000, None, Some(b"third"))
+            .build();
+        log.append(&batch).expect("append");
+        // Fill the first segment so the tie below lands in a later one.
+        append_records(&mut log, 6);
+        let tie = RecordBatchBuilder::new(0)
+            .record(20_000, None, Some(b"later"))
+            .build();
+        log.append(&tie).expect("append");
+        assert!(log.segments().count() > 1);
+
+        let latest = log.max_timestamp_offset().expect("max timestamp");
+        assert_eq!((latest.timestamp, latest.offset), (20_000, 2));
+
+        drop(log);
+        for entry in fs::read_dir(dir.path()).expect("read dir") {
+            let path = entry.expect("entry").path();
+            if path.extension().is_some_and(|ext| ext == "timeindex") {
+                fs::remove_file(path).expect("remove time index");
+            }
+        }
+        let log = Log::open(dir.path(), test_config(), SystemClock::shared()).expect("reopen");
+        let latest = log.max_timestamp_offset().expect("max timestamp");
+        assert_eq!((latest.timestamp, latest.offset), (20_000, 2));
+    }
+
+    #[test]
+    fn recover_drops_segments_after_corruption() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let corrupt_segment = {
+            let mut log =
+                Log::open(dir.path(), test_config(), SystemClock::shared()).expect("open");
+            append_records(&mut log, 20);
+            assert!(log.segments().count() >= 3);
+            let path = log.segments().nth(1).expect("second segment").log_path();
+            path.to_path_buf()
+        };
+        let mut bytes = fs::read(&corrupt_segment).expect("read segment");
+        let last = bytes.len() - 1;
+        bytes[last] ^= 0xFF;
+        fs::write(&corrupt_segment, &bytes).expect("write segment");
+
+        let mut log = Log::open(dir.path(), test_config(), SystemClock::shared()).expect("reopen");
+        log.recover(0).expect("recover");
+
+        assert_eq!(log.segments().count(), 2);
+        let second = log.segments().nth(1).expect("second segment");
+        assert!(log.log_end_offset() > second.base_offset());
+        assert!(log.log_end_offset() < 20);
+        assert_eq!(log.recovery_point(), log.log_end_offset());
+        assert!(log.read(log.log_end_offset() - 1, 1).is_ok());
+    }
+
+    #[test]
+    fn flush_messages_advances_recovery_point() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let config = LogConfig {
+            flush_messages: Some(3),
+            ..test_config()
+        };
+        let mut log = Log::open(dir.path(), config, SystemClock::shared()).expect("open");
+
+        append_records(&mut log, 2);
+        assert_eq!(log.recovery_point(), 0);
+        assert_eq!(log.unflushed_messages(), 2);
+
+        append_records(&mut log, 1);
+        assert_eq!(log.recovery_point(), 3);
+        assert_eq!(log.unflushed_messages(), 0);
+    }
+
+    #[test]
+    fn flush_ms_flushes_once_interval_elapses() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let clock = MockClock::new(0);
+        let config = LogConfig {
+            flush_ms: Some(1_000),
+            ..test_config()
+        };
+        let mut log = Log::open(dir.path(), config, clock.clone()).expect("open");
+        append_records(&mut log, 2);
+
+        clock.advance(999);
+        assert!(!log.flush_if_due().expect("flush check"));
+        assert_eq!(log.recovery_point(), 0);
+
+        clock.advance(1);
+        assert!(log.flush_if_due().expect("flush check"));
+        assert_eq!(log.recovery_point(), 2);
+
+        clock.advance(5_000);
+        assert!(!log.flush_if_due().expect("nothing left to flush"));
+    }
+
+    #[test]
+    fn os_managed_flush_never_flushes() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let clock = MockClock::new(0);
+        let mut log = Log::open(dir.path(), test_config(), clock.clone()).expect("open");
+        append_records(&mut log, 50);
+
+        clock.advance(i64::from(u32::MAX));
+        assert!(!log.flush_if_due().expect("flush check"));
+        assert_eq!(log.recovery_point(), 0);
+    }
+
+    fn retention_config(retention_ms: Option<i64>, retention_bytes: Option<u64>) -> LogConfig {
+        LogConfig {
+            retention_ms,
+            retention_bytes,
+            ..test_config()
+        }
+    }
+
+    #[test]
+    fn retention_ms_deletes_expired_segments_only() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let clock = MockClock::new(10_000);
+        let config = retention_config(Some(1_000), None);
+        let mut log = Log::open(dir.path(), config, clock.clone()).expect("open");
+        append_records(&mut log, 20);
+        let segments_before = log.segments().count();
+        let second_base = log.segments().nth(1).expect("second segment").base_offset();
+
+        assert_eq!(log.delete_old_segments().expect("retention"), 0);
+
+        let first_max = log.segments().next().expect("segment").max_timestamp();
+        clock.set(first_max + 1_001);
+        assert_eq!(log.delete_old_segments().expect("retention"), 1);
+        assert_eq!(log.segments().count(), segments_before - 1);
+        assert_eq!(log.log_start_offset(), second_base);
+        assert!(log.read(second_base - 1, 1).is_err());
+    }
+
+    #[test]
+    fn delete_segments_before_keeps_segments_reaching_the_offset() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let mut log = Log::open(dir.path(), test_config(), SystemClock::shared()).expect("open");
+        append_records(&mut log, 20);
+        let third_base = log.segments().nth(2).expect("third segment").base_offset();
+
+        assert_eq!(
+            log.delete_segments_before(third_base - 1).expect("delete"),
+            1
+        );
+        assert_eq!(log.delete_segments_before(third_base).expect("delete"), 1);
+        assert_eq!(log.log_start_offset(), third_base);
+
+        let closed = log.segments().count() - 1;
+        assert_eq!(
+            log.delete_segments_before(i64::MAX).expect("delete"),
+            closed
+        );
+        assert_eq!(log.segments().count(), 1);
+        assert_eq!(log.log_end_offset(), 20);
+    }
+
+    #[test]
+    fn retention_ms_rolls_expired_active_segment() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let clock = MockClock::new(0);
+        let config = retention_config(Some(1_000), None);
+        let mut log = Log::open(dir.path(), config, clock.clone()).expect("open");
+        append_records(&mut log, 20);
+
+        clock.set(100_000);
+        log.delete_old_segments().expect("retention");
+
+        assert_eq!(log.segments().count(), 1);
+        assert_eq!(log.log_start_offset(), 20);
+        assert_eq!(log.log_end_offset(), 20);
+        assert!(log.read(20, 1024).expect("read at end").is_empty());
+
+        append_records(&mut log, 1);
+        assert_eq!(log.log_end_offset(), 21);
+    }
+
+    #[test]
+    fn retention_bytes_keeps_log_within_limit() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let config = retention_config(None, Some(1_024));
+        let mut log = Log::open(dir.path(), config, MockClock::new(0)).expect("open");
+        append_records(&mut log, 40);
+        assert!(log.size() > 1_024);
+
+        let deleted = log.delete_old_segments().expect("retention");
+
+        assert!(deleted > 0);
+        assert!(log.size() >= 1_024);
+        let oldest = log.segments().next().expect("segment");
+        assert!(log.size() - u64::from(oldest.size()) < 1_024);
+        assert_eq!(log.log_start_offset(), oldest.base_offset());
+    }
+
+    #[test]
+    fn producer_sequences_survive_reopen() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let batch = |sequence| {
+            RecordBatchBuilder::new(0)
+                .producer(42, 0, sequence)
+                .record(1_000, None, Some(b"v"))
+                .build()
+        };
+        {
+            let mut log =
+                Log::open(dir.path(), test_config(), SystemClock::shared()).expect("open");
+            log.append(&batch(0)).expect("first");
+            log.append(&batch(1)).expect("second");
+            let err = log.append(&batch(1)).expect_err("duplicate");
+            let sequence_error = SequenceError::from_io_error(&err).expect("sequence error");
+            assert!(
+                matches!(sequence_error, SequenceError::Duplicate(found) if found.last_offset == 1)
+            );
+            assert_eq!(log.log_end_offset(), 2);
+        }
+
+        // No snapshot was taken, so the state is rebuilt from the log.
+        let mut log = Log::open(dir.path(), test_config(), SystemClock::shared()).expect("reopen");
+        assert!(log.append(&batch(1)).is_err());
+        log.append(&batch(2)).expect("next in sequence");
+        log.take_producer_snapshot().expect("snapshot");
+
+        let mut log = Log::open(dir.path(), test_config(), SystemClock::shared()).expect("reopen");
+        let err = log.append(&batch(4)).expect_err("gap");
+        assert!(matches!(
+            SequenceError::from_io_error(&err),
+            Some(SequenceError::OutOfOrder { expected: 3, .. })
+        ));
+    }
+
+    #[test]
+    fn reopen_recovers_log_end_offset() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        {
+            let mut log =
+                Log::open(dir.path(), test_config(), SystemClock::shared()).expect("open");
+            append_records(&mut log, 12);
+        }
+
+        let log = Log::open(dir.path(), test_config(), SystemClock::shared()).expect("reopen");
+        assert_eq!(log.log_end_offset(), 12);
+        let bytes = log.read(7, 1).expect("read");
+        assert_eq!(BatchHeader::parse(&bytes).expect("header").base_offset, 7);
+    }
+}
@@ -0,0 +1,576 @@
+use super::checkpoint::OffsetCheckpointFile;
+use super::cleaner::{self, CLEANER_CHECKPOINT_FILE};
+use super::{Log, LogAppendInfo, LogConfig, TopicPartition};
+use crate::time::Clock;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::Sender;
+use std::sync::Arc;
+
+pub const RECOVERY_POINT_CHECKPOINT_FILE: &str = "recovery-point-offset-checkpoint";
+pub const CLEAN_SHUTDOWN_FILE: &str = ".kafka_cleanshutdown";
+
+/// Owns every partition log stored under a single log directory.
+#[derive(Debug)]
+pub struct LogManager {
+    log_dir: PathBuf,
+    default_config: LogConfig,
+    topic_configs: HashMap<String, LogConfig>,
+    recovery_checkpoint: OffsetCheckpointFile,
+    cleaner_checkpoint: OffsetCheckpointFile,
+    logs: BTreeMap<TopicPartition, Log>,
+    clock: Arc<dyn Clock>,
+    /// Set once [`LogManager::shutdown`] has closed every log.
+    shut_down: bool,
+    /// Told the partition of every append, so waiting fetches can be
+    /// completed once the caller releases the manager.
+    append_listener: Option<Sender<TopicPartition>>,
+}
+
+impl LogManager {
+    /// Loads every partition directory under `log_dir`.
+    ///
+    /// Unless the previous run left a clean-shutdown marker, each log is recovered
+    /// from its checkpointed recovery point. The marker is removed once loading
+    /// completes, so a crash before the next clean shutdown triggers recovery.
+    pub fn load(
+        log_dir: &Path,
+        default_config: LogConfig,
+        clock: Arc<dyn Clock>,
+    ) -> io::Result<Self> {
+        fs::create_dir_all(log_dir)?;
+        let clean_shutdown_marker = log_dir.join(CLEAN_SHUTDOWN_FILE);
+        let had_clean_shutdown = clean_shutdown_marker.exists();
+        let recovery_checkpoint =
+            OffsetCheckpointFile::new(log_dir.join(RECOVERY_POINT_CHECKPOINT_FILE));
+        let recovery_points = recovery_checkpoint.read().unwrap_or_else(|err| {
+            eprintln!("ignoring unreadable recovery checkpoint: {err}");
+            BTreeMap::new()
+        });
+
+        let mut logs = BTreeMap::new();
+        for entry in fs::read_dir(log_dir)? {
+            let entry = entry?;
+            if !entry.file_type()?.is_dir() {
+                continue;
+            }
+            let Some(tp) = entry
+                .file_name()
+                .to_str()
+                .and_then(TopicPartition::from_dir_name)
+            else {
+                continue;
+            };
+
+            let mut log = Log::open(&entry.path(), default_config.clone(), Arc::clone(&clock))?;
+            if !had_clean_shutdown {
+                let recovery_point = recovery_points.get(&tp).copied().unwrap_or(0);
+                println!("recovering log {tp} from offset {recovery_point}");
+                log.recover(recovery_point)?;
+            }
+            logs.insert(tp, log);
+        }
+
+        if had_clean_shutdown {
+            fs::remove_file(&clean_shutdown_marker)?;
+        }
+
+        Ok(Self {
+            log_dir: log_dir.to_path_buf(),
+            default_config,
+            topic_configs: HashMap::new(),
+            recovery_checkpoint,
+            cleaner_checkpoint: OffsetCheckpointFile::new(log_dir.join(CLEANER_CHECKPOINT_FILE)),
+            logs,
+            clock,
+            shut_down: false,
+            append_listener: None,
+        })
+    }
+
+    pub fn log_dir(&self) -> &Path {
+        &self.log_dir
+    }
+
+    pub fn get_log(&self, tp: &TopicPartition) -> Option<&Log> {
+        self.logs.get(tp)
+    }
+
+    pub fn get_log_mut(&mut self, tp: &TopicPartition) -> Option<&mut Log> {
+        self.logs.get_mut(tp)
+    }
+
+    /// Returns the log for `tp`, creating an empty one with the topic's config.
+    pub fn get_or_create_log(&mut self, tp: &TopicPartition) -> io::Result<&mut Log> {
+        if self.shut_down {
+            return Err(io::Error::new(
+                io::ErrorKind::BrokenPipe,
+                "log manager is shut down",
+            ));
+        }
+        if !self.logs.contains_key(tp) {
+            let log = Log::open(
+                &self.log_dir.join(tp.dir_name()),
+                self.config_for(&tp.topic).clone(),
+                Arc::clone(&self.clock),
+            )?;
+            self.logs.insert(tp.clone(), log);
+        }
+        Ok(self.logs.get_mut(tp).expect("log inserted above"))
+    }
+
+    /// Closes the log of `tp` and deletes its directory, returning `false` if
+    /// there was no such log.
+    pub fn delete_log(&mut self, tp: &TopicPartition) -> io::Result<bool> {
+        let Some(log) = self.logs.remove(tp) else {
+            return Ok(false);
+        };
+        let dir = log.dir().to_path_buf();
+        drop(log);
+        fs::remove_dir_all(&dir)?;
+        self.checkpoint_recovery_points()?;
+        Ok(true)
+    }
+
+    /// Forgets the topic-level overrides of `topic`, so its logs fall back to
+    /// the default config.
+    pub fn remove_topic_config(&mut self, topic: &str) {
+        self.topic_configs.remove(topic);
+        for (_, log) in self.logs.iter_mut().filter(|(tp, _)| tp.topic == topic) {
+            log.update_config(self.default_config.clone());
+        }
+    }
+
+    /// Applies topic-level overrides on top of the default log config for every
+    /// current and future partition of `topic`.
+    pub fn set_topic_config(
+        &mut self,
+        topic: &str,
+        overrides: &BTreeMap<String, String>,
+    ) -> io::Result<()> {
+        let config = self.default_config.with_overrides(overrides)?;
+        for (_, log) in self.logs.iter_mut().filter(|(tp, _)| tp.topic == topic) {
+            log.update_config(config.clone());
+        }
+        self.topic_configs.insert(topic.to_string(), config);
+        Ok(())
+    }
+
+    pub fn default_config(&self) -> &LogConfig {
+        &self.default_config
+    }
+
+    pub fn config_for(&self, topic: &str) -> &LogConfig {
+        self.topic_configs
+            .get(topic)
+            .unwrap_or(&self.default_config)
+    }
+
+    /// Appends `batch` to the log of `tp`, checkpointing recovery points if the
+    /// append triggered a `flush.messages` flush.
+    pub fn append(&mut self, tp: &TopicPartition, batch: &[u8]) -> io::Result<LogAppendInfo> {
+        let log = self.get_or_create_log(tp)?;
+        let recovery_point = log.recovery_point();
+        let info = log.append(batch)?;
+        if log.recovery_point() != recovery_point {
+            self.checkpoint_recovery_points()?;
+        }
+        // The high watermark is the log end offset, so every append moves it.
+        if let Some(listener) = &self.append_listener {
+            if listener.send(tp.clone()).is_err() {
+                self.append_listener = None;
+            }
+        }
+        Ok(info)
+    }
+
+    /// Sends the partition of every later append to `listener`.
+    pub fn notify_appends(&mut self, listener: Sender<TopicPartition>) {
+        self.append_listener = Some(listener);
+    }
+
+    /// Flushes every log whose `flush.ms` interval has elapsed and checkpoints the
+    /// new recovery points.
+    pub fn flush_dirty_logs(&mut self) -> io::Result<()> {
+        let mut flushed = false;
+        for (tp, log) in self.logs.iter_mut() {
+            match log.flush_if_due() {
+                Ok(did_flush) => flushed |= did_flush,
+                Err(err) => eprintln!("failed to flush log {tp}: {err}"),
+            }
+        }
+        if flushed {
+            self.checkpoint_recovery_points()?;
+        }
+        Ok(())
+    }
+
+    /// Applies `retention.ms` and `retention.bytes` to every log, returning the
+    /// total number of segments deleted.
+    pub fn delete_old_segments(&mut self) -> usize {
+        let mut total = 0;
+        for (tp, log) in self.logs.iter_mut() {
+            match log.delete_old_segments() {
+                Ok(0) => {}
+                Ok(deleted) => {
+                    println!(
+                        "deleted {deleted} segments from {tp}, log start offset is now {}",
+                        log.log_start_offset()
+                    );
+                    total += deleted;
+                }
+                Err(err) => eprintln!("failed to apply retention to {tp}: {err}"),
+            }
+        }
+        total
+    }
+
+    /// Compacts every log whose cleanup policy includes `compact` and whose dirty
+    /// ratio has reached `min.cleanable.dirty.ratio`, returning the number of
+    /// logs cleaned.
+    ///
+    /// How far each log has been compacted is kept in the cleaner checkpoint so
+    /// the next pass only collects keys from newly written segments.
+    pub fn clean_logs(&mut self) -> io::Result<usize> {
+        let mut cleaned_offsets = self.cleaner_checkpoint.read().unwrap_or_else(|err| {
+            eprintln!("ignoring unreadable cleaner checkpoint: {err}");
+            BTreeMap::new()
+        });
+        let now_ms = self.clock.now_ms();
+        let mut cleaned = 0;
+
+        for (tp, log) in self.logs.iter_mut() {
+            if !log.config().cleanup_policy.compact {
+                continue;
+            }
+            let first_dirty_offset = cleaned_offsets.get(tp).copied().unwrap_or(0);
+            match cleaner::clean(log, first_dirty_offset, now_ms) {
+                Ok(None) => {}
+                Ok(Some(stats)) => {
+                    println!(
+                        "cleaned log {tp} up to offset {}: removed {} records, {} -> {} bytes",
+                        stats.end_offset,
+                        stats.records_removed,
+                        stats.bytes_before,
+                        stats.bytes_after
+                    );
+                    cleaned_offsets.insert(tp.clone(), stats.end_offset);
+                    cleaned += 1;
+                }
+                Err(err) => eprintln!("failed to clean log {tp}: {err}"),
+            }
+        }
+
+        if cleaned > 0 {
+            self.cleaner_checkpoint.write(&cleaned_offsets)?;
+        }
+        Ok(cleaned)
+    }
+
+    pub fn logs(&self) -> impl Iterator<Item = (&TopicPartition, &Log)> {
+        self.logs.iter()
+    }
+
+    /// Persists the recovery point of every log.
+    pub fn checkpoint_recovery_points(&self) -> io::Result<()> {
+        if self.shut_down {
+            // The checkpoint written at shutdown covers every log.
+            return Ok(());
+        }
+        let points = self
+            .logs
+            .iter()
+            .map(|(tp, log)| (tp.clone(), log.recovery_point()))
+            .collect();
+        self.recovery_checkpoint.write(&points)
+    }
+
+    /// Flushes every log, snapshots producer state, checkpoints recovery points
+    /// and writes the clean-shutdown marker so the next start can skip recovery.
+    ///
+    /// The logs are closed and later appends fail, so callers that still hold
+    /// the manager cannot write past the marker.
+    pub fn shutdown(&mut self) -> io::Result<()> {
+        if self.shut_down {
+            return Ok(());
+        }
+        for log in self.logs.values_mut() {
+            log.flush()?;
+            log.take_producer_snapshot()?;
+        }
+        self.checkpoint_recovery_points()?;
+        self.logs.clear();
+        self.append_listener = None;
+        self.shut_down = true;
+        fs::File::create(self.log_dir.join(CLEAN_SHUTDOWN_FILE))?.sync_all()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::batch::RecordBatchBuilder;
+    use crate::storage::config::{
+        CLEANUP_POLICY_CONFIG, FLUSH_MESSAGES_CONFIG, FLUSH_MS_CONFIG,
+        MIN_CLEANABLE_DIRTY_RATIO_CONFIG, RETENTION_MS_CONFIG, SEGMENT_BYTES_CONFIG,
+    };
+    use crate::time::{MockClock, SystemClock};
+    use std::io::Write;
+
+    fn append(manager: &mut LogManager, tp: &TopicPartition, count: usize) {
+        for i in 0..count {
+            let batch = RecordBatchBuilder::new(0)
+                .record(i as i64, Some(b"k"), Some(b"v"))
+                .build();
+            manager.append(tp, &batch).expect("append");
+        }
+    }
+
+    fn append_garbage(dir: &Path, tp: &TopicPartition) {
+        let segment = dir.join(tp.dir_name()).join("00000000000000000000.log");
+        fs::OpenOptions::new()
+            .append(true)
+            .open(segment)
+            .and_then(|mut file| file.write_all(&[0xAB; 30]))
+            .expect("append garbage");
+    }
+
+    #[test]
+    fn clean_shutdown_writes_marker_and_checkpoint() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let tp = TopicPartition::new("orders", 0);
+        let mut manager = LogManager::load(dir.path(), LogConfig::default(), SystemClock::shared())
+            .expect("load");
+        append(&mut manager, &tp, 3);
+
+        manager.shutdown().expect("shutdown");
+
+        assert!(dir.path().join(CLEAN_SHUTDOWN_FILE).exists());
+        assert!(manager.get_or_create_log(&tp).is_err());
+        let points = OffsetCheckpointFile::new(dir.path().join(RECOVERY_POINT_CHECKPOINT_FILE))
+            .read()
+            .expect("checkpoint");
+        assert_eq!(points.get(&tp), Some(&3));
+
+        let manager = LogManager::load(dir.path(), LogConfig::default(), SystemClock::shared())
+            .expect("reload");
+        assert!(!dir.path().join(CLEAN_SHUTDOWN_FILE).exists());
+        assert_eq!(manager.get_log(&tp).expect("log").log_end_offset(), 3);
+    }
+
+    #[test]
+    fn unclean_shutdown_truncates_torn_batches() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let tp = TopicPartition::new("orders", 0);
+        {
+            let mut manager =
+                LogManager::load(dir.path(), LogConfig::default(), SystemClock::shared())
+                    .expect("load");
+            append(&mut manager, &tp, 3);
+        }
+        append_garbage(dir.path(), &tp);
+
+        let manager = LogManager::load(dir.path(), LogConfig::default(), SystemClock::shared())
+            .expect("reload");
+
+        let log = manager.get_log(&tp).expect("log");
+        assert_eq!(log.log_end_offset(), 3);
+        let segment = log.segments().next().expect("segment");
+        assert_eq!(
+            fs::metadata(segment.log_path()).expect("metadata").len(),
+            u64::from(segment.size())
+        );
+    }
+
+    #[test]
+    fn clean_shutdown_marker_skips_recovery() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let tp = TopicPartition::new("orders", 0);
+        let mut manager = LogManager::load(dir.path(), LogConfig::default(), SystemClock::shared())
+            .expect("load");
+        append(&mut manager, &tp, 2);
+        let valid_size = manager
+            .get_log(&tp)
+            .expect("log")
+            .segments()
+            .next()
+            .unwrap()
+            .size();
+        manager.shutdown().expect("shutdown");
+        append_garbage(dir.path(), &tp);
+
+        let manager = LogManager::load(dir.path(), LogConfig::default(), SystemClock::shared())
+            .expect("reload");
+
+        let segment = manager
+            .get_log(&tp)
+            .expect("log")
+            .segments()
+            .next()
+            .unwrap();
+        assert_eq!(segment.size(), valid_size + 30);
+    }
+
+    fn checkpointed(dir: &Path, tp: &TopicPartition) -> Option<i64> {
+        OffsetCheckpointFile::new(dir.join(RECOVERY_POINT_CHECKPOINT_FILE))
+            .read()
+            .expect("checkpoint")
+            .get(tp)
+            .copied()
+    }
+
+    fn topic_overrides(key: &str, value: &str) -> BTreeMap<String, String> {
+        BTreeMap::from([(key.to_string(), value.to_string())])
+    }
+
+    #[test]
+    fn appends_are_announced_until_shutdown() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let tp = TopicPartition::new("orders", 1);
+        let mut manager = LogManager::load(dir.path(), LogConfig::default(), SystemClock::shared())
+            .expect("load");
+        let (listener, appends) = std::sync::mpsc::channel();
+        manager.notify_appends(listener);
+
+        append(&mut manager, &tp, 2);
+        manager.shutdown().expect("shutdown");
+
+        assert_eq!(appends.iter().collect::<Vec<_>>(), [tp.clone(), tp]);
+    }
+
+    #[test]
+    fn flush_messages_writes_recovery_checkpoint() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let tp = TopicPartition::new("payments", 0);
+        let mut manager = LogManager::load(dir.path(), LogConfig::default(), SystemClock::shared())
+            .expect("load");
+        manager
+            .set_topic_config("payments", &topic_overrides(FLUSH_MESSAGES_CONFIG, "2"))
+            .expect("topic config");
+
+        append(&mut manager, &tp, 1);
+        assert_eq!(checkpointed(dir.path(), &tp), None);
+
+        append(&mut manager, &tp, 1);
+        assert_eq!(checkpointed(dir.path(), &tp), Some(2));
+    }
+
+    #[test]
+    fn flush_dirty_logs_honours_per_topic_flush_ms() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let clock = MockClock::new(0);
+        let fast = TopicPartition::new("fast", 0);
+        let lazy = TopicPartition::new("lazy", 0);
+        let mut manager =
+            LogManager::load(dir.path(), LogConfig::default(), clock.clone()).expect("load");
+        manager
+            .set_topic_config("fast", &topic_overrides(FLUSH_MS_CONFIG, "100"))
+            .expect("topic config");
+        append(&mut manager, &fast, 2);
+        append(&mut manager, &lazy, 2);
+
+        clock.advance(100);
+        manager.flush_dirty_logs().expect("flush");
+
+        assert_eq!(checkpointed(dir.path(), &fast), Some(2));
+        assert_eq!(checkpointed(dir.path(), &lazy), Some(0));
+        assert_eq!(manager.get_log(&lazy).expect("log").unflushed_messages(), 2);
+    }
+
+    #[test]
+    fn set_topic_config_updates_existing_logs() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let tp = TopicPartition::new("orders", 0);
+        let mut manager = LogManager::load(dir.path(), LogConfig::default(), SystemClock::shared())
+            .expect("load");
+        append(&mut manager, &tp, 1);
+
+        manager
+            .set_topic_config("orders", &topic_overrides(FLUSH_MESSAGES_CONFIG, "1"))
+            .expect("topic config");
+
+        assert_eq!(
+            manager.get_log(&tp).expect("log").config().flush_messages,
+            Some(1)
+        );
+        let err = manager
+            .set_topic_config("orders", &topic_overrides(FLUSH_MS_CONFIG, "soon"))
+            .expect_err("invalid config");
+        assert!(err.to_string().contains(FLUSH_MS_CONFIG));
+    }
+
+    #[test]
+    fn delete_old_segments_applies_topic_retention() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let clock = MockClock::new(0);
+        let short = TopicPartition::new("short", 0);
+        let long = TopicPartition::new("long", 0);
+        let mut manager =
+            LogManager::load(dir.path(), LogConfig::default(), clock.clone()).expect("load");
+        let overrides = BTreeMap::from([
+            (RETENTION_MS_CONFIG.to_string(), "60000".to_string()),
+            (SEGMENT_BYTES_CONFIG.to_string(), "100".to_string()),
+        ]);
+        manager
+            .set_topic_config("short", &overrides)
+            .expect("topic config");
+        append(&mut manager, &short, 5);
+        append(&mut manager, &long, 5);
+
+        clock.set(60_010);
+        let deleted = manager.delete_old_segments();
+
+        assert_eq!(deleted, 5);
+        assert_eq!(manager.get_log(&short).expect("log").log_start_offset(), 5);
+        assert_eq!(manager.get_log(&long).expect("log").log_start_offset(), 0);
+    }
+
+    #[test]
+    fn clean_logs_compacts_only_compacted_topics() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let clock = MockClock::new(0);
+        let compacted = TopicPartition::new("snapshots", 0);
+        let plain = TopicPartition::new("events", 0);
+        let mut manager =
+            LogManager::load(dir.path(), LogConfig::default(), clock.clone()).expect("load");
+        let segment_overrides = |policy: &str| {
+            BTreeMap::from([
+                (CLEANUP_POLICY_CONFIG.to_string(), policy.to_string()),
+                (SEGMENT_BYTES_CONFIG.to_string(), "100".to_string()),
+                (
+                    MIN_CLEANABLE_DIRTY_RATIO_CONFIG.to_string(),
+                    "0.5".to_string(),
+                ),
+                (RETENTION_MS_CONFIG.to_string(), "1000".to_string()),
+            ])
+        };
+        manager
+            .set_topic_config("snapshots", &segment_overrides("compact"))
+            .expect("topic config");
+        manager
+            .set_topic_config("events", &segment_overrides("delete"))
+            .expect("topic config");
+        append(&mut manager, &compacted, 5);
+        append(&mut manager, &plain, 5);
+        let size_before = manager.get_log(&compacted).expect("log").size();
+
+        assert_eq!(manager.clean_logs().expect("clean"), 1);
+        assert!(manager.get_log(&compacted).expect("log").size() < size_before);
+        let checkpoint = OffsetCheckpointFile::new(dir.path().join(CLEANER_CHECKPOINT_FILE))
+            .read()
+            .expect("cleaner checkpoint");
+        assert_eq!(checkpoint.get(&compacted), Some(&4));
+        assert_eq!(checkpoint.get(&plain), None);
+
+        assert_eq!(manager.clean_logs().expect("clean"), 0);
+
+        clock.set(10_000);
+        assert_eq!(manager.delete_old_segments(), 5);
+        assert_eq!(
+            manager.get_log(&compacted).expect("log").log_start_offset(),
+            0
+        );
+    }
+}
@@ -0,0 +1,227 @@
+use crate::metadata::Uuid;
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::Path;
+
+pub const META_PROPERTIES_FILE: &str = "meta.properties";
+
+/// The `meta.properties` version used by KRaft-mode storage directories.
+const KRAFT_VERSION: i32 = 1;
+
+const VERSION_KEY: &str = "version";
+const CLUSTER_ID_KEY: &str = "cluster.id";
+const NODE_ID_KEY: &str = "node.id";
+const DIRECTORY_ID_KEY: &str = "directory.id";
+
+/// Identity of a formatted storage directory, stored in its `meta.properties`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MetaProperties {
+    pub cluster_id: String,
+    pub node_id: i32,
+    pub directory_id: Uuid,
+}
+
+impl MetaProperties {
+    /// Formats `dir` for `node_id` in `cluster_id`, generating a new directory id.
+    ///
+    /// An already formatted directory is left untouched and is an
+    /// `AlreadyExists` error unless `ignore_formatted` is set, in which case its
+    /// existing properties are returned.
+    pub fn format(
+        dir: &Path,
+        cluster_id: &str,
+        node_id: i32,
+        ignore_formatted: bool,
+    ) -> io::Result<Self> {
+        cluster_id.parse::<Uuid>().map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("cluster id '{cluster_id}' is not a base64 encoded uuid"),
+            )
+        })?;
+        if node_id < 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("node id {node_id} must not be negative"),
+            ));
+        }
+
+        if let Some(existing) = Self::read(dir)? {
+            if ignore_formatted {
+                return Ok(existing);
+            }
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("log directory {} is already formatted", dir.display()),
+            ));
+        }
+
+        let properties = Self {
+            cluster_id: cluster_id.to_string(),
+            node_id,
+            directory_id: Uuid::random()?,
+        };
+        fs::create_dir_all(dir)?;
+        properties.write(dir)?;
+        Ok(properties)
+    }
+
+    /// Reads the properties of `dir`, returning `None` if it is not formatted.
+    pub fn read(dir: &Path) -> io::Result<Option<Self>> {
+        let path = dir.join(META_PROPERTIES_FILE);
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err),
+        };
+
+        let properties = parse_properties(&contents);
+        let field = |key: &str| {
+            properties
+                .get(key)
+                .ok_or_else(|| malformed(&path, &format!("missing {key}")))
+        };
+        let version = field(VERSION_KEY)?;
+        if version.parse() != Ok(KRAFT_VERSION) {
+            return Err(malformed(&path, &format!("unsupported version {version}")));
+        }
+        let node_id = field(NODE_ID_KEY)?;
+        let directory_id = field(DIRECTORY_ID_KEY)?;
+        Ok(Some(Self {
+            cluster_id: field(CLUSTER_ID_KEY)?.clone(),
+            node_id: node_id
+                .parse()
+                .map_err(|_| malformed(&path, &format!("invalid {NODE_ID_KEY} {node_id}")))?,
+            directory_id: directory_id.parse().map_err(|_| {
+                malformed(&path, &format!("invalid {DIRECTORY_ID_KEY} {directory_id}"))
+            })?,
+        }))
+    }
+
+    /// Reads the properties of `dir` and checks that it was formatted for
+    /// `node_id`, failing if it is unformatted or belongs to another node.
+    pub fn verify(dir: &Path, node_id: i32) -> io::Result<Self> {
+        let properties = Self::read(dir)?.ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!(
+                    "log directory {} is not formatted; run the format command first",
+                    dir.display()
+                ),
+            )
+        })?;
+        if properties.node_id != node_id {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "log directory {} belongs to node {} but this is node {node_id}",
+                    dir.display(),
+                    properties.node_id
+                ),
+            ));
+        }
+        Ok(properties)
+    }
+
+    /// Atomically writes the properties to `dir`'s `meta.properties`.
+    fn write(&self, dir: &Path) -> io::Result<()> {
+        let contents = format!(
+            "#\n{CLUSTER_ID_KEY}={}\n{DIRECTORY_ID_KEY}={}\n{NODE_ID_KEY}={}\n{VERSION_KEY}={KRAFT_VERSION}\n",
+            self.cluster_id, self.directory_id, self.node_id
+        );
+
+        let path = dir.join(META_PROPERTIES_FILE);
+        let tmp = path.with_extension("properties.tmp");
+        let mut file = File::create(&tmp)?;
+        file.write_all(contents.as_bytes())?;
+        file.sync_all()?;
+        fs::rename(&tmp, &path)
+    }
+}
+
+/// Parses `key=value` lines in Java properties style, skipping blank lines and
+/// `#` or `!` comments.
+pub(crate) fn parse_properties(contents: &str) -> BTreeMap<String, String> {
+    contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#') && !line.starts_with('!'))
+        .filter_map(|line| {
+            let (key, value) = line.split_once(['=', ':'])?;
+            Some((key.trim().to_string(), value.trim().to_string()))
+        })
+        .collect()
+}
+
+fn malformed(path: &Path, reason: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("malformed {}: {reason}", path.display()),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CLUSTER_ID: &str = "MkU3OEVBNTcwNTJENDM2Qg";
+
+    #[test]
+    fn formatted_directory_reads_back() {
+        let dir = tempfile::tempdir().expect("tempdir");
+
+        let formatted = MetaProperties::format(dir.path(), CLUSTER_ID, 3, false).expect("format");
+
+        assert_eq!(formatted.cluster_id, CLUSTER_ID);
+        assert_eq!(formatted.node_id, 3);
+        assert_eq!(
+            MetaProperties::read(dir.path()).expect("read"),
+            Some(formatted.clone())
+        );
+        assert_eq!(
+            MetaProperties::verify(dir.path(), 3).expect("verify"),
+            formatted
+        );
+    }
+
+    #[test]
+    fn format_refuses_formatted_directory_and_bad_ids() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let formatted = MetaProperties::format(dir.path(), CLUSTER_ID, 1, false).expect("format");
+
+        let err = MetaProperties::format(dir.path(), CLUSTER_ID, 1, false)
+            .expect_err("already formatted");
+        assert_eq!(err.kind(), io::ErrorKind::AlreadyExists);
+        assert_eq!(
+            MetaProperties::format(dir.path(), CLUSTER_ID, 1, true).expect("ignore formatted"),
+            formatted
+        );
+
+        let other = tempfile::tempdir().expect("tempdir");
+        for (cluster_id, node_id) in [("not-a-uuid", 1), (CLUSTER_ID, -1)] {
+            let err = MetaProperties::format(other.path(), cluster_id, node_id, false)
+                .expect_err("invalid ids");
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        }
+    }
+
+    #[test]
+    fn verify_rejects_unformatted_and_mismatched_directories() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let err = MetaProperties::verify(dir.path(), 1).expect_err("unformatted");
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+
+        MetaProperties::format(dir.path(), CLUSTER_ID, 2, false).expect("format");
+        let err = MetaProperties::verify(dir.path(), 1).expect_err("other node");
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        fs::write(
+            dir.path().join(META_PROPERTIES_FILE),
+            "version=0\nbroker.id=2\ncluster.id=x\n",
+        )
+        .expect("write zk properties");
+        let err = MetaProperties::read(dir.path()).expect_err("zookeeper version");
+        assert!(err.to_string().contains("unsupported version"));
+    }
+}
@@ -0,0 +1,19 @@
+pub mod batch;
+pub mod checkpoint;
+pub mod cleaner;
+pub mod config;
+pub mod index;
+pub mod log;
+pub mod manager;
+pub mod meta_properties;
+pub mod producer_state;
+pub mod segment;
+pub mod topic_partition;
+
+pub use config::LogConfig;
+pub use log::{Log, LogAppendInfo};
+pub use manager::LogManager;
+pub use meta_properties::MetaProperties;
+pub use producer_state::{ProducerStateManager, SequenceError};
+pub use segment::LogSegment;
+pub use topic_partition::TopicPartition;
@@ -0,0 +1,438 @@
+use super::batch::BatchHeader;
+use super::segment;
+use crate::codec::primitives;
+use crate::protocol::error;
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, Cursor, Write};
+use std::path::{Path, PathBuf};
+
+pub const PRODUCER_SNAPSHOT_FILE_SUFFIX: &str = ".snapshot";
+
+const SNAPSHOT_VERSION: i16 = 1;
+/// Size of the version and CRC fields that precede the snapshot entries.
+const SNAPSHOT_HEADER_SIZE: usize = 6;
+const NO_COORDINATOR_EPOCH: i32 = -1;
+const NO_TXN_FIRST_OFFSET: i64 = -1;
+/// Batches remembered per producer for duplicate detection, matching the
+/// number of in-flight requests an idempotent producer may have.
+const BATCHES_TO_RETAIN: usize = 5;
+
+/// Sequence numbers and offsets of a batch appended by an idempotent producer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BatchMetadata {
+    pub first_sequence: i32,
+    pub last_sequence: i32,
+    pub last_offset: i64,
+    pub offset_delta: i32,
+    pub timestamp: i64,
+}
+
+impl BatchMetadata {
+    pub fn first_offset(&self) -> i64 {
+        self.last_offset - i64::from(self.offset_delta)
+    }
+}
+
+/// What the log knows about one producer: its current epoch and its most
+/// recently appended batches, newest last.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProducerStateEntry {
+    pub producer_epoch: i16,
+    batches: VecDeque<BatchMetadata>,
+}
+
+impl ProducerStateEntry {
+    pub fn last_sequence(&self) -> Option<i32> {
+        self.batches.back().map(|batch| batch.last_sequence)
+    }
+
+    pub fn batches(&self) -> impl Iterator<Item = &BatchMetadata> {
+        self.batches.iter()
+    }
+}
+
+/// Why a batch from an idempotent producer was rejected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SequenceError {
+    /// The batch was already appended, at the offsets of the given batch.
+    Duplicate(BatchMetadata),
+    OutOfOrder {
+        producer_id: i64,
+        expected: i32,
+        found: i32,
+    },
+    /// The batch comes from a producer fenced by a newer epoch.
+    InvalidEpoch {
+        producer_id: i64,
+        current: i16,
+        found: i16,
+    },
+}
+
+impl SequenceError {
+    /// Extracts the sequence error an append failed with, if any.
+    pub fn from_io_error(err: &io::Error) -> Option<&Self> {
+        err.get_ref()?.downcast_ref()
+    }
+
+    pub fn error_code(&self) -> i16 {
+        match self {
+            Self::Duplicate(_) => error::DUPLICATE_SEQUENCE_NUMBER,
+            Self::OutOfOrder { .. } => error::OUT_OF_ORDER_SEQUENCE_NUMBER,
+            Self::InvalidEpoch { .. } => error::INVALID_PRODUCER_EPOCH,
+        }
+    }
+}
+
+impl fmt::Display for SequenceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Duplicate(batch) => write!(
+                f,
+                "duplicate batch with sequences {}..={} already at offset {}",
+                batch.first_sequence,
+                batch.last_sequence,
+                batch.first_offset()
+            ),
+            Self::OutOfOrder {
+                producer_id,
+                expected,
+                found,
+            } => write!(
+                f,
+                "out of order sequence {found} from producer {producer_id}, expected {expected}"
+            ),
+            Self::InvalidEpoch {
+                producer_id,
+                current,
+                found,
+            } => write!(
+                f,
+                "producer {producer_id} epoch {found} is older than current epoch {current}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SequenceError {}
+
+/// Tracks the last sequence numbers of every idempotent producer that wrote
+/// to a partition, so retried batches are not appended twice.
+///
+/// The state is persisted as `<offset>.snapshot` files in the partition
+/// directory, in Kafka's producer snapshot format. A snapshot holds the state
+/// after every batch below its offset; batches after it are replayed from the
+/// log on load.
+#[derive(Debug)]
+pub struct ProducerStateManager {
+    dir: PathBuf,
+    producers: BTreeMap<i64, ProducerStateEntry>,
+}
+
+impl ProducerStateManager {
+    /// Empty state for the partition directory `dir`.
+    pub fn new(dir: &Path) -> Self {
+        Self {
+            dir: dir.to_path_buf(),
+            producers: BTreeMap::new(),
+        }
+    }
+
+    /// Loads the newest snapshot in `dir` at or below `log_end_offset`, deleting
+    /// any later ones, and returns the offset replay has to resume from, or
+    /// `None` if there was no usable snapshot.
+    pub fn load(dir: &Path, log_end_offset: i64) -> io::Result<(Self, Option<i64>)> {
+        let mut manager = Self::new(dir);
+        let mut offsets = manager.snapshot_offsets()?;
+        while let Some(offset) = offsets.pop() {
+            let path = manager.snapshot_path(offset);
+            if offset > log_end_offset {
+                fs::remove_file(&path)?;
+                continue;
+            }
+            match read_snapshot(&path) {
+                Ok(producers) => {
+                    manager.producers = producers;
+                    return Ok((manager, Some(offset)));
+                }
+                Err(err) => {
+                    eprintln!(
+                        "deleting unreadable producer snapshot {}: {err}",
+                        path.display()
+                    );
+                    fs::remove_file(&path)?;
+                }
+            }
+        }
+        Ok((manager, None))
+    }
+
+    pub fn producer(&self, producer_id: i64) -> Option<&ProducerStateEntry> {
+        self.producers.get(&producer_id)
+    }
+
+    /// Checks the producer epoch and sequence numbers of `header`, a batch about
+    /// to be appended. Batches without a producer id are always accepted.
+    pub fn check(&self, header: &BatchHeader) -> Result<(), SequenceError> {
+        if header.producer_id < 0 || header.is_control() {
+            return Ok(());
+        }
+        // A producer whose state is gone, e.g. removed with old segments, is
+        // accepted at any sequence.
+        let Some(entry) = self.producers.get(&header.producer_id) else {
+            return Ok(());
+        };
+
+        if header.producer_epoch < entry.producer_epoch {
+            return Err(SequenceError::InvalidEpoch {
+                producer_id: header.producer_id,
+                current: entry.producer_epoch,
+                found: header.producer_epoch,
+            });
+        }
+        if header.producer_epoch > entry.producer_epoch {
+            if header.base_sequence != 0 {
+                return Err(SequenceError::OutOfOrder {
+                    producer_id: header.producer_id,
+                    expected: 0,
+                    found: header.base_sequence,
+                });
+            }
+            return Ok(());
+        }
+
+        let last_sequence = increment_sequence(header.base_sequence, header.last_offset_delta);
+        if let Some(duplicate) = entry.batches.iter().find(|batch| {
+            batch.first_sequence == header.base_sequence && batch.last_sequence == last_sequence
+        }) {
+            return Err(SequenceError::Duplicate(*duplicate));
+        }
+        match entry.last_sequence() {
+            Some(last) if header.base_sequence != increment_sequence(last, 1) => {
+                Err(SequenceError::OutOfOrder {
+                    producer_id: header.producer_id,
+                    expected: increment_sequence(last, 1),
+                    found: header.base_sequence,
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Records `header`, a batch that was appended with its final offsets.
+    pub fn update(&mut self, header: &BatchHeader) {
+        if header.producer_id < 0 || header.is_control() {
+            return;
+        }
+        let entry =
+            self.producers
+                .entry(header.producer_id)
+                .or_insert_with(|| ProducerStateEntry {
+                    producer_epoch: header.producer_epoch,
+                    batches: VecDeque::new(),
+                });
+        if header.producer_epoch != entry.producer_epoch {
+            entry.producer_epoch = header.producer_epoch;
+            entry.batches.clear();
+        }
+        if entry.batches.len() == BATCHES_TO_RETAIN {
+            entry.batches.pop_front();
+        }
+        entry.batches.push_back(BatchMetadata {
+            first_sequence: header.base_sequence,
+            last_sequence: increment_sequence(header.base_sequence, header.last_offset_delta),
+            last_offset: header.last_offset(),
+            offset_delta: header.last_offset_delta,
+            timestamp: header.max_timestamp,
+        });
+    }
+
+    /// Writes the current state as the snapshot for `offset` and deletes older
+    /// snapshots.
+    ///
+    /// Only each producer's latest batch is kept, as in Kafka, so duplicates of
+    /// older in-flight batches are not detected right after a restart.
+    pub fn take_snapshot(&self, offset: i64) -> io::Result<()> {
+        let latest: Vec<_> = self
+            .producers
+            .iter()
+            .filter_map(|(producer_id, entry)| {
+                let batch = entry.batches.back()?;
+                Some((producer_id, entry.producer_epoch, batch))
+            })
+            .collect();
+        let mut entries = Vec::new();
+        entries.extend_from_slice(&(latest.len() as i32).to_be_bytes());
+        for (producer_id, producer_epoch, batch) in latest {
+            entries.extend_from_slice(&producer_id.to_be_bytes());
+            entries.extend_from_slice(&producer_epoch.to_be_bytes());
+            entries.extend_from_slice(&batch.last_sequence.to_be_bytes());
+            entries.extend_from_slice(&batch.last_offset.to_be_bytes());
+            entries.extend_from_slice(&batch.offset_delta.to_be_bytes());
+            entries.extend_from_slice(&batch.timestamp.to_be_bytes());
+            entries.extend_from_slice(&NO_COORDINATOR_EPOCH.to_be_bytes());
+            entries.extend_from_slice(&NO_TXN_FIRST_OFFSET.to_be_bytes());
+        }
+
+        let mut contents = Vec::with_capacity(SNAPSHOT_HEADER_SIZE + entries.len());
+        contents.extend_from_slice(&SNAPSHOT_VERSION.to_be_bytes());
+        contents.extend_from_slice(&crc32c::crc32c(&entries).to_be_bytes());
+        contents.extend_from_slice(&entries);
+
+        let path = self.snapshot_path(offset);
+        let tmp = path.with_extension("tmp");
+        let mut file = File::create(&tmp)?;
+        file.write_all(&contents)?;
+        file.sync_all()?;
+        fs::rename(&tmp, &path)?;
+
+        for older in self.snapshot_offsets()? {
+            if older < offset {
+                fs::remove_file(self.snapshot_path(older))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn snapshot_path(&self, offset: i64) -> PathBuf {
+        self.dir.join(segment::segment_file_name(
+            offset,
+            PRODUCER_SNAPSHOT_FILE_SUFFIX,
+        ))
+    }
+
+    fn snapshot_offsets(&self) -> io::Result<Vec<i64>> {
+        let mut offsets = Vec::new();
+        for entry in fs::read_dir(&self.dir)? {
+            let name = entry?.file_name();
+            if let Some(offset) = name
+                .to_str()
+                .and_then(|name| segment::parse_base_offset(name, PRODUCER_SNAPSHOT_FILE_SUFFIX))
+            {
+                offsets.push(offset);
+            }
+        }
+        offsets.sort_unstable();
+        Ok(offsets)
+    }
+}
+
+fn read_snapshot(path: &Path) -> io::Result<BTreeMap<i64, ProducerStateEntry>> {
+    let contents = fs::read(path)?;
+    let malformed = |reason: &str| io::Error::new(io::ErrorKind::InvalidData, reason.to_string());
+    if contents.len() < SNAPSHOT_HEADER_SIZE {
+        return Err(malformed("snapshot is truncated"));
+    }
+    let mut cursor = Cursor::new(&contents[..SNAPSHOT_HEADER_SIZE]);
+    let version = primitives::read_i16(&mut cursor)?;
+    if version != SNAPSHOT_VERSION {
+        return Err(malformed(&format!(
+            "unsupported snapshot version {version}"
+        )));
+    }
+    let crc = primitives::read_i32(&mut cursor)? as u32;
+    let entries = &contents[SNAPSHOT_HEADER_SIZE..];
+    if crc32c::crc32c(entries) != crc {
+        return Err(malformed("snapshot CRC mismatch"));
+    }
+
+    let mut cursor = Cursor::new(entries);
+    let count = primitives::read_i32(&mut cursor)?;
+    let mut producers = BTreeMap::new();
+    for _ in 0..count {
+        let producer_id = primitives::read_i64(&mut cursor)?;
+        let producer_epoch = primitives::read_i16(&mut cursor)?;
+        let last_sequence = primitives::read_i32(&mut cursor)?;
+        let last_offset = primitives::read_i64(&mut cursor)?;
+        let offset_delta = primitives::read_i32(&mut cursor)?;
+        let timestamp = primitives::read_i64(&mut cursor)?;
+        let _coordinator_epoch = primitives::read_i32(&mut cursor)?;
+        let _current_txn_first_offset = primitives::read_i64(&mut cursor)?;
+        let batch = BatchMetadata {
+            first_sequence: increment_sequence(last_sequence, -offset_delta),
+            last_sequence,
+            last_offset,
+            offset_delta,
+            timestamp,
+        };
+        producers.insert(
+            producer_id,
+            ProducerStateEntry {
+                producer_epoch,
+                batches: VecDeque::from([batch]),
+            },
+        );
+    }
+    Ok(producers)
+}
+
+/// Adds `delta` to `sequence`, wrapping within the non-negative `i32` range
+/// as producers do after `i32::MAX`.
+fn increment_sequence(sequence: i32, delta: i32) -> i32 {
+    let modulus = i64::from(i32::MAX) + 1;
+    (i64::from(sequence) + i64::from(delta)).rem_euclid(modulus) as i32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::batch::RecordBatchBuilder;
+
+    fn header(base_offset: i64, epoch: i16, base_sequence: i32, records: usize) -> BatchHeader {
+        let batch = (0..records)
+            .fold(
+                RecordBatchBuilder::new(base_offset).producer(7, epoch, base_sequence),
+                |builder, _| builder.record(1_000, None, Some(b"v")),
+            )
+            .build();
+        BatchHeader::parse(&batch).expect("header")
+    }
+
+    #[test]
+    fn rejects_duplicates_gaps_and_fenced_epochs() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let (mut state, _) = ProducerStateManager::load(dir.path(), 0).expect("load");
+        state.update(&header(0, 0, 0, 3));
+        state.update(&header(3, 0, 3, 2));
+
+        let duplicate = state.check(&header(5, 0, 0, 3)).expect_err("duplicate");
+        assert_eq!(duplicate.error_code(), error::DUPLICATE_SEQUENCE_NUMBER);
+        assert!(matches!(duplicate, SequenceError::Duplicate(batch) if batch.first_offset() == 0));
+
+        let gap = state.check(&header(5, 0, 6, 1)).expect_err("gap");
+        assert_eq!(gap.error_code(), error::OUT_OF_ORDER_SEQUENCE_NUMBER);
+        assert!(state.check(&header(5, 0, 5, 1)).is_ok());
+
+        state.update(&header(5, 1, 0, 1));
+        let stale = state.check(&header(6, 0, 5, 1)).expect_err("stale epoch");
+        assert_eq!(stale.error_code(), error::INVALID_PRODUCER_EPOCH);
+        let restarted = state.check(&header(6, 2, 4, 1)).expect_err("new epoch");
+        assert_eq!(restarted.error_code(), error::OUT_OF_ORDER_SEQUENCE_NUMBER);
+        assert!(state.check(&header(6, 2, 0, 1)).is_ok());
+    }
+
+    #[test]
+    fn snapshot_round_trips_the_latest_batch_per_producer() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let (mut state, _) = ProducerStateManager::load(dir.path(), 0).expect("load");
+        state.update(&header(0, 0, i32::MAX - 1, 3));
+        state.take_snapshot(3).expect("snapshot");
+        state.take_snapshot(5).expect("later snapshot");
+        assert!(!dir.path().join("00000000000000000003.snapshot").exists());
+
+        let (restored, offset) = ProducerStateManager::load(dir.path(), 5).expect("reload");
+        assert_eq!(offset, Some(5));
+        let entry = restored.producer(7).expect("producer 7");
+        assert_eq!(entry.last_sequence(), Some(0));
+        assert_eq!(
+            entry.batches().next().map(|batch| batch.first_sequence),
+            Some(i32::MAX - 1)
+        );
+
+        let (_, offset) = ProducerStateManager::load(dir.path(), 4).expect("truncated log");
+        assert_eq!(offset, None);
+        assert!(!dir.path().join("00000000000000000005.snapshot").exists());
+    }
+}
@@ -0,0 +1,592 @@
+use super::batch::{self, BatchHeader, BATCH_HEADER_SIZE};
+use super::config::LogConfig;
+use super::index::{OffsetIndex, TimeIndex, TimestampOffset};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+pub const LOG_FILE_SUFFIX: &str = ".log";
+pub const INDEX_FILE_SUFFIX: &str = ".index";
+pub const TIME_INDEX_FILE_SUFFIX: &str = ".timeindex";
+/// Suffix of the file a compacted copy of a segment is written to before it
+/// replaces the original.
+pub const CLEANED_FILE_SUFFIX: &str = ".log.cleaned";
+
+/// Returns the file name Kafka uses for a segment file starting at `base_offset`.
+pub fn segment_file_name(base_offset: i64, suffix: &str) -> String {
+    format!("{base_offset:020}{suffix}")
+}
+
+/// A batch located inside a segment's `.log` file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BatchPosition {
+    pub position: u32,
+    pub header: BatchHeader,
+}
+
+/// One `.log` file together with its offset and time indexes.
+#[derive(Debug)]
+pub struct LogSegment {
+    base_offset: i64,
+    log_path: PathBuf,
+    log: File,
+    offset_index: OffsetIndex,
+    time_index: TimeIndex,
+    size: u32,
+    index_interval_bytes: u32,
+    bytes_since_last_index_entry: u32,
+    max_timestamp_so_far: i64,
+    offset_of_max_timestamp_so_far: i64,
+}
+
+impl LogSegment {
+    /// Opens (or creates) the segment starting at `base_offset` inside `dir`.
+    ///
+    /// Indexes that are missing or fail their sanity check are rebuilt from the
+    /// `.log` file.
+    pub fn open(dir: &Path, base_offset: i64, config: &LogConfig) -> io::Result<Self> {
+        let log_path = dir.join(segment_file_name(base_offset, LOG_FILE_SUFFIX));
+        let index_path = dir.join(segment_file_name(base_offset, INDEX_FILE_SUFFIX));
+        let time_index_path = dir.join(segment_file_name(base_offset, TIME_INDEX_FILE_SUFFIX));
+        let indexes_existed = index_path.exists() && time_index_path.exists();
+
+        let log = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(&log_path)?;
+        let size = u32::try_from(log.metadata()?.len()).map_err(|_| {
+            io::Error::new(io::ErrorKind::InvalidData, "segment is larger than 4 GiB")
+        })?;
+
+        let mut segment = Self {
+            base_offset,
+            log_path,
+            log,
+            offset_index: OffsetIndex::open(&index_path, base_offset, config.segment_index_bytes)?,
+            time_index: TimeIndex::open(&time_index_path, base_offset, config.segment_index_bytes)?,
+            size,
+            index_interval_bytes: config.index_interval_bytes,
+            bytes_since_last_index_entry: 0,
+            max_timestamp_so_far: -1,
+            offset_of_max_timestamp_so_far: base_offset,
+        };
+
+        let indexes_valid = segment.offset_index.sanity_check(size).is_ok()
+            && segment.time_index.sanity_check().is_ok();
+        if !indexes_existed || !indexes_valid {
+            if indexes_existed {
+                eprintln!(
+                    "rebuilding corrupt indexes for segment {}",
+                    segment.log_path.display()
+                );
+            }
+            segment.rebuild_indexes()?;
+        } else if let Some(entry) = segment.time_index.last_entry() {
+            segment.max_timestamp_so_far = entry.timestamp;
+            segment.offset_of_max_timestamp_so_far = entry.offset;
+        }
+
+        Ok(segment)
+    }
+
+    pub fn base_offset(&self) -> i64 {
+        self.base_offset
+    }
+
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+
+    pub fn log_path(&self) -> &Path {
+        &self.log_path
+    }
+
+    /// Returns `true` once either index has no room for further entries.
+    pub fn indexes_full(&self) -> bool {
+        self.offset_index.is_full() || self.time_index.is_full()
+    }
+
+    /// Appends an already offset-assigned batch to the end of the segment.
+    pub fn append(&mut self, batch: &[u8]) -> io::Result<BatchHeader> {
+        let header = BatchHeader::parse(batch)?;
+        let position = self.size;
+        // Found before writing, so a batch whose records cannot be decoded
+        // leaves the segment untouched.
+        let max_timestamp_offset = self.new_max_timestamp_offset(&header, position, Some(batch))?;
+
+        self.log.write_all(batch)?;
+        self.size += batch.len() as u32;
+        self.track_batch(&header, position, batch.len() as u32, max_timestamp_offset)?;
+        Ok(header)
+    }
+
+    /// Reads whole batches starting with the one that contains `offset`.
+    ///
+    /// At least one batch is returned even if it exceeds `max_bytes`, so a consumer
+    /// can always make progress. Returns `None` if `offset` is past the segment end.
+    pub fn read(&self, offset: i64, max_bytes: usize) -> io::Result<Option<Vec<u8>>> {
+        let Some(start) = self.find_batch(offset)? else {
+            return Ok(None);
+        };
+
+        let mut end = start.position as usize + start.header.total_size();
+        let limit = start.position as usize + max_bytes;
+        let mut position = end as u32;
+        while let Some(next) = self.read_batch_header(position)? {
+            let next_end = position as usize + next.total_size();
+            if next_end > limit {
+                break;
+            }
+            end = next_end;
+            position = end as u32;
+        }
+
+        self.read_range(start.position, end as u32).map(Some)
+    }
+
+    /// Locates the first batch whose last offset is `>= offset`.
+    pub fn find_batch(&self, offset: i64) -> io::Result<Option<BatchPosition>> {
+        let mut position = self.offset_index.lookup(offset).position;
+        while let Some(header) = self.read_batch_header(position)? {
+            if header.last_offset() >= offset {
+                return Ok(Some(BatchPosition { position, header }));
+            }
+            position += header.total_size() as u32;
+        }
+        Ok(None)
+    }
+
+    /// Finds the first record with a timestamp `>= timestamp`.
+    pub fn find_offset_by_timestamp(&self, timestamp: i64) -> io::Result<Option<TimestampOffset>> {
+        if self.max_timestamp_so_far < timestamp {
+            return Ok(None);
+        }
+
+        let indexed = self.time_index.lookup(timestamp);
+        let mut position = self.offset_index.lookup(indexed.offset).position;
+        while let Some(header) = self.read_batch_header(position)? {
+            if header.max_timestamp >= timestamp {
+                let bytes = self.read_range(position, position + header.total_size() as u32)?;
+                let found = batch::read_records(&bytes)?
+                    .into_iter()
+                    .find(|record| record.timestamp >= timestamp)
+                    .map(|record| TimestampOffset {
+                        timestamp: record.timestamp,
+                        offset: record.offset,
+                    });
+                if found.is_some() {
+                    return Ok(found);
+                }
+            }
+            position += header.total_size() as u32;
+        }
+        Ok(None)
+    }
+
+    /// Largest timestamp seen in the segment, or `-1` if it is empty.
+    pub fn max_timestamp(&self) -> i64 {
+        self.max_timestamp_so_far
+    }
+
+    /// Timestamp used to decide whether the segment has outlived `retention.ms`:
+    /// the largest record timestamp, or the file's modification time if the
+    /// segment holds no timestamped records.
+    pub fn largest_timestamp(&self) -> io::Result<i64> {
+        if self.max_timestamp_so_far >= 0 {
+            return Ok(self.max_timestamp_so_far);
+        }
+        let modified = self.log.metadata()?.modified()?;
+        Ok(modified
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_millis() as i64))
+    }
+
+    /// Offset of the record carrying [`max_timestamp`](Self::max_timestamp).
+    pub fn offset_of_max_timestamp(&self) -> i64 {
+        self.offset_of_max_timestamp_so_far
+    }
+
+    /// Offset after the last batch in the segment, found by scanning from the
+    /// last indexed position.
+    pub fn next_offset(&self) -> io::Result<i64> {
+        let mut next = self.base_offset;
+        let mut position = self
+            .offset_index
+            .last_entry()
+            .map_or(0, |entry| entry.position);
+        while let Some(header) = self.read_batch_header(position)? {
+            next = header.last_offset() + 1;
+            position += header.total_size() as u32;
+        }
+        Ok(next)
+    }
+
+    /// Discards both indexes and recreates them by scanning the `.log` file.
+    pub fn rebuild_indexes(&mut self) -> io::Result<()> {
+        self.offset_index.reset();
+        self.time_index.reset();
+        self.bytes_since_last_index_entry = 0;
+        self.max_timestamp_so_far = -1;
+        self.offset_of_max_timestamp_so_far = self.base_offset;
+
+        let mut position = 0;
+        while let Some(header) = self.read_batch_header(position)? {
+            let size = header.total_size() as u32;
+            let max_timestamp_offset = self.new_max_timestamp_offset(&header, position, None)?;
+            self.track_batch(&header, position, size, max_timestamp_offset)?;
+            position += size;
+        }
+        Ok(())
+    }
+
+    /// Validates every batch's length and CRC, truncating the segment at the first
+    /// invalid batch and rebuilding both indexes from the valid prefix.
+    ///
+    /// Returns the number of bytes that were truncated.
+    pub fn recover(&mut self) -> io::Result<u32> {
+        self.offset_index.reset();
+        self.time_index.reset();
+        self.bytes_since_last_index_entry = 0;
+        self.max_timestamp_so_far = -1;
+        self.offset_of_max_timestamp_so_far = self.base_offset;
+
+        let mut position = 0;
+        while let Some(header) = self.read_valid_batch(position)? {
+            let size = header.total_size() as u32;
+            let max_timestamp_offset = self.new_max_timestamp_offset(&header, position, None)?;
+            self.track_batch(&header, position, size, max_timestamp_offset)?;
+            position += size;
+        }
+
+        let truncated = self.size - position;
+        if truncated > 0 {
+            self.log.set_len(u64::from(position))?;
+            self.size = position;
+        }
+        Ok(truncated)
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.log.sync_all()?;
+        self.offset_index.flush()?;
+        self.time_index.flush()
+    }
+
+    /// Reads the whole `.log` file.
+    pub fn read_all(&self) -> io::Result<Vec<u8>> {
+        self.read_range(0, self.size)
+    }
+
+    /// Deletes the segment's files from disk.
+    pub fn delete(self) -> io::Result<()> {
+        self.remove_files(&[LOG_FILE_SUFFIX, INDEX_FILE_SUFFIX, TIME_INDEX_FILE_SUFFIX])
+    }
+
+    /// Closes the segment and deletes its index files, leaving the `.log` file
+    /// in place to be replaced.
+    pub fn delete_indexes(self) -> io::Result<()> {
+        self.remove_files(&[INDEX_FILE_SUFFIX, TIME_INDEX_FILE_SUFFIX])
+    }
+
+    fn remove_files(self, suffixes: &[&str]) -> io::Result<()> {
+        let log_path = self.log_path.clone();
+        drop(self);
+        for suffix in suffixes {
+            let path = log_path.with_extension(&suffix[1..]);
+            match fs::remove_file(&path) {
+                Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Offset of the record holding the max timestamp of the batch at
+    /// `position`, if that timestamp is a new maximum for the segment. The
+    /// batch is read back from the file unless its bytes are passed in.
+    fn new_max_timestamp_offset(
+        &self,
+        header: &BatchHeader,
+        position: u32,
+        bytes: Option<&[u8]>,
+    ) -> io::Result<Option<i64>> {
+        // Only a strictly larger timestamp moves the offset, so ties keep the
+        // earliest record.
+        if header.max_timestamp <= self.max_timestamp_so_far {
+            return Ok(None);
+        }
+        let offset = match bytes {
+            Some(bytes) => batch::offset_of_max_timestamp(bytes)?,
+            None => {
+                let end = position + header.total_size() as u32;
+                batch::offset_of_max_timestamp(&self.read_range(position, end)?)?
+            }
+        };
+        Ok(Some(offset))
+    }
+
+    /// Updates the max timestamp and indexes for the batch at `position`.
+    fn track_batch(
+        &mut self,
+        header: &BatchHeader,
+        position: u32,
+        size: u32,
+        max_timestamp_offset: Option<i64>,
+    ) -> io::Result<()> {
+        if let Some(offset) = max_timestamp_offset {
+            self.max_timestamp_so_far = header.max_timestamp;
+            self.offset_of_max_timestamp_so_far = offset;
+        }
+
+        if self.bytes_since_last_index_entry > self.index_interval_bytes
+            || self.offset_index.entries() == 0
+        {
+            self.offset_index.append(header.last_offset(), position)?;
+            self.time_index.maybe_append(
+                self.max_timestamp_so_far,
+                self.offset_of_max_timestamp_so_far,
+            )?;
+            self.bytes_since_last_index_entry = 0;
+        }
+        self.bytes_since_last_index_entry += size;
+        Ok(())
+    }
+
+    /// Reads the header of the batch at `position`, or `None` at the end of the
+    /// segment or when the remaining bytes cannot hold a complete batch.
+    fn read_batch_header(&self, position: u32) -> io::Result<Option<BatchHeader>> {
+        if position as usize + BATCH_HEADER_SIZE > self.size as usize {
+            return Ok(None);
+        }
+
+        let bytes = self.read_range(position, position + BATCH_HEADER_SIZE as u32)?;
+        let header = BatchHeader::parse(&bytes)?;
+        if header.total_size() < BATCH_HEADER_SIZE
+            || position as usize + header.total_size() > self.size as usize
+        {
+            return Ok(None);
+        }
+        Ok(Some(header))
+    }
+
+    /// Like [`read_batch_header`](Self::read_batch_header), but also rejects batches
+    /// with an unknown magic byte or a CRC that does not match their contents.
+    fn read_valid_batch(&self, position: u32) -> io::Result<Option<BatchHeader>> {
+        let Some(header) = self.read_batch_header(position)? else {
+            return Ok(None);
+        };
+        if header.magic != batch::CURRENT_MAGIC {
+            return Ok(None);
+        }
+
+        let bytes = self.read_range(position, position + header.total_size() as u32)?;
+        if batch::compute_crc(&bytes) != header.crc {
+            return Ok(None);
+        }
+        Ok(Some(header))
+    }
+
+    fn read_range(&self, start: u32, end: u32) -> io::Result<Vec<u8>> {
+        let mut file = &self.log;
+        file.seek(SeekFrom::Start(u64::from(start)))?;
+        let mut buffer = vec![0_u8; (end - start) as usize];
+        file.read_exact(&mut buffer)?;
+        Ok(buffer)
+    }
+}
+
+/// Parses the base offset out of a segment file name such as
+/// `00000000000000000042.log`.
+pub fn parse_base_offset(file_name: &str, suffix: &str) -> Option<i64> {
+    file_name.strip_suffix(suffix)?.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::batch::RecordBatchBuilder;
+
+    fn small_index_config() -> LogConfig {
+        LogConfig {
+            index_interval_bytes: 1,
+            ..LogConfig::default()
+        }
+    }
+
+    fn append_batches(segment: &mut LogSegment, base: i64, count: i64) {
+        for offset in base..base + count {
+            let batch = RecordBatchBuilder::new(offset)
+                .record(1_000 + offset * 10, Some(b"key"), Some(b"value"))
+                .build();
+            segment.append(&batch).expect("append");
+        }
+    }
+
+    #[test]
+    fn read_starts_at_batch_containing_offset() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let mut segment = LogSegment::open(dir.path(), 0, &small_index_config()).expect("open");
+        append_batches(&mut segment, 0, 5);
+
+        let bytes = segment
+            .read(3, 1)
+            .expect("read")
+            .expect("offset in segment");
+        let header = BatchHeader::parse(&bytes).expect("header");
+        assert_eq!(header.base_offset, 3);
+        assert_eq!(bytes.len(), header.total_size());
+
+        assert!(segment.read(5, 1024).expect("read").is_none());
+    }
+
+    #[test]
+    fn read_respects_max_bytes() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let mut segment = LogSegment::open(dir.path(), 0, &small_index_config()).expect("open");
+        append_batches(&mut segment, 0, 5);
+        let batch_size = segment.size() as usize / 5;
+
+        let bytes = segment
+            .read(1, batch_size * 2 + 1)
+            .expect("read")
+            .expect("offset in segment");
+        assert_eq!(bytes.len(), batch_size * 2);
+    }
+
+    #[test]
+    fn find_offset_by_timestamp_returns_first_record_at_or_after() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let mut segment = LogSegment::open(dir.path(), 0, &small_index_config()).expect("open");
+        append_batches(&mut segment, 0, 5);
+
+        let found = segment
+            .find_offset_by_timestamp(1_015)
+            .expect("lookup")
+            .expect("timestamp in segment");
+        assert_eq!(found.offset, 2);
+        assert_eq!(found.timestamp, 1_020);
+
+        assert!(segment
+            .find_offset_by_timestamp(5_000)
+            .expect("lookup")
+            .is_none());
+    }
+
+    #[test]
+    fn missing_indexes_are_rebuilt_on_open() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        {
+            let mut segment = LogSegment::open(dir.path(), 0, &small_index_config()).expect("open");
+            append_batches(&mut segment, 0, 4);
+        }
+        fs::remove_file(dir.path().join(segment_file_name(0, INDEX_FILE_SUFFIX))).expect("rm");
+
+        let segment = LogSegment::open(dir.path(), 0, &small_index_config()).expect("reopen");
+        assert!(segment.offset_index.entries() > 1);
+        assert_eq!(segment.next_offset().expect("next offset"), 4);
+        assert_eq!(segment.max_timestamp(), 1_030);
+    }
+
+    #[test]
+    fn corrupt_indexes_are_rebuilt_on_open() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        {
+            let mut segment = LogSegment::open(dir.path(), 0, &small_index_config()).expect("open");
+            append_batches(&mut segment, 0, 4);
+        }
+        let index_path = dir.path().join(segment_file_name(0, INDEX_FILE_SUFFIX));
+        fs::write(&index_path, [0xFF; 16]).expect("corrupt index");
+
+        let segment = LogSegment::open(dir.path(), 0, &small_index_config()).expect("reopen");
+        segment
+            .offset_index
+            .sanity_check(segment.size())
+            .expect("index rebuilt");
+        let bytes = segment
+            .read(2, 1)
+            .expect("read")
+            .expect("offset in segment");
+        assert_eq!(BatchHeader::parse(&bytes).expect("header").base_offset, 2);
+    }
+
+    #[test]
+    fn recover_truncates_torn_tail() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let valid_size = {
+            let mut segment = LogSegment::open(dir.path(), 0, &small_index_config()).expect("open");
+            append_batches(&mut segment, 0, 3);
+            segment.size()
+        };
+        let torn = RecordBatchBuilder::new(3)
+            .record(2_000, Some(b"key"), Some(b"value"))
+            .build();
+        OpenOptions::new()
+            .append(true)
+            .open(dir.path().join(segment_file_name(0, LOG_FILE_SUFFIX)))
+            .and_then(|mut file| file.write_all(&torn[..torn.len() / 2]))
+            .expect("append torn batch");
+
+        let mut segment = LogSegment::open(dir.path(), 0, &small_index_config()).expect("reopen");
+        let truncated = segment.recover().expect("recover");
+
+        assert_eq!(truncated, (torn.len() / 2) as u32);
+        assert_eq!(segment.size(), valid_size);
+        assert_eq!(segment.next_offset().expect("next offset"), 3);
+    }
+
+    #[test]
+    fn recover_truncates_at_first_crc_mismatch() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        {
+            let mut segment = LogSegment::open(dir.path(), 0, &small_index_config()).expect("open");
+            append_batches(&mut segment, 0, 4);
+        }
+        let log_path = dir.path().join(segment_file_name(0, LOG_FILE_SUFFIX));
+        let mut bytes = fs::read(&log_path).expect("read log");
+        let batch_size = bytes.len() / 4;
+        bytes[batch_size * 2 + BATCH_HEADER_SIZE] ^= 0xFF;
+        fs::write(&log_path, &bytes).expect("write log");
+
+        let mut segment = LogSegment::open(dir.path(), 0, &small_index_config()).expect("reopen");
+        segment.recover().expect("recover");
+
+        assert_eq!(segment.size() as usize, batch_size * 2);
+        assert_eq!(segment.next_offset().expect("next offset"), 2);
+        assert_eq!(
+            fs::metadata(&log_path).expect("metadata").len() as usize,
+            batch_size * 2
+        );
+    }
+
+    #[test]
+    fn append_rejects_undecodable_batch_without_writing() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let mut segment = LogSegment::open(dir.path(), 0, &small_index_config()).expect("open");
+        append_batches(&mut segment, 0, 2);
+        let size = segment.size();
+
+        let mut batch = RecordBatchBuilder::new(2)
+            .record(5_000, Some(b"key"), Some(b"value"))
+            .build();
+        batch[57..61].copy_from_slice(&i32::MAX.to_be_bytes());
+        let crc = batch::compute_crc(&batch);
+        batch[17..21].copy_from_slice(&crc.to_be_bytes());
+
+        assert!(segment.append(&batch).is_err());
+        assert_eq!(segment.size(), size);
+        assert_eq!(segment.next_offset().expect("next offset"), 2);
+    }
+
+    #[test]
+    fn parse_base_offset_reads_padded_names() {
+        assert_eq!(
+            parse_base_offset("00000000000000000042.log", LOG_FILE_SUFFIX),
+            Some(42)
+        );
+        assert_eq!(
+            parse_base_offset("leader-epoch-checkpoint", LOG_FILE_SUFFIX),
+            None
+        );
+    }
+}
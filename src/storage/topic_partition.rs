@@ -0,0 +1,57 @@
+use std::fmt;
+
+/// Identifies a single partition of a topic.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct TopicPartition {
+    pub topic: String,
+    pub partition: i32,
+}
+
+impl TopicPartition {
+    pub fn new(topic: impl Into<String>, partition: i32) -> Self {
+        Self {
+            topic: topic.into(),
+            partition,
+        }
+    }
+
+    /// Name of the directory holding this partition's log, e.g. `orders-3`.
+    pub fn dir_name(&self) -> String {
+        format!("{}-{}", self.topic, self.partition)
+    }
+
+    /// Parses a partition directory name such as `orders-3`.
+    pub fn from_dir_name(name: &str) -> Option<Self> {
+        let (topic, partition) = name.rsplit_once('-')?;
+        if topic.is_empty() {
+            return None;
+        }
+        let partition = partition.parse().ok().filter(|p: &i32| *p >= 0)?;
+        Some(Self::new(topic, partition))
+    }
+}
+
+impl fmt::Display for TopicPartition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-{}", self.topic, self.partition)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dir_name_round_trips() {
+        let tp = TopicPartition::new("my-topic", 12);
+        assert_eq!(tp.dir_name(), "my-topic-12");
+        assert_eq!(TopicPartition::from_dir_name("my-topic-12"), Some(tp));
+    }
+
+    #[test]
+    fn from_dir_name_rejects_non_partition_dirs() {
+        assert_eq!(TopicPartition::from_dir_name("lost+found"), None);
+        assert_eq!(TopicPartition::from_dir_name("-3"), None);
+        assert_eq!(TopicPartition::from_dir_name("topic-x"), None);
+    }
+}
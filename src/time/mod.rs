@@ -0,0 +1,79 @@
+pub mod scheduler;
+
+pub use scheduler::Scheduler;
+
+use std::fmt::Debug;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Source of wall-clock time in milliseconds since the Unix epoch.
+///
+/// Time-driven behaviour takes a clock instead of reading the system time directly
+/// so tests can move time forward deterministically.
+pub trait Clock: Debug + Send + Sync {
+    fn now_ms(&self) -> i64;
+}
+
+/// Reads the operating system clock.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl SystemClock {
+    pub fn shared() -> Arc<dyn Clock> {
+        Arc::new(Self)
+    }
+}
+
+impl Clock for SystemClock {
+    fn now_ms(&self) -> i64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_millis() as i64)
+    }
+}
+
+/// A clock that only moves when told to.
+#[derive(Debug, Default)]
+pub struct MockClock {
+    now_ms: AtomicI64,
+}
+
+impl MockClock {
+    pub fn new(now_ms: i64) -> Arc<Self> {
+        Arc::new(Self {
+            now_ms: AtomicI64::new(now_ms),
+        })
+    }
+
+    pub fn advance(&self, millis: i64) {
+        self.now_ms.fetch_add(millis, Ordering::SeqCst);
+    }
+
+    pub fn set(&self, now_ms: i64) {
+        self.now_ms.store(now_ms, Ordering::SeqCst);
+    }
+}
+
+impl Clock for MockClock {
+    fn now_ms(&self) -> i64 {
+        self.now_ms.load(Ordering::SeqCst)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mock_clock_moves_only_when_advanced() {
+        let clock = MockClock::new(1_000);
+        assert_eq!(clock.now_ms(), 1_000);
+
+        clock.advance(250);
+        assert_eq!(clock.now_ms(), 1_250);
+
+        clock.set(10);
+        assert_eq!(clock.now_ms(), 10);
+    }
+}
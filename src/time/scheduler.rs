@@ -0,0 +1,81 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// Runs named tasks periodically on background threads until shut down.
+#[derive(Debug, Default)]
+pub struct Scheduler {
+    stopped: Arc<AtomicBool>,
+    tasks: Vec<JoinHandle<()>>,
+}
+
+impl Scheduler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Runs `task` every `period`, starting one period from now.
+    pub fn schedule(
+        &mut self,
+        name: &str,
+        period: Duration,
+        mut task: impl FnMut() + Send + 'static,
+    ) -> std::io::Result<()> {
+        let stopped = Arc::clone(&self.stopped);
+        let handle = thread::Builder::new()
+            .name(name.to_string())
+            .spawn(move || {
+                let mut next_run = Instant::now() + period;
+                while !stopped.load(Ordering::SeqCst) {
+                    let now = Instant::now();
+                    if now < next_run {
+                        thread::park_timeout(next_run - now);
+                        continue;
+                    }
+                    task();
+                    next_run += period;
+                }
+            })?;
+        self.tasks.push(handle);
+        Ok(())
+    }
+
+    /// Stops every task and waits for any in-progress run to finish.
+    pub fn shutdown(self) {
+        self.stopped.store(true, Ordering::SeqCst);
+        for handle in &self.tasks {
+            handle.thread().unpark();
+        }
+        for handle in self.tasks {
+            let _ = handle.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+
+    #[test]
+    fn scheduled_task_runs_until_shutdown() {
+        let runs = Arc::new(AtomicUsize::new(0));
+        let mut scheduler = Scheduler::new();
+        let counter = Arc::clone(&runs);
+        scheduler
+            .schedule("test-task", Duration::from_millis(5), move || {
+                counter.fetch_add(1, Ordering::SeqCst);
+            })
+            .expect("schedule");
+
+        while runs.load(Ordering::SeqCst) < 2 {
+            thread::sleep(Duration::from_millis(1));
+        }
+        scheduler.shutdown();
+
+        let after_shutdown = runs.load(Ordering::SeqCst);
+        thread::sleep(Duration::from_millis(20));
+        assert_eq!(runs.load(Ordering::SeqCst), after_shutdown);
+    }
+}